@@ -0,0 +1,81 @@
+use actix_web::dev::Payload;
+use actix_web::http::StatusCode;
+use actix_web::web::{Bytes, Data};
+use actix_web::{FromRequest, HttpRequest, HttpResponse, ResponseError};
+use futures_util::future::LocalBoxFuture;
+use serde::de::DeserializeOwned;
+use crate::{extract, VerifiedWebhook, Verifier, WebhookRejection, SIGNATURE_HEADER, TIMESTAMP_HEADER};
+
+/// Takes the [`Verifier`] from `App::app_data(web::Data::new(verifier))`:
+/// `async fn hook(VerifiedWebhook(event): VerifiedWebhook<Event>) -> impl Responder { ... }`.
+impl<T: DeserializeOwned + 'static> FromRequest for VerifiedWebhook<T> {
+    type Error = WebhookRejection;
+    type Future = LocalBoxFuture<'static, Result<Self, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, payload: &mut Payload) -> Self::Future {
+        let verifier = req.app_data::<Data<Verifier>>().cloned();
+        let header = |name| req.headers().get(name).and_then(|value| value.to_str().ok()).map(str::to_string);
+        let timestamp = header(TIMESTAMP_HEADER);
+        let signatures = header(SIGNATURE_HEADER);
+        let body = Bytes::from_request(req, payload);
+
+        Box::pin(async move {
+            let verifier = verifier.ok_or(WebhookRejection::NotConfigured)?;
+            let body = body.await.map_err(|err| WebhookRejection::Body(err.to_string()))?;
+
+            extract(&verifier, timestamp.as_deref(), signatures.as_deref(), &body)
+        })
+    }
+}
+
+impl ResponseError for WebhookRejection {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            WebhookRejection::Verify(_) => StatusCode::UNAUTHORIZED,
+            WebhookRejection::Body(_) => StatusCode::BAD_REQUEST,
+            WebhookRejection::NotConfigured => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+
+    fn error_response(&self) -> HttpResponse {
+        HttpResponse::build(self.status_code()).body(self.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use actix_web::{test, web, App};
+    use serde_json::Value;
+    use crate::sign;
+    use super::*;
+
+    #[actix_web::test]
+    async fn test_extractor() {
+        let app = test::init_service(
+            App::new()
+                .app_data(Data::new(Verifier::new("whsec")))
+                .route("/hook", web::post().to(|VerifiedWebhook(event): VerifiedWebhook<Value>| async move {
+                    event["type"].as_str().unwrap_or_default().to_string()
+                })),
+        ).await;
+        let body = r#"{"type":"invoice.paid"}"#;
+        let timestamp = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).unwrap().as_secs();
+
+        let request = test::TestRequest::post()
+            .uri("/hook")
+            .insert_header((TIMESTAMP_HEADER, timestamp.to_string()))
+            .insert_header((SIGNATURE_HEADER, sign("whsec", timestamp, body.as_bytes())))
+            .set_payload(body)
+            .to_request();
+        let response = test::call_service(&app, request).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(test::read_body(response).await, "invoice.paid");
+
+        let request = test::TestRequest::post()
+            .uri("/hook")
+            .insert_header((TIMESTAMP_HEADER, timestamp.to_string()))
+            .set_payload(body)
+            .to_request();
+        assert_eq!(test::call_service(&app, request).await.status(), StatusCode::UNAUTHORIZED);
+    }
+}
@@ -0,0 +1,94 @@
+use axum::async_trait;
+use axum::body::Bytes;
+use axum::extract::{FromRef, FromRequest, Request};
+use axum::http::StatusCode;
+use axum::response::{IntoResponse, Response};
+use serde::de::DeserializeOwned;
+use crate::{extract, VerifiedWebhook, Verifier, WebhookRejection, SIGNATURE_HEADER, TIMESTAMP_HEADER};
+
+/// Takes the [`Verifier`] from the router state:
+/// `async fn hook(VerifiedWebhook(event): VerifiedWebhook<Event>) { ... }`.
+#[async_trait]
+impl<S, T> FromRequest<S> for VerifiedWebhook<T>
+where
+    S: Send + Sync,
+    T: DeserializeOwned,
+    Verifier: FromRef<S>,
+{
+    type Rejection = WebhookRejection;
+
+    async fn from_request(req: Request, state: &S) -> Result<Self, Self::Rejection> {
+        let timestamp = header(&req, TIMESTAMP_HEADER);
+        let signatures = header(&req, SIGNATURE_HEADER);
+
+        let body = Bytes::from_request(req, state)
+            .await
+            .map_err(|err| WebhookRejection::Body(err.body_text()))?;
+
+        extract(&Verifier::from_ref(state), timestamp.as_deref(), signatures.as_deref(), &body)
+    }
+}
+
+fn header(req: &Request, name: &str) -> Option<String> {
+    req.headers().get(name).and_then(|value| value.to_str().ok()).map(str::to_string)
+}
+
+impl IntoResponse for WebhookRejection {
+    fn into_response(self) -> Response {
+        let status = match self {
+            WebhookRejection::Verify(_) => StatusCode::UNAUTHORIZED,
+            WebhookRejection::Body(_) => StatusCode::BAD_REQUEST,
+            WebhookRejection::NotConfigured => StatusCode::INTERNAL_SERVER_ERROR,
+        };
+        (status, self.to_string()).into_response()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::body::Body;
+    use axum::routing::post;
+    use axum::Router;
+    use serde::Deserialize;
+    use tower::ServiceExt;
+    use crate::sign;
+    use super::*;
+
+    #[derive(Deserialize)]
+    struct Event {
+        #[serde(rename = "type")]
+        event_type: String,
+    }
+
+    fn app() -> Router {
+        Router::new()
+            .route("/hook", post(|VerifiedWebhook(event): VerifiedWebhook<Event>| async move { event.event_type }))
+            .with_state(Verifier::new("whsec"))
+    }
+
+    fn request(signature: &str, body: &'static str) -> Request {
+        let timestamp = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).unwrap().as_secs();
+        Request::post("/hook")
+            .header(TIMESTAMP_HEADER, timestamp.to_string())
+            .header(SIGNATURE_HEADER, match signature {
+                "" => sign("whsec", timestamp, body.as_bytes()),
+                other => other.to_string(),
+            })
+            .body(Body::from(body))
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_extractor() {
+        let response = app().oneshot(request("", r#"{"type":"invoice.paid"}"#)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = axum::body::to_bytes(response.into_body(), 1024).await.unwrap();
+        assert_eq!(&body[..], b"invoice.paid");
+
+        let response = app().oneshot(request("00", r#"{"type":"invoice.paid"}"#)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+        let response = app().oneshot(request("", r#"{"id":1}"#)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    }
+}
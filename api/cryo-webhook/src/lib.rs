@@ -0,0 +1,273 @@
+//! Signing and verification of CryoPay webhooks.
+//!
+//! Every delivery carries two headers:
+//! - `X-Webhook-Timestamp`: Unix seconds when the delivery was signed;
+//! - `X-Signature-256`: hex HMAC-SHA256 of `"{timestamp}.{body}"` keyed with the webhook secret.
+//!   While a rotated-out secret is in its grace period there are two signatures, comma-separated;
+//!   a delivery is valid when any of them matches.
+//!
+//! ```
+//! use cryo_webhook::{sign, Verifier};
+//!
+//! let body = br#"{"id":"...","type":"invoice.paid"}"#;
+//! let signature = sign("whsec", 1_700_000_000, body);
+//!
+//! let verifier = Verifier::new("whsec");
+//! assert!(verifier.verify_at("1700000000", &signature, body, 1_700_000_060).is_ok());
+//! // The same delivery seen twice is a replay.
+//! assert!(verifier.verify_at("1700000000", &signature, body, 1_700_000_061).is_err());
+//! ```
+//!
+//! With the `axum` or `actix` feature, [`VerifiedWebhook`] does the same as a request extractor.
+
+use std::collections::HashSet;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use hmac::{Hmac, Mac};
+use sha2::{Digest, Sha256};
+
+#[cfg(feature = "actix")]
+mod actix_extractor;
+#[cfg(feature = "axum")]
+mod axum_extractor;
+
+pub const SIGNATURE_HEADER: &str = "X-Signature-256";
+pub const TIMESTAMP_HEADER: &str = "X-Webhook-Timestamp";
+
+/// How far the timestamp may be from the receiver's clock by default.
+pub const DEFAULT_TOLERANCE: Duration = Duration::from_secs(5 * 60);
+
+type HmacSha256 = Hmac<Sha256>;
+/// Timestamps and body hashes of accepted deliveries. Keyed on the delivery rather than the
+/// signature, since a rotation header carries several signatures for the same delivery.
+type SeenDeliveries = Arc<Mutex<HashSet<(u64, [u8; 32])>>>;
+
+fn mac(secret: &[u8], timestamp: u64, body: &[u8]) -> HmacSha256 {
+    let mut mac = HmacSha256::new_from_slice(secret).expect("HMAC accepts keys of any length");
+    mac.update(timestamp.to_string().as_bytes());
+    mac.update(b".");
+    mac.update(body);
+    mac
+}
+
+/// Hex signature of `body` sent at `timestamp`.
+pub fn sign(secret: &str, timestamp: u64, body: &[u8]) -> String {
+    hex::encode(mac(secret.as_bytes(), timestamp, body).finalize().into_bytes())
+}
+
+/// `X-Signature-256` value: one signature per non-empty secret, comma-separated.
+/// `None` when there is nothing to sign with (webhooks created without a secret).
+pub fn signature_header(secrets: &[&str], timestamp: u64, body: &[u8]) -> Option<String> {
+    let signatures = secrets.iter()
+        .filter(|secret| !secret.is_empty())
+        .map(|secret| sign(secret, timestamp, body))
+        .collect::<Vec<_>>();
+
+    (!signatures.is_empty()).then(|| signatures.join(","))
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum VerifyError {
+    #[error("missing {0} header")]
+    MissingHeader(&'static str),
+    #[error("invalid timestamp")]
+    InvalidTimestamp,
+    #[error("timestamp is outside the tolerance")]
+    Expired,
+    #[error("no signature matches")]
+    InvalidSignature,
+    #[error("delivery was already received")]
+    Replayed,
+}
+
+/// Checks signatures, the timestamp tolerance and, unless disabled, replays.
+///
+/// Clones share the replay cache, so keep one verifier per endpoint (e.g. in app state).
+/// The cache is in memory: with several receiving processes, deduplicate on the event `id` too.
+#[derive(Clone)]
+pub struct Verifier {
+    secrets: Vec<Vec<u8>>,
+    tolerance: Duration,
+    /// Deliveries accepted within the tolerance window; `None` when replays are allowed.
+    seen: Option<SeenDeliveries>,
+}
+
+impl Verifier {
+    pub fn new(secret: &str) -> Self {
+        Self {
+            secrets: vec![secret.as_bytes().to_vec()],
+            tolerance: DEFAULT_TOLERANCE,
+            seen: Some(Arc::default()),
+        }
+    }
+
+    /// Also accept deliveries signed with `secret`, e.g. while switching to a rotated one.
+    pub fn also_accept(mut self, secret: &str) -> Self {
+        self.secrets.push(secret.as_bytes().to_vec());
+        self
+    }
+
+    pub fn tolerance(mut self, tolerance: Duration) -> Self {
+        self.tolerance = tolerance;
+        self
+    }
+
+    /// Accept the same delivery more than once.
+    pub fn allow_replays(mut self) -> Self {
+        self.seen = None;
+        self
+    }
+
+    /// Verifies against the current time; returns the delivery timestamp.
+    pub fn verify(&self, timestamp: &str, signatures: &str, body: &[u8]) -> Result<u64, VerifyError> {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+        self.verify_at(timestamp, signatures, body, now)
+    }
+
+    /// [`Verifier::verify`] with an explicit current time, in Unix seconds.
+    pub fn verify_at(&self, timestamp: &str, signatures: &str, body: &[u8], now: u64) -> Result<u64, VerifyError> {
+        let timestamp: u64 = timestamp.trim().parse().map_err(|_| VerifyError::InvalidTimestamp)?;
+        if now.abs_diff(timestamp) > self.tolerance.as_secs() {
+            return Err(VerifyError::Expired);
+        }
+
+        let matches = signatures.split(',')
+            .filter_map(|signature| hex::decode(signature.trim()).ok())
+            .any(|signature| {
+                self.secrets.iter().any(|secret| mac(secret, timestamp, body).verify_slice(&signature).is_ok())
+            });
+        if !matches {
+            return Err(VerifyError::InvalidSignature);
+        }
+
+        if let Some(seen) = &self.seen {
+            let mut seen = seen.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+            let oldest = now.saturating_sub(self.tolerance.as_secs());
+            seen.retain(|(seen_at, _)| *seen_at >= oldest);
+            if !seen.insert((timestamp, Sha256::digest(body).into())) {
+                return Err(VerifyError::Replayed);
+            }
+        }
+
+        Ok(timestamp)
+    }
+}
+
+/// Extractor that verifies the delivery and deserializes its JSON body into `T`.
+/// The [`Verifier`] comes from the app state (axum, via `FromRef`) or `app_data` (actix-web).
+#[cfg(any(feature = "axum", feature = "actix"))]
+#[derive(Debug)]
+pub struct VerifiedWebhook<T = serde_json::Value>(pub T);
+
+#[cfg(any(feature = "axum", feature = "actix"))]
+#[derive(Debug, thiserror::Error)]
+pub enum WebhookRejection {
+    /// Answered with 401.
+    #[error("webhook verification failed: {0}")]
+    Verify(#[from] VerifyError),
+    /// Answered with 400.
+    #[error("invalid webhook body: {0}")]
+    Body(String),
+    /// No `Verifier` registered with actix-web; answered with 500.
+    #[error("webhook verifier is not configured")]
+    NotConfigured,
+}
+
+#[cfg(any(feature = "axum", feature = "actix"))]
+fn extract<T: serde::de::DeserializeOwned>(
+    verifier: &Verifier, timestamp: Option<&str>, signatures: Option<&str>, body: &[u8],
+) -> Result<VerifiedWebhook<T>, WebhookRejection> {
+    let timestamp = timestamp.ok_or(VerifyError::MissingHeader(TIMESTAMP_HEADER))?;
+    let signatures = signatures.ok_or(VerifyError::MissingHeader(SIGNATURE_HEADER))?;
+    verifier.verify(timestamp, signatures, body)?;
+
+    serde_json::from_slice(body)
+        .map(VerifiedWebhook)
+        .map_err(|err| WebhookRejection::Body(err.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const NOW: u64 = 1_700_000_000;
+    const BODY: &[u8] = br#"{"id":"e1","type":"invoice.paid"}"#;
+
+    #[test]
+    fn test_sign_matches_the_documented_format() {
+        let mut mac = HmacSha256::new_from_slice(b"whsec").unwrap();
+        mac.update(format!("{NOW}.{}", std::str::from_utf8(BODY).unwrap()).as_bytes());
+
+        assert_eq!(sign("whsec", NOW, BODY), hex::encode(mac.finalize().into_bytes()));
+    }
+
+    #[test]
+    fn test_signature_header() {
+        assert_eq!(signature_header(&["", ""], NOW, BODY), None);
+        assert_eq!(signature_header(&["a", ""], NOW, BODY), Some(sign("a", NOW, BODY)));
+        assert_eq!(
+            signature_header(&["a", "b"], NOW, BODY),
+            Some(format!("{},{}", sign("a", NOW, BODY), sign("b", NOW, BODY))),
+        );
+    }
+
+    #[test]
+    fn test_verify() {
+        let verifier = Verifier::new("whsec").allow_replays();
+        let signature = sign("whsec", NOW, BODY);
+
+        assert_eq!(verifier.verify_at(&NOW.to_string(), &signature, BODY, NOW + 10), Ok(NOW));
+        assert_eq!(verifier.verify_at(&NOW.to_string(), &signature, b"{}", NOW), Err(VerifyError::InvalidSignature));
+        assert_eq!(verifier.verify_at(&NOW.to_string(), &sign("other", NOW, BODY), BODY, NOW), Err(VerifyError::InvalidSignature));
+        assert_eq!(verifier.verify_at(&NOW.to_string(), "not hex", BODY, NOW), Err(VerifyError::InvalidSignature));
+        assert_eq!(verifier.verify_at("yesterday", &signature, BODY, NOW), Err(VerifyError::InvalidTimestamp));
+    }
+
+    #[test]
+    fn test_any_signature_of_a_rotation_verifies() {
+        let header = signature_header(&["new", "old"], NOW, BODY).unwrap();
+
+        assert!(Verifier::new("old").verify_at(&NOW.to_string(), &header, BODY, NOW).is_ok());
+        assert!(Verifier::new("new").verify_at(&NOW.to_string(), &header, BODY, NOW).is_ok());
+        let switching = Verifier::new("newer").also_accept("new");
+        assert!(switching.verify_at(&NOW.to_string(), &sign("new", NOW, BODY), BODY, NOW).is_ok());
+    }
+
+    #[test]
+    fn test_timestamp_tolerance() {
+        let verifier = Verifier::new("whsec").tolerance(Duration::from_secs(60)).allow_replays();
+        let signature = sign("whsec", NOW, BODY);
+
+        assert!(verifier.verify_at(&NOW.to_string(), &signature, BODY, NOW + 60).is_ok());
+        assert!(verifier.verify_at(&NOW.to_string(), &signature, BODY, NOW - 60).is_ok());
+        assert_eq!(verifier.verify_at(&NOW.to_string(), &signature, BODY, NOW + 61), Err(VerifyError::Expired));
+    }
+
+    #[test]
+    fn test_replays_are_rejected() {
+        let verifier = Verifier::new("whsec");
+        let shared = verifier.clone();
+
+        assert!(verifier.verify_at(&NOW.to_string(), &sign("whsec", NOW, BODY), BODY, NOW).is_ok());
+        assert_eq!(
+            shared.verify_at(&NOW.to_string(), &sign("whsec", NOW, BODY), BODY, NOW + 1),
+            Err(VerifyError::Replayed),
+        );
+        // A redelivery is signed again with a new timestamp.
+        assert!(verifier.verify_at(&(NOW + 30).to_string(), &sign("whsec", NOW + 30, BODY), BODY, NOW + 31).is_ok());
+    }
+
+    #[test]
+    fn test_replaying_one_signature_of_a_rotation_is_rejected() {
+        let verifier = Verifier::new("new").also_accept("old");
+        let header = signature_header(&["new", "old"], NOW, BODY).unwrap();
+
+        assert!(verifier.verify_at(&NOW.to_string(), &header, BODY, NOW).is_ok());
+        for signature in header.split(',') {
+            assert_eq!(verifier.verify_at(&NOW.to_string(), signature, BODY, NOW + 1), Err(VerifyError::Replayed));
+        }
+    }
+}
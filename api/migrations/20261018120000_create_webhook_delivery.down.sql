@@ -0,0 +1,2 @@
+DROP TABLE webhook_delivery_attempt;
+DROP TABLE webhook_delivery;
@@ -0,0 +1,2 @@
+ALTER TABLE webhook DROP COLUMN api_version;
+ALTER TABLE webhook DROP COLUMN event_types;
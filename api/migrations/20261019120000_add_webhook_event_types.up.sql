@@ -0,0 +1,3 @@
+-- Existing endpoints keep receiving only the original invoice-paid payload.
+ALTER TABLE webhook ADD COLUMN event_types TEXT[] NOT NULL DEFAULT ARRAY['invoice.paid'];
+ALTER TABLE webhook ADD COLUMN api_version TEXT NOT NULL DEFAULT 'legacy';
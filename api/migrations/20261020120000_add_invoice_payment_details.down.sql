@@ -0,0 +1,2 @@
+ALTER TABLE invoice DROP COLUMN tx_hash;
+ALTER TABLE invoice DROP COLUMN paid_network;
@@ -0,0 +1,3 @@
+-- Chain id and transaction of the transfer that paid the invoice.
+ALTER TABLE invoice ADD COLUMN paid_network INT;
+ALTER TABLE invoice ADD COLUMN tx_hash TEXT;
@@ -0,0 +1,3 @@
+ALTER TABLE webhook DROP COLUMN previous_secret_expires_at;
+ALTER TABLE webhook DROP COLUMN previous_secret;
+ALTER TABLE webhook DROP COLUMN enabled;
@@ -0,0 +1,4 @@
+ALTER TABLE webhook ADD COLUMN enabled BOOLEAN NOT NULL DEFAULT TRUE;
+-- Kept after a rotation so deliveries carry both signatures until it expires.
+ALTER TABLE webhook ADD COLUMN previous_secret TEXT;
+ALTER TABLE webhook ADD COLUMN previous_secret_expires_at TIMESTAMP;
@@ -0,0 +1,2 @@
+DROP TABLE notification_template;
+ALTER TABLE users DROP COLUMN locale;
@@ -0,0 +1,14 @@
+ALTER TABLE users ADD COLUMN locale TEXT NOT NULL DEFAULT 'en';
+
+-- Seller overrides of the built-in notification templates; NULL fields keep the built-in one.
+CREATE TABLE notification_template (
+    user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    kind TEXT NOT NULL,
+    locale TEXT NOT NULL,
+    subject TEXT,
+    text_body TEXT,
+    html_body TEXT,
+    telegram_body TEXT,
+    updated_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    PRIMARY KEY (user_id, kind, locale)
+);
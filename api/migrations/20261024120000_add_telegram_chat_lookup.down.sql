@@ -0,0 +1 @@
+ALTER TABLE users DROP COLUMN telegram_chat_lookup;
@@ -0,0 +1,3 @@
+-- Keyed hash of the encrypted telegram_chat_id so the bot can find the user behind a chat.
+ALTER TABLE users ADD COLUMN telegram_chat_lookup TEXT;
+CREATE INDEX users_telegram_chat_lookup_idx ON users (telegram_chat_lookup);
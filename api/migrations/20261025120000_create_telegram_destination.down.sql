@@ -0,0 +1,16 @@
+ALTER TABLE users ADD COLUMN telegram_chat_id TEXT;
+ALTER TABLE users ADD COLUMN telegram_chat_lookup TEXT;
+CREATE INDEX users_telegram_chat_lookup_idx ON users (telegram_chat_lookup);
+
+-- Only one chat per user fits; the oldest private chat is kept.
+UPDATE users
+SET telegram_chat_id = destination.chat_id, telegram_chat_lookup = destination.chat_lookup
+FROM (
+    SELECT DISTINCT ON (user_id) user_id, chat_id, chat_lookup
+    FROM telegram_destination
+    WHERE chat_type = 'private'
+    ORDER BY user_id, created_at
+) AS destination
+WHERE users.id = destination.user_id;
+
+DROP TABLE telegram_destination;
@@ -0,0 +1,24 @@
+-- Chats, groups and channels that receive a seller's Telegram notifications.
+-- chat_id is stored encrypted; chat_lookup is a keyed hash of it for finding the row by chat.
+CREATE TABLE telegram_destination (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    label TEXT NOT NULL,
+    chat_id TEXT NOT NULL,
+    chat_lookup TEXT,
+    chat_type TEXT NOT NULL,
+    event_types TEXT[] NOT NULL,
+    min_amount NUMERIC,
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+CREATE INDEX telegram_destination_user_id_idx ON telegram_destination (user_id);
+CREATE INDEX telegram_destination_chat_lookup_idx ON telegram_destination (chat_lookup);
+
+-- Linked chats keep getting what they got before: invoice-paid notifications.
+INSERT INTO telegram_destination (user_id, label, chat_id, chat_lookup, chat_type, event_types)
+SELECT id, 'Private chat', telegram_chat_id, telegram_chat_lookup, 'private', ARRAY['invoice.paid']
+FROM users
+WHERE telegram_chat_id IS NOT NULL;
+
+ALTER TABLE users DROP COLUMN telegram_chat_id;
+ALTER TABLE users DROP COLUMN telegram_chat_lookup;
@@ -0,0 +1,13 @@
+-- Slack, Discord and generic incoming webhooks that receive a seller's notifications as chat
+-- messages. The URL carries the channel's credentials, so it is stored encrypted.
+CREATE TABLE chat_webhook (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    kind TEXT NOT NULL,
+    label TEXT NOT NULL,
+    url TEXT NOT NULL,
+    event_types TEXT[] NOT NULL,
+    enabled BOOLEAN NOT NULL DEFAULT TRUE,
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+CREATE INDEX chat_webhook_user_id_idx ON chat_webhook (user_id);
@@ -0,0 +1,2 @@
+ALTER TABLE subscriptions DROP COLUMN expiry_notified_until;
+DROP TABLE notification_preference;
@@ -0,0 +1,17 @@
+-- Per channel and event overrides of the defaults; a missing row means the event's default.
+CREATE TABLE notification_preference (
+    user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    -- `email`, `telegram` or `chat`.
+    channel TEXT NOT NULL,
+    -- e.g. `invoice.paid` or `subscription.expiring`.
+    event_type TEXT NOT NULL,
+    enabled BOOLEAN NOT NULL,
+    min_amount NUMERIC,
+    external_id_prefix TEXT,
+    updated_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    PRIMARY KEY (user_id, channel, event_type)
+);
+
+-- The `until` the seller was last told about, so each subscription period gets one
+-- `subscription.expiring` notice; renewing moves `until` and allows the next one.
+ALTER TABLE subscriptions ADD COLUMN expiry_notified_until TIMESTAMP;
@@ -0,0 +1,2 @@
+DROP TABLE notification_digest_item;
+DROP TABLE notification_digest;
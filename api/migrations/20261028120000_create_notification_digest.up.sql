@@ -0,0 +1,27 @@
+-- How often a seller's channel gets its notifications; no row means immediately.
+CREATE TABLE notification_digest (
+    user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    -- `email` or `telegram`.
+    channel TEXT NOT NULL,
+    -- `immediate`, `hourly`, `daily` or `weekly`.
+    frequency TEXT NOT NULL,
+    -- End of the last period that was summarized.
+    last_sent_at TIMESTAMP,
+    updated_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    PRIMARY KEY (user_id, channel)
+);
+
+-- Notifications waiting for the next digest of their channel.
+CREATE TABLE notification_digest_item (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    channel TEXT NOT NULL,
+    event_type TEXT NOT NULL,
+    invoice_id UUID NOT NULL,
+    amount NUMERIC NOT NULL,
+    network INTEGER,
+    external_id TEXT,
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX notification_digest_item_user_channel_idx ON notification_digest_item (user_id, channel, created_at);
@@ -0,0 +1,28 @@
+-- Every email, Telegram and chat message sent to a seller, with how it went.
+CREATE TABLE notification_log (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    -- `email`, `telegram` or `chat`.
+    channel TEXT NOT NULL,
+    -- A notification event, e.g. `invoice.paid`, or `digest`.
+    event_type TEXT NOT NULL,
+    invoice_id UUID,
+    -- The Telegram destination or chat webhook; NULL for email.
+    destination_id UUID,
+    -- Shown to the seller: the masked email address or the chat's label.
+    destination TEXT NOT NULL,
+    -- The transfer of a partial payment or anomaly, to send the same message again.
+    transfer JSONB,
+    -- `sent`, `failed` or `pending` (resend requested).
+    status TEXT NOT NULL,
+    error TEXT,
+    attempts INTEGER NOT NULL DEFAULT 1,
+    -- When a pending resend is due.
+    next_attempt_at TIMESTAMP,
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    updated_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    sent_at TIMESTAMP
+);
+
+CREATE INDEX notification_log_user_created_idx ON notification_log (user_id, created_at DESC);
+CREATE INDEX notification_log_pending_idx ON notification_log (next_attempt_at) WHERE status = 'pending';
@@ -0,0 +1,3 @@
+ALTER TABLE users DROP COLUMN buyer_receipts;
+ALTER TABLE invoice DROP COLUMN send_receipt;
+ALTER TABLE invoice DROP COLUMN buyer_email;
@@ -0,0 +1,5 @@
+-- Receipts mailed to the buyer once the invoice is paid; the address is encrypted.
+ALTER TABLE invoice ADD COLUMN buyer_email TEXT;
+ALTER TABLE invoice ADD COLUMN send_receipt BOOLEAN NOT NULL DEFAULT false;
+-- Default for send_receipt on the user's new invoices.
+ALTER TABLE users ADD COLUMN buyer_receipts BOOLEAN NOT NULL DEFAULT false;
@@ -0,0 +1,5 @@
+DROP INDEX invoice_unpaid_expires_at_idx;
+ALTER TABLE invoice DROP COLUMN confirming_at;
+ALTER TABLE invoice DROP COLUMN expires_at;
+ALTER TABLE invoice DROP COLUMN status_changed_at;
+ALTER TABLE invoice DROP COLUMN status;
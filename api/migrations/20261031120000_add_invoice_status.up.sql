@@ -0,0 +1,11 @@
+-- Invoice life cycle: open -> confirming -> paid, or expired / void while unpaid.
+ALTER TABLE invoice ADD COLUMN status TEXT NOT NULL DEFAULT 'open'
+    CHECK (status IN ('open', 'confirming', 'paid', 'expired', 'void'));
+ALTER TABLE invoice ADD COLUMN status_changed_at TIMESTAMP NOT NULL DEFAULT NOW();
+ALTER TABLE invoice ADD COLUMN expires_at TIMESTAMP;
+-- First time the buyer reported a transaction; an invoice is confirming only once.
+ALTER TABLE invoice ADD COLUMN confirming_at TIMESTAMP;
+
+UPDATE invoice SET status = 'paid', status_changed_at = paid_at WHERE paid_at IS NOT NULL;
+
+CREATE INDEX invoice_unpaid_expires_at_idx ON invoice (expires_at) WHERE status IN ('open', 'confirming');
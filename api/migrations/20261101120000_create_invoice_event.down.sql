@@ -0,0 +1,2 @@
+DROP TABLE invoice_event;
+ALTER TABLE invoice DROP COLUMN metadata;
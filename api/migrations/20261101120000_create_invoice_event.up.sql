@@ -0,0 +1,19 @@
+ALTER TABLE invoice ADD COLUMN metadata JSONB;
+
+-- Timeline of an invoice: its creation, every edit with the changed fields, and every status change.
+CREATE TABLE invoice_event (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    invoice_id UUID NOT NULL REFERENCES invoice (id) ON DELETE CASCADE,
+    -- 'created', 'updated' or the status the invoice moved to
+    kind TEXT NOT NULL,
+    -- {"field": {"from": ..., "to": ...}} for 'updated'
+    changes JSONB,
+    created_at TIMESTAMP NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX invoice_event_invoice_idx ON invoice_event (invoice_id, created_at);
+
+INSERT INTO invoice_event (invoice_id, kind, created_at)
+SELECT id, 'created', created_at FROM invoice;
+INSERT INTO invoice_event (invoice_id, kind, created_at)
+SELECT id, status, status_changed_at FROM invoice WHERE status <> 'open';
@@ -0,0 +1,2 @@
+DROP INDEX telegram_destination_chat_lookup_key;
+CREATE INDEX telegram_destination_chat_lookup_idx ON telegram_destination (chat_lookup);
@@ -0,0 +1,9 @@
+-- A chat goes to one seller at a time; keep the newest link where concurrent /start messages
+-- left more than one.
+DELETE FROM telegram_destination older
+USING telegram_destination newer
+WHERE older.chat_lookup = newer.chat_lookup
+  AND (older.created_at, older.id) < (newer.created_at, newer.id);
+
+DROP INDEX telegram_destination_chat_lookup_idx;
+CREATE UNIQUE INDEX telegram_destination_chat_lookup_key ON telegram_destination (chat_lookup);
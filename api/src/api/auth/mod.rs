@@ -14,19 +14,22 @@ use crate::api::ping_pong::ping_pong;
 use crate::api::response_error::ResponseError;
 use crate::api::state::{AppState, VerifyError};
 
-const JWT_EXPIRE_DAYS: i64 = 7;
-
 pub fn get_router(app_state: Arc<AppState>) -> Router {
-    Router::new()
+    let mut router = Router::new()
         .route("/ping", get(ping_pong))
-        .route(
+        .route("/logout", post(logout));
+
+    // Without Firebase there is no web login; API keys issued with `create-api-key` still work.
+    if app_state.gc.is_some() {
+        router = router.route(
             "/login",
             post(login)
                 .layer(middleware::from_fn_with_state(app_state.clone(), RateLimitType::login))
                 .layer(middleware::from_fn_with_state(app_state.clone(), extract_user)),
-        )
-        .route("/logout", post(logout))
-        .with_state(app_state)
+        );
+    }
+
+    router.with_state(app_state)
 }
 
 #[derive(Deserialize, utoipa::ToSchema)]
@@ -58,27 +61,24 @@ pub(crate) async fn login(
     jar: CookieJar,
     Json(payload): Json<FirebaseTokenRequest>,
 ) -> Result<impl IntoResponse, ResponseError> {
-    let token = state.gc.get_verified_token(&payload.token)
+    let token = state.gc
+        .as_ref()
+        .ok_or(ResponseError::NotFound)?
+        .get_verified_token(&payload.token)
         .await
         .map_err(|err| match err {
             VerifyError::Verification(tve) => {
-                debug!("Invalid token: {:?}", tve);
+                debug!("Invalid token: {tve}");
                 ResponseError::Bad("Invalid token".to_string())
             }
             VerifyError::Unexpected(err) => ResponseError::from_error(err)
         })?;
 
-    let user_id = token.critical_claims.sub;
-    let email = token.all_claims
-        .get("email")
-        .and_then(|v| v.as_str())
-        .map(|s| s.to_string());
-
-    let jwt = state.jwt.generate(user_id, email)
+    let jwt = state.jwt.generate(token.sub, token.email)
         .map_err(|err| ResponseError::from_error(format!("{err:?}")))?;
 
     let mut cookie = build_jwt_cookie(jwt);
-    cookie.set_expires(OffsetDateTime::now_utc() + Duration::days(JWT_EXPIRE_DAYS));
+    cookie.set_expires(OffsetDateTime::now_utc() + Duration::days(state.config.limits.session_cookie_days));
 
     Ok((StatusCode::OK, jar.add(cookie)))
 }
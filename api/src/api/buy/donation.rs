@@ -86,18 +86,18 @@ async fn donate_create(
     Extension(app_user): Extension<AppUser>,
     Json(payload): Json<DonateRequest>,
 ) -> Result<impl IntoResponse, ResponseError> {
-    if payload.amount <= BigDecimal::from(0) {
+    if payload.amount <= 0 {
         return Err(ResponseError::Bad("Amount must be positive".to_string()));
     }
 
     let amount = payload.amount;
     let custom_id = Some(format!("Donation of {amount}"));
     let payable = Payable::Donation(Donation::anonymous_no_target(amount.clone()));
-    let to_pay = ToPay::create(amount, custom_id, payable)
+    let to_pay = ToPay::create(&state.config, amount, custom_id, payable)
         .await
         .map_err(ResponseError::from_error)?;
 
-    let payment_url = to_pay::create_payment_url(&to_pay, &state.db, app_user.user_id())
+    let payment_url = to_pay::create_payment_url(&to_pay, &state.config, &state.db, app_user.user_id())
         .await
         .map_err(ResponseError::from)?;
 
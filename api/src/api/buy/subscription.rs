@@ -81,6 +81,7 @@ async fn create_subscription(
     let payable = Payable::Subscription(subscription);
 
     let to_pay = ToPay::create(
+        &state.config,
         price,
         Some(format!("Subscription #{:?} for {} days (until={})", target, request_data.days, until)),
         payable
@@ -88,7 +89,7 @@ async fn create_subscription(
         .await
         .map_err(ResponseError::from_error)?;
 
-    let payment_url = create_payment_url(&to_pay, &state.db, Some(user.id))
+    let payment_url = create_payment_url(&to_pay, &state.config, &state.db, Some(user.id))
         .await
         .map_err(ResponseError::from)?;
 
@@ -2,6 +2,7 @@ use tracing::warn;
 use uuid::Uuid;
 use serde_json::Value;
 use crate::api::state::DB;
+use crate::config::ApiConfig;
 use crate::db::billing::Payment;
 use crate::error::AppError;
 use crate::payments::payable::Payable;
@@ -16,13 +17,10 @@ impl TryFrom<&ToPay> for ToPayAdapterPayment {
     type Error = ();
 
     fn try_from(value: &ToPay) -> Result<ToPayAdapterPayment, Self::Error> {
-        let invoice_id = match value.id {
-            ToPayId::CryoPay(id) => id
-        };
+        let ToPayId::CryoPay(invoice_id) = value.id;
         let data = serde_json::to_value(&value.payable)
             .map_err(|err| {
                 warn!("Failed parse value to Payable: {err}");
-                ()
             })?;
 
         Ok(ToPayAdapterPayment { invoice_id, data })
@@ -36,16 +34,15 @@ impl TryFrom<Payment> for ToPay {
         let payable: Payable = serde_json::from_value(value.data)
             .map_err(|err| {
                 warn!("Failed parse value to Payable: {err}");
-                ()
             })?;
 
         Ok(ToPay { id: ToPayId::CryoPay(value.id), payable })
     }
 }
 
-pub async fn create_payment_url(to_pay: &ToPay, db: &DB, user_id: Option<Uuid>) -> Result<String, AppError> {
-    let payment_url = to_pay.payment_url()
-        .map_err(|e| AppError::Internal(e))?;
+pub async fn create_payment_url(to_pay: &ToPay, config: &ApiConfig, db: &DB, user_id: Option<Uuid>) -> Result<String, AppError> {
+    let payment_url = to_pay.payment_url(config)
+        .map_err(AppError::Internal)?;
 
     let payment_adapter: ToPayAdapterPayment = to_pay
         .try_into()
@@ -50,7 +50,7 @@ async fn callback(
     let payment = apply_paid_by_id(&state, &payment_query.invoice_id).await?;
 
     let redirect_url = utils::combine_paths(&[
-        &utils::web_base_url().map_err(ResponseError::from_error)?,
+        &state.config.web_base_url,
         "/payment",
         &format!("/{}", payment.id),
     ]);
@@ -59,7 +59,7 @@ async fn callback(
 }
 
 pub async fn apply_paid_by_id(state: &Arc<AppState>, id: &Uuid) -> Result<Payment, ResponseError> {
-    match get_paid_payable(&state.db, id)
+    match get_paid_payable(&state.config, &state.db, id)
         .await
         .map_err(ResponseError::from)?
     {
@@ -67,7 +67,7 @@ pub async fn apply_paid_by_id(state: &Arc<AppState>, id: &Uuid) -> Result<Paymen
         PaidPayableResult::NotFound => Err(ResponseError::NotFound),
         PaidPayableResult::Payment(payment) => match payment.paid_at.is_some() {
             true => Ok(payment),
-            false => apply(&state, &payment)
+            false => apply(state, &payment)
                 .await
                 .map(|_| Ok(payment))
                 .map_err(ResponseError::from)?
@@ -0,0 +1,68 @@
+use std::sync::Arc;
+use axum::extract::{Path, State};
+use axum::Json;
+use axum::response::IntoResponse;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use crate::api::response_error::ResponseError;
+use crate::api::state::AppState;
+use crate::utils;
+
+#[derive(Clone, Serialize, Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct GasPriceResponse {
+    low: GasFeeDetails,
+    medium: GasFeeDetails,
+    high: GasFeeDetails,
+    estimated_base_fee: String,
+    network_congestion: f64,
+}
+
+#[derive(Clone, Serialize, Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct GasFeeDetails {
+    suggested_max_priority_fee_per_gas: String,
+    suggested_max_fee_per_gas: String,
+    min_wait_time_estimate: u64,
+    max_wait_time_estimate: u64,
+}
+
+pub(crate) async fn get_suggested_gas_fees(
+    State(state): State<Arc<AppState>>,
+    Path(network_id): Path<i64>,
+) -> Result<impl IntoResponse, ResponseError> {
+    let allowed_ids = state
+        .config
+        .networks
+        .iter()
+        .map(|n| n.id)
+        .collect::<Vec<i64>>();
+    if !allowed_ids.contains(&network_id) {
+        return Err(ResponseError::Bad("Unknown network_id".to_string()));
+    }
+
+    if let Ok(Some(value)) = state.redis.get_suggested_gas_fees(&network_id).await {
+        if let Ok(gas_prices) = serde_json::from_str::<GasPriceResponse>(&value) {
+            return Ok(Json(json!({ "source": "cache", "data": gas_prices })));
+        }
+    }
+
+    let infura_token = state.config.infura_token
+        .as_deref()
+        .ok_or(ResponseError::NotFound)?;
+    let value = utils::get_suggested_gas_fees(infura_token, network_id)
+        .await
+        .map_err(ResponseError::from_error)?;
+
+    let response = serde_json::from_value::<GasPriceResponse>(value)
+        .map_err(|err| ResponseError::from_error(format!("{err:?}")))?;
+
+    state.redis.set_suggested_gas_fees(
+        &network_id, serde_json::to_string(&response.clone())
+            .map_err(|err| ResponseError::from_error(format!("{err:?}")))?,
+    ).
+        await
+        .map_err(ResponseError::from)?;
+
+    Ok(Json(json!({ "source": "api", "data": response })))
+}
\ No newline at end of file
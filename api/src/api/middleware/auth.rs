@@ -1,6 +1,7 @@
 use uuid::Uuid;
 use crate::db::User;
 
+#[allow(clippy::upper_case_acronyms)]
 #[derive(Clone, Debug)]
 pub enum AuthType {
     API,
@@ -9,10 +10,7 @@ pub enum AuthType {
 
 impl AuthType {
     pub fn is_web(&self) -> bool {
-        match self {
-            AuthType::WEB => true,
-            _ => false,
-        }
+        matches!(self, AuthType::WEB)
     }
 }
 
@@ -51,7 +51,7 @@ async fn get_web_user_from_headers(headers: &HeaderMap, state: Arc<AppState>) ->
                     }
                 })
         })
-        .and_then(|cookie| state.jwt.claims_from_jwt(&cookie.value()).ok());
+        .and_then(|cookie| state.jwt.claims_from_jwt(cookie.value()).ok());
 
     match claims {
         None => None,
@@ -73,7 +73,7 @@ async fn get_api_user_from_headers(headers: &HeaderMap, state: Arc<AppState>) ->
 
     if let Some(api_key) = api_key {
         if let Ok(Some(api_key)) = state.db
-            .get_api_key_by_api_key(&utils::ApiKey::hash_value(api_key))
+            .get_api_key_by_api_key(&utils::ApiKey::hash_value(api_key, &state.config.app_secret))
             .await
             .map_err(|e| {
                 error!("{e}");
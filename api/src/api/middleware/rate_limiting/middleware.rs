@@ -3,28 +3,28 @@ use std::sync::Arc;
 use axum::Extension;
 use axum::middleware::Next;
 use axum::response::IntoResponse;
-use tracing::warn;
-use crate::api::middleware::auth::{AppUser, AuthType};
-use crate::api::middleware::rate_limiting::{Limit, Period, RateLimit, Target};
+use crate::api::middleware::auth::AppUser;
+use crate::api::middleware::rate_limiting::{RateLimit, Target};
 use crate::api::response_error::ResponseError;
-use crate::api::state::{AppState, DB};
-use crate::payments::subscription::SubscriptionTarget;
+use crate::api::state::AppState;
 
 
 pub enum RateLimitType {
     CreateProductInvoice,
     CreateUserInvoice,
     Login,
-    CreateUserWebhook
+    CreateUserWebhook,
+    Checkout,
 }
 
 impl RateLimitType {
-    async fn rate_limit(&self, app_user: &AppUser, db: &DB) -> Result<RateLimit, String> {
+    async fn rate_limit(&self, app_user: &AppUser, state: &AppState) -> Result<RateLimit, String> {
         match self {
-            RateLimitType::CreateProductInvoice => CreateProductInvoiceRateLimitGetter::get(app_user, db).await,
-            RateLimitType::CreateUserInvoice => CreateUserInvoiceRateLimitGetter::get(app_user, db).await,
-            RateLimitType::Login => LoginRateLimitGetter::get(app_user, db).await,
-            RateLimitType::CreateUserWebhook => CreateUserWebhookRateLimitGetter::get(app_user, db).await,
+            RateLimitType::CreateProductInvoice => CreateProductInvoiceRateLimitGetter::get(app_user, state).await,
+            RateLimitType::CreateUserInvoice => CreateUserInvoiceRateLimitGetter::get(app_user, state).await,
+            RateLimitType::Login => LoginRateLimitGetter::get(app_user, state).await,
+            RateLimitType::CreateUserWebhook => CreateUserWebhookRateLimitGetter::get(app_user, state).await,
+            RateLimitType::Checkout => CheckoutRateLimitGetter::get(app_user, state).await,
         }
     }
 
@@ -36,7 +36,7 @@ impl RateLimitType {
         next: Next,
     ) -> Result<impl IntoResponse, ResponseError> {
         match self
-            .rate_limit(app_user, &state.db)
+            .rate_limit(app_user, state)
             .await
             .map_err(ResponseError::from_error)?
             .is_ok(&state.redis, app_user)
@@ -82,10 +82,19 @@ impl RateLimitType {
     ) -> Result<impl IntoResponse, ResponseError> {
         Self::CreateUserWebhook.check(&state, &app_user, req, next).await
     }
+
+    pub async fn checkout(
+        State(state): State<Arc<AppState>>,
+        Extension(app_user): Extension<AppUser>,
+        req: Request,
+        next: Next,
+    ) -> Result<impl IntoResponse, ResponseError> {
+        Self::Checkout.check(&state, &app_user, req, next).await
+    }
 }
 
 trait RateLimitGetter {
-    async fn get(app_user: &AppUser, db: &DB) -> Result<RateLimit, String>;
+    async fn get(app_user: &AppUser, state: &AppState) -> Result<RateLimit, String>;
 }
 
 struct CreateProductInvoiceRateLimitGetter {}
@@ -93,59 +102,34 @@ struct CreateProductInvoiceRateLimitGetter {}
 struct CreateUserInvoiceRateLimitGetter {}
 struct LoginRateLimitGetter {}
 struct CreateUserWebhookRateLimitGetter {}
+struct CheckoutRateLimitGetter {}
 
 impl RateLimitGetter for CreateProductInvoiceRateLimitGetter {
-    async fn get(app_user: &AppUser, db: &DB) -> Result<RateLimit, String> {
-        Ok(RateLimit {
-            target: Target::ProductInvoice,
-            period: Period::Day,
-            limit: {
-                const API_LIMIT: i32 = 10;
-                const WEB_LIMIT: i32 = 3;
-                const ANONYMUS_LIMIT: i32 = WEB_LIMIT;
-
-                let get_default_limit = || {
-                    Limit::Limited(match &app_user.auth {
-                        None => ANONYMUS_LIMIT,
-                        Some(auth) => match auth.auth_type {
-                            AuthType::API => API_LIMIT,
-                            AuthType::WEB => WEB_LIMIT,
-                        },
-                    } as u16)
-                };
-                match app_user.user_id() {
-                    Some(user_id) => {
-                        let target: String = SubscriptionTarget::UnlimitedInvoices.into();
-                        match db.get_user_active_subscription(&user_id, &target).await {
-                            Err(err) => {
-                                warn!("Failed to get_user_active_subscription: {err}");
-                                get_default_limit()
-                            }
-                            Ok(None) => get_default_limit(),
-                            Ok(Some(_)) => Limit::Unlimited,
-                        }
-                    }
-                    None => get_default_limit()
-                }
-            },
-        })
+    async fn get(app_user: &AppUser, state: &AppState) -> Result<RateLimit, String> {
+        Ok(RateLimit::product_invoice(&state.db, &state.config.limits.rate, app_user).await)
     }
 }
 
 impl RateLimitGetter for CreateUserInvoiceRateLimitGetter {
-    async fn get(_: &AppUser, _: &DB) -> Result<RateLimit, String> {
-        Ok(RateLimit::create_10_times_per_day(Target::UserInvoice))
+    async fn get(_: &AppUser, state: &AppState) -> Result<RateLimit, String> {
+        Ok(RateLimit::per_day(Target::UserInvoice, state.config.limits.rate.user_invoice_per_day))
     }
 }
 
 impl RateLimitGetter for LoginRateLimitGetter {
-    async fn get(_: &AppUser, _: &DB) -> Result<RateLimit, String> {
-        Ok(RateLimit::create_10_times_per_day(Target::Login))
+    async fn get(_: &AppUser, state: &AppState) -> Result<RateLimit, String> {
+        Ok(RateLimit::per_day(Target::Login, state.config.limits.rate.login_per_day))
     }
 }
 
 impl RateLimitGetter for CreateUserWebhookRateLimitGetter {
-    async fn get(_: &AppUser, _: &DB) -> Result<RateLimit, String> {
-        Ok(RateLimit::create_5_times_per_minute(Target::CreateUserWebhook))
+    async fn get(_: &AppUser, state: &AppState) -> Result<RateLimit, String> {
+        Ok(RateLimit::per_minute(Target::CreateUserWebhook, state.config.limits.rate.webhook_create_per_minute))
+    }
+}
+
+impl RateLimitGetter for CheckoutRateLimitGetter {
+    async fn get(_: &AppUser, state: &AppState) -> Result<RateLimit, String> {
+        Ok(RateLimit::per_minute(Target::Checkout, state.config.limits.rate.checkout_per_minute))
     }
 }
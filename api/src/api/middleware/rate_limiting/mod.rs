@@ -1,9 +1,12 @@
 pub mod middleware;
 
 use chrono::Utc;
-use crate::api::middleware::auth::AppUser;
-use crate::api::state::Redis;
+use tracing::warn;
+use crate::api::middleware::auth::{AppUser, AuthType};
+use crate::api::state::{Redis, DB};
+use crate::config::RateLimits;
 use crate::error::AppError;
+use crate::payments::subscription::SubscriptionTarget;
 
 pub enum Limit {
     Unlimited,
@@ -34,19 +37,53 @@ impl RateLimit {
         }
     }
 
-    pub fn create_10_times_per_day(target: Target) -> Self {
+    /// Invoices created through `POST /payment/invoice` or the Telegram bot: per day by how the
+    /// user is authenticated, unlimited with the `UnlimitedInvoices` subscription.
+    pub async fn product_invoice(db: &DB, limits: &RateLimits, app_user: &AppUser) -> Self {
+        let get_default_limit = || {
+            Limit::Limited(match &app_user.auth {
+                None => limits.product_invoice_anonymous_per_day,
+                Some(auth) => match auth.auth_type {
+                    AuthType::API => limits.product_invoice_api_per_day,
+                    AuthType::WEB => limits.product_invoice_web_per_day,
+                },
+            })
+        };
+        let limit = match app_user.user_id() {
+            Some(user_id) => {
+                let target: String = SubscriptionTarget::UnlimitedInvoices.into();
+                match db.get_user_active_subscription(&user_id, &target).await {
+                    Err(err) => {
+                        warn!("Failed to get_user_active_subscription: {err}");
+                        get_default_limit()
+                    }
+                    Ok(None) => get_default_limit(),
+                    Ok(Some(_)) => Limit::Unlimited,
+                }
+            }
+            None => get_default_limit()
+        };
+
+        RateLimit {
+            target: Target::ProductInvoice,
+            period: Period::Day,
+            limit,
+        }
+    }
+
+    pub fn per_day(target: Target, times: u16) -> Self {
         RateLimit {
             target,
             period: Period::Day,
-            limit: Limit::Limited(10),
+            limit: Limit::Limited(times),
         }
     }
 
-    pub fn create_5_times_per_minute(target: Target) -> Self {
+    pub fn per_minute(target: Target, times: u16) -> Self {
         RateLimit {
             target,
             period: Period::Minute,
-            limit: Limit::Limited(5),
+            limit: Limit::Limited(times),
         }
     }
 }
@@ -56,7 +93,8 @@ pub enum Target {
     ProductInvoice,
     UserInvoice,
     Login,
-    CreateUserWebhook
+    CreateUserWebhook,
+    Checkout,
 }
 
 pub enum Period {
@@ -95,15 +133,15 @@ mod tests {
     }
 
     #[test]
-    fn test_create_10_times_per_day() {
-        let rl = RateLimit::create_10_times_per_day(Target::ProductInvoice);
+    fn test_per_day() {
+        let rl = RateLimit::per_day(Target::ProductInvoice, 10);
         assert!(matches!(rl.limit, Limit::Limited(10)));
         assert!(matches!(rl.period, Period::Day));
     }
 
     #[test]
-    fn test_create_5_times_per_minute() {
-        let rl = RateLimit::create_5_times_per_minute(Target::Login);
+    fn test_per_minute() {
+        let rl = RateLimit::per_minute(Target::Login, 5);
         assert!(matches!(rl.limit, Limit::Limited(5)));
         assert!(matches!(rl.period, Period::Minute));
     }
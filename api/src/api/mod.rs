@@ -2,8 +2,10 @@ pub(crate) mod ping_pong;
 pub mod state;
 pub(crate) mod payments;
 mod blockchain;
+#[cfg(feature = "infura")]
+mod gas_fees;
 pub(crate) mod auth;
-mod middleware;
+pub(crate) mod middleware;
 pub(crate) mod user;
 mod external;
 mod buy;
@@ -25,8 +27,10 @@ use utoipa_swagger_ui::SwaggerUi;
 use ping_pong::{ping_pong, health_check};
 use crate::api::openapi::ApiDoc;
 use crate::api::state::DB;
+use crate::config::ApiConfig;
 use crate::monitoring::health::DaemonHealth;
-use crate::network::Network;
+use crate::shutdown::Shutdown;
+#[cfg(feature = "telegram")]
 use crate::telegram::TelegramClient;
 use crate::utils as base_utils;
 
@@ -51,10 +55,17 @@ pub fn get_cryo_pay_callback_full_path() -> String {
 
 
 pub async fn run_api(
-    networks: Vec<Network>, db: DB, telegram_client: TelegramClient,
-    daemon_health: Arc<DaemonHealth>,
+    config: ApiConfig, db: DB,
+    #[cfg(feature = "telegram")] telegram_client: Option<TelegramClient>,
+    daemon_health: Option<Arc<DaemonHealth>>, shutdown: Shutdown,
 ) -> Result<(), String> {
-    let app_state = state::setup_app_state(networks, db, telegram_client, daemon_health).await?;
+    let app_state = state::setup_app_state(
+        config,
+        db,
+        #[cfg(feature = "telegram")]
+        telegram_client,
+        daemon_health,
+    ).await?;
     app_state.db.run_migrations()
         .await
         .map_err(|err| base_utils::make_err(Box::new(err), "run migrations"))?;
@@ -76,12 +87,17 @@ pub async fn run_api(
         .merge(
             SwaggerUi::new("/swagger-ui")
                 .url("/api-docs/openapi.json", ApiDoc::openapi())
-        )
-        .layer(TraceLayer::new_for_http());
+        );
+    #[cfg(feature = "telegram")]
+    if let Some(webhook) = &app_state.telegram_webhook {
+        tokio::spawn(webhook.clone().register(shutdown.clone()));
+        router = router.merge(webhook.clone().router());
+    }
+    router = router.layer(TraceLayer::new_for_http());
 
-    let web_origin = base_utils::web_base_url()
-        .and_then(|url| url.parse::<header::HeaderValue>()
-            .map_err(|e| format!("Invalid WEB_BASE_URL for CORS: {e}")))?;
+    let web_origin = app_state.config.web_base_url
+        .parse::<header::HeaderValue>()
+        .map_err(|e| format!("Invalid WEB_BASE_URL for CORS: {e}"))?;
 
     let cors = CorsLayer::new()
         .allow_methods([Method::GET, Method::POST, Method::DELETE, Method::OPTIONS])
@@ -90,14 +106,19 @@ pub async fn run_api(
         .allow_credentials(true);
     router = router.layer(cors);
 
-    let bind_address = base_utils::get_bind_address()?;
+    let bind_address = app_state.config.bind_address();
     info!("Listening on {}", bind_address);
     let listener = tokio::net::TcpListener::bind(bind_address)
         .await
         .map_err(|err| base_utils::make_err(Box::new(err), "init listener"))?;
 
-    axum::serve(listener, router.into_make_service()).await
+    axum::serve(listener, router.into_make_service())
+        .with_graceful_shutdown(shutdown.signal())
+        .await
         .map_err(|err| base_utils::make_err(Box::new(err), "start serving"))?;
 
+    info!("API stopped accepting requests");
+    app_state.db.close().await;
+
     Ok(())
 }
\ No newline at end of file
@@ -29,7 +29,10 @@ impl Modify for SecurityAddon {
         crate::api::payments::get_invoices_handler,
         crate::api::payments::create_invoice_handler,
         crate::api::payments::get_invoice_handler,
+        crate::api::payments::update_invoice_handler,
         crate::api::payments::delete_invoice_handler,
+        crate::api::payments::set_buyer_email_handler,
+        crate::api::payments::confirming_invoice_handler,
         crate::api::user::get_user,
         crate::api::user::update_user,
         crate::api::user::api_key::list,
@@ -38,22 +41,47 @@ impl Modify for SecurityAddon {
         crate::api::user::api_key::destroy,
         crate::api::user::webhook::list,
         crate::api::user::webhook::create,
+        crate::api::user::webhook::update,
+        crate::api::user::webhook::rotate_secret,
+        crate::api::user::webhook::send_test,
         crate::api::user::webhook::destroy,
+        crate::api::user::webhook::list_deliveries,
+        crate::api::user::webhook::redeliver,
+        crate::api::user::chat_webhook::list,
+        crate::api::user::chat_webhook::create,
+        crate::api::user::chat_webhook::update,
+        crate::api::user::chat_webhook::send_test,
+        crate::api::user::chat_webhook::destroy,
         crate::api::user::callback_url::list,
         crate::api::user::callback_url::create,
         crate::api::user::callback_url::destroy,
+        crate::api::user::template::list,
+        crate::api::user::notification_preference::list,
+        crate::api::user::notification_preference::upsert,
+        crate::api::user::notification_preference::destroy,
+        crate::api::user::notification_digest::list,
+        crate::api::user::notification_digest::upsert,
+        crate::api::user::notifications::list,
+        crate::api::user::notifications::resend,
+        crate::api::user::template::upsert,
+        crate::api::user::template::destroy,
         crate::api::user::analytics::get_analytics,
     ),
     components(
         schemas(
             crate::db::User,
             crate::db::Invoice,
+            crate::db::invoice::InvoiceStatus,
             crate::db::ApiKey,
             crate::db::CallbackUrl,
             crate::db::Webhook,
             crate::api::ping_pong::PongResponse,
             crate::api::payments::InvoiceResponse,
             crate::api::payments::CreateInvoiceRequest,
+            crate::api::payments::UpdateInvoiceRequest,
+            crate::api::payments::ConfirmingRequest,
+            crate::api::payments::InvoiceEventResponse,
+            crate::api::payments::BuyerEmailRequest,
             crate::api::auth::FirebaseTokenRequest,
             crate::api::user::UserRequest,
             crate::api::user::UserResponse,
@@ -61,8 +89,36 @@ impl Modify for SecurityAddon {
             crate::api::user::api_key::CreateApiKeyResponse,
             crate::api::user::webhook::GetWebhookResponse,
             crate::api::user::webhook::CreateWebhookRequest,
+            crate::api::user::webhook::UpdateWebhookRequest,
+            crate::api::user::webhook::RotateWebhookSecretRequest,
+            crate::api::user::webhook::TestWebhookResponse,
+            crate::events::webhook_event::WebhookEventType,
+            crate::events::webhook_event::WebhookEvent,
+            crate::events::webhook_event::WebhookEventData,
+            crate::events::webhook_event::PaymentEventData,
+            crate::events::webhook_event::SubscriptionEventData,
+            crate::events::webhook_event::TestEventData,
+            crate::events::notifications::InvoicePaidNotification,
+            crate::api::user::webhook::WebhookDeliveryResponse,
+            crate::api::user::webhook::WebhookDeliveryAttemptResponse,
+            crate::api::user::chat_webhook::GetChatWebhookResponse,
+            crate::api::user::chat_webhook::CreateChatWebhookRequest,
+            crate::api::user::chat_webhook::UpdateChatWebhookRequest,
+            crate::api::user::chat_webhook::TestChatWebhookResponse,
+            crate::events::notifications::chat::ChatKind,
             crate::api::user::callback_url::GetCallbackUrlResponse,
             crate::api::user::callback_url::CreateCallbackUrlRequest,
+            crate::api::user::template::GetNotificationTemplateResponse,
+            crate::api::user::template::ListNotificationTemplatesResponse,
+            crate::api::user::template::NotificationTemplateRequest,
+            crate::api::user::notification_preference::GetNotificationPreferenceResponse,
+            crate::api::user::notification_preference::NotificationPreferenceRequest,
+            crate::api::user::notification_digest::GetNotificationDigestResponse,
+            crate::api::user::notification_digest::NotificationDigestRequest,
+            crate::api::user::notifications::GetNotificationLogResponse,
+            crate::events::notifications::digest::DigestFrequency,
+            crate::events::notifications::preferences::NotificationChannel,
+            crate::events::notifications::preferences::NotificationEvent,
             crate::api::user::analytics::AnalyticsResponse,
             crate::db::analytics::InvoicePeriodStats,
             crate::db::analytics::InvoiceSummary,
@@ -81,3 +137,17 @@ impl Modify for SecurityAddon {
     )
 )]
 pub struct ApiDoc;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_webhook_payload_schemas_are_published() {
+        let openapi = ApiDoc::openapi();
+        let schemas = &openapi.components.as_ref().unwrap().schemas;
+        for name in ["WebhookEvent", "WebhookEventType", "WebhookEventData", "PaymentEventData", "SubscriptionEventData", "InvoiceResponse", "InvoicePaidNotification"] {
+            assert!(schemas.contains_key(name), "{name} missing from the OpenAPI document");
+        }
+    }
+}
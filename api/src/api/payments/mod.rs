@@ -3,33 +3,56 @@ use axum::extract::{Path, Query, State};
 use axum::http::StatusCode;
 use axum::{Extension, Json, middleware, Router};
 use axum::response::{IntoResponse, Redirect};
-use axum::routing::{delete, get, post};
+use axum::routing::{delete, get, post, put};
 use bigdecimal::BigDecimal;
-use chrono::NaiveDateTime;
+use chrono::{NaiveDateTime, Utc};
+use ethers::types::H256;
 use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use tracing::error;
 use url::Url;
 use uuid::Uuid;
 use crate::api::INVOICE_PATH;
 use crate::db::{Invoice, User};
+use crate::db::invoice::{InvoiceOptions, InvoiceStatus, InvoiceUpdate};
+use crate::db::invoice_event::InvoiceEvent;
 use crate::api::middleware::{extract_user, only_auth, only_bill_owner};
 use crate::api::middleware::auth::AppUser;
 use crate::api::middleware::rate_limiting::middleware::RateLimitType;
 use crate::api::ping_pong::ping_pong;
 use crate::api::response_error::ResponseError;
-use crate::api::state::AppState;
-use crate::api::utils::Pagination;
+use crate::api::state::{AppState, DB};
+use crate::api::utils::{nullable, Pagination};
+use crate::events;
+use crate::events::webhook_event::{self, WebhookEvent, WebhookEventType};
+use crate::network::Network;
 use crate::payments::subscription::SubscriptionTarget;
 
-#[derive(Serialize, utoipa::ToSchema)]
+/// Serialized size limit of an invoice's metadata.
+const MAX_METADATA_LENGTH: usize = 4096;
+
+#[derive(Clone, Debug, Serialize, utoipa::ToSchema)]
 pub(crate) struct InvoiceResponse {
     pub id: Uuid,
     pub created_at: NaiveDateTime,
     #[schema(value_type = String, example = "10.00")]
     pub amount: BigDecimal,
     pub seller: String,
+    pub buyer: Option<String>,
     pub paid_at: Option<NaiveDateTime>,
     pub networks: Vec<i32>,
     pub external_id: Option<String>,
+    /// Chain id of the network the invoice was paid on.
+    pub paid_network: Option<i32>,
+    pub tx_hash: Option<String>,
+    /// Whether the buyer is mailed a receipt once paid; checkout then asks for their email.
+    pub send_receipt: bool,
+    pub status: InvoiceStatus,
+    /// When the invoice expires if it is still unpaid.
+    pub expires_at: Option<NaiveDateTime>,
+    /// The seller's own JSON object.
+    #[schema(value_type = Option<Object>)]
+    pub metadata: Option<Value>,
 }
 
 impl From<Invoice> for InvoiceResponse {
@@ -39,9 +62,43 @@ impl From<Invoice> for InvoiceResponse {
             created_at: i.created_at,
             amount: i.amount,
             seller: i.seller,
+            buyer: i.buyer,
             paid_at: i.paid_at,
             networks: i.networks,
             external_id: i.external_id,
+            paid_network: i.paid_network,
+            tx_hash: i.tx_hash,
+            send_receipt: i.send_receipt,
+            status: i.status,
+            expires_at: i.expires_at,
+            metadata: i.metadata,
+        }
+    }
+}
+
+/// An entry in the invoice's timeline.
+#[derive(Serialize, utoipa::ToSchema)]
+pub(crate) struct InvoiceEventResponse {
+    pub id: Uuid,
+    pub invoice_id: Uuid,
+    /// `created`, `updated` or the status the invoice moved to.
+    #[schema(example = "updated")]
+    pub kind: String,
+    /// `{"field": {"from": ..., "to": ...}}` for `updated`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[schema(value_type = Option<Object>)]
+    pub changes: Option<Value>,
+    pub created_at: NaiveDateTime,
+}
+
+impl From<InvoiceEvent> for InvoiceEventResponse {
+    fn from(event: InvoiceEvent) -> Self {
+        Self {
+            id: event.id,
+            invoice_id: event.invoice_id,
+            kind: event.kind,
+            changes: event.changes,
+            created_at: event.created_at,
         }
     }
 }
@@ -50,6 +107,15 @@ impl From<Invoice> for InvoiceResponse {
 struct OwnInvoiceResponse {
     own: bool,
     invoice: InvoiceResponse,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    timeline: Option<Vec<InvoiceEventResponse>>,
+}
+
+#[derive(Serialize, utoipa::ToSchema)]
+struct InvoiceWithTimelineResponse {
+    #[serde(flatten)]
+    invoice: InvoiceResponse,
+    timeline: Vec<InvoiceEventResponse>,
 }
 
 pub fn get_router(app_state: Arc<AppState>) -> Router {
@@ -61,9 +127,18 @@ pub fn get_router(app_state: Arc<AppState>) -> Router {
             post(create_invoice_handler)
                 .layer(middleware::from_fn_with_state(app_state.clone(), RateLimitType::product_invoice)))
         .route(&format!("{INVOICE_PATH}/:invoice_id"), get(get_invoice_handler))
+        .route(
+            &format!("{INVOICE_PATH}/:invoice_id/buyer_email"),
+            put(set_buyer_email_handler)
+                .layer(middleware::from_fn_with_state(app_state.clone(), RateLimitType::checkout)))
+        .route(
+            &format!("{INVOICE_PATH}/:invoice_id/confirming"),
+            post(confirming_invoice_handler)
+                .layer(middleware::from_fn_with_state(app_state.clone(), RateLimitType::checkout)))
         .route(
             &format!("{INVOICE_PATH}/:invoice_id"),
             delete(delete_invoice_handler)
+                .patch(update_invoice_handler)
                 .layer(middleware::from_fn_with_state(app_state.clone(), only_auth))
                 .layer(middleware::from_fn_with_state(app_state.clone(), only_bill_owner)))
         .layer(middleware::from_fn_with_state(app_state.clone(), extract_user))
@@ -79,7 +154,7 @@ pub enum UserIdFilter {
 }
 
 #[derive(Deserialize)]
-struct Filter {
+pub(crate) struct Filter {
     #[serde(default = "default_user_id")]
     user_id: UserIdFilter,
 }
@@ -134,6 +209,16 @@ pub(crate) struct CreateInvoiceRequest {
     pub seller: String,
     pub networks: Vec<i32>,
     pub external_id: Option<String>,
+    /// Mail the buyer a receipt once paid; defaults to the owner's `buyer_receipts` setting.
+    pub send_receipt: Option<bool>,
+    /// Where the receipt goes; checkout asks the buyer when omitted.
+    #[schema(example = "buyer@example.com")]
+    pub buyer_email: Option<String>,
+    /// UTC; the invoice expires if it is still unpaid by then. Never expires when omitted.
+    pub expires_at: Option<NaiveDateTime>,
+    /// Any JSON object, returned with the invoice and in its webhook events.
+    #[schema(value_type = Option<Object>)]
+    pub metadata: Option<Value>,
 }
 
 #[utoipa::path(
@@ -151,22 +236,47 @@ pub(crate) async fn create_invoice_handler(
     Extension(app_user): Extension<AppUser>,
     Json(payload): Json<CreateInvoiceRequest>,
 ) -> Result<Json<InvoiceResponse>, ResponseError> {
-    if payload.amount <= BigDecimal::from(0) {
-        return Err(ResponseError::Bad("Amount must be positive".to_string()));
-    }
-    if payload.networks.is_empty() {
-        return Err(ResponseError::Bad("At least one network required".to_string()));
+    let invoice = create_invoice(&state.db, &state.config.networks, app_user.user_id(), payload).await?;
+
+    Ok(Json(invoice.into()))
+}
+
+/// Validates and creates an invoice, private when the owner subscribes to private invoices,
+/// and publishes `invoice.created`. Shared by the API and the Telegram bot.
+///
+/// Only invoices with an owner can send receipts, since the texts come from their templates.
+pub(crate) async fn create_invoice(
+    db: &DB,
+    networks: &[Network],
+    user_id: Option<Uuid>,
+    payload: CreateInvoiceRequest,
+) -> Result<Invoice, ResponseError> {
+    check_amount(&payload.amount)?;
+    check_networks(networks, &payload.networks)?;
+    let buyer_email = payload.buyer_email.as_deref().map(parse_buyer_email).transpose()?;
+    if let Some(expires_at) = &payload.expires_at {
+        check_expires_at(expires_at)?;
     }
-    let valid_ids: Vec<i32> = state.networks.iter().map(|n| n.id as i32).collect();
-    if let Some(invalid) = payload.networks.iter().find(|id| !valid_ids.contains(id)) {
-        return Err(ResponseError::Bad(format!("Invalid network ID: {}", invalid)));
+    if let Some(metadata) = &payload.metadata {
+        check_metadata(metadata)?;
     }
 
-    let is_private = match app_user.user_id() {
+    let send_receipt = match user_id {
+        None if payload.send_receipt == Some(true) => {
+            return Err(ResponseError::Bad("Receipts require an authorized owner".to_string()));
+        }
+        None => false,
+        Some(user_id) => match payload.send_receipt {
+            Some(send_receipt) => send_receipt,
+            None => db.get_user_by_id(&user_id).await.map_err(ResponseError::from)?.buyer_receipts,
+        },
+    };
+
+    let is_private = match user_id {
         None => false,
         Some(user_id) => {
             let target: String = SubscriptionTarget::PrivateInvoices.into();
-            state.db.get_user_active_subscription(
+            db.get_user_active_subscription(
                 &user_id,
                 &target,
             )
@@ -176,23 +286,87 @@ pub(crate) async fn create_invoice_handler(
         },
     };
 
-    let invoice = state.db.create_invoice(
+    let invoice = db.create_invoice_with_options(
         payload.amount,
         &payload.seller,
         &payload.networks,
-        app_user.user_id(),
+        user_id,
         payload.external_id,
-        is_private,
+        InvoiceOptions {
+            is_private,
+            send_receipt,
+            buyer_email,
+            expires_at: payload.expires_at,
+            metadata: payload.metadata,
+        },
     )
         .await
         .map_err(ResponseError::from)?;
 
-    Ok(Json(invoice.into()))
+    if let Some(user_id) = invoice.user_id {
+        let event = WebhookEvent::invoice(WebhookEventType::InvoiceCreated, &invoice);
+        if let Err(err) = webhook_event::publish(db, &user_id, &event).await {
+            error!("Failed to publish invoice.created for {}: {err}", invoice.id);
+        }
+    }
+
+    Ok(invoice)
+}
+
+fn check_amount(amount: &BigDecimal) -> Result<(), ResponseError> {
+    match amount > &BigDecimal::from(0) {
+        true => Ok(()),
+        false => Err(ResponseError::Bad("Amount must be positive".to_string())),
+    }
+}
+
+fn check_networks(networks: &[Network], ids: &[i32]) -> Result<(), ResponseError> {
+    if ids.is_empty() {
+        return Err(ResponseError::Bad("At least one network required".to_string()));
+    }
+    let valid_ids: Vec<i32> = networks.iter().map(|n| n.id as i32).collect();
+    match ids.iter().find(|id| !valid_ids.contains(id)) {
+        Some(invalid) => Err(ResponseError::Bad(format!("Invalid network ID: {}", invalid))),
+        None => Ok(()),
+    }
+}
+
+fn check_expires_at(expires_at: &NaiveDateTime) -> Result<(), ResponseError> {
+    match expires_at > &Utc::now().naive_utc() {
+        true => Ok(()),
+        false => Err(ResponseError::Bad("expires_at must be in the future".to_string())),
+    }
+}
+
+/// Metadata is a JSON object of at most [`MAX_METADATA_LENGTH`] bytes.
+fn check_metadata(metadata: &Value) -> Result<(), ResponseError> {
+    if !metadata.is_object() {
+        return Err(ResponseError::Bad("metadata must be a JSON object".to_string()));
+    }
+    match metadata.to_string().len() <= MAX_METADATA_LENGTH {
+        true => Ok(()),
+        false => Err(ResponseError::Bad(format!("metadata must be at most {MAX_METADATA_LENGTH} bytes"))),
+    }
+}
+
+/// Trimmed and lowercased; rejects anything that cannot be a mailbox.
+fn parse_buyer_email(email: &str) -> Result<String, ResponseError> {
+    let email = email.trim().to_lowercase();
+    let valid = email.len() <= 254
+        && !email.chars().any(char::is_whitespace)
+        && email.split_once('@').is_some_and(|(local, domain)| {
+            !local.is_empty() && !domain.contains('@') && domain.split('.').count() > 1 && domain.split('.').all(|label| !label.is_empty())
+        });
+    match valid {
+        true => Ok(email),
+        false => Err(ResponseError::Bad("Invalid buyer email".to_string())),
+    }
 }
 
 #[derive(Deserialize)]
-struct GetInvoiceQueryParams {
-    with_own: Option<bool>
+pub(crate) struct GetInvoiceQueryParams {
+    with_own: Option<bool>,
+    with_timeline: Option<bool>,
 }
 
 #[utoipa::path(
@@ -201,6 +375,7 @@ struct GetInvoiceQueryParams {
     params(
         ("id" = Uuid, Path, description = "Invoice ID"),
         ("with_own" = Option<bool>, Query, description = "Include ownership info"),
+        ("with_timeline" = Option<bool>, Query, description = "Include the invoice's timeline: creation, edits and status changes; owner only, omitted for anyone else"),
     ),
     responses(
         (status = 200, description = "Invoice", body = InvoiceResponse),
@@ -217,24 +392,167 @@ pub(crate) async fn get_invoice_handler(
     let invoice = state.db.get_invoice(&invoice_id)
         .await
         .map_err(ResponseError::from)?
-        .ok_or_else(|| ResponseError::NotFound)?;
+        .ok_or(ResponseError::NotFound)?;
 
     let invoice: InvoiceResponse = invoice.into();
+    let with_own = query_params.with_own.unwrap_or(false);
+    let with_timeline = query_params.with_timeline.unwrap_or(false);
+    let own = match with_own || with_timeline {
+        false => false,
+        true => is_owner(&state.db, &invoice_id, app_user.user_id()).await?,
+    };
+    // Edits and status history are the seller's business, not the buyer's.
+    let timeline = match with_timeline && own {
+        false => None,
+        true => Some(state.db.list_invoice_events(&invoice_id)
+            .await
+            .map_err(ResponseError::from)?
+            .into_iter()
+            .map(InvoiceEventResponse::from)
+            .collect::<Vec<_>>()),
+    };
 
-    Ok(match query_params.with_own.unwrap_or(false) {
-        false => Json(invoice).into_response(),
-        true => Json( OwnInvoiceResponse {
-            invoice,
-            own: match app_user.user_id() {
-                None => false,
-                Some(user_id) => state.db.get_is_owner(&invoice_id, &user_id)
-                    .await
-                    .map_err(ResponseError::from)?
-            }
-        } ).into_response()
+    Ok(match (with_own, timeline) {
+        (false, None) => Json(invoice).into_response(),
+        (false, Some(timeline)) => Json(InvoiceWithTimelineResponse { invoice, timeline }).into_response(),
+        (true, timeline) => Json( OwnInvoiceResponse { invoice, own, timeline } ).into_response()
     })
 }
 
+/// Anonymous callers own nothing.
+async fn is_owner(db: &DB, invoice_id: &Uuid, user_id: Option<Uuid>) -> Result<bool, ResponseError> {
+    match user_id {
+        None => Ok(false),
+        Some(user_id) => db.get_is_owner(invoice_id, &user_id)
+            .await
+            .map_err(ResponseError::from),
+    }
+}
+
+/// `PATCH` body; only the fields present change, and `null` clears the optional ones.
+#[derive(Deserialize, Default, utoipa::ToSchema)]
+pub(crate) struct UpdateInvoiceRequest {
+    #[schema(value_type = Option<String>, example = "12.50")]
+    pub amount: Option<BigDecimal>,
+    pub networks: Option<Vec<i32>>,
+    #[serde(default, deserialize_with = "nullable")]
+    #[schema(value_type = Option<String>)]
+    pub external_id: Option<Option<String>>,
+    /// UTC.
+    #[serde(default, deserialize_with = "nullable")]
+    #[schema(value_type = Option<NaiveDateTime>)]
+    pub expires_at: Option<Option<NaiveDateTime>>,
+    /// Replaces the whole object.
+    #[serde(default, deserialize_with = "nullable")]
+    #[schema(value_type = Option<Object>)]
+    pub metadata: Option<Option<Value>>,
+}
+
+impl UpdateInvoiceRequest {
+    /// Validates the request and keeps only the fields that differ from `invoice`, with their
+    /// `{"from", "to"}` values for the timeline.
+    fn diff(self, invoice: &Invoice, networks: &[Network]) -> Result<(InvoiceUpdate, Map<String, Value>), ResponseError> {
+        let mut update = InvoiceUpdate::default();
+        let mut changes = Map::new();
+        let mut change = |field: &str, from: Value, to: Value| {
+            changes.insert(field.to_string(), serde_json::json!({"from": from, "to": to}));
+        };
+
+        if let Some(amount) = self.amount.filter(|amount| amount != &invoice.amount) {
+            check_amount(&amount)?;
+            change("amount", Value::String(invoice.amount.to_string()), Value::String(amount.to_string()));
+            update.amount = Some(amount);
+        }
+        if let Some(ids) = self.networks.filter(|ids| ids != &invoice.networks) {
+            check_networks(networks, &ids)?;
+            change("networks", invoice.networks.clone().into(), ids.clone().into());
+            update.networks = Some(ids);
+        }
+        if let Some(external_id) = self.external_id.filter(|external_id| external_id != &invoice.external_id) {
+            change("external_id", invoice.external_id.clone().into(), external_id.clone().into());
+            update.external_id = Some(external_id);
+        }
+        if let Some(expires_at) = self.expires_at.filter(|expires_at| expires_at != &invoice.expires_at) {
+            if let Some(expires_at) = &expires_at {
+                check_expires_at(expires_at)?;
+            }
+            let json = |at: Option<NaiveDateTime>| serde_json::to_value(at).unwrap_or_default();
+            change("expires_at", json(invoice.expires_at), json(expires_at));
+            update.expires_at = Some(expires_at);
+        }
+        if let Some(metadata) = self.metadata.filter(|metadata| metadata != &invoice.metadata) {
+            if let Some(metadata) = &metadata {
+                check_metadata(metadata)?;
+            }
+            change("metadata", invoice.metadata.clone().into(), metadata.clone().into());
+            update.metadata = Some(metadata);
+        }
+
+        Ok((update, changes))
+    }
+}
+
+#[utoipa::path(
+    patch,
+    path = "/payment/invoice/{id}",
+    params(
+        ("id" = Uuid, Path, description = "Invoice ID"),
+    ),
+    request_body = UpdateInvoiceRequest,
+    responses(
+        (status = 200, description = "Updated invoice", body = InvoiceResponse),
+        (status = 400, description = "Invalid field, or the invoice is not open"),
+        (status = 404, description = "Not found"),
+    ),
+    tag = "invoices",
+    security(("jwt_cookie" = []))
+)]
+pub(crate) async fn update_invoice_handler(
+    State(state): State<Arc<AppState>>,
+    Path(invoice_id): Path<Uuid>,
+    Json(payload): Json<UpdateInvoiceRequest>,
+) -> Result<Json<InvoiceResponse>, ResponseError> {
+    let invoice = state.db.get_invoice(&invoice_id)
+        .await
+        .map_err(ResponseError::from)?
+        .ok_or(ResponseError::NotFound)?;
+
+    let invoice = update_invoice(&state.db, &state.config.networks, invoice, payload).await?;
+
+    Ok(Json(invoice.into()))
+}
+
+/// Edits an open invoice, records the changed fields in its timeline and publishes
+/// `invoice.updated`. Nothing is recorded when no field changes.
+async fn update_invoice(
+    db: &DB,
+    networks: &[Network],
+    invoice: Invoice,
+    payload: UpdateInvoiceRequest,
+) -> Result<Invoice, ResponseError> {
+    if invoice.status != InvoiceStatus::Open {
+        return Err(ResponseError::Bad(format!("A {} invoice cannot be edited", invoice.status.as_str())));
+    }
+    let (update, changes) = payload.diff(&invoice, networks)?;
+    if changes.is_empty() {
+        return Ok(invoice);
+    }
+
+    let invoice = db.update_invoice(&invoice.id, &update, Value::Object(changes))
+        .await
+        .map_err(ResponseError::from)?
+        .ok_or_else(|| ResponseError::Bad("The invoice is no longer open".to_string()))?;
+
+    if let Some(user_id) = invoice.user_id {
+        let event = WebhookEvent::invoice(WebhookEventType::InvoiceUpdated, &invoice);
+        if let Err(err) = webhook_event::publish(db, &user_id, &event).await {
+            error!("Failed to publish invoice.updated for {}: {err}", invoice.id);
+        }
+    }
+
+    Ok(invoice)
+}
+
 #[utoipa::path(
     delete,
     path = "/payment/invoice/{id}",
@@ -242,23 +560,182 @@ pub(crate) async fn get_invoice_handler(
         ("id" = Uuid, Path, description = "Invoice ID"),
     ),
     responses(
-        (status = 204, description = "Deleted"),
+        (status = 204, description = "Voided"),
+        (status = 400, description = "The invoice is paid, expired or already void"),
         (status = 404, description = "Not found"),
     ),
     tag = "invoices",
     security(("jwt_cookie" = []))
 )]
+/// Voids the invoice rather than deleting it, so payments that still arrive are reported as
+/// anomalies instead of being lost. Publishes `invoice.deleted`.
 pub(crate) async fn delete_invoice_handler(
     State(state): State<Arc<AppState>>,
     Path(invoice_id): Path<Uuid>,
     Extension(user): Extension<User>,
 ) -> Result<impl IntoResponse, ResponseError> {
-    Ok(match state.db.delete_own_invoice(&invoice_id, &user.id)
+    let invoice = state.db.get_invoice(&invoice_id)
         .await
-        .map_err(ResponseError::from)? {
-        true => StatusCode::NO_CONTENT,
-        false => return Err(ResponseError::NotFound),
-    })
+        .map_err(ResponseError::from)?
+        .ok_or(ResponseError::NotFound)?;
+
+    let invoice = state.db.transition_invoice(&invoice.id, InvoiceStatus::Void)
+        .await
+        .map_err(ResponseError::from)?
+        .ok_or_else(|| ResponseError::Bad(format!("A {} invoice cannot be voided", invoice.status.as_str())))?;
+
+    let event = WebhookEvent::invoice(WebhookEventType::InvoiceDeleted, &invoice);
+    if let Err(err) = webhook_event::publish(&state.db, &user.id, &event).await {
+        error!("Failed to publish invoice.deleted for {invoice_id}: {err}");
+    }
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[derive(Deserialize, utoipa::ToSchema)]
+pub(crate) struct BuyerEmailRequest {
+    #[schema(example = "buyer@example.com")]
+    pub email: String,
+}
+
+/// Checkout form: where the receipt goes once the invoice is paid. Anyone with the invoice
+/// link can set it once, so the address is never returned; after that only the seller can
+/// change it.
+#[utoipa::path(
+    put,
+    path = "/payment/invoice/{id}/buyer_email",
+    params(
+        ("id" = Uuid, Path, description = "Invoice ID"),
+    ),
+    request_body = BuyerEmailRequest,
+    responses(
+        (status = 204, description = "Saved"),
+        (status = 400, description = "Invalid email or receipts are off for the invoice"),
+        (status = 409, description = "Email already set or invoice no longer payable"),
+        (status = 429, description = "Too many requests"),
+        (status = 404, description = "Not found"),
+    ),
+    tag = "invoices"
+)]
+pub(crate) async fn set_buyer_email_handler(
+    State(state): State<Arc<AppState>>,
+    Path(invoice_id): Path<Uuid>,
+    Extension(app_user): Extension<AppUser>,
+    Json(payload): Json<BuyerEmailRequest>,
+) -> Result<impl IntoResponse, ResponseError> {
+    let invoice = state.db.get_invoice(&invoice_id)
+        .await
+        .map_err(ResponseError::from)?
+        .ok_or(ResponseError::NotFound)?;
+    let email = parse_buyer_email(&payload.email)?;
+    let own = is_owner(&state.db, &invoice_id, app_user.user_id()).await?;
+
+    set_buyer_email(&state.db, &invoice, &email, own).await?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// A buyer cannot replace an address already set, or anyone with the link could redirect
+/// the receipt. The update itself checks that, and that the invoice is unpaid, so it holds
+/// against a concurrent request or payment.
+async fn set_buyer_email(db: &DB, invoice: &Invoice, email: &str, own: bool) -> Result<(), ResponseError> {
+    if !invoice.send_receipt {
+        return Err(ResponseError::Bad("The invoice does not send receipts".to_string()));
+    }
+    if db.set_invoice_buyer_email(&invoice.id, email, own).await.map_err(ResponseError::from)?.is_some() {
+        return Ok(());
+    }
+    let current = db.get_invoice(&invoice.id)
+        .await
+        .map_err(ResponseError::from)?
+        .ok_or(ResponseError::NotFound)?;
+    match current.is_payable() {
+        true => Err(ResponseError::Conflict("The receipt email is already set".to_string())),
+        false => Err(ResponseError::Conflict(format!("The invoice is {}", current.status.as_str()))),
+    }
+}
+
+#[derive(Deserialize, utoipa::ToSchema)]
+pub(crate) struct ConfirmingRequest {
+    /// Hash of the buyer's `payInvoice` transaction.
+    #[schema(example = "0x5c504ed432cb51138bcf09aa5e8a410dd4a1e204ef84bfed1be16dfba1b22060")]
+    pub tx_hash: String,
+    /// Chain id the transaction was sent to.
+    #[schema(example = 10)]
+    pub network: i32,
+}
+
+/// Checkout: the buyer sent a transaction, so the invoice waits for it to be mined instead of
+/// expiring. The transaction must be pending or mined and pay this invoice to its seller, and an
+/// invoice goes through `confirming` only once. `monitoring::invoice_expiry` reopens or expires
+/// it if no payment arrives.
+#[utoipa::path(
+    post,
+    path = "/payment/invoice/{id}/confirming",
+    params(
+        ("id" = Uuid, Path, description = "Invoice ID"),
+    ),
+    request_body = ConfirmingRequest,
+    responses(
+        (status = 204, description = "Confirming"),
+        (status = 400, description = "The invoice is not open, was already confirming, or the transaction does not pay it"),
+        (status = 404, description = "Not found"),
+        (status = 429, description = "Too many requests"),
+    ),
+    tag = "invoices"
+)]
+pub(crate) async fn confirming_invoice_handler(
+    State(state): State<Arc<AppState>>,
+    Path(invoice_id): Path<Uuid>,
+    Json(payload): Json<ConfirmingRequest>,
+) -> Result<impl IntoResponse, ResponseError> {
+    let invoice = state.db.get_invoice(&invoice_id)
+        .await
+        .map_err(ResponseError::from)?
+        .ok_or(ResponseError::NotFound)?;
+    if invoice.status == InvoiceStatus::Confirming {
+        return Ok(StatusCode::NO_CONTENT);
+    }
+    check_confirming(&invoice)?;
+
+    let network = state.config.networks.iter()
+        .find(|network| network.id == payload.network as i64 && invoice.networks.contains(&payload.network))
+        .ok_or_else(|| ResponseError::Bad("The invoice is not payable on this network".to_string()))?;
+    let tx_hash = payload.tx_hash.parse::<H256>()
+        .map_err(|_| ResponseError::Bad("Invalid tx_hash".to_string()))?;
+    let (tx, receipt) = events::get_payment_transaction(network, tx_hash)
+        .await
+        .map_err(ResponseError::from)?
+        .ok_or_else(|| ResponseError::Bad("Transaction not found".to_string()))?;
+    events::check_payment_transaction(&tx, receipt.as_ref(), network, &invoice)
+        .map_err(ResponseError::Bad)?;
+
+    mark_confirming(&state.db, &invoice).await?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Only open invoices that never were confirming, so a reported transaction holds off expiry once.
+fn check_confirming(invoice: &Invoice) -> Result<(), ResponseError> {
+    if invoice.status != InvoiceStatus::Open {
+        return Err(ResponseError::Bad(format!("The invoice is {}", invoice.status.as_str())));
+    }
+    match invoice.confirming_at {
+        Some(_) => Err(ResponseError::Bad("The invoice was already confirming".to_string())),
+        None => Ok(()),
+    }
+}
+
+/// Idempotent, since wallets may report the same transaction more than once.
+async fn mark_confirming(db: &DB, invoice: &Invoice) -> Result<(), ResponseError> {
+    if invoice.status == InvoiceStatus::Confirming {
+        return Ok(());
+    }
+    db.transition_invoice(&invoice.id, InvoiceStatus::Confirming)
+        .await
+        .map_err(ResponseError::from)?
+        .ok_or_else(|| ResponseError::Bad(format!("The invoice is {}", invoice.status.as_str())))
+        .map(|_| ())
 }
 
 #[derive(Deserialize)]
@@ -279,10 +756,10 @@ async fn redirect_invoice_handler(
             None => Err(ResponseError::NotFound),
             Some(invoice) => {
                 let was_paid = invoice.paid_at.is_some();
-                let get_success_response = || get_redirect_url(&url, &invoice_id, was_paid);
+                let get_success_response = || get_redirect_url(url, &invoice_id, was_paid);
                 match invoice.user_id {
                     None => get_success_response(),
-                    Some(user_id) => match state.db.exists_callback_url(&url, &user_id)
+                    Some(user_id) => match state.db.exists_callback_url(url, &user_id)
                         .await
                         .map_err(ResponseError::from)? {
                         false => Err(ResponseError::Bad("`url` not found in callback_urls".to_string())),
@@ -301,3 +778,164 @@ fn get_redirect_url(url: &str, invoice_id: &Uuid, was_paid: bool) -> Result<impl
 
     Ok(Redirect::to(parsed_url.as_str()))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    #[rstest]
+    #[case(" Buyer@Example.com ", Some("buyer@example.com"))]
+    #[case("a.b+c@mail.example.co", Some("a.b+c@mail.example.co"))]
+    #[case("buyer@localhost", None)]
+    #[case("buyer@example.", None)]
+    #[case("@example.com", None)]
+    #[case("buyer@@example.com", None)]
+    #[case("bu yer@example.com", None)]
+    fn test_parse_buyer_email(#[case] email: &str, #[case] expected: Option<&str>) {
+        assert_eq!(parse_buyer_email(email).ok().as_deref(), expected);
+    }
+
+    #[tokio::test]
+    async fn test_is_owner() {
+        let db = DB::in_memory();
+        let user_id = Uuid::new_v4();
+        let invoice = db.create_invoice(BigDecimal::from(10), "0xSeller", &[1], Some(user_id), None, false).await.unwrap();
+
+        assert!(is_owner(&db, &invoice.id, Some(user_id)).await.unwrap());
+        assert!(!is_owner(&db, &invoice.id, Some(Uuid::new_v4())).await.unwrap());
+        assert!(!is_owner(&db, &invoice.id, None).await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_receipts_at_checkout() {
+        let db = DB::in_memory();
+        let networks = Network::parse_networks(r#"[
+            {"name":"Ethereum","id":1,"link":"https://rpc.example.com","addresses":{"erc20":"0xabc","contract":"0xdef"}}
+        ]"#).unwrap();
+        let seller = db.get_or_create_user("seller", None).await.unwrap();
+        let request = |send_receipt, buyer_email: Option<&str>| CreateInvoiceRequest {
+            amount: BigDecimal::from(10),
+            seller: "0xSeller".to_string(),
+            networks: vec![1],
+            external_id: None,
+            send_receipt,
+            buyer_email: buyer_email.map(str::to_string),
+            expires_at: None,
+            metadata: None,
+        };
+
+        let invoice = create_invoice(&db, &networks, Some(seller.id), request(None, None)).await.unwrap();
+        assert!(!invoice.send_receipt);
+        assert!(matches!(set_buyer_email(&db, &invoice, "buyer@example.com", false).await, Err(ResponseError::Bad(_))));
+
+        // The account-wide setting is the default, the request overrides it.
+        db.set_user_buyer_receipts(&seller.id, true).await.unwrap();
+        assert!(create_invoice(&db, &networks, Some(seller.id), request(None, None)).await.unwrap().send_receipt);
+        assert!(!create_invoice(&db, &networks, Some(seller.id), request(Some(false), None)).await.unwrap().send_receipt);
+        assert!(matches!(create_invoice(&db, &networks, None, request(Some(true), None)).await, Err(ResponseError::Bad(_))));
+
+        let invoice = create_invoice(&db, &networks, Some(seller.id), request(None, Some("First@Example.com"))).await.unwrap();
+        assert_eq!(db.get_buyer_email(&invoice).unwrap().as_deref(), Some("first@example.com"));
+        assert!(matches!(set_buyer_email(&db, &invoice, "buyer@example.com", false).await, Err(ResponseError::Conflict(message)) if message == "The receipt email is already set"));
+        set_buyer_email(&db, &invoice, "buyer@example.com", true).await.unwrap();
+        let invoice = db.get_invoice(&invoice.id).await.unwrap().unwrap();
+        assert_eq!(db.get_buyer_email(&invoice).unwrap().as_deref(), Some("buyer@example.com"));
+
+        let invoice = create_invoice(&db, &networks, Some(seller.id), request(Some(true), None)).await.unwrap();
+        set_buyer_email(&db, &invoice, "buyer@example.com", false).await.unwrap();
+        // `invoice` was read before the email was set, like a concurrent request's would be.
+        assert!(matches!(set_buyer_email(&db, &invoice, "other@example.com", false).await, Err(ResponseError::Conflict(_))));
+
+        db.transition_invoice(&invoice.id, InvoiceStatus::Void).await.unwrap().unwrap();
+        let invoice = db.get_invoice(&invoice.id).await.unwrap().unwrap();
+        assert!(matches!(set_buyer_email(&db, &invoice, "buyer@example.com", true).await, Err(ResponseError::Conflict(message)) if message == "The invoice is void"));
+    }
+
+    #[tokio::test]
+    async fn test_invoice_expiry_and_confirming() {
+        let db = DB::in_memory();
+        let networks = Network::parse_networks(r#"[
+            {"name":"Ethereum","id":1,"link":"https://rpc.example.com","addresses":{"erc20":"0xabc","contract":"0xdef"}}
+        ]"#).unwrap();
+        let request = |expires_at| CreateInvoiceRequest {
+            amount: BigDecimal::from(10),
+            seller: "0xSeller".to_string(),
+            networks: vec![1],
+            external_id: None,
+            send_receipt: None,
+            buyer_email: None,
+            expires_at,
+            metadata: None,
+        };
+
+        let past = Utc::now().naive_utc() - chrono::Duration::minutes(1);
+        assert!(matches!(create_invoice(&db, &networks, None, request(Some(past))).await, Err(ResponseError::Bad(_))));
+        let expires_at = Utc::now().naive_utc() + chrono::Duration::hours(1);
+        let invoice = create_invoice(&db, &networks, None, request(Some(expires_at))).await.unwrap();
+        assert_eq!((invoice.status, invoice.expires_at), (InvoiceStatus::Open, Some(expires_at)));
+
+        check_confirming(&invoice).unwrap();
+        mark_confirming(&db, &invoice).await.unwrap();
+        let invoice = db.get_invoice(&invoice.id).await.unwrap().unwrap();
+        assert_eq!(invoice.status, InvoiceStatus::Confirming);
+        mark_confirming(&db, &invoice).await.unwrap();
+
+        let now = Utc::now().naive_utc() + chrono::Duration::minutes(1);
+        db.reopen_stale_invoices(now, now).await.unwrap();
+        let invoice = db.get_invoice(&invoice.id).await.unwrap().unwrap();
+        assert_eq!(invoice.status, InvoiceStatus::Open);
+        assert!(matches!(check_confirming(&invoice),
+            Err(ResponseError::Bad(message)) if message == "The invoice was already confirming"));
+        assert!(db.transition_invoice(&invoice.id, InvoiceStatus::Confirming).await.unwrap().is_none());
+
+        db.transition_invoice(&invoice.id, InvoiceStatus::Expired).await.unwrap().unwrap();
+        let invoice = db.get_invoice(&invoice.id).await.unwrap().unwrap();
+        assert!(matches!(check_confirming(&invoice), Err(ResponseError::Bad(_))));
+        assert!(matches!(mark_confirming(&db, &invoice).await, Err(ResponseError::Bad(_))));
+    }
+
+    #[tokio::test]
+    async fn test_update_invoice_records_timeline() {
+        let db = DB::in_memory();
+        let networks = Network::parse_networks(r#"[
+            {"name":"Ethereum","id":1,"link":"https://rpc.example.com","addresses":{"erc20":"0xabc","contract":"0xdef"}},
+            {"name":"Polygon","id":137,"link":"https://rpc.example.com","addresses":{"erc20":"0xabc","contract":"0xdef"}}
+        ]"#).unwrap();
+        let invoice = create_invoice(&db, &networks, None, CreateInvoiceRequest {
+            amount: BigDecimal::from(10),
+            seller: "0xSeller".to_string(),
+            networks: vec![1],
+            external_id: Some("order-1".to_string()),
+            send_receipt: None,
+            buyer_email: None,
+            expires_at: None,
+            metadata: None,
+        }).await.unwrap();
+        let patch = |body: &str| serde_json::from_str::<UpdateInvoiceRequest>(body).unwrap();
+
+        let invoice = update_invoice(&db, &networks, invoice, patch(r#"{"amount":"10","networks":[1,137],"metadata":{"sku":"A1"}}"#)).await.unwrap();
+        assert_eq!((invoice.amount.clone(), invoice.networks.clone()), (BigDecimal::from(10), vec![1, 137]));
+        assert_eq!(invoice.metadata, Some(serde_json::json!({"sku": "A1"})));
+        let invoice = update_invoice(&db, &networks, invoice, patch(r#"{"external_id":null}"#)).await.unwrap();
+        assert_eq!(invoice.external_id, None);
+        let invoice = update_invoice(&db, &networks, invoice, patch(r#"{"networks":[1,137]}"#)).await.unwrap();
+
+        for body in [r#"{"amount":"0"}"#, r#"{"networks":[5]}"#, r#"{"metadata":[1]}"#, r#"{"expires_at":"2000-01-01T00:00:00"}"#] {
+            assert!(matches!(update_invoice(&db, &networks, invoice.clone(), patch(body)).await, Err(ResponseError::Bad(_))), "{body}");
+        }
+
+        let timeline = db.list_invoice_events(&invoice.id).await.unwrap();
+        assert_eq!(timeline.iter().map(|event| event.kind.as_str()).collect::<Vec<_>>(), ["created", "updated", "updated"]);
+        assert_eq!(timeline[1].changes, Some(serde_json::json!({
+            "networks": {"from": [1], "to": [1, 137]},
+            "metadata": {"from": null, "to": {"sku": "A1"}},
+        })));
+        assert_eq!(timeline[2].changes, Some(serde_json::json!({"external_id": {"from": "order-1", "to": null}})));
+
+        db.transition_invoice(&invoice.id, InvoiceStatus::Void).await.unwrap().unwrap();
+        let invoice = db.get_invoice(&invoice.id).await.unwrap().unwrap();
+        assert!(matches!(update_invoice(&db, &networks, invoice, patch(r#"{"amount":"5"}"#)).await,
+            Err(ResponseError::Bad(message)) if message == "A void invoice cannot be edited"));
+    }
+}
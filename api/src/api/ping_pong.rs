@@ -31,7 +31,11 @@ struct HealthResponse {
     status: String,
     postgres: bool,
     redis: bool,
-    daemon: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    daemon: Option<bool>,
+    /// Whether Telegram accepted the bot's webhook; informational, as payments do not depend on it.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    telegram_webhook: Option<bool>,
 }
 
 #[utoipa::path(
@@ -50,11 +54,15 @@ pub async fn health_check(State(state): State<Arc<AppState>>) -> impl IntoRespon
     );
     let postgres = pg_result.is_ok();
     let redis = redis_result.is_ok();
-    let daemon = state.daemon_health.is_healthy();
+    let daemon = state.daemon_health.as_ref().map(|health| health.is_healthy());
+    #[cfg(feature = "telegram")]
+    let telegram_webhook = state.telegram_webhook.as_ref().map(|webhook| webhook.is_registered());
+    #[cfg(not(feature = "telegram"))]
+    let telegram_webhook = None;
 
-    let all_healthy = postgres && redis && daemon;
+    let all_healthy = postgres && redis && daemon.unwrap_or(true);
     let status = if all_healthy { "ok" } else { "degraded" }.to_string();
     let code = if all_healthy { StatusCode::OK } else { StatusCode::SERVICE_UNAVAILABLE };
 
-    (code, Json(HealthResponse { status, postgres, redis, daemon }))
+    (code, Json(HealthResponse { status, postgres, redis, daemon, telegram_webhook }))
 }
@@ -10,6 +10,8 @@ pub enum ResponseError {
     Bad(String),
     InternalServerError(String),
     NotFound,
+    /// The request lost to the resource's current state.
+    Conflict(String),
     Unauthorized,
     TooManyRequests,
 }
@@ -41,6 +43,9 @@ impl IntoResponse for ResponseError {
                 (StatusCode::INTERNAL_SERVER_ERROR, json_error("internal_server_error"))
             },
             ResponseError::NotFound => (StatusCode::NOT_FOUND, with_message(None, "not_found")),
+            ResponseError::Conflict(message) => (
+                StatusCode::CONFLICT, with_message(Some(json_error("conflict")), &message)
+            ),
             ResponseError::Unauthorized => (StatusCode::UNAUTHORIZED, json_error("unauthorized")),
             ResponseError::TooManyRequests => (StatusCode::TOO_MANY_REQUESTS, json_error("too_many_requests")),
         }.into_response()
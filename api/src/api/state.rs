@@ -1,70 +1,139 @@
 use std::sync::Arc;
+use std::time::Duration;
 use bigdecimal::BigDecimal;
 use hex;
 use sha2;
 use chrono::NaiveDateTime;
 use jsonwebtoken::{Algorithm, decode, DecodingKey, encode, EncodingKey, Header, Validation};
-use redis::{aio::ConnectionManager, AsyncCommands, RedisResult};
-use rs_firebase_admin_sdk::{credentials_provider, App, auth::token::{cache::{HttpCache, PubKeys}, error::TokenVerificationError, jwt::JWToken, LiveTokenVerifier, TokenVerifier}};
+use redis::aio::ConnectionManager;
+#[cfg(any(feature = "infura", feature = "telegram"))]
+use redis::{AsyncCommands, RedisResult};
+#[cfg(feature = "firebase")]
+use rs_firebase_admin_sdk::{credentials_provider, App, auth::token::{cache::{HttpCache, PubKeys}, LiveTokenVerifier, TokenVerifier}};
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
 use sqlx::migrate::MigrateError;
-use sqlx::PgPool;
+use tracing::error;
 use uuid::Uuid;
+use crate::config::{ApiConfig, DatabaseConfig};
 use crate::db::{self, ApiKey, CallbackUrl, Invoice, User, Webhook};
-use crate::db::billing::{self, Payment, Subscription};
+use crate::db::billing::{Payment, Subscription};
+use crate::db::chat_webhook::{ChatWebhook, NewChatWebhook};
+use crate::db::encryption::Keyring;
+use crate::db::invoice::{InvoiceOptions, InvoicePayment, InvoiceStatus, InvoiceUpdate};
+use crate::db::invoice_event::InvoiceEvent;
+use crate::db::notification_digest::{NewNotificationDigestItem, NotificationDigest, NotificationDigestItem};
+use crate::db::notification_log::{NewNotificationLog, NotificationLog, NotificationLogFilter};
+use crate::db::notification_preference::NotificationPreference;
+use crate::db::notification_template::NotificationTemplate;
+use crate::db::postgres::PgRepository;
+#[cfg(feature = "telegram")]
+use crate::db::telegram_destination::NewTelegramDestination;
+use crate::db::telegram_destination::TelegramDestination;
+use crate::db::repository::*;
+use crate::db::webhook_delivery::{DueWebhookDelivery, NewWebhookDeliveryAttempt, WebhookDelivery, WebhookDeliveryAttempt, WebhookDeliveryStatus};
 use crate::error::AppError;
+#[cfg(feature = "telegram")]
+use crate::events::webhook_event::WebhookEventType;
+use crate::monitoring::app_state::Webhooker;
 use crate::monitoring::health::DaemonHealth;
-use crate::network::Network;
-use crate::telegram::TelegramClient;
+#[cfg(feature = "telegram")]
+use crate::telegram::{TelegramClient, TelegramWebhook};
 use crate::utils;
-
-const SUGGESTED_GAS_FEE_TIMEOUT: u64 = 60 * 10;
+use crate::utils::ssrf::OutboundGuard;
 
 pub async fn setup_app_state(
-    networks: Vec<Network>, db: DB, telegram_client: TelegramClient,
-    daemon_health: Arc<DaemonHealth>,
+    config: ApiConfig, db: DB,
+    #[cfg(feature = "telegram")] telegram_client: Option<TelegramClient>,
+    daemon_health: Option<Arc<DaemonHealth>>,
 ) -> Result<AppState, String> {
-    let gc = GC::new().await?;
-    let jwt = JWT::new()?;
-    let redis = Redis::new().await?;
-    let infura_token = utils::get_env_var("INFURA_TOKEN")?;
-
-    Ok(AppState { db, telegram_client, networks, gc, jwt, redis, infura_token, daemon_health })
+    let gc = match config.firebase_auth {
+        true => Some(GC::new().await?),
+        false => None,
+    };
+    let jwt = JWT::new(config.app_secret.clone(), config.limits.jwt_expiry_hours);
+    let redis = Redis::new(&config.redis_url, config.limits.gas_fee_cache_ttl_secs).await?;
+    #[cfg(feature = "telegram")]
+    let telegram_webhook = telegram_client.as_ref()
+        .and_then(|client| client.webhook(&config, db.clone(), redis.clone()));
+
+    Ok(AppState {
+        config: Arc::new(config),
+        db,
+        #[cfg(feature = "telegram")]
+        telegram_client,
+        #[cfg(feature = "telegram")]
+        telegram_webhook,
+        gc,
+        jwt,
+        redis,
+        daemon_health,
+        webhooker: Webhooker::new(OutboundGuard::system())?,
+    })
 }
 
+/// Storage facade used by handlers and the monitor. Wraps a [`Repository`] and maps its
+/// errors to [`AppError`]; the backend is Postgres outside of tests.
+///
+/// Webhook secrets, emails and Telegram chat ids are encrypted here on the way in and
+/// decrypted on the way out, so repositories only ever see ciphertext.
 #[derive(Clone)]
 pub struct DB {
-    pg_pool: PgPool,
+    repo: Arc<dyn Repository>,
+    keyring: Arc<Keyring>,
+}
+
+/// Rows rewritten by [`DB::reencrypt`].
+#[derive(Debug, Default, PartialEq)]
+pub struct ReencryptReport {
+    pub users: usize,
+    pub telegram_destinations: usize,
+    pub chat_webhooks: usize,
+    pub webhooks: usize,
+    pub invoices: usize,
 }
 
+#[cfg(feature = "firebase")]
 type FirebaseVerifier = LiveTokenVerifier<HttpCache<reqwest::Client, PubKeys>>;
 
 #[derive(Clone)]
 pub struct GC {
+    #[cfg(feature = "firebase")]
     verifier: Arc<FirebaseVerifier>,
 }
 
+#[allow(clippy::upper_case_acronyms)]
 #[derive(Clone)]
 pub struct JWT {
     secret: String,
+    expiry_hours: i64,
 }
 
 #[derive(Clone)]
 pub struct Redis {
     connection: ConnectionManager,
+    #[cfg_attr(not(feature = "infura"), allow(dead_code))]
+    gas_fee_cache_ttl_secs: u64,
 }
 
 #[derive(Clone)]
 pub struct AppState {
+    pub config: Arc<ApiConfig>,
     pub db: DB,
-    pub telegram_client: TelegramClient,
-    pub networks: Vec<Network>,
-    pub gc: GC,
+    /// `None` when the Telegram integration is disabled.
+    #[cfg(feature = "telegram")]
+    pub telegram_client: Option<TelegramClient>,
+    /// Set when bot updates arrive by webhook rather than long polling.
+    #[cfg(feature = "telegram")]
+    pub telegram_webhook: Option<Arc<TelegramWebhook>>,
+    /// `None` when Firebase login is disabled; API keys still work.
+    pub gc: Option<GC>,
     pub jwt: JWT,
     pub redis: Redis,
-    pub infura_token: String,
-    pub daemon_health: Arc<DaemonHealth>,
+    /// Present only when the monitor runs in the same process as the API.
+    pub daemon_health: Option<Arc<DaemonHealth>>,
+    /// Client for user-facing webhook calls (endpoint checks, test events).
+    pub webhooker: Webhooker,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -84,15 +153,13 @@ impl Claims {
 }
 
 impl JWT {
-    fn new() -> Result<Self, String> {
-        let secret = utils::get_env_var("APP_SECRET")?;
-
-        Ok(JWT { secret })
+    fn new(secret: String, expiry_hours: i64) -> Self {
+        JWT { secret, expiry_hours }
     }
 
     pub fn generate(&self, user_id: String, email: Option<String>) -> Result<String, jsonwebtoken::errors::Error> {
         let expiration = chrono::Utc::now()
-            .checked_add_signed(chrono::Duration::hours(24))
+            .checked_add_signed(chrono::Duration::hours(self.expiry_hours))
             .expect("valid timestamp")
             .timestamp() as usize;
 
@@ -116,94 +183,257 @@ impl JWT {
 }
 
 impl DB {
-    pub async fn new() -> Result<Self, String> {
-        let db_url = utils::get_env_var("POSTGRES_URL")?;
+    pub async fn new(config: &DatabaseConfig) -> Result<Self, String> {
+        let keyring = Keyring::new(config.encryption_keys.clone())?;
+        let pg_pool = db::get_db_connection(&config.postgres_url).await.map_err(|_| "Failed to connect to database".to_string())?;
+
+        Ok(Self::from_repository(Arc::new(PgRepository::new(pg_pool)), keyring))
+    }
+
+    pub fn from_repository(repo: Arc<dyn Repository>, keyring: Keyring) -> Self {
+        Self { repo, keyring: Arc::new(keyring) }
+    }
+
+    fn encrypt(&self, value: &str) -> Result<String, AppError> {
+        self.keyring.encrypt(value).map_err(AppError::Internal)
+    }
+
+    fn decrypt_user(&self, mut user: User) -> Result<User, AppError> {
+        user.email = self.keyring.decrypt_opt(user.email.as_deref()).map_err(AppError::Internal)?;
+        Ok(user)
+    }
+
+    fn decrypt_telegram_destination(&self, mut destination: TelegramDestination) -> Result<TelegramDestination, AppError> {
+        destination.chat_id = self.keyring.decrypt(&destination.chat_id).map_err(AppError::Internal)?;
+        Ok(destination)
+    }
+
+    fn decrypt_chat_webhook(&self, mut chat_webhook: ChatWebhook) -> Result<ChatWebhook, AppError> {
+        chat_webhook.url = self.keyring.decrypt(&chat_webhook.url).map_err(AppError::Internal)?;
+        Ok(chat_webhook)
+    }
+
+    fn decrypt_webhook(&self, mut webhook: Webhook) -> Result<Webhook, AppError> {
+        webhook.secret = self.keyring.decrypt(&webhook.secret).map_err(AppError::Internal)?;
+        webhook.previous_secret = self.keyring.decrypt_opt(webhook.previous_secret.as_deref()).map_err(AppError::Internal)?;
+        Ok(webhook)
+    }
+
+    fn decrypt_webhooks(&self, webhooks: Vec<Webhook>) -> Result<Vec<Webhook>, AppError> {
+        webhooks.into_iter().map(|webhook| self.decrypt_webhook(webhook)).collect()
+    }
+
+    /// Brings every encrypted column under the current key: plaintext left from before
+    /// encryption is encrypted, values under a previous key get their data key re-wrapped.
+    pub async fn reencrypt(&self, batch: i64) -> Result<ReencryptReport, AppError> {
+        let mut report = ReencryptReport::default();
+        let reencrypt = |value: Option<&str>| value
+            .map(|value| self.keyring.reencrypt(value))
+            .transpose()
+            .map(Option::flatten)
+            .map_err(AppError::Internal);
+
+        let mut after = Uuid::nil();
+        loop {
+            let users = self.repo.list_users_after(&after, batch).await.map_err(AppError::Db)?;
+            let Some(last) = users.last() else { break };
+            after = last.id;
+
+            for user in users {
+                let Some(email) = reencrypt(user.email.as_deref())? else { continue };
+                self.repo.set_user_email(&user.id, Some(email)).await.map_err(AppError::Db)?;
+                report.users += 1;
+            }
+        }
+
+        let mut after = Uuid::nil();
+        loop {
+            let destinations = self.repo.list_telegram_destinations_after(&after, batch).await.map_err(AppError::Db)?;
+            let Some(last) = destinations.last() else { break };
+            after = last.id;
+
+            for destination in destinations {
+                let chat_id = reencrypt(Some(&destination.chat_id))?;
+                // Chats linked before lookups existed get one too.
+                let stale_lookup = !destination.chat_lookup
+                    .as_deref()
+                    .is_some_and(|lookup| self.keyring.is_current_lookup_hash(lookup));
+                if chat_id.is_none() && !stale_lookup {
+                    continue;
+                }
+                let lookup = self.keyring.lookup_hash(&self.keyring.decrypt(&destination.chat_id).map_err(AppError::Internal)?);
+                self.repo.set_telegram_destination_chat(
+                    &destination.id,
+                    chat_id.as_deref().unwrap_or(&destination.chat_id),
+                    Some(&lookup),
+                    &destination.chat_type,
+                ).await.map_err(AppError::Db)?;
+                report.telegram_destinations += 1;
+            }
+        }
+
+        let mut after = Uuid::nil();
+        loop {
+            let chat_webhooks = self.repo.list_chat_webhooks_after(&after, batch).await.map_err(AppError::Db)?;
+            let Some(last) = chat_webhooks.last() else { break };
+            after = last.id;
+
+            for chat_webhook in chat_webhooks {
+                let Some(url) = reencrypt(Some(&chat_webhook.url))? else { continue };
+                self.repo.set_chat_webhook_url(&chat_webhook.id, &url).await.map_err(AppError::Db)?;
+                report.chat_webhooks += 1;
+            }
+        }
+
+        let mut after = Uuid::nil();
+        loop {
+            let webhooks = self.repo.list_webhooks_after(&after, batch).await.map_err(AppError::Db)?;
+            let Some(last) = webhooks.last() else { break };
+            after = last.id;
+
+            for webhook in webhooks {
+                // Empty secrets belong to legacy unsigned webhooks and stay empty.
+                let secret = reencrypt(Some(&webhook.secret).filter(|secret| !secret.is_empty()).map(String::as_str))?;
+                let previous_secret = reencrypt(webhook.previous_secret.as_deref())?;
+                if secret.is_none() && previous_secret.is_none() {
+                    continue;
+                }
+                self.repo.set_webhook_secrets(
+                    &webhook.id,
+                    secret.as_deref().unwrap_or(&webhook.secret),
+                    previous_secret.as_deref().or(webhook.previous_secret.as_deref()),
+                )
+                    .await
+                    .map_err(AppError::Db)?;
+                report.webhooks += 1;
+            }
+        }
+
+        let mut after = Uuid::nil();
+        loop {
+            let invoices = self.repo.list_invoices_with_buyer_email_after(&after, batch).await.map_err(AppError::Db)?;
+            let Some(last) = invoices.last() else { break };
+            after = last.id;
+
+            for invoice in invoices {
+                let Some(buyer_email) = reencrypt(invoice.buyer_email.as_deref())? else { continue };
+                self.repo.rewrite_invoice_buyer_email(&invoice.id, &buyer_email).await.map_err(AppError::Db)?;
+                report.invoices += 1;
+            }
+        }
 
-        let pg_pool = db::get_db_connection(&db_url).await.map_err(|_| "Failed to connect to database".to_string())?;
+        Ok(report)
+    }
 
-        Ok(Self { pg_pool })
+    pub async fn close(&self) {
+        self.repo.close().await
     }
 
     pub async fn run_migrations(&self) -> Result<(), MigrateError> {
-        sqlx::migrate!("./migrations")
-            .run(&self.pg_pool)
-            .await
+        self.repo.run_migrations().await
     }
 
     pub async fn health_check(&self) -> Result<(), AppError> {
-        sqlx::query("SELECT 1")
-            .fetch_one(&self.pg_pool)
+        self.repo.health_check()
             .await
-            .map_err(AppError::Db)?;
-        Ok(())
+            .map_err(AppError::Db)
     }
 
     pub async fn list_invoices(&self, limit: i64, offset: i64, user_id: Option<Uuid>) -> Result<Vec<Invoice>, AppError> {
-        db::invoice::list_invoices(&self.pg_pool, limit, offset, user_id)
+        self.repo.list_invoices(limit, offset, user_id)
             .await
             .map_err(AppError::Db)
     }
 
     pub async fn user_own_invoices(&self, limit: i64, offset: i64, user_id: &Uuid) -> Result<Vec<Invoice>, AppError> {
-        db::invoice::user_own_invoices(&self.pg_pool, limit, offset, user_id)
+        self.repo.user_own_invoices(limit, offset, user_id)
             .await
             .map_err(AppError::Db)
     }
 
+    /// Shorthand for tests that need no [`InvoiceOptions`] besides `is_private`.
+    #[cfg(test)]
     pub async fn create_invoice(
         &self,
         amount: BigDecimal,
         seller: &str,
-        networks: &Vec<i32>,
+        networks: &[i32],
         user_id: Option<Uuid>,
         external_id: Option<String>,
         is_private: bool,
     ) -> Result<Invoice, AppError> {
-        db::invoice::create_invoice(&self.pg_pool, amount, seller, networks, user_id, external_id, is_private)
+        let options = InvoiceOptions { is_private, ..Default::default() };
+        self.create_invoice_with_options(amount, seller, networks, user_id, external_id, options).await
+    }
+
+    /// Writes the invoice and its `options` at once; `options.buyer_email` is plaintext here.
+    pub async fn create_invoice_with_options(
+        &self,
+        amount: BigDecimal,
+        seller: &str,
+        networks: &[i32],
+        user_id: Option<Uuid>,
+        external_id: Option<String>,
+        mut options: InvoiceOptions,
+    ) -> Result<Invoice, AppError> {
+        options.buyer_email = self.keyring.encrypt_opt(options.buyer_email.as_deref()).map_err(AppError::Internal)?;
+        self.repo.create_invoice(amount, seller, networks, user_id, external_id, options)
             .await
             .map_err(AppError::Db)
     }
 
     pub async fn get_invoice(&self, id: &Uuid) -> Result<Option<Invoice>, AppError> {
-        db::invoice::get_invoice(&self.pg_pool, id)
+        self.repo.get_invoice(id)
             .await
             .map_err(AppError::Db)
     }
 
     pub async fn get_is_owner(&self, id: &Uuid, user_id: &Uuid) -> Result<bool, AppError> {
-        db::invoice::get_is_owner(&self.pg_pool, id, user_id)
+        self.repo.get_is_owner(id, user_id)
             .await
             .map_err(AppError::Db)
     }
 
-    pub async fn set_invoice_paid(&self, id: Uuid, seller: &str, amount: BigDecimal, buyer: &str, paid_at: NaiveDateTime) -> Result<Invoice, AppError> {
-        db::invoice::set_invoice_paid(&self.pg_pool, id, seller, amount, buyer, paid_at)
+    pub async fn set_invoice_paid(&self, id: Uuid, payment: &InvoicePayment) -> Result<Invoice, AppError> {
+        self.repo.set_invoice_paid(id, payment)
             .await
             .map_err(AppError::Db)
     }
 
     pub async fn get_block_number(&self, network: &str) -> Result<Option<i64>, AppError> {
-        db::blockchain::get_block_number(&self.pg_pool, network)
+        self.repo.get_block_number(network)
             .await
             .map_err(AppError::Db)
     }
 
     pub async fn set_block_number(&self, network: &str, block_number: i64) -> Result<(), AppError> {
-        db::blockchain::create_or_update_block_number(&self.pg_pool, network, block_number)
+        self.repo.set_block_number(network, block_number)
             .await
             .map_err(AppError::Db)
     }
 
     pub async fn get_user_by_id(&self, id: &Uuid) -> Result<User, AppError> {
-        db::user::get_user_by_id(&self.pg_pool, id)
+        let user = self.repo.get_user_by_id(id)
             .await
-            .map_err(AppError::Db)
+            .map_err(AppError::Db)?;
+        self.decrypt_user(user)
     }
 
     pub async fn get_or_create_user(&self, firebase_user_id: &str, email: Option<String>) -> Result<User, AppError> {
-        db::user::get_or_create_user(&self.pg_pool, firebase_user_id, email)
+        let encrypted = self.keyring.encrypt_opt(email.as_deref()).map_err(AppError::Internal)?;
+        let user = self.repo.get_or_create_user(firebase_user_id, encrypted.clone())
             .await
-            .map_err(AppError::Db)
+            .map_err(AppError::Db)?;
+        let mut user = self.decrypt_user(user)?;
+
+        if user.email != email {
+            self.repo.set_user_email(&user.id, encrypted)
+                .await
+                .map_err(AppError::Db)?;
+            user.email = email;
+        }
+
+        Ok(user)
     }
 
     pub async fn update_user(
@@ -212,29 +442,377 @@ impl DB {
         email_notification: Option<bool>,
         telegram_notification: Option<bool>,
     ) -> Result<User, AppError> {
-        db::user::update_user(&self.pg_pool, user_id, email_notification, telegram_notification)
+        let user = self.repo.update_user(user_id, email_notification, telegram_notification)
+            .await
+            .map_err(AppError::Db)?;
+        self.decrypt_user(user)
+    }
+
+    /// Links `chat_id` to the user, taking it away from whoever had it before. A chat the user
+    /// already has is returned as is. New destinations get every event type, like new webhooks.
+    /// The upsert keeps concurrent links of one chat from leaving two destinations; a link found
+    /// under an older key's lookup is deleted first, since the upsert cannot see it.
+    #[cfg(feature = "telegram")]
+    pub async fn link_telegram_chat(
+        &self,
+        user_id: &Uuid,
+        chat_id: &str,
+        chat_type: &str,
+        label: &str,
+    ) -> Result<TelegramDestination, AppError> {
+        let lookup = self.keyring.lookup_hash(chat_id);
+        if let Some(previous) = self.get_telegram_destination(chat_id).await? {
+            if &previous.user_id == user_id {
+                return Ok(previous);
+            }
+            if previous.chat_lookup.as_ref() != Some(&lookup) {
+                self.repo.delete_telegram_destination(&previous.id, &previous.user_id).await.map_err(AppError::Db)?;
+            }
+        }
+
+        let destination = NewTelegramDestination {
+            user_id: *user_id,
+            label: label.to_string(),
+            chat_id: self.encrypt(chat_id)?,
+            chat_lookup: Some(lookup),
+            chat_type: chat_type.to_string(),
+            event_types: WebhookEventType::ALL.iter().map(|event_type| event_type.as_str().to_string()).collect(),
+        };
+        let destination = self.repo.upsert_telegram_destination(&destination).await.map_err(AppError::Db)?;
+        self.decrypt_telegram_destination(destination)
+    }
+
+    /// `false` when the chat was not linked.
+    #[cfg(feature = "telegram")]
+    pub async fn unlink_telegram_chat(&self, chat_id: &str) -> Result<bool, AppError> {
+        match self.get_telegram_destination(chat_id).await? {
+            Some(destination) => self.delete_telegram_destination(&destination.id, &destination.user_id).await,
+            None => Ok(false),
+        }
+    }
+
+    /// Unlinks every chat of the user.
+    #[cfg(feature = "telegram")]
+    pub async fn unlink_telegram_chats(&self, user_id: &Uuid) -> Result<u64, AppError> {
+        self.repo.delete_telegram_destinations(user_id)
             .await
             .map_err(AppError::Db)
     }
 
-    pub async fn set_user_telegram_chat_id(
+    /// Follows a group that Telegram turned into a supergroup with a new id.
+    #[cfg(feature = "telegram")]
+    pub async fn move_telegram_chat(&self, chat_id: &str, new_chat_id: &str, chat_type: &str) -> Result<bool, AppError> {
+        let Some(destination) = self.get_telegram_destination(chat_id).await? else {
+            return Ok(false);
+        };
+        self.repo.set_telegram_destination_chat(
+            &destination.id,
+            &self.encrypt(new_chat_id)?,
+            Some(&self.keyring.lookup_hash(new_chat_id)),
+            chat_type,
+        )
+            .await
+            .map_err(AppError::Db)?;
+        Ok(true)
+    }
+
+    #[cfg(feature = "telegram")]
+    pub async fn get_telegram_destination(&self, chat_id: &str) -> Result<Option<TelegramDestination>, AppError> {
+        self.repo.get_telegram_destination_by_chat_lookup(&self.keyring.lookup_hashes(chat_id))
+            .await
+            .map_err(AppError::Db)?
+            .map(|destination| self.decrypt_telegram_destination(destination))
+            .transpose()
+    }
+
+    /// The owner of the chat, in whichever role the chat is linked.
+    #[cfg(feature = "telegram")]
+    pub async fn get_user_by_telegram_chat_id(&self, chat_id: &str) -> Result<Option<User>, AppError> {
+        match self.get_telegram_destination(chat_id).await? {
+            Some(destination) => self.get_user_by_id(&destination.user_id).await.map(Some),
+            None => Ok(None),
+        }
+    }
+
+    pub async fn list_telegram_destinations(&self, user_id: &Uuid) -> Result<Vec<TelegramDestination>, AppError> {
+        self.repo.list_telegram_destinations(user_id)
+            .await
+            .map_err(AppError::Db)?
+            .into_iter()
+            .map(|destination| self.decrypt_telegram_destination(destination))
+            .collect()
+    }
+
+    #[cfg(feature = "telegram")]
+    pub async fn update_telegram_destination(
         &self,
+        id: &Uuid,
         user_id: &Uuid,
-        telegram_chat_id: Option<String>,
-    ) -> Result<(), AppError> {
-        db::user::set_user_telegram_chat_id(&self.pg_pool, user_id, telegram_chat_id)
+        label: Option<&str>,
+        event_types: Option<&[String]>,
+        min_amount: Option<Option<BigDecimal>>,
+    ) -> Result<Option<TelegramDestination>, AppError> {
+        self.repo.update_telegram_destination(id, user_id, label, event_types, min_amount)
+            .await
+            .map_err(AppError::Db)?
+            .map(|destination| self.decrypt_telegram_destination(destination))
+            .transpose()
+    }
+
+    #[cfg(feature = "telegram")]
+    pub async fn delete_telegram_destination(&self, id: &Uuid, user_id: &Uuid) -> Result<bool, AppError> {
+        self.repo.delete_telegram_destination(id, user_id)
+            .await
+            .map_err(AppError::Db)
+    }
+
+    pub async fn create_chat_webhook(
+        &self,
+        user_id: &Uuid,
+        kind: &str,
+        label: &str,
+        url: &str,
+        event_types: &[String],
+    ) -> Result<ChatWebhook, AppError> {
+        let chat_webhook = NewChatWebhook {
+            user_id: *user_id,
+            kind: kind.to_string(),
+            label: label.to_string(),
+            url: self.encrypt(url)?,
+            event_types: event_types.to_vec(),
+        };
+        let chat_webhook = self.repo.create_chat_webhook(&chat_webhook).await.map_err(AppError::Db)?;
+        self.decrypt_chat_webhook(chat_webhook)
+    }
+
+    pub async fn get_chat_webhook(&self, id: &Uuid, user_id: &Uuid) -> Result<Option<ChatWebhook>, AppError> {
+        self.repo.get_chat_webhook(id, user_id)
+            .await
+            .map_err(AppError::Db)?
+            .map(|chat_webhook| self.decrypt_chat_webhook(chat_webhook))
+            .transpose()
+    }
+
+    pub async fn list_chat_webhooks(&self, user_id: &Uuid) -> Result<Vec<ChatWebhook>, AppError> {
+        self.repo.list_chat_webhooks(user_id)
+            .await
+            .map_err(AppError::Db)?
+            .into_iter()
+            .map(|chat_webhook| self.decrypt_chat_webhook(chat_webhook))
+            .collect()
+    }
+
+    pub async fn count_chat_webhooks(&self, user_id: &Uuid) -> Result<usize, AppError> {
+        let count = self.repo.count_chat_webhooks(user_id)
+            .await
+            .map_err(AppError::Db)?;
+        Ok(count as usize)
+    }
+
+    pub async fn update_chat_webhook(
+        &self,
+        id: &Uuid,
+        user_id: &Uuid,
+        label: Option<&str>,
+        url: Option<&str>,
+        enabled: Option<bool>,
+        event_types: Option<&[String]>,
+    ) -> Result<Option<ChatWebhook>, AppError> {
+        let url = url.map(|url| self.encrypt(url)).transpose()?;
+        self.repo.update_chat_webhook(id, user_id, label, url.as_deref(), enabled, event_types)
+            .await
+            .map_err(AppError::Db)?
+            .map(|chat_webhook| self.decrypt_chat_webhook(chat_webhook))
+            .transpose()
+    }
+
+    pub async fn delete_chat_webhook(&self, id: &Uuid, user_id: &Uuid) -> Result<bool, AppError> {
+        self.repo.delete_chat_webhook(id, user_id)
+            .await
+            .map_err(AppError::Db)
+    }
+
+    pub async fn set_user_locale(&self, user_id: &Uuid, locale: &str) -> Result<(), AppError> {
+        self.repo.set_user_locale(user_id, locale)
+            .await
+            .map_err(AppError::Db)
+    }
+
+    pub async fn set_user_buyer_receipts(&self, user_id: &Uuid, buyer_receipts: bool) -> Result<(), AppError> {
+        self.repo.set_user_buyer_receipts(user_id, buyer_receipts)
+            .await
+            .map_err(AppError::Db)
+    }
+
+    pub async fn list_notification_templates(&self, user_id: &Uuid) -> Result<Vec<NotificationTemplate>, AppError> {
+        self.repo.list_notification_templates(user_id)
+            .await
+            .map_err(AppError::Db)
+    }
+
+    pub async fn get_notification_template(&self, user_id: &Uuid, kind: &str, locale: &str) -> Result<Option<NotificationTemplate>, AppError> {
+        self.repo.get_notification_template(user_id, kind, locale)
+            .await
+            .map_err(AppError::Db)
+    }
+
+    pub async fn upsert_notification_template(&self, template: &NotificationTemplate) -> Result<NotificationTemplate, AppError> {
+        self.repo.upsert_notification_template(template)
+            .await
+            .map_err(AppError::Db)
+    }
+
+    pub async fn delete_notification_template(&self, user_id: &Uuid, kind: &str, locale: &str) -> Result<bool, AppError> {
+        self.repo.delete_notification_template(user_id, kind, locale)
+            .await
+            .map_err(AppError::Db)
+    }
+
+    pub async fn list_notification_preferences(&self, user_id: &Uuid) -> Result<Vec<NotificationPreference>, AppError> {
+        self.repo.list_notification_preferences(user_id)
+            .await
+            .map_err(AppError::Db)
+    }
+
+    pub async fn upsert_notification_preference(&self, preference: &NotificationPreference) -> Result<NotificationPreference, AppError> {
+        self.repo.upsert_notification_preference(preference)
+            .await
+            .map_err(AppError::Db)
+    }
+
+    pub async fn delete_notification_preference(&self, user_id: &Uuid, channel: &str, event_type: &str) -> Result<bool, AppError> {
+        self.repo.delete_notification_preference(user_id, channel, event_type)
+            .await
+            .map_err(AppError::Db)
+    }
+
+    pub async fn list_notification_digests(&self, user_id: &Uuid) -> Result<Vec<NotificationDigest>, AppError> {
+        self.repo.list_notification_digests(user_id)
+            .await
+            .map_err(AppError::Db)
+    }
+
+    pub async fn upsert_notification_digest(&self, user_id: &Uuid, channel: &str, frequency: &str) -> Result<NotificationDigest, AppError> {
+        self.repo.upsert_notification_digest(user_id, channel, frequency)
+            .await
+            .map_err(AppError::Db)
+    }
+
+    pub async fn list_pending_notification_digests(&self) -> Result<Vec<NotificationDigest>, AppError> {
+        self.repo.list_pending_notification_digests()
+            .await
+            .map_err(AppError::Db)
+    }
+
+    pub async fn claim_notification_digest(&self, user_id: &Uuid, channel: &str, period_end: &NaiveDateTime) -> Result<bool, AppError> {
+        self.repo.claim_notification_digest(user_id, channel, period_end)
+            .await
+            .map_err(AppError::Db)
+    }
+
+    #[cfg_attr(not(any(feature = "mailer", feature = "telegram")), allow(dead_code))]
+    pub async fn create_notification_digest_item(&self, item: &NewNotificationDigestItem) -> Result<NotificationDigestItem, AppError> {
+        self.repo.create_notification_digest_item(item)
+            .await
+            .map_err(AppError::Db)
+    }
+
+    pub async fn list_notification_digest_items(&self, user_id: &Uuid, channel: &str, before: &NaiveDateTime) -> Result<Vec<NotificationDigestItem>, AppError> {
+        self.repo.list_notification_digest_items(user_id, channel, before)
+            .await
+            .map_err(AppError::Db)
+    }
+
+    pub async fn delete_notification_digest_items(&self, ids: &[Uuid]) -> Result<(), AppError> {
+        self.repo.delete_notification_digest_items(ids)
+            .await
+            .map_err(AppError::Db)
+    }
+
+    pub async fn create_notification_log(&self, log: &NewNotificationLog) -> Result<NotificationLog, AppError> {
+        self.repo.create_notification_log(log)
+            .await
+            .map_err(AppError::Db)
+    }
+
+    pub async fn list_notification_logs(&self, user_id: &Uuid, filter: &NotificationLogFilter, limit: i64, offset: i64) -> Result<Vec<NotificationLog>, AppError> {
+        self.repo.list_notification_logs(user_id, filter, limit, offset)
+            .await
+            .map_err(AppError::Db)
+    }
+
+    pub async fn get_notification_log(&self, id: &Uuid, user_id: &Uuid) -> Result<Option<NotificationLog>, AppError> {
+        self.repo.get_notification_log(id, user_id)
+            .await
+            .map_err(AppError::Db)
+    }
+
+    pub async fn resend_notification(&self, id: &Uuid, user_id: &Uuid) -> Result<bool, AppError> {
+        self.repo.resend_notification(id, user_id)
+            .await
+            .map_err(AppError::Db)
+    }
+
+    pub async fn claim_pending_notifications(&self, limit: i64, lease: Duration) -> Result<Vec<NotificationLog>, AppError> {
+        self.repo.claim_pending_notifications(limit, lease.as_secs_f64())
+            .await
+            .map_err(AppError::Db)
+    }
+
+    pub async fn record_notification_attempt(&self, id: &Uuid, error: Option<&str>) -> Result<(), AppError> {
+        self.repo.record_notification_attempt(id, error)
+            .await
+            .map_err(AppError::Db)
+    }
+
+    /// `None` unless the invoice is unpaid and, without `replace`, has no buyer email yet.
+    pub async fn set_invoice_buyer_email(&self, id: &Uuid, buyer_email: &str, replace: bool) -> Result<Option<Invoice>, AppError> {
+        let encrypted = self.encrypt(buyer_email)?;
+        self.repo.set_invoice_buyer_email(id, &encrypted, replace)
+            .await
+            .map_err(AppError::Db)
+    }
+
+    /// The decrypted [`Invoice::buyer_email`].
+    #[cfg_attr(not(feature = "mailer"), allow(dead_code))]
+    pub fn get_buyer_email(&self, invoice: &Invoice) -> Result<Option<String>, AppError> {
+        self.keyring.decrypt_opt(invoice.buyer_email.as_deref()).map_err(AppError::Internal)
+    }
+
+    /// Moves the invoice to `to` from any status allowed by [`InvoiceStatus::can_become`];
+    /// `None` when it is missing or the transition is not allowed.
+    pub async fn transition_invoice(&self, id: &Uuid, to: InvoiceStatus) -> Result<Option<Invoice>, AppError> {
+        self.repo.set_invoice_status(id, &to.sources(), to)
+            .await
+            .map_err(AppError::Db)
+    }
+
+    pub async fn expire_invoices(&self, now: NaiveDateTime, confirming_before: NaiveDateTime) -> Result<Vec<Invoice>, AppError> {
+        self.repo.expire_invoices(now, confirming_before)
+            .await
+            .map_err(AppError::Db)
+    }
+
+    pub async fn reopen_stale_invoices(&self, now: NaiveDateTime, confirming_before: NaiveDateTime) -> Result<Vec<Invoice>, AppError> {
+        self.repo.reopen_stale_invoices(now, confirming_before)
+            .await
+            .map_err(AppError::Db)
+    }
+
+    pub async fn update_invoice(&self, id: &Uuid, update: &InvoiceUpdate, changes: Value) -> Result<Option<Invoice>, AppError> {
+        self.repo.update_invoice(id, update, changes)
             .await
             .map_err(AppError::Db)
     }
 
-    pub async fn delete_own_invoice(&self, id: &Uuid, user_id: &Uuid) -> Result<bool, AppError> {
-        db::invoice::delete_own_invoice(&self.pg_pool, id, user_id)
+    pub async fn list_invoice_events(&self, invoice_id: &Uuid) -> Result<Vec<InvoiceEvent>, AppError> {
+        self.repo.list_invoice_events(invoice_id)
             .await
             .map_err(AppError::Db)
     }
 
     pub async fn delete_api_key(&self, id: &Uuid, user_id: &Uuid) -> Result<bool, AppError> {
-        db::api_key::delete_api_key(&self.pg_pool, id, user_id)
+        self.repo.delete_api_key(id, user_id)
             .await
             .map_err(AppError::Db)
     }
@@ -244,13 +822,13 @@ impl DB {
         user_id: &Uuid,
         hashed_api_key: &str,
     ) -> Result<ApiKey, AppError> {
-        db::api_key::create_api_key(&self.pg_pool, user_id, hashed_api_key)
+        self.repo.create_api_key(user_id, hashed_api_key)
             .await
             .map_err(AppError::Db)
     }
 
     pub async fn get_api_key(&self, id: &Uuid, user_id: &Uuid) -> Result<Option<ApiKey>, AppError> {
-        db::api_key::get_api_key(&self.pg_pool, id, user_id)
+        self.repo.get_api_key(id, user_id)
             .await
             .map_err(AppError::Db)
     }
@@ -259,7 +837,7 @@ impl DB {
         &self,
         hashed_api_key: &str,
     ) -> Result<Option<ApiKey>, AppError> {
-        db::api_key::get_api_key_by_api_key(&self.pg_pool, hashed_api_key)
+        self.repo.get_api_key_by_api_key(hashed_api_key)
             .await
             .map_err(AppError::Db)
     }
@@ -268,132 +846,254 @@ impl DB {
         &self,
         user_id: &Uuid,
     ) -> Result<Vec<ApiKey>, AppError> {
-        db::api_key::list_api_key(&self.pg_pool, user_id)
+        self.repo.list_api_key(user_id)
             .await
             .map_err(AppError::Db)
     }
 
     pub async fn update_api_key_last_used(&self, id: &Uuid) -> Result<(), AppError> {
-        db::api_key::update_api_key_last_used(&self.pg_pool, id)
+        self.repo.update_api_key_last_used(id)
             .await
             .map_err(AppError::Db)
     }
 
     pub async fn count_api_keys_by_user_id(&self, user_id: &Uuid) -> Result<usize, AppError> {
-        let count = db::api_key::count_api_keys_by_user_id(&self.pg_pool, user_id)
+        let count = self.repo.count_api_keys(user_id)
             .await
             .map_err(AppError::Db)?;
-        Ok(count.unwrap_or(0) as usize)
+        Ok(count as usize)
     }
 
     pub async fn create_callback_url(&self, url: &str, user_id: &Uuid) -> Result<CallbackUrl, AppError> {
-        db::callback_url::create_callback_url(&self.pg_pool, url, user_id)
+        self.repo.create_callback_url(url, user_id)
             .await
             .map_err(AppError::Db)
     }
 
     pub async fn list_callback_urls(&self, user_id: &Uuid) -> Result<Vec<CallbackUrl>, AppError> {
-        db::callback_url::list_callback_urls_by_user_id(&self.pg_pool, user_id)
+        self.repo.list_callback_urls(user_id)
             .await
             .map_err(AppError::Db)
     }
 
     pub async fn delete_callback_url(&self, callback_url_id: &Uuid, user_id: &Uuid) -> Result<bool, AppError> {
-        db::callback_url::delete_callback_url_by_id_and_user_id(&self.pg_pool, callback_url_id, user_id)
+        self.repo.delete_callback_url(callback_url_id, user_id)
             .await
             .map_err(AppError::Db)
     }
 
     pub async fn count_callback_urls(&self, user_id: &Uuid) -> Result<usize, AppError> {
-        let count = db::callback_url::count_callback_urls_by_user_id(&self.pg_pool, user_id)
+        let count = self.repo.count_callback_urls(user_id)
             .await
             .map_err(AppError::Db)?;
-        Ok(count.unwrap_or(0) as usize)
+        Ok(count as usize)
     }
 
     pub async fn exists_callback_url(&self, url: &str, user_id: &Uuid) -> Result<bool, AppError> {
-        db::callback_url::exists_callback_url(&self.pg_pool, url, user_id)
+        self.repo.exists_callback_url(url, user_id)
             .await
             .map_err(AppError::Db)
     }
 
     pub async fn get_payment(&self, id: &Uuid) -> Result<Option<Payment>, AppError> {
-        billing::get_payment(&self.pg_pool, id)
+        self.repo.get_payment(id)
             .await
             .map_err(AppError::Db)
     }
 
     pub async fn user_list_payment(&self, user_id: &Uuid, limit: i64, offset: i64) -> Result<Vec<Payment>, AppError> {
-        billing::user_list_payment(&self.pg_pool, user_id, limit, offset)
+        self.repo.user_list_payment(user_id, limit, offset)
             .await
             .map_err(AppError::Db)
     }
 
     pub async fn list_payment(&self, payment_type: &str, limit: i64, offset: i64) -> Result<Vec<Payment>, AppError> {
-        billing::list_payment(&self.pg_pool, payment_type, limit, offset)
+        self.repo.list_payment(payment_type, limit, offset)
             .await
             .map_err(AppError::Db)
     }
 
     pub async fn create_payment(&self, id: &Uuid, user_id: Option<Uuid>, data: &Value) -> Result<Payment, AppError> {
-        billing::create_payment(&self.pg_pool, id, user_id, data)
+        self.repo.create_payment(id, user_id, data)
             .await
             .map_err(AppError::Db)
     }
 
     pub async fn list_user_subscriptions(&self, user_id: &Uuid) -> Result<Vec<Subscription>, AppError> {
-        billing::list_subscriptions(&self.pg_pool, user_id)
+        self.repo.list_user_subscriptions(user_id)
             .await
             .map_err(AppError::Db)
     }
 
     pub async fn get_user_active_subscription(&self, user_id: &Uuid, target: &str) -> Result<Option<Subscription>, AppError> {
-        billing::get_active_subscription(&self.pg_pool, user_id, target)
+        self.repo.get_user_active_subscription(user_id, target)
             .await
             .map_err(AppError::Db)
     }
 
     pub async fn create_or_update_subscription(&self, user_id: &Uuid, target: &str, data: Option<Value>, until: NaiveDateTime) -> Result<(), AppError> {
-        billing::create_or_update_subscription(&self.pg_pool, user_id, target, data, until)
+        self.repo.create_or_update_subscription(user_id, target, data, until)
+            .await
+            .map_err(AppError::Db)
+    }
+
+    pub async fn claim_expiring_subscriptions(&self, now: &NaiveDateTime, before: &NaiveDateTime) -> Result<Vec<Subscription>, AppError> {
+        self.repo.claim_expiring_subscriptions(now, before)
+            .await
+            .map_err(AppError::Db)
+    }
+
+    pub async fn release_subscription_expiry(&self, subscription: &Subscription) -> Result<(), AppError> {
+        self.repo.release_subscription_expiry(&subscription.user_id, &subscription.target, &subscription.until)
             .await
             .map_err(AppError::Db)
     }
 
     pub async fn set_payment_paid(&self, id: &Uuid) -> Result<(), AppError> {
-        billing::set_payment_paid(&self.pg_pool, id)
+        self.repo.set_payment_paid(id)
             .await
             .map_err(AppError::Db)
     }
 
     pub async fn sync_payment_paid_at(&self, id: &Uuid, paid_at: &chrono::NaiveDateTime) -> Result<(), AppError> {
-        billing::sync_payment_paid_at(&self.pg_pool, id, paid_at)
+        self.repo.sync_payment_paid_at(id, paid_at)
             .await
             .map_err(AppError::Db)
     }
 
-    pub async fn create_webhook(&self, url: &str, secret: &str, user_id: &Uuid) -> Result<Webhook, AppError> {
-        db::webhook::create_webhook(&self.pg_pool, url, secret, user_id)
+    pub async fn create_webhook(
+        &self,
+        url: &str,
+        secret: &str,
+        user_id: &Uuid,
+        event_types: &[String],
+        api_version: &str,
+    ) -> Result<Webhook, AppError> {
+        let webhook = self.repo.create_webhook(url, &self.encrypt(secret)?, user_id, event_types, api_version)
             .await
-            .map_err(AppError::Db)
+            .map_err(AppError::Db)?;
+        self.decrypt_webhook(webhook)
+    }
+
+    pub async fn get_webhook(&self, webhook_id: &Uuid, user_id: &Uuid) -> Result<Option<Webhook>, AppError> {
+        let webhook = self.repo.get_webhook(webhook_id, user_id)
+            .await
+            .map_err(AppError::Db)?;
+        webhook.map(|webhook| self.decrypt_webhook(webhook)).transpose()
     }
 
     pub async fn list_webhooks(&self, user_id: &Uuid) -> Result<Vec<Webhook>, AppError> {
-        db::webhook::list_webhooks_by_user_id(&self.pg_pool, user_id)
+        let webhooks = self.repo.list_webhooks(user_id)
             .await
-            .map_err(AppError::Db)
+            .map_err(AppError::Db)?;
+        self.decrypt_webhooks(webhooks)
+    }
+
+    pub async fn list_webhooks_for_event(&self, user_id: &Uuid, event_type: &str) -> Result<Vec<Webhook>, AppError> {
+        let webhooks = self.repo.list_webhooks_for_event(user_id, event_type)
+            .await
+            .map_err(AppError::Db)?;
+        self.decrypt_webhooks(webhooks)
+    }
+
+    pub async fn update_webhook(
+        &self,
+        webhook_id: &Uuid,
+        user_id: &Uuid,
+        url: Option<&str>,
+        enabled: Option<bool>,
+        event_types: Option<&[String]>,
+    ) -> Result<Option<Webhook>, AppError> {
+        let webhook = self.repo.update_webhook(webhook_id, user_id, url, enabled, event_types)
+            .await
+            .map_err(AppError::Db)?;
+        webhook.map(|webhook| self.decrypt_webhook(webhook)).transpose()
+    }
+
+    pub async fn rotate_webhook_secret(&self, webhook_id: &Uuid, user_id: &Uuid, secret: &str, grace: Duration) -> Result<Option<Webhook>, AppError> {
+        let webhook = self.repo.rotate_webhook_secret(webhook_id, user_id, &self.encrypt(secret)?, grace.as_secs_f64())
+            .await
+            .map_err(AppError::Db)?;
+        webhook.map(|webhook| self.decrypt_webhook(webhook)).transpose()
     }
 
     pub async fn delete_webhook(&self, webhook_id: &Uuid, user_id: &Uuid) -> Result<bool, AppError> {
-        db::webhook::delete_webhook_by_id_and_user_id(&self.pg_pool, webhook_id, user_id)
+        self.repo.delete_webhook(webhook_id, user_id)
             .await
             .map_err(AppError::Db)
     }
 
     pub async fn count_webhooks(&self, user_id: &Uuid) -> Result<usize, AppError> {
-        let count = db::webhook::count_webhooks_by_user_id(&self.pg_pool, user_id)
+        let count = self.repo.count_webhooks(user_id)
+            .await
+            .map_err(AppError::Db)?;
+        Ok(count as usize)
+    }
+
+    pub async fn enqueue_webhook_delivery(&self, webhook_id: &Uuid, payload: &Value) -> Result<WebhookDelivery, AppError> {
+        self.repo.enqueue_webhook_delivery(webhook_id, payload)
+            .await
+            .map_err(AppError::Db)
+    }
+
+    /// A delivery whose secret cannot be decrypted is marked failed, with the error recorded as
+    /// its attempt, instead of holding up the rest of the batch.
+    pub async fn claim_due_webhook_deliveries(&self, limit: i64, lease: Duration) -> Result<Vec<DueWebhookDelivery>, AppError> {
+        let deliveries = self.repo.claim_due_webhook_deliveries(limit, lease.as_secs_f64())
             .await
             .map_err(AppError::Db)?;
-        Ok(count.unwrap_or(0) as usize)
+
+        let mut decrypted = Vec::with_capacity(deliveries.len());
+        for mut delivery in deliveries {
+            let secrets = self.keyring.decrypt(&delivery.secret)
+                .and_then(|secret| Ok((secret, self.keyring.decrypt_opt(delivery.previous_secret.as_deref())?)));
+            match secrets {
+                Ok((secret, previous_secret)) => {
+                    (delivery.secret, delivery.previous_secret) = (secret, previous_secret);
+                    decrypted.push(delivery);
+                }
+                Err(err) => {
+                    error!("Failed to decrypt the secret of webhook {} for delivery {}: {err}", delivery.webhook_id, delivery.id);
+                    let attempt = NewWebhookDeliveryAttempt {
+                        error: Some(format!("Failed to decrypt the webhook secret: {err}")),
+                        ..Default::default()
+                    };
+                    self.record_webhook_delivery_attempt(&delivery.id, &attempt, WebhookDeliveryStatus::Failed, Duration::ZERO).await?;
+                }
+            }
+        }
+
+        Ok(decrypted)
+    }
+
+    pub async fn record_webhook_delivery_attempt(
+        &self,
+        delivery_id: &Uuid,
+        attempt: &NewWebhookDeliveryAttempt,
+        status: WebhookDeliveryStatus,
+        retry_in: Duration,
+    ) -> Result<(), AppError> {
+        self.repo.record_webhook_delivery_attempt(delivery_id, attempt, status, retry_in.as_secs_f64())
+            .await
+            .map_err(AppError::Db)
+    }
+
+    pub async fn list_webhook_deliveries(&self, webhook_id: &Uuid, limit: i64, offset: i64) -> Result<Vec<WebhookDelivery>, AppError> {
+        self.repo.list_webhook_deliveries(webhook_id, limit, offset)
+            .await
+            .map_err(AppError::Db)
+    }
+
+    pub async fn list_webhook_delivery_attempts(&self, delivery_ids: &[Uuid]) -> Result<Vec<WebhookDeliveryAttempt>, AppError> {
+        self.repo.list_webhook_delivery_attempts(delivery_ids)
+            .await
+            .map_err(AppError::Db)
+    }
+
+    pub async fn redeliver_webhook_delivery(&self, delivery_id: &Uuid, webhook_id: &Uuid) -> Result<bool, AppError> {
+        self.repo.redeliver_webhook_delivery(delivery_id, webhook_id)
+            .await
+            .map_err(AppError::Db)
     }
 
     pub async fn invoice_stats_by_day(
@@ -401,7 +1101,7 @@ impl DB {
         user_id: &Uuid,
         since: NaiveDateTime,
     ) -> Result<Vec<db::analytics::InvoicePeriodStats>, AppError> {
-        db::analytics::invoice_stats_by_day(&self.pg_pool, user_id, since)
+        self.repo.invoice_stats_by_day(user_id, since)
             .await
             .map_err(AppError::Db)
     }
@@ -411,14 +1111,19 @@ impl DB {
         user_id: &Uuid,
         since: NaiveDateTime,
     ) -> Result<db::analytics::InvoiceSummary, AppError> {
-        db::analytics::invoice_summary(&self.pg_pool, user_id, since)
+        self.repo.invoice_summary(user_id, since)
             .await
             .map_err(AppError::Db)
     }
 
     #[cfg(test)]
-    pub fn from_pool(pool: PgPool) -> Self {
-        Self { pg_pool: pool }
+    pub fn from_pool(pool: sqlx::PgPool) -> Self {
+        Self::from_repository(Arc::new(PgRepository::new(pool)), Keyring::for_tests())
+    }
+
+    #[cfg(test)]
+    pub fn in_memory() -> Self {
+        Self::from_repository(Arc::new(db::memory::MemoryRepository::default()), Keyring::for_tests())
     }
 }
 
@@ -426,11 +1131,12 @@ impl DB {
 mod tests {
     use super::*;
     use bigdecimal::BigDecimal;
+    use chrono::Timelike;
 
     async fn setup_test_db() -> DB {
         let url = std::env::var("DATABASE_URL")
             .expect("DATABASE_URL must be set for integration tests");
-        let pool = PgPool::connect(&url).await
+        let pool = sqlx::PgPool::connect(&url).await
             .expect("Failed to connect to test database");
         let db = DB::from_pool(pool);
         db.run_migrations().await
@@ -444,18 +1150,139 @@ mod tests {
         let db = setup_test_db().await;
 
         let invoice = db.create_invoice(
-            BigDecimal::from(10), "0xseller", &vec![10], None, None, false,
+            BigDecimal::from(10), "0xseller", &[10], None, None, false,
         ).await.unwrap();
 
         let fetched = db.get_invoice(&invoice.id).await.unwrap().unwrap();
         assert_eq!(fetched.id, invoice.id);
         assert!(fetched.paid_at.is_none());
 
-        let paid = db.set_invoice_paid(
-            invoice.id, "0xseller", BigDecimal::from(10), "0xbuyer",
-            chrono::Utc::now().naive_utc(),
-        ).await.unwrap();
+        let payment = InvoicePayment {
+            seller: "0xseller".to_string(),
+            amount: BigDecimal::from(10),
+            buyer: "0xbuyer".to_string(),
+            paid_at: chrono::Utc::now().naive_utc(),
+            network: 10,
+            tx_hash: "0xtx".to_string(),
+        };
+        let paid = db.set_invoice_paid(invoice.id, &payment).await.unwrap();
         assert!(paid.paid_at.is_some());
+        assert_eq!(paid.tx_hash.as_deref(), Some("0xtx"));
+    }
+
+    #[ignore]
+    #[tokio::test]
+    async fn test_invoice_buyer_receipt() {
+        let db = setup_test_db().await;
+        let uid = format!("test-{}", Uuid::new_v4());
+        let user = db.get_or_create_user(&uid, None).await.unwrap();
+        assert!(!user.buyer_receipts);
+        db.set_user_buyer_receipts(&user.id, true).await.unwrap();
+        assert!(db.get_user_by_id(&user.id).await.unwrap().buyer_receipts);
+
+        let invoice = db.create_invoice(BigDecimal::from(10), "0xseller", &[10], Some(user.id), None, false).await.unwrap();
+        assert!(!invoice.send_receipt && invoice.buyer_email.is_none());
+
+        let expires_at = chrono::Utc::now().naive_utc().with_nanosecond(0).unwrap() + chrono::Duration::hours(1);
+        let options = InvoiceOptions {
+            send_receipt: true,
+            buyer_email: Some("first@example.com".to_string()),
+            expires_at: Some(expires_at),
+            metadata: Some(serde_json::json!({"sku": "A1"})),
+            ..Default::default()
+        };
+        let invoice = db.create_invoice_with_options(BigDecimal::from(10), "0xseller", &[10], Some(user.id), None, options).await.unwrap();
+        assert!(invoice.send_receipt && invoice.buyer_email.as_deref().unwrap().starts_with("enc1:"));
+        assert_eq!(db.get_buyer_email(&invoice).unwrap().as_deref(), Some("first@example.com"));
+        assert_eq!((invoice.expires_at, invoice.metadata), (Some(expires_at), Some(serde_json::json!({"sku": "A1"}))));
+
+        assert!(db.set_invoice_buyer_email(&invoice.id, "buyer@example.com", false).await.unwrap().is_none());
+        let invoice = db.set_invoice_buyer_email(&invoice.id, "buyer@example.com", true).await.unwrap().unwrap();
+        assert!(invoice.buyer_email.as_deref().unwrap().starts_with("enc1:"));
+        assert_eq!(db.get_buyer_email(&invoice).unwrap().as_deref(), Some("buyer@example.com"));
+        let listed = db.repo.list_invoices_with_buyer_email_after(&Uuid::nil(), 1000).await.unwrap();
+        assert!(listed.iter().any(|listed| listed.id == invoice.id));
+
+        let payment = InvoicePayment {
+            seller: "0xseller".to_string(),
+            amount: BigDecimal::from(10),
+            buyer: "0xbuyer".to_string(),
+            paid_at: chrono::Utc::now().naive_utc(),
+            network: 10,
+            tx_hash: "0xtx".to_string(),
+        };
+        assert!(db.set_invoice_paid(invoice.id, &payment).await.unwrap().send_receipt);
+    }
+
+    #[ignore]
+    #[tokio::test]
+    async fn test_invoice_status_life_cycle() {
+        let db = setup_test_db().await;
+        let now = chrono::Utc::now().naive_utc();
+        let past = now - chrono::Duration::minutes(1);
+        let options = InvoiceOptions { expires_at: Some(past), ..Default::default() };
+        let invoice = db.create_invoice_with_options(BigDecimal::from(10), "0xseller", &[10], None, None, options).await.unwrap();
+        assert_eq!(invoice.status, InvoiceStatus::Open);
+
+        let invoice = db.transition_invoice(&invoice.id, InvoiceStatus::Confirming).await.unwrap().unwrap();
+        assert_eq!(invoice.status, InvoiceStatus::Confirming);
+        // Still within the confirming grace period.
+        let expired = db.expire_invoices(now, now - chrono::Duration::minutes(30)).await.unwrap();
+        assert!(expired.iter().all(|expired| expired.id != invoice.id));
+        let expired = db.expire_invoices(now, now + chrono::Duration::minutes(1)).await.unwrap();
+        assert!(expired.iter().any(|expired| expired.id == invoice.id && expired.status == InvoiceStatus::Expired));
+
+        let payment = InvoicePayment {
+            seller: "0xseller".to_string(),
+            amount: BigDecimal::from(10),
+            buyer: "0xbuyer".to_string(),
+            paid_at: now,
+            network: 10,
+            tx_hash: "0xtx".to_string(),
+        };
+        assert!(matches!(db.set_invoice_paid(invoice.id, &payment).await, Err(AppError::Db(sqlx::Error::RowNotFound))));
+        assert!(db.transition_invoice(&invoice.id, InvoiceStatus::Void).await.unwrap().is_none());
+
+        let timeline = db.list_invoice_events(&invoice.id).await.unwrap().into_iter().map(|event| event.kind).collect::<Vec<_>>();
+        assert_eq!(timeline, ["created", "confirming", "expired"]);
+
+        let other = db.create_invoice(BigDecimal::from(10), "0xseller", &[10], None, None, false).await.unwrap();
+        let update = InvoiceUpdate { amount: Some(BigDecimal::from(12)), external_id: Some(Some("order-1".to_string())), ..Default::default() };
+        let changes = serde_json::json!({"amount": {"from": "10", "to": "12"}});
+        let updated = db.update_invoice(&other.id, &update, changes.clone()).await.unwrap().unwrap();
+        assert_eq!((updated.amount, updated.external_id.as_deref()), (BigDecimal::from(12), Some("order-1")));
+        assert_eq!(db.transition_invoice(&other.id, InvoiceStatus::Void).await.unwrap().unwrap().status, InvoiceStatus::Void);
+        assert!(db.update_invoice(&other.id, &update, changes.clone()).await.unwrap().is_none());
+        let timeline = db.list_invoice_events(&other.id).await.unwrap();
+        assert_eq!(timeline.iter().map(|event| event.kind.as_str()).collect::<Vec<_>>(), ["created", "updated", "void"]);
+        assert_eq!(timeline[1].changes, Some(changes));
+    }
+
+    #[ignore]
+    #[tokio::test]
+    async fn test_void_and_expired_invoices_in_lists_and_analytics() {
+        let db = setup_test_db().await;
+        let user = db.get_or_create_user(&format!("test-{}", Uuid::new_v4()), None).await.unwrap();
+        let since = chrono::Utc::now().naive_utc() - chrono::Duration::days(1);
+        let mut ids = vec![];
+        for (amount, status) in [(5, None), (7, Some(InvoiceStatus::Expired)), (100, Some(InvoiceStatus::Void))] {
+            let invoice = db.create_invoice(BigDecimal::from(amount), "0xseller", &[10], Some(user.id), None, false).await.unwrap();
+            if let Some(status) = status {
+                db.transition_invoice(&invoice.id, status).await.unwrap().unwrap();
+            }
+            ids.push(invoice.id);
+        }
+
+        let listed = db.user_own_invoices(10, 0, &user.id).await.unwrap().into_iter().map(|invoice| invoice.id).collect::<Vec<_>>();
+        assert_eq!(listed, [ids[1], ids[0]]);
+        let listed = db.list_invoices(100, 0, Some(user.id)).await.unwrap();
+        assert!(listed.iter().all(|invoice| invoice.id != ids[2]));
+
+        let summary = db.invoice_summary(&user.id, since).await.unwrap();
+        assert_eq!((summary.total_invoices, summary.total_amount), (1, BigDecimal::from(5)));
+        assert_eq!((summary.expired_invoices, summary.void_invoices), (1, 1));
+        let by_day = db.invoice_stats_by_day(&user.id, since).await.unwrap();
+        assert_eq!((by_day[0].total_invoices, by_day[0].expired_invoices, by_day[0].void_invoices), (1, 1, 1));
     }
 
     #[ignore]
@@ -486,9 +1313,255 @@ mod tests {
         let keys_after = db.list_api_key(&user.id).await.unwrap();
         assert_eq!(keys_after.len(), 0);
     }
+
+    #[ignore]
+    #[tokio::test]
+    async fn test_webhook_update_and_rotate_secret() {
+        let db = setup_test_db().await;
+        let uid = format!("test-{}", Uuid::new_v4());
+        let user = db.get_or_create_user(&uid, None).await.unwrap();
+        let webhook = db.create_webhook("https://example.com/hook", "old", &user.id, &["invoice.paid".to_string()], "legacy")
+            .await
+            .unwrap();
+        assert!(webhook.enabled);
+
+        let updated = db.update_webhook(&webhook.id, &user.id, None, Some(false), None).await.unwrap().unwrap();
+        assert_eq!((updated.enabled, updated.url.as_str()), (false, "https://example.com/hook"));
+        assert!(db.list_webhooks_for_event(&user.id, "invoice.paid").await.unwrap().is_empty());
+        assert!(db.update_webhook(&webhook.id, &Uuid::new_v4(), None, Some(true), None).await.unwrap().is_none());
+
+        let rotated = db.rotate_webhook_secret(&webhook.id, &user.id, "new", Duration::from_secs(60)).await.unwrap().unwrap();
+        assert_eq!((rotated.secret.as_str(), rotated.previous_secret.as_deref()), ("new", Some("old")));
+        assert!(rotated.previous_secret_expires_at.unwrap() > chrono::Utc::now().naive_utc());
+
+        let rotated = db.rotate_webhook_secret(&webhook.id, &user.id, "newer", Duration::ZERO).await.unwrap().unwrap();
+        assert!(rotated.previous_secret.is_none() && rotated.previous_secret_expires_at.is_none());
+    }
+
+    fn keyring(ids: &[&str]) -> Keyring {
+        use sha2::{Digest, Sha256};
+
+        Keyring::new(ids.iter()
+            .map(|id| db::encryption::DataKey::parse(&format!("{id}:{}", hex::encode(Sha256::digest(id)))).unwrap())
+            .collect())
+            .unwrap()
+    }
+
+    #[cfg(feature = "telegram")]
+    #[tokio::test]
+    async fn test_credentials_are_encrypted_at_rest() {
+        let db = DB::in_memory();
+        let user = db.get_or_create_user("firebase-1", Some("merchant@example.com".to_string())).await.unwrap();
+        db.link_telegram_chat(&user.id, "4242", "private", "Me").await.unwrap();
+        let webhook = db.create_webhook("https://example.com/hook", "whsec", &user.id, &[], "legacy").await.unwrap();
+        let chat_webhook = db.create_chat_webhook(&user.id, "slack", "Sales", "https://hooks.slack.com/services/T0/B0/x", &[]).await.unwrap();
+
+        let stored = db.repo.get_user_by_id(&user.id).await.unwrap();
+        assert!(stored.email.as_deref().unwrap().starts_with("enc1:test:"));
+        assert!(db.repo.list_telegram_destinations(&user.id).await.unwrap()[0].chat_id.starts_with("enc1:test:"));
+        assert!(db.repo.get_webhook(&webhook.id, &user.id).await.unwrap().unwrap().secret.starts_with("enc1:test:"));
+        assert!(db.repo.get_chat_webhook(&chat_webhook.id, &user.id).await.unwrap().unwrap().url.starts_with("enc1:test:"));
+
+        let user = db.get_or_create_user("firebase-1", Some("merchant@example.com".to_string())).await.unwrap();
+        assert_eq!(user.email.as_deref(), Some("merchant@example.com"));
+        assert_eq!(db.list_telegram_destinations(&user.id).await.unwrap()[0].chat_id, "4242");
+        // Logging in again with the same email does not rewrite it.
+        assert_eq!(db.repo.get_user_by_id(&user.id).await.unwrap().email, stored.email);
+        assert_eq!(db.get_webhook(&webhook.id, &user.id).await.unwrap().unwrap().secret, "whsec");
+        assert_eq!(db.list_chat_webhooks(&user.id).await.unwrap()[0].url, "https://hooks.slack.com/services/T0/B0/x");
+
+        let user = db.get_or_create_user("firebase-1", Some("new@example.com".to_string())).await.unwrap();
+        assert_eq!(db.get_user_by_id(&user.id).await.unwrap().email.as_deref(), Some("new@example.com"));
+    }
+
+    #[tokio::test]
+    async fn test_undecryptable_webhook_delivery_fails_alone() {
+        let repo = Arc::new(db::memory::MemoryRepository::default());
+        let db = DB::from_repository(repo.clone(), keyring(&["current"]));
+        let lost = DB::from_repository(repo.clone(), keyring(&["lost"]));
+        let user_id = Uuid::new_v4();
+        let broken = lost.create_webhook("https://example.com/broken", "whsec", &user_id, &[], "legacy").await.unwrap();
+        let working = db.create_webhook("https://example.com/hook", "whsec", &user_id, &[], "legacy").await.unwrap();
+        let broken_delivery = db.enqueue_webhook_delivery(&broken.id, &serde_json::json!({"id": 1})).await.unwrap();
+        let working_delivery = db.enqueue_webhook_delivery(&working.id, &serde_json::json!({"id": 2})).await.unwrap();
+
+        let claimed = db.claim_due_webhook_deliveries(100, Duration::from_secs(60)).await.unwrap();
+        assert_eq!(claimed.iter().map(|due| (due.id, due.secret.as_str())).collect::<Vec<_>>(), [(working_delivery.id, "whsec")]);
+
+        let failed = &db.list_webhook_deliveries(&broken.id, 10, 0).await.unwrap()[0];
+        assert_eq!((failed.status.as_str(), failed.attempts), ("failed", 1));
+        let attempts = db.list_webhook_delivery_attempts(&[broken_delivery.id]).await.unwrap();
+        assert!(attempts[0].error.as_deref().unwrap().starts_with("Failed to decrypt the webhook secret"));
+    }
+
+    #[cfg(feature = "telegram")]
+    #[tokio::test]
+    async fn test_reencrypt_after_key_rotation() {
+        let repo = Arc::new(db::memory::MemoryRepository::default());
+        let old = DB::from_repository(repo.clone(), keyring(&["old"]));
+        let user = old.get_or_create_user("firebase-1", Some("merchant@example.com".to_string())).await.unwrap();
+        let webhook = old.create_webhook("https://example.com/hook", "whsec", &user.id, &[], "legacy").await.unwrap();
+        let chat_webhook = old.create_chat_webhook(&user.id, "discord", "Team", "https://discord.com/api/webhooks/1/x", &[]).await.unwrap();
+        let invoice = old.create_invoice(BigDecimal::from(10), "0xseller", &[1], Some(user.id), None, false).await.unwrap();
+        old.set_invoice_buyer_email(&invoice.id, "buyer@example.com", false).await.unwrap().unwrap();
+        // Written before encryption existed.
+        repo.upsert_telegram_destination(&NewTelegramDestination {
+            user_id: user.id,
+            label: "Me".to_string(),
+            chat_id: "4242".to_string(),
+            chat_lookup: None,
+            chat_type: "private".to_string(),
+            event_types: vec!["invoice.paid".to_string()],
+        }).await.unwrap();
+
+        let rotated = DB::from_repository(repo.clone(), keyring(&["new", "old"]));
+        assert_eq!(rotated.reencrypt(1).await.unwrap(), ReencryptReport { users: 1, telegram_destinations: 1, chat_webhooks: 1, webhooks: 1, invoices: 1 });
+        assert_eq!(rotated.reencrypt(1).await.unwrap(), ReencryptReport::default());
+
+        let new_only = DB::from_repository(repo.clone(), keyring(&["new"]));
+        let user = new_only.get_user_by_id(&user.id).await.unwrap();
+        assert_eq!(user.email.as_deref(), Some("merchant@example.com"));
+        assert_eq!(new_only.get_webhook(&webhook.id, &user.id).await.unwrap().unwrap().secret, "whsec");
+        assert_eq!(new_only.get_chat_webhook(&chat_webhook.id, &user.id).await.unwrap().unwrap().url, "https://discord.com/api/webhooks/1/x");
+        assert!(repo.list_telegram_destinations(&user.id).await.unwrap()[0].chat_id.starts_with("enc1:new:"));
+        assert_eq!(new_only.get_user_by_telegram_chat_id("4242").await.unwrap().unwrap().id, user.id);
+        let invoice = new_only.get_invoice(&invoice.id).await.unwrap().unwrap();
+        assert_eq!(new_only.get_buyer_email(&invoice).unwrap().as_deref(), Some("buyer@example.com"));
+    }
+
+    #[cfg(feature = "telegram")]
+    #[tokio::test]
+    async fn test_telegram_chat_moves_between_users() {
+        let db = DB::in_memory();
+        let first = db.get_or_create_user("firebase-1", None).await.unwrap();
+        let second = db.get_or_create_user("firebase-2", None).await.unwrap();
+
+        let linked = db.link_telegram_chat(&first.id, "4242", "private", "Me").await.unwrap();
+        assert_eq!(db.link_telegram_chat(&first.id, "4242", "private", "Again").await.unwrap().id, linked.id);
+        db.link_telegram_chat(&first.id, "-100", "supergroup", "Team").await.unwrap();
+        assert_eq!(db.get_user_by_telegram_chat_id("4242").await.unwrap().unwrap().id, first.id);
+        assert!(db.get_user_by_telegram_chat_id("4243").await.unwrap().is_none());
+        assert_eq!(db.list_telegram_destinations(&first.id).await.unwrap().len(), 2);
+
+        db.link_telegram_chat(&second.id, "4242", "private", "Me").await.unwrap();
+        let remaining = db.list_telegram_destinations(&first.id).await.unwrap();
+        assert_eq!((remaining.len(), remaining[0].label.as_str()), (1, "Team"));
+        assert_eq!(db.get_user_by_telegram_chat_id("4242").await.unwrap().unwrap().id, second.id);
+
+        assert!(db.move_telegram_chat("-100", "-200", "supergroup").await.unwrap());
+        assert!(db.get_telegram_destination("-100").await.unwrap().is_none());
+        assert_eq!(db.get_telegram_destination("-200").await.unwrap().unwrap().label, "Team");
+
+        assert!(db.unlink_telegram_chat("4242").await.unwrap());
+        assert!(!db.unlink_telegram_chat("4242").await.unwrap());
+        assert!(db.get_user_by_telegram_chat_id("4242").await.unwrap().is_none());
+        assert_eq!(db.unlink_telegram_chats(&first.id).await.unwrap(), 1);
+    }
+
+    #[ignore]
+    #[tokio::test]
+    async fn test_webhook_outbox_claim_and_record() {
+        let db = setup_test_db().await;
+        let uid = format!("test-{}", Uuid::new_v4());
+        let user = db.get_or_create_user(&uid, None).await.unwrap();
+        let webhook = db.create_webhook("https://example.com/hook", "secret", &user.id, &["invoice.paid".to_string()], "legacy")
+            .await
+            .unwrap();
+        assert_eq!(db.list_webhooks_for_event(&user.id, "invoice.paid").await.unwrap().len(), 1);
+        assert!(db.list_webhooks_for_event(&user.id, "invoice.created").await.unwrap().is_empty());
+        let delivery = db.enqueue_webhook_delivery(&webhook.id, &serde_json::json!({"id": 1})).await.unwrap();
+
+        let claimed = db.claim_due_webhook_deliveries(100, Duration::from_secs(60)).await.unwrap();
+        assert!(claimed.iter().any(|due| due.id == delivery.id && due.url == webhook.url));
+        let claimed_again = db.claim_due_webhook_deliveries(100, Duration::from_secs(60)).await.unwrap();
+        assert!(!claimed_again.iter().any(|due| due.id == delivery.id));
+
+        let attempt = NewWebhookDeliveryAttempt { status_code: Some(200), latency_ms: 12, ..Default::default() };
+        db.record_webhook_delivery_attempt(&delivery.id, &attempt, WebhookDeliveryStatus::Delivered, Duration::ZERO).await.unwrap();
+
+        let stored = db.list_webhook_deliveries(&webhook.id, 10, 0).await.unwrap();
+        assert_eq!((stored[0].status.as_str(), stored[0].attempts), ("delivered", 1));
+        assert!(stored[0].delivered_at.is_some());
+        assert_eq!(db.list_webhook_delivery_attempts(&[delivery.id]).await.unwrap().len(), 1);
+        assert!(db.redeliver_webhook_delivery(&delivery.id, &webhook.id).await.unwrap());
+    }
+
+    #[ignore]
+    #[tokio::test]
+    async fn test_notification_digest_claim_and_take() {
+        let db = setup_test_db().await;
+        let uid = format!("test-{}", Uuid::new_v4());
+        let user = db.get_or_create_user(&uid, None).await.unwrap();
+        db.upsert_notification_digest(&user.id, "email", "daily").await.unwrap();
+        let item = NewNotificationDigestItem {
+            user_id: user.id,
+            channel: "email".to_string(),
+            event_type: "invoice.paid".to_string(),
+            invoice_id: Uuid::new_v4(),
+            amount: BigDecimal::from(10),
+            network: Some(1),
+            external_id: Some("order-1".to_string()),
+        };
+        db.create_notification_digest_item(&item).await.unwrap();
+        assert!(db.list_pending_notification_digests().await.unwrap().iter().any(|digest| digest.user_id == user.id));
+
+        let period_end = chrono::Utc::now().naive_utc() + chrono::Duration::days(1);
+        assert!(db.claim_notification_digest(&user.id, "email", &period_end).await.unwrap());
+        assert!(!db.claim_notification_digest(&user.id, "email", &period_end).await.unwrap());
+
+        let items = db.list_notification_digest_items(&user.id, "email", &period_end).await.unwrap();
+        assert_eq!(items.len(), 1);
+        assert_eq!(items[0].external_id.as_deref(), Some("order-1"));
+        db.delete_notification_digest_items(&[items[0].id]).await.unwrap();
+        assert!(db.list_notification_digest_items(&user.id, "email", &period_end).await.unwrap().is_empty());
+        assert!(db.list_notification_digests(&user.id).await.unwrap()[0].last_sent_at.is_some());
+    }
+
+    #[ignore]
+    #[tokio::test]
+    async fn test_notification_log_resend() {
+        let db = setup_test_db().await;
+        let uid = format!("test-{}", Uuid::new_v4());
+        let user = db.get_or_create_user(&uid, None).await.unwrap();
+        let invoice_id = Uuid::new_v4();
+        let log = NewNotificationLog {
+            user_id: user.id,
+            channel: "chat".to_string(),
+            event_type: "payment.anomaly".to_string(),
+            invoice_id: Some(invoice_id),
+            destination_id: Some(Uuid::new_v4()),
+            destination: "Sales".to_string(),
+            transfer: Some(serde_json::json!({"amount": "5"})),
+            error: Some("HTTP 500".to_string()),
+        };
+        let failed = db.create_notification_log(&log).await.unwrap();
+        assert_eq!((failed.status.as_str(), failed.sent_at), ("failed", None));
+        db.create_notification_log(&NewNotificationLog { error: None, ..log }).await.unwrap();
+
+        let filter = NotificationLogFilter { invoice_id: Some(invoice_id), status: Some("failed".to_string()), ..Default::default() };
+        let listed = db.list_notification_logs(&user.id, &filter, 10, 0).await.unwrap();
+        assert_eq!(listed.iter().map(|log| log.id).collect::<Vec<_>>(), vec![failed.id]);
+
+        assert!(db.resend_notification(&failed.id, &user.id).await.unwrap());
+        let claimed = db.claim_pending_notifications(100, Duration::from_secs(60)).await.unwrap();
+        assert!(claimed.iter().any(|log| log.id == failed.id));
+        assert!(!db.claim_pending_notifications(100, Duration::from_secs(60)).await.unwrap().iter().any(|log| log.id == failed.id));
+
+        db.record_notification_attempt(&failed.id, None).await.unwrap();
+        let resent = db.get_notification_log(&failed.id, &user.id).await.unwrap().unwrap();
+        assert_eq!((resent.status.as_str(), resent.attempts), ("sent", 2));
+        assert!(resent.sent_at.is_some() && resent.error.is_none());
+    }
+}
+
+/// Identity proven by a verified Firebase ID token.
+pub struct VerifiedToken {
+    pub sub: String,
+    pub email: Option<String>,
 }
 
 impl GC {
+    #[cfg(feature = "firebase")]
     async fn new() -> Result<Self, String> {
         let credentials = credentials_provider()
             .await
@@ -502,30 +1575,49 @@ impl GC {
         Ok(Self { verifier: Arc::new(verifier) })
     }
 
-    pub async fn get_verified_token(&self, token: &str) -> Result<JWToken, VerifyError> {
+    #[cfg(not(feature = "firebase"))]
+    async fn new() -> Result<Self, String> {
+        Err("Firebase login requires the `firebase` feature".to_string())
+    }
+
+    #[cfg(feature = "firebase")]
+    pub async fn get_verified_token(&self, token: &str) -> Result<VerifiedToken, VerifyError> {
         match self.verifier.verify_token(token).await {
-            Ok(token) => Ok(token),
-            Err(err) => Err(VerifyError::Verification(err.current_context().clone()))
+            Ok(token) => Ok(VerifiedToken {
+                sub: token.critical_claims.sub,
+                email: token.all_claims
+                    .get("email")
+                    .and_then(|v| v.as_str())
+                    .map(|s| s.to_string()),
+            }),
+            Err(err) => Err(VerifyError::Verification(format!("{:?}", err.current_context()))),
         }
     }
+
+    #[cfg(not(feature = "firebase"))]
+    pub async fn get_verified_token(&self, _token: &str) -> Result<VerifiedToken, VerifyError> {
+        Err(VerifyError::Unexpected("Firebase login requires the `firebase` feature".to_string()))
+    }
 }
 
 #[derive(Debug)]
 pub enum VerifyError {
-    Verification(TokenVerificationError),
+    #[cfg_attr(not(feature = "firebase"), allow(dead_code))]
+    Verification(String),
+    #[cfg_attr(feature = "firebase", allow(dead_code))]
     Unexpected(String),
 }
 
 impl Redis {
-    async fn new() -> Result<Self, String> {
-        let client = redis::Client::open(utils::get_env_var("REDIS_URL")?)
+    pub async fn new(url: &str, gas_fee_cache_ttl_secs: u64) -> Result<Self, String> {
+        let client = redis::Client::open(url)
             .map_err(|e| utils::make_err(Box::new(e), "get redis client"))?;
 
         let connection = ConnectionManager::new(client)
             .await
             .map_err(|e| utils::make_err(Box::new(e), "get redis connection"))?;
 
-        Ok(Self { connection })
+        Ok(Self { connection, gas_fee_cache_ttl_secs })
     }
 
     pub async fn health_check(&self) -> Result<(), AppError> {
@@ -536,6 +1628,7 @@ impl Redis {
         Ok(())
     }
 
+    #[cfg(feature = "infura")]
     async fn get(&self, key: &str) -> Result<Option<String>, AppError> {
         self.connection
             .clone()
@@ -544,6 +1637,7 @@ impl Redis {
             .map_err(AppError::Redis)
     }
 
+    #[cfg(any(feature = "infura", feature = "telegram"))]
     async fn set(&self, key: &str, value: String, timeout: u64) -> Result<(), AppError> {
         let set_result: RedisResult<()> = self.connection
             .clone()
@@ -569,17 +1663,46 @@ impl Redis {
             .map_err(AppError::Redis)
     }
 
+    /// A random single-use token that links the chat it is sent from to `user_id`, see
+    /// [`Self::take_telegram_link_token`].
+    #[cfg(feature = "telegram")]
+    pub async fn issue_telegram_link_token(&self, user_id: &Uuid, ttl_secs: u64) -> Result<String, AppError> {
+        let token = hex::encode(rand::random::<[u8; 16]>());
+        self.set(&get_telegram_link_token_key(&token), user_id.to_string(), ttl_secs).await?;
+        Ok(token)
+    }
+
+    /// The user the token was issued to; `None` once it expired or was used.
+    #[cfg(feature = "telegram")]
+    pub async fn take_telegram_link_token(&self, token: &str) -> Result<Option<Uuid>, AppError> {
+        let user_id: Option<String> = self.connection
+            .clone()
+            .get_del(get_telegram_link_token_key(token))
+            .await
+            .map_err(AppError::Redis)?;
+
+        Ok(user_id.and_then(|user_id| Uuid::parse_str(&user_id).ok()))
+    }
+
+    #[cfg(feature = "infura")]
     pub async fn get_suggested_gas_fees(&self, network: &i64) -> Result<Option<String>, AppError> {
         let redis_key = get_suggested_gas_fees_key(network);
         self.get(&redis_key).await
     }
 
+    #[cfg(feature = "infura")]
     pub async fn set_suggested_gas_fees(&self, network: &i64, value: String) -> Result<(), AppError> {
         let redis_key = get_suggested_gas_fees_key(network);
-        self.set(&redis_key, value, SUGGESTED_GAS_FEE_TIMEOUT).await
+        self.set(&redis_key, value, self.gas_fee_cache_ttl_secs).await
     }
 }
 
+#[cfg(feature = "infura")]
 fn get_suggested_gas_fees_key(network: &i64) -> String {
     format!("network-suggested-gas-fees:{}", network)
-}
\ No newline at end of file
+}
+
+#[cfg(feature = "telegram")]
+fn get_telegram_link_token_key(token: &str) -> String {
+    format!("telegram-link-token:{}", token)
+}
@@ -36,7 +36,7 @@ pub(crate) async fn get_analytics(
     Extension(user): Extension<User>,
     Query(query): Query<AnalyticsQuery>,
 ) -> Result<Json<AnalyticsResponse>, ResponseError> {
-    let days = query.days.min(365).max(1);
+    let days = query.days.clamp(1, 365);
     let since: NaiveDateTime = (Utc::now() - Duration::days(days as i64)).naive_utc();
 
     let summary = state.db.invoice_summary(&user.id, since)
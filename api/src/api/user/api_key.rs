@@ -13,9 +13,6 @@ use crate::{db, utils};
 use crate::api::response_error::ResponseError;
 
 
-const API_KEY_LIMIT: usize = 5;
-
-
 pub fn get_router(app_state: Arc<AppState>) -> Router<Arc<AppState>> {
     Router::new()
         .route("/ping", get(ping_pong))
@@ -100,14 +97,14 @@ pub(crate) async fn create(
         .await
         .map_err(ResponseError::from)?;
 
-    if api_keys_number >= API_KEY_LIMIT {
+    if api_keys_number >= state.config.limits.api_keys_per_user {
         return Err(ResponseError::Bad("too many api keys".to_string()))
     }
 
     let api_key = utils::new_api_key(user.id);
 
     let instance: GetApiKeyResponse = state.db
-        .create_api_key(&user.id, &api_key.hashed_value())
+        .create_api_key(&user.id, &api_key.hashed_value(&state.config.app_secret))
         .await
         .map_err(ResponseError::from)?
         .into();
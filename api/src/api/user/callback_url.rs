@@ -13,9 +13,6 @@ use crate::db;
 use crate::api::response_error::ResponseError;
 
 
-const CALLBACK_URLS_LIMIT: usize = 5;
-
-
 pub fn get_router(app_state: Arc<AppState>) -> Router<Arc<AppState>> {
     Router::new()
         .route("/ping", get(ping_pong))
@@ -94,7 +91,7 @@ pub(crate) async fn create(
         .await
         .map_err(ResponseError::from)?;
 
-    if callback_urls_number >= CALLBACK_URLS_LIMIT {
+    if callback_urls_number >= state.config.limits.callback_urls_per_user {
         return Err(ResponseError::Bad("too many callback urls".to_string()))
     }
 
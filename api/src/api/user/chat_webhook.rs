@@ -0,0 +1,302 @@
+use std::sync::Arc;
+use axum::{Extension, Json, middleware, Router};
+use axum::extract::{Path, State};
+use axum::http::StatusCode;
+use axum::response::IntoResponse;
+use axum::routing::{delete, get, patch, post};
+use chrono::NaiveDateTime;
+use serde::{Deserialize, Serialize};
+use url::Url;
+use uuid::Uuid;
+use crate::api::middleware::rate_limiting::middleware::RateLimitType;
+use crate::api::ping_pong::ping_pong;
+use crate::api::response_error::ResponseError;
+use crate::api::state::AppState;
+use crate::api::user::webhook::event_type_names;
+use crate::db::User;
+use crate::db::chat_webhook::ChatWebhook;
+use crate::events::notifications::chat::{ChatKind, ChatMessage};
+use crate::events::notifications::templates::{self, TemplateKind, TemplateVars};
+use crate::events::webhook_event::WebhookEventType;
+use crate::monitoring::app_state::Webhooker;
+
+const MAX_LABEL_LENGTH: usize = 64;
+
+pub fn get_router(app_state: Arc<AppState>) -> Router<Arc<AppState>> {
+    Router::new()
+        .route("/", post(create))
+        .route("/:chat_webhook_id", patch(update))
+        .route("/:chat_webhook_id/test", post(send_test))
+        .layer(middleware::from_fn_with_state(app_state.clone(), RateLimitType::user_webhook))
+        .route("/", get(list))
+        .route("/:chat_webhook_id", delete(destroy))
+        .with_state(app_state)
+        .route("/ping", get(ping_pong))
+}
+
+#[derive(Serialize, utoipa::ToSchema)]
+pub(crate) struct GetChatWebhookResponse {
+    pub id: Uuid,
+    pub kind: ChatKind,
+    pub label: String,
+    /// The URL is the channel's credential, so only its host and last four characters are shown.
+    #[schema(example = "https://hooks.slack.com/****Xk2f")]
+    pub url: String,
+    #[schema(example = json!(["invoice.paid"]))]
+    pub events: Vec<String>,
+    pub enabled: bool,
+    pub created_at: NaiveDateTime,
+}
+
+/// `https://host/****` plus the last four characters of the URL.
+fn mask_url(url: &str) -> String {
+    let origin = Url::parse(url)
+        .map(|parsed| format!("{}://{}", parsed.scheme(), parsed.host_str().unwrap_or_default()))
+        .unwrap_or_default();
+    let tail = url.char_indices().rev().nth(3).map(|(start, _)| &url[start..]).unwrap_or_default();
+    format!("{origin}/****{tail}")
+}
+
+impl TryFrom<ChatWebhook> for GetChatWebhookResponse {
+    type Error = ResponseError;
+
+    fn try_from(value: ChatWebhook) -> Result<Self, Self::Error> {
+        Ok(Self {
+            id: value.id,
+            kind: ChatKind::parse(&value.kind).ok_or_else(|| ResponseError::from_error(format!("unknown chat kind {}", value.kind)))?,
+            label: value.label,
+            url: mask_url(&value.url),
+            events: value.event_types,
+            enabled: value.enabled,
+            created_at: value.created_at,
+        })
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/user/chat_webhook",
+    responses(
+        (status = 200, description = "Slack, Discord and generic chat webhooks, oldest first", body = Vec<GetChatWebhookResponse>),
+        (status = 401, description = "Unauthorized"),
+    ),
+    tag = "user",
+    security(("jwt_cookie" = []))
+)]
+pub(crate) async fn list(
+    State(state): State<Arc<AppState>>,
+    Extension(user): Extension<User>,
+) -> Result<impl IntoResponse, ResponseError> {
+    let chat_webhooks = state.db
+        .list_chat_webhooks(&user.id)
+        .await
+        .map_err(ResponseError::from)?
+        .into_iter()
+        .map(GetChatWebhookResponse::try_from)
+        .collect::<Result<Vec<_>, _>>()?;
+
+    Ok(Json(chat_webhooks))
+}
+
+fn check_label(label: &str) -> Result<&str, String> {
+    match label.trim() {
+        "" => Err("label must not be empty".to_string()),
+        label if label.chars().count() > MAX_LABEL_LENGTH => Err(format!("label must be at most {MAX_LABEL_LENGTH} characters")),
+        label => Ok(label),
+    }
+}
+
+/// The URL must be the kind's webhook endpoint and pass the outbound guard. Nothing is posted:
+/// chat apps would show an empty test message in the channel.
+async fn check_url(webhooker: &Webhooker, kind: ChatKind, url: &str) -> Result<(), String> {
+    let parsed = kind.check_url(url)?;
+    webhooker.guard().check(&parsed).await
+}
+
+#[derive(Deserialize, utoipa::ToSchema)]
+pub(crate) struct CreateChatWebhookRequest {
+    pub kind: ChatKind,
+    pub label: String,
+    /// The incoming webhook URL from Slack, Discord or the chat app.
+    pub url: String,
+    /// Event types to post; every type in the catalog when omitted.
+    pub events: Option<Vec<WebhookEventType>>,
+}
+
+#[utoipa::path(
+    post,
+    path = "/user/chat_webhook",
+    request_body = CreateChatWebhookRequest,
+    responses(
+        (status = 200, description = "Created chat webhook", body = GetChatWebhookResponse),
+        (status = 400, description = "Invalid URL or label, or too many chat webhooks"),
+        (status = 401, description = "Unauthorized"),
+    ),
+    tag = "user",
+    security(("jwt_cookie" = []))
+)]
+pub(crate) async fn create(
+    State(state): State<Arc<AppState>>,
+    Extension(user): Extension<User>,
+    Json(payload): Json<CreateChatWebhookRequest>,
+) -> Result<impl IntoResponse, ResponseError> {
+    let label = check_label(&payload.label).map_err(ResponseError::Bad)?;
+    let event_types = event_type_names(payload.events.as_deref().unwrap_or(&WebhookEventType::ALL))
+        .map_err(ResponseError::Bad)?;
+    check_url(&state.webhooker, payload.kind, &payload.url).await.map_err(ResponseError::Bad)?;
+
+    let count = state.db.count_chat_webhooks(&user.id).await.map_err(ResponseError::from)?;
+    if count >= state.config.limits.chat_webhooks_per_user {
+        return Err(ResponseError::Bad("too many chat webhooks".to_string()));
+    }
+
+    let instance: GetChatWebhookResponse = state.db
+        .create_chat_webhook(&user.id, payload.kind.as_str(), label, &payload.url, &event_types)
+        .await
+        .map_err(ResponseError::from)?
+        .try_into()?;
+
+    Ok(Json(instance))
+}
+
+#[derive(Deserialize, utoipa::ToSchema)]
+pub(crate) struct UpdateChatWebhookRequest {
+    pub label: Option<String>,
+    /// Checked like on create, against the webhook's kind.
+    pub url: Option<String>,
+    pub enabled: Option<bool>,
+    pub events: Option<Vec<WebhookEventType>>,
+}
+
+#[utoipa::path(
+    patch,
+    path = "/user/chat_webhook/{id}",
+    params(("id" = Uuid, Path, description = "Chat webhook ID")),
+    request_body = UpdateChatWebhookRequest,
+    responses(
+        (status = 200, description = "Updated chat webhook", body = GetChatWebhookResponse),
+        (status = 400, description = "Invalid URL, label or event types"),
+        (status = 404, description = "Not found"),
+        (status = 401, description = "Unauthorized"),
+    ),
+    tag = "user",
+    security(("jwt_cookie" = []))
+)]
+pub(crate) async fn update(
+    State(state): State<Arc<AppState>>,
+    Extension(user): Extension<User>,
+    Path(chat_webhook_id): Path<Uuid>,
+    Json(payload): Json<UpdateChatWebhookRequest>,
+) -> Result<impl IntoResponse, ResponseError> {
+    let current: GetChatWebhookResponse = state.db.get_chat_webhook(&chat_webhook_id, &user.id)
+        .await
+        .map_err(ResponseError::from)?
+        .ok_or(ResponseError::NotFound)?
+        .try_into()?;
+
+    let label = payload.label.as_deref().map(check_label).transpose().map_err(ResponseError::Bad)?;
+    let event_types = payload.events
+        .as_deref()
+        .map(event_type_names)
+        .transpose()
+        .map_err(ResponseError::Bad)?;
+    if let Some(url) = &payload.url {
+        check_url(&state.webhooker, current.kind, url).await.map_err(ResponseError::Bad)?;
+    }
+
+    let instance: GetChatWebhookResponse = state.db
+        .update_chat_webhook(&chat_webhook_id, &user.id, label, payload.url.as_deref(), payload.enabled, event_types.as_deref())
+        .await
+        .map_err(ResponseError::from)?
+        .ok_or(ResponseError::NotFound)?
+        .try_into()?;
+
+    Ok(Json(instance))
+}
+
+#[derive(Serialize, utoipa::ToSchema)]
+pub(crate) struct TestChatWebhookResponse {
+    pub delivered: bool,
+    /// What went wrong, including the chat app's answer when it refused the message.
+    pub error: Option<String>,
+}
+
+#[utoipa::path(
+    post,
+    path = "/user/chat_webhook/{id}/test",
+    params(("id" = Uuid, Path, description = "Chat webhook ID")),
+    responses(
+        (status = 200, description = "Whether a sample invoice-paid message was posted", body = TestChatWebhookResponse),
+        (status = 404, description = "Not found"),
+        (status = 401, description = "Unauthorized"),
+    ),
+    tag = "user",
+    security(("jwt_cookie" = []))
+)]
+pub(crate) async fn send_test(
+    State(state): State<Arc<AppState>>,
+    Extension(user): Extension<User>,
+    Path(chat_webhook_id): Path<Uuid>,
+) -> Result<impl IntoResponse, ResponseError> {
+    let chat_webhook = state.db.get_chat_webhook(&chat_webhook_id, &user.id)
+        .await
+        .map_err(ResponseError::from)?
+        .ok_or(ResponseError::NotFound)?;
+    let kind = ChatKind::parse(&chat_webhook.kind)
+        .ok_or_else(|| ResponseError::from_error(format!("unknown chat kind {}", chat_webhook.kind)))?;
+
+    let vars = TemplateVars::sample(&state.config.web_base_url);
+    let rendered = templates::render(&state.db, &user.id, TemplateKind::InvoicePaid, &user.locale, &vars)
+        .await
+        .map_err(ResponseError::Bad)?;
+    let message = ChatMessage::new(&rendered, &vars, &user.locale);
+
+    let error = kind.send(&state.webhooker, &chat_webhook.url, &message).await.err();
+    Ok(Json(TestChatWebhookResponse { delivered: error.is_none(), error }))
+}
+
+#[utoipa::path(
+    delete,
+    path = "/user/chat_webhook/{id}",
+    params(("id" = Uuid, Path, description = "Chat webhook ID")),
+    responses(
+        (status = 204, description = "Deleted"),
+        (status = 404, description = "Not found"),
+        (status = 401, description = "Unauthorized"),
+    ),
+    tag = "user",
+    security(("jwt_cookie" = []))
+)]
+pub(crate) async fn destroy(
+    State(state): State<Arc<AppState>>,
+    Extension(user): Extension<User>,
+    Path(chat_webhook_id): Path<Uuid>,
+) -> Result<impl IntoResponse, ResponseError> {
+    Ok(match state.db.delete_chat_webhook(&chat_webhook_id, &user.id)
+        .await
+        .map_err(ResponseError::from)? {
+        true => StatusCode::NO_CONTENT,
+        false => return Err(ResponseError::NotFound),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    #[rstest]
+    #[case("https://hooks.slack.com/services/T0/B0/abcdXk2f", "https://hooks.slack.com/****Xk2f")]
+    #[case("https://discord.com/api/webhooks/1/tok", "https://discord.com/****/tok")]
+    fn test_mask_url(#[case] url: &str, #[case] expected: &str) {
+        assert_eq!(mask_url(url), expected);
+    }
+
+    #[rstest]
+    #[case(" Sales ", Ok("Sales"))]
+    #[case("  ", Err(()))]
+    #[case("Payments of the northern warehouse team, including weekend night shifts", Err(()))]
+    fn test_check_label(#[case] label: &str, #[case] expected: Result<&str, ()>) {
+        assert_eq!(check_label(label).map_err(|_| ()), expected);
+    }
+}
@@ -1,13 +1,20 @@
 pub(crate) mod analytics;
 pub(crate) mod api_key;
 pub(crate) mod callback_url;
+pub(crate) mod chat_webhook;
+#[cfg(feature = "telegram")]
+pub(crate) mod telegram;
+pub(crate) mod notification_digest;
+pub(crate) mod notification_preference;
+pub(crate) mod notifications;
+pub(crate) mod template;
 pub(crate) mod webhook;
 
 use std::collections::HashMap;
 use std::sync::Arc;
 use axum::extract::State;
 use axum::{Extension, Json, middleware, Router};
-use axum::response::{IntoResponse, Redirect};
+use axum::response::IntoResponse;
 use axum::routing::{get, patch};
 use analytics::get_analytics;
 use chrono::NaiveDateTime;
@@ -18,24 +25,41 @@ use crate::api::response_error::ResponseError;
 use crate::api::state::AppState;
 use crate::api::USER_BASE_PATH;
 use crate::db::{billing, User};
+use crate::events::notifications::templates::LOCALES;
 use crate::payments::subscription::{Subscription, SubscriptionTarget};
 
 const ATTACH_TELEGRAM_PATH: &str = "/attach_telegram";
+#[cfg(feature = "telegram")]
+const TELEGRAM_PATH: &str = "/telegram";
 
 fn get_attach_telegram_full_path() -> String {
     format!("{}{}", USER_BASE_PATH, ATTACH_TELEGRAM_PATH)
 }
 
 pub fn get_router(app_state: Arc<AppState>) -> Router {
-    Router::new()
+    #[allow(unused_mut)]
+    let mut router = Router::new()
         .route("/ping", get(ping_pong))
         .route("/", get(get_user))
-        .route("/", patch(update_user))
-        .route(ATTACH_TELEGRAM_PATH, get(attach_telegram))
+        .route("/", patch(update_user));
+
+    #[cfg(feature = "telegram")]
+    if app_state.telegram_client.is_some() {
+        router = router
+            .route(ATTACH_TELEGRAM_PATH, get(attach_telegram))
+            .nest(TELEGRAM_PATH, telegram::get_router(app_state.clone()));
+    }
+
+    router
         .route("/analytics", get(get_analytics))
         .nest("/api_key", api_key::get_router(app_state.clone()))
         .nest("/callback_url", callback_url::get_router(app_state.clone()))
         .nest("/webhook", webhook::get_router(app_state.clone()))
+        .nest("/chat_webhook", chat_webhook::get_router(app_state.clone()))
+        .nest("/template", template::get_router(app_state.clone()))
+        .nest("/notification_preference", notification_preference::get_router(app_state.clone()))
+        .nest("/notification_digest", notification_digest::get_router(app_state.clone()))
+        .nest("/notifications", notifications::get_router(app_state.clone()))
         .layer(middleware::from_fn_with_state(app_state.clone(), only_web))
         .layer(middleware::from_fn_with_state(app_state.clone(), extract_user))
         .with_state(app_state)
@@ -43,19 +67,40 @@ pub fn get_router(app_state: Arc<AppState>) -> Router {
 
 #[derive(Deserialize, utoipa::ToSchema)]
 pub struct UserRequest {
+    /// Turns the channel on or off for every event; `/user/notification_preference` chooses
+    /// the events and rules while it is on.
     pub email_notification: Option<bool>,
     pub telegram_notification: Option<bool>,
+    /// Language of notifications, one of `en`, `es`, `ru`.
+    #[schema(example = "en")]
+    pub locale: Option<String>,
+    /// Whether new invoices mail the buyer a receipt unless created with `send_receipt`.
+    pub buyer_receipts: Option<bool>,
 }
 
 #[derive(Serialize, utoipa::ToSchema)]
 pub struct UserResponse {
+    /// Present while Telegram notifications are on but no chat is linked yet.
     pub attach_telegram_path: Option<String>,
+    /// Whether any Telegram chat receives the notifications; `GET /user/telegram` lists them.
+    pub telegram_linked: bool,
     pub email_notification: bool,
     pub telegram_notification: bool,
+    pub locale: String,
+    pub buyer_receipts: bool,
     pub subscriptions: HashMap<String, Option<NaiveDateTime>>,
 }
 
 impl UserResponse {
+    /// Hides the Telegram link when the integration is disabled, since the route is not mounted.
+    fn with_telegram(self, telegram_enabled: bool, linked: bool) -> Self {
+        Self {
+            attach_telegram_path: self.attach_telegram_path.filter(|_| telegram_enabled && !linked),
+            telegram_linked: linked && telegram_enabled,
+            ..self
+        }
+    }
+
     fn with_subscriptions(self, subscriptions: Vec<Subscription>) -> Self {
         let mut subscriptions: HashMap<String, Option<NaiveDateTime>> = subscriptions
             .into_iter()
@@ -63,10 +108,7 @@ impl UserResponse {
             .collect();
 
         for target in SubscriptionTarget::iterator() {
-            let key: String = target.into();
-            if !subscriptions.contains_key(&key) {
-                subscriptions.insert(key, None);
-            }
+            subscriptions.entry(target.into()).or_insert(None);
         }
 
         Self {
@@ -78,16 +120,18 @@ impl UserResponse {
 
 impl From<User> for UserResponse {
     fn from(value: User) -> Self {
-        let need_telegram = value.telegram_notification && value.telegram_chat_id.is_none();
-        let attach_telegram_path = match need_telegram {
+        let attach_telegram_path = match value.telegram_notification {
             true => Some(get_attach_telegram_full_path()),
             false => None
         };
 
         UserResponse {
             attach_telegram_path,
+            telegram_linked: false,
             email_notification: value.email_notification,
             telegram_notification: value.telegram_notification,
+            locale: value.locale,
+            buyer_receipts: value.buyer_receipts,
             subscriptions: HashMap::new(),
         }
     }
@@ -120,7 +164,8 @@ pub(crate) async fn get_user(
     State(state): State<Arc<AppState>>,
     Extension(user): Extension<User>,
 ) -> Result<impl IntoResponse, ResponseError> {
-    let response: UserResponse = user.clone().into();
+    let linked = !state.db.list_telegram_destinations(&user.id).await.map_err(ResponseError::from)?.is_empty();
+    let response = UserResponse::from(user.clone()).with_telegram(state.config.telegram.is_some(), linked);
 
     let subscriptions: Result<Vec<_>, _> = state.db.list_user_subscriptions(&user.id)
         .await
@@ -142,6 +187,7 @@ pub(crate) async fn get_user(
     request_body = UserRequest,
     responses(
         (status = 200, description = "Updated user info", body = UserResponse),
+        (status = 400, description = "Unsupported locale"),
         (status = 401, description = "Unauthorized"),
     ),
     tag = "user",
@@ -152,29 +198,79 @@ pub(crate) async fn update_user(
     Extension(user): Extension<User>,
     Json(payload): Json<UserRequest>,
 ) -> Result<impl IntoResponse, ResponseError> {
-    let response: UserResponse = state.db
+    if let Some(locale) = &payload.locale {
+        if !LOCALES.contains(&locale.as_str()) {
+            return Err(ResponseError::Bad(format!("unsupported locale {locale}, expected one of {}", LOCALES.join(", "))));
+        }
+        state.db.set_user_locale(&user.id, locale).await.map_err(ResponseError::from)?;
+    }
+    if let Some(buyer_receipts) = payload.buyer_receipts {
+        state.db.set_user_buyer_receipts(&user.id, buyer_receipts).await.map_err(ResponseError::from)?;
+    }
+
+    let user = state.db
         .update_user(&user.id, payload.email_notification, payload.telegram_notification)
-        // .update_user(&user.id, None, payload.telegram_notification)     // TODO notification_turned_off
         .await
-        .map_err(ResponseError::from)?
-        .into();
+        .map_err(ResponseError::from)?;
+    let linked = !state.db.list_telegram_destinations(&user.id).await.map_err(ResponseError::from)?.is_empty();
+    let response = UserResponse::from(user).with_telegram(state.config.telegram.is_some(), linked);
 
     Ok(Json(response))
 }
 
+/// Which kind of chat `/user/attach_telegram` links.
+#[cfg(feature = "telegram")]
+#[derive(Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum AttachTelegramChat {
+    #[default]
+    Private,
+    Group,
+    Channel,
+}
+
+#[cfg(feature = "telegram")]
+#[derive(Deserialize)]
+struct AttachTelegramParams {
+    #[serde(default)]
+    chat: AttachTelegramChat,
+}
+
+/// Redirects to the bot with a single-use link token: `/start` in the private chat, or adding
+/// the bot to a group. Telegram passes no token when adding to a channel, so channels are
+/// linked to whoever adds the bot there, once their private chat is linked.
+#[cfg(feature = "telegram")]
 async fn attach_telegram(
     State(state): State<Arc<AppState>>,
     Extension(user): Extension<User>,
+    axum::extract::Query(params): axum::extract::Query<AttachTelegramParams>,
 ) -> Result<impl IntoResponse, ResponseError> {
-    let telegram_bot_name = state.telegram_client.get_bot_name()
+    let telegram_bot_name = state.telegram_client
+        .as_ref()
+        .ok_or(ResponseError::NotFound)?
+        .get_bot_name()
         .await
         .map_err(ResponseError::from_error)?;
 
-    let telegram_redirect_url = format!(
-        "https://t.me/{}?start={}",
-        telegram_bot_name,
-        user.id
-    );
+    let linked = state.db.list_telegram_destinations(&user.id).await.map_err(ResponseError::from)?;
+    if linked.len() >= state.config.limits.telegram_destinations_per_user {
+        return Err(ResponseError::Bad("too many telegram chats".to_string()));
+    }
+
+    let telegram_redirect_url = match params.chat {
+        AttachTelegramChat::Channel => format!("https://t.me/{telegram_bot_name}?startchannel&admin=post_messages"),
+        chat => {
+            let token = state.redis
+                .issue_telegram_link_token(&user.id, state.config.limits.telegram_link_ttl_secs)
+                .await
+                .map_err(ResponseError::from)?;
+            let start = match chat {
+                AttachTelegramChat::Group => "startgroup",
+                _ => "start",
+            };
+            format!("https://t.me/{telegram_bot_name}?{start}={token}")
+        }
+    };
 
-    Ok(Redirect::temporary(&telegram_redirect_url))
+    Ok(axum::response::Redirect::temporary(&telegram_redirect_url))
 }
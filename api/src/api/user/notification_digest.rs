@@ -0,0 +1,145 @@
+use std::sync::Arc;
+use axum::{Extension, Json, Router};
+use axum::extract::{Path, State};
+use axum::response::IntoResponse;
+use axum::routing::{get, put};
+use chrono::NaiveDateTime;
+use serde::{Deserialize, Serialize};
+use crate::api::ping_pong::ping_pong;
+use crate::api::response_error::ResponseError;
+use crate::api::state::AppState;
+use crate::db;
+use crate::db::notification_digest::NotificationDigest;
+use crate::events::notifications::digest::{DigestFrequency, DIGEST_CHANNELS};
+use crate::events::notifications::preferences::NotificationChannel;
+
+pub fn get_router(app_state: Arc<AppState>) -> Router<Arc<AppState>> {
+    Router::new()
+        .route("/ping", get(ping_pong))
+        .route("/", get(list))
+        .route("/:channel", put(upsert))
+        .with_state(app_state)
+}
+
+#[derive(Serialize, utoipa::ToSchema)]
+pub(crate) struct GetNotificationDigestResponse {
+    pub channel: NotificationChannel,
+    pub frequency: DigestFrequency,
+    /// End of the last period that was summarized.
+    pub last_sent_at: Option<NaiveDateTime>,
+}
+
+impl GetNotificationDigestResponse {
+    fn new(channel: NotificationChannel, stored: Option<&NotificationDigest>) -> Self {
+        Self {
+            channel,
+            frequency: stored
+                .and_then(|digest| DigestFrequency::parse(&digest.frequency))
+                .unwrap_or_default(),
+            last_sent_at: stored.and_then(|digest| digest.last_sent_at),
+        }
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/user/notification_digest",
+    responses(
+        (status = 200, description = "Frequency of every channel that supports digests", body = Vec<GetNotificationDigestResponse>),
+        (status = 401, description = "Unauthorized"),
+    ),
+    tag = "user",
+    security(("jwt_cookie" = []))
+)]
+pub(crate) async fn list(
+    State(state): State<Arc<AppState>>,
+    Extension(user): Extension<db::User>,
+) -> Result<impl IntoResponse, ResponseError> {
+    let digests = state.db.list_notification_digests(&user.id).await.map_err(ResponseError::from)?;
+
+    Ok(Json(with_defaults(&digests)))
+}
+
+/// Every channel that supports digests; channels without a stored row are immediate.
+fn with_defaults(digests: &[NotificationDigest]) -> Vec<GetNotificationDigestResponse> {
+    DIGEST_CHANNELS.into_iter()
+        .map(|channel| GetNotificationDigestResponse::new(
+            channel,
+            digests.iter().find(|digest| digest.channel == channel.as_str()),
+        ))
+        .collect()
+}
+
+#[derive(Deserialize, utoipa::ToSchema)]
+pub(crate) struct NotificationDigestRequest {
+    pub frequency: DigestFrequency,
+}
+
+fn parse_channel(channel: &str) -> Result<NotificationChannel, ResponseError> {
+    match NotificationChannel::parse(channel) {
+        Some(channel) if DIGEST_CHANNELS.contains(&channel) => Ok(channel),
+        Some(channel) => Err(ResponseError::Bad(format!("{} notifications are always sent immediately", channel.as_str()))),
+        None => Err(ResponseError::NotFound),
+    }
+}
+
+#[utoipa::path(
+    put,
+    path = "/user/notification_digest/{channel}",
+    params(("channel" = String, Path, description = "email or telegram")),
+    request_body = NotificationDigestRequest,
+    responses(
+        (status = 200, description = "Saved frequency; events already queued go out with the next digest", body = GetNotificationDigestResponse),
+        (status = 400, description = "The channel has no digests"),
+        (status = 404, description = "Unknown channel"),
+        (status = 401, description = "Unauthorized"),
+    ),
+    tag = "user",
+    security(("jwt_cookie" = []))
+)]
+pub(crate) async fn upsert(
+    State(state): State<Arc<AppState>>,
+    Extension(user): Extension<db::User>,
+    Path(channel): Path<String>,
+    Json(payload): Json<NotificationDigestRequest>,
+) -> Result<impl IntoResponse, ResponseError> {
+    let channel = parse_channel(&channel)?;
+
+    let digest = state.db
+        .upsert_notification_digest(&user.id, channel.as_str(), payload.frequency.as_str())
+        .await
+        .map_err(ResponseError::from)?;
+
+    Ok(Json(GetNotificationDigestResponse::new(channel, Some(&digest))))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+    use crate::api::state::DB;
+
+    #[rstest]
+    #[case("email", Some(NotificationChannel::Email))]
+    #[case("telegram", Some(NotificationChannel::Telegram))]
+    #[case("chat", None)]
+    #[case("sms", None)]
+    fn test_parse_channel(#[case] channel: &str, #[case] expected: Option<NotificationChannel>) {
+        assert_eq!(parse_channel(channel).ok(), expected);
+    }
+
+    #[tokio::test]
+    async fn test_list_fills_in_defaults() {
+        let db = DB::in_memory();
+        let user = db.get_or_create_user("seller", None).await.unwrap();
+        db.upsert_notification_digest(&user.id, "telegram", "daily").await.unwrap();
+        db.upsert_notification_digest(&user.id, "telegram", "weekly").await.unwrap();
+
+        let digests = with_defaults(&db.list_notification_digests(&user.id).await.unwrap());
+        let frequencies = digests.iter().map(|digest| (digest.channel, digest.frequency)).collect::<Vec<_>>();
+        assert_eq!(frequencies, vec![
+            (NotificationChannel::Email, DigestFrequency::Immediate),
+            (NotificationChannel::Telegram, DigestFrequency::Weekly),
+        ]);
+    }
+}
@@ -0,0 +1,253 @@
+use std::sync::Arc;
+use axum::{Extension, Json, Router};
+use axum::extract::{Path, State};
+use axum::http::StatusCode;
+use axum::response::IntoResponse;
+use axum::routing::{get, put};
+use bigdecimal::{BigDecimal, Zero};
+use chrono::NaiveDateTime;
+use serde::{Deserialize, Serialize};
+use crate::api::ping_pong::ping_pong;
+use crate::api::response_error::ResponseError;
+use crate::api::state::AppState;
+use crate::db;
+use crate::db::notification_preference::NotificationPreference;
+use crate::events::notifications::preferences::{NotificationChannel, NotificationEvent, Preferences};
+
+const MAX_PREFIX_LENGTH: usize = 64;
+
+pub fn get_router(app_state: Arc<AppState>) -> Router<Arc<AppState>> {
+    Router::new()
+        .route("/ping", get(ping_pong))
+        .route("/", get(list))
+        .route("/:channel/:event", put(upsert).delete(destroy))
+        .with_state(app_state)
+}
+
+#[derive(Serialize, utoipa::ToSchema)]
+pub(crate) struct GetNotificationPreferenceResponse {
+    pub channel: NotificationChannel,
+    pub event: NotificationEvent,
+    pub enabled: bool,
+    /// Invoices below it are not notified.
+    #[schema(value_type = Option<String>, example = "100")]
+    pub min_amount: Option<BigDecimal>,
+    /// Only invoices whose `external_id` starts with it are notified.
+    #[schema(example = "shop-")]
+    pub external_id_prefix: Option<String>,
+    /// `None` while the event's default applies.
+    pub updated_at: Option<NaiveDateTime>,
+}
+
+impl GetNotificationPreferenceResponse {
+    fn new(channel: NotificationChannel, event: NotificationEvent, stored: Option<&NotificationPreference>) -> Self {
+        Self {
+            channel,
+            event,
+            enabled: stored.map_or(event.enabled_by_default(), |preference| preference.enabled),
+            min_amount: stored.and_then(|preference| preference.min_amount.clone()),
+            external_id_prefix: stored.and_then(|preference| preference.external_id_prefix.clone()),
+            updated_at: stored.map(|preference| preference.updated_at),
+        }
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/user/notification_preference",
+    responses(
+        (status = 200, description = "Every channel and event, with the stored preference or the default", body = Vec<GetNotificationPreferenceResponse>),
+        (status = 401, description = "Unauthorized"),
+    ),
+    tag = "user",
+    security(("jwt_cookie" = []))
+)]
+pub(crate) async fn list(
+    State(state): State<Arc<AppState>>,
+    Extension(user): Extension<db::User>,
+) -> Result<impl IntoResponse, ResponseError> {
+    let preferences = Preferences::new(state.db.list_notification_preferences(&user.id).await.map_err(ResponseError::from)?);
+
+    Ok(Json(with_defaults(&preferences)))
+}
+
+/// Every channel and event, in catalog order.
+fn with_defaults(preferences: &Preferences) -> Vec<GetNotificationPreferenceResponse> {
+    NotificationChannel::ALL.into_iter()
+        .flat_map(|channel| NotificationEvent::ALL.into_iter().map(move |event| (channel, event)))
+        .map(|(channel, event)| GetNotificationPreferenceResponse::new(channel, event, preferences.get(channel, event)))
+        .collect()
+}
+
+#[derive(Deserialize, utoipa::ToSchema)]
+pub(crate) struct NotificationPreferenceRequest {
+    pub enabled: bool,
+    /// Only invoices of at least this amount; `0` or omitted for any amount.
+    #[schema(value_type = Option<String>, example = "100")]
+    pub min_amount: Option<BigDecimal>,
+    /// Only invoices whose `external_id` starts with it.
+    #[schema(example = "shop-")]
+    pub external_id_prefix: Option<String>,
+}
+
+impl NotificationPreferenceRequest {
+    /// The rules without no-op values; rejected for events that are not about an invoice.
+    fn rules(&self, event: NotificationEvent) -> Result<(Option<BigDecimal>, Option<String>), String> {
+        let min_amount = match &self.min_amount {
+            Some(amount) if amount < &BigDecimal::zero() => return Err("min_amount must not be negative".to_string()),
+            Some(amount) if amount.is_zero() => None,
+            amount => amount.clone(),
+        };
+        let prefix = match self.external_id_prefix.as_deref().map(str::trim) {
+            Some(prefix) if prefix.chars().count() > MAX_PREFIX_LENGTH => {
+                return Err(format!("external_id_prefix must be at most {MAX_PREFIX_LENGTH} characters"));
+            }
+            Some("") | None => None,
+            Some(prefix) => Some(prefix.to_string()),
+        };
+
+        match (min_amount.is_some() || prefix.is_some()) && !event.is_about_invoice() {
+            true => Err(format!("{} is not about an invoice, so it takes no rules", event.as_str())),
+            false => Ok((min_amount, prefix)),
+        }
+    }
+}
+
+fn parse_path(channel: &str, event: &str) -> Result<(NotificationChannel, NotificationEvent), ResponseError> {
+    NotificationChannel::parse(channel)
+        .zip(NotificationEvent::parse(event))
+        .ok_or(ResponseError::NotFound)
+}
+
+#[utoipa::path(
+    put,
+    path = "/user/notification_preference/{channel}/{event}",
+    params(
+        ("channel" = String, Path, description = "email, telegram or chat"),
+        ("event" = String, Path, description = "Event, e.g. invoice.paid"),
+    ),
+    request_body = NotificationPreferenceRequest,
+    responses(
+        (status = 200, description = "Saved preference", body = GetNotificationPreferenceResponse),
+        (status = 400, description = "Invalid rules"),
+        (status = 404, description = "Unknown channel or event"),
+        (status = 401, description = "Unauthorized"),
+    ),
+    tag = "user",
+    security(("jwt_cookie" = []))
+)]
+pub(crate) async fn upsert(
+    State(state): State<Arc<AppState>>,
+    Extension(user): Extension<db::User>,
+    Path((channel, event)): Path<(String, String)>,
+    Json(payload): Json<NotificationPreferenceRequest>,
+) -> Result<impl IntoResponse, ResponseError> {
+    let (channel, event) = parse_path(&channel, &event)?;
+    let (min_amount, external_id_prefix) = payload.rules(event).map_err(ResponseError::Bad)?;
+
+    let preference = state.db
+        .upsert_notification_preference(&NotificationPreference {
+            user_id: user.id,
+            channel: channel.as_str().to_string(),
+            event_type: event.as_str().to_string(),
+            enabled: payload.enabled,
+            min_amount,
+            external_id_prefix,
+            updated_at: NaiveDateTime::default(),
+        })
+        .await
+        .map_err(ResponseError::from)?;
+
+    Ok(Json(GetNotificationPreferenceResponse::new(channel, event, Some(&preference))))
+}
+
+#[utoipa::path(
+    delete,
+    path = "/user/notification_preference/{channel}/{event}",
+    params(
+        ("channel" = String, Path, description = "email, telegram or chat"),
+        ("event" = String, Path, description = "Event, e.g. invoice.paid"),
+    ),
+    responses(
+        (status = 204, description = "Preference deleted; the event's default applies again"),
+        (status = 404, description = "Not found"),
+        (status = 401, description = "Unauthorized"),
+    ),
+    tag = "user",
+    security(("jwt_cookie" = []))
+)]
+pub(crate) async fn destroy(
+    State(state): State<Arc<AppState>>,
+    Extension(user): Extension<db::User>,
+    Path((channel, event)): Path<(String, String)>,
+) -> Result<impl IntoResponse, ResponseError> {
+    let (channel, event) = parse_path(&channel, &event)?;
+
+    Ok(match state.db.delete_notification_preference(&user.id, channel.as_str(), event.as_str())
+        .await
+        .map_err(ResponseError::from)? {
+        true => StatusCode::NO_CONTENT,
+        false => return Err(ResponseError::NotFound),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+    use crate::api::state::DB;
+
+    fn request(min_amount: Option<&str>, prefix: Option<&str>) -> NotificationPreferenceRequest {
+        NotificationPreferenceRequest {
+            enabled: true,
+            min_amount: min_amount.map(|amount| amount.parse().unwrap()),
+            external_id_prefix: prefix.map(str::to_string),
+        }
+    }
+
+    #[rstest]
+    #[case(None, None, NotificationEvent::InvoicePaid, Ok((None, None)))]
+    #[case(Some("100"), Some(" shop- "), NotificationEvent::InvoicePaid, Ok((Some("100"), Some("shop-"))))]
+    #[case(Some("0"), Some(""), NotificationEvent::SubscriptionExpiring, Ok((None, None)))]
+    #[case(Some("-1"), None, NotificationEvent::InvoicePaid, Err(()))]
+    #[case(Some("100"), None, NotificationEvent::SubscriptionExpiring, Err(()))]
+    #[case(None, Some("order-reference-prefix-used-by-the-northern-warehouse-team-shop-front"), NotificationEvent::InvoicePaid, Err(()))]
+    fn test_rules(
+        #[case] min_amount: Option<&str>,
+        #[case] prefix: Option<&str>,
+        #[case] event: NotificationEvent,
+        #[case] expected: Result<(Option<&str>, Option<&str>), ()>,
+    ) {
+        let expected = expected.map(|(amount, prefix)| (amount.map(|amount| amount.parse().unwrap()), prefix.map(str::to_string)));
+        assert_eq!(request(min_amount, prefix).rules(event).map_err(|_| ()), expected);
+    }
+
+    #[tokio::test]
+    async fn test_list_fills_in_defaults() {
+        let db = DB::in_memory();
+        let user = db.get_or_create_user("seller", None).await.unwrap();
+        let (min_amount, external_id_prefix) = request(Some("100"), Some("shop-")).rules(NotificationEvent::InvoiceExpired).unwrap();
+        db.upsert_notification_preference(&NotificationPreference {
+            user_id: user.id,
+            channel: "telegram".to_string(),
+            event_type: "invoice.expired".to_string(),
+            enabled: true,
+            min_amount,
+            external_id_prefix,
+            updated_at: NaiveDateTime::default(),
+        }).await.unwrap();
+
+        let preferences = with_defaults(&Preferences::new(db.list_notification_preferences(&user.id).await.unwrap()));
+        assert_eq!(preferences.len(), NotificationChannel::ALL.len() * NotificationEvent::ALL.len());
+
+        let find = |channel, event| preferences.iter()
+            .find(|preference| preference.channel == channel && preference.event == event)
+            .unwrap();
+        assert!(find(NotificationChannel::Email, NotificationEvent::InvoicePaid).enabled);
+        let default = find(NotificationChannel::Email, NotificationEvent::InvoiceExpired);
+        assert!(!default.enabled && default.updated_at.is_none());
+        let stored = find(NotificationChannel::Telegram, NotificationEvent::InvoiceExpired);
+        assert!(stored.enabled && stored.updated_at.is_some());
+        assert_eq!(stored.external_id_prefix.as_deref(), Some("shop-"));
+    }
+}
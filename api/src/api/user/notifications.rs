@@ -0,0 +1,247 @@
+use std::sync::Arc;
+use axum::{Extension, Json, Router};
+use axum::extract::{Path, Query, State};
+use axum::http::StatusCode;
+use axum::response::IntoResponse;
+use axum::routing::{get, post};
+use chrono::NaiveDateTime;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+use crate::api::ping_pong::ping_pong;
+use crate::api::response_error::ResponseError;
+use crate::api::state::AppState;
+use crate::api::utils::Pagination;
+use crate::db;
+use crate::db::notification_log::{NotificationLog, NotificationLogFilter, NotificationLogStatus, DIGEST_EVENT, RECEIPT_EVENT};
+use crate::events::notifications::preferences::{NotificationChannel, NotificationEvent};
+
+pub fn get_router(app_state: Arc<AppState>) -> Router<Arc<AppState>> {
+    Router::new()
+        .route("/ping", get(ping_pong))
+        .route("/", get(list))
+        .route("/:id/resend", post(resend))
+        .with_state(app_state)
+}
+
+#[derive(Serialize, utoipa::ToSchema)]
+pub(crate) struct GetNotificationLogResponse {
+    pub id: Uuid,
+    /// `email`, `telegram` or `chat`.
+    pub channel: String,
+    /// A notification event, e.g. `invoice.paid`, or `digest`.
+    #[schema(example = "invoice.paid")]
+    pub event: String,
+    pub invoice_id: Option<Uuid>,
+    /// The masked email address or the chat's label.
+    #[schema(example = "j***@example.com")]
+    pub destination: String,
+    /// `sent`, `failed` or `pending` (resend requested).
+    pub status: String,
+    pub error: Option<String>,
+    pub attempts: i32,
+    /// Failed notifications about an invoice can be resent.
+    pub resendable: bool,
+    /// When the requested resend is due, for pending notifications.
+    pub next_attempt_at: Option<NaiveDateTime>,
+    pub created_at: NaiveDateTime,
+    /// Time of the last attempt.
+    pub updated_at: NaiveDateTime,
+    pub sent_at: Option<NaiveDateTime>,
+}
+
+impl From<NotificationLog> for GetNotificationLogResponse {
+    fn from(value: NotificationLog) -> Self {
+        Self {
+            resendable: is_resendable(&value),
+            next_attempt_at: value.next_attempt_at,
+            id: value.id,
+            channel: value.channel,
+            event: value.event_type,
+            invoice_id: value.invoice_id,
+            destination: value.destination,
+            status: value.status,
+            error: value.error,
+            attempts: value.attempts,
+            created_at: value.created_at,
+            updated_at: value.updated_at,
+            sent_at: value.sent_at,
+        }
+    }
+}
+
+fn is_resendable(log: &NotificationLog) -> bool {
+    log.status == NotificationLogStatus::Failed.as_str() && log.invoice_id.is_some()
+}
+
+#[derive(Deserialize)]
+pub(crate) struct NotificationLogQuery {
+    pub channel: Option<String>,
+    pub event: Option<String>,
+    pub status: Option<String>,
+    pub invoice_id: Option<Uuid>,
+}
+
+impl NotificationLogQuery {
+    fn filter(self) -> Result<NotificationLogFilter, String> {
+        if let Some(channel) = self.channel.as_deref().filter(|channel| NotificationChannel::parse(channel).is_none()) {
+            return Err(format!("Unknown channel {channel}"));
+        }
+        if let Some(event) = self.event.as_deref().filter(|event| ![DIGEST_EVENT, RECEIPT_EVENT].contains(event) && NotificationEvent::parse(event).is_none()) {
+            return Err(format!("Unknown event {event}"));
+        }
+        if let Some(status) = self.status.as_deref().filter(|status| !NotificationLogStatus::ALL.iter().any(|known| known.as_str() == *status)) {
+            return Err(format!("Unknown status {status}"));
+        }
+
+        Ok(NotificationLogFilter {
+            channel: self.channel,
+            event_type: self.event,
+            status: self.status,
+            invoice_id: self.invoice_id,
+        })
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/user/notifications",
+    params(
+        ("channel" = Option<String>, Query, description = "email, telegram or chat"),
+        ("event" = Option<String>, Query, description = "Event, e.g. invoice.paid, or digest"),
+        ("status" = Option<String>, Query, description = "sent, failed or pending"),
+        ("invoice_id" = Option<Uuid>, Query, description = "Invoice ID"),
+        ("limit" = Option<i64>, Query, description = "Max 100"),
+        ("offset" = Option<i64>, Query, description = "Number of notifications to skip"),
+    ),
+    responses(
+        (status = 200, description = "Email, Telegram and chat notifications, newest first", body = Vec<GetNotificationLogResponse>),
+        (status = 400, description = "Unknown filter value"),
+        (status = 401, description = "Unauthorized"),
+    ),
+    tag = "user",
+    security(("jwt_cookie" = []))
+)]
+pub(crate) async fn list(
+    State(state): State<Arc<AppState>>,
+    Extension(user): Extension<db::User>,
+    Query(pagination): Query<Pagination>,
+    Query(query): Query<NotificationLogQuery>,
+) -> Result<impl IntoResponse, ResponseError> {
+    let (limit, offset) = pagination.get_valid(100)?;
+    let filter = query.filter().map_err(ResponseError::Bad)?;
+
+    Ok(Json(state.db
+        .list_notification_logs(&user.id, &filter, limit, offset)
+        .await
+        .map_err(ResponseError::from)?
+        .into_iter()
+        .map(GetNotificationLogResponse::from)
+        .collect::<Vec<_>>()))
+}
+
+#[utoipa::path(
+    post,
+    path = "/user/notifications/{id}/resend",
+    params(("id" = Uuid, Path, description = "Notification ID")),
+    responses(
+        (status = 202, description = "Queued to be sent again to the same address or chat"),
+        (status = 400, description = "Only failed notifications about an invoice can be resent"),
+        (status = 404, description = "Not found"),
+        (status = 401, description = "Unauthorized"),
+    ),
+    tag = "user",
+    security(("jwt_cookie" = []))
+)]
+pub(crate) async fn resend(
+    State(state): State<Arc<AppState>>,
+    Extension(user): Extension<db::User>,
+    Path(id): Path<Uuid>,
+) -> Result<impl IntoResponse, ResponseError> {
+    let log = state.db.get_notification_log(&id, &user.id)
+        .await
+        .map_err(ResponseError::from)?
+        .ok_or(ResponseError::NotFound)?;
+    if !is_resendable(&log) {
+        return Err(ResponseError::Bad("Only failed notifications about an invoice can be resent".to_string()));
+    }
+
+    Ok(match state.db.resend_notification(&id, &user.id)
+        .await
+        .map_err(ResponseError::from)? {
+        true => StatusCode::ACCEPTED,
+        // Someone else queued it in the meantime.
+        false => return Err(ResponseError::Bad("The notification is already being resent".to_string())),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+    use rstest::rstest;
+    use crate::api::state::DB;
+    use crate::db::notification_log::NewNotificationLog;
+
+    fn query(channel: Option<&str>, event: Option<&str>, status: Option<&str>) -> NotificationLogQuery {
+        NotificationLogQuery {
+            channel: channel.map(str::to_string),
+            event: event.map(str::to_string),
+            status: status.map(str::to_string),
+            invoice_id: None,
+        }
+    }
+
+    #[rstest]
+    #[case(None, None, None, true)]
+    #[case(Some("telegram"), Some("invoice.paid"), Some("failed"), true)]
+    #[case(Some("email"), Some("digest"), Some("sent"), true)]
+    #[case(Some("email"), Some("receipt"), Some("failed"), true)]
+    #[case(Some("sms"), None, None, false)]
+    #[case(None, Some("invoice.created"), None, false)]
+    #[case(None, None, Some("delivered"), false)]
+    fn test_filter(#[case] channel: Option<&str>, #[case] event: Option<&str>, #[case] status: Option<&str>, #[case] valid: bool) {
+        assert_eq!(query(channel, event, status).filter().is_ok(), valid);
+    }
+
+    fn log(user_id: Uuid, event_type: &str, error: Option<&str>) -> NewNotificationLog {
+        NewNotificationLog {
+            user_id,
+            channel: "email".to_string(),
+            event_type: event_type.to_string(),
+            invoice_id: Some(Uuid::new_v4()),
+            destination_id: None,
+            destination: "s***@example.com".to_string(),
+            transfer: None,
+            error: error.map(str::to_string),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_failed_notification_is_resent_once() {
+        let db = DB::in_memory();
+        let user_id = Uuid::new_v4();
+        let sent = db.create_notification_log(&log(user_id, "invoice.paid", None)).await.unwrap();
+        let failed = db.create_notification_log(&log(user_id, "invoice.paid", Some("mailbox full"))).await.unwrap();
+        let digest = db.create_notification_log(&NewNotificationLog { invoice_id: None, ..log(user_id, DIGEST_EVENT, Some("mailbox full")) }).await.unwrap();
+        let notice = db.create_notification_log(&NewNotificationLog { invoice_id: None, ..log(user_id, "subscription.expiring", Some("mailbox full")) }).await.unwrap();
+        assert!(!is_resendable(&sent) && is_resendable(&failed) && !is_resendable(&digest) && !is_resendable(&notice));
+
+        let filter = query(None, None, Some("failed")).filter().unwrap();
+        assert_eq!(db.list_notification_logs(&user_id, &filter, 10, 0).await.unwrap().len(), 3);
+
+        assert!(!db.resend_notification(&digest.id, &user_id).await.unwrap());
+        assert!(!db.resend_notification(&notice.id, &user_id).await.unwrap());
+        assert!(!db.resend_notification(&failed.id, &Uuid::new_v4()).await.unwrap());
+        assert!(db.resend_notification(&failed.id, &user_id).await.unwrap());
+        assert!(!db.resend_notification(&failed.id, &user_id).await.unwrap());
+
+        let claimed = db.claim_pending_notifications(10, Duration::from_secs(60)).await.unwrap();
+        assert_eq!(claimed.iter().map(|log| log.id).collect::<Vec<_>>(), vec![failed.id]);
+        assert!(db.claim_pending_notifications(10, Duration::from_secs(60)).await.unwrap().is_empty());
+
+        db.record_notification_attempt(&failed.id, None).await.unwrap();
+        let resent = db.get_notification_log(&failed.id, &user_id).await.unwrap().unwrap();
+        assert_eq!((resent.status.as_str(), resent.attempts, resent.error), ("sent", 2, None));
+        assert!(resent.sent_at.is_some());
+    }
+}
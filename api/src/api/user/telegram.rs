@@ -0,0 +1,174 @@
+use std::sync::Arc;
+use axum::{Extension, Json, Router};
+use axum::extract::{Path, State};
+use axum::http::StatusCode;
+use axum::response::IntoResponse;
+use axum::routing::{get, patch};
+use bigdecimal::{BigDecimal, Zero};
+use chrono::NaiveDateTime;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+use crate::api::ping_pong::ping_pong;
+use crate::api::response_error::ResponseError;
+use crate::api::state::AppState;
+use crate::api::user::webhook::event_type_names;
+use crate::db::User;
+use crate::db::telegram_destination::TelegramDestination;
+use crate::events::webhook_event::WebhookEventType;
+
+const MAX_LABEL_LENGTH: usize = 64;
+
+pub fn get_router(app_state: Arc<AppState>) -> Router<Arc<AppState>> {
+    Router::new()
+        .route("/ping", get(ping_pong))
+        .route("/", get(list).delete(unlink_all))
+        .route("/:destination_id", patch(update).delete(destroy))
+        .with_state(app_state)
+}
+
+#[derive(Serialize)]
+pub(crate) struct GetTelegramDestinationResponse {
+    pub id: Uuid,
+    pub label: String,
+    /// `private`, `group`, `supergroup` or `channel`.
+    pub chat_type: String,
+    pub events: Vec<String>,
+    /// Invoices below it are not notified to this chat.
+    pub min_amount: Option<BigDecimal>,
+    pub created_at: NaiveDateTime,
+}
+
+impl From<TelegramDestination> for GetTelegramDestinationResponse {
+    fn from(value: TelegramDestination) -> Self {
+        Self {
+            id: value.id,
+            label: value.label,
+            chat_type: value.chat_type,
+            events: value.event_types,
+            min_amount: value.min_amount,
+            created_at: value.created_at,
+        }
+    }
+}
+
+/// Chats, groups and channels linked with `/user/attach_telegram`, oldest first.
+async fn list(
+    State(state): State<Arc<AppState>>,
+    Extension(user): Extension<User>,
+) -> Result<impl IntoResponse, ResponseError> {
+    let destinations = state.db
+        .list_telegram_destinations(&user.id)
+        .await
+        .map_err(ResponseError::from)?
+        .into_iter()
+        .map(GetTelegramDestinationResponse::from)
+        .collect::<Vec<_>>();
+
+    Ok(Json(destinations))
+}
+
+#[derive(Deserialize)]
+pub(crate) struct UpdateTelegramDestinationRequest {
+    pub label: Option<String>,
+    pub events: Option<Vec<WebhookEventType>>,
+    /// `0` removes the threshold.
+    pub min_amount: Option<BigDecimal>,
+}
+
+impl UpdateTelegramDestinationRequest {
+    fn label(&self) -> Result<Option<&str>, String> {
+        match self.label.as_deref().map(str::trim) {
+            Some("") => Err("label must not be empty".to_string()),
+            Some(label) if label.chars().count() > MAX_LABEL_LENGTH => Err(format!("label must be at most {MAX_LABEL_LENGTH} characters")),
+            label => Ok(label),
+        }
+    }
+
+    fn min_amount(&self) -> Result<Option<Option<BigDecimal>>, String> {
+        match &self.min_amount {
+            Some(amount) if amount < &BigDecimal::zero() => Err("min_amount must not be negative".to_string()),
+            Some(amount) if amount.is_zero() => Ok(Some(None)),
+            amount => Ok(amount.clone().map(Some)),
+        }
+    }
+}
+
+async fn update(
+    State(state): State<Arc<AppState>>,
+    Extension(user): Extension<User>,
+    Path(destination_id): Path<Uuid>,
+    Json(payload): Json<UpdateTelegramDestinationRequest>,
+) -> Result<impl IntoResponse, ResponseError> {
+    let label = payload.label().map_err(ResponseError::Bad)?;
+    let min_amount = payload.min_amount().map_err(ResponseError::Bad)?;
+    let event_types = payload.events
+        .as_deref()
+        .map(event_type_names)
+        .transpose()
+        .map_err(ResponseError::Bad)?;
+
+    let destination: GetTelegramDestinationResponse = state.db
+        .update_telegram_destination(&destination_id, &user.id, label, event_types.as_deref(), min_amount)
+        .await
+        .map_err(ResponseError::from)?
+        .ok_or(ResponseError::NotFound)?
+        .into();
+
+    Ok(Json(destination))
+}
+
+/// Unlinks one chat; the bot stays in groups and channels but no longer posts there.
+async fn destroy(
+    State(state): State<Arc<AppState>>,
+    Extension(user): Extension<User>,
+    Path(destination_id): Path<Uuid>,
+) -> Result<impl IntoResponse, ResponseError> {
+    Ok(match state.db.delete_telegram_destination(&destination_id, &user.id)
+        .await
+        .map_err(ResponseError::from)? {
+        true => StatusCode::NO_CONTENT,
+        false => return Err(ResponseError::NotFound),
+    })
+}
+
+async fn unlink_all(
+    State(state): State<Arc<AppState>>,
+    Extension(user): Extension<User>,
+) -> Result<impl IntoResponse, ResponseError> {
+    state.db.unlink_telegram_chats(&user.id).await.map_err(ResponseError::from)?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    fn request(label: Option<&str>, min_amount: Option<&str>) -> UpdateTelegramDestinationRequest {
+        UpdateTelegramDestinationRequest {
+            label: label.map(str::to_string),
+            events: None,
+            min_amount: min_amount.map(|amount| amount.parse().unwrap()),
+        }
+    }
+
+    #[rstest]
+    #[case(None, Ok(None))]
+    #[case(Some(" Team "), Ok(Some("Team")))]
+    #[case(Some("  "), Err(()))]
+    #[case(Some("Payments of the northern warehouse team, including weekend night shifts"), Err(()))]
+    fn test_label(#[case] label: Option<&str>, #[case] expected: Result<Option<&str>, ()>) {
+        assert_eq!(request(label, None).label().map_err(|_| ()), expected);
+    }
+
+    #[rstest]
+    #[case(None, Ok(None))]
+    #[case(Some("0"), Ok(Some(None)))]
+    #[case(Some("25.5"), Ok(Some(Some("25.5"))))]
+    #[case(Some("-1"), Err(()))]
+    fn test_min_amount(#[case] amount: Option<&str>, #[case] expected: Result<Option<Option<&str>>, ()>) {
+        let expected = expected.map(|amount| amount.map(|amount| amount.map(|amount| amount.parse::<BigDecimal>().unwrap())));
+        assert_eq!(request(None, amount).min_amount().map_err(|_| ()), expected);
+    }
+}
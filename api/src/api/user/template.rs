@@ -0,0 +1,183 @@
+use std::sync::Arc;
+use axum::{Extension, Json, Router};
+use axum::extract::{Path, State};
+use axum::http::StatusCode;
+use axum::response::IntoResponse;
+use axum::routing::{get, put};
+use chrono::NaiveDateTime;
+use serde::{Deserialize, Serialize};
+use crate::api::ping_pong::ping_pong;
+use crate::api::response_error::ResponseError;
+use crate::api::state::AppState;
+use crate::db;
+use crate::db::notification_template::NotificationTemplate;
+use crate::events::notifications::templates::{self, TemplateKind, LOCALES, VARIABLES};
+
+pub fn get_router(app_state: Arc<AppState>) -> Router<Arc<AppState>> {
+    Router::new()
+        .route("/ping", get(ping_pong))
+        .route("/", get(list))
+        .route("/:kind/:locale", put(upsert).delete(destroy))
+        .with_state(app_state)
+}
+
+#[derive(Serialize, utoipa::ToSchema)]
+pub(crate) struct GetNotificationTemplateResponse {
+    pub kind: String,
+    pub locale: String,
+    pub subject: Option<String>,
+    pub text_body: Option<String>,
+    pub html_body: Option<String>,
+    pub telegram_body: Option<String>,
+    pub updated_at: NaiveDateTime,
+}
+
+impl From<NotificationTemplate> for GetNotificationTemplateResponse {
+    fn from(value: NotificationTemplate) -> Self {
+        Self {
+            kind: value.kind,
+            locale: value.locale,
+            subject: value.subject,
+            text_body: value.text_body,
+            html_body: value.html_body,
+            telegram_body: value.telegram_body,
+            updated_at: value.updated_at,
+        }
+    }
+}
+
+#[derive(Serialize, utoipa::ToSchema)]
+pub(crate) struct ListNotificationTemplatesResponse {
+    /// The user's overrides; everything else uses the built-in templates.
+    pub templates: Vec<GetNotificationTemplateResponse>,
+    #[schema(example = json!(["invoice_paid", "invoice_partially_paid", "payment_anomaly"]))]
+    pub kinds: Vec<&'static str>,
+    #[schema(example = json!(["en", "es", "ru"]))]
+    pub locales: Vec<&'static str>,
+    /// Usable as `{{name}}`; `{{#name}}...{{/name}}` is kept only when the variable is not empty.
+    pub variables: Vec<&'static str>,
+}
+
+#[utoipa::path(
+    get,
+    path = "/user/template",
+    responses(
+        (status = 200, description = "Notification template overrides with the kinds, locales and variables available", body = ListNotificationTemplatesResponse),
+        (status = 401, description = "Unauthorized"),
+    ),
+    tag = "user",
+    security(("jwt_cookie" = []))
+)]
+pub(crate) async fn list(
+    State(state): State<Arc<AppState>>,
+    Extension(user): Extension<db::User>,
+) -> Result<impl IntoResponse, ResponseError> {
+    let templates = state.db
+        .list_notification_templates(&user.id)
+        .await
+        .map_err(ResponseError::from)?
+        .into_iter()
+        .map(GetNotificationTemplateResponse::from)
+        .collect();
+
+    Ok(Json(ListNotificationTemplatesResponse {
+        templates,
+        kinds: TemplateKind::ALL.iter().map(TemplateKind::as_str).collect(),
+        locales: LOCALES.to_vec(),
+        variables: VARIABLES.to_vec(),
+    }))
+}
+
+/// Omitted fields keep the built-in template.
+#[derive(Deserialize, utoipa::ToSchema)]
+pub(crate) struct NotificationTemplateRequest {
+    #[schema(example = "Order {{external_id}} is paid")]
+    pub subject: Option<String>,
+    pub text_body: Option<String>,
+    pub html_body: Option<String>,
+    /// Telegram MarkdownV2; variables are escaped, the rest must be valid MarkdownV2.
+    #[schema(example = "*{{amount}} USDT* received for `{{external_id}}`")]
+    pub telegram_body: Option<String>,
+}
+
+fn parse_path(kind: &str, locale: &str) -> Result<TemplateKind, ResponseError> {
+    if !LOCALES.contains(&locale) {
+        return Err(ResponseError::Bad(format!("unsupported locale {locale}, expected one of {}", LOCALES.join(", "))));
+    }
+    TemplateKind::parse(kind).ok_or(ResponseError::NotFound)
+}
+
+#[utoipa::path(
+    put,
+    path = "/user/template/{kind}/{locale}",
+    params(
+        ("kind" = String, Path, description = "Notification kind, e.g. invoice_paid"),
+        ("locale" = String, Path, description = "Locale, e.g. en"),
+    ),
+    request_body = NotificationTemplateRequest,
+    responses(
+        (status = 200, description = "Saved override", body = GetNotificationTemplateResponse),
+        (status = 400, description = "Unsupported locale or invalid template"),
+        (status = 404, description = "Unknown kind"),
+        (status = 401, description = "Unauthorized"),
+    ),
+    tag = "user",
+    security(("jwt_cookie" = []))
+)]
+pub(crate) async fn upsert(
+    State(state): State<Arc<AppState>>,
+    Extension(user): Extension<db::User>,
+    Path((kind, locale)): Path<(String, String)>,
+    Json(payload): Json<NotificationTemplateRequest>,
+) -> Result<impl IntoResponse, ResponseError> {
+    let kind = parse_path(&kind, &locale)?;
+    let non_empty = |value: Option<String>| value.filter(|value| !value.trim().is_empty());
+    let template = NotificationTemplate {
+        user_id: user.id,
+        kind: kind.as_str().to_string(),
+        locale,
+        subject: non_empty(payload.subject),
+        text_body: non_empty(payload.text_body),
+        html_body: non_empty(payload.html_body),
+        telegram_body: non_empty(payload.telegram_body),
+        updated_at: NaiveDateTime::default(),
+    };
+    templates::validate(&template).map_err(ResponseError::Bad)?;
+
+    let template = state.db
+        .upsert_notification_template(&template)
+        .await
+        .map_err(ResponseError::from)?;
+
+    Ok(Json(GetNotificationTemplateResponse::from(template)))
+}
+
+#[utoipa::path(
+    delete,
+    path = "/user/template/{kind}/{locale}",
+    params(
+        ("kind" = String, Path, description = "Notification kind, e.g. invoice_paid"),
+        ("locale" = String, Path, description = "Locale, e.g. en"),
+    ),
+    responses(
+        (status = 204, description = "Override deleted; the built-in template is used again"),
+        (status = 404, description = "Not found"),
+        (status = 401, description = "Unauthorized"),
+    ),
+    tag = "user",
+    security(("jwt_cookie" = []))
+)]
+pub(crate) async fn destroy(
+    State(state): State<Arc<AppState>>,
+    Extension(user): Extension<db::User>,
+    Path((kind, locale)): Path<(String, String)>,
+) -> Result<impl IntoResponse, ResponseError> {
+    let kind = parse_path(&kind, &locale)?;
+
+    Ok(match state.db.delete_notification_template(&user.id, kind.as_str(), &locale)
+        .await
+        .map_err(ResponseError::from)? {
+        true => StatusCode::NO_CONTENT,
+        false => return Err(ResponseError::NotFound),
+    })
+}
@@ -1,30 +1,39 @@
-use std::net::IpAddr;
+use std::collections::HashMap;
 use std::sync::Arc;
+use std::time::{Duration, Instant};
 use axum::{Extension, Json, middleware, Router};
-use axum::extract::{Path, State};
+use axum::extract::{Path, Query, State};
 use axum::http::StatusCode;
 use axum::response::IntoResponse;
-use axum::routing::{delete, get, post};
+use axum::routing::{delete, get, patch, post};
 use chrono::NaiveDateTime;
 use serde::{Deserialize, Serialize};
-use serde_json::json;
+use serde_json::{json, Value};
 use url::Url;
 use uuid::Uuid;
 use crate::api::middleware::rate_limiting::middleware::RateLimitType;
 use crate::api::ping_pong::ping_pong;
 use crate::api::state::AppState;
 use crate::api::response_error::ResponseError;
+use crate::api::utils::Pagination;
 use crate::db::{User, Webhook};
+use crate::db::webhook_delivery::{WebhookDelivery, WebhookDeliveryAttempt, WebhookDeliveryStatus};
+use crate::events::webhook_event::{WebhookEvent, WebhookEventType, API_VERSION};
+use crate::monitoring::app_state::Webhooker;
 use crate::utils;
 
-const WEBHOOKS_LIMIT: usize = 2;
 
 pub fn get_router(app_state: Arc<AppState>) -> Router<Arc<AppState>> {
     Router::new()
         .route("/", post(create))
+        .route("/:webhook_id", patch(update))
+        .route("/:webhook_id/test", post(send_test))
+        .route("/:webhook_id/rotate-secret", post(rotate_secret))
+        .route("/:webhook_id/deliveries/:delivery_id/redeliver", post(redeliver))
         .layer(middleware::from_fn_with_state(app_state.clone(), RateLimitType::user_webhook))
         .route("/", get(list))
         .route("/:webhook_id", delete(destroy))
+        .route("/:webhook_id/deliveries", get(list_deliveries))
         .with_state(app_state)
         .route("/ping", get(ping_pong))
 }
@@ -33,8 +42,35 @@ pub fn get_router(app_state: Arc<AppState>) -> Router<Arc<AppState>> {
 pub(crate) struct GetWebhookResponse {
     pub id: Uuid,
     pub url: String,
+    /// Shown in full only in the responses of create and rotate-secret; masked to the last
+    /// four characters everywhere else.
+    #[schema(example = "****9f2c")]
     pub secret: String,
     pub created_at: NaiveDateTime,
+    #[schema(example = json!(["invoice.paid"]))]
+    pub events: Vec<String>,
+    /// Payload format this webhook receives; `legacy` means the flat invoice-paid body.
+    pub api_version: String,
+    pub enabled: bool,
+    /// Until then deliveries are also signed with the secret that was rotated out.
+    pub previous_secret_expires_at: Option<NaiveDateTime>,
+}
+
+/// `****` plus the last four characters; legacy webhooks without a secret stay empty.
+fn mask_secret(secret: &str) -> String {
+    match secret.len() {
+        0 => String::new(),
+        len if len > 8 => format!("****{}", secret.get(len - 4..).unwrap_or_default()),
+        _ => "****".to_string(),
+    }
+}
+
+impl GetWebhookResponse {
+    /// Response that reveals the secret, for the moment it is issued.
+    fn with_secret(webhook: Webhook) -> Self {
+        let secret = webhook.secret.clone();
+        Self { secret, ..webhook.into() }
+    }
 }
 
 impl From<Webhook> for GetWebhookResponse {
@@ -42,8 +78,12 @@ impl From<Webhook> for GetWebhookResponse {
         Self {
             id: value.id,
             url: value.url,
-            secret: value.secret,
+            secret: mask_secret(&value.secret),
             created_at: value.created_at,
+            events: value.event_types,
+            api_version: value.api_version,
+            enabled: value.enabled,
+            previous_secret_expires_at: value.previous_secret.and(value.previous_secret_expires_at),
         }
     }
 }
@@ -76,65 +116,49 @@ pub(crate) async fn list(
 #[derive(Deserialize, utoipa::ToSchema)]
 pub(crate) struct CreateWebhookRequest {
     pub url: String,
+    /// Event types to receive; every type in the catalog when omitted.
+    pub events: Option<Vec<WebhookEventType>>,
 }
 
-/// Block internal/private IPs and Docker-internal hostnames to prevent SSRF.
-/// Note: DNS rebinding is not covered — would require a custom resolver;
-/// hostname + IP validation is sufficient for this threat model.
-fn validate_webhook_url(url: &Url) -> Result<(), String> {
-    match url.scheme() {
-        "http" | "https" => {}
-        s => return Err(format!("Unsupported scheme: {s}")),
-    }
+/// The endpoint must pass the outbound guard and answer 2xx to an empty `POST`.
+async fn check_endpoint(webhooker: &Webhooker, url: &str) -> Result<(), String> {
+    let parsed = Url::parse(url)
+        .map_err(|_| "Invalid url".to_string())?;
 
-    let host = url.host_str().ok_or("URL must have a host")?;
+    webhooker.guard().check(&parsed).await?;
 
-    const BLOCKED_HOSTS: &[&str] = &[
-        "localhost", "postgres", "redis", "api", "web", "nginx", "traefik",
-    ];
-    let host_lower = host.to_lowercase();
-    if BLOCKED_HOSTS.iter().any(|&b| host_lower == b) {
-        return Err(format!("Blocked host: {host}"));
+    match webhooker.client()
+        .post(parsed)
+        .header("content-type", "application/json")
+        .json(&json!({}))
+        .send().await {
+        Ok(response) if response.status().is_success() => Ok(()),
+        Ok(response) => Err(format!("URL returned status: {}", response.status())),
+        Err(e) => Err(format!("Failed to reach URL: {}", e)),
     }
+}
 
-    if let Ok(ip) = host.parse::<IpAddr>() {
-        let is_internal = match ip {
-            IpAddr::V4(v4) => {
-                v4.is_loopback()
-                || v4.is_private()
-                || v4.is_link_local()
-                || v4.is_unspecified()
-                || v4.is_broadcast()
-            }
-            IpAddr::V6(v6) => {
-                v6.is_loopback()
-                || v6.is_unspecified()
-            }
-        };
-        if is_internal {
-            return Err(format!("Internal IP not allowed: {ip}"));
-        }
+/// Catalog names, sorted and deduplicated; an explicit empty list is rejected.
+pub(crate) fn event_type_names(events: &[WebhookEventType]) -> Result<Vec<String>, String> {
+    if events.is_empty() {
+        return Err("At least one event type required".to_string());
     }
 
-    Ok(())
+    let mut names = events.iter()
+        .map(|event_type| event_type.as_str().to_string())
+        .collect::<Vec<_>>();
+    names.sort();
+    names.dedup();
+    Ok(names)
 }
 
 impl CreateWebhookRequest {
-    async fn validate(&self) -> Result<(), String> {
-        let parsed = Url::parse(&self.url)
-            .map_err(|_| "Invalid url".to_string())?;
-
-        validate_webhook_url(&parsed)?;
-
-        match reqwest::Client::new()
-            .post(&self.url)
-            .header("content-type", "application/json")
-            .json(&json!({}))
-            .send().await {
-            Ok(response) if response.status().is_success() => Ok(()),
-            Ok(response) => Err(format!("URL returned status: {}", response.status())),
-            Err(e) => Err(format!("Failed to reach URL: {}", e)),
+    async fn validate(&self, webhooker: &Webhooker) -> Result<(), String> {
+        if let Some(events) = &self.events {
+            event_type_names(events)?;
         }
+
+        check_endpoint(webhooker, &self.url).await
     }
 }
 
@@ -143,7 +167,7 @@ impl CreateWebhookRequest {
     path = "/user/webhook",
     request_body = CreateWebhookRequest,
     responses(
-        (status = 200, description = "Created webhook", body = GetWebhookResponse),
+        (status = 200, description = "Created webhook with its full secret, shown only this once", body = GetWebhookResponse),
         (status = 400, description = "Invalid URL or too many webhooks"),
         (status = 401, description = "Unauthorized"),
     ),
@@ -156,7 +180,7 @@ pub(crate) async fn create(
     Json(payload): Json<CreateWebhookRequest>,
 ) -> Result<impl IntoResponse, ResponseError> {
     payload
-        .validate()
+        .validate(&state.webhooker)
         .await
         .map_err(ResponseError::Bad)?;
 
@@ -165,21 +189,190 @@ pub(crate) async fn create(
         .await
         .map_err(ResponseError::from)?;
 
-    if webhooks_number >= WEBHOOKS_LIMIT {
+    if webhooks_number >= state.config.limits.webhooks_per_user {
         return Err(ResponseError::Bad("too many webhooks".to_string()));
     }
 
     let secret = utils::generate_webhook_secret();
+    let event_types = event_type_names(payload.events.as_deref().unwrap_or(&WebhookEventType::ALL))
+        .map_err(ResponseError::Bad)?;
+
+    let instance = GetWebhookResponse::with_secret(state.db
+        .create_webhook(&payload.url, &secret, &user.id, &event_types, API_VERSION)
+        .await
+        .map_err(ResponseError::from)?);
+
+    Ok(Json(instance))
+}
+
+#[derive(Deserialize, utoipa::ToSchema)]
+pub(crate) struct UpdateWebhookRequest {
+    /// Checked like on create: must be allowed and answer 2xx to an empty `POST`.
+    pub url: Option<String>,
+    pub enabled: Option<bool>,
+    pub events: Option<Vec<WebhookEventType>>,
+}
+
+#[utoipa::path(
+    patch,
+    path = "/user/webhook/{id}",
+    params(("id" = Uuid, Path, description = "Webhook ID")),
+    request_body = UpdateWebhookRequest,
+    responses(
+        (status = 200, description = "Updated webhook", body = GetWebhookResponse),
+        (status = 400, description = "Invalid URL or event types"),
+        (status = 404, description = "Not found"),
+        (status = 401, description = "Unauthorized"),
+    ),
+    tag = "user",
+    security(("jwt_cookie" = []))
+)]
+pub(crate) async fn update(
+    State(state): State<Arc<AppState>>,
+    Extension(user): Extension<User>,
+    Path(webhook_id): Path<Uuid>,
+    Json(payload): Json<UpdateWebhookRequest>,
+) -> Result<impl IntoResponse, ResponseError> {
+    state.db.get_webhook(&webhook_id, &user.id)
+        .await
+        .map_err(ResponseError::from)?
+        .ok_or(ResponseError::NotFound)?;
+
+    let event_types = payload.events
+        .as_deref()
+        .map(event_type_names)
+        .transpose()
+        .map_err(ResponseError::Bad)?;
+    if let Some(url) = &payload.url {
+        check_endpoint(&state.webhooker, url).await.map_err(ResponseError::Bad)?;
+    }
 
     let instance: GetWebhookResponse = state.db
-        .create_webhook(&payload.url, &secret, &user.id)
+        .update_webhook(&webhook_id, &user.id, payload.url.as_deref(), payload.enabled, event_types.as_deref())
         .await
         .map_err(ResponseError::from)?
+        .ok_or(ResponseError::NotFound)?
         .into();
 
     Ok(Json(instance))
 }
 
+const DEFAULT_SECRET_GRACE_PERIOD: Duration = Duration::from_secs(24 * 3600);
+const MAX_SECRET_GRACE_PERIOD: Duration = Duration::from_secs(7 * 24 * 3600);
+
+#[derive(Deserialize, utoipa::ToSchema)]
+pub(crate) struct RotateWebhookSecretRequest {
+    /// How long deliveries stay signed with the old secret as well. 24 hours by default,
+    /// at most 7 days; `0` drops the old secret right away.
+    pub grace_period_secs: Option<u64>,
+}
+
+#[utoipa::path(
+    post,
+    path = "/user/webhook/{id}/rotate-secret",
+    params(("id" = Uuid, Path, description = "Webhook ID")),
+    request_body = RotateWebhookSecretRequest,
+    responses(
+        (status = 200, description = "Webhook with the new full secret, shown only this once", body = GetWebhookResponse),
+        (status = 400, description = "Grace period too long"),
+        (status = 404, description = "Not found"),
+        (status = 401, description = "Unauthorized"),
+    ),
+    tag = "user",
+    security(("jwt_cookie" = []))
+)]
+pub(crate) async fn rotate_secret(
+    State(state): State<Arc<AppState>>,
+    Extension(user): Extension<User>,
+    Path(webhook_id): Path<Uuid>,
+    Json(payload): Json<RotateWebhookSecretRequest>,
+) -> Result<impl IntoResponse, ResponseError> {
+    let grace = payload.grace_period_secs
+        .map(Duration::from_secs)
+        .unwrap_or(DEFAULT_SECRET_GRACE_PERIOD);
+    if grace > MAX_SECRET_GRACE_PERIOD {
+        return Err(ResponseError::Bad(format!("grace_period_secs must be at most {}", MAX_SECRET_GRACE_PERIOD.as_secs())));
+    }
+
+    let secret = utils::generate_webhook_secret();
+
+    let instance = GetWebhookResponse::with_secret(state.db
+        .rotate_webhook_secret(&webhook_id, &user.id, &secret, grace)
+        .await
+        .map_err(ResponseError::from)?
+        .ok_or(ResponseError::NotFound)?);
+
+    Ok(Json(instance))
+}
+
+#[derive(Serialize, utoipa::ToSchema)]
+pub(crate) struct TestWebhookResponse {
+    /// `id` of the `webhook.test` event that was sent.
+    pub event_id: Uuid,
+    /// Missing when no response was received.
+    pub status_code: Option<u16>,
+    pub latency_ms: i64,
+    /// First 1 KiB of the response body.
+    pub response_excerpt: Option<String>,
+    pub error: Option<String>,
+}
+
+#[utoipa::path(
+    post,
+    path = "/user/webhook/{id}/test",
+    params(("id" = Uuid, Path, description = "Webhook ID")),
+    responses(
+        (status = 200, description = "What the endpoint answered to a signed `webhook.test` event", body = TestWebhookResponse),
+        (status = 404, description = "Not found"),
+        (status = 401, description = "Unauthorized"),
+    ),
+    tag = "user",
+    security(("jwt_cookie" = []))
+)]
+pub(crate) async fn send_test(
+    State(state): State<Arc<AppState>>,
+    Extension(user): Extension<User>,
+    Path(webhook_id): Path<Uuid>,
+) -> Result<impl IntoResponse, ResponseError> {
+    let webhook = state.db.get_webhook(&webhook_id, &user.id)
+        .await
+        .map_err(ResponseError::from)?
+        .ok_or(ResponseError::NotFound)?;
+
+    let event = WebhookEvent::test(webhook.id);
+    let payload = event.payload_for(&webhook.api_version)
+        .map_err(ResponseError::from_error)?
+        .ok_or_else(|| ResponseError::from_error("test event has no payload".to_string()))?;
+
+    let now = chrono::Utc::now().naive_utc();
+    let previous_secret = webhook.previous_secret_expires_at
+        .is_some_and(|expires_at| expires_at > now)
+        .then_some(webhook.previous_secret.as_deref())
+        .flatten();
+    let secrets = [Some(webhook.secret.as_str()), previous_secret].into_iter().flatten().collect::<Vec<_>>();
+
+    let started = Instant::now();
+    let result = state.webhooker.send(&webhook.url, &secrets, &payload).await;
+    let latency_ms = started.elapsed().as_millis() as i64;
+
+    Ok(Json(match result {
+        Ok(response) => TestWebhookResponse {
+            event_id: event.id,
+            status_code: Some(response.status),
+            latency_ms,
+            response_excerpt: Some(response.body_excerpt),
+            error: None,
+        },
+        Err(err) => TestWebhookResponse {
+            event_id: event.id,
+            status_code: None,
+            latency_ms,
+            response_excerpt: None,
+            error: Some(err),
+        },
+    }))
+}
+
 #[utoipa::path(
     delete,
     path = "/user/webhook/{id}",
@@ -204,3 +397,153 @@ pub(crate) async fn destroy(
         false => return Err(ResponseError::NotFound),
     })
 }
+
+#[derive(Serialize, utoipa::ToSchema)]
+pub(crate) struct WebhookDeliveryAttemptResponse {
+    pub attempted_at: NaiveDateTime,
+    /// Missing when no response was received.
+    pub status_code: Option<i32>,
+    pub latency_ms: i64,
+    /// First 1 KiB of the response body.
+    pub response_excerpt: Option<String>,
+    pub error: Option<String>,
+}
+
+impl From<WebhookDeliveryAttempt> for WebhookDeliveryAttemptResponse {
+    fn from(value: WebhookDeliveryAttempt) -> Self {
+        Self {
+            attempted_at: value.attempted_at,
+            status_code: value.status_code,
+            latency_ms: value.latency_ms,
+            response_excerpt: value.response_excerpt,
+            error: value.error,
+        }
+    }
+}
+
+#[derive(Serialize, utoipa::ToSchema)]
+pub(crate) struct WebhookDeliveryResponse {
+    pub id: Uuid,
+    pub payload: Value,
+    /// `pending`, `delivered` or `failed` (retries exhausted).
+    pub status: String,
+    pub attempts: i32,
+    /// When the next retry is due, for pending deliveries.
+    pub next_attempt_at: Option<NaiveDateTime>,
+    pub created_at: NaiveDateTime,
+    pub delivered_at: Option<NaiveDateTime>,
+    pub attempt_log: Vec<WebhookDeliveryAttemptResponse>,
+}
+
+impl WebhookDeliveryResponse {
+    fn new(delivery: WebhookDelivery, attempt_log: Vec<WebhookDeliveryAttemptResponse>) -> Self {
+        Self {
+            id: delivery.id,
+            payload: delivery.payload,
+            next_attempt_at: (delivery.status == WebhookDeliveryStatus::Pending.as_str()).then_some(delivery.next_attempt_at),
+            status: delivery.status,
+            attempts: delivery.attempts,
+            created_at: delivery.created_at,
+            delivered_at: delivery.delivered_at,
+            attempt_log,
+        }
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/user/webhook/{id}/deliveries",
+    params(
+        ("id" = Uuid, Path, description = "Webhook ID"),
+        ("limit" = Option<i64>, Query, description = "Max 50"),
+        ("offset" = Option<i64>, Query, description = "Number of deliveries to skip"),
+    ),
+    responses(
+        (status = 200, description = "Deliveries, newest first, with their attempts", body = Vec<WebhookDeliveryResponse>),
+        (status = 404, description = "Not found"),
+        (status = 401, description = "Unauthorized"),
+    ),
+    tag = "user",
+    security(("jwt_cookie" = []))
+)]
+pub(crate) async fn list_deliveries(
+    State(state): State<Arc<AppState>>,
+    Extension(user): Extension<User>,
+    Path(webhook_id): Path<Uuid>,
+    Query(pagination): Query<Pagination>,
+) -> Result<impl IntoResponse, ResponseError> {
+    let (limit, offset) = pagination.get_valid(50)?;
+
+    state.db.get_webhook(&webhook_id, &user.id)
+        .await
+        .map_err(ResponseError::from)?
+        .ok_or(ResponseError::NotFound)?;
+
+    let deliveries = state.db
+        .list_webhook_deliveries(&webhook_id, limit, offset)
+        .await
+        .map_err(ResponseError::from)?;
+    let ids = deliveries.iter().map(|delivery| delivery.id).collect::<Vec<_>>();
+    let mut attempts: HashMap<Uuid, Vec<WebhookDeliveryAttemptResponse>> = HashMap::new();
+    for attempt in state.db
+        .list_webhook_delivery_attempts(&ids)
+        .await
+        .map_err(ResponseError::from)? {
+        attempts.entry(attempt.delivery_id).or_default().push(attempt.into());
+    }
+
+    Ok(Json(deliveries
+        .into_iter()
+        .map(|delivery| {
+            let attempt_log = attempts.remove(&delivery.id).unwrap_or_default();
+            WebhookDeliveryResponse::new(delivery, attempt_log)
+        })
+        .collect::<Vec<_>>()))
+}
+
+#[utoipa::path(
+    post,
+    path = "/user/webhook/{id}/deliveries/{delivery_id}/redeliver",
+    params(
+        ("id" = Uuid, Path, description = "Webhook ID"),
+        ("delivery_id" = Uuid, Path, description = "Delivery ID"),
+    ),
+    responses(
+        (status = 202, description = "Queued for another delivery with a fresh retry budget"),
+        (status = 404, description = "Not found"),
+        (status = 401, description = "Unauthorized"),
+    ),
+    tag = "user",
+    security(("jwt_cookie" = []))
+)]
+pub(crate) async fn redeliver(
+    State(state): State<Arc<AppState>>,
+    Extension(user): Extension<User>,
+    Path((webhook_id, delivery_id)): Path<(Uuid, Uuid)>,
+) -> Result<impl IntoResponse, ResponseError> {
+    state.db.get_webhook(&webhook_id, &user.id)
+        .await
+        .map_err(ResponseError::from)?
+        .ok_or(ResponseError::NotFound)?;
+
+    Ok(match state.db.redeliver_webhook_delivery(&delivery_id, &webhook_id)
+        .await
+        .map_err(ResponseError::from)? {
+        true => StatusCode::ACCEPTED,
+        false => return Err(ResponseError::NotFound),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    #[rstest]
+    #[case("", "")]
+    #[case("short", "****")]
+    #[case("5f0c1d2e3a4b5c6d7e8f9a0b1c2d3e4f", "****3e4f")]
+    fn test_mask_secret(#[case] secret: &str, #[case] masked: &str) {
+        assert_eq!(mask_secret(secret), masked);
+    }
+}
@@ -1,6 +1,16 @@
-use serde::Deserialize;
+use serde::{Deserialize, Deserializer};
 use crate::api::response_error::ResponseError;
 
+/// For `PATCH` fields that `null` clears: a missing field is `None`, `null` is `Some(None)`.
+/// Use with `#[serde(default, deserialize_with = "nullable")]`.
+pub fn nullable<'de, D, T>(deserializer: D) -> Result<Option<Option<T>>, D::Error>
+where
+    D: Deserializer<'de>,
+    T: Deserialize<'de>,
+{
+    Option::<T>::deserialize(deserializer).map(Some)
+}
+
 fn default_limit() -> i64 {
     10
 }
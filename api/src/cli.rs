@@ -0,0 +1,369 @@
+use std::future::Future;
+use std::path::PathBuf;
+use std::sync::Arc;
+use clap::{Parser, Subcommand, ValueEnum};
+use tokio::task::JoinHandle;
+use tracing::{error, info};
+use uuid::Uuid;
+use crate::api::state::DB;
+#[cfg(feature = "telegram")]
+use crate::api::state::Redis;
+use crate::config::{self, ApiConfig, BotConfig, ConfigSource, DatabaseConfig, MonitorConfig, RuntimeConfig};
+use crate::monitoring::health::DaemonHealth;
+use crate::shutdown::Shutdown;
+#[cfg(feature = "telegram")]
+use crate::telegram::TelegramClient;
+use crate::{api, monitoring, utils};
+
+const REENCRYPT_BATCH: i64 = 500;
+
+#[derive(Parser)]
+#[command(name = "cryo-pay", about = "Cryo Pay API server, blockchain monitor and Telegram bot")]
+pub struct Cli {
+    /// TOML file with configuration; environment variables take precedence over it
+    #[arg(long, global = true, env = "CONFIG_FILE")]
+    config: Option<PathBuf>,
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Subcommand, Debug, PartialEq)]
+enum Command {
+    /// Run the HTTP API, the blockchain monitor and the Telegram bot in one process (default)
+    All,
+    /// Run the HTTP API server
+    ServeApi,
+    /// Run the blockchain monitoring daemon
+    Monitor {
+        /// Only print decoded events instead of processing them
+        #[arg(long)]
+        dry_run: bool,
+    },
+    /// Run the Telegram bot
+    Bot,
+    /// Apply pending database migrations and exit
+    Migrate,
+    /// Rewind the monitor checkpoint so the daemon rescans a network from the given block
+    Rescan {
+        /// Network name as configured in NETWORKS
+        #[arg(long)]
+        network: String,
+        /// First block to scan again
+        #[arg(long)]
+        from_block: u64,
+    },
+    /// Issue an API key for an existing user and print it once
+    CreateApiKey {
+        #[arg(long)]
+        user_id: Uuid,
+    },
+    /// Encrypt stored credentials under ENCRYPTION_KEY: rows from before encryption and rows
+    /// under a key now listed in ENCRYPTION_PREVIOUS_KEYS
+    Reencrypt,
+    /// Check that the configuration required by a component is present and valid
+    CheckConfig {
+        #[arg(long, value_enum, default_value_t = Component::All)]
+        component: Component,
+    },
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, ValueEnum)]
+enum Component {
+    All,
+    Api,
+    Monitor,
+    Bot,
+}
+
+impl Component {
+    fn check(&self, source: &ConfigSource) -> Vec<String> {
+        let result = match self {
+            Component::All => return [Component::Api, Component::Monitor, Component::Bot]
+                .iter()
+                .flat_map(|c| c.check(source))
+                .collect(),
+            Component::Api => ApiConfig::load(source).map(|_| ()),
+            Component::Monitor => MonitorConfig::load(source).map(|_| ()),
+            Component::Bot => BotConfig::load(source).map(|_| ()),
+        };
+
+        let runtime = RuntimeConfig::load(source).map(|_| ());
+        [runtime, result]
+            .into_iter()
+            .filter_map(Result::err)
+            .map(|err| format!("{self:?}: {err}"))
+            .collect()
+    }
+}
+
+impl Cli {
+    pub async fn run(self) -> Result<(), String> {
+        let source = ConfigSource::load(self.config.as_deref())?;
+
+        match self.command.unwrap_or(Command::All) {
+            Command::All => run_all(&source, listen_for_shutdown(&source)?).await,
+            Command::ServeApi => {
+                let config = ApiConfig::load(&source)?;
+                let shutdown = listen_for_shutdown(&source)?;
+                shutdown.with_deadline("api", serve_api(config, None, shutdown.clone())).await
+            }
+            Command::Monitor { dry_run } => {
+                let config = MonitorConfig::load(&source)?;
+                let shutdown = listen_for_shutdown(&source)?;
+                shutdown.with_deadline("monitor", monitor(config, dry_run, Arc::new(DaemonHealth::new()), shutdown.clone())).await
+            }
+            Command::Bot => {
+                let config = BotConfig::load(&source)?
+                    .ok_or("Telegram integration is disabled (TELEGRAM_ENABLED)")?;
+                let shutdown = listen_for_shutdown(&source)?;
+                shutdown.with_deadline("bot", bot(config, shutdown.clone())).await
+            }
+            Command::Migrate => migrate(&source).await,
+            Command::Rescan { network, from_block } => rescan(&source, &network, from_block).await,
+            Command::CreateApiKey { user_id } => create_api_key(&source, &user_id).await,
+            Command::Reencrypt => reencrypt(&source).await,
+            Command::CheckConfig { component } => check_config(&source, component),
+        }
+    }
+}
+
+fn listen_for_shutdown(source: &ConfigSource) -> Result<Shutdown, String> {
+    let shutdown = Shutdown::new(RuntimeConfig::load(source)?.shutdown_timeout);
+    shutdown.listen_for_signals();
+    Ok(shutdown)
+}
+
+async fn run_all(source: &ConfigSource, shutdown: Shutdown) -> Result<(), String> {
+    // Validate everything before starting anything, so a typo does not leave half the process running.
+    let api_config = ApiConfig::load(source);
+    let monitor_config = MonitorConfig::load(source);
+    let bot_config = BotConfig::load(source);
+    let (api_config, monitor_config, bot_config) = match (api_config, monitor_config, bot_config) {
+        (Ok(api), Ok(monitor), Ok(bot)) => (api, monitor, bot),
+        (api, monitor, bot) => return Err(
+            [api.err(), monitor.err(), bot.err()].into_iter().flatten().collect::<Vec<_>>().join("; ")
+        ),
+    };
+
+    let daemon_health = Arc::new(DaemonHealth::new());
+
+    let mut components = vec![
+        spawn_component(&shutdown, "monitor", monitor(monitor_config, false, daemon_health.clone(), shutdown.clone())),
+        spawn_component(&shutdown, "api", serve_api(api_config, Some(daemon_health), shutdown.clone())),
+    ];
+    match bot_config {
+        Some(bot_config) if bot_config.telegram.webhook.is_some() => info!("Telegram updates arrive by webhook at the API, not starting the bot"),
+        Some(bot_config) => components.push(spawn_component(&shutdown, "bot", bot(bot_config, shutdown.clone()))),
+        None => info!("Telegram integration is disabled, not starting the bot"),
+    }
+
+    for (name, handle) in components {
+        match handle.await {
+            Ok(Ok(())) => info!("{name} stopped"),
+            Ok(Err(err)) => error!("{name} failed: {err}"),
+            Err(err) => error!("{name} panicked: {err}"),
+        }
+    }
+
+    Ok(())
+}
+
+fn spawn_component<F>(shutdown: &Shutdown, name: &'static str, component: F) -> (&'static str, JoinHandle<Result<(), String>>)
+where
+    F: Future<Output = Result<(), String>> + Send + 'static,
+{
+    let shutdown = shutdown.clone();
+    (name, tokio::spawn(async move { shutdown.with_deadline(name, component).await }))
+}
+
+#[cfg(feature = "telegram")]
+async fn telegram_client(config: Option<&crate::config::TelegramConfig>) -> Result<Option<TelegramClient>, String> {
+    match config {
+        Some(config) => TelegramClient::new(config).await.map(Some),
+        None => Ok(None),
+    }
+}
+
+async fn serve_api(config: ApiConfig, daemon_health: Option<Arc<DaemonHealth>>, shutdown: Shutdown) -> Result<(), String> {
+    let db = DB::new(&config.database).await?;
+    #[cfg(feature = "telegram")]
+    let telegram_client = telegram_client(config.telegram.as_ref()).await?;
+
+    api::run_api(
+        config,
+        db,
+        #[cfg(feature = "telegram")]
+        telegram_client,
+        daemon_health,
+        shutdown,
+    ).await
+}
+
+async fn monitor(config: MonitorConfig, dry_run: bool, daemon_health: Arc<DaemonHealth>, shutdown: Shutdown) -> Result<(), String> {
+    let db = DB::new(&config.database).await?;
+    #[cfg(feature = "telegram")]
+    let telegram_client = telegram_client(config.telegram.as_ref()).await?;
+
+    monitoring::daemon::process_networks(
+        dry_run,
+        config,
+        &db,
+        #[cfg(feature = "telegram")]
+        telegram_client,
+        daemon_health,
+        shutdown,
+    ).await
+}
+
+#[cfg(feature = "telegram")]
+async fn bot(config: BotConfig, shutdown: Shutdown) -> Result<(), String> {
+    let db = DB::new(&config.database).await?;
+    let redis = Redis::new(&config.redis_url, config.limits.gas_fee_cache_ttl_secs).await?;
+    let telegram_client = TelegramClient::new(&config.telegram).await?;
+
+    telegram_client.run_as_bot(&config, db.clone(), redis, shutdown).await?;
+    db.close().await;
+
+    Ok(())
+}
+
+#[cfg(not(feature = "telegram"))]
+async fn bot(_config: BotConfig, _shutdown: Shutdown) -> Result<(), String> {
+    Err("The bot requires the `telegram` feature".to_string())
+}
+
+async fn migrate(source: &ConfigSource) -> Result<(), String> {
+    DB::new(&DatabaseConfig::load(source)?).await?
+        .run_migrations()
+        .await
+        .map_err(|err| utils::make_err(Box::new(err), "run migrations"))?;
+
+    info!("Migrations applied");
+    Ok(())
+}
+
+async fn rescan(source: &ConfigSource, network_name: &str, from_block: u64) -> Result<(), String> {
+    let network = config::load_networks(source)?
+        .into_iter()
+        .find(|n| n.name.eq_ignore_ascii_case(network_name))
+        .ok_or_else(|| format!("Unknown network: {network_name}"))?;
+
+    let checkpoint = from_block.saturating_sub(1) as i64;
+    DB::new(&DatabaseConfig::load(source)?).await?
+        .set_block_number(&network.name, checkpoint)
+        .await
+        .map_err(|e| e.to_string())?;
+
+    info!("Checkpoint for {} set to block {checkpoint}; the monitor will rescan from block {from_block}", network.name);
+    Ok(())
+}
+
+async fn create_api_key(source: &ConfigSource, user_id: &Uuid) -> Result<(), String> {
+    let app_secret = config::load_app_secret(source)?;
+    let db = DB::new(&DatabaseConfig::load(source)?).await?;
+
+    let user = db.get_user_by_id(user_id)
+        .await
+        .map_err(|e| format!("User {user_id} not found: {e}"))?;
+
+    let api_key = utils::new_api_key(user.id);
+    let instance = db.create_api_key(&user.id, &api_key.hashed_value(&app_secret))
+        .await
+        .map_err(|e| e.to_string())?;
+
+    info!("Created API key {} for user {}", instance.id, user.id);
+    println!("{}", api_key.value);
+    Ok(())
+}
+
+async fn reencrypt(source: &ConfigSource) -> Result<(), String> {
+    let report = DB::new(&DatabaseConfig::load(source)?).await?
+        .reencrypt(REENCRYPT_BATCH)
+        .await
+        .map_err(|e| e.to_string())?;
+
+    info!(
+        "Re-encrypted {} users, {} telegram destinations, {} chat webhooks, {} webhooks and {} invoices",
+        report.users, report.telegram_destinations, report.chat_webhooks, report.webhooks, report.invoices,
+    );
+    Ok(())
+}
+
+fn check_config(source: &ConfigSource, component: Component) -> Result<(), String> {
+    let errors = component.check(source);
+
+    match errors.is_empty() {
+        true => {
+            info!("Configuration for {component:?} is valid");
+            Ok(())
+        }
+        false => Err(errors.join("; ")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    fn parse(args: &[&str]) -> Option<Command> {
+        Cli::try_parse_from(std::iter::once("api").chain(args.iter().copied()))
+            .expect("valid args")
+            .command
+    }
+
+    #[test]
+    fn test_no_subcommand_runs_everything() {
+        assert_eq!(parse(&[]), None);
+    }
+
+    #[test]
+    fn test_parse_rescan() {
+        assert_eq!(
+            parse(&["rescan", "--network", "optimism", "--from-block", "42"]),
+            Some(Command::Rescan { network: "optimism".to_string(), from_block: 42 })
+        );
+    }
+
+    #[test]
+    fn test_parse_create_api_key_requires_uuid() {
+        assert!(Cli::try_parse_from(["api", "create-api-key", "--user-id", "nope"]).is_err());
+    }
+
+    const ENCRYPTION_KEY: &str = "k1:0101010101010101010101010101010101010101010101010101010101010101";
+    const NETWORKS: &str = r#"[{"name":"Optimism","id":10,"link":"https://rpc.example.com","addresses":{"erc20":"0xabc","contract":"0xdef"}}]"#;
+
+    fn source(env: &[(&str, &str)]) -> ConfigSource {
+        ConfigSource::new(
+            env.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect::<HashMap<_, _>>(),
+            toml::Table::new(),
+        )
+    }
+
+    #[test]
+    fn test_bot_does_not_require_api_vars() {
+        let source = source(&[
+            ("POSTGRES_URL", "x"), ("ENCRYPTION_KEY", ENCRYPTION_KEY), ("TGBOT_TOKEN", "x"), ("REDIS_URL", "x"),
+            ("WEB_BASE_URL", "https://example.com"), ("NETWORKS", NETWORKS),
+        ]);
+        assert!(Component::Bot.check(&source).is_empty());
+        assert!(!Component::Api.check(&source).is_empty());
+    }
+
+    #[test]
+    fn test_check_reports_invalid_networks() {
+        let source = source(&[
+            ("POSTGRES_URL", "x"), ("ENCRYPTION_KEY", ENCRYPTION_KEY), ("TGBOT_TOKEN", "x"), ("EVENT_SIGNATURE", "x"), ("BREVO_API_KEY", "x"),
+            ("EMAIL_SENDER", "x"), ("WEB_BASE_URL", "https://example.com"), ("NETWORKS", "not json"),
+        ]);
+        let errors = Component::Monitor.check(&source);
+        assert_eq!(errors.len(), 1);
+        assert!(errors[0].contains("NETWORKS"));
+    }
+
+    #[test]
+    fn test_parse_global_config_flag() {
+        let cli = Cli::try_parse_from(["api", "check-config", "--config", "cryo.toml"]).expect("valid args");
+        assert_eq!(cli.config, Some(PathBuf::from("cryo.toml")));
+    }
+}
@@ -0,0 +1,670 @@
+use std::time::Duration;
+use serde_json::Value;
+use crate::db::encryption::DataKey;
+use crate::network::Network;
+use crate::utils::{self, ApiGlobalUrl};
+
+mod source;
+
+pub use source::{ConfigSource, Loader};
+
+const DEFAULT_SHUTDOWN_TIMEOUT_SECS: u64 = 30;
+
+/// Settings shared by every component of the process.
+#[derive(Clone)]
+pub struct RuntimeConfig {
+    pub shutdown_timeout: Duration,
+}
+
+#[derive(Clone)]
+pub struct DatabaseConfig {
+    pub postgres_url: String,
+    /// `ENCRYPTION_KEY` first, then `ENCRYPTION_PREVIOUS_KEYS` that still decrypt during a rotation.
+    pub encryption_keys: Vec<DataKey>,
+}
+
+/// `<NAME>_ENABLED` switches for integrations that are also cargo features.
+const TELEGRAM_ENABLED: &str = "TELEGRAM_ENABLED";
+const MAILER_ENABLED: &str = "MAILER_ENABLED";
+const FIREBASE_AUTH_ENABLED: &str = "FIREBASE_AUTH_ENABLED";
+const INFURA_GAS_FEES_ENABLED: &str = "INFURA_GAS_FEES_ENABLED";
+
+#[derive(Clone)]
+#[cfg_attr(not(feature = "telegram"), allow(dead_code))]
+pub struct TelegramConfig {
+    pub token: String,
+    /// `None` runs the bot with long polling; otherwise the API receives the updates.
+    pub webhook: Option<TelegramWebhookConfig>,
+}
+
+#[derive(Clone)]
+#[cfg_attr(not(feature = "telegram"), allow(dead_code))]
+pub struct TelegramWebhookConfig {
+    /// Public HTTPS URL that reaches the API's `/telegram/webhook`.
+    pub url: String,
+    /// Telegram sends it back in `X-Telegram-Bot-Api-Secret-Token` with every update.
+    pub secret: String,
+}
+
+#[derive(Clone)]
+#[cfg_attr(not(feature = "mailer"), allow(dead_code))]
+pub struct MailerConfig {
+    pub transport: MailTransportConfig,
+    pub sender: SenderConfig,
+}
+
+/// `MAILER_TRANSPORT`: `brevo` (default), `smtp` or `file`.
+#[derive(Clone, Debug)]
+#[cfg_attr(not(feature = "mailer"), allow(dead_code))]
+pub enum MailTransportConfig {
+    Brevo { api_key: String },
+    Smtp(SmtpConfig),
+    /// Writes every email as an `.eml` file into the directory instead of sending it.
+    File { dir: String },
+}
+
+#[derive(Clone, Debug)]
+#[cfg_attr(not(feature = "mailer"), allow(dead_code))]
+pub struct SmtpConfig {
+    pub host: String,
+    pub port: u16,
+    pub security: SmtpSecurity,
+    pub username: Option<String>,
+    pub password: Option<String>,
+}
+
+/// `SMTP_SECURITY`; the default port follows from it.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SmtpSecurity {
+    /// Plain connection upgraded with STARTTLS, which is required; port 587.
+    StartTls,
+    /// TLS from the start (SMTPS); port 465.
+    Tls,
+    /// No encryption, for local relays and mail catchers; port 25.
+    None,
+}
+
+/// Who the transport sends as.
+#[derive(Clone, Debug)]
+#[cfg_attr(not(feature = "mailer"), allow(dead_code))]
+pub struct SenderConfig {
+    pub email: String,
+    pub name: Option<String>,
+    pub reply_to: Option<String>,
+}
+
+/// Cryo Pay paying itself for subscriptions and donations. Optional as a whole:
+/// without `CRYO_PAY_SELF_ADDRESS` the `/buy` endpoints answer with an error.
+#[derive(Clone)]
+pub struct CryoPayConfig {
+    pub seller: String,
+    pub networks: Vec<i64>,
+    pub api_key: Option<String>,
+    pub api_global_url: ApiGlobalUrl,
+}
+
+#[derive(Clone)]
+pub struct AbiConfig {
+    pub erc20: Value,
+    pub contract: Value,
+}
+
+#[derive(Clone)]
+pub struct RateLimits {
+    pub product_invoice_api_per_day: u16,
+    pub product_invoice_web_per_day: u16,
+    pub product_invoice_anonymous_per_day: u16,
+    pub user_invoice_per_day: u16,
+    pub login_per_day: u16,
+    pub webhook_create_per_minute: u16,
+    /// Public checkout calls on an invoice, such as reporting the buyer's transaction or receipt email.
+    pub checkout_per_minute: u16,
+}
+
+#[derive(Clone)]
+pub struct Limits {
+    pub api_keys_per_user: usize,
+    pub webhooks_per_user: usize,
+    pub callback_urls_per_user: usize,
+    /// Chats, groups and channels a user can link to the bot.
+    pub telegram_destinations_per_user: usize,
+    /// Slack, Discord and generic chat webhooks per user.
+    pub chat_webhooks_per_user: usize,
+    pub jwt_expiry_hours: i64,
+    pub session_cookie_days: i64,
+    pub gas_fee_cache_ttl_secs: u64,
+    /// How long a Telegram link from `/user/attach_telegram` stays usable.
+    pub telegram_link_ttl_secs: u64,
+    pub rate: RateLimits,
+}
+
+impl Default for RateLimits {
+    fn default() -> Self {
+        Self {
+            product_invoice_api_per_day: 10,
+            product_invoice_web_per_day: 3,
+            product_invoice_anonymous_per_day: 3,
+            user_invoice_per_day: 10,
+            login_per_day: 10,
+            webhook_create_per_minute: 5,
+            checkout_per_minute: 10,
+        }
+    }
+}
+
+impl Default for Limits {
+    fn default() -> Self {
+        Self {
+            api_keys_per_user: 5,
+            webhooks_per_user: 2,
+            callback_urls_per_user: 5,
+            telegram_destinations_per_user: 5,
+            chat_webhooks_per_user: 5,
+            jwt_expiry_hours: 24,
+            session_cookie_days: 7,
+            gas_fee_cache_ttl_secs: 60 * 10,
+            telegram_link_ttl_secs: 60 * 10,
+            rate: RateLimits::default(),
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct ApiConfig {
+    pub database: DatabaseConfig,
+    pub redis_url: String,
+    pub host: String,
+    pub port: u16,
+    pub web_base_url: String,
+    pub app_secret: String,
+    /// `None` when Infura gas fees are disabled.
+    #[cfg_attr(not(feature = "infura"), allow(dead_code))]
+    pub infura_token: Option<String>,
+    pub firebase_auth: bool,
+    pub networks: Vec<Network>,
+    pub abi: AbiConfig,
+    pub telegram: Option<TelegramConfig>,
+    pub cryo_pay: Option<CryoPayConfig>,
+    pub limits: Limits,
+}
+
+#[derive(Clone)]
+pub struct MonitorConfig {
+    pub database: DatabaseConfig,
+    pub networks: Vec<Network>,
+    pub event_signature: String,
+    pub infura_rpm: u64,
+    pub web_base_url: String,
+    #[cfg_attr(not(feature = "telegram"), allow(dead_code))]
+    pub telegram: Option<TelegramConfig>,
+    #[cfg_attr(not(feature = "mailer"), allow(dead_code))]
+    pub mailer: Option<MailerConfig>,
+}
+
+/// Read only by `bot`, which needs the `telegram` feature.
+#[derive(Clone)]
+#[cfg_attr(not(feature = "telegram"), allow(dead_code))]
+pub struct BotConfig {
+    pub database: DatabaseConfig,
+    /// Holds the one-time link tokens issued by the API, and the invoice rate limits shared with it.
+    pub redis_url: String,
+    pub telegram: TelegramConfig,
+    /// For pay links of invoices created from chat.
+    pub web_base_url: String,
+    pub networks: Vec<Network>,
+    pub limits: Limits,
+}
+
+impl RuntimeConfig {
+    pub fn load(source: &ConfigSource) -> Result<Self, String> {
+        let mut l = Loader::new(source);
+        let config = Self::read(&mut l);
+        l.finish(config)
+    }
+
+    fn read(l: &mut Loader) -> Self {
+        Self { shutdown_timeout: Duration::from_secs(l.parse_or("SHUTDOWN_TIMEOUT_SECS", DEFAULT_SHUTDOWN_TIMEOUT_SECS)) }
+    }
+}
+
+impl DatabaseConfig {
+    pub fn load(source: &ConfigSource) -> Result<Self, String> {
+        let mut l = Loader::new(source);
+        let config = Self::read(&mut l);
+        l.finish(config)
+    }
+
+    fn read(l: &mut Loader) -> Self {
+        let postgres_url = l.secret("POSTGRES_URL");
+
+        let mut encryption_keys = Vec::new();
+        let current = l.secret("ENCRYPTION_KEY");
+        if !current.is_empty() {
+            match DataKey::parse(&current) {
+                Ok(key) => encryption_keys.push(key),
+                Err(err) => l.error(format!("ENCRYPTION_KEY: {err}")),
+            }
+        }
+        match DataKey::parse_list(&l.optional_secret("ENCRYPTION_PREVIOUS_KEYS").unwrap_or_default()) {
+            Ok(previous) => encryption_keys.extend(previous),
+            Err(err) => l.error(format!("ENCRYPTION_PREVIOUS_KEYS: {err}")),
+        }
+
+        Self { postgres_url, encryption_keys }
+    }
+}
+
+impl TelegramConfig {
+    fn read(l: &mut Loader) -> Option<Self> {
+        l.enabled(TELEGRAM_ENABLED, "telegram", cfg!(feature = "telegram")).then(|| Self {
+            token: l.secret("TGBOT_TOKEN"),
+            webhook: l.optional("TELEGRAM_WEBHOOK_URL")
+                .filter(|url| !utils::is_false(url))
+                .map(|_| TelegramWebhookConfig::read(l)),
+        })
+    }
+}
+
+impl TelegramWebhookConfig {
+    fn read(l: &mut Loader) -> Self {
+        let url = l.parsed("TELEGRAM_WEBHOOK_URL", |value| match url::Url::parse(value) {
+            Ok(url) if url.scheme() == "https" => Ok(value.to_string()),
+            Ok(_) => Err("Telegram only delivers to https URLs".to_string()),
+            Err(err) => Err(err.to_string()),
+        });
+        let secret = l.secret("TELEGRAM_WEBHOOK_SECRET");
+        // Telegram's own limits for `secret_token`.
+        if !secret.is_empty() && (secret.len() > 256 || !secret.chars().all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')) {
+            l.error("TELEGRAM_WEBHOOK_SECRET: 1-256 characters of A-Z, a-z, 0-9, _ and -".to_string());
+        }
+
+        Self { url: url.unwrap_or_default(), secret }
+    }
+}
+
+impl MailerConfig {
+    fn read(l: &mut Loader) -> Option<Self> {
+        l.enabled(MAILER_ENABLED, "mailer", cfg!(feature = "mailer")).then(|| Self {
+            transport: MailTransportConfig::read(l),
+            sender: SenderConfig {
+                email: l.required("EMAIL_SENDER"),
+                name: l.optional("EMAIL_SENDER_NAME"),
+                reply_to: l.optional("EMAIL_REPLY_TO"),
+            },
+        })
+    }
+}
+
+impl MailTransportConfig {
+    fn read(l: &mut Loader) -> Self {
+        match l.optional("MAILER_TRANSPORT").map(|value| value.to_lowercase()).as_deref() {
+            None | Some("brevo") => Self::Brevo { api_key: l.secret("BREVO_API_KEY") },
+            Some("smtp") => Self::Smtp(SmtpConfig::read(l)),
+            Some("file") => Self::File { dir: l.required("EMAIL_FILE_DIR") },
+            Some(other) => {
+                l.error(format!("MAILER_TRANSPORT: unknown transport {other}, expected brevo, smtp or file"));
+                Self::File { dir: String::new() }
+            }
+        }
+    }
+}
+
+impl std::str::FromStr for SmtpSecurity {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.to_lowercase().as_str() {
+            "starttls" => Ok(Self::StartTls),
+            "tls" => Ok(Self::Tls),
+            "none" => Ok(Self::None),
+            _ => Err("expected starttls, tls or none".to_string()),
+        }
+    }
+}
+
+impl SmtpConfig {
+    fn read(l: &mut Loader) -> Self {
+        let security = l.parse_or("SMTP_SECURITY", SmtpSecurity::StartTls);
+        let default_port = match security {
+            SmtpSecurity::StartTls => 587,
+            SmtpSecurity::Tls => 465,
+            SmtpSecurity::None => 25,
+        };
+
+        Self {
+            host: l.required("SMTP_HOST"),
+            port: l.parse_or("SMTP_PORT", default_port),
+            security,
+            username: l.optional("SMTP_USERNAME"),
+            password: l.optional_secret("SMTP_PASSWORD"),
+        }
+    }
+}
+
+impl CryoPayConfig {
+    fn read(l: &mut Loader, networks: &[Network]) -> Option<Self> {
+        let seller = l.optional("CRYO_PAY_SELF_ADDRESS")?;
+
+        let names = l.parsed("CRYO_PAY_RECIEVE_FROM_NETWORKS", |value| {
+            serde_json::from_str::<Vec<String>>(value).map_err(|err| err.to_string())
+        }).unwrap_or_default();
+        if let Some(unknown) = names.iter().find(|name| !networks.iter().any(|n| &n.name == *name)) {
+            l.error(format!("CRYO_PAY_RECIEVE_FROM_NETWORKS: unknown network {unknown}"));
+        }
+
+        let api_global_url = l.parsed("API_GLOBAL_URL", |value| ApiGlobalUrl::from_str(value.to_string()))?;
+
+        Some(Self {
+            seller,
+            networks: networks.iter().filter(|n| names.contains(&n.name)).map(|n| n.id).collect(),
+            api_key: l.optional_secret("CRYO_PAY_API_KEY"),
+            api_global_url,
+        })
+    }
+}
+
+impl AbiConfig {
+    fn read(l: &mut Loader) -> Self {
+        let mut load = |key: &str| l
+            .parsed(key, |path| std::fs::read_to_string(path)
+                .map_err(|err| format!("cannot read {path}: {err}"))
+                .and_then(|contents| serde_json::from_str::<Value>(&contents)
+                    .map_err(|err| format!("invalid JSON in {path}: {err}"))))
+            .unwrap_or_default();
+
+        Self { erc20: load("ERC20_ABI_PATH"), contract: load("CONTRACT_ABI_PATH") }
+    }
+}
+
+impl RateLimits {
+    fn read(l: &mut Loader) -> Self {
+        let default = Self::default();
+        Self {
+            product_invoice_api_per_day: l.parse_or("RATE_LIMIT_PRODUCT_INVOICE_API_PER_DAY", default.product_invoice_api_per_day),
+            product_invoice_web_per_day: l.parse_or("RATE_LIMIT_PRODUCT_INVOICE_WEB_PER_DAY", default.product_invoice_web_per_day),
+            product_invoice_anonymous_per_day: l.parse_or("RATE_LIMIT_PRODUCT_INVOICE_ANONYMOUS_PER_DAY", default.product_invoice_anonymous_per_day),
+            user_invoice_per_day: l.parse_or("RATE_LIMIT_USER_INVOICE_PER_DAY", default.user_invoice_per_day),
+            login_per_day: l.parse_or("RATE_LIMIT_LOGIN_PER_DAY", default.login_per_day),
+            webhook_create_per_minute: l.parse_or("RATE_LIMIT_WEBHOOK_CREATE_PER_MINUTE", default.webhook_create_per_minute),
+            checkout_per_minute: l.parse_or("RATE_LIMIT_CHECKOUT_PER_MINUTE", default.checkout_per_minute),
+        }
+    }
+}
+
+impl Limits {
+    fn read(l: &mut Loader) -> Self {
+        let default = Self::default();
+        Self {
+            api_keys_per_user: l.parse_or("API_KEYS_PER_USER", default.api_keys_per_user),
+            webhooks_per_user: l.parse_or("WEBHOOKS_PER_USER", default.webhooks_per_user),
+            callback_urls_per_user: l.parse_or("CALLBACK_URLS_PER_USER", default.callback_urls_per_user),
+            telegram_destinations_per_user: l.parse_or("TELEGRAM_DESTINATIONS_PER_USER", default.telegram_destinations_per_user),
+            chat_webhooks_per_user: l.parse_or("CHAT_WEBHOOKS_PER_USER", default.chat_webhooks_per_user),
+            jwt_expiry_hours: l.parse_or("JWT_EXPIRY_HOURS", default.jwt_expiry_hours),
+            session_cookie_days: l.parse_or("SESSION_COOKIE_DAYS", default.session_cookie_days),
+            gas_fee_cache_ttl_secs: l.parse_or("GAS_FEE_CACHE_TTL_SECS", default.gas_fee_cache_ttl_secs),
+            telegram_link_ttl_secs: l.parse_or("TELEGRAM_LINK_TTL_SECS", default.telegram_link_ttl_secs),
+            rate: RateLimits::read(l),
+        }
+    }
+}
+
+fn read_networks(l: &mut Loader) -> Vec<Network> {
+    let networks = l.parsed("NETWORKS", Network::parse_networks).unwrap_or_default();
+    if networks.is_empty() && l.optional("NETWORKS").is_some() {
+        l.error("NETWORKS: at least one network must be configured".to_string());
+    }
+    networks
+}
+
+fn read_url(l: &mut Loader, key: &str) -> String {
+    l.parsed(key, |value| url::Url::parse(value)
+        .map(|_| value.trim_end_matches('/').to_string())
+        .map_err(|err| err.to_string()))
+        .unwrap_or_default()
+}
+
+impl ApiConfig {
+    pub fn load(source: &ConfigSource) -> Result<Self, String> {
+        let mut l = Loader::new(source);
+        let networks = read_networks(&mut l);
+
+        let config = Self {
+            database: DatabaseConfig::read(&mut l),
+            redis_url: l.secret("REDIS_URL"),
+            host: l.required("HOST"),
+            port: l.parsed("PORT", |value| value.parse::<u16>().map_err(|err| err.to_string())).unwrap_or_default(),
+            web_base_url: read_url(&mut l, "WEB_BASE_URL"),
+            app_secret: l.secret("APP_SECRET"),
+            infura_token: l.enabled(INFURA_GAS_FEES_ENABLED, "infura", cfg!(feature = "infura"))
+                .then(|| l.secret("INFURA_TOKEN")),
+            firebase_auth: l.enabled(FIREBASE_AUTH_ENABLED, "firebase", cfg!(feature = "firebase")),
+            abi: AbiConfig::read(&mut l),
+            telegram: TelegramConfig::read(&mut l),
+            cryo_pay: CryoPayConfig::read(&mut l, &networks),
+            limits: Limits::read(&mut l),
+            networks,
+        };
+
+        l.finish(config)
+    }
+
+    pub fn bind_address(&self) -> String {
+        format!("{}:{}", self.host, self.port)
+    }
+
+    /// Base URL the API uses to call itself (Cryo Pay self-payments).
+    pub fn self_url(&self) -> String {
+        format!("http://{}", self.bind_address())
+    }
+}
+
+impl MonitorConfig {
+    pub fn load(source: &ConfigSource) -> Result<Self, String> {
+        let mut l = Loader::new(source);
+
+        let config = Self {
+            database: DatabaseConfig::read(&mut l),
+            networks: read_networks(&mut l),
+            event_signature: l.required("EVENT_SIGNATURE"),
+            infura_rpm: l.parse_or("INFRA_RPM", 1),
+            web_base_url: read_url(&mut l, "WEB_BASE_URL"),
+            telegram: TelegramConfig::read(&mut l),
+            mailer: MailerConfig::read(&mut l),
+        };
+
+        l.finish(config)
+    }
+}
+
+impl BotConfig {
+    /// `Ok(None)` when the Telegram integration is disabled.
+    pub fn load(source: &ConfigSource) -> Result<Option<Self>, String> {
+        let mut l = Loader::new(source);
+
+        let config = TelegramConfig::read(&mut l).map(|telegram| Self {
+            database: DatabaseConfig::read(&mut l),
+            redis_url: l.secret("REDIS_URL"),
+            telegram,
+            web_base_url: read_url(&mut l, "WEB_BASE_URL"),
+            networks: read_networks(&mut l),
+            limits: Limits::read(&mut l),
+        });
+
+        l.finish(config)
+    }
+}
+
+/// Networks alone, for operator commands that do not need a full component config.
+pub fn load_networks(source: &ConfigSource) -> Result<Vec<Network>, String> {
+    let mut l = Loader::new(source);
+    let networks = read_networks(&mut l);
+    l.finish(networks)
+}
+
+pub fn load_app_secret(source: &ConfigSource) -> Result<String, String> {
+    let mut l = Loader::new(source);
+    let secret = l.secret("APP_SECRET");
+    l.finish(secret)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    const ENCRYPTION_KEY: &str = "k1:0101010101010101010101010101010101010101010101010101010101010101";
+    const NETWORKS: &str = r#"[{"name":"Optimism","id":10,"link":"https://rpc.example.com","addresses":{"erc20":"0xabc","contract":"0xdef"}}]"#;
+
+    fn source(env: &[(&str, &str)]) -> ConfigSource {
+        ConfigSource::new(
+            env.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect::<HashMap<_, _>>(),
+            toml::Table::new(),
+        )
+    }
+
+    #[cfg(feature = "telegram")]
+    #[test]
+    fn test_bot_config_does_not_need_api_or_monitor_settings() {
+        let config = BotConfig::load(&source(&[
+            ("POSTGRES_URL", "postgres://db"), ("ENCRYPTION_KEY", ENCRYPTION_KEY), ("TGBOT_TOKEN", "t"), ("REDIS_URL", "redis://r"),
+            ("WEB_BASE_URL", "https://example.com"), ("NETWORKS", NETWORKS),
+        ])).unwrap();
+        assert!(config.unwrap().telegram.webhook.is_none());
+    }
+
+    #[cfg(feature = "telegram")]
+    #[test]
+    fn test_telegram_webhook_needs_https_and_a_secret() {
+        let read = |env: &[(&str, &str)]| {
+            let source = source(env);
+            let mut l = Loader::new(&source);
+            let config = TelegramConfig::read(&mut l);
+            l.finish(config).map(|config| config.unwrap().webhook)
+        };
+
+        assert!(read(&[("TGBOT_TOKEN", "t")]).unwrap().is_none());
+        assert!(read(&[("TGBOT_TOKEN", "t"), ("TELEGRAM_WEBHOOK_URL", "false")]).unwrap().is_none());
+        let webhook = read(&[
+            ("TGBOT_TOKEN", "t"), ("TELEGRAM_WEBHOOK_URL", "https://example.com/api/telegram/webhook"), ("TELEGRAM_WEBHOOK_SECRET", "s3cret_-"),
+        ]).unwrap().unwrap();
+        assert_eq!((webhook.url.as_str(), webhook.secret.as_str()), ("https://example.com/api/telegram/webhook", "s3cret_-"));
+
+        assert!(read(&[("TGBOT_TOKEN", "t"), ("TELEGRAM_WEBHOOK_URL", "https://example.com/hook")]).err().unwrap().contains("TELEGRAM_WEBHOOK_SECRET"));
+        assert!(read(&[("TGBOT_TOKEN", "t"), ("TELEGRAM_WEBHOOK_URL", "http://example.com/hook"), ("TELEGRAM_WEBHOOK_SECRET", "s")])
+            .err().unwrap().contains("https"));
+        assert!(read(&[("TGBOT_TOKEN", "t"), ("TELEGRAM_WEBHOOK_URL", "https://example.com/hook"), ("TELEGRAM_WEBHOOK_SECRET", "not secret!")])
+            .err().unwrap().contains("TELEGRAM_WEBHOOK_SECRET"));
+    }
+
+    #[test]
+    fn test_disabled_integrations_need_no_credentials() {
+        let source = source(&[
+            ("POSTGRES_URL", "postgres://db"), ("ENCRYPTION_KEY", ENCRYPTION_KEY), ("NETWORKS", NETWORKS), ("EVENT_SIGNATURE", "x"),
+            ("WEB_BASE_URL", "https://example.com"), ("TELEGRAM_ENABLED", "false"), ("MAILER_ENABLED", "0"),
+        ]);
+        let config = MonitorConfig::load(&source).unwrap();
+        assert!(config.telegram.is_none());
+        assert!(config.mailer.is_none());
+        assert!(BotConfig::load(&source).unwrap().is_none());
+    }
+
+    #[cfg(all(feature = "telegram", feature = "mailer"))]
+    #[test]
+    fn test_monitor_config_reports_every_missing_key() {
+        let err = MonitorConfig::load(&source(&[("NETWORKS", NETWORKS)])).err().unwrap();
+        for key in ["POSTGRES_URL", "ENCRYPTION_KEY", "EVENT_SIGNATURE", "WEB_BASE_URL", "TGBOT_TOKEN", "BREVO_API_KEY", "EMAIL_SENDER"] {
+            assert!(err.contains(key), "{key} missing from {err}");
+        }
+        assert!(!err.contains("NETWORKS"));
+    }
+
+    #[test]
+    fn test_encryption_keys() {
+        let previous = format!("k0:{}", "02".repeat(32));
+        let config = DatabaseConfig::load(&source(&[
+            ("POSTGRES_URL", "postgres://db"), ("ENCRYPTION_KEY", ENCRYPTION_KEY), ("ENCRYPTION_PREVIOUS_KEYS", &previous),
+        ])).unwrap();
+        assert_eq!(config.encryption_keys.iter().map(|key| key.id.as_str()).collect::<Vec<_>>(), ["k1", "k0"]);
+
+        let err = DatabaseConfig::load(&source(&[("POSTGRES_URL", "postgres://db"), ("ENCRYPTION_KEY", "k1:abcd")])).err().unwrap();
+        assert!(err.contains("ENCRYPTION_KEY: key must be 32 bytes"), "{err}");
+    }
+
+    #[test]
+    fn test_mail_transports() {
+        let read = |env: &[(&str, &str)]| {
+            let source = source(env);
+            let mut l = Loader::new(&source);
+            let transport = MailTransportConfig::read(&mut l);
+            l.finish(transport)
+        };
+
+        assert!(matches!(read(&[("BREVO_API_KEY", "k")]), Ok(MailTransportConfig::Brevo { .. })));
+        assert!(matches!(read(&[("MAILER_TRANSPORT", "file"), ("EMAIL_FILE_DIR", "/tmp/mail")]), Ok(MailTransportConfig::File { .. })));
+
+        let Ok(MailTransportConfig::Smtp(smtp)) = read(&[("MAILER_TRANSPORT", "smtp"), ("SMTP_HOST", "mail"), ("SMTP_SECURITY", "TLS")]) else {
+            panic!("expected SMTP");
+        };
+        assert_eq!((smtp.security, smtp.port), (SmtpSecurity::Tls, 465));
+
+        let err = read(&[("MAILER_TRANSPORT", "smtp"), ("SMTP_SECURITY", "ssl")]).err().unwrap();
+        assert!(err.contains("SMTP_HOST must be set") && err.contains("SMTP_SECURITY"), "{err}");
+        assert!(read(&[("MAILER_TRANSPORT", "sendgrid")]).err().unwrap().contains("unknown transport sendgrid"));
+    }
+
+    #[test]
+    fn test_invalid_networks_rejected() {
+        assert!(load_networks(&source(&[("NETWORKS", "not json")])).is_err());
+        assert!(load_networks(&source(&[("NETWORKS", "[]")])).is_err());
+        assert!(load_networks(&source(&[])).is_err());
+        assert_eq!(load_networks(&source(&[("NETWORKS", NETWORKS)])).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_networks_from_toml_table() {
+        let file = r#"
+            [[networks]]
+            name = "Optimism"
+            id = 10
+            link = "https://rpc.example.com"
+            addresses = { erc20 = "0xabc", contract = "0xdef" }
+        "#;
+        let source = ConfigSource::new(HashMap::new(), file.parse().unwrap());
+        assert_eq!(load_networks(&source).unwrap()[0].id, 10);
+    }
+
+    #[test]
+    fn test_limits_defaults_and_overrides() {
+        let source = source(&[("WEBHOOKS_PER_USER", "4")]);
+        let mut l = Loader::new(&source);
+        let limits = Limits::read(&mut l);
+        assert!(l.finish(()).is_ok());
+        assert_eq!(limits.webhooks_per_user, 4);
+        assert_eq!(limits.api_keys_per_user, 5);
+        assert_eq!(limits.session_cookie_days, 7);
+        assert_eq!(limits.gas_fee_cache_ttl_secs, 600);
+        assert_eq!(limits.telegram_link_ttl_secs, 600);
+        assert_eq!(limits.telegram_destinations_per_user, 5);
+        assert_eq!(limits.chat_webhooks_per_user, 5);
+    }
+
+    #[test]
+    fn test_cryo_pay_is_optional_but_validated_when_enabled() {
+        let source_without = source(&[]);
+        let mut l = Loader::new(&source_without);
+        assert!(CryoPayConfig::read(&mut l, &[]).is_none());
+        assert!(l.finish(()).is_ok());
+
+        let networks = Network::parse_networks(NETWORKS).unwrap();
+        let source_with = source(&[
+            ("CRYO_PAY_SELF_ADDRESS", "0xself"),
+            ("CRYO_PAY_RECIEVE_FROM_NETWORKS", r#"["Optimism","Base"]"#),
+            ("API_GLOBAL_URL", "/api"),
+        ]);
+        let mut l = Loader::new(&source_with);
+        let config = CryoPayConfig::read(&mut l, &networks).unwrap();
+        assert_eq!(config.networks, vec![10]);
+        assert!(l.finish(()).unwrap_err().contains("unknown network Base"));
+    }
+}
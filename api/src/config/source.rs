@@ -0,0 +1,210 @@
+use std::collections::HashMap;
+use std::fmt::Display;
+use std::path::Path;
+use std::str::FromStr;
+use toml::{Table, Value};
+use crate::utils;
+
+/// Where configuration values come from. Environment variables win over the TOML file;
+/// secrets may also be given as `<KEY>_FILE` pointing at a file with the value
+/// (e.g. Docker/Kubernetes secrets). File keys are the lowercased variable names.
+pub struct ConfigSource {
+    env: HashMap<String, String>,
+    file: Table,
+}
+
+impl ConfigSource {
+    pub fn load(path: Option<&Path>) -> Result<Self, String> {
+        let file = match path {
+            None => Table::new(),
+            Some(path) => std::fs::read_to_string(path)
+                .map_err(|err| utils::make_err(Box::new(err), &format!("read config file {}", path.display())))?
+                .parse::<Table>()
+                .map_err(|err| utils::make_err(Box::new(err), &format!("parse config file {}", path.display())))?,
+        };
+
+        Ok(Self::new(std::env::vars().collect(), file))
+    }
+
+    pub fn new(env: HashMap<String, String>, file: Table) -> Self {
+        Self { env, file }
+    }
+
+    fn get(&self, key: &str) -> Option<String> {
+        if let Some(value) = self.env.get(key) {
+            return Some(value.clone());
+        }
+
+        self.file.get(&key.to_lowercase()).map(|value| match value {
+            Value::String(s) => s.clone(),
+            // Structured values (e.g. the networks list) are handed over as JSON,
+            // the same shape the corresponding environment variable uses.
+            other => serde_json::to_string(other).unwrap_or_else(|_| other.to_string()),
+        })
+    }
+
+    fn get_secret(&self, key: &str) -> Result<Option<String>, String> {
+        if let Some(value) = self.env.get(key) {
+            return Ok(Some(value.clone()));
+        }
+
+        let file_key = format!("{key}_FILE");
+        if let Some(path) = self.env.get(&file_key).cloned().or_else(|| self.get(&file_key)) {
+            return std::fs::read_to_string(&path)
+                .map(|value| Some(value.trim().to_string()))
+                .map_err(|err| format!("{file_key}: cannot read {path}: {err}"));
+        }
+
+        Ok(self.get(key))
+    }
+}
+
+/// Reads values from a [`ConfigSource`] and collects every problem instead of stopping
+/// at the first one, so `check-config` and startup report the whole list at once.
+pub struct Loader<'a> {
+    source: &'a ConfigSource,
+    errors: Vec<String>,
+}
+
+impl<'a> Loader<'a> {
+    pub fn new(source: &'a ConfigSource) -> Self {
+        Self { source, errors: vec![] }
+    }
+
+    pub fn error(&mut self, message: String) {
+        self.errors.push(message);
+    }
+
+    pub fn optional(&self, key: &str) -> Option<String> {
+        self.source.get(key).filter(|value| !value.is_empty())
+    }
+
+    pub fn required(&mut self, key: &str) -> String {
+        self.optional(key).unwrap_or_else(|| {
+            self.error(format!("{key} must be set"));
+            String::new()
+        })
+    }
+
+    pub fn optional_secret(&mut self, key: &str) -> Option<String> {
+        match self.source.get_secret(key) {
+            Ok(value) => value.filter(|value| !value.is_empty()),
+            Err(err) => {
+                self.error(err);
+                None
+            }
+        }
+    }
+
+    pub fn secret(&mut self, key: &str) -> String {
+        self.optional_secret(key).unwrap_or_else(|| {
+            self.error(format!("{key} or {key}_FILE must be set"));
+            String::new()
+        })
+    }
+
+    /// Runtime switch for an optional integration. Defaults to on when the integration is
+    /// compiled in; turning it on in a binary built without the cargo feature is an error.
+    pub fn enabled(&mut self, key: &str, feature: &str, compiled: bool) -> bool {
+        let enabled = self.optional(key).map(|value| !utils::is_false(&value)).unwrap_or(compiled);
+        if enabled && !compiled {
+            self.error(format!("{key}: this binary was built without the `{feature}` feature"));
+        }
+        enabled && compiled
+    }
+
+    /// Parses a required value, recording an error and returning `None` when it is missing or invalid.
+    pub fn parsed<T>(&mut self, key: &str, parse: impl FnOnce(&str) -> Result<T, String>) -> Option<T> {
+        let value = self.optional(key);
+        match value {
+            None => {
+                self.error(format!("{key} must be set"));
+                None
+            }
+            Some(value) => parse(&value)
+                .map_err(|err| self.error(format!("{key}: {err}")))
+                .ok(),
+        }
+    }
+
+    pub fn parse_or<T>(&mut self, key: &str, default: T) -> T
+    where
+        T: FromStr,
+        T::Err: Display,
+    {
+        match self.optional(key) {
+            None => default,
+            Some(value) => value.parse::<T>().unwrap_or_else(|err| {
+                self.error(format!("{key}: invalid value {value:?}: {err}"));
+                default
+            }),
+        }
+    }
+
+    pub fn finish<T>(self, config: T) -> Result<T, String> {
+        match self.errors.is_empty() {
+            true => Ok(config),
+            false => Err(self.errors.join("; ")),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn source(env: &[(&str, &str)], file: &str) -> ConfigSource {
+        ConfigSource::new(
+            env.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect(),
+            file.parse().expect("valid toml"),
+        )
+    }
+
+    #[test]
+    fn test_env_overrides_file() {
+        let source = source(&[("HOST", "0.0.0.0")], "host = \"127.0.0.1\"\nport = 3000");
+        let loader = Loader::new(&source);
+        assert_eq!(loader.optional("HOST").as_deref(), Some("0.0.0.0"));
+        assert_eq!(loader.optional("PORT").as_deref(), Some("3000"));
+    }
+
+    #[test]
+    fn test_secret_from_file() {
+        let path = std::env::temp_dir().join(format!("cryo-secret-{}", uuid::Uuid::new_v4()));
+        std::fs::write(&path, "s3cret\n").unwrap();
+        let source = source(&[("APP_SECRET_FILE", path.to_str().unwrap())], "");
+
+        let mut loader = Loader::new(&source);
+        assert_eq!(loader.secret("APP_SECRET"), "s3cret");
+        assert!(loader.finish(()).is_ok());
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn test_enabled_defaults_to_compiled_in() {
+        let source = source(&[("MAILER_ENABLED", "false"), ("TELEGRAM_ENABLED", "true")], "");
+        let mut loader = Loader::new(&source);
+        assert!(!loader.enabled("MAILER_ENABLED", "mailer", true));
+        assert!(loader.enabled("INFURA_GAS_FEES_ENABLED", "infura", true));
+        assert!(!loader.enabled("FIREBASE_AUTH_ENABLED", "firebase", false));
+        assert!(loader.finish(()).is_ok());
+
+        let mut loader = Loader::new(&source);
+        assert!(!loader.enabled("TELEGRAM_ENABLED", "telegram", false));
+        assert!(loader.finish(()).unwrap_err().contains("`telegram` feature"));
+    }
+
+    #[test]
+    fn test_collects_all_errors() {
+        let source = source(&[("PORT", "nope")], "");
+        let mut loader = Loader::new(&source);
+        loader.required("HOST");
+        loader.parse_or::<u16>("PORT", 3000);
+        loader.secret("APP_SECRET");
+
+        let err = loader.finish(()).unwrap_err();
+        assert!(err.contains("HOST must be set"));
+        assert!(err.contains("PORT: invalid value"));
+        assert!(err.contains("APP_SECRET or APP_SECRET_FILE must be set"));
+    }
+}
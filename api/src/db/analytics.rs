@@ -9,6 +9,10 @@ pub struct InvoicePeriodStats {
     pub paid_invoices: i64,
     pub total_amount: BigDecimal,
     pub paid_amount: BigDecimal,
+    /// Left out of the totals above.
+    pub expired_invoices: i64,
+    /// Left out of the totals above.
+    pub void_invoices: i64,
 }
 
 #[derive(Debug, Serialize, sqlx::FromRow, utoipa::ToSchema)]
@@ -17,8 +21,13 @@ pub struct InvoiceSummary {
     pub paid_invoices: i64,
     pub total_amount: BigDecimal,
     pub paid_amount: BigDecimal,
+    /// Left out of the totals above.
+    pub expired_invoices: i64,
+    /// Left out of the totals above.
+    pub void_invoices: i64,
 }
 
+/// Totals count open, confirming and paid invoices; expired and void ones are counted apart.
 pub async fn invoice_stats_by_day(
     pg_pool: &PgPool,
     user_id: &Uuid,
@@ -28,10 +37,12 @@ pub async fn invoice_stats_by_day(
         InvoicePeriodStats,
         r#"
         SELECT DATE_TRUNC('day', created_at) AS "period!: NaiveDateTime",
-               COUNT(*) AS "total_invoices!: i64",
-               COUNT(paid_at) AS "paid_invoices!: i64",
-               COALESCE(SUM(amount), 0::NUMERIC) AS "total_amount!: BigDecimal",
-               COALESCE(SUM(CASE WHEN paid_at IS NOT NULL THEN amount ELSE 0::NUMERIC END), 0::NUMERIC) AS "paid_amount!: BigDecimal"
+               COUNT(*) FILTER (WHERE status IN ('open', 'confirming', 'paid')) AS "total_invoices!: i64",
+               COUNT(*) FILTER (WHERE status = 'paid') AS "paid_invoices!: i64",
+               COALESCE(SUM(amount) FILTER (WHERE status IN ('open', 'confirming', 'paid')), 0::NUMERIC) AS "total_amount!: BigDecimal",
+               COALESCE(SUM(amount) FILTER (WHERE status = 'paid'), 0::NUMERIC) AS "paid_amount!: BigDecimal",
+               COUNT(*) FILTER (WHERE status = 'expired') AS "expired_invoices!: i64",
+               COUNT(*) FILTER (WHERE status = 'void') AS "void_invoices!: i64"
         FROM invoice
         WHERE user_id = $1 AND created_at >= $2
         GROUP BY 1 ORDER BY 1 DESC
@@ -51,10 +62,12 @@ pub async fn invoice_summary(
     sqlx::query_as!(
         InvoiceSummary,
         r#"
-        SELECT COUNT(*) AS "total_invoices!: i64",
-               COUNT(paid_at) AS "paid_invoices!: i64",
-               COALESCE(SUM(amount), 0::NUMERIC) AS "total_amount!: BigDecimal",
-               COALESCE(SUM(CASE WHEN paid_at IS NOT NULL THEN amount ELSE 0::NUMERIC END), 0::NUMERIC) AS "paid_amount!: BigDecimal"
+        SELECT COUNT(*) FILTER (WHERE status IN ('open', 'confirming', 'paid')) AS "total_invoices!: i64",
+               COUNT(*) FILTER (WHERE status = 'paid') AS "paid_invoices!: i64",
+               COALESCE(SUM(amount) FILTER (WHERE status IN ('open', 'confirming', 'paid')), 0::NUMERIC) AS "total_amount!: BigDecimal",
+               COALESCE(SUM(amount) FILTER (WHERE status = 'paid'), 0::NUMERIC) AS "paid_amount!: BigDecimal",
+               COUNT(*) FILTER (WHERE status = 'expired') AS "expired_invoices!: i64",
+               COUNT(*) FILTER (WHERE status = 'void') AS "void_invoices!: i64"
         FROM invoice
         WHERE user_id = $1 AND created_at >= $2
         "#,
@@ -99,12 +99,16 @@ pub async fn get_payment(pg_pool: &PgPool, id: &Uuid) -> Result<Option<Payment>,
 
 #[derive(Clone, Serialize, sqlx::FromRow)]
 pub struct Subscription {
-    id: Uuid,
-    user_id: Uuid,
+    pub(super) id: Uuid,
+    pub user_id: Uuid,
     pub target: String,
-    data: Value,
-    created_at: NaiveDateTime,
+    pub(super) data: Value,
+    pub(super) created_at: NaiveDateTime,
     pub until: NaiveDateTime,
+    /// Postgres only compares it in SQL; the in-memory store reads it back.
+    #[serde(skip)]
+    #[allow(dead_code)]
+    pub(super) expiry_notified_until: Option<NaiveDateTime>,
 }
 
 pub async fn create_or_update_subscription(
@@ -167,6 +171,46 @@ pub async fn get_active_subscription(
         .await
 }
 
+/// Subscriptions ending within `(now, before]` whose seller has not been told about this
+/// `until` yet, marked as told.
+pub async fn claim_expiring_subscriptions(
+    pg_pool: &PgPool,
+    now: &NaiveDateTime,
+    before: &NaiveDateTime,
+) -> Result<Vec<Subscription>, sqlx::Error> {
+    sqlx::query_as!(
+        Subscription,
+        r#"
+        UPDATE subscriptions
+        SET expiry_notified_until = until
+        WHERE until > $1 AND until <= $2 AND expiry_notified_until IS DISTINCT FROM until
+        RETURNING *
+        "#,
+        now,
+        before,
+    )
+        .fetch_all(pg_pool)
+        .await
+}
+
+/// Undoes [`claim_expiring_subscriptions`] for a notice that could not be sent, unless the
+/// subscription was renewed meanwhile.
+pub async fn release_subscription_expiry(pg_pool: &PgPool, user_id: &Uuid, target: &str, until: &NaiveDateTime) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        r#"
+        UPDATE subscriptions
+        SET expiry_notified_until = NULL
+        WHERE user_id = $1 AND target = $2 AND expiry_notified_until = $3
+        "#,
+        user_id,
+        target,
+        until,
+    )
+        .execute(pg_pool)
+        .await
+        .map(|_| ())
+}
+
 pub async fn sync_payment_paid_at(pg_pool: &PgPool, id: &Uuid, paid_at: &NaiveDateTime) -> Result<(), sqlx::Error> {
     sqlx::query(
         "UPDATE payments SET paid_at = $1 WHERE id = $2 AND paid_at IS NULL"
@@ -0,0 +1,170 @@
+use sqlx::PgPool;
+use sqlx::types::Uuid;
+use sqlx::types::chrono::NaiveDateTime;
+
+/// A Slack, Discord or generic incoming webhook that posts a seller's notifications to a chat.
+#[derive(Clone, Debug, sqlx::FromRow)]
+pub struct ChatWebhook {
+    pub id: Uuid,
+    pub user_id: Uuid,
+    /// `slack`, `discord` or `generic`, see [`crate::events::notifications::chat::ChatKind`].
+    pub kind: String,
+    pub label: String,
+    pub url: String,
+    /// Catalog names, e.g. `invoice.paid`, see [`crate::events::webhook_event::WebhookEventType`].
+    pub event_types: Vec<String>,
+    pub enabled: bool,
+    pub created_at: NaiveDateTime,
+}
+
+impl ChatWebhook {
+    pub fn accepts(&self, event_type: &str) -> bool {
+        self.enabled && self.event_types.iter().any(|name| name == event_type)
+    }
+}
+
+pub struct NewChatWebhook {
+    pub user_id: Uuid,
+    pub kind: String,
+    pub label: String,
+    pub url: String,
+    pub event_types: Vec<String>,
+}
+
+pub async fn create_chat_webhook(pg_pool: &PgPool, chat_webhook: &NewChatWebhook) -> Result<ChatWebhook, sqlx::Error> {
+    sqlx::query_as!(
+        ChatWebhook,
+        r#"
+        INSERT INTO chat_webhook (user_id, kind, label, url, event_types)
+        VALUES ($1, $2, $3, $4, $5)
+        RETURNING *
+        "#,
+        chat_webhook.user_id,
+        chat_webhook.kind,
+        chat_webhook.label,
+        chat_webhook.url,
+        &chat_webhook.event_types,
+    )
+        .fetch_one(pg_pool)
+        .await
+}
+
+pub async fn get_chat_webhook(pg_pool: &PgPool, id: &Uuid, user_id: &Uuid) -> Result<Option<ChatWebhook>, sqlx::Error> {
+    sqlx::query_as!(
+        ChatWebhook,
+        r#"
+        SELECT * FROM chat_webhook
+        WHERE id = $1 AND user_id = $2
+        "#,
+        id,
+        user_id,
+    )
+        .fetch_optional(pg_pool)
+        .await
+}
+
+pub async fn list_chat_webhooks(pg_pool: &PgPool, user_id: &Uuid) -> Result<Vec<ChatWebhook>, sqlx::Error> {
+    sqlx::query_as!(
+        ChatWebhook,
+        r#"
+        SELECT * FROM chat_webhook
+        WHERE user_id = $1
+        ORDER BY created_at
+        "#,
+        user_id,
+    )
+        .fetch_all(pg_pool)
+        .await
+}
+
+pub async fn count_chat_webhooks(pg_pool: &PgPool, user_id: &Uuid) -> Result<i64, sqlx::Error> {
+    sqlx::query_scalar!(
+        r#"
+        SELECT COUNT(*) AS "count!" FROM chat_webhook
+        WHERE user_id = $1
+        "#,
+        user_id,
+    )
+        .fetch_one(pg_pool)
+        .await
+}
+
+/// Changes only the fields that are `Some`. `None` when the webhook does not exist.
+pub async fn update_chat_webhook(
+    pg_pool: &PgPool,
+    id: &Uuid,
+    user_id: &Uuid,
+    label: Option<&str>,
+    url: Option<&str>,
+    enabled: Option<bool>,
+    event_types: Option<&[String]>,
+) -> Result<Option<ChatWebhook>, sqlx::Error> {
+    sqlx::query_as!(
+        ChatWebhook,
+        r#"
+        UPDATE chat_webhook
+        SET label = COALESCE($3, label),
+            url = COALESCE($4, url),
+            enabled = COALESCE($5, enabled),
+            event_types = COALESCE($6, event_types)
+        WHERE id = $1 AND user_id = $2
+        RETURNING *
+        "#,
+        id,
+        user_id,
+        label,
+        url,
+        enabled,
+        event_types,
+    )
+        .fetch_optional(pg_pool)
+        .await
+}
+
+pub async fn set_chat_webhook_url(pg_pool: &PgPool, id: &Uuid, url: &str) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        r#"
+        UPDATE chat_webhook
+        SET url = $2
+        WHERE id = $1
+        "#,
+        id,
+        url,
+    )
+        .execute(pg_pool)
+        .await?;
+
+    Ok(())
+}
+
+pub async fn delete_chat_webhook(pg_pool: &PgPool, id: &Uuid, user_id: &Uuid) -> Result<bool, sqlx::Error> {
+    let result = sqlx::query!(
+        r#"
+        DELETE FROM chat_webhook
+        WHERE id = $1 AND user_id = $2
+        "#,
+        id,
+        user_id,
+    )
+        .execute(pg_pool)
+        .await?;
+
+    Ok(result.rows_affected() > 0)
+}
+
+/// Chat webhooks of every user ordered by id, starting after `after`; for walking the whole table in batches.
+pub async fn list_chat_webhooks_after(pg_pool: &PgPool, after: &Uuid, limit: i64) -> Result<Vec<ChatWebhook>, sqlx::Error> {
+    sqlx::query_as!(
+        ChatWebhook,
+        r#"
+        SELECT * FROM chat_webhook
+        WHERE id > $1
+        ORDER BY id
+        LIMIT $2
+        "#,
+        after,
+        limit,
+    )
+        .fetch_all(pg_pool)
+        .await
+}
@@ -0,0 +1,277 @@
+//! Envelope encryption of credentials at rest (webhook secrets, emails, Telegram chat ids).
+//!
+//! Every value gets its own random data key; the value is sealed with the data key and the
+//! data key with a key-encryption key from config. Stored values look like
+//! `enc1:<key id>:<hex wrapped data key>:<hex ciphertext>`. Rotating the key-encryption key
+//! only re-wraps the data keys (see `api reencrypt`). Values without the prefix were written
+//! before encryption existed and are read as plaintext.
+//!
+//! Encrypted values cannot be searched, so columns that need an equality lookup also store
+//! a keyed hash, `<key id>:<hex HMAC-SHA256>`, see [`Keyring::lookup_hash`].
+
+use std::fmt;
+use aes_gcm::aead::{Aead, AeadCore, KeyInit, OsRng};
+use aes_gcm::{Aes256Gcm, Key, Nonce};
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+
+const PREFIX: &str = "enc1";
+const NONCE_LEN: usize = 12;
+
+/// A key-encryption key with the id stored next to what it wraps.
+#[derive(Clone)]
+pub struct DataKey {
+    pub id: String,
+    key: Key<Aes256Gcm>,
+}
+
+impl DataKey {
+    /// `<id>:<64 hex chars>`, e.g. `2026-10:9f86d0…`.
+    pub fn parse(value: &str) -> Result<Self, String> {
+        let (id, key) = value.trim().split_once(':').ok_or("expected <id>:<hex key>")?;
+        if id.is_empty() || id.contains(':') {
+            return Err("key id must be non-empty and without ':'".to_string());
+        }
+
+        let key = hex::decode(key).map_err(|_| "key must be hex")?;
+        if key.len() != 32 {
+            return Err(format!("key must be 32 bytes, got {}", key.len()));
+        }
+
+        Ok(Self { id: id.to_string(), key: *Key::<Aes256Gcm>::from_slice(&key) })
+    }
+
+    /// Comma-separated list of [`DataKey::parse`] values.
+    pub fn parse_list(value: &str) -> Result<Vec<Self>, String> {
+        value.split(',')
+            .filter(|key| !key.trim().is_empty())
+            .map(Self::parse)
+            .collect()
+    }
+}
+
+impl fmt::Debug for DataKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("DataKey").field("id", &self.id).finish_non_exhaustive()
+    }
+}
+
+/// Encrypts with the current key and decrypts with any configured one.
+pub struct Keyring {
+    current: DataKey,
+    previous: Vec<DataKey>,
+}
+
+struct Envelope<'a> {
+    key_id: &'a str,
+    wrapped_key: Vec<u8>,
+    ciphertext: Vec<u8>,
+}
+
+impl<'a> Envelope<'a> {
+    /// `None` for plaintext values.
+    fn parse(value: &'a str) -> Result<Option<Self>, String> {
+        let Some(rest) = value.strip_prefix(PREFIX).and_then(|rest| rest.strip_prefix(':')) else {
+            return Ok(None);
+        };
+
+        let mut parts = rest.splitn(3, ':');
+        let (Some(key_id), Some(wrapped_key), Some(ciphertext)) = (parts.next(), parts.next(), parts.next()) else {
+            return Err("malformed encrypted value".to_string());
+        };
+
+        Ok(Some(Self {
+            key_id,
+            wrapped_key: hex::decode(wrapped_key).map_err(|_| "malformed encrypted value")?,
+            ciphertext: hex::decode(ciphertext).map_err(|_| "malformed encrypted value")?,
+        }))
+    }
+}
+
+fn seal(key: &Key<Aes256Gcm>, plaintext: &[u8]) -> Result<Vec<u8>, String> {
+    let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+    let ciphertext = Aes256Gcm::new(key)
+        .encrypt(&nonce, plaintext)
+        .map_err(|_| "encryption failed")?;
+
+    Ok([nonce.as_slice(), &ciphertext].concat())
+}
+
+fn open(key: &Key<Aes256Gcm>, sealed: &[u8]) -> Result<Vec<u8>, String> {
+    if sealed.len() < NONCE_LEN {
+        return Err("malformed encrypted value".to_string());
+    }
+    let (nonce, ciphertext) = sealed.split_at(NONCE_LEN);
+
+    Aes256Gcm::new(key)
+        .decrypt(Nonce::from_slice(nonce), ciphertext)
+        .map_err(|_| "decryption failed".to_string())
+}
+
+/// The HMAC key is derived from the key-encryption key so the same bytes never serve both purposes.
+fn lookup_hash(key: &DataKey, value: &str) -> String {
+    let mut derive = <Hmac<Sha256> as Mac>::new_from_slice(&key.key).expect("HMAC takes keys of any size");
+    derive.update(b"lookup");
+    let lookup_key = derive.finalize().into_bytes();
+
+    let mut mac = <Hmac<Sha256> as Mac>::new_from_slice(&lookup_key).expect("HMAC takes keys of any size");
+    mac.update(value.as_bytes());
+    format!("{}:{}", key.id, hex::encode(mac.finalize().into_bytes()))
+}
+
+impl Keyring {
+    /// The first key encrypts; all of them decrypt.
+    pub fn new(keys: Vec<DataKey>) -> Result<Self, String> {
+        let mut keys = keys.into_iter();
+        let current = keys.next().ok_or("no encryption key configured")?;
+        Ok(Self { current, previous: keys.collect() })
+    }
+
+    fn key(&self, id: &str) -> Result<&DataKey, String> {
+        std::iter::once(&self.current)
+            .chain(&self.previous)
+            .find(|key| key.id == id)
+            .ok_or_else(|| format!("unknown encryption key {id}"))
+    }
+
+    fn envelope(&self, data_key: &Key<Aes256Gcm>, ciphertext: &[u8]) -> Result<String, String> {
+        let wrapped_key = seal(&self.current.key, data_key)?;
+        Ok(format!("{PREFIX}:{}:{}:{}", self.current.id, hex::encode(wrapped_key), hex::encode(ciphertext)))
+    }
+
+    pub fn encrypt(&self, plaintext: &str) -> Result<String, String> {
+        let data_key = Aes256Gcm::generate_key(&mut OsRng);
+        let ciphertext = seal(&data_key, plaintext.as_bytes())?;
+        self.envelope(&data_key, &ciphertext)
+    }
+
+    pub fn decrypt(&self, value: &str) -> Result<String, String> {
+        let Some(envelope) = Envelope::parse(value)? else {
+            return Ok(value.to_string());
+        };
+
+        let data_key = open(&self.key(envelope.key_id)?.key, &envelope.wrapped_key)?;
+        let plaintext = open(Key::<Aes256Gcm>::from_slice(&data_key), &envelope.ciphertext)?;
+        String::from_utf8(plaintext).map_err(|_| "decrypted value is not UTF-8".to_string())
+    }
+
+    pub fn encrypt_opt(&self, plaintext: Option<&str>) -> Result<Option<String>, String> {
+        plaintext.map(|plaintext| self.encrypt(plaintext)).transpose()
+    }
+
+    pub fn decrypt_opt(&self, value: Option<&str>) -> Result<Option<String>, String> {
+        value.map(|value| self.decrypt(value)).transpose()
+    }
+
+    /// The value under the current key, or `None` when it already is. Plaintext gets encrypted;
+    /// values under an older key only get their data key re-wrapped.
+    pub fn reencrypt(&self, value: &str) -> Result<Option<String>, String> {
+        match Envelope::parse(value)? {
+            None => self.encrypt(value).map(Some),
+            Some(envelope) if envelope.key_id == self.current.id => Ok(None),
+            Some(envelope) => {
+                let data_key = open(&self.key(envelope.key_id)?.key, &envelope.wrapped_key)?;
+                self.envelope(Key::<Aes256Gcm>::from_slice(&data_key), &envelope.ciphertext).map(Some)
+            }
+        }
+    }
+
+    /// Keyed hash of `value` under the current key, for columns searched by equality.
+    pub fn lookup_hash(&self, value: &str) -> String {
+        lookup_hash(&self.current, value)
+    }
+
+    /// Hashes of `value` under every configured key, so rows hashed before a rotation are still found.
+    #[cfg_attr(not(feature = "telegram"), allow(dead_code))]
+    pub fn lookup_hashes(&self, value: &str) -> Vec<String> {
+        std::iter::once(&self.current)
+            .chain(&self.previous)
+            .map(|key| lookup_hash(key, value))
+            .collect()
+    }
+
+    /// Whether `hash` was made with the current key.
+    pub fn is_current_lookup_hash(&self, hash: &str) -> bool {
+        hash.split_once(':').is_some_and(|(key_id, _)| key_id == self.current.id)
+    }
+
+    #[cfg(test)]
+    pub fn for_tests() -> Self {
+        Self::new(vec![DataKey::parse(&format!("test:{}", "11".repeat(32))).unwrap()]).unwrap()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    fn key(id: &str, byte: &str) -> DataKey {
+        DataKey::parse(&format!("{id}:{}", byte.repeat(32))).unwrap()
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let keyring = Keyring::new(vec![key("a", "01")]).unwrap();
+
+        let first = keyring.encrypt("merchant@example.com").unwrap();
+        let second = keyring.encrypt("merchant@example.com").unwrap();
+        assert!(first.starts_with("enc1:a:"));
+        assert_ne!(first, second);
+        assert_eq!(keyring.decrypt(&first).unwrap(), "merchant@example.com");
+        assert_eq!(keyring.decrypt("plain from before").unwrap(), "plain from before");
+    }
+
+    #[test]
+    fn test_tampering_and_unknown_keys_fail() {
+        let keyring = Keyring::new(vec![key("a", "01")]).unwrap();
+        let value = keyring.encrypt("secret").unwrap();
+
+        let mut tampered = value.clone();
+        let last = tampered.pop().unwrap();
+        tampered.push(if last == '0' { '1' } else { '0' });
+        assert!(keyring.decrypt(&tampered).is_err());
+
+        let other = Keyring::new(vec![key("b", "02")]).unwrap();
+        assert!(other.decrypt(&value).unwrap_err().contains("unknown encryption key a"));
+    }
+
+    #[test]
+    fn test_rotation_rewraps_data_keys() {
+        let old = Keyring::new(vec![key("a", "01")]).unwrap();
+        let rotated = Keyring::new(vec![key("b", "02"), key("a", "01")]).unwrap();
+        let value = old.encrypt("secret").unwrap();
+
+        assert_eq!(rotated.decrypt(&value).unwrap(), "secret");
+        let rewrapped = rotated.reencrypt(&value).unwrap().unwrap();
+        assert!(rewrapped.starts_with("enc1:b:"));
+        assert_eq!(rewrapped.rsplit(':').next(), value.rsplit(':').next());
+        assert_eq!(Keyring::new(vec![key("b", "02")]).unwrap().decrypt(&rewrapped).unwrap(), "secret");
+
+        assert!(rotated.reencrypt(&rewrapped).unwrap().is_none());
+        assert!(rotated.reencrypt("plaintext").unwrap().unwrap().starts_with("enc1:b:"));
+    }
+
+    #[test]
+    fn test_lookup_hashes_survive_rotation() {
+        let old = Keyring::new(vec![key("a", "01")]).unwrap();
+        let rotated = Keyring::new(vec![key("b", "02"), key("a", "01")]).unwrap();
+        let hash = old.lookup_hash("4242");
+
+        assert!(hash.starts_with("a:"));
+        assert_eq!(hash, old.lookup_hash("4242"));
+        assert_ne!(hash, old.lookup_hash("4243"));
+        assert_eq!(rotated.lookup_hashes("4242")[1], hash);
+        assert!(!rotated.is_current_lookup_hash(&hash));
+        assert!(rotated.is_current_lookup_hash(&rotated.lookup_hash("4242")));
+    }
+
+    #[rstest]
+    #[case("no-separator")]
+    #[case(":1111")]
+    #[case("a:not-hex")]
+    #[case("a:1111")]
+    fn test_invalid_key(#[case] value: &str) {
+        assert!(DataKey::parse(value).is_err());
+    }
+}
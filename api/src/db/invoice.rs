@@ -1,9 +1,70 @@
 use bigdecimal::BigDecimal;
+use serde::Serialize;
+use serde_json::Value;
 use sqlx::PgPool;
 use sqlx::types::Uuid;
 use sqlx::types::chrono::NaiveDateTime;
 use super::Invoice;
+use super::invoice_event::{record_invoice_events, CREATED_EVENT, UPDATED_EVENT};
 
+/// Where an invoice is in its life cycle; stored in `invoice.status`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, sqlx::Type, utoipa::ToSchema)]
+#[serde(rename_all = "lowercase")]
+#[sqlx(type_name = "text", rename_all = "lowercase")]
+pub enum InvoiceStatus {
+    /// Waiting for a payment.
+    Open,
+    /// The buyer sent a transaction that is not mined yet.
+    Confirming,
+    Paid,
+    /// Not paid before `expires_at`.
+    Expired,
+    /// Cancelled by its owner.
+    Void,
+}
+
+impl InvoiceStatus {
+    pub const ALL: [Self; 5] = [Self::Open, Self::Confirming, Self::Paid, Self::Expired, Self::Void];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Open => "open",
+            Self::Confirming => "confirming",
+            Self::Paid => "paid",
+            Self::Expired => "expired",
+            Self::Void => "void",
+        }
+    }
+
+    /// Whether a payment can still settle an invoice in this status.
+    pub fn is_payable(&self) -> bool {
+        matches!(self, Self::Open | Self::Confirming)
+    }
+
+    /// Paid, expired and void are final; unpaid invoices move anywhere else but paid, which
+    /// only [`set_invoice_paid`] sets, together with the payment.
+    pub fn can_become(&self, next: Self) -> bool {
+        self.is_payable() && *self != next && next != Self::Paid
+    }
+
+    /// The statuses an invoice can move to `self` from.
+    pub fn sources(&self) -> Vec<Self> {
+        Self::ALL.into_iter().filter(|status| status.can_become(*self)).collect()
+    }
+}
+
+/// The on-chain transfer that settles an invoice.
+#[derive(Clone, Debug)]
+pub struct InvoicePayment {
+    pub seller: String,
+    pub amount: BigDecimal,
+    pub buyer: String,
+    pub paid_at: NaiveDateTime,
+    pub network: i32,
+    pub tx_hash: String,
+}
+
+/// Void invoices are left out of lists; expired ones stay, with their status.
 pub async fn user_own_invoices(
     pg_pool: &PgPool,
     limit: i64,
@@ -13,8 +74,8 @@ pub async fn user_own_invoices(
     sqlx::query_as!(
         Invoice,
         r#"
-        SELECT * FROM invoice
-        WHERE user_id = $1
+        SELECT id, created_at, amount, seller, buyer, paid_at, networks, user_id, external_id, is_private, paid_network, tx_hash, buyer_email, send_receipt, status AS "status: InvoiceStatus", status_changed_at, expires_at, confirming_at, metadata FROM invoice
+        WHERE user_id = $1 AND status <> 'void'
         ORDER BY created_at DESC
         LIMIT $2 OFFSET $3"#,
         user_id,
@@ -35,8 +96,8 @@ pub async fn list_invoices(
         None => sqlx::query_as!(
             Invoice,
             r#"
-            SELECT * FROM invoice
-            WHERE is_private = false
+            SELECT id, created_at, amount, seller, buyer, paid_at, networks, user_id, external_id, is_private, paid_network, tx_hash, buyer_email, send_receipt, status AS "status: InvoiceStatus", status_changed_at, expires_at, confirming_at, metadata FROM invoice
+            WHERE is_private = false AND status <> 'void'
             ORDER BY created_at DESC
             LIMIT $1 OFFSET $2"#,
             limit,
@@ -47,8 +108,8 @@ pub async fn list_invoices(
         Some(user_id) => sqlx::query_as!(
             Invoice,
             r#"
-            SELECT invoice.* FROM invoice
-            WHERE is_private = false OR user_id = $1
+            SELECT id, created_at, amount, seller, buyer, paid_at, networks, user_id, external_id, is_private, paid_network, tx_hash, buyer_email, send_receipt, status AS "status: InvoiceStatus", status_changed_at, expires_at, confirming_at, metadata FROM invoice
+            WHERE (is_private = false OR user_id = $1) AND status <> 'void'
             ORDER BY created_at DESC
             LIMIT $2 OFFSET $3"#,
             user_id,
@@ -60,38 +121,58 @@ pub async fn list_invoices(
     }
 }
 
+/// Settings written together with the invoice by [`create_invoice`], so it is never visible
+/// half-configured.
+#[derive(Clone, Debug, Default)]
+pub struct InvoiceOptions {
+    pub is_private: bool,
+    pub send_receipt: bool,
+    /// Encrypted by the time it reaches the repository.
+    pub buyer_email: Option<String>,
+    pub expires_at: Option<NaiveDateTime>,
+    pub metadata: Option<Value>,
+}
+
 pub async fn create_invoice(
     pg_pool: &PgPool,
     amount: BigDecimal,
     seller: &str,
-    networks: &Vec<i32>,
+    networks: &[i32],
     user_id: Option<Uuid>,
     external_id: Option<String>,
-    is_private: bool,
+    options: InvoiceOptions,
 ) -> Result<Invoice, sqlx::Error> {
-    sqlx::query_as!(
+    let mut tx = pg_pool.begin().await?;
+    let invoice = sqlx::query_as!(
         Invoice,
         r#"
-        INSERT INTO invoice (amount, seller, networks, user_id, external_id, is_private)
-        VALUES ($1, $2, $3, $4, $5, $6)
-        RETURNING *
+        INSERT INTO invoice (amount, seller, networks, user_id, external_id, is_private, send_receipt, buyer_email, expires_at, metadata)
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10)
+        RETURNING id, created_at, amount, seller, buyer, paid_at, networks, user_id, external_id, is_private, paid_network, tx_hash, buyer_email, send_receipt, status AS "status: InvoiceStatus", status_changed_at, expires_at, confirming_at, metadata
         "#,
         amount,
         seller.to_lowercase(),
         networks,
         user_id,
         external_id,
-        is_private,
+        options.is_private,
+        options.send_receipt,
+        options.buyer_email,
+        options.expires_at,
+        options.metadata,
     )
-        .fetch_one(pg_pool)
-        .await
+        .fetch_one(&mut *tx)
+        .await?;
+    record_invoice_events(&mut tx, &[invoice.id], CREATED_EVENT, None).await?;
+    tx.commit().await?;
+    Ok(invoice)
 }
 
 pub async fn get_invoice(db: &PgPool, id: &Uuid) -> Result<Option<Invoice>, sqlx::Error> {
     sqlx::query_as!(
         Invoice,
         r#"
-        SELECT * FROM invoice
+        SELECT id, created_at, amount, seller, buyer, paid_at, networks, user_id, external_id, is_private, paid_network, tx_hash, buyer_email, send_receipt, status AS "status: InvoiceStatus", status_changed_at, expires_at, confirming_at, metadata FROM invoice
         WHERE id = $1
         "#,
         id
@@ -119,36 +200,198 @@ pub async fn get_is_owner(db: &PgPool, id: &Uuid, user_id: &Uuid) -> Result<bool
     }
 }
 
-pub async fn set_invoice_paid(db: &PgPool, id: Uuid, seller: &str, amount: BigDecimal, buyer: &str, paid_at: NaiveDateTime) -> Result<Invoice, sqlx::Error> {
+pub async fn set_invoice_paid(db: &PgPool, id: Uuid, payment: &InvoicePayment) -> Result<Invoice, sqlx::Error> {
+    let mut tx = db.begin().await?;
+    let invoice = sqlx::query_as!(
+        Invoice,
+        r#"
+        UPDATE invoice
+        SET buyer = $1, paid_at = $2, paid_network = $6, tx_hash = $7, status = 'paid', status_changed_at = NOW()
+        WHERE id = $3 AND seller = $4 AND amount = $5 AND status IN ('open', 'confirming')
+        RETURNING id, created_at, amount, seller, buyer, paid_at, networks, user_id, external_id, is_private, paid_network, tx_hash, buyer_email, send_receipt, status AS "status: InvoiceStatus", status_changed_at, expires_at, confirming_at, metadata
+        "#,
+        payment.buyer.to_lowercase(),
+        payment.paid_at,
+        id,
+        payment.seller.to_lowercase(),
+        payment.amount,
+        payment.network,
+        payment.tx_hash,
+    )
+        .fetch_one(&mut *tx)
+        .await?;
+    record_invoice_events(&mut tx, &[invoice.id], InvoiceStatus::Paid.as_str(), None).await?;
+    tx.commit().await?;
+    Ok(invoice)
+}
+
+/// Moves the invoice to `to` if it is currently in one of `from`; `None` otherwise, and when
+/// `to` is confirming but the invoice already was.
+pub async fn set_invoice_status(db: &PgPool, id: &Uuid, from: &[InvoiceStatus], to: InvoiceStatus) -> Result<Option<Invoice>, sqlx::Error> {
+    let from = from.iter().map(|status| status.as_str().to_string()).collect::<Vec<_>>();
+    let mut tx = db.begin().await?;
+    let invoice = sqlx::query_as!(
+        Invoice,
+        r#"
+        UPDATE invoice
+        SET status = $3, status_changed_at = NOW(),
+            confirming_at = CASE WHEN $3 = 'confirming' THEN NOW() ELSE confirming_at END
+        WHERE id = $1 AND status = ANY($2) AND NOT ($3 = 'confirming' AND confirming_at IS NOT NULL)
+        RETURNING id, created_at, amount, seller, buyer, paid_at, networks, user_id, external_id, is_private, paid_network, tx_hash, buyer_email, send_receipt, status AS "status: InvoiceStatus", status_changed_at, expires_at, confirming_at, metadata
+        "#,
+        id,
+        &from,
+        to.as_str(),
+    )
+        .fetch_optional(&mut *tx)
+        .await?;
+    if let Some(invoice) = &invoice {
+        record_invoice_events(&mut tx, &[invoice.id], to.as_str(), None).await?;
+    }
+    tx.commit().await?;
+    Ok(invoice)
+}
+
+/// Expires unpaid invoices past `expires_at`. Confirming ones get until `confirming_before`
+/// after they started confirming, so a transaction already sent can still be mined.
+pub async fn expire_invoices(db: &PgPool, now: NaiveDateTime, confirming_before: NaiveDateTime) -> Result<Vec<Invoice>, sqlx::Error> {
+    let mut tx = db.begin().await?;
+    let invoices = sqlx::query_as!(
+        Invoice,
+        r#"
+        UPDATE invoice
+        SET status = 'expired', status_changed_at = NOW()
+        WHERE expires_at <= $1
+          AND (status = 'open' OR (status = 'confirming' AND status_changed_at <= $2))
+        RETURNING id, created_at, amount, seller, buyer, paid_at, networks, user_id, external_id, is_private, paid_network, tx_hash, buyer_email, send_receipt, status AS "status: InvoiceStatus", status_changed_at, expires_at, confirming_at, metadata
+        "#,
+        now,
+        confirming_before,
+    )
+        .fetch_all(&mut *tx)
+        .await?;
+    let ids = invoices.iter().map(|invoice| invoice.id).collect::<Vec<_>>();
+    record_invoice_events(&mut tx, &ids, InvoiceStatus::Expired.as_str(), None).await?;
+    tx.commit().await?;
+    Ok(invoices)
+}
+
+/// Reopens confirming invoices whose transaction was never mined and that have not expired.
+pub async fn reopen_stale_invoices(db: &PgPool, now: NaiveDateTime, confirming_before: NaiveDateTime) -> Result<Vec<Invoice>, sqlx::Error> {
+    let mut tx = db.begin().await?;
+    let invoices = sqlx::query_as!(
+        Invoice,
+        r#"
+        UPDATE invoice
+        SET status = 'open', status_changed_at = NOW()
+        WHERE status = 'confirming' AND status_changed_at <= $2
+          AND (expires_at IS NULL OR expires_at > $1)
+        RETURNING id, created_at, amount, seller, buyer, paid_at, networks, user_id, external_id, is_private, paid_network, tx_hash, buyer_email, send_receipt, status AS "status: InvoiceStatus", status_changed_at, expires_at, confirming_at, metadata
+        "#,
+        now,
+        confirming_before,
+    )
+        .fetch_all(&mut *tx)
+        .await?;
+    let ids = invoices.iter().map(|invoice| invoice.id).collect::<Vec<_>>();
+    record_invoice_events(&mut tx, &ids, InvoiceStatus::Open.as_str(), None).await?;
+    tx.commit().await?;
+    Ok(invoices)
+}
+
+/// Fields changed by `PATCH /payment/invoice/{id}`; `None` keeps the stored value.
+#[derive(Clone, Debug, Default)]
+pub struct InvoiceUpdate {
+    pub amount: Option<BigDecimal>,
+    pub networks: Option<Vec<i32>>,
+    pub external_id: Option<Option<String>>,
+    pub expires_at: Option<Option<NaiveDateTime>>,
+    pub metadata: Option<Option<Value>>,
+}
+
+/// Applies `update` to an open invoice and records `changes` in its timeline; `None` when the
+/// invoice is missing or no longer open.
+pub async fn update_invoice(db: &PgPool, id: &Uuid, update: &InvoiceUpdate, changes: Value) -> Result<Option<Invoice>, sqlx::Error> {
+    let mut tx = db.begin().await?;
+    let invoice = sqlx::query_as!(
+        Invoice,
+        r#"
+        UPDATE invoice
+        SET amount = COALESCE($2, amount),
+            networks = COALESCE($3, networks),
+            external_id = CASE WHEN $4 THEN $5 ELSE external_id END,
+            expires_at = CASE WHEN $6 THEN $7 ELSE expires_at END,
+            metadata = CASE WHEN $8 THEN $9 ELSE metadata END
+        WHERE id = $1 AND status = 'open'
+        RETURNING id, created_at, amount, seller, buyer, paid_at, networks, user_id, external_id, is_private, paid_network, tx_hash, buyer_email, send_receipt, status AS "status: InvoiceStatus", status_changed_at, expires_at, confirming_at, metadata
+        "#,
+        id,
+        update.amount,
+        update.networks.as_deref(),
+        update.external_id.is_some(),
+        update.external_id.clone().flatten(),
+        update.expires_at.is_some(),
+        update.expires_at.flatten(),
+        update.metadata.is_some(),
+        update.metadata.clone().flatten(),
+    )
+        .fetch_optional(&mut *tx)
+        .await?;
+    if let Some(invoice) = &invoice {
+        record_invoice_events(&mut tx, &[invoice.id], UPDATED_EVENT, Some(changes)).await?;
+    }
+    tx.commit().await?;
+    Ok(invoice)
+}
+
+/// Sets the buyer email of an unpaid invoice, replacing one already set only if `replace`;
+/// `None` otherwise, or when the invoice is missing.
+pub async fn set_invoice_buyer_email(db: &PgPool, id: &Uuid, buyer_email: &str, replace: bool) -> Result<Option<Invoice>, sqlx::Error> {
     sqlx::query_as!(
         Invoice,
         r#"
         UPDATE invoice
-        SET buyer = $1, paid_at = $2
-        WHERE id = $3 AND seller = $4 AND amount = $5
-        RETURNING *
+        SET buyer_email = $2
+        WHERE id = $1 AND status IN ('open', 'confirming') AND (buyer_email IS NULL OR $3)
+        RETURNING id, created_at, amount, seller, buyer, paid_at, networks, user_id, external_id, is_private, paid_network, tx_hash, buyer_email, send_receipt, status AS "status: InvoiceStatus", status_changed_at, expires_at, confirming_at, metadata
         "#,
-        buyer.to_lowercase(),
-        paid_at,
         id,
-        seller.to_lowercase(),
-        amount,
+        buyer_email,
+        replace,
     )
-        .fetch_one(db)
+        .fetch_optional(db)
         .await
 }
 
-pub async fn delete_own_invoice(db: &PgPool, id: &Uuid, user_id: &Uuid) -> Result<bool, sqlx::Error> {
-    let result = sqlx::query!(
+/// Overwrites the stored buyer email whatever the invoice status; for re-encrypting it.
+pub async fn rewrite_invoice_buyer_email(db: &PgPool, id: &Uuid, buyer_email: &str) -> Result<(), sqlx::Error> {
+    sqlx::query!(
         r#"
-        DELETE FROM invoice
-        WHERE id = $1 AND user_id = $2 AND paid_at IS NULL
+        UPDATE invoice
+        SET buyer_email = $2
+        WHERE id = $1
         "#,
         id,
-        user_id
+        buyer_email,
     )
         .execute(db)
-        .await?;
+        .await
+        .map(|_| ())
+}
 
-    Ok(result.rows_affected() > 0)
+/// Invoices with a buyer email, ordered by id, starting after `after`; for re-encrypting them.
+pub async fn list_invoices_with_buyer_email_after(db: &PgPool, after: &Uuid, limit: i64) -> Result<Vec<Invoice>, sqlx::Error> {
+    sqlx::query_as!(
+        Invoice,
+        r#"
+        SELECT id, created_at, amount, seller, buyer, paid_at, networks, user_id, external_id, is_private, paid_network, tx_hash, buyer_email, send_receipt, status AS "status: InvoiceStatus", status_changed_at, expires_at, confirming_at, metadata FROM invoice
+        WHERE id > $1 AND buyer_email IS NOT NULL
+        ORDER BY id
+        LIMIT $2
+        "#,
+        after,
+        limit,
+    )
+        .fetch_all(db)
+        .await
 }
@@ -0,0 +1,60 @@
+use serde_json::Value;
+use sqlx::{PgConnection, PgPool};
+use sqlx::types::Uuid;
+use sqlx::types::chrono::NaiveDateTime;
+
+/// `kind` of the first entry of every timeline.
+pub const CREATED_EVENT: &str = "created";
+/// `kind` of an edit through `PATCH /payment/invoice/{id}`; its `changes` list the fields.
+pub const UPDATED_EVENT: &str = "updated";
+
+/// An entry in an invoice's timeline.
+#[derive(Clone, Debug, sqlx::FromRow)]
+pub struct InvoiceEvent {
+    pub id: Uuid,
+    pub invoice_id: Uuid,
+    /// [`CREATED_EVENT`], [`UPDATED_EVENT`] or the [`super::invoice::InvoiceStatus`] the invoice moved to.
+    pub kind: String,
+    /// `{"field": {"from": ..., "to": ...}}` for [`UPDATED_EVENT`].
+    pub changes: Option<Value>,
+    pub created_at: NaiveDateTime,
+}
+
+/// Oldest first.
+pub async fn list_invoice_events(pg_pool: &PgPool, invoice_id: &Uuid) -> Result<Vec<InvoiceEvent>, sqlx::Error> {
+    sqlx::query_as!(
+        InvoiceEvent,
+        r#"
+        SELECT * FROM invoice_event
+        WHERE invoice_id = $1
+        ORDER BY created_at, id
+        "#,
+        invoice_id,
+    )
+        .fetch_all(pg_pool)
+        .await
+}
+
+/// Adds the same entry to the timeline of each invoice; called in the transaction that changed them.
+pub(super) async fn record_invoice_events(
+    conn: &mut PgConnection,
+    invoice_ids: &[Uuid],
+    kind: &str,
+    changes: Option<Value>,
+) -> Result<(), sqlx::Error> {
+    if invoice_ids.is_empty() {
+        return Ok(());
+    }
+    sqlx::query!(
+        r#"
+        INSERT INTO invoice_event (invoice_id, kind, changes)
+        SELECT invoice_id, $2, $3 FROM UNNEST($1::uuid[]) AS invoice_id
+        "#,
+        invoice_ids,
+        kind,
+        changes,
+    )
+        .execute(conn)
+        .await
+        .map(|_| ())
+}
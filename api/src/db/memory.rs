@@ -0,0 +1,1422 @@
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Mutex;
+use async_trait::async_trait;
+use bigdecimal::BigDecimal;
+use chrono::Utc;
+use serde_json::Value;
+use sqlx::migrate::MigrateError;
+use sqlx::types::{chrono::NaiveDateTime, Uuid};
+use super::analytics::{InvoicePeriodStats, InvoiceSummary};
+use super::billing::{Payment, Subscription};
+use super::invoice::{InvoiceOptions, InvoicePayment, InvoiceStatus, InvoiceUpdate};
+use super::invoice_event::{InvoiceEvent, CREATED_EVENT, UPDATED_EVENT};
+use super::notification_digest::{NewNotificationDigestItem, NotificationDigest, NotificationDigestItem};
+use super::notification_log::{NewNotificationLog, NotificationLog, NotificationLogFilter, NotificationLogStatus};
+use super::notification_preference::NotificationPreference;
+use super::notification_template::NotificationTemplate;
+use super::repository::*;
+use super::chat_webhook::{ChatWebhook, NewChatWebhook};
+#[cfg(feature = "telegram")]
+use super::telegram_destination::NewTelegramDestination;
+use super::telegram_destination::TelegramDestination;
+use super::webhook_delivery::{DueWebhookDelivery, NewWebhookDeliveryAttempt, WebhookDelivery, WebhookDeliveryAttempt, WebhookDeliveryStatus};
+use super::{ApiKey, CallbackUrl, Invoice, User, Webhook};
+
+/// [`Repository`] kept in process memory, for tests that exercise handler and event logic
+/// without a database. Mirrors the filtering and ordering of the Postgres queries.
+#[derive(Default)]
+pub struct MemoryRepository {
+    tables: Mutex<Tables>,
+}
+
+#[derive(Default)]
+struct Tables {
+    invoices: Vec<Invoice>,
+    invoice_events: Vec<InvoiceEvent>,
+    users: Vec<User>,
+    notification_templates: Vec<NotificationTemplate>,
+    notification_preferences: Vec<NotificationPreference>,
+    notification_digests: Vec<NotificationDigest>,
+    notification_digest_items: Vec<NotificationDigestItem>,
+    notification_logs: Vec<NotificationLog>,
+    telegram_destinations: Vec<TelegramDestination>,
+    chat_webhooks: Vec<ChatWebhook>,
+    api_keys: Vec<ApiKey>,
+    callback_urls: Vec<CallbackUrl>,
+    webhooks: Vec<Webhook>,
+    webhook_deliveries: Vec<WebhookDelivery>,
+    webhook_delivery_attempts: Vec<WebhookDeliveryAttempt>,
+    payments: Vec<Payment>,
+    subscriptions: Vec<Subscription>,
+    block_numbers: HashMap<String, i64>,
+}
+
+impl MemoryRepository {
+    fn tables(&self) -> std::sync::MutexGuard<'_, Tables> {
+        self.tables.lock().expect("memory repository lock poisoned")
+    }
+}
+
+impl Tables {
+    fn record_invoice_event(&mut self, invoice_id: Uuid, kind: &str, changes: Option<Value>) {
+        self.invoice_events.push(InvoiceEvent { id: Uuid::new_v4(), invoice_id, kind: kind.to_string(), changes, created_at: now() });
+    }
+}
+
+fn now() -> NaiveDateTime {
+    Utc::now().naive_utc()
+}
+
+fn secs_from_now(secs: f64) -> NaiveDateTime {
+    now() + chrono::Duration::milliseconds((secs * 1000.0) as i64)
+}
+
+fn page<T: Clone>(rows: Vec<&T>, limit: i64, offset: i64) -> Vec<T> {
+    rows.into_iter()
+        .skip(offset.max(0) as usize)
+        .take(limit.max(0) as usize)
+        .cloned()
+        .collect()
+}
+
+fn sorted_newest_first<T>(mut rows: Vec<&T>, key: impl Fn(&T) -> NaiveDateTime) -> Vec<&T> {
+    rows.sort_by_key(|row| std::cmp::Reverse(key(row)));
+    rows
+}
+
+/// `ORDER BY paid_at DESC` puts NULLs first in Postgres.
+fn sorted_by_paid_at_desc(mut rows: Vec<&Payment>) -> Vec<&Payment> {
+    rows.sort_by_key(|payment| std::cmp::Reverse((payment.paid_at.is_none(), payment.paid_at)));
+    rows
+}
+
+#[async_trait]
+impl Store for MemoryRepository {
+    async fn run_migrations(&self) -> Result<(), MigrateError> {
+        Ok(())
+    }
+
+    async fn health_check(&self) -> Result<(), sqlx::Error> {
+        Ok(())
+    }
+
+    async fn close(&self) {}
+}
+
+#[async_trait]
+impl InvoiceRepository for MemoryRepository {
+    async fn list_invoices(&self, limit: i64, offset: i64, user_id: Option<Uuid>) -> Result<Vec<Invoice>, sqlx::Error> {
+        let tables = self.tables();
+        let rows = tables.invoices.iter()
+            .filter(|invoice| !invoice.is_private || (user_id.is_some() && invoice.user_id == user_id))
+            .filter(|invoice| invoice.status != InvoiceStatus::Void)
+            .collect();
+        Ok(page(sorted_newest_first(rows, |invoice| invoice.created_at), limit, offset))
+    }
+
+    async fn user_own_invoices(&self, limit: i64, offset: i64, user_id: &Uuid) -> Result<Vec<Invoice>, sqlx::Error> {
+        let tables = self.tables();
+        let rows = tables.invoices.iter()
+            .filter(|invoice| invoice.user_id.as_ref() == Some(user_id) && invoice.status != InvoiceStatus::Void)
+            .collect();
+        Ok(page(sorted_newest_first(rows, |invoice| invoice.created_at), limit, offset))
+    }
+
+    async fn create_invoice(
+        &self,
+        amount: BigDecimal,
+        seller: &str,
+        networks: &[i32],
+        user_id: Option<Uuid>,
+        external_id: Option<String>,
+        options: InvoiceOptions,
+    ) -> Result<Invoice, sqlx::Error> {
+        let invoice = Invoice {
+            id: Uuid::new_v4(),
+            created_at: now(),
+            amount,
+            seller: seller.to_lowercase(),
+            buyer: None,
+            paid_at: None,
+            networks: networks.to_vec(),
+            user_id,
+            external_id,
+            is_private: options.is_private,
+            paid_network: None,
+            tx_hash: None,
+            buyer_email: options.buyer_email,
+            send_receipt: options.send_receipt,
+            status: InvoiceStatus::Open,
+            status_changed_at: now(),
+            expires_at: options.expires_at,
+            confirming_at: None,
+            metadata: options.metadata,
+        };
+        let mut tables = self.tables();
+        tables.invoices.push(invoice.clone());
+        tables.record_invoice_event(invoice.id, CREATED_EVENT, None);
+        Ok(invoice)
+    }
+
+    async fn get_invoice(&self, id: &Uuid) -> Result<Option<Invoice>, sqlx::Error> {
+        Ok(self.tables().invoices.iter().find(|invoice| &invoice.id == id).cloned())
+    }
+
+    async fn get_is_owner(&self, id: &Uuid, user_id: &Uuid) -> Result<bool, sqlx::Error> {
+        Ok(self.tables().invoices.iter().any(|invoice| &invoice.id == id && invoice.user_id.as_ref() == Some(user_id)))
+    }
+
+    async fn set_invoice_paid(&self, id: Uuid, payment: &InvoicePayment) -> Result<Invoice, sqlx::Error> {
+        let mut tables = self.tables();
+        let invoice = tables.invoices.iter_mut()
+            .find(|invoice| invoice.id == id && invoice.seller == payment.seller.to_lowercase() && invoice.amount == payment.amount && invoice.is_payable())
+            .ok_or(sqlx::Error::RowNotFound)?;
+        invoice.status = InvoiceStatus::Paid;
+        invoice.status_changed_at = now();
+        invoice.buyer = Some(payment.buyer.to_lowercase());
+        invoice.paid_at = Some(payment.paid_at);
+        invoice.paid_network = Some(payment.network);
+        invoice.tx_hash = Some(payment.tx_hash.clone());
+        let invoice = invoice.clone();
+        tables.record_invoice_event(invoice.id, InvoiceStatus::Paid.as_str(), None);
+        Ok(invoice)
+    }
+
+    async fn set_invoice_status(&self, id: &Uuid, from: &[InvoiceStatus], to: InvoiceStatus) -> Result<Option<Invoice>, sqlx::Error> {
+        let mut tables = self.tables();
+        let Some(invoice) = tables.invoices.iter_mut()
+            .find(|invoice| &invoice.id == id && from.contains(&invoice.status))
+            .filter(|invoice| to != InvoiceStatus::Confirming || invoice.confirming_at.is_none()) else {
+            return Ok(None);
+        };
+        invoice.status = to;
+        invoice.status_changed_at = now();
+        if to == InvoiceStatus::Confirming {
+            invoice.confirming_at = Some(invoice.status_changed_at);
+        }
+        let invoice = invoice.clone();
+        tables.record_invoice_event(invoice.id, to.as_str(), None);
+        Ok(Some(invoice))
+    }
+
+    async fn expire_invoices(&self, now: NaiveDateTime, confirming_before: NaiveDateTime) -> Result<Vec<Invoice>, sqlx::Error> {
+        let mut tables = self.tables();
+        let mut expired = vec![];
+        for invoice in tables.invoices.iter_mut() {
+            let due = invoice.expires_at.is_some_and(|expires_at| expires_at <= now)
+                && (invoice.status == InvoiceStatus::Open
+                    || (invoice.status == InvoiceStatus::Confirming && invoice.status_changed_at <= confirming_before));
+            if due {
+                invoice.status = InvoiceStatus::Expired;
+                invoice.status_changed_at = now;
+                expired.push(invoice.clone());
+            }
+        }
+        for invoice in &expired {
+            tables.record_invoice_event(invoice.id, InvoiceStatus::Expired.as_str(), None);
+        }
+        Ok(expired)
+    }
+
+    async fn reopen_stale_invoices(&self, now: NaiveDateTime, confirming_before: NaiveDateTime) -> Result<Vec<Invoice>, sqlx::Error> {
+        let mut tables = self.tables();
+        let mut reopened = vec![];
+        for invoice in tables.invoices.iter_mut() {
+            let stale = invoice.status == InvoiceStatus::Confirming
+                && invoice.status_changed_at <= confirming_before
+                && invoice.expires_at.is_none_or(|expires_at| expires_at > now);
+            if stale {
+                invoice.status = InvoiceStatus::Open;
+                invoice.status_changed_at = now;
+                reopened.push(invoice.clone());
+            }
+        }
+        for invoice in &reopened {
+            tables.record_invoice_event(invoice.id, InvoiceStatus::Open.as_str(), None);
+        }
+        Ok(reopened)
+    }
+
+    async fn update_invoice(&self, id: &Uuid, update: &InvoiceUpdate, changes: Value) -> Result<Option<Invoice>, sqlx::Error> {
+        let mut tables = self.tables();
+        let Some(invoice) = tables.invoices.iter_mut()
+            .find(|invoice| &invoice.id == id && invoice.status == InvoiceStatus::Open) else {
+            return Ok(None);
+        };
+        if let Some(amount) = &update.amount {
+            invoice.amount = amount.clone();
+        }
+        if let Some(networks) = &update.networks {
+            invoice.networks = networks.clone();
+        }
+        if let Some(external_id) = &update.external_id {
+            invoice.external_id = external_id.clone();
+        }
+        if let Some(expires_at) = update.expires_at {
+            invoice.expires_at = expires_at;
+        }
+        if let Some(metadata) = &update.metadata {
+            invoice.metadata = metadata.clone();
+        }
+        let invoice = invoice.clone();
+        tables.record_invoice_event(invoice.id, UPDATED_EVENT, Some(changes));
+        Ok(Some(invoice))
+    }
+
+    async fn list_invoice_events(&self, invoice_id: &Uuid) -> Result<Vec<InvoiceEvent>, sqlx::Error> {
+        Ok(self.tables().invoice_events.iter().filter(|event| &event.invoice_id == invoice_id).cloned().collect())
+    }
+
+    async fn set_invoice_buyer_email(&self, id: &Uuid, buyer_email: &str, replace: bool) -> Result<Option<Invoice>, sqlx::Error> {
+        let mut tables = self.tables();
+        let Some(invoice) = tables.invoices.iter_mut()
+            .find(|invoice| &invoice.id == id && invoice.status.is_payable() && (invoice.buyer_email.is_none() || replace)) else {
+            return Ok(None);
+        };
+        invoice.buyer_email = Some(buyer_email.to_string());
+        Ok(Some(invoice.clone()))
+    }
+
+    async fn rewrite_invoice_buyer_email(&self, id: &Uuid, buyer_email: &str) -> Result<(), sqlx::Error> {
+        if let Some(invoice) = self.tables().invoices.iter_mut().find(|invoice| &invoice.id == id) {
+            invoice.buyer_email = Some(buyer_email.to_string());
+        }
+        Ok(())
+    }
+
+    async fn list_invoices_with_buyer_email_after(&self, after: &Uuid, limit: i64) -> Result<Vec<Invoice>, sqlx::Error> {
+        let mut invoices = self.tables().invoices.iter()
+            .filter(|invoice| &invoice.id > after && invoice.buyer_email.is_some())
+            .cloned()
+            .collect::<Vec<_>>();
+        invoices.sort_by_key(|invoice| invoice.id);
+        invoices.truncate(limit as usize);
+        Ok(invoices)
+    }
+}
+
+#[async_trait]
+impl UserRepository for MemoryRepository {
+    async fn get_user_by_id(&self, id: &Uuid) -> Result<User, sqlx::Error> {
+        self.tables().users.iter()
+            .find(|user| &user.id == id)
+            .cloned()
+            .ok_or(sqlx::Error::RowNotFound)
+    }
+
+    async fn get_or_create_user(&self, firebase_user_id: &str, email: Option<String>) -> Result<User, sqlx::Error> {
+        let mut tables = self.tables();
+        if let Some(user) = tables.users.iter().find(|user| user.firebase_user_id == firebase_user_id) {
+            return Ok(user.clone());
+        }
+
+        let user = User {
+            id: Uuid::new_v4(),
+            created_at: now(),
+            firebase_user_id: firebase_user_id.to_string(),
+            email,
+            email_notification: false,
+            telegram_notification: false,
+            locale: "en".to_string(),
+            buyer_receipts: false,
+        };
+        tables.users.push(user.clone());
+        Ok(user)
+    }
+
+    async fn update_user(
+        &self,
+        user_id: &Uuid,
+        email_notification: Option<bool>,
+        telegram_notification: Option<bool>,
+    ) -> Result<User, sqlx::Error> {
+        let mut tables = self.tables();
+        let user = tables.users.iter_mut()
+            .find(|user| &user.id == user_id)
+            .ok_or(sqlx::Error::RowNotFound)?;
+        if let Some(email_notification) = email_notification {
+            user.email_notification = email_notification;
+        }
+        if let Some(telegram_notification) = telegram_notification {
+            user.telegram_notification = telegram_notification;
+        }
+        Ok(user.clone())
+    }
+
+    async fn set_user_email(&self, user_id: &Uuid, email: Option<String>) -> Result<(), sqlx::Error> {
+        if let Some(user) = self.tables().users.iter_mut().find(|user| &user.id == user_id) {
+            user.email = email;
+        }
+        Ok(())
+    }
+
+    async fn list_users_after(&self, after: &Uuid, limit: i64) -> Result<Vec<User>, sqlx::Error> {
+        let mut users = self.tables().users.iter()
+            .filter(|user| &user.id > after)
+            .cloned()
+            .collect::<Vec<_>>();
+        users.sort_by_key(|user| user.id);
+        users.truncate(limit as usize);
+        Ok(users)
+    }
+
+    async fn set_user_locale(&self, user_id: &Uuid, locale: &str) -> Result<(), sqlx::Error> {
+        if let Some(user) = self.tables().users.iter_mut().find(|user| &user.id == user_id) {
+            user.locale = locale.to_string();
+        }
+        Ok(())
+    }
+
+    async fn set_user_buyer_receipts(&self, user_id: &Uuid, buyer_receipts: bool) -> Result<(), sqlx::Error> {
+        if let Some(user) = self.tables().users.iter_mut().find(|user| &user.id == user_id) {
+            user.buyer_receipts = buyer_receipts;
+        }
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl NotificationTemplateRepository for MemoryRepository {
+    async fn list_notification_templates(&self, user_id: &Uuid) -> Result<Vec<NotificationTemplate>, sqlx::Error> {
+        let mut templates = self.tables().notification_templates.iter()
+            .filter(|template| &template.user_id == user_id)
+            .cloned()
+            .collect::<Vec<_>>();
+        templates.sort_by(|a, b| (&a.kind, &a.locale).cmp(&(&b.kind, &b.locale)));
+        Ok(templates)
+    }
+
+    async fn get_notification_template(&self, user_id: &Uuid, kind: &str, locale: &str) -> Result<Option<NotificationTemplate>, sqlx::Error> {
+        Ok(self.tables().notification_templates.iter()
+            .find(|template| &template.user_id == user_id && template.kind == kind && template.locale == locale)
+            .cloned())
+    }
+
+    async fn upsert_notification_template(&self, template: &NotificationTemplate) -> Result<NotificationTemplate, sqlx::Error> {
+        let mut tables = self.tables();
+        let template = NotificationTemplate { updated_at: now(), ..template.clone() };
+        tables.notification_templates.retain(|existing| {
+            (existing.user_id, &existing.kind, &existing.locale) != (template.user_id, &template.kind, &template.locale)
+        });
+        tables.notification_templates.push(template.clone());
+        Ok(template)
+    }
+
+    async fn delete_notification_template(&self, user_id: &Uuid, kind: &str, locale: &str) -> Result<bool, sqlx::Error> {
+        let mut tables = self.tables();
+        let before = tables.notification_templates.len();
+        tables.notification_templates.retain(|template| {
+            !(&template.user_id == user_id && template.kind == kind && template.locale == locale)
+        });
+        Ok(tables.notification_templates.len() < before)
+    }
+}
+
+#[async_trait]
+impl NotificationPreferenceRepository for MemoryRepository {
+    async fn list_notification_preferences(&self, user_id: &Uuid) -> Result<Vec<NotificationPreference>, sqlx::Error> {
+        let mut preferences = self.tables().notification_preferences.iter()
+            .filter(|preference| &preference.user_id == user_id)
+            .cloned()
+            .collect::<Vec<_>>();
+        preferences.sort_by(|a, b| (&a.channel, &a.event_type).cmp(&(&b.channel, &b.event_type)));
+        Ok(preferences)
+    }
+
+    async fn upsert_notification_preference(&self, preference: &NotificationPreference) -> Result<NotificationPreference, sqlx::Error> {
+        let mut tables = self.tables();
+        let preference = NotificationPreference { updated_at: now(), ..preference.clone() };
+        tables.notification_preferences.retain(|existing| {
+            (existing.user_id, &existing.channel, &existing.event_type) != (preference.user_id, &preference.channel, &preference.event_type)
+        });
+        tables.notification_preferences.push(preference.clone());
+        Ok(preference)
+    }
+
+    async fn delete_notification_preference(&self, user_id: &Uuid, channel: &str, event_type: &str) -> Result<bool, sqlx::Error> {
+        let mut tables = self.tables();
+        let before = tables.notification_preferences.len();
+        tables.notification_preferences.retain(|preference| {
+            !(&preference.user_id == user_id && preference.channel == channel && preference.event_type == event_type)
+        });
+        Ok(tables.notification_preferences.len() < before)
+    }
+}
+
+#[async_trait]
+impl NotificationDigestRepository for MemoryRepository {
+    async fn list_notification_digests(&self, user_id: &Uuid) -> Result<Vec<NotificationDigest>, sqlx::Error> {
+        let mut digests = self.tables().notification_digests.iter()
+            .filter(|digest| &digest.user_id == user_id)
+            .cloned()
+            .collect::<Vec<_>>();
+        digests.sort_by(|a, b| a.channel.cmp(&b.channel));
+        Ok(digests)
+    }
+
+    async fn upsert_notification_digest(&self, user_id: &Uuid, channel: &str, frequency: &str) -> Result<NotificationDigest, sqlx::Error> {
+        let mut tables = self.tables();
+        let existing = tables.notification_digests.iter_mut()
+            .find(|digest| &digest.user_id == user_id && digest.channel == channel);
+        if let Some(digest) = existing {
+            digest.frequency = frequency.to_string();
+            digest.updated_at = now();
+            return Ok(digest.clone());
+        }
+
+        let digest = NotificationDigest {
+            user_id: *user_id,
+            channel: channel.to_string(),
+            frequency: frequency.to_string(),
+            last_sent_at: None,
+            updated_at: now(),
+        };
+        tables.notification_digests.push(digest.clone());
+        Ok(digest)
+    }
+
+    async fn list_pending_notification_digests(&self) -> Result<Vec<NotificationDigest>, sqlx::Error> {
+        let tables = self.tables();
+        Ok(tables.notification_digests.iter()
+            .filter(|digest| tables.notification_digest_items.iter()
+                .any(|item| item.user_id == digest.user_id && item.channel == digest.channel))
+            .cloned()
+            .collect())
+    }
+
+    async fn claim_notification_digest(&self, user_id: &Uuid, channel: &str, period_end: &NaiveDateTime) -> Result<bool, sqlx::Error> {
+        let mut tables = self.tables();
+        let digest = tables.notification_digests.iter_mut()
+            .find(|digest| &digest.user_id == user_id && digest.channel == channel)
+            .filter(|digest| digest.last_sent_at.is_none_or(|last_sent_at| &last_sent_at < period_end));
+        Ok(match digest {
+            Some(digest) => {
+                digest.last_sent_at = Some(*period_end);
+                true
+            }
+            None => false,
+        })
+    }
+
+    async fn create_notification_digest_item(&self, item: &NewNotificationDigestItem) -> Result<NotificationDigestItem, sqlx::Error> {
+        let item = NotificationDigestItem {
+            id: Uuid::new_v4(),
+            user_id: item.user_id,
+            channel: item.channel.clone(),
+            event_type: item.event_type.clone(),
+            invoice_id: item.invoice_id,
+            amount: item.amount.clone(),
+            network: item.network,
+            external_id: item.external_id.clone(),
+            created_at: now(),
+        };
+        self.tables().notification_digest_items.push(item.clone());
+        Ok(item)
+    }
+
+    async fn list_notification_digest_items(&self, user_id: &Uuid, channel: &str, before: &NaiveDateTime) -> Result<Vec<NotificationDigestItem>, sqlx::Error> {
+        let mut items = self.tables().notification_digest_items.iter()
+            .filter(|item| &item.user_id == user_id && item.channel == channel && &item.created_at < before)
+            .cloned()
+            .collect::<Vec<_>>();
+        items.sort_by_key(|item| item.created_at);
+        Ok(items)
+    }
+
+    async fn delete_notification_digest_items(&self, ids: &[Uuid]) -> Result<(), sqlx::Error> {
+        self.tables().notification_digest_items.retain(|item| !ids.contains(&item.id));
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl NotificationLogRepository for MemoryRepository {
+    async fn create_notification_log(&self, log: &NewNotificationLog) -> Result<NotificationLog, sqlx::Error> {
+        let status = match log.error {
+            None => NotificationLogStatus::Sent,
+            Some(_) => NotificationLogStatus::Failed,
+        };
+        let log = NotificationLog {
+            id: Uuid::new_v4(),
+            user_id: log.user_id,
+            channel: log.channel.clone(),
+            event_type: log.event_type.clone(),
+            invoice_id: log.invoice_id,
+            destination_id: log.destination_id,
+            destination: log.destination.clone(),
+            transfer: log.transfer.clone(),
+            status: status.as_str().to_string(),
+            error: log.error.clone(),
+            attempts: 1,
+            next_attempt_at: None,
+            created_at: now(),
+            updated_at: now(),
+            sent_at: log.error.is_none().then(now),
+        };
+        self.tables().notification_logs.push(log.clone());
+        Ok(log)
+    }
+
+    async fn list_notification_logs(&self, user_id: &Uuid, filter: &NotificationLogFilter, limit: i64, offset: i64) -> Result<Vec<NotificationLog>, sqlx::Error> {
+        let matches = |value: &str, wanted: &Option<String>| wanted.as_deref().is_none_or(|wanted| value == wanted);
+        let mut logs = self.tables().notification_logs.iter()
+            .filter(|log| &log.user_id == user_id)
+            .filter(|log| matches(&log.channel, &filter.channel)
+                && matches(&log.event_type, &filter.event_type)
+                && matches(&log.status, &filter.status))
+            .filter(|log| filter.invoice_id.is_none_or(|invoice_id| log.invoice_id == Some(invoice_id)))
+            .cloned()
+            .collect::<Vec<_>>();
+        logs.sort_by_key(|log| std::cmp::Reverse(log.created_at));
+        Ok(logs.into_iter().skip(offset.max(0) as usize).take(limit.max(0) as usize).collect())
+    }
+
+    async fn get_notification_log(&self, id: &Uuid, user_id: &Uuid) -> Result<Option<NotificationLog>, sqlx::Error> {
+        Ok(self.tables().notification_logs.iter()
+            .find(|log| &log.id == id && &log.user_id == user_id)
+            .cloned())
+    }
+
+    async fn resend_notification(&self, id: &Uuid, user_id: &Uuid) -> Result<bool, sqlx::Error> {
+        let mut tables = self.tables();
+        let log = tables.notification_logs.iter_mut()
+            .find(|log| &log.id == id && &log.user_id == user_id)
+            .filter(|log| log.status == NotificationLogStatus::Failed.as_str() && log.invoice_id.is_some());
+        Ok(match log {
+            Some(log) => {
+                log.status = NotificationLogStatus::Pending.as_str().to_string();
+                log.next_attempt_at = Some(now());
+                log.updated_at = now();
+                true
+            }
+            None => false,
+        })
+    }
+
+    async fn claim_pending_notifications(&self, limit: i64, lease_secs: f64) -> Result<Vec<NotificationLog>, sqlx::Error> {
+        let mut tables = self.tables();
+        let now = now();
+        let mut due = tables.notification_logs.iter_mut()
+            .filter(|log| log.status == NotificationLogStatus::Pending.as_str()
+                && log.next_attempt_at.is_some_and(|next_attempt_at| next_attempt_at <= now))
+            .collect::<Vec<_>>();
+        due.sort_by_key(|log| log.next_attempt_at);
+
+        Ok(due.into_iter()
+            .take(limit.max(0) as usize)
+            .map(|log| {
+                log.next_attempt_at = Some(secs_from_now(lease_secs));
+                log.clone()
+            })
+            .collect())
+    }
+
+    async fn record_notification_attempt(&self, id: &Uuid, error: Option<&str>) -> Result<(), sqlx::Error> {
+        let mut tables = self.tables();
+        if let Some(log) = tables.notification_logs.iter_mut().find(|log| &log.id == id) {
+            let status = match error {
+                None => NotificationLogStatus::Sent,
+                Some(_) => NotificationLogStatus::Failed,
+            };
+            log.status = status.as_str().to_string();
+            log.error = error.map(str::to_string);
+            log.attempts += 1;
+            log.next_attempt_at = None;
+            log.updated_at = now();
+            if error.is_none() {
+                log.sent_at = Some(now());
+            }
+        }
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl TelegramDestinationRepository for MemoryRepository {
+    #[cfg(feature = "telegram")]
+    async fn upsert_telegram_destination(&self, destination: &NewTelegramDestination) -> Result<TelegramDestination, sqlx::Error> {
+        let destination = TelegramDestination {
+            id: Uuid::new_v4(),
+            user_id: destination.user_id,
+            label: destination.label.clone(),
+            chat_id: destination.chat_id.clone(),
+            chat_lookup: destination.chat_lookup.clone(),
+            chat_type: destination.chat_type.clone(),
+            event_types: destination.event_types.clone(),
+            min_amount: None,
+            created_at: now(),
+        };
+        let mut tables = self.tables();
+        tables.telegram_destinations.retain(|existing| {
+            destination.chat_lookup.is_none() || existing.chat_lookup != destination.chat_lookup
+        });
+        tables.telegram_destinations.push(destination.clone());
+        Ok(destination)
+    }
+
+    async fn list_telegram_destinations(&self, user_id: &Uuid) -> Result<Vec<TelegramDestination>, sqlx::Error> {
+        Ok(self.tables().telegram_destinations.iter()
+            .filter(|destination| &destination.user_id == user_id)
+            .cloned()
+            .collect())
+    }
+
+    #[cfg(feature = "telegram")]
+    async fn get_telegram_destination_by_chat_lookup(&self, lookups: &[String]) -> Result<Option<TelegramDestination>, sqlx::Error> {
+        Ok(self.tables().telegram_destinations.iter()
+            .find(|destination| destination.chat_lookup.as_ref().is_some_and(|lookup| lookups.contains(lookup)))
+            .cloned())
+    }
+
+    #[cfg(feature = "telegram")]
+    async fn update_telegram_destination(
+        &self,
+        id: &Uuid,
+        user_id: &Uuid,
+        label: Option<&str>,
+        event_types: Option<&[String]>,
+        min_amount: Option<Option<BigDecimal>>,
+    ) -> Result<Option<TelegramDestination>, sqlx::Error> {
+        let mut tables = self.tables();
+        let Some(destination) = tables.telegram_destinations.iter_mut()
+            .find(|destination| &destination.id == id && &destination.user_id == user_id) else {
+            return Ok(None);
+        };
+        if let Some(label) = label {
+            destination.label = label.to_string();
+        }
+        if let Some(event_types) = event_types {
+            destination.event_types = event_types.to_vec();
+        }
+        if let Some(min_amount) = min_amount {
+            destination.min_amount = min_amount;
+        }
+        Ok(Some(destination.clone()))
+    }
+
+    async fn set_telegram_destination_chat(
+        &self,
+        id: &Uuid,
+        chat_id: &str,
+        chat_lookup: Option<&str>,
+        chat_type: &str,
+    ) -> Result<(), sqlx::Error> {
+        if let Some(destination) = self.tables().telegram_destinations.iter_mut().find(|destination| &destination.id == id) {
+            destination.chat_id = chat_id.to_string();
+            destination.chat_lookup = chat_lookup.map(str::to_string);
+            destination.chat_type = chat_type.to_string();
+        }
+        Ok(())
+    }
+
+    #[cfg(feature = "telegram")]
+    async fn delete_telegram_destination(&self, id: &Uuid, user_id: &Uuid) -> Result<bool, sqlx::Error> {
+        let mut tables = self.tables();
+        let before = tables.telegram_destinations.len();
+        tables.telegram_destinations.retain(|destination| !(&destination.id == id && &destination.user_id == user_id));
+        Ok(tables.telegram_destinations.len() < before)
+    }
+
+    #[cfg(feature = "telegram")]
+    async fn delete_telegram_destinations(&self, user_id: &Uuid) -> Result<u64, sqlx::Error> {
+        let mut tables = self.tables();
+        let before = tables.telegram_destinations.len();
+        tables.telegram_destinations.retain(|destination| &destination.user_id != user_id);
+        Ok((before - tables.telegram_destinations.len()) as u64)
+    }
+
+    async fn list_telegram_destinations_after(&self, after: &Uuid, limit: i64) -> Result<Vec<TelegramDestination>, sqlx::Error> {
+        let mut destinations = self.tables().telegram_destinations.iter()
+            .filter(|destination| &destination.id > after)
+            .cloned()
+            .collect::<Vec<_>>();
+        destinations.sort_by_key(|destination| destination.id);
+        destinations.truncate(limit as usize);
+        Ok(destinations)
+    }
+}
+
+#[async_trait]
+impl ChatWebhookRepository for MemoryRepository {
+    async fn create_chat_webhook(&self, chat_webhook: &NewChatWebhook) -> Result<ChatWebhook, sqlx::Error> {
+        let chat_webhook = ChatWebhook {
+            id: Uuid::new_v4(),
+            user_id: chat_webhook.user_id,
+            kind: chat_webhook.kind.clone(),
+            label: chat_webhook.label.clone(),
+            url: chat_webhook.url.clone(),
+            event_types: chat_webhook.event_types.clone(),
+            enabled: true,
+            created_at: now(),
+        };
+        self.tables().chat_webhooks.push(chat_webhook.clone());
+        Ok(chat_webhook)
+    }
+
+    async fn get_chat_webhook(&self, id: &Uuid, user_id: &Uuid) -> Result<Option<ChatWebhook>, sqlx::Error> {
+        Ok(self.tables().chat_webhooks.iter()
+            .find(|chat_webhook| &chat_webhook.id == id && &chat_webhook.user_id == user_id)
+            .cloned())
+    }
+
+    async fn list_chat_webhooks(&self, user_id: &Uuid) -> Result<Vec<ChatWebhook>, sqlx::Error> {
+        Ok(self.tables().chat_webhooks.iter()
+            .filter(|chat_webhook| &chat_webhook.user_id == user_id)
+            .cloned()
+            .collect())
+    }
+
+    async fn count_chat_webhooks(&self, user_id: &Uuid) -> Result<i64, sqlx::Error> {
+        Ok(self.tables().chat_webhooks.iter().filter(|chat_webhook| &chat_webhook.user_id == user_id).count() as i64)
+    }
+
+    async fn update_chat_webhook(
+        &self,
+        id: &Uuid,
+        user_id: &Uuid,
+        label: Option<&str>,
+        url: Option<&str>,
+        enabled: Option<bool>,
+        event_types: Option<&[String]>,
+    ) -> Result<Option<ChatWebhook>, sqlx::Error> {
+        let mut tables = self.tables();
+        let Some(chat_webhook) = tables.chat_webhooks.iter_mut()
+            .find(|chat_webhook| &chat_webhook.id == id && &chat_webhook.user_id == user_id) else {
+            return Ok(None);
+        };
+        if let Some(label) = label {
+            chat_webhook.label = label.to_string();
+        }
+        if let Some(url) = url {
+            chat_webhook.url = url.to_string();
+        }
+        if let Some(enabled) = enabled {
+            chat_webhook.enabled = enabled;
+        }
+        if let Some(event_types) = event_types {
+            chat_webhook.event_types = event_types.to_vec();
+        }
+        Ok(Some(chat_webhook.clone()))
+    }
+
+    async fn set_chat_webhook_url(&self, id: &Uuid, url: &str) -> Result<(), sqlx::Error> {
+        if let Some(chat_webhook) = self.tables().chat_webhooks.iter_mut().find(|chat_webhook| &chat_webhook.id == id) {
+            chat_webhook.url = url.to_string();
+        }
+        Ok(())
+    }
+
+    async fn delete_chat_webhook(&self, id: &Uuid, user_id: &Uuid) -> Result<bool, sqlx::Error> {
+        let mut tables = self.tables();
+        let before = tables.chat_webhooks.len();
+        tables.chat_webhooks.retain(|chat_webhook| !(&chat_webhook.id == id && &chat_webhook.user_id == user_id));
+        Ok(tables.chat_webhooks.len() < before)
+    }
+
+    async fn list_chat_webhooks_after(&self, after: &Uuid, limit: i64) -> Result<Vec<ChatWebhook>, sqlx::Error> {
+        let mut chat_webhooks = self.tables().chat_webhooks.iter()
+            .filter(|chat_webhook| &chat_webhook.id > after)
+            .cloned()
+            .collect::<Vec<_>>();
+        chat_webhooks.sort_by_key(|chat_webhook| chat_webhook.id);
+        chat_webhooks.truncate(limit as usize);
+        Ok(chat_webhooks)
+    }
+}
+
+#[async_trait]
+impl ApiKeyRepository for MemoryRepository {
+    async fn create_api_key(&self, user_id: &Uuid, hashed_api_key: &str) -> Result<ApiKey, sqlx::Error> {
+        let api_key = ApiKey {
+            id: Uuid::new_v4(),
+            user_id: *user_id,
+            api_key: hashed_api_key.to_string(),
+            created: now(),
+            last_used: None,
+        };
+        self.tables().api_keys.push(api_key.clone());
+        Ok(api_key)
+    }
+
+    async fn get_api_key(&self, id: &Uuid, user_id: &Uuid) -> Result<Option<ApiKey>, sqlx::Error> {
+        Ok(self.tables().api_keys.iter().find(|key| &key.id == id && &key.user_id == user_id).cloned())
+    }
+
+    async fn get_api_key_by_api_key(&self, hashed_api_key: &str) -> Result<Option<ApiKey>, sqlx::Error> {
+        Ok(self.tables().api_keys.iter().find(|key| key.api_key == hashed_api_key).cloned())
+    }
+
+    async fn list_api_key(&self, user_id: &Uuid) -> Result<Vec<ApiKey>, sqlx::Error> {
+        let tables = self.tables();
+        let rows = tables.api_keys.iter().filter(|key| &key.user_id == user_id).collect();
+        Ok(sorted_newest_first(rows, |key| key.created).into_iter().cloned().collect())
+    }
+
+    async fn update_api_key_last_used(&self, id: &Uuid) -> Result<(), sqlx::Error> {
+        if let Some(key) = self.tables().api_keys.iter_mut().find(|key| &key.id == id) {
+            key.last_used = Some(now());
+        }
+        Ok(())
+    }
+
+    async fn count_api_keys(&self, user_id: &Uuid) -> Result<i64, sqlx::Error> {
+        Ok(self.tables().api_keys.iter().filter(|key| &key.user_id == user_id).count() as i64)
+    }
+
+    async fn delete_api_key(&self, id: &Uuid, user_id: &Uuid) -> Result<bool, sqlx::Error> {
+        let mut tables = self.tables();
+        let before = tables.api_keys.len();
+        tables.api_keys.retain(|key| !(&key.id == id && &key.user_id == user_id));
+        Ok(tables.api_keys.len() < before)
+    }
+}
+
+#[async_trait]
+impl CallbackUrlRepository for MemoryRepository {
+    async fn create_callback_url(&self, url: &str, user_id: &Uuid) -> Result<CallbackUrl, sqlx::Error> {
+        let callback_url = CallbackUrl {
+            id: Uuid::new_v4(),
+            user_id: *user_id,
+            url: url.to_string(),
+            created_at: now(),
+        };
+        self.tables().callback_urls.push(callback_url.clone());
+        Ok(callback_url)
+    }
+
+    async fn list_callback_urls(&self, user_id: &Uuid) -> Result<Vec<CallbackUrl>, sqlx::Error> {
+        let tables = self.tables();
+        let rows = tables.callback_urls.iter().filter(|callback_url| &callback_url.user_id == user_id).collect();
+        Ok(sorted_newest_first(rows, |callback_url| callback_url.created_at).into_iter().cloned().collect())
+    }
+
+    async fn count_callback_urls(&self, user_id: &Uuid) -> Result<i64, sqlx::Error> {
+        Ok(self.tables().callback_urls.iter().filter(|callback_url| &callback_url.user_id == user_id).count() as i64)
+    }
+
+    async fn exists_callback_url(&self, url: &str, user_id: &Uuid) -> Result<bool, sqlx::Error> {
+        let tables = self.tables();
+        let mut urls = tables.callback_urls.iter().filter(|callback_url| &callback_url.user_id == user_id).peekable();
+        Ok(urls.peek().is_none() || urls.any(|callback_url| callback_url.url == url))
+    }
+
+    async fn delete_callback_url(&self, id: &Uuid, user_id: &Uuid) -> Result<bool, sqlx::Error> {
+        let mut tables = self.tables();
+        let before = tables.callback_urls.len();
+        tables.callback_urls.retain(|callback_url| !(&callback_url.id == id && &callback_url.user_id == user_id));
+        Ok(tables.callback_urls.len() < before)
+    }
+}
+
+#[async_trait]
+impl WebhookRepository for MemoryRepository {
+    async fn create_webhook(
+        &self,
+        url: &str,
+        secret: &str,
+        user_id: &Uuid,
+        event_types: &[String],
+        api_version: &str,
+    ) -> Result<Webhook, sqlx::Error> {
+        let webhook = Webhook {
+            id: Uuid::new_v4(),
+            user_id: *user_id,
+            url: url.to_string(),
+            secret: secret.to_string(),
+            created_at: now(),
+            event_types: event_types.to_vec(),
+            api_version: api_version.to_string(),
+            enabled: true,
+            previous_secret: None,
+            previous_secret_expires_at: None,
+        };
+        self.tables().webhooks.push(webhook.clone());
+        Ok(webhook)
+    }
+
+    async fn get_webhook(&self, id: &Uuid, user_id: &Uuid) -> Result<Option<Webhook>, sqlx::Error> {
+        Ok(self.tables().webhooks.iter().find(|webhook| &webhook.id == id && &webhook.user_id == user_id).cloned())
+    }
+
+    async fn list_webhooks(&self, user_id: &Uuid) -> Result<Vec<Webhook>, sqlx::Error> {
+        let tables = self.tables();
+        let rows = tables.webhooks.iter().filter(|webhook| &webhook.user_id == user_id).collect();
+        Ok(sorted_newest_first(rows, |webhook| webhook.created_at).into_iter().cloned().collect())
+    }
+
+    async fn list_webhooks_for_event(&self, user_id: &Uuid, event_type: &str) -> Result<Vec<Webhook>, sqlx::Error> {
+        let tables = self.tables();
+        let rows = tables.webhooks.iter()
+            .filter(|webhook| &webhook.user_id == user_id && webhook.enabled && webhook.event_types.iter().any(|t| t == event_type))
+            .collect();
+        Ok(sorted_newest_first(rows, |webhook| webhook.created_at).into_iter().cloned().collect())
+    }
+
+    async fn count_webhooks(&self, user_id: &Uuid) -> Result<i64, sqlx::Error> {
+        Ok(self.tables().webhooks.iter().filter(|webhook| &webhook.user_id == user_id).count() as i64)
+    }
+
+    async fn update_webhook(
+        &self,
+        id: &Uuid,
+        user_id: &Uuid,
+        url: Option<&str>,
+        enabled: Option<bool>,
+        event_types: Option<&[String]>,
+    ) -> Result<Option<Webhook>, sqlx::Error> {
+        let mut tables = self.tables();
+        let Some(webhook) = tables.webhooks.iter_mut().find(|webhook| &webhook.id == id && &webhook.user_id == user_id) else {
+            return Ok(None);
+        };
+        if let Some(url) = url {
+            webhook.url = url.to_string();
+        }
+        if let Some(enabled) = enabled {
+            webhook.enabled = enabled;
+        }
+        if let Some(event_types) = event_types {
+            webhook.event_types = event_types.to_vec();
+        }
+        Ok(Some(webhook.clone()))
+    }
+
+    async fn rotate_webhook_secret(&self, id: &Uuid, user_id: &Uuid, secret: &str, grace_secs: f64) -> Result<Option<Webhook>, sqlx::Error> {
+        let mut tables = self.tables();
+        let Some(webhook) = tables.webhooks.iter_mut().find(|webhook| &webhook.id == id && &webhook.user_id == user_id) else {
+            return Ok(None);
+        };
+        let old_secret = std::mem::replace(&mut webhook.secret, secret.to_string());
+        (webhook.previous_secret, webhook.previous_secret_expires_at) = match grace_secs > 0.0 {
+            true => (Some(old_secret), Some(secs_from_now(grace_secs))),
+            false => (None, None),
+        };
+        Ok(Some(webhook.clone()))
+    }
+
+    async fn delete_webhook(&self, id: &Uuid, user_id: &Uuid) -> Result<bool, sqlx::Error> {
+        let mut tables = self.tables();
+        let before = tables.webhooks.len();
+        tables.webhooks.retain(|webhook| !(&webhook.id == id && &webhook.user_id == user_id));
+        let deleted = tables.webhooks.len() < before;
+        if deleted {
+            tables.webhook_deliveries.retain(|delivery| &delivery.webhook_id != id);
+        }
+        Ok(deleted)
+    }
+
+    async fn list_webhooks_after(&self, after: &Uuid, limit: i64) -> Result<Vec<Webhook>, sqlx::Error> {
+        let mut webhooks = self.tables().webhooks.iter()
+            .filter(|webhook| &webhook.id > after)
+            .cloned()
+            .collect::<Vec<_>>();
+        webhooks.sort_by_key(|webhook| webhook.id);
+        webhooks.truncate(limit as usize);
+        Ok(webhooks)
+    }
+
+    async fn set_webhook_secrets(&self, id: &Uuid, secret: &str, previous_secret: Option<&str>) -> Result<(), sqlx::Error> {
+        if let Some(webhook) = self.tables().webhooks.iter_mut().find(|webhook| &webhook.id == id) {
+            webhook.secret = secret.to_string();
+            webhook.previous_secret = previous_secret.map(str::to_string);
+        }
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl WebhookDeliveryRepository for MemoryRepository {
+    async fn enqueue_webhook_delivery(&self, webhook_id: &Uuid, payload: &Value) -> Result<WebhookDelivery, sqlx::Error> {
+        let now = now();
+        let delivery = WebhookDelivery {
+            id: Uuid::new_v4(),
+            webhook_id: *webhook_id,
+            payload: payload.clone(),
+            status: WebhookDeliveryStatus::Pending.as_str().to_string(),
+            attempts: 0,
+            next_attempt_at: now,
+            created_at: now,
+            delivered_at: None,
+        };
+        self.tables().webhook_deliveries.push(delivery.clone());
+        Ok(delivery)
+    }
+
+    async fn claim_due_webhook_deliveries(&self, limit: i64, lease_secs: f64) -> Result<Vec<DueWebhookDelivery>, sqlx::Error> {
+        let mut tables = self.tables();
+        let Tables { webhooks, webhook_deliveries, .. } = &mut *tables;
+        let now = now();
+        let mut due: Vec<&mut WebhookDelivery> = webhook_deliveries.iter_mut()
+            .filter(|delivery| delivery.status == WebhookDeliveryStatus::Pending.as_str() && delivery.next_attempt_at <= now)
+            .filter(|delivery| webhooks.iter().any(|webhook| webhook.id == delivery.webhook_id && webhook.enabled))
+            .collect();
+        due.sort_by_key(|delivery| delivery.next_attempt_at);
+
+        Ok(due.into_iter()
+            .take(limit.max(0) as usize)
+            .filter_map(|delivery| {
+                let webhook = webhooks.iter().find(|webhook| webhook.id == delivery.webhook_id)?;
+                delivery.next_attempt_at = secs_from_now(lease_secs);
+                Some(DueWebhookDelivery {
+                    id: delivery.id,
+                    webhook_id: delivery.webhook_id,
+                    payload: delivery.payload.clone(),
+                    attempts: delivery.attempts,
+                    url: webhook.url.clone(),
+                    secret: webhook.secret.clone(),
+                    previous_secret: webhook.previous_secret_expires_at
+                        .is_some_and(|expires_at| expires_at > now)
+                        .then(|| webhook.previous_secret.clone())
+                        .flatten(),
+                })
+            })
+            .collect())
+    }
+
+    async fn record_webhook_delivery_attempt(
+        &self,
+        delivery_id: &Uuid,
+        attempt: &NewWebhookDeliveryAttempt,
+        status: WebhookDeliveryStatus,
+        retry_in_secs: f64,
+    ) -> Result<(), sqlx::Error> {
+        let mut tables = self.tables();
+        tables.webhook_delivery_attempts.push(WebhookDeliveryAttempt {
+            id: Uuid::new_v4(),
+            delivery_id: *delivery_id,
+            attempted_at: now(),
+            status_code: attempt.status_code,
+            latency_ms: attempt.latency_ms,
+            response_excerpt: attempt.response_excerpt.clone(),
+            error: attempt.error.clone(),
+        });
+        if let Some(delivery) = tables.webhook_deliveries.iter_mut().find(|delivery| &delivery.id == delivery_id) {
+            delivery.attempts += 1;
+            delivery.status = status.as_str().to_string();
+            delivery.next_attempt_at = secs_from_now(retry_in_secs);
+            if status == WebhookDeliveryStatus::Delivered {
+                delivery.delivered_at = Some(now());
+            }
+        }
+        Ok(())
+    }
+
+    async fn list_webhook_deliveries(&self, webhook_id: &Uuid, limit: i64, offset: i64) -> Result<Vec<WebhookDelivery>, sqlx::Error> {
+        let tables = self.tables();
+        let rows = tables.webhook_deliveries.iter().filter(|delivery| &delivery.webhook_id == webhook_id).collect();
+        Ok(page(sorted_newest_first(rows, |delivery| delivery.created_at), limit, offset))
+    }
+
+    async fn list_webhook_delivery_attempts(&self, delivery_ids: &[Uuid]) -> Result<Vec<WebhookDeliveryAttempt>, sqlx::Error> {
+        Ok(self.tables().webhook_delivery_attempts.iter()
+            .filter(|attempt| delivery_ids.contains(&attempt.delivery_id))
+            .cloned()
+            .collect())
+    }
+
+    async fn redeliver_webhook_delivery(&self, id: &Uuid, webhook_id: &Uuid) -> Result<bool, sqlx::Error> {
+        let mut tables = self.tables();
+        let Some(delivery) = tables.webhook_deliveries.iter_mut().find(|delivery| &delivery.id == id && &delivery.webhook_id == webhook_id) else {
+            return Ok(false);
+        };
+        delivery.status = WebhookDeliveryStatus::Pending.as_str().to_string();
+        delivery.attempts = 0;
+        delivery.next_attempt_at = now();
+        delivery.delivered_at = None;
+        Ok(true)
+    }
+}
+
+#[async_trait]
+impl PaymentRepository for MemoryRepository {
+    async fn create_payment(&self, id: &Uuid, user_id: Option<Uuid>, data: &Value) -> Result<Payment, sqlx::Error> {
+        let payment = Payment {
+            id: *id,
+            user_id,
+            data: data.clone(),
+            created_at: now(),
+            paid_at: None,
+        };
+        self.tables().payments.push(payment.clone());
+        Ok(payment)
+    }
+
+    async fn get_payment(&self, id: &Uuid) -> Result<Option<Payment>, sqlx::Error> {
+        Ok(self.tables().payments.iter().find(|payment| &payment.id == id).cloned())
+    }
+
+    async fn user_list_payment(&self, user_id: &Uuid, limit: i64, offset: i64) -> Result<Vec<Payment>, sqlx::Error> {
+        let tables = self.tables();
+        let rows = tables.payments.iter().filter(|payment| payment.user_id.as_ref() == Some(user_id)).collect();
+        Ok(page(sorted_by_paid_at_desc(rows), limit, offset))
+    }
+
+    async fn list_payment(&self, payment_type: &str, limit: i64, offset: i64) -> Result<Vec<Payment>, sqlx::Error> {
+        let tables = self.tables();
+        let rows = tables.payments.iter()
+            .filter(|payment| payment.paid_at.is_some() && payment.data.get(payment_type).is_some())
+            .collect();
+        Ok(page(sorted_by_paid_at_desc(rows), limit, offset))
+    }
+
+    async fn set_payment_paid(&self, id: &Uuid) -> Result<(), sqlx::Error> {
+        self.sync_payment_paid_at(id, &now()).await
+    }
+
+    async fn sync_payment_paid_at(&self, id: &Uuid, paid_at: &NaiveDateTime) -> Result<(), sqlx::Error> {
+        if let Some(payment) = self.tables().payments.iter_mut().find(|payment| &payment.id == id && payment.paid_at.is_none()) {
+            payment.paid_at = Some(*paid_at);
+        }
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl SubscriptionRepository for MemoryRepository {
+    async fn list_user_subscriptions(&self, user_id: &Uuid) -> Result<Vec<Subscription>, sqlx::Error> {
+        let tables = self.tables();
+        let mut rows: Vec<Subscription> = tables.subscriptions.iter()
+            .filter(|subscription| &subscription.user_id == user_id)
+            .cloned()
+            .collect();
+        rows.sort_by(|a, b| a.target.cmp(&b.target));
+        Ok(rows)
+    }
+
+    async fn get_user_active_subscription(&self, user_id: &Uuid, target: &str) -> Result<Option<Subscription>, sqlx::Error> {
+        let now = now();
+        Ok(self.tables().subscriptions.iter()
+            .find(|subscription| &subscription.user_id == user_id && subscription.target == target && subscription.until > now)
+            .cloned())
+    }
+
+    async fn create_or_update_subscription(&self, user_id: &Uuid, target: &str, data: Option<Value>, until: NaiveDateTime) -> Result<(), sqlx::Error> {
+        let data = data.unwrap_or(Value::Null);
+        let mut tables = self.tables();
+        match tables.subscriptions.iter_mut().find(|subscription| &subscription.user_id == user_id && subscription.target == target) {
+            Some(subscription) => {
+                subscription.data = data;
+                subscription.until = until;
+            }
+            None => tables.subscriptions.push(Subscription {
+                id: Uuid::new_v4(),
+                user_id: *user_id,
+                target: target.to_string(),
+                data,
+                created_at: now(),
+                until,
+                expiry_notified_until: None,
+            }),
+        }
+        Ok(())
+    }
+
+    async fn claim_expiring_subscriptions(&self, now: &NaiveDateTime, before: &NaiveDateTime) -> Result<Vec<Subscription>, sqlx::Error> {
+        let mut tables = self.tables();
+        Ok(tables.subscriptions.iter_mut()
+            .filter(|subscription| &subscription.until > now && &subscription.until <= before)
+            .filter(|subscription| subscription.expiry_notified_until != Some(subscription.until))
+            .map(|subscription| {
+                subscription.expiry_notified_until = Some(subscription.until);
+                subscription.clone()
+            })
+            .collect())
+    }
+
+    async fn release_subscription_expiry(&self, user_id: &Uuid, target: &str, until: &NaiveDateTime) -> Result<(), sqlx::Error> {
+        if let Some(subscription) = self.tables().subscriptions.iter_mut()
+            .find(|subscription| &subscription.user_id == user_id && subscription.target == target && subscription.expiry_notified_until.as_ref() == Some(until)) {
+            subscription.expiry_notified_until = None;
+        }
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl BlockchainRepository for MemoryRepository {
+    async fn get_block_number(&self, network: &str) -> Result<Option<i64>, sqlx::Error> {
+        Ok(self.tables().block_numbers.get(network).copied())
+    }
+
+    async fn set_block_number(&self, network: &str, block_number: i64) -> Result<(), sqlx::Error> {
+        self.tables().block_numbers.insert(network.to_lowercase(), block_number);
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl AnalyticsRepository for MemoryRepository {
+    async fn invoice_stats_by_day(&self, user_id: &Uuid, since: NaiveDateTime) -> Result<Vec<InvoicePeriodStats>, sqlx::Error> {
+        let tables = self.tables();
+        let mut days: BTreeMap<NaiveDateTime, Vec<&Invoice>> = BTreeMap::new();
+        for invoice in tables.invoices.iter().filter(|invoice| invoice.user_id.as_ref() == Some(user_id) && invoice.created_at >= since) {
+            let day = invoice.created_at.date().and_hms_opt(0, 0, 0).expect("midnight is valid");
+            days.entry(day).or_default().push(invoice);
+        }
+
+        Ok(days.into_iter()
+            .rev()
+            .map(|(period, invoices)| {
+                let summary = summarize(&invoices);
+                InvoicePeriodStats {
+                    period,
+                    total_invoices: summary.total_invoices,
+                    paid_invoices: summary.paid_invoices,
+                    total_amount: summary.total_amount,
+                    paid_amount: summary.paid_amount,
+                    expired_invoices: summary.expired_invoices,
+                    void_invoices: summary.void_invoices,
+                }
+            })
+            .collect())
+    }
+
+    async fn invoice_summary(&self, user_id: &Uuid, since: NaiveDateTime) -> Result<InvoiceSummary, sqlx::Error> {
+        let tables = self.tables();
+        let invoices: Vec<&Invoice> = tables.invoices.iter()
+            .filter(|invoice| invoice.user_id.as_ref() == Some(user_id) && invoice.created_at >= since)
+            .collect();
+        Ok(summarize(&invoices))
+    }
+}
+
+fn summarize(invoices: &[&Invoice]) -> InvoiceSummary {
+    let count = |status: InvoiceStatus| invoices.iter().filter(|invoice| invoice.status == status).count() as i64;
+    let counted: Vec<&&Invoice> = invoices.iter()
+        .filter(|invoice| !matches!(invoice.status, InvoiceStatus::Expired | InvoiceStatus::Void))
+        .collect();
+    let paid: Vec<&&&Invoice> = counted.iter().filter(|invoice| invoice.status == InvoiceStatus::Paid).collect();
+    InvoiceSummary {
+        total_invoices: counted.len() as i64,
+        paid_invoices: paid.len() as i64,
+        total_amount: counted.iter().map(|invoice| &invoice.amount).sum(),
+        paid_amount: paid.iter().map(|invoice| &invoice.amount).sum(),
+        expired_invoices: count(InvoiceStatus::Expired),
+        void_invoices: count(InvoiceStatus::Void),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn payment(seller: &str, amount: i32) -> InvoicePayment {
+        InvoicePayment {
+            seller: seller.to_string(),
+            amount: BigDecimal::from(amount),
+            buyer: "0xb".to_string(),
+            paid_at: now(),
+            network: 10,
+            tx_hash: "0xtx".to_string(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_list_invoices_hides_other_users_private() {
+        let repo = MemoryRepository::default();
+        let (owner, other) = (Uuid::new_v4(), Uuid::new_v4());
+        repo.create_invoice(BigDecimal::from(1), "0xA", &[1], Some(owner), None, InvoiceOptions { is_private: true, ..Default::default() }).await.unwrap();
+        repo.create_invoice(BigDecimal::from(2), "0xA", &[1], Some(other), None, InvoiceOptions::default()).await.unwrap();
+
+        assert_eq!(repo.list_invoices(10, 0, None).await.unwrap().len(), 1);
+        assert_eq!(repo.list_invoices(10, 0, Some(owner)).await.unwrap().len(), 2);
+        assert_eq!(repo.list_invoices(1, 1, Some(owner)).await.unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_list_invoices_hides_void() {
+        let repo = MemoryRepository::default();
+        let owner = Uuid::new_v4();
+        let void = repo.create_invoice(BigDecimal::from(1), "0xA", &[1], Some(owner), None, InvoiceOptions::default()).await.unwrap();
+        let expired = repo.create_invoice(BigDecimal::from(2), "0xA", &[1], Some(owner), None, InvoiceOptions::default()).await.unwrap();
+        repo.set_invoice_status(&void.id, &[InvoiceStatus::Open], InvoiceStatus::Void).await.unwrap().unwrap();
+        repo.set_invoice_status(&expired.id, &[InvoiceStatus::Open], InvoiceStatus::Expired).await.unwrap().unwrap();
+
+        let ids = |invoices: Vec<Invoice>| invoices.into_iter().map(|invoice| invoice.id).collect::<Vec<_>>();
+        assert_eq!(ids(repo.list_invoices(10, 0, None).await.unwrap()), [expired.id]);
+        assert_eq!(ids(repo.user_own_invoices(10, 0, &owner).await.unwrap()), [expired.id]);
+    }
+
+    #[tokio::test]
+    async fn test_set_invoice_paid_requires_matching_seller_and_amount() {
+        let repo = MemoryRepository::default();
+        let invoice = repo.create_invoice(BigDecimal::from(10), "0xSeller", &[1], None, None, InvoiceOptions::default()).await.unwrap();
+        // Only a payment marks an invoice paid.
+        assert!(InvoiceStatus::Paid.sources().is_empty());
+        assert!(repo.set_invoice_status(&invoice.id, &InvoiceStatus::Paid.sources(), InvoiceStatus::Paid).await.unwrap().is_none());
+
+        let wrong_amount = repo.set_invoice_paid(invoice.id, &payment("0xseller", 9)).await;
+        assert!(matches!(wrong_amount, Err(sqlx::Error::RowNotFound)));
+
+        let paid = repo.set_invoice_paid(invoice.id, &InvoicePayment { buyer: "0xB".to_string(), ..payment("0xSELLER", 10) })
+            .await
+            .unwrap();
+        assert_eq!(paid.buyer.as_deref(), Some("0xb"));
+        assert_eq!((paid.paid_network, paid.tx_hash.as_deref()), (Some(10), Some("0xtx")));
+        assert_eq!(paid.status, InvoiceStatus::Paid);
+        assert!(repo.set_invoice_status(&invoice.id, &InvoiceStatus::Void.sources(), InvoiceStatus::Void).await.unwrap().is_none());
+        assert!(matches!(repo.set_invoice_paid(invoice.id, &payment("0xseller", 10)).await, Err(sqlx::Error::RowNotFound)));
+    }
+
+    #[tokio::test]
+    async fn test_expire_and_reopen_invoices() {
+        let repo = MemoryRepository::default();
+        let t0 = now();
+        let past = t0 - chrono::Duration::minutes(1);
+        let mut ids = vec![];
+        for expires_at in [Some(past), Some(past), Some(past), None] {
+            let options = InvoiceOptions { expires_at, ..Default::default() };
+            ids.push(repo.create_invoice(BigDecimal::from(1), "0xA", &[1], None, None, options).await.unwrap().id);
+        }
+        for id in &ids[1..] {
+            repo.set_invoice_status(id, &[InvoiceStatus::Open], InvoiceStatus::Confirming).await.unwrap().unwrap();
+        }
+        // ids[1] started confirming long ago, ids[2] just now.
+        repo.tables().invoices[1].status_changed_at = t0 - chrono::Duration::hours(1);
+        repo.tables().invoices[3].status_changed_at = t0 - chrono::Duration::hours(1);
+        let grace = t0 - chrono::Duration::minutes(30);
+
+        let expired = repo.expire_invoices(t0, grace).await.unwrap();
+        assert_eq!(expired.iter().map(|invoice| invoice.id).collect::<Vec<_>>(), ids[..2]);
+        let reopened = repo.reopen_stale_invoices(t0, grace).await.unwrap();
+        assert_eq!(reopened.iter().map(|invoice| invoice.id).collect::<Vec<_>>(), ids[3..]);
+
+        let statuses = repo.tables().invoices.iter().map(|invoice| invoice.status).collect::<Vec<_>>();
+        assert_eq!(statuses, [InvoiceStatus::Expired, InvoiceStatus::Expired, InvoiceStatus::Confirming, InvoiceStatus::Open]);
+        let timeline = repo.list_invoice_events(&ids[3]).await.unwrap().into_iter().map(|event| event.kind).collect::<Vec<_>>();
+        assert_eq!(timeline, ["created", "confirming", "open"]);
+    }
+
+    #[tokio::test]
+    async fn test_exists_callback_url_allows_any_until_one_is_registered() {
+        let repo = MemoryRepository::default();
+        let user_id = Uuid::new_v4();
+        assert!(repo.exists_callback_url("https://a.example", &user_id).await.unwrap());
+
+        repo.create_callback_url("https://b.example", &user_id).await.unwrap();
+        assert!(!repo.exists_callback_url("https://a.example", &user_id).await.unwrap());
+        assert!(repo.exists_callback_url("https://b.example", &user_id).await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_invoice_summary() {
+        let repo = MemoryRepository::default();
+        let user_id = Uuid::new_v4();
+        let invoice = repo.create_invoice(BigDecimal::from(5), "0xa", &[1], Some(user_id), None, InvoiceOptions::default()).await.unwrap();
+        repo.create_invoice(BigDecimal::from(7), "0xa", &[1], Some(user_id), None, InvoiceOptions::default()).await.unwrap();
+        repo.set_invoice_paid(invoice.id, &payment("0xa", 5)).await.unwrap();
+        for status in [InvoiceStatus::Expired, InvoiceStatus::Void] {
+            let unpaid = repo.create_invoice(BigDecimal::from(100), "0xa", &[1], Some(user_id), None, InvoiceOptions::default()).await.unwrap();
+            repo.set_invoice_status(&unpaid.id, &[InvoiceStatus::Open], status).await.unwrap().unwrap();
+        }
+
+        let since = now() - chrono::Duration::days(1);
+        let summary = repo.invoice_summary(&user_id, since).await.unwrap();
+        assert_eq!((summary.total_invoices, summary.paid_invoices), (2, 1));
+        assert_eq!(summary.total_amount, BigDecimal::from(12));
+        assert_eq!(summary.paid_amount, BigDecimal::from(5));
+        assert_eq!((summary.expired_invoices, summary.void_invoices), (1, 1));
+        let by_day = repo.invoice_stats_by_day(&user_id, since).await.unwrap();
+        assert_eq!((by_day.len(), by_day[0].total_invoices, by_day[0].total_amount.clone()), (1, 2, BigDecimal::from(12)));
+        assert_eq!((by_day[0].expired_invoices, by_day[0].void_invoices), (1, 1));
+    }
+}
@@ -1,11 +1,24 @@
 pub mod analytics;
 pub mod billing;
 pub mod invoice;
+pub mod invoice_event;
 pub mod blockchain;
 pub mod user;
 pub mod api_key;
 pub mod callback_url;
 pub mod webhook;
+pub mod webhook_delivery;
+pub mod notification_digest;
+pub mod notification_log;
+pub mod notification_preference;
+pub mod notification_template;
+pub mod telegram_destination;
+pub mod chat_webhook;
+pub mod encryption;
+pub mod repository;
+pub mod postgres;
+#[cfg(test)]
+pub mod memory;
 
 // Re-export structs so existing imports (use crate::db::{ApiKey, CallbackUrl, Invoice, User, Webhook}) keep working
 pub use api_key::ApiKey;
@@ -27,9 +40,12 @@ pub struct User {
     pub created_at: NaiveDateTime,
     pub firebase_user_id: String,
     pub email: Option<String>,
-    pub telegram_chat_id: Option<String>,
     pub email_notification: bool,
     pub telegram_notification: bool,
+    /// Language of notifications sent to the user, see [`crate::events::notifications::templates::LOCALES`].
+    pub locale: String,
+    /// Whether new invoices mail the buyer a receipt unless the request says otherwise.
+    pub buyer_receipts: bool,
 }
 
 #[derive(Clone, Serialize, sqlx::FromRow, utoipa::ToSchema)]
@@ -45,10 +61,33 @@ pub struct Invoice {
     pub user_id: Option<Uuid>,
     pub external_id: Option<String>,
     is_private: bool,
+    /// Chain id the invoice was paid on.
+    pub paid_network: Option<i32>,
+    pub tx_hash: Option<String>,
+    /// Encrypted; read it with [`crate::api::state::DB::get_buyer_email`].
+    #[serde(skip)]
+    pub buyer_email: Option<String>,
+    /// Whether the buyer is mailed a receipt once the invoice is paid.
+    pub send_receipt: bool,
+    #[schema(inline)]
+    pub status: invoice::InvoiceStatus,
+    pub status_changed_at: NaiveDateTime,
+    /// When `monitoring::invoice_expiry` expires the invoice if it is still unpaid.
+    pub expires_at: Option<NaiveDateTime>,
+    /// When the buyer first reported a transaction; set once, even if the invoice reopens.
+    pub confirming_at: Option<NaiveDateTime>,
+    /// The seller's own JSON object, returned with the invoice.
+    #[schema(value_type = Option<Object>)]
+    pub metadata: Option<serde_json::Value>,
 }
 
 impl Invoice {
-    pub fn web_url(&self) -> Result<String, String> {
-        utils::get_invoice_url(&self.id)
+    pub fn web_url(&self, web_base_url: &str) -> String {
+        utils::get_invoice_url(web_base_url, &self.id)
+    }
+
+    /// Whether a payment can still settle the invoice.
+    pub fn is_payable(&self) -> bool {
+        self.status.is_payable()
     }
 }
@@ -0,0 +1,181 @@
+use bigdecimal::BigDecimal;
+use sqlx::PgPool;
+use sqlx::types::Uuid;
+use sqlx::types::chrono::NaiveDateTime;
+
+/// How often a seller's channel gets its notifications, when not immediately.
+#[derive(Clone, Debug, PartialEq, sqlx::FromRow)]
+pub struct NotificationDigest {
+    pub user_id: Uuid,
+    /// `email` or `telegram`.
+    pub channel: String,
+    /// See [`crate::events::notifications::digest::DigestFrequency`].
+    pub frequency: String,
+    /// End of the last period that was summarized.
+    pub last_sent_at: Option<NaiveDateTime>,
+    pub updated_at: NaiveDateTime,
+}
+
+/// A notification waiting for the next digest of its channel.
+#[derive(Clone, Debug, PartialEq, sqlx::FromRow)]
+pub struct NotificationDigestItem {
+    pub id: Uuid,
+    pub user_id: Uuid,
+    pub channel: String,
+    pub event_type: String,
+    pub invoice_id: Uuid,
+    /// The invoice amount.
+    pub amount: BigDecimal,
+    /// Chain id the invoice was paid on or the transfer was read from.
+    pub network: Option<i32>,
+    pub external_id: Option<String>,
+    pub created_at: NaiveDateTime,
+}
+
+#[cfg_attr(not(any(feature = "mailer", feature = "telegram")), allow(dead_code))]
+pub struct NewNotificationDigestItem {
+    pub user_id: Uuid,
+    pub channel: String,
+    pub event_type: String,
+    pub invoice_id: Uuid,
+    pub amount: BigDecimal,
+    pub network: Option<i32>,
+    pub external_id: Option<String>,
+}
+
+pub async fn list_notification_digests(pg_pool: &PgPool, user_id: &Uuid) -> Result<Vec<NotificationDigest>, sqlx::Error> {
+    sqlx::query_as!(
+        NotificationDigest,
+        r#"
+        SELECT * FROM notification_digest
+        WHERE user_id = $1
+        ORDER BY channel
+        "#,
+        user_id,
+    )
+        .fetch_all(pg_pool)
+        .await
+}
+
+/// Pending items stay queued and go out with the next digest, or right away after switching
+/// to `immediate`.
+pub async fn upsert_notification_digest(
+    pg_pool: &PgPool,
+    user_id: &Uuid,
+    channel: &str,
+    frequency: &str,
+) -> Result<NotificationDigest, sqlx::Error> {
+    sqlx::query_as!(
+        NotificationDigest,
+        r#"
+        INSERT INTO notification_digest (user_id, channel, frequency)
+        VALUES ($1, $2, $3)
+        ON CONFLICT (user_id, channel)
+        DO UPDATE SET frequency = EXCLUDED.frequency, updated_at = CURRENT_TIMESTAMP
+        RETURNING *
+        "#,
+        user_id,
+        channel,
+        frequency,
+    )
+        .fetch_one(pg_pool)
+        .await
+}
+
+/// Digests of every user with queued items.
+pub async fn list_pending_notification_digests(pg_pool: &PgPool) -> Result<Vec<NotificationDigest>, sqlx::Error> {
+    sqlx::query_as!(
+        NotificationDigest,
+        r#"
+        SELECT * FROM notification_digest digest
+        WHERE EXISTS (
+            SELECT 1 FROM notification_digest_item item
+            WHERE item.user_id = digest.user_id AND item.channel = digest.channel
+        )
+        "#,
+    )
+        .fetch_all(pg_pool)
+        .await
+}
+
+/// Moves `last_sent_at` to `period_end` unless it is already there, so only one worker sends
+/// the digest of a period.
+pub async fn claim_notification_digest(
+    pg_pool: &PgPool,
+    user_id: &Uuid,
+    channel: &str,
+    period_end: &NaiveDateTime,
+) -> Result<bool, sqlx::Error> {
+    let result = sqlx::query!(
+        r#"
+        UPDATE notification_digest
+        SET last_sent_at = $3
+        WHERE user_id = $1 AND channel = $2 AND (last_sent_at IS NULL OR last_sent_at < $3)
+        "#,
+        user_id,
+        channel,
+        period_end,
+    )
+        .execute(pg_pool)
+        .await?;
+
+    Ok(result.rows_affected() > 0)
+}
+
+pub async fn create_notification_digest_item(
+    pg_pool: &PgPool,
+    item: &NewNotificationDigestItem,
+) -> Result<NotificationDigestItem, sqlx::Error> {
+    sqlx::query_as!(
+        NotificationDigestItem,
+        r#"
+        INSERT INTO notification_digest_item (user_id, channel, event_type, invoice_id, amount, network, external_id)
+        VALUES ($1, $2, $3, $4, $5, $6, $7)
+        RETURNING *
+        "#,
+        item.user_id,
+        item.channel,
+        item.event_type,
+        item.invoice_id,
+        item.amount,
+        item.network,
+        item.external_id,
+    )
+        .fetch_one(pg_pool)
+        .await
+}
+
+/// The items queued before `before`, oldest first.
+pub async fn list_notification_digest_items(
+    pg_pool: &PgPool,
+    user_id: &Uuid,
+    channel: &str,
+    before: &NaiveDateTime,
+) -> Result<Vec<NotificationDigestItem>, sqlx::Error> {
+    sqlx::query_as!(
+        NotificationDigestItem,
+        r#"
+        SELECT * FROM notification_digest_item
+        WHERE user_id = $1 AND channel = $2 AND created_at < $3
+        ORDER BY created_at
+        "#,
+        user_id,
+        channel,
+        before,
+    )
+        .fetch_all(pg_pool)
+        .await
+}
+
+pub async fn delete_notification_digest_items(pg_pool: &PgPool, ids: &[Uuid]) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        r#"
+        DELETE FROM notification_digest_item
+        WHERE id = ANY($1)
+        "#,
+        ids,
+    )
+        .execute(pg_pool)
+        .await
+        .map(|_| ())
+}
@@ -0,0 +1,208 @@
+use serde_json::Value;
+use sqlx::PgPool;
+use sqlx::types::Uuid;
+use sqlx::types::chrono::NaiveDateTime;
+
+/// `event_type` of digest messages, which cover many events and cannot be resent.
+pub const DIGEST_EVENT: &str = "digest";
+/// `event_type` of receipts mailed to the buyer of a paid invoice.
+pub const RECEIPT_EVENT: &str = "receipt";
+
+pub enum NotificationLogStatus {
+    Sent,
+    Failed,
+    /// A resend was requested and is waiting for `monitoring::notification_resend`.
+    Pending,
+}
+
+impl NotificationLogStatus {
+    pub const ALL: [Self; 3] = [Self::Sent, Self::Failed, Self::Pending];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Sent => "sent",
+            Self::Failed => "failed",
+            Self::Pending => "pending",
+        }
+    }
+}
+
+/// A message sent to a seller on email, Telegram or a chat webhook.
+#[derive(Clone, Debug, sqlx::FromRow)]
+pub struct NotificationLog {
+    pub id: Uuid,
+    pub user_id: Uuid,
+    pub channel: String,
+    /// A notification event, e.g. `invoice.paid`, [`DIGEST_EVENT`] or [`RECEIPT_EVENT`].
+    pub event_type: String,
+    pub invoice_id: Option<Uuid>,
+    /// The Telegram destination or chat webhook; `None` for email.
+    pub destination_id: Option<Uuid>,
+    /// The masked email address or the chat's label.
+    pub destination: String,
+    /// [`crate::events::notifications::Transfer`] of partial payments and anomalies.
+    pub transfer: Option<Value>,
+    pub status: String,
+    pub error: Option<String>,
+    pub attempts: i32,
+    pub next_attempt_at: Option<NaiveDateTime>,
+    pub created_at: NaiveDateTime,
+    pub updated_at: NaiveDateTime,
+    pub sent_at: Option<NaiveDateTime>,
+}
+
+/// A first attempt, logged as `sent` or `failed` with its error.
+#[derive(Clone, Debug)]
+pub struct NewNotificationLog {
+    pub user_id: Uuid,
+    pub channel: String,
+    pub event_type: String,
+    pub invoice_id: Option<Uuid>,
+    pub destination_id: Option<Uuid>,
+    pub destination: String,
+    pub transfer: Option<Value>,
+    pub error: Option<String>,
+}
+
+/// Conditions of `GET /user/notifications`; `None` matches everything.
+#[derive(Clone, Debug, Default)]
+pub struct NotificationLogFilter {
+    pub channel: Option<String>,
+    pub event_type: Option<String>,
+    pub status: Option<String>,
+    pub invoice_id: Option<Uuid>,
+}
+
+pub async fn create_notification_log(pg_pool: &PgPool, log: &NewNotificationLog) -> Result<NotificationLog, sqlx::Error> {
+    sqlx::query_as!(
+        NotificationLog,
+        r#"
+        INSERT INTO notification_log (user_id, channel, event_type, invoice_id, destination_id, destination, transfer, status, error, sent_at)
+        VALUES ($1, $2, $3, $4, $5, $6, $7,
+            CASE WHEN $8::TEXT IS NULL THEN 'sent' ELSE 'failed' END,
+            $8,
+            CASE WHEN $8::TEXT IS NULL THEN CURRENT_TIMESTAMP END)
+        RETURNING *
+        "#,
+        log.user_id,
+        log.channel,
+        log.event_type,
+        log.invoice_id,
+        log.destination_id,
+        log.destination,
+        log.transfer,
+        log.error,
+    )
+        .fetch_one(pg_pool)
+        .await
+}
+
+/// Newest first.
+pub async fn list_notification_logs(
+    pg_pool: &PgPool,
+    user_id: &Uuid,
+    filter: &NotificationLogFilter,
+    limit: i64,
+    offset: i64,
+) -> Result<Vec<NotificationLog>, sqlx::Error> {
+    sqlx::query_as!(
+        NotificationLog,
+        r#"
+        SELECT * FROM notification_log
+        WHERE user_id = $1
+            AND ($2::TEXT IS NULL OR channel = $2)
+            AND ($3::TEXT IS NULL OR event_type = $3)
+            AND ($4::TEXT IS NULL OR status = $4)
+            AND ($5::UUID IS NULL OR invoice_id = $5)
+        ORDER BY created_at DESC, id
+        LIMIT $6 OFFSET $7
+        "#,
+        user_id,
+        filter.channel,
+        filter.event_type,
+        filter.status,
+        filter.invoice_id,
+        limit,
+        offset,
+    )
+        .fetch_all(pg_pool)
+        .await
+}
+
+pub async fn get_notification_log(pg_pool: &PgPool, id: &Uuid, user_id: &Uuid) -> Result<Option<NotificationLog>, sqlx::Error> {
+    sqlx::query_as!(
+        NotificationLog,
+        r#"
+        SELECT * FROM notification_log
+        WHERE id = $1 AND user_id = $2
+        "#,
+        id,
+        user_id,
+    )
+        .fetch_optional(pg_pool)
+        .await
+}
+
+/// Queues a failed message to be sent again; only those about an invoice can be, not digests
+/// or subscription notices.
+pub async fn resend_notification(pg_pool: &PgPool, id: &Uuid, user_id: &Uuid) -> Result<bool, sqlx::Error> {
+    let result = sqlx::query!(
+        r#"
+        UPDATE notification_log
+        SET status = 'pending', next_attempt_at = NOW(), updated_at = NOW()
+        WHERE id = $1 AND user_id = $2 AND status = 'failed' AND invoice_id IS NOT NULL
+        "#,
+        id,
+        user_id,
+    )
+        .execute(pg_pool)
+        .await?;
+
+    Ok(result.rows_affected() > 0)
+}
+
+/// Pending resends that are due, leased for `lease_secs` so a crashed worker's claims are
+/// picked up again.
+pub async fn claim_pending_notifications(pg_pool: &PgPool, limit: i64, lease_secs: f64) -> Result<Vec<NotificationLog>, sqlx::Error> {
+    sqlx::query_as!(
+        NotificationLog,
+        r#"
+        UPDATE notification_log
+        SET next_attempt_at = NOW() + make_interval(secs => $2)
+        WHERE id IN (
+            SELECT id FROM notification_log
+            WHERE status = 'pending' AND next_attempt_at <= NOW()
+            ORDER BY next_attempt_at
+            LIMIT $1
+            FOR UPDATE SKIP LOCKED
+        )
+        RETURNING *
+        "#,
+        limit,
+        lease_secs,
+    )
+        .fetch_all(pg_pool)
+        .await
+}
+
+/// Records the outcome of a resend.
+pub async fn record_notification_attempt(pg_pool: &PgPool, id: &Uuid, error: Option<&str>) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        r#"
+        UPDATE notification_log
+        SET status = CASE WHEN $2::TEXT IS NULL THEN 'sent' ELSE 'failed' END,
+            error = $2,
+            attempts = attempts + 1,
+            next_attempt_at = NULL,
+            updated_at = NOW(),
+            sent_at = CASE WHEN $2::TEXT IS NULL THEN NOW() ELSE sent_at END
+        WHERE id = $1
+        "#,
+        id,
+        error,
+    )
+        .execute(pg_pool)
+        .await?;
+
+    Ok(())
+}
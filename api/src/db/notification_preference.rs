@@ -0,0 +1,91 @@
+use bigdecimal::BigDecimal;
+use sqlx::PgPool;
+use sqlx::types::Uuid;
+use sqlx::types::chrono::NaiveDateTime;
+
+/// A seller's choice for one channel and event, overriding the event's default.
+#[derive(Clone, Debug, PartialEq, sqlx::FromRow)]
+pub struct NotificationPreference {
+    pub user_id: Uuid,
+    /// `email`, `telegram` or `chat`.
+    pub channel: String,
+    /// e.g. `invoice.paid`, see [`crate::events::notifications::preferences::NotificationEvent`].
+    pub event_type: String,
+    pub enabled: bool,
+    /// Invoices below it are not notified.
+    pub min_amount: Option<BigDecimal>,
+    /// Only invoices whose `external_id` starts with it are notified.
+    pub external_id_prefix: Option<String>,
+    pub updated_at: NaiveDateTime,
+}
+
+impl NotificationPreference {
+    /// Whether a notification about an invoice of `amount` passes the preference.
+    pub fn accepts(&self, amount: &BigDecimal, external_id: Option<&str>) -> bool {
+        self.enabled
+            && self.min_amount.as_ref().is_none_or(|min_amount| amount >= min_amount)
+            && self.external_id_prefix.as_deref().is_none_or(|prefix| external_id.is_some_and(|id| id.starts_with(prefix)))
+    }
+}
+
+pub async fn list_notification_preferences(pg_pool: &PgPool, user_id: &Uuid) -> Result<Vec<NotificationPreference>, sqlx::Error> {
+    sqlx::query_as!(
+        NotificationPreference,
+        r#"
+        SELECT * FROM notification_preference
+        WHERE user_id = $1
+        ORDER BY channel, event_type
+        "#,
+        user_id,
+    )
+        .fetch_all(pg_pool)
+        .await
+}
+
+pub async fn upsert_notification_preference(
+    pg_pool: &PgPool,
+    preference: &NotificationPreference,
+) -> Result<NotificationPreference, sqlx::Error> {
+    sqlx::query_as!(
+        NotificationPreference,
+        r#"
+        INSERT INTO notification_preference (user_id, channel, event_type, enabled, min_amount, external_id_prefix)
+        VALUES ($1, $2, $3, $4, $5, $6)
+        ON CONFLICT (user_id, channel, event_type)
+        DO UPDATE SET enabled = EXCLUDED.enabled,
+                      min_amount = EXCLUDED.min_amount,
+                      external_id_prefix = EXCLUDED.external_id_prefix,
+                      updated_at = CURRENT_TIMESTAMP
+        RETURNING *
+        "#,
+        preference.user_id,
+        preference.channel,
+        preference.event_type,
+        preference.enabled,
+        preference.min_amount,
+        preference.external_id_prefix,
+    )
+        .fetch_one(pg_pool)
+        .await
+}
+
+pub async fn delete_notification_preference(
+    pg_pool: &PgPool,
+    user_id: &Uuid,
+    channel: &str,
+    event_type: &str,
+) -> Result<bool, sqlx::Error> {
+    let result = sqlx::query!(
+        r#"
+        DELETE FROM notification_preference
+        WHERE user_id = $1 AND channel = $2 AND event_type = $3
+        "#,
+        user_id,
+        channel,
+        event_type,
+    )
+        .execute(pg_pool)
+        .await?;
+
+    Ok(result.rows_affected() > 0)
+}
@@ -0,0 +1,95 @@
+use serde::Serialize;
+use sqlx::PgPool;
+use sqlx::types::Uuid;
+use sqlx::types::chrono::NaiveDateTime;
+
+/// A seller's override of a built-in notification template for one locale.
+/// `None` fields fall back to the built-in template.
+#[derive(Clone, Debug, Serialize, sqlx::FromRow)]
+pub struct NotificationTemplate {
+    pub user_id: Uuid,
+    /// Which notification, e.g. `invoice_paid`.
+    pub kind: String,
+    pub locale: String,
+    pub subject: Option<String>,
+    pub text_body: Option<String>,
+    pub html_body: Option<String>,
+    pub telegram_body: Option<String>,
+    pub updated_at: NaiveDateTime,
+}
+
+pub async fn list_notification_templates(pg_pool: &PgPool, user_id: &Uuid) -> Result<Vec<NotificationTemplate>, sqlx::Error> {
+    sqlx::query_as!(
+        NotificationTemplate,
+        r#"
+        SELECT * FROM notification_template
+        WHERE user_id = $1
+        ORDER BY kind, locale
+        "#,
+        user_id,
+    )
+        .fetch_all(pg_pool)
+        .await
+}
+
+pub async fn get_notification_template(
+    pg_pool: &PgPool,
+    user_id: &Uuid,
+    kind: &str,
+    locale: &str,
+) -> Result<Option<NotificationTemplate>, sqlx::Error> {
+    sqlx::query_as!(
+        NotificationTemplate,
+        r#"
+        SELECT * FROM notification_template
+        WHERE user_id = $1 AND kind = $2 AND locale = $3
+        "#,
+        user_id,
+        kind,
+        locale,
+    )
+        .fetch_optional(pg_pool)
+        .await
+}
+
+pub async fn upsert_notification_template(pg_pool: &PgPool, template: &NotificationTemplate) -> Result<NotificationTemplate, sqlx::Error> {
+    sqlx::query_as!(
+        NotificationTemplate,
+        r#"
+        INSERT INTO notification_template (user_id, kind, locale, subject, text_body, html_body, telegram_body)
+        VALUES ($1, $2, $3, $4, $5, $6, $7)
+        ON CONFLICT (user_id, kind, locale)
+        DO UPDATE SET subject = EXCLUDED.subject,
+                      text_body = EXCLUDED.text_body,
+                      html_body = EXCLUDED.html_body,
+                      telegram_body = EXCLUDED.telegram_body,
+                      updated_at = CURRENT_TIMESTAMP
+        RETURNING *
+        "#,
+        template.user_id,
+        template.kind,
+        template.locale,
+        template.subject,
+        template.text_body,
+        template.html_body,
+        template.telegram_body,
+    )
+        .fetch_one(pg_pool)
+        .await
+}
+
+pub async fn delete_notification_template(pg_pool: &PgPool, user_id: &Uuid, kind: &str, locale: &str) -> Result<bool, sqlx::Error> {
+    let result = sqlx::query!(
+        r#"
+        DELETE FROM notification_template
+        WHERE user_id = $1 AND kind = $2 AND locale = $3
+        "#,
+        user_id,
+        kind,
+        locale,
+    )
+        .execute(pg_pool)
+        .await?;
+
+    Ok(result.rows_affected() > 0)
+}
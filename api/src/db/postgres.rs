@@ -0,0 +1,566 @@
+use async_trait::async_trait;
+use bigdecimal::BigDecimal;
+use serde_json::Value;
+use sqlx::migrate::MigrateError;
+use sqlx::PgPool;
+use sqlx::types::{chrono::NaiveDateTime, Uuid};
+use super::analytics::{self, InvoicePeriodStats, InvoiceSummary};
+use super::billing::{self, Payment, Subscription};
+use super::invoice::{InvoiceOptions, InvoicePayment, InvoiceStatus, InvoiceUpdate};
+use super::invoice_event::{self, InvoiceEvent};
+use super::notification_digest::{self, NewNotificationDigestItem, NotificationDigest, NotificationDigestItem};
+use super::notification_log::{self, NewNotificationLog, NotificationLog, NotificationLogFilter};
+use super::notification_preference::{self, NotificationPreference};
+use super::notification_template::{self, NotificationTemplate};
+use super::repository::*;
+use super::chat_webhook::{self, ChatWebhook, NewChatWebhook};
+#[cfg(feature = "telegram")]
+use super::telegram_destination::NewTelegramDestination;
+use super::telegram_destination::{self, TelegramDestination};
+use super::webhook_delivery::{self, DueWebhookDelivery, NewWebhookDeliveryAttempt, WebhookDelivery, WebhookDeliveryAttempt, WebhookDeliveryStatus};
+use super::{api_key, blockchain, callback_url, invoice, user, webhook};
+use super::{ApiKey, CallbackUrl, Invoice, User, Webhook};
+
+/// [`Repository`] backed by the Postgres pool; delegates to the query functions of each `db` module.
+pub struct PgRepository {
+    pg_pool: PgPool,
+}
+
+impl PgRepository {
+    pub fn new(pg_pool: PgPool) -> Self {
+        Self { pg_pool }
+    }
+}
+
+#[async_trait]
+impl Store for PgRepository {
+    async fn run_migrations(&self) -> Result<(), MigrateError> {
+        sqlx::migrate!("./migrations")
+            .run(&self.pg_pool)
+            .await
+    }
+
+    async fn health_check(&self) -> Result<(), sqlx::Error> {
+        sqlx::query("SELECT 1")
+            .fetch_one(&self.pg_pool)
+            .await
+            .map(|_| ())
+    }
+
+    async fn close(&self) {
+        self.pg_pool.close().await
+    }
+}
+
+#[async_trait]
+impl InvoiceRepository for PgRepository {
+    async fn list_invoices(&self, limit: i64, offset: i64, user_id: Option<Uuid>) -> Result<Vec<Invoice>, sqlx::Error> {
+        invoice::list_invoices(&self.pg_pool, limit, offset, user_id).await
+    }
+
+    async fn user_own_invoices(&self, limit: i64, offset: i64, user_id: &Uuid) -> Result<Vec<Invoice>, sqlx::Error> {
+        invoice::user_own_invoices(&self.pg_pool, limit, offset, user_id).await
+    }
+
+    async fn create_invoice(
+        &self,
+        amount: BigDecimal,
+        seller: &str,
+        networks: &[i32],
+        user_id: Option<Uuid>,
+        external_id: Option<String>,
+        options: InvoiceOptions,
+    ) -> Result<Invoice, sqlx::Error> {
+        invoice::create_invoice(&self.pg_pool, amount, seller, networks, user_id, external_id, options).await
+    }
+
+    async fn get_invoice(&self, id: &Uuid) -> Result<Option<Invoice>, sqlx::Error> {
+        invoice::get_invoice(&self.pg_pool, id).await
+    }
+
+    async fn get_is_owner(&self, id: &Uuid, user_id: &Uuid) -> Result<bool, sqlx::Error> {
+        invoice::get_is_owner(&self.pg_pool, id, user_id).await
+    }
+
+    async fn set_invoice_paid(&self, id: Uuid, payment: &InvoicePayment) -> Result<Invoice, sqlx::Error> {
+        invoice::set_invoice_paid(&self.pg_pool, id, payment).await
+    }
+
+    async fn set_invoice_status(&self, id: &Uuid, from: &[InvoiceStatus], to: InvoiceStatus) -> Result<Option<Invoice>, sqlx::Error> {
+        invoice::set_invoice_status(&self.pg_pool, id, from, to).await
+    }
+
+    async fn expire_invoices(&self, now: NaiveDateTime, confirming_before: NaiveDateTime) -> Result<Vec<Invoice>, sqlx::Error> {
+        invoice::expire_invoices(&self.pg_pool, now, confirming_before).await
+    }
+
+    async fn reopen_stale_invoices(&self, now: NaiveDateTime, confirming_before: NaiveDateTime) -> Result<Vec<Invoice>, sqlx::Error> {
+        invoice::reopen_stale_invoices(&self.pg_pool, now, confirming_before).await
+    }
+
+    async fn update_invoice(&self, id: &Uuid, update: &InvoiceUpdate, changes: Value) -> Result<Option<Invoice>, sqlx::Error> {
+        invoice::update_invoice(&self.pg_pool, id, update, changes).await
+    }
+
+    async fn list_invoice_events(&self, invoice_id: &Uuid) -> Result<Vec<InvoiceEvent>, sqlx::Error> {
+        invoice_event::list_invoice_events(&self.pg_pool, invoice_id).await
+    }
+
+    async fn set_invoice_buyer_email(&self, id: &Uuid, buyer_email: &str, replace: bool) -> Result<Option<Invoice>, sqlx::Error> {
+        invoice::set_invoice_buyer_email(&self.pg_pool, id, buyer_email, replace).await
+    }
+
+    async fn rewrite_invoice_buyer_email(&self, id: &Uuid, buyer_email: &str) -> Result<(), sqlx::Error> {
+        invoice::rewrite_invoice_buyer_email(&self.pg_pool, id, buyer_email).await
+    }
+
+    async fn list_invoices_with_buyer_email_after(&self, after: &Uuid, limit: i64) -> Result<Vec<Invoice>, sqlx::Error> {
+        invoice::list_invoices_with_buyer_email_after(&self.pg_pool, after, limit).await
+    }
+}
+
+#[async_trait]
+impl UserRepository for PgRepository {
+    async fn get_user_by_id(&self, id: &Uuid) -> Result<User, sqlx::Error> {
+        user::get_user_by_id(&self.pg_pool, id).await
+    }
+
+    async fn get_or_create_user(&self, firebase_user_id: &str, email: Option<String>) -> Result<User, sqlx::Error> {
+        user::get_or_create_user(&self.pg_pool, firebase_user_id, email).await
+    }
+
+    async fn update_user(
+        &self,
+        user_id: &Uuid,
+        email_notification: Option<bool>,
+        telegram_notification: Option<bool>,
+    ) -> Result<User, sqlx::Error> {
+        user::update_user(&self.pg_pool, user_id, email_notification, telegram_notification).await
+    }
+
+    async fn set_user_email(&self, user_id: &Uuid, email: Option<String>) -> Result<(), sqlx::Error> {
+        user::set_user_email(&self.pg_pool, user_id, email).await
+    }
+
+    async fn list_users_after(&self, after: &Uuid, limit: i64) -> Result<Vec<User>, sqlx::Error> {
+        user::list_users_after(&self.pg_pool, after, limit).await
+    }
+
+    async fn set_user_locale(&self, user_id: &Uuid, locale: &str) -> Result<(), sqlx::Error> {
+        user::set_user_locale(&self.pg_pool, user_id, locale).await
+    }
+
+    async fn set_user_buyer_receipts(&self, user_id: &Uuid, buyer_receipts: bool) -> Result<(), sqlx::Error> {
+        user::set_user_buyer_receipts(&self.pg_pool, user_id, buyer_receipts).await
+    }
+}
+
+#[async_trait]
+impl NotificationTemplateRepository for PgRepository {
+    async fn list_notification_templates(&self, user_id: &Uuid) -> Result<Vec<NotificationTemplate>, sqlx::Error> {
+        notification_template::list_notification_templates(&self.pg_pool, user_id).await
+    }
+
+    async fn get_notification_template(&self, user_id: &Uuid, kind: &str, locale: &str) -> Result<Option<NotificationTemplate>, sqlx::Error> {
+        notification_template::get_notification_template(&self.pg_pool, user_id, kind, locale).await
+    }
+
+    async fn upsert_notification_template(&self, template: &NotificationTemplate) -> Result<NotificationTemplate, sqlx::Error> {
+        notification_template::upsert_notification_template(&self.pg_pool, template).await
+    }
+
+    async fn delete_notification_template(&self, user_id: &Uuid, kind: &str, locale: &str) -> Result<bool, sqlx::Error> {
+        notification_template::delete_notification_template(&self.pg_pool, user_id, kind, locale).await
+    }
+}
+
+#[async_trait]
+impl NotificationPreferenceRepository for PgRepository {
+    async fn list_notification_preferences(&self, user_id: &Uuid) -> Result<Vec<NotificationPreference>, sqlx::Error> {
+        notification_preference::list_notification_preferences(&self.pg_pool, user_id).await
+    }
+
+    async fn upsert_notification_preference(&self, preference: &NotificationPreference) -> Result<NotificationPreference, sqlx::Error> {
+        notification_preference::upsert_notification_preference(&self.pg_pool, preference).await
+    }
+
+    async fn delete_notification_preference(&self, user_id: &Uuid, channel: &str, event_type: &str) -> Result<bool, sqlx::Error> {
+        notification_preference::delete_notification_preference(&self.pg_pool, user_id, channel, event_type).await
+    }
+}
+
+#[async_trait]
+impl NotificationDigestRepository for PgRepository {
+    async fn list_notification_digests(&self, user_id: &Uuid) -> Result<Vec<NotificationDigest>, sqlx::Error> {
+        notification_digest::list_notification_digests(&self.pg_pool, user_id).await
+    }
+
+    async fn upsert_notification_digest(&self, user_id: &Uuid, channel: &str, frequency: &str) -> Result<NotificationDigest, sqlx::Error> {
+        notification_digest::upsert_notification_digest(&self.pg_pool, user_id, channel, frequency).await
+    }
+
+    async fn list_pending_notification_digests(&self) -> Result<Vec<NotificationDigest>, sqlx::Error> {
+        notification_digest::list_pending_notification_digests(&self.pg_pool).await
+    }
+
+    async fn claim_notification_digest(&self, user_id: &Uuid, channel: &str, period_end: &NaiveDateTime) -> Result<bool, sqlx::Error> {
+        notification_digest::claim_notification_digest(&self.pg_pool, user_id, channel, period_end).await
+    }
+
+    async fn create_notification_digest_item(&self, item: &NewNotificationDigestItem) -> Result<NotificationDigestItem, sqlx::Error> {
+        notification_digest::create_notification_digest_item(&self.pg_pool, item).await
+    }
+
+    async fn list_notification_digest_items(&self, user_id: &Uuid, channel: &str, before: &NaiveDateTime) -> Result<Vec<NotificationDigestItem>, sqlx::Error> {
+        notification_digest::list_notification_digest_items(&self.pg_pool, user_id, channel, before).await
+    }
+
+    async fn delete_notification_digest_items(&self, ids: &[Uuid]) -> Result<(), sqlx::Error> {
+        notification_digest::delete_notification_digest_items(&self.pg_pool, ids).await
+    }
+}
+
+#[async_trait]
+impl NotificationLogRepository for PgRepository {
+    async fn create_notification_log(&self, log: &NewNotificationLog) -> Result<NotificationLog, sqlx::Error> {
+        notification_log::create_notification_log(&self.pg_pool, log).await
+    }
+
+    async fn list_notification_logs(&self, user_id: &Uuid, filter: &NotificationLogFilter, limit: i64, offset: i64) -> Result<Vec<NotificationLog>, sqlx::Error> {
+        notification_log::list_notification_logs(&self.pg_pool, user_id, filter, limit, offset).await
+    }
+
+    async fn get_notification_log(&self, id: &Uuid, user_id: &Uuid) -> Result<Option<NotificationLog>, sqlx::Error> {
+        notification_log::get_notification_log(&self.pg_pool, id, user_id).await
+    }
+
+    async fn resend_notification(&self, id: &Uuid, user_id: &Uuid) -> Result<bool, sqlx::Error> {
+        notification_log::resend_notification(&self.pg_pool, id, user_id).await
+    }
+
+    async fn claim_pending_notifications(&self, limit: i64, lease_secs: f64) -> Result<Vec<NotificationLog>, sqlx::Error> {
+        notification_log::claim_pending_notifications(&self.pg_pool, limit, lease_secs).await
+    }
+
+    async fn record_notification_attempt(&self, id: &Uuid, error: Option<&str>) -> Result<(), sqlx::Error> {
+        notification_log::record_notification_attempt(&self.pg_pool, id, error).await
+    }
+}
+
+#[async_trait]
+impl TelegramDestinationRepository for PgRepository {
+    #[cfg(feature = "telegram")]
+    async fn upsert_telegram_destination(&self, destination: &NewTelegramDestination) -> Result<TelegramDestination, sqlx::Error> {
+        telegram_destination::upsert_telegram_destination(&self.pg_pool, destination).await
+    }
+
+    async fn list_telegram_destinations(&self, user_id: &Uuid) -> Result<Vec<TelegramDestination>, sqlx::Error> {
+        telegram_destination::list_telegram_destinations(&self.pg_pool, user_id).await
+    }
+
+    #[cfg(feature = "telegram")]
+    async fn get_telegram_destination_by_chat_lookup(&self, lookups: &[String]) -> Result<Option<TelegramDestination>, sqlx::Error> {
+        telegram_destination::get_telegram_destination_by_chat_lookup(&self.pg_pool, lookups).await
+    }
+
+    #[cfg(feature = "telegram")]
+    async fn update_telegram_destination(
+        &self,
+        id: &Uuid,
+        user_id: &Uuid,
+        label: Option<&str>,
+        event_types: Option<&[String]>,
+        min_amount: Option<Option<BigDecimal>>,
+    ) -> Result<Option<TelegramDestination>, sqlx::Error> {
+        telegram_destination::update_telegram_destination(&self.pg_pool, id, user_id, label, event_types, min_amount).await
+    }
+
+    async fn set_telegram_destination_chat(
+        &self,
+        id: &Uuid,
+        chat_id: &str,
+        chat_lookup: Option<&str>,
+        chat_type: &str,
+    ) -> Result<(), sqlx::Error> {
+        telegram_destination::set_telegram_destination_chat(&self.pg_pool, id, chat_id, chat_lookup, chat_type).await
+    }
+
+    #[cfg(feature = "telegram")]
+    async fn delete_telegram_destination(&self, id: &Uuid, user_id: &Uuid) -> Result<bool, sqlx::Error> {
+        telegram_destination::delete_telegram_destination(&self.pg_pool, id, user_id).await
+    }
+
+    #[cfg(feature = "telegram")]
+    async fn delete_telegram_destinations(&self, user_id: &Uuid) -> Result<u64, sqlx::Error> {
+        telegram_destination::delete_telegram_destinations(&self.pg_pool, user_id).await
+    }
+
+    async fn list_telegram_destinations_after(&self, after: &Uuid, limit: i64) -> Result<Vec<TelegramDestination>, sqlx::Error> {
+        telegram_destination::list_telegram_destinations_after(&self.pg_pool, after, limit).await
+    }
+}
+
+#[async_trait]
+impl ChatWebhookRepository for PgRepository {
+    async fn create_chat_webhook(&self, chat_webhook: &NewChatWebhook) -> Result<ChatWebhook, sqlx::Error> {
+        chat_webhook::create_chat_webhook(&self.pg_pool, chat_webhook).await
+    }
+
+    async fn get_chat_webhook(&self, id: &Uuid, user_id: &Uuid) -> Result<Option<ChatWebhook>, sqlx::Error> {
+        chat_webhook::get_chat_webhook(&self.pg_pool, id, user_id).await
+    }
+
+    async fn list_chat_webhooks(&self, user_id: &Uuid) -> Result<Vec<ChatWebhook>, sqlx::Error> {
+        chat_webhook::list_chat_webhooks(&self.pg_pool, user_id).await
+    }
+
+    async fn count_chat_webhooks(&self, user_id: &Uuid) -> Result<i64, sqlx::Error> {
+        chat_webhook::count_chat_webhooks(&self.pg_pool, user_id).await
+    }
+
+    async fn update_chat_webhook(
+        &self,
+        id: &Uuid,
+        user_id: &Uuid,
+        label: Option<&str>,
+        url: Option<&str>,
+        enabled: Option<bool>,
+        event_types: Option<&[String]>,
+    ) -> Result<Option<ChatWebhook>, sqlx::Error> {
+        chat_webhook::update_chat_webhook(&self.pg_pool, id, user_id, label, url, enabled, event_types).await
+    }
+
+    async fn set_chat_webhook_url(&self, id: &Uuid, url: &str) -> Result<(), sqlx::Error> {
+        chat_webhook::set_chat_webhook_url(&self.pg_pool, id, url).await
+    }
+
+    async fn delete_chat_webhook(&self, id: &Uuid, user_id: &Uuid) -> Result<bool, sqlx::Error> {
+        chat_webhook::delete_chat_webhook(&self.pg_pool, id, user_id).await
+    }
+
+    async fn list_chat_webhooks_after(&self, after: &Uuid, limit: i64) -> Result<Vec<ChatWebhook>, sqlx::Error> {
+        chat_webhook::list_chat_webhooks_after(&self.pg_pool, after, limit).await
+    }
+}
+
+#[async_trait]
+impl ApiKeyRepository for PgRepository {
+    async fn create_api_key(&self, user_id: &Uuid, hashed_api_key: &str) -> Result<ApiKey, sqlx::Error> {
+        api_key::create_api_key(&self.pg_pool, user_id, hashed_api_key).await
+    }
+
+    async fn get_api_key(&self, id: &Uuid, user_id: &Uuid) -> Result<Option<ApiKey>, sqlx::Error> {
+        api_key::get_api_key(&self.pg_pool, id, user_id).await
+    }
+
+    async fn get_api_key_by_api_key(&self, hashed_api_key: &str) -> Result<Option<ApiKey>, sqlx::Error> {
+        api_key::get_api_key_by_api_key(&self.pg_pool, hashed_api_key).await
+    }
+
+    async fn list_api_key(&self, user_id: &Uuid) -> Result<Vec<ApiKey>, sqlx::Error> {
+        api_key::list_api_key(&self.pg_pool, user_id).await
+    }
+
+    async fn update_api_key_last_used(&self, id: &Uuid) -> Result<(), sqlx::Error> {
+        api_key::update_api_key_last_used(&self.pg_pool, id).await
+    }
+
+    async fn count_api_keys(&self, user_id: &Uuid) -> Result<i64, sqlx::Error> {
+        api_key::count_api_keys_by_user_id(&self.pg_pool, user_id)
+            .await
+            .map(|count| count.unwrap_or(0))
+    }
+
+    async fn delete_api_key(&self, id: &Uuid, user_id: &Uuid) -> Result<bool, sqlx::Error> {
+        api_key::delete_api_key(&self.pg_pool, id, user_id).await
+    }
+}
+
+#[async_trait]
+impl CallbackUrlRepository for PgRepository {
+    async fn create_callback_url(&self, url: &str, user_id: &Uuid) -> Result<CallbackUrl, sqlx::Error> {
+        callback_url::create_callback_url(&self.pg_pool, url, user_id).await
+    }
+
+    async fn list_callback_urls(&self, user_id: &Uuid) -> Result<Vec<CallbackUrl>, sqlx::Error> {
+        callback_url::list_callback_urls_by_user_id(&self.pg_pool, user_id).await
+    }
+
+    async fn count_callback_urls(&self, user_id: &Uuid) -> Result<i64, sqlx::Error> {
+        callback_url::count_callback_urls_by_user_id(&self.pg_pool, user_id)
+            .await
+            .map(|count| count.unwrap_or(0))
+    }
+
+    async fn exists_callback_url(&self, url: &str, user_id: &Uuid) -> Result<bool, sqlx::Error> {
+        callback_url::exists_callback_url(&self.pg_pool, url, user_id).await
+    }
+
+    async fn delete_callback_url(&self, id: &Uuid, user_id: &Uuid) -> Result<bool, sqlx::Error> {
+        callback_url::delete_callback_url_by_id_and_user_id(&self.pg_pool, id, user_id).await
+    }
+}
+
+#[async_trait]
+impl WebhookRepository for PgRepository {
+    async fn create_webhook(
+        &self,
+        url: &str,
+        secret: &str,
+        user_id: &Uuid,
+        event_types: &[String],
+        api_version: &str,
+    ) -> Result<Webhook, sqlx::Error> {
+        webhook::create_webhook(&self.pg_pool, url, secret, user_id, event_types, api_version).await
+    }
+
+    async fn get_webhook(&self, id: &Uuid, user_id: &Uuid) -> Result<Option<Webhook>, sqlx::Error> {
+        webhook::get_webhook(&self.pg_pool, id, user_id).await
+    }
+
+    async fn list_webhooks(&self, user_id: &Uuid) -> Result<Vec<Webhook>, sqlx::Error> {
+        webhook::list_webhooks_by_user_id(&self.pg_pool, user_id).await
+    }
+
+    async fn list_webhooks_for_event(&self, user_id: &Uuid, event_type: &str) -> Result<Vec<Webhook>, sqlx::Error> {
+        webhook::list_webhooks_for_event(&self.pg_pool, user_id, event_type).await
+    }
+
+    async fn count_webhooks(&self, user_id: &Uuid) -> Result<i64, sqlx::Error> {
+        webhook::count_webhooks_by_user_id(&self.pg_pool, user_id)
+            .await
+            .map(|count| count.unwrap_or(0))
+    }
+
+    async fn update_webhook(
+        &self,
+        id: &Uuid,
+        user_id: &Uuid,
+        url: Option<&str>,
+        enabled: Option<bool>,
+        event_types: Option<&[String]>,
+    ) -> Result<Option<Webhook>, sqlx::Error> {
+        webhook::update_webhook(&self.pg_pool, id, user_id, url, enabled, event_types).await
+    }
+
+    async fn rotate_webhook_secret(&self, id: &Uuid, user_id: &Uuid, secret: &str, grace_secs: f64) -> Result<Option<Webhook>, sqlx::Error> {
+        webhook::rotate_webhook_secret(&self.pg_pool, id, user_id, secret, grace_secs).await
+    }
+
+    async fn delete_webhook(&self, id: &Uuid, user_id: &Uuid) -> Result<bool, sqlx::Error> {
+        webhook::delete_webhook_by_id_and_user_id(&self.pg_pool, id, user_id).await
+    }
+
+    async fn list_webhooks_after(&self, after: &Uuid, limit: i64) -> Result<Vec<Webhook>, sqlx::Error> {
+        webhook::list_webhooks_after(&self.pg_pool, after, limit).await
+    }
+
+    async fn set_webhook_secrets(&self, id: &Uuid, secret: &str, previous_secret: Option<&str>) -> Result<(), sqlx::Error> {
+        webhook::set_webhook_secrets(&self.pg_pool, id, secret, previous_secret).await
+    }
+}
+
+#[async_trait]
+impl WebhookDeliveryRepository for PgRepository {
+    async fn enqueue_webhook_delivery(&self, webhook_id: &Uuid, payload: &Value) -> Result<WebhookDelivery, sqlx::Error> {
+        webhook_delivery::enqueue_delivery(&self.pg_pool, webhook_id, payload).await
+    }
+
+    async fn claim_due_webhook_deliveries(&self, limit: i64, lease_secs: f64) -> Result<Vec<DueWebhookDelivery>, sqlx::Error> {
+        webhook_delivery::claim_due_deliveries(&self.pg_pool, limit, lease_secs).await
+    }
+
+    async fn record_webhook_delivery_attempt(
+        &self,
+        delivery_id: &Uuid,
+        attempt: &NewWebhookDeliveryAttempt,
+        status: WebhookDeliveryStatus,
+        retry_in_secs: f64,
+    ) -> Result<(), sqlx::Error> {
+        webhook_delivery::record_attempt(&self.pg_pool, delivery_id, attempt, status, retry_in_secs).await
+    }
+
+    async fn list_webhook_deliveries(&self, webhook_id: &Uuid, limit: i64, offset: i64) -> Result<Vec<WebhookDelivery>, sqlx::Error> {
+        webhook_delivery::list_deliveries(&self.pg_pool, webhook_id, limit, offset).await
+    }
+
+    async fn list_webhook_delivery_attempts(&self, delivery_ids: &[Uuid]) -> Result<Vec<WebhookDeliveryAttempt>, sqlx::Error> {
+        webhook_delivery::list_attempts(&self.pg_pool, delivery_ids).await
+    }
+
+    async fn redeliver_webhook_delivery(&self, id: &Uuid, webhook_id: &Uuid) -> Result<bool, sqlx::Error> {
+        webhook_delivery::redeliver(&self.pg_pool, id, webhook_id).await
+    }
+}
+
+#[async_trait]
+impl PaymentRepository for PgRepository {
+    async fn create_payment(&self, id: &Uuid, user_id: Option<Uuid>, data: &Value) -> Result<Payment, sqlx::Error> {
+        billing::create_payment(&self.pg_pool, id, user_id, data).await
+    }
+
+    async fn get_payment(&self, id: &Uuid) -> Result<Option<Payment>, sqlx::Error> {
+        billing::get_payment(&self.pg_pool, id).await
+    }
+
+    async fn user_list_payment(&self, user_id: &Uuid, limit: i64, offset: i64) -> Result<Vec<Payment>, sqlx::Error> {
+        billing::user_list_payment(&self.pg_pool, user_id, limit, offset).await
+    }
+
+    async fn list_payment(&self, payment_type: &str, limit: i64, offset: i64) -> Result<Vec<Payment>, sqlx::Error> {
+        billing::list_payment(&self.pg_pool, payment_type, limit, offset).await
+    }
+
+    async fn set_payment_paid(&self, id: &Uuid) -> Result<(), sqlx::Error> {
+        billing::set_payment_paid(&self.pg_pool, id).await
+    }
+
+    async fn sync_payment_paid_at(&self, id: &Uuid, paid_at: &NaiveDateTime) -> Result<(), sqlx::Error> {
+        billing::sync_payment_paid_at(&self.pg_pool, id, paid_at).await
+    }
+}
+
+#[async_trait]
+impl SubscriptionRepository for PgRepository {
+    async fn list_user_subscriptions(&self, user_id: &Uuid) -> Result<Vec<Subscription>, sqlx::Error> {
+        billing::list_subscriptions(&self.pg_pool, user_id).await
+    }
+
+    async fn get_user_active_subscription(&self, user_id: &Uuid, target: &str) -> Result<Option<Subscription>, sqlx::Error> {
+        billing::get_active_subscription(&self.pg_pool, user_id, target).await
+    }
+
+    async fn create_or_update_subscription(&self, user_id: &Uuid, target: &str, data: Option<Value>, until: NaiveDateTime) -> Result<(), sqlx::Error> {
+        billing::create_or_update_subscription(&self.pg_pool, user_id, target, data, until).await
+    }
+
+    async fn claim_expiring_subscriptions(&self, now: &NaiveDateTime, before: &NaiveDateTime) -> Result<Vec<Subscription>, sqlx::Error> {
+        billing::claim_expiring_subscriptions(&self.pg_pool, now, before).await
+    }
+
+    async fn release_subscription_expiry(&self, user_id: &Uuid, target: &str, until: &NaiveDateTime) -> Result<(), sqlx::Error> {
+        billing::release_subscription_expiry(&self.pg_pool, user_id, target, until).await
+    }
+}
+
+#[async_trait]
+impl BlockchainRepository for PgRepository {
+    async fn get_block_number(&self, network: &str) -> Result<Option<i64>, sqlx::Error> {
+        blockchain::get_block_number(&self.pg_pool, network).await
+    }
+
+    async fn set_block_number(&self, network: &str, block_number: i64) -> Result<(), sqlx::Error> {
+        blockchain::create_or_update_block_number(&self.pg_pool, network, block_number).await
+    }
+}
+
+#[async_trait]
+impl AnalyticsRepository for PgRepository {
+    async fn invoice_stats_by_day(&self, user_id: &Uuid, since: NaiveDateTime) -> Result<Vec<InvoicePeriodStats>, sqlx::Error> {
+        analytics::invoice_stats_by_day(&self.pg_pool, user_id, since).await
+    }
+
+    async fn invoice_summary(&self, user_id: &Uuid, since: NaiveDateTime) -> Result<InvoiceSummary, sqlx::Error> {
+        analytics::invoice_summary(&self.pg_pool, user_id, since).await
+    }
+}
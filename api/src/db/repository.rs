@@ -0,0 +1,433 @@
+//! Storage interface, one trait per domain. [`super::postgres::PgRepository`] is the
+//! production implementation; tests can use [`super::memory::MemoryRepository`] instead.
+
+use async_trait::async_trait;
+use bigdecimal::BigDecimal;
+use serde_json::Value;
+use sqlx::migrate::MigrateError;
+use sqlx::types::{chrono::NaiveDateTime, Uuid};
+use super::analytics::{InvoicePeriodStats, InvoiceSummary};
+use super::billing::{Payment, Subscription};
+use super::invoice::{InvoiceOptions, InvoicePayment, InvoiceStatus, InvoiceUpdate};
+use super::invoice_event::InvoiceEvent;
+use super::notification_digest::{NewNotificationDigestItem, NotificationDigest, NotificationDigestItem};
+use super::notification_log::{NewNotificationLog, NotificationLog, NotificationLogFilter};
+use super::notification_preference::NotificationPreference;
+use super::notification_template::NotificationTemplate;
+use super::chat_webhook::{ChatWebhook, NewChatWebhook};
+#[cfg(feature = "telegram")]
+use super::telegram_destination::NewTelegramDestination;
+use super::telegram_destination::TelegramDestination;
+use super::webhook_delivery::{DueWebhookDelivery, NewWebhookDeliveryAttempt, WebhookDelivery, WebhookDeliveryAttempt, WebhookDeliveryStatus};
+use super::{ApiKey, CallbackUrl, Invoice, User, Webhook};
+
+#[async_trait]
+pub trait Store {
+    async fn run_migrations(&self) -> Result<(), MigrateError>;
+
+    async fn health_check(&self) -> Result<(), sqlx::Error>;
+
+    async fn close(&self);
+}
+
+#[async_trait]
+pub trait InvoiceRepository {
+    /// Public invoices, plus the private ones owned by `user_id` when given.
+    async fn list_invoices(&self, limit: i64, offset: i64, user_id: Option<Uuid>) -> Result<Vec<Invoice>, sqlx::Error>;
+
+    async fn user_own_invoices(&self, limit: i64, offset: i64, user_id: &Uuid) -> Result<Vec<Invoice>, sqlx::Error>;
+
+    async fn create_invoice(
+        &self,
+        amount: BigDecimal,
+        seller: &str,
+        networks: &[i32],
+        user_id: Option<Uuid>,
+        external_id: Option<String>,
+        options: InvoiceOptions,
+    ) -> Result<Invoice, sqlx::Error>;
+
+    async fn get_invoice(&self, id: &Uuid) -> Result<Option<Invoice>, sqlx::Error>;
+
+    async fn get_is_owner(&self, id: &Uuid, user_id: &Uuid) -> Result<bool, sqlx::Error>;
+
+    /// Fails with [`sqlx::Error::RowNotFound`] unless id, seller and amount all match
+    /// and the invoice is still open or confirming.
+    async fn set_invoice_paid(&self, id: Uuid, payment: &InvoicePayment) -> Result<Invoice, sqlx::Error>;
+
+    /// `None` when the invoice is missing or its status is not one of `from`.
+    async fn set_invoice_status(&self, id: &Uuid, from: &[InvoiceStatus], to: InvoiceStatus) -> Result<Option<Invoice>, sqlx::Error>;
+
+    /// Open invoices past `expires_at`, and confirming ones that also started confirming
+    /// before `confirming_before`, become expired.
+    async fn expire_invoices(&self, now: NaiveDateTime, confirming_before: NaiveDateTime) -> Result<Vec<Invoice>, sqlx::Error>;
+
+    /// Confirming invoices that started before `confirming_before` and have not expired become open again.
+    async fn reopen_stale_invoices(&self, now: NaiveDateTime, confirming_before: NaiveDateTime) -> Result<Vec<Invoice>, sqlx::Error>;
+
+    /// Records `changes` as an `updated` timeline entry. `None` when the invoice is missing or not open.
+    async fn update_invoice(&self, id: &Uuid, update: &InvoiceUpdate, changes: Value) -> Result<Option<Invoice>, sqlx::Error>;
+
+    /// The invoice's timeline, oldest first.
+    async fn list_invoice_events(&self, invoice_id: &Uuid) -> Result<Vec<InvoiceEvent>, sqlx::Error>;
+
+    /// Only while the invoice is open or confirming, and over an email already set only if
+    /// `replace`; `None` otherwise, or when the invoice is missing.
+    async fn set_invoice_buyer_email(&self, id: &Uuid, buyer_email: &str, replace: bool) -> Result<Option<Invoice>, sqlx::Error>;
+
+    /// Unconditional, for re-encrypting the column.
+    async fn rewrite_invoice_buyer_email(&self, id: &Uuid, buyer_email: &str) -> Result<(), sqlx::Error>;
+
+    /// Up to `limit` invoices with a buyer email and ids greater than `after`, ordered by id.
+    async fn list_invoices_with_buyer_email_after(&self, after: &Uuid, limit: i64) -> Result<Vec<Invoice>, sqlx::Error>;
+}
+
+#[async_trait]
+pub trait UserRepository {
+    async fn get_user_by_id(&self, id: &Uuid) -> Result<User, sqlx::Error>;
+
+    /// Existing users are returned as stored; a changed email is saved with [`UserRepository::set_user_email`].
+    async fn get_or_create_user(&self, firebase_user_id: &str, email: Option<String>) -> Result<User, sqlx::Error>;
+
+    async fn set_user_email(&self, user_id: &Uuid, email: Option<String>) -> Result<(), sqlx::Error>;
+
+    /// Up to `limit` users with ids greater than `after`, ordered by id.
+    async fn list_users_after(&self, after: &Uuid, limit: i64) -> Result<Vec<User>, sqlx::Error>;
+
+    async fn update_user(
+        &self,
+        user_id: &Uuid,
+        email_notification: Option<bool>,
+        telegram_notification: Option<bool>,
+    ) -> Result<User, sqlx::Error>;
+
+    async fn set_user_locale(&self, user_id: &Uuid, locale: &str) -> Result<(), sqlx::Error>;
+
+    async fn set_user_buyer_receipts(&self, user_id: &Uuid, buyer_receipts: bool) -> Result<(), sqlx::Error>;
+}
+
+/// Sellers' overrides of the built-in notification templates.
+#[async_trait]
+pub trait NotificationTemplateRepository {
+    async fn list_notification_templates(&self, user_id: &Uuid) -> Result<Vec<NotificationTemplate>, sqlx::Error>;
+
+    async fn get_notification_template(&self, user_id: &Uuid, kind: &str, locale: &str) -> Result<Option<NotificationTemplate>, sqlx::Error>;
+
+    /// Creates or replaces the override for the template's user, kind and locale.
+    async fn upsert_notification_template(&self, template: &NotificationTemplate) -> Result<NotificationTemplate, sqlx::Error>;
+
+    async fn delete_notification_template(&self, user_id: &Uuid, kind: &str, locale: &str) -> Result<bool, sqlx::Error>;
+}
+
+/// Sellers' per channel and event notification choices.
+#[async_trait]
+pub trait NotificationPreferenceRepository {
+    async fn list_notification_preferences(&self, user_id: &Uuid) -> Result<Vec<NotificationPreference>, sqlx::Error>;
+
+    /// Creates or replaces the preference for its user, channel and event type.
+    async fn upsert_notification_preference(&self, preference: &NotificationPreference) -> Result<NotificationPreference, sqlx::Error>;
+
+    async fn delete_notification_preference(&self, user_id: &Uuid, channel: &str, event_type: &str) -> Result<bool, sqlx::Error>;
+}
+
+/// Digest schedules per channel and the notifications queued for them.
+#[async_trait]
+pub trait NotificationDigestRepository {
+    async fn list_notification_digests(&self, user_id: &Uuid) -> Result<Vec<NotificationDigest>, sqlx::Error>;
+
+    async fn upsert_notification_digest(&self, user_id: &Uuid, channel: &str, frequency: &str) -> Result<NotificationDigest, sqlx::Error>;
+
+    async fn list_pending_notification_digests(&self) -> Result<Vec<NotificationDigest>, sqlx::Error>;
+
+    /// Whether this call moved `last_sent_at` to `period_end`.
+    async fn claim_notification_digest(&self, user_id: &Uuid, channel: &str, period_end: &NaiveDateTime) -> Result<bool, sqlx::Error>;
+
+    async fn create_notification_digest_item(&self, item: &NewNotificationDigestItem) -> Result<NotificationDigestItem, sqlx::Error>;
+
+    /// The items queued before `before`, oldest first; they stay queued until deleted.
+    async fn list_notification_digest_items(&self, user_id: &Uuid, channel: &str, before: &NaiveDateTime) -> Result<Vec<NotificationDigestItem>, sqlx::Error>;
+
+    async fn delete_notification_digest_items(&self, ids: &[Uuid]) -> Result<(), sqlx::Error>;
+}
+
+/// Every email, Telegram and chat message sent to a seller, and resends of failed ones.
+#[async_trait]
+pub trait NotificationLogRepository {
+    async fn create_notification_log(&self, log: &NewNotificationLog) -> Result<NotificationLog, sqlx::Error>;
+
+    async fn list_notification_logs(&self, user_id: &Uuid, filter: &NotificationLogFilter, limit: i64, offset: i64) -> Result<Vec<NotificationLog>, sqlx::Error>;
+
+    async fn get_notification_log(&self, id: &Uuid, user_id: &Uuid) -> Result<Option<NotificationLog>, sqlx::Error>;
+
+    /// Whether a failed message was queued to be sent again.
+    async fn resend_notification(&self, id: &Uuid, user_id: &Uuid) -> Result<bool, sqlx::Error>;
+
+    async fn claim_pending_notifications(&self, limit: i64, lease_secs: f64) -> Result<Vec<NotificationLog>, sqlx::Error>;
+
+    async fn record_notification_attempt(&self, id: &Uuid, error: Option<&str>) -> Result<(), sqlx::Error>;
+}
+
+/// Chats that receive a seller's Telegram notifications. Chat ids arrive encrypted.
+#[async_trait]
+pub trait TelegramDestinationRepository {
+    /// Replaces the destination with the same chat lookup, if any.
+    #[cfg(feature = "telegram")]
+    async fn upsert_telegram_destination(&self, destination: &NewTelegramDestination) -> Result<TelegramDestination, sqlx::Error>;
+
+    /// Oldest first.
+    async fn list_telegram_destinations(&self, user_id: &Uuid) -> Result<Vec<TelegramDestination>, sqlx::Error>;
+
+    /// The destination whose `chat_lookup` is any of `lookups`.
+    #[cfg(feature = "telegram")]
+    async fn get_telegram_destination_by_chat_lookup(&self, lookups: &[String]) -> Result<Option<TelegramDestination>, sqlx::Error>;
+
+    /// Changes only the fields that are `Some`; `min_amount: Some(None)` removes the threshold.
+    #[cfg(feature = "telegram")]
+    async fn update_telegram_destination(
+        &self,
+        id: &Uuid,
+        user_id: &Uuid,
+        label: Option<&str>,
+        event_types: Option<&[String]>,
+        min_amount: Option<Option<BigDecimal>>,
+    ) -> Result<Option<TelegramDestination>, sqlx::Error>;
+
+    async fn set_telegram_destination_chat(
+        &self,
+        id: &Uuid,
+        chat_id: &str,
+        chat_lookup: Option<&str>,
+        chat_type: &str,
+    ) -> Result<(), sqlx::Error>;
+
+    #[cfg(feature = "telegram")]
+    async fn delete_telegram_destination(&self, id: &Uuid, user_id: &Uuid) -> Result<bool, sqlx::Error>;
+
+    #[cfg(feature = "telegram")]
+    async fn delete_telegram_destinations(&self, user_id: &Uuid) -> Result<u64, sqlx::Error>;
+
+    /// Up to `limit` destinations of any user with ids greater than `after`, ordered by id.
+    async fn list_telegram_destinations_after(&self, after: &Uuid, limit: i64) -> Result<Vec<TelegramDestination>, sqlx::Error>;
+}
+
+/// Slack, Discord and generic chat webhooks. URLs arrive encrypted.
+#[async_trait]
+pub trait ChatWebhookRepository {
+    async fn create_chat_webhook(&self, chat_webhook: &NewChatWebhook) -> Result<ChatWebhook, sqlx::Error>;
+
+    async fn get_chat_webhook(&self, id: &Uuid, user_id: &Uuid) -> Result<Option<ChatWebhook>, sqlx::Error>;
+
+    /// Oldest first.
+    async fn list_chat_webhooks(&self, user_id: &Uuid) -> Result<Vec<ChatWebhook>, sqlx::Error>;
+
+    async fn count_chat_webhooks(&self, user_id: &Uuid) -> Result<i64, sqlx::Error>;
+
+    /// Changes only the fields that are `Some`.
+    async fn update_chat_webhook(
+        &self,
+        id: &Uuid,
+        user_id: &Uuid,
+        label: Option<&str>,
+        url: Option<&str>,
+        enabled: Option<bool>,
+        event_types: Option<&[String]>,
+    ) -> Result<Option<ChatWebhook>, sqlx::Error>;
+
+    async fn set_chat_webhook_url(&self, id: &Uuid, url: &str) -> Result<(), sqlx::Error>;
+
+    async fn delete_chat_webhook(&self, id: &Uuid, user_id: &Uuid) -> Result<bool, sqlx::Error>;
+
+    /// Up to `limit` chat webhooks of any user with ids greater than `after`, ordered by id.
+    async fn list_chat_webhooks_after(&self, after: &Uuid, limit: i64) -> Result<Vec<ChatWebhook>, sqlx::Error>;
+}
+
+#[async_trait]
+pub trait ApiKeyRepository {
+    async fn create_api_key(&self, user_id: &Uuid, hashed_api_key: &str) -> Result<ApiKey, sqlx::Error>;
+
+    async fn get_api_key(&self, id: &Uuid, user_id: &Uuid) -> Result<Option<ApiKey>, sqlx::Error>;
+
+    async fn get_api_key_by_api_key(&self, hashed_api_key: &str) -> Result<Option<ApiKey>, sqlx::Error>;
+
+    async fn list_api_key(&self, user_id: &Uuid) -> Result<Vec<ApiKey>, sqlx::Error>;
+
+    async fn update_api_key_last_used(&self, id: &Uuid) -> Result<(), sqlx::Error>;
+
+    async fn count_api_keys(&self, user_id: &Uuid) -> Result<i64, sqlx::Error>;
+
+    async fn delete_api_key(&self, id: &Uuid, user_id: &Uuid) -> Result<bool, sqlx::Error>;
+}
+
+#[async_trait]
+pub trait CallbackUrlRepository {
+    async fn create_callback_url(&self, url: &str, user_id: &Uuid) -> Result<CallbackUrl, sqlx::Error>;
+
+    async fn list_callback_urls(&self, user_id: &Uuid) -> Result<Vec<CallbackUrl>, sqlx::Error>;
+
+    async fn count_callback_urls(&self, user_id: &Uuid) -> Result<i64, sqlx::Error>;
+
+    /// `true` when the user has no callback urls registered at all (any url is allowed)
+    /// or when `url` is one of them.
+    async fn exists_callback_url(&self, url: &str, user_id: &Uuid) -> Result<bool, sqlx::Error>;
+
+    async fn delete_callback_url(&self, id: &Uuid, user_id: &Uuid) -> Result<bool, sqlx::Error>;
+}
+
+#[async_trait]
+pub trait WebhookRepository {
+    async fn create_webhook(
+        &self,
+        url: &str,
+        secret: &str,
+        user_id: &Uuid,
+        event_types: &[String],
+        api_version: &str,
+    ) -> Result<Webhook, sqlx::Error>;
+
+    async fn get_webhook(&self, id: &Uuid, user_id: &Uuid) -> Result<Option<Webhook>, sqlx::Error>;
+
+    async fn list_webhooks(&self, user_id: &Uuid) -> Result<Vec<Webhook>, sqlx::Error>;
+
+    /// Only the enabled webhooks whose `event_types` include `event_type`.
+    async fn list_webhooks_for_event(&self, user_id: &Uuid, event_type: &str) -> Result<Vec<Webhook>, sqlx::Error>;
+
+    async fn count_webhooks(&self, user_id: &Uuid) -> Result<i64, sqlx::Error>;
+
+    /// Changes only the fields that are `Some`.
+    async fn update_webhook(
+        &self,
+        id: &Uuid,
+        user_id: &Uuid,
+        url: Option<&str>,
+        enabled: Option<bool>,
+        event_types: Option<&[String]>,
+    ) -> Result<Option<Webhook>, sqlx::Error>;
+
+    /// Sets a new secret and keeps the old one for `grace_secs` (dropped right away when zero).
+    async fn rotate_webhook_secret(&self, id: &Uuid, user_id: &Uuid, secret: &str, grace_secs: f64) -> Result<Option<Webhook>, sqlx::Error>;
+
+    async fn delete_webhook(&self, id: &Uuid, user_id: &Uuid) -> Result<bool, sqlx::Error>;
+
+    /// Up to `limit` webhooks of any user with ids greater than `after`, ordered by id.
+    async fn list_webhooks_after(&self, after: &Uuid, limit: i64) -> Result<Vec<Webhook>, sqlx::Error>;
+
+    /// Stores the secrets as given; the grace period is left as is.
+    async fn set_webhook_secrets(&self, id: &Uuid, secret: &str, previous_secret: Option<&str>) -> Result<(), sqlx::Error>;
+}
+
+/// Outbox of webhook deliveries and the log of their attempts.
+#[async_trait]
+pub trait WebhookDeliveryRepository {
+    async fn enqueue_webhook_delivery(&self, webhook_id: &Uuid, payload: &Value) -> Result<WebhookDelivery, sqlx::Error>;
+
+    /// Due pending deliveries of enabled webhooks, hidden from other claims for `lease_secs`.
+    async fn claim_due_webhook_deliveries(&self, limit: i64, lease_secs: f64) -> Result<Vec<DueWebhookDelivery>, sqlx::Error>;
+
+    /// Logs the attempt, bumps the attempt counter and schedules the next try `retry_in_secs` from now.
+    async fn record_webhook_delivery_attempt(
+        &self,
+        delivery_id: &Uuid,
+        attempt: &NewWebhookDeliveryAttempt,
+        status: WebhookDeliveryStatus,
+        retry_in_secs: f64,
+    ) -> Result<(), sqlx::Error>;
+
+    async fn list_webhook_deliveries(&self, webhook_id: &Uuid, limit: i64, offset: i64) -> Result<Vec<WebhookDelivery>, sqlx::Error>;
+
+    async fn list_webhook_delivery_attempts(&self, delivery_ids: &[Uuid]) -> Result<Vec<WebhookDeliveryAttempt>, sqlx::Error>;
+
+    async fn redeliver_webhook_delivery(&self, id: &Uuid, webhook_id: &Uuid) -> Result<bool, sqlx::Error>;
+}
+
+#[async_trait]
+pub trait PaymentRepository {
+    async fn create_payment(&self, id: &Uuid, user_id: Option<Uuid>, data: &Value) -> Result<Payment, sqlx::Error>;
+
+    async fn get_payment(&self, id: &Uuid) -> Result<Option<Payment>, sqlx::Error>;
+
+    async fn user_list_payment(&self, user_id: &Uuid, limit: i64, offset: i64) -> Result<Vec<Payment>, sqlx::Error>;
+
+    /// Paid payments whose data has a top-level `payment_type` key.
+    async fn list_payment(&self, payment_type: &str, limit: i64, offset: i64) -> Result<Vec<Payment>, sqlx::Error>;
+
+    async fn set_payment_paid(&self, id: &Uuid) -> Result<(), sqlx::Error>;
+
+    async fn sync_payment_paid_at(&self, id: &Uuid, paid_at: &NaiveDateTime) -> Result<(), sqlx::Error>;
+}
+
+#[async_trait]
+pub trait SubscriptionRepository {
+    async fn list_user_subscriptions(&self, user_id: &Uuid) -> Result<Vec<Subscription>, sqlx::Error>;
+
+    async fn get_user_active_subscription(&self, user_id: &Uuid, target: &str) -> Result<Option<Subscription>, sqlx::Error>;
+
+    async fn create_or_update_subscription(&self, user_id: &Uuid, target: &str, data: Option<Value>, until: NaiveDateTime) -> Result<(), sqlx::Error>;
+
+    /// Subscriptions ending within `(now, before]` not yet notified for their `until`, marked
+    /// as notified.
+    async fn claim_expiring_subscriptions(&self, now: &NaiveDateTime, before: &NaiveDateTime) -> Result<Vec<Subscription>, sqlx::Error>;
+
+    /// Lets the expiry of `until` be claimed again.
+    async fn release_subscription_expiry(&self, user_id: &Uuid, target: &str, until: &NaiveDateTime) -> Result<(), sqlx::Error>;
+}
+
+/// Last processed block per network, used by the monitor to resume.
+#[async_trait]
+pub trait BlockchainRepository {
+    async fn get_block_number(&self, network: &str) -> Result<Option<i64>, sqlx::Error>;
+
+    async fn set_block_number(&self, network: &str, block_number: i64) -> Result<(), sqlx::Error>;
+}
+
+#[async_trait]
+pub trait AnalyticsRepository {
+    async fn invoice_stats_by_day(&self, user_id: &Uuid, since: NaiveDateTime) -> Result<Vec<InvoicePeriodStats>, sqlx::Error>;
+
+    async fn invoice_summary(&self, user_id: &Uuid, since: NaiveDateTime) -> Result<InvoiceSummary, sqlx::Error>;
+}
+
+/// Everything [`crate::api::state::DB`] needs from a backend.
+pub trait Repository:
+    Store
+    + InvoiceRepository
+    + UserRepository
+    + NotificationTemplateRepository
+    + NotificationPreferenceRepository
+    + NotificationDigestRepository
+    + NotificationLogRepository
+    + TelegramDestinationRepository
+    + ChatWebhookRepository
+    + ApiKeyRepository
+    + CallbackUrlRepository
+    + WebhookRepository
+    + WebhookDeliveryRepository
+    + PaymentRepository
+    + SubscriptionRepository
+    + BlockchainRepository
+    + AnalyticsRepository
+    + Send
+    + Sync
+{}
+
+impl<T> Repository for T
+where
+    T: Store
+        + InvoiceRepository
+        + UserRepository
+        + NotificationTemplateRepository
+        + NotificationPreferenceRepository
+        + NotificationDigestRepository
+        + NotificationLogRepository
+        + TelegramDestinationRepository
+        + ChatWebhookRepository
+        + ApiKeyRepository
+        + CallbackUrlRepository
+        + WebhookRepository
+        + WebhookDeliveryRepository
+        + PaymentRepository
+        + SubscriptionRepository
+        + BlockchainRepository
+        + AnalyticsRepository
+        + Send
+        + Sync,
+{}
@@ -0,0 +1,248 @@
+use bigdecimal::BigDecimal;
+use sqlx::PgPool;
+use sqlx::types::Uuid;
+use sqlx::types::chrono::NaiveDateTime;
+
+/// A chat, group or channel that receives a seller's Telegram notifications.
+#[derive(Clone, Debug, sqlx::FromRow)]
+#[cfg_attr(not(feature = "telegram"), allow(dead_code))]
+pub struct TelegramDestination {
+    pub id: Uuid,
+    pub user_id: Uuid,
+    pub label: String,
+    pub chat_id: String,
+    /// [`super::encryption::Keyring::lookup_hash`] of the chat id.
+    pub chat_lookup: Option<String>,
+    /// `private`, `group`, `supergroup` or `channel`.
+    pub chat_type: String,
+    /// Catalog names, e.g. `invoice.paid`, see [`crate::events::webhook_event::WebhookEventType`].
+    pub event_types: Vec<String>,
+    /// Invoices below it are not notified here.
+    pub min_amount: Option<BigDecimal>,
+    pub created_at: NaiveDateTime,
+}
+
+impl TelegramDestination {
+    #[cfg(feature = "telegram")]
+    pub fn is_private(&self) -> bool {
+        self.chat_type == "private"
+    }
+
+    /// Whether a notification about `event_type` for an invoice of `amount` goes here.
+    #[cfg(feature = "telegram")]
+    pub fn accepts(&self, event_type: &str, amount: &BigDecimal) -> bool {
+        self.event_types.iter().any(|name| name == event_type)
+            && self.min_amount.as_ref().is_none_or(|min_amount| amount >= min_amount)
+    }
+}
+
+#[cfg(feature = "telegram")]
+pub struct NewTelegramDestination {
+    pub user_id: Uuid,
+    pub label: String,
+    pub chat_id: String,
+    pub chat_lookup: Option<String>,
+    pub chat_type: String,
+    pub event_types: Vec<String>,
+}
+
+/// A chat already linked under the same lookup is handed over as a new destination.
+#[cfg(feature = "telegram")]
+pub async fn upsert_telegram_destination(
+    pg_pool: &PgPool,
+    destination: &NewTelegramDestination,
+) -> Result<TelegramDestination, sqlx::Error> {
+    sqlx::query_as!(
+        TelegramDestination,
+        r#"
+        INSERT INTO telegram_destination (user_id, label, chat_id, chat_lookup, chat_type, event_types)
+        VALUES ($1, $2, $3, $4, $5, $6)
+        ON CONFLICT (chat_lookup) DO UPDATE SET
+            id = gen_random_uuid(),
+            user_id = EXCLUDED.user_id,
+            label = EXCLUDED.label,
+            chat_id = EXCLUDED.chat_id,
+            chat_type = EXCLUDED.chat_type,
+            event_types = EXCLUDED.event_types,
+            min_amount = NULL,
+            created_at = CURRENT_TIMESTAMP
+        RETURNING *
+        "#,
+        destination.user_id,
+        destination.label,
+        destination.chat_id,
+        destination.chat_lookup,
+        destination.chat_type,
+        &destination.event_types,
+    )
+        .fetch_one(pg_pool)
+        .await
+}
+
+pub async fn list_telegram_destinations(pg_pool: &PgPool, user_id: &Uuid) -> Result<Vec<TelegramDestination>, sqlx::Error> {
+    sqlx::query_as!(
+        TelegramDestination,
+        r#"
+        SELECT * FROM telegram_destination
+        WHERE user_id = $1
+        ORDER BY created_at
+        "#,
+        user_id,
+    )
+        .fetch_all(pg_pool)
+        .await
+}
+
+#[cfg(feature = "telegram")]
+pub async fn get_telegram_destination_by_chat_lookup(
+    pg_pool: &PgPool,
+    lookups: &[String],
+) -> Result<Option<TelegramDestination>, sqlx::Error> {
+    sqlx::query_as!(
+        TelegramDestination,
+        r#"
+        SELECT * FROM telegram_destination
+        WHERE chat_lookup = ANY($1)
+        LIMIT 1
+        "#,
+        lookups,
+    )
+        .fetch_optional(pg_pool)
+        .await
+}
+
+/// Changes only the fields that are `Some`; `min_amount: Some(None)` removes the threshold.
+/// `None` when the destination does not exist.
+#[cfg(feature = "telegram")]
+pub async fn update_telegram_destination(
+    pg_pool: &PgPool,
+    id: &Uuid,
+    user_id: &Uuid,
+    label: Option<&str>,
+    event_types: Option<&[String]>,
+    min_amount: Option<Option<BigDecimal>>,
+) -> Result<Option<TelegramDestination>, sqlx::Error> {
+    sqlx::query_as!(
+        TelegramDestination,
+        r#"
+        UPDATE telegram_destination
+        SET label = COALESCE($3, label),
+            event_types = COALESCE($4, event_types),
+            min_amount = CASE WHEN $5 THEN $6 ELSE min_amount END
+        WHERE id = $1 AND user_id = $2
+        RETURNING *
+        "#,
+        id,
+        user_id,
+        label,
+        event_types,
+        min_amount.is_some(),
+        min_amount.flatten(),
+    )
+        .fetch_optional(pg_pool)
+        .await
+}
+
+/// Points the destination at another chat, e.g. when a group becomes a supergroup.
+pub async fn set_telegram_destination_chat(
+    pg_pool: &PgPool,
+    id: &Uuid,
+    chat_id: &str,
+    chat_lookup: Option<&str>,
+    chat_type: &str,
+) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        r#"
+        UPDATE telegram_destination
+        SET chat_id = $2, chat_lookup = $3, chat_type = $4
+        WHERE id = $1
+        "#,
+        id,
+        chat_id,
+        chat_lookup,
+        chat_type,
+    )
+        .execute(pg_pool)
+        .await?;
+
+    Ok(())
+}
+
+#[cfg(feature = "telegram")]
+pub async fn delete_telegram_destination(pg_pool: &PgPool, id: &Uuid, user_id: &Uuid) -> Result<bool, sqlx::Error> {
+    let result = sqlx::query!(
+        r#"
+        DELETE FROM telegram_destination
+        WHERE id = $1 AND user_id = $2
+        "#,
+        id,
+        user_id,
+    )
+        .execute(pg_pool)
+        .await?;
+
+    Ok(result.rows_affected() > 0)
+}
+
+#[cfg(feature = "telegram")]
+pub async fn delete_telegram_destinations(pg_pool: &PgPool, user_id: &Uuid) -> Result<u64, sqlx::Error> {
+    let result = sqlx::query!(
+        r#"
+        DELETE FROM telegram_destination
+        WHERE user_id = $1
+        "#,
+        user_id,
+    )
+        .execute(pg_pool)
+        .await?;
+
+    Ok(result.rows_affected())
+}
+
+/// Destinations of every user ordered by id, starting after `after`; for walking the whole table in batches.
+pub async fn list_telegram_destinations_after(
+    pg_pool: &PgPool,
+    after: &Uuid,
+    limit: i64,
+) -> Result<Vec<TelegramDestination>, sqlx::Error> {
+    sqlx::query_as!(
+        TelegramDestination,
+        r#"
+        SELECT * FROM telegram_destination
+        WHERE id > $1
+        ORDER BY id
+        LIMIT $2
+        "#,
+        after,
+        limit,
+    )
+        .fetch_all(pg_pool)
+        .await
+}
+
+#[cfg(all(test, feature = "telegram"))]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    #[rstest]
+    #[case("invoice.paid", "10", None, true)]
+    #[case("invoice.created", "10", None, false)]
+    #[case("invoice.paid", "10", Some("10"), true)]
+    #[case("invoice.paid", "9.99", Some("10"), false)]
+    fn test_accepts(#[case] event_type: &str, #[case] amount: &str, #[case] min_amount: Option<&str>, #[case] expected: bool) {
+        let destination = TelegramDestination {
+            id: Uuid::nil(),
+            user_id: Uuid::nil(),
+            label: "Team".to_string(),
+            chat_id: "-100".to_string(),
+            chat_lookup: None,
+            chat_type: "supergroup".to_string(),
+            event_types: vec!["invoice.paid".to_string(), "payment.anomaly".to_string()],
+            min_amount: min_amount.map(|amount| amount.parse().unwrap()),
+            created_at: NaiveDateTime::default(),
+        };
+
+        assert_eq!(destination.accepts(event_type, &amount.parse().unwrap()), expected);
+    }
+}
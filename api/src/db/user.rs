@@ -20,10 +20,9 @@ pub async fn get_or_create_user(db: &PgPool, firebase_user_id: &str, email: Opti
         User,
         r#"
         SELECT * FROM "users"
-        WHERE firebase_user_id = $1 AND email = $2
+        WHERE firebase_user_id = $1
         "#,
         firebase_user_id,
-        email
     )
         .fetch_optional(db)
         .await?;
@@ -108,15 +107,63 @@ pub async fn update_user(
     get_user_by_id(db, user_id).await
 }
 
-pub async fn set_user_telegram_chat_id(db: &PgPool, id: &Uuid, telegram_chat_id: Option<String>) -> Result<(), sqlx::Error> {
+pub async fn set_user_locale(db: &PgPool, id: &Uuid, locale: &str) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        r#"
+        UPDATE "users"
+        SET locale = $1
+        WHERE id = $2
+        "#,
+        locale,
+        id,
+    )
+        .execute(db)
+        .await?;
+
+    Ok(())
+}
+
+pub async fn set_user_email(db: &PgPool, id: &Uuid, email: Option<String>) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        r#"
+        UPDATE "users"
+        SET email = $1
+        WHERE id = $2
+        "#,
+        email,
+        id,
+    )
+        .execute(db)
+        .await?;
+
+    Ok(())
+}
+
+/// Users ordered by id, starting after `after`; for walking the whole table in batches.
+pub async fn list_users_after(db: &PgPool, after: &Uuid, limit: i64) -> Result<Vec<User>, sqlx::Error> {
     sqlx::query_as!(
         User,
+        r#"
+        SELECT * FROM "users"
+        WHERE id > $1
+        ORDER BY id
+        LIMIT $2
+        "#,
+        after,
+        limit,
+    )
+        .fetch_all(db)
+        .await
+}
+
+pub async fn set_user_buyer_receipts(db: &PgPool, id: &Uuid, buyer_receipts: bool) -> Result<(), sqlx::Error> {
+    sqlx::query!(
         r#"
         UPDATE "users"
-        SET telegram_chat_id = $1
+        SET buyer_receipts = $1
         WHERE id = $2
         "#,
-        telegram_chat_id,
+        buyer_receipts,
         id,
     )
         .execute(db)
@@ -10,24 +10,56 @@ pub struct Webhook {
     pub url: String,
     pub secret: String,
     pub created_at: NaiveDateTime,
+    /// Catalog names, e.g. `invoice.paid`, see [`crate::events::webhook_event::WebhookEventType`].
+    pub event_types: Vec<String>,
+    /// Payload format; `legacy` rows get the pre-envelope invoice-paid body.
+    pub api_version: String,
+    /// Disabled webhooks get no new events; their pending deliveries wait until re-enabled.
+    pub enabled: bool,
+    /// The secret before the last rotation, still used for signing until `previous_secret_expires_at`.
+    pub previous_secret: Option<String>,
+    pub previous_secret_expires_at: Option<NaiveDateTime>,
 }
 
-pub async fn create_webhook(pg_pool: &PgPool, url: &str, secret: &str, user_id: &Uuid) -> Result<Webhook, sqlx::Error> {
+pub async fn create_webhook(
+    pg_pool: &PgPool,
+    url: &str,
+    secret: &str,
+    user_id: &Uuid,
+    event_types: &[String],
+    api_version: &str,
+) -> Result<Webhook, sqlx::Error> {
     sqlx::query_as!(
         Webhook,
         r#"
-        INSERT INTO webhook (url, secret, user_id)
-        VALUES ($1, $2, $3)
+        INSERT INTO webhook (url, secret, user_id, event_types, api_version)
+        VALUES ($1, $2, $3, $4, $5)
         RETURNING *
         "#,
         url,
         secret,
-        user_id
+        user_id,
+        event_types,
+        api_version
     )
     .fetch_one(pg_pool)
     .await
 }
 
+pub async fn get_webhook(pg_pool: &PgPool, id: &Uuid, user_id: &Uuid) -> Result<Option<Webhook>, sqlx::Error> {
+    sqlx::query_as!(
+        Webhook,
+        r#"
+        SELECT * FROM webhook
+        WHERE id = $1 AND user_id = $2
+        "#,
+        id,
+        user_id
+    )
+    .fetch_optional(pg_pool)
+    .await
+}
+
 pub async fn list_webhooks_by_user_id(
     pg_pool: &PgPool,
     user_id: &Uuid,
@@ -45,6 +77,82 @@ pub async fn list_webhooks_by_user_id(
     .await
 }
 
+/// Enabled webhooks of `user_id` subscribed to `event_type`.
+pub async fn list_webhooks_for_event(
+    pg_pool: &PgPool,
+    user_id: &Uuid,
+    event_type: &str,
+) -> Result<Vec<Webhook>, sqlx::Error> {
+    sqlx::query_as!(
+        Webhook,
+        r#"
+        SELECT * FROM webhook
+        WHERE user_id = $1 AND enabled AND $2 = ANY(event_types)
+        ORDER BY created_at DESC
+        "#,
+        user_id,
+        event_type
+    )
+    .fetch_all(pg_pool)
+    .await
+}
+
+/// Changes only the fields that are `Some`; `None` when the webhook does not exist.
+pub async fn update_webhook(
+    pg_pool: &PgPool,
+    id: &Uuid,
+    user_id: &Uuid,
+    url: Option<&str>,
+    enabled: Option<bool>,
+    event_types: Option<&[String]>,
+) -> Result<Option<Webhook>, sqlx::Error> {
+    sqlx::query_as!(
+        Webhook,
+        r#"
+        UPDATE webhook
+        SET url = COALESCE($3, url),
+            enabled = COALESCE($4, enabled),
+            event_types = COALESCE($5, event_types)
+        WHERE id = $1 AND user_id = $2
+        RETURNING *
+        "#,
+        id,
+        user_id,
+        url,
+        enabled,
+        event_types
+    )
+    .fetch_optional(pg_pool)
+    .await
+}
+
+/// Replaces the secret, keeping the old one valid for `grace_secs` (none when zero).
+pub async fn rotate_webhook_secret(
+    pg_pool: &PgPool,
+    id: &Uuid,
+    user_id: &Uuid,
+    secret: &str,
+    grace_secs: f64,
+) -> Result<Option<Webhook>, sqlx::Error> {
+    sqlx::query_as!(
+        Webhook,
+        r#"
+        UPDATE webhook
+        SET previous_secret = CASE WHEN $4::DOUBLE PRECISION > 0 THEN secret END,
+            previous_secret_expires_at = CASE WHEN $4 > 0 THEN NOW() + make_interval(secs => $4) END,
+            secret = $3
+        WHERE id = $1 AND user_id = $2
+        RETURNING *
+        "#,
+        id,
+        user_id,
+        secret,
+        grace_secs
+    )
+    .fetch_optional(pg_pool)
+    .await
+}
+
 pub async fn delete_webhook_by_id_and_user_id(
     pg_pool: &PgPool,
     id: &Uuid,
@@ -79,3 +187,47 @@ pub async fn count_webhooks_by_user_id(
     .fetch_one(pg_pool)
     .await
 }
+
+/// Webhooks of every user ordered by id, starting after `after`; for walking the whole table in batches.
+pub async fn list_webhooks_after(
+    pg_pool: &PgPool,
+    after: &Uuid,
+    limit: i64,
+) -> Result<Vec<Webhook>, sqlx::Error> {
+    sqlx::query_as!(
+        Webhook,
+        r#"
+        SELECT * FROM webhook
+        WHERE id > $1
+        ORDER BY id
+        LIMIT $2
+        "#,
+        after,
+        limit
+    )
+    .fetch_all(pg_pool)
+    .await
+}
+
+/// Overwrites the stored secrets as they are, leaving the grace period untouched.
+pub async fn set_webhook_secrets(
+    pg_pool: &PgPool,
+    id: &Uuid,
+    secret: &str,
+    previous_secret: Option<&str>,
+) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        r#"
+        UPDATE webhook
+        SET secret = $2, previous_secret = $3
+        WHERE id = $1
+        "#,
+        id,
+        secret,
+        previous_secret
+    )
+    .execute(pg_pool)
+    .await?;
+
+    Ok(())
+}
@@ -0,0 +1,206 @@
+use serde::Serialize;
+use serde_json::Value;
+use sqlx::PgPool;
+use sqlx::types::Uuid;
+use sqlx::types::chrono::NaiveDateTime;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum WebhookDeliveryStatus {
+    Pending,
+    Delivered,
+    /// Gave up after the last retry; can still be redelivered by hand.
+    Failed,
+}
+
+impl WebhookDeliveryStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Pending => "pending",
+            Self::Delivered => "delivered",
+            Self::Failed => "failed",
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize, sqlx::FromRow)]
+pub struct WebhookDelivery {
+    pub id: Uuid,
+    pub webhook_id: Uuid,
+    pub payload: Value,
+    pub status: String,
+    pub attempts: i32,
+    pub next_attempt_at: NaiveDateTime,
+    pub created_at: NaiveDateTime,
+    pub delivered_at: Option<NaiveDateTime>,
+}
+
+#[derive(Clone, Debug, Serialize, sqlx::FromRow)]
+pub struct WebhookDeliveryAttempt {
+    pub id: Uuid,
+    pub delivery_id: Uuid,
+    pub attempted_at: NaiveDateTime,
+    /// `None` when the request failed before a response arrived (DNS, TLS, timeout).
+    pub status_code: Option<i32>,
+    pub latency_ms: i64,
+    pub response_excerpt: Option<String>,
+    pub error: Option<String>,
+}
+
+/// A delivery claimed by the outbox worker, joined with the endpoint it goes to.
+#[derive(Clone, Debug, sqlx::FromRow)]
+pub struct DueWebhookDelivery {
+    pub id: Uuid,
+    pub webhook_id: Uuid,
+    pub payload: Value,
+    pub attempts: i32,
+    pub url: String,
+    pub secret: String,
+    /// Set while a rotated-out secret is still in its grace period.
+    pub previous_secret: Option<String>,
+}
+
+/// Outcome of one HTTP attempt, as written to `webhook_delivery_attempt`.
+#[derive(Clone, Debug, Default)]
+pub struct NewWebhookDeliveryAttempt {
+    pub status_code: Option<i32>,
+    pub latency_ms: i64,
+    pub response_excerpt: Option<String>,
+    pub error: Option<String>,
+}
+
+pub async fn enqueue_delivery(pg_pool: &PgPool, webhook_id: &Uuid, payload: &Value) -> Result<WebhookDelivery, sqlx::Error> {
+    sqlx::query_as!(
+        WebhookDelivery,
+        r#"
+        INSERT INTO webhook_delivery (webhook_id, payload)
+        VALUES ($1, $2)
+        RETURNING *
+        "#,
+        webhook_id,
+        payload
+    )
+    .fetch_one(pg_pool)
+    .await
+}
+
+/// Picks up to `limit` due deliveries of enabled webhooks and pushes their `next_attempt_at` forward by
+/// `lease_secs`, so another worker (or this one after a crash) only retries them once the lease expires.
+pub async fn claim_due_deliveries(pg_pool: &PgPool, limit: i64, lease_secs: f64) -> Result<Vec<DueWebhookDelivery>, sqlx::Error> {
+    sqlx::query_as!(
+        DueWebhookDelivery,
+        r#"
+        UPDATE webhook_delivery AS d
+        SET next_attempt_at = NOW() + make_interval(secs => $2)
+        FROM webhook AS w
+        WHERE w.id = d.webhook_id AND d.id IN (
+            SELECT pending.id FROM webhook_delivery AS pending
+            JOIN webhook ON webhook.id = pending.webhook_id
+            WHERE pending.status = 'pending' AND pending.next_attempt_at <= NOW() AND webhook.enabled
+            ORDER BY pending.next_attempt_at
+            LIMIT $1
+            FOR UPDATE OF pending SKIP LOCKED
+        )
+        RETURNING d.id, d.webhook_id, d.payload, d.attempts, w.url, w.secret,
+            CASE WHEN w.previous_secret_expires_at > NOW() THEN w.previous_secret END AS previous_secret
+        "#,
+        limit,
+        lease_secs
+    )
+    .fetch_all(pg_pool)
+    .await
+}
+
+pub async fn record_attempt(
+    pg_pool: &PgPool,
+    delivery_id: &Uuid,
+    attempt: &NewWebhookDeliveryAttempt,
+    status: WebhookDeliveryStatus,
+    retry_in_secs: f64,
+) -> Result<(), sqlx::Error> {
+    let mut tx = pg_pool.begin().await?;
+
+    sqlx::query!(
+        r#"
+        INSERT INTO webhook_delivery_attempt (delivery_id, status_code, latency_ms, response_excerpt, error)
+        VALUES ($1, $2, $3, $4, $5)
+        "#,
+        delivery_id,
+        attempt.status_code,
+        attempt.latency_ms,
+        attempt.response_excerpt,
+        attempt.error
+    )
+    .execute(&mut *tx)
+    .await?;
+
+    sqlx::query!(
+        r#"
+        UPDATE webhook_delivery
+        SET attempts = attempts + 1,
+            status = $2,
+            next_attempt_at = NOW() + make_interval(secs => $3),
+            delivered_at = CASE WHEN $2 = 'delivered' THEN NOW() ELSE delivered_at END
+        WHERE id = $1
+        "#,
+        delivery_id,
+        status.as_str(),
+        retry_in_secs
+    )
+    .execute(&mut *tx)
+    .await?;
+
+    tx.commit().await
+}
+
+pub async fn list_deliveries(
+    pg_pool: &PgPool,
+    webhook_id: &Uuid,
+    limit: i64,
+    offset: i64,
+) -> Result<Vec<WebhookDelivery>, sqlx::Error> {
+    sqlx::query_as!(
+        WebhookDelivery,
+        r#"
+        SELECT * FROM webhook_delivery
+        WHERE webhook_id = $1
+        ORDER BY created_at DESC
+        LIMIT $2 OFFSET $3
+        "#,
+        webhook_id,
+        limit,
+        offset
+    )
+    .fetch_all(pg_pool)
+    .await
+}
+
+pub async fn list_attempts(pg_pool: &PgPool, delivery_ids: &[Uuid]) -> Result<Vec<WebhookDeliveryAttempt>, sqlx::Error> {
+    sqlx::query_as!(
+        WebhookDeliveryAttempt,
+        r#"
+        SELECT * FROM webhook_delivery_attempt
+        WHERE delivery_id = ANY($1)
+        ORDER BY attempted_at
+        "#,
+        delivery_ids
+    )
+    .fetch_all(pg_pool)
+    .await
+}
+
+/// Puts a delivery back in the queue with a fresh retry budget; its attempt log is kept.
+pub async fn redeliver(pg_pool: &PgPool, id: &Uuid, webhook_id: &Uuid) -> Result<bool, sqlx::Error> {
+    let result = sqlx::query!(
+        r#"
+        UPDATE webhook_delivery
+        SET status = 'pending', attempts = 0, next_attempt_at = NOW(), delivered_at = NULL
+        WHERE id = $1 AND webhook_id = $2
+        "#,
+        id,
+        webhook_id
+    )
+    .execute(pg_pool)
+    .await?;
+
+    Ok(result.rows_affected() > 0)
+}
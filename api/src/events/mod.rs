@@ -1,18 +1,21 @@
 pub mod notifications;
+pub mod webhook_event;
 
 use std::fmt::Debug;
 use std::sync::Arc;
 use bigdecimal::BigDecimal;
 use chrono::{DateTime, Utc};
-use ethers::{abi::RawLog, prelude::*};
+use ethers::{abi::{AbiDecode, RawLog}, prelude::*};
 use futures::future::join_all;
 use tracing::{info, warn};
 use uuid::Uuid;
 use crate::api::state::DB;
 use crate::db::Invoice;
+use crate::db::invoice::{InvoicePayment, InvoiceStatus};
 use crate::error::AppError;
-use crate::events::notifications::Notifier;
+use crate::events::notifications::{InvoiceNotification, Notifier, Transfer};
 use crate::monitoring::app_state::MonitorAppState;
+use crate::network::Network;
 use crate::utils;
 
 
@@ -27,40 +30,137 @@ pub struct PayInvoiceEvent {
     pub amount: U128,
 }
 
+/// The contract call a buyer's wallet sends from checkout.
+#[derive(Debug, Clone, EthCall)]
+#[ethcall(name = "payInvoice", abi = "payInvoice(address,string,uint256)")]
+pub struct PayInvoiceCall {
+    pub seller: Address,
+    pub invoice_id: String,
+    pub amount: U256,
+}
+
 pub async fn just_print_log(log: &Log) -> Result<(), String> {
     parse_event(log)
         .map(|event| info!("New transaction event: {:?}", event))
 }
 
 
-pub async fn set_invoice_paid(postgres_db: &DB, event: PayInvoiceEvent) -> Result<Invoice, AppError> {
+/// What an on-chain `PayInvoice` event did to its invoice.
+pub enum PaymentOutcome {
+    Paid(Invoice),
+    /// Less than the invoice amount arrived; the invoice stays unpaid.
+    PartiallyPaid { invoice: Invoice, paid_amount: BigDecimal, payer: String },
+    /// The transfer could not be applied: already paid, expired, void, wrong seller or overpaid.
+    Anomaly { invoice: Invoice, paid_amount: BigDecimal, payer: String, reason: String },
+}
+
+/// `network` is the chain id the event was read from, `tx_hash` the transaction that emitted it.
+pub async fn set_invoice_paid(postgres_db: &DB, event: PayInvoiceEvent, network: i32, tx_hash: String) -> Result<PaymentOutcome, AppError> {
     let invoice_id = Uuid::parse_str(&event.invoice_id)
         .map_err(|err| AppError::Internal(utils::make_err(Box::new(err), "parse invoice_id")))?;
 
     let paid_amount = BigDecimal::from(event.amount.as_u128()) / BigDecimal::from(1_000_000u128);
+    let seller = format!("{:#020x}", event.seller);
+    let payer = format!("{:#020x}", event.payer);
 
     let stored = postgres_db.get_invoice(&invoice_id)
         .await?
         .ok_or_else(|| AppError::Internal(format!("Invoice {} not found", invoice_id)))?;
 
+    let anomaly = if let Some(reason) = unpayable_reason(&stored) {
+        Some(reason)
+    } else if stored.seller.to_lowercase() != seller {
+        Some(format!("paid to seller {seller}"))
+    } else if paid_amount > stored.amount {
+        Some(format!("overpaid: required {}, got {}", stored.amount, paid_amount))
+    } else {
+        None
+    };
+    if let Some(reason) = anomaly {
+        return Ok(PaymentOutcome::Anomaly { invoice: stored, paid_amount, payer, reason });
+    }
+
     if paid_amount < stored.amount {
-        return Err(AppError::Internal(format!(
-            "Underpayment on invoice {}: required {}, got {}",
-            invoice_id, stored.amount, paid_amount
-        )));
+        return Ok(PaymentOutcome::PartiallyPaid { invoice: stored, paid_amount, payer });
     }
 
     let paid_at = DateTime::<Utc>::from_timestamp(event.paid_at.as_u64() as i64, 0)
         .map(|dt| dt.naive_utc())
         .ok_or_else(|| AppError::Internal("Invalid timestamp".to_string()))?;
 
-    postgres_db.set_invoice_paid(
-        invoice_id,
-        &format!("{:#020x}", event.seller),
-        paid_amount,
-        &format!("{:#020x}", event.payer),
-        paid_at,
-    ).await
+    let payment = InvoicePayment { seller, amount: paid_amount, buyer: payer, paid_at, network, tx_hash };
+    match postgres_db.set_invoice_paid(invoice_id, &payment).await {
+        Ok(invoice) => Ok(PaymentOutcome::Paid(invoice)),
+        // Expired, voided or paid since it was read above.
+        Err(AppError::Db(sqlx::Error::RowNotFound)) => {
+            let invoice = postgres_db.get_invoice(&invoice_id)
+                .await?
+                .ok_or_else(|| AppError::Internal(format!("Invoice {} not found", invoice_id)))?;
+            let reason = unpayable_reason(&invoice).unwrap_or_else(|| "invoice changed while paying".to_string());
+            Ok(PaymentOutcome::Anomaly { invoice, paid_amount: payment.amount, payer: payment.buyer, reason })
+        }
+        Err(err) => Err(err),
+    }
+}
+
+/// Why a payment can no longer settle the invoice, which is never marked paid once expired or void.
+fn unpayable_reason(invoice: &Invoice) -> Option<String> {
+    if invoice.paid_at.is_some() {
+        return Some("invoice already paid".to_string());
+    }
+    match invoice.status {
+        InvoiceStatus::Expired => Some("paid after the invoice expired".to_string()),
+        InvoiceStatus::Void => Some("paid to a void invoice".to_string()),
+        _ => None,
+    }
+}
+
+/// `tx_hash` on `network`, with its receipt once mined; `None` when the node does not know it.
+pub async fn get_payment_transaction(
+    network: &Network,
+    tx_hash: H256,
+) -> Result<Option<(Transaction, Option<TransactionReceipt>)>, AppError> {
+    let provider = Provider::<Http>::try_from(&network.link)
+        .map_err(|err| AppError::Network(utils::make_err(Box::new(err), "create provider")))?;
+    let Some(tx) = provider.get_transaction(tx_hash)
+        .await
+        .map_err(|err| AppError::Network(utils::make_err(Box::new(err), "get transaction")))? else {
+        return Ok(None);
+    };
+    let receipt = match tx.block_number {
+        None => None,
+        Some(_) => provider.get_transaction_receipt(tx_hash)
+            .await
+            .map_err(|err| AppError::Network(utils::make_err(Box::new(err), "get transaction receipt")))?,
+    };
+    Ok(Some((tx, receipt)))
+}
+
+/// Whether `tx`, pending or mined, is a `payInvoice` call to `network`'s contract paying
+/// `invoice` to its seller; a mined one must have succeeded.
+pub fn check_payment_transaction(
+    tx: &Transaction,
+    receipt: Option<&TransactionReceipt>,
+    network: &Network,
+    invoice: &Invoice,
+) -> Result<(), String> {
+    let contract = network.addresses.contract.parse::<Address>()
+        .map_err(|err| utils::make_err(Box::new(err), "parse contract address"))?;
+    if tx.to != Some(contract) {
+        return Err("The transaction is not sent to the payment contract".to_string());
+    }
+    let call = PayInvoiceCall::decode(&tx.input)
+        .map_err(|_| "The transaction is not an invoice payment".to_string())?;
+    if call.invoice_id != invoice.id.to_string() {
+        return Err("The transaction pays another invoice".to_string());
+    }
+    if invoice.seller.parse::<Address>().ok() != Some(call.seller) {
+        return Err("The transaction pays another seller".to_string());
+    }
+    if receipt.is_some_and(|receipt| receipt.status != Some(U64::one())) {
+        return Err("The transaction failed".to_string());
+    }
+    Ok(())
 }
 
 pub fn parse_event(log: &Log) -> Result<PayInvoiceEvent, String> {
@@ -69,10 +169,25 @@ pub fn parse_event(log: &Log) -> Result<PayInvoiceEvent, String> {
         .map_err(|err| utils::make_err(Box::new(err), "decode log"))
 }
 
-pub async fn process_log(app_state: &MonitorAppState, log: &Log) -> Result<(), String> {
+pub async fn process_log(app_state: &MonitorAppState, network: &Network, log: &Log) -> Result<(), String> {
     let event = parse_event(log)?;
-    let invoice = set_invoice_paid(&app_state.db, event).await
-        .map_err(|e| e.to_string())?;
+    let tx_hash = log.transaction_hash
+        .map(|hash| format!("{hash:#x}"))
+        .ok_or("log without transaction hash")?;
+    let transfer = |amount, payer, reason| Transfer { amount, payer, network: network.id as i32, tx_hash: tx_hash.clone(), reason };
+    let invoice = match set_invoice_paid(&app_state.db, event, network.id as i32, tx_hash.clone()).await.map_err(|e| e.to_string())? {
+        PaymentOutcome::Paid(invoice) => invoice,
+        PaymentOutcome::PartiallyPaid { invoice, paid_amount, payer } => {
+            warn!("Underpayment on invoice {}: required {}, got {}", invoice.id, invoice.amount, paid_amount);
+            let notification = InvoiceNotification::partially_paid(invoice, transfer(paid_amount, payer, None));
+            return notify_owner(app_state, notification).await;
+        }
+        PaymentOutcome::Anomaly { invoice, paid_amount, payer, reason } => {
+            warn!("Payment anomaly on invoice {}: {reason}", invoice.id);
+            let notification = InvoiceNotification::payment_anomaly(invoice, transfer(paid_amount, payer, Some(reason)));
+            return notify_owner(app_state, notification).await;
+        }
+    };
 
     // Sync payments.paid_at for self-issued invoices (donations/subscriptions).
     // Regular seller invoices have no matching payments row so this is a no-op for them.
@@ -81,27 +196,161 @@ pub async fn process_log(app_state: &MonitorAppState, log: &Log) -> Result<(), S
         warn!("Failed to sync payment paid_at for invoice {}: {err}", invoice.id);
     }
 
-    if let Some(user_id) = invoice.user_id {
-        let tasks = Notifier::get_notifiers(&app_state.db, &user_id)
-            .await
-            .map_err(|e| e.to_string())?
-            .into_iter()
-            .map(|n| {
-                let app_state = Arc::new(app_state.clone());
-                let invoice = invoice.clone();
-                tokio::spawn(async move {
-                    n.notify(app_state, invoice).await
-                })
-            }
-            )
-            .collect::<Vec<_>>();
-
-        let results = join_all(tasks).await;
-
-        for result in results {
-            result.map_err(|e| format!("Notify failed: {:?}", e))??;
+    notify_owner(app_state, InvoiceNotification::paid(invoice)).await
+}
+
+/// Sends the notification on every channel of the invoice's seller, if it has one.
+pub(crate) async fn notify_owner(app_state: &MonitorAppState, notification: InvoiceNotification) -> Result<(), String> {
+    let Some(user_id) = notification.invoice.user_id else {
+        return Ok(());
+    };
+
+    let tasks = Notifier::get_notifiers(app_state, &user_id, &notification)
+        .await
+        .map_err(|e| e.to_string())?
+        .into_iter()
+        .map(|n| {
+            let app_state = Arc::new(app_state.clone());
+            let notification = notification.clone();
+            tokio::spawn(async move {
+                n.notify(app_state, notification).await
+            })
         }
+        )
+        .collect::<Vec<_>>();
+
+    let results = join_all(tasks).await;
+
+    for result in results {
+        result.map_err(|e| format!("Notify failed: {:?}", e))??;
     }
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use ethers::abi::AbiEncode;
+    use super::*;
+
+    fn event(invoice_id: Uuid, seller: Address, amount: u128) -> PayInvoiceEvent {
+        PayInvoiceEvent {
+            invoice_id: invoice_id.to_string(),
+            seller,
+            payer: Address::repeat_byte(2),
+            paid_at: U128::from(1_700_000_000u64),
+            amount: U128::from(amount),
+        }
+    }
+
+    async fn pay(db: &DB, event: PayInvoiceEvent) -> Result<PaymentOutcome, AppError> {
+        set_invoice_paid(db, event, 10, "0xtx".to_string()).await
+    }
+
+    #[tokio::test]
+    async fn test_set_invoice_paid() {
+        let db = DB::in_memory();
+        let seller = Address::repeat_byte(1);
+        let invoice = db.create_invoice(BigDecimal::from(10), &format!("{:#020x}", seller), &[1], None, None, false)
+            .await
+            .unwrap();
+
+        let PaymentOutcome::Paid(paid) = pay(&db, event(invoice.id, seller, 10_000_000)).await.unwrap() else {
+            panic!("invoice not paid");
+        };
+        assert_eq!(paid.buyer, Some(format!("{:#020x}", Address::repeat_byte(2))));
+        assert_eq!(paid.paid_at.unwrap().and_utc().timestamp(), 1_700_000_000);
+        assert_eq!((paid.paid_network, paid.tx_hash.as_deref()), (Some(10), Some("0xtx")));
+    }
+
+    #[tokio::test]
+    async fn test_set_invoice_paid_underpayment_is_partial() {
+        let db = DB::in_memory();
+        let seller = Address::repeat_byte(1);
+        let invoice = db.create_invoice(BigDecimal::from(10), &format!("{:#020x}", seller), &[1], None, None, false)
+            .await
+            .unwrap();
+
+        let result = pay(&db, event(invoice.id, seller, 9_990_000)).await.unwrap();
+        assert!(matches!(result, PaymentOutcome::PartiallyPaid { paid_amount, .. } if paid_amount == BigDecimal::from(999) / 100));
+        assert!(db.get_invoice(&invoice.id).await.unwrap().unwrap().paid_at.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_set_invoice_paid_anomalies() {
+        let db = DB::in_memory();
+        let seller = Address::repeat_byte(1);
+        let invoice = db.create_invoice(BigDecimal::from(10), &format!("{:#020x}", seller), &[1], None, None, false)
+            .await
+            .unwrap();
+
+        let overpaid = pay(&db, event(invoice.id, seller, 11_000_000)).await.unwrap();
+        assert!(matches!(overpaid, PaymentOutcome::Anomaly { reason, .. } if reason.starts_with("overpaid")));
+        let wrong_seller = pay(&db, event(invoice.id, Address::repeat_byte(3), 10_000_000)).await.unwrap();
+        assert!(matches!(wrong_seller, PaymentOutcome::Anomaly { reason, .. } if reason.contains("seller")));
+
+        pay(&db, event(invoice.id, seller, 10_000_000)).await.unwrap();
+        let twice = pay(&db, event(invoice.id, seller, 10_000_000)).await.unwrap();
+        assert!(matches!(twice, PaymentOutcome::Anomaly { reason, .. } if reason == "invoice already paid"));
+    }
+
+    #[tokio::test]
+    async fn test_set_invoice_paid_after_close() {
+        let db = DB::in_memory();
+        let seller = Address::repeat_byte(1);
+        for (status, expected) in [
+            (InvoiceStatus::Expired, "paid after the invoice expired"),
+            (InvoiceStatus::Void, "paid to a void invoice"),
+        ] {
+            let invoice = db.create_invoice(BigDecimal::from(10), &format!("{:#020x}", seller), &[1], None, None, false)
+                .await
+                .unwrap();
+            db.transition_invoice(&invoice.id, status).await.unwrap().unwrap();
+
+            let late = pay(&db, event(invoice.id, seller, 10_000_000)).await.unwrap();
+            assert!(matches!(late, PaymentOutcome::Anomaly { reason, .. } if reason == expected));
+            let stored = db.get_invoice(&invoice.id).await.unwrap().unwrap();
+            assert_eq!((stored.status, stored.paid_at), (status, None));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_set_invoice_paid_unknown_invoice() {
+        let db = DB::in_memory();
+        let result = pay(&db, event(Uuid::new_v4(), Address::zero(), 1)).await;
+        assert!(matches!(result, Err(AppError::Internal(message)) if message.contains("not found")));
+    }
+
+    #[tokio::test]
+    async fn test_check_payment_transaction() {
+        let db = DB::in_memory();
+        let seller = Address::repeat_byte(1);
+        let invoice = db.create_invoice(BigDecimal::from(10), &format!("{:#020x}", seller), &[1], None, None, false)
+            .await
+            .unwrap();
+        let network = Network::parse_networks(r#"[
+            {"name":"Ethereum","id":1,"link":"https://rpc.example.com","addresses":{"erc20":"0xabc","contract":"0x0303030303030303030303030303030303030303"}}
+        ]"#).unwrap().remove(0);
+        let tx = |to: Address, invoice_id: String, seller: Address| Transaction {
+            to: Some(to),
+            input: PayInvoiceCall { seller, invoice_id, amount: U256::from(10_000_000) }.encode().into(),
+            ..Default::default()
+        };
+        let contract = Address::repeat_byte(3);
+        let receipt = |status: u64| TransactionReceipt { status: Some(U64::from(status)), ..Default::default() };
+
+        let paying = tx(contract, invoice.id.to_string(), seller);
+        assert!(check_payment_transaction(&paying, None, &network, &invoice).is_ok());
+        assert!(check_payment_transaction(&paying, Some(&receipt(1)), &network, &invoice).is_ok());
+        assert!(check_payment_transaction(&paying, Some(&receipt(0)), &network, &invoice).is_err());
+
+        for other in [
+            tx(Address::repeat_byte(4), invoice.id.to_string(), seller),
+            tx(contract, Uuid::new_v4().to_string(), seller),
+            tx(contract, invoice.id.to_string(), Address::repeat_byte(5)),
+            Transaction { to: Some(contract), ..Default::default() },
+        ] {
+            assert!(check_payment_transaction(&other, None, &network, &invoice).is_err());
+        }
+    }
+}
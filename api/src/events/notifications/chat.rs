@@ -0,0 +1,306 @@
+//! Notifications posted to chat apps through their incoming webhooks: Slack gets Block Kit
+//! blocks, Discord an embed, and anything else (Mattermost, Rocket.Chat, Google Chat...) a
+//! plain `{"text": ...}` body, which they all accept.
+
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use url::Url;
+use crate::monitoring::app_state::Webhooker;
+use super::templates::{Rendered, TemplateVars};
+
+/// Slack refuses longer header texts, Discord longer embed titles and field values.
+const MAX_SLACK_HEADER: usize = 150;
+const MAX_DISCORD_TITLE: usize = 256;
+const MAX_FIELD_VALUE: usize = 1024;
+/// Green side bar of Discord embeds.
+const DISCORD_COLOR: u32 = 0x2e_cc_71;
+const SLACK_HOSTS: &[&str] = &["hooks.slack.com"];
+const DISCORD_HOSTS: &[&str] = &["discord.com", "discordapp.com", "ptb.discord.com", "canary.discord.com"];
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize, Serialize, utoipa::ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum ChatKind {
+    Slack,
+    Discord,
+    Generic,
+}
+
+impl ChatKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Slack => "slack",
+            Self::Discord => "discord",
+            Self::Generic => "generic",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "slack" => Some(Self::Slack),
+            "discord" => Some(Self::Discord),
+            "generic" => Some(Self::Generic),
+            _ => None,
+        }
+    }
+
+    /// Slack and Discord URLs must be their https webhook endpoints, so a URL pasted into the
+    /// wrong kind is caught early. Whether the host is reachable is up to the outbound guard.
+    pub fn check_url(&self, url: &str) -> Result<Url, String> {
+        let parsed = Url::parse(url).map_err(|_| "Invalid url".to_string())?;
+        let host = parsed.host_str().unwrap_or_default();
+        let expected = match self {
+            Self::Slack => Some((SLACK_HOSTS, "/services/")),
+            Self::Discord => Some((DISCORD_HOSTS, "/api/webhooks/")),
+            Self::Generic => None,
+        };
+        match expected {
+            Some((hosts, path)) if parsed.scheme() != "https" || !hosts.contains(&host) || !parsed.path().starts_with(path) => {
+                Err(format!("Not a {} webhook URL: expected https://{}{path}...", self.as_str(), hosts[0]))
+            }
+            _ => Ok(parsed),
+        }
+    }
+
+    /// Posts through the webhook client, so chat URLs are held to the same outbound guard as
+    /// seller webhooks.
+    pub async fn send(&self, webhooker: &Webhooker, url: &str, message: &ChatMessage) -> Result<(), String> {
+        let response = webhooker.send(url, &[], &self.payload(message)).await?;
+        match response.is_success() {
+            true => Ok(()),
+            false => Err(format!("{} webhook answered {}: {}", self.as_str(), response.status, response.body_excerpt)),
+        }
+    }
+
+    pub fn payload(&self, message: &ChatMessage) -> Value {
+        match self {
+            Self::Slack => message.slack(),
+            Self::Discord => message.discord(),
+            Self::Generic => json!({ "text": message.plain_text() }),
+        }
+    }
+}
+
+/// Field names shown next to the values, per notification locale.
+fn field_names(locale: &str) -> [&'static str; 9] {
+    match locale {
+        "es" => ["Importe", "Recibido", "Red", "ID externo", "Pagado por", "Transacción", "Motivo", "Plan", "Termina"],
+        "ru" => ["Сумма", "Поступило", "Сеть", "Внешний ID", "Плательщик", "Транзакция", "Причина", "Тариф", "Заканчивается"],
+        _ => ["Amount", "Received", "Network", "External ID", "Paid by", "Transaction", "Reason", "Plan", "Ends"],
+    }
+}
+
+/// What every chat message shows: the rendered subject as title, the invoice or subscription
+/// details as fields and a button to the invoice or to renew.
+#[derive(Debug, PartialEq)]
+pub struct ChatMessage {
+    pub title: String,
+    /// `(name, value)`, only those with a value.
+    pub fields: Vec<(&'static str, String)>,
+    pub url: String,
+    pub button: String,
+}
+
+impl ChatMessage {
+    pub fn new(rendered: &Rendered, vars: &TemplateVars, locale: &str) -> Self {
+        let [amount, paid_amount, network, external_id, buyer, transaction, reason, plan, until] = field_names(locale);
+        let transaction_value = match vars.get("explorer_link") {
+            "" => vars.get("tx_hash"),
+            link => link,
+        };
+        let usdt = |name| match vars.get(name) {
+            "" => String::new(),
+            value => format!("{value} USDT"),
+        };
+        let fields = [
+            (amount, usdt("amount")),
+            (paid_amount, usdt("paid_amount")),
+            (network, vars.get("network").to_string()),
+            (external_id, vars.get("external_id").to_string()),
+            (buyer, vars.get("buyer").to_string()),
+            (transaction, transaction_value.to_string()),
+            (reason, vars.get("reason").to_string()),
+            (plan, vars.get("plan").to_string()),
+            (until, vars.get("until").to_string()),
+        ];
+
+        Self {
+            title: rendered.subject.clone(),
+            fields: fields.into_iter()
+                .filter(|(_, value)| !value.is_empty())
+                .map(|(name, value)| (name, truncate(&value, MAX_FIELD_VALUE)))
+                .collect(),
+            url: match vars.get("invoice_url") {
+                "" => vars.get("renew_url").to_string(),
+                url => url.to_string(),
+            },
+            button: rendered.telegram_button.clone(),
+        }
+    }
+
+    fn slack(&self) -> Value {
+        let fields = self.fields.iter()
+            .map(|(name, value)| json!({ "type": "mrkdwn", "text": format!("*{name}*\n{}", escape_slack(value)) }))
+            .collect::<Vec<_>>();
+
+        json!({
+            "text": self.title,
+            "blocks": [
+                { "type": "header", "text": { "type": "plain_text", "text": truncate(&self.title, MAX_SLACK_HEADER) } },
+                { "type": "section", "fields": fields },
+                { "type": "actions", "elements": [
+                    { "type": "button", "text": { "type": "plain_text", "text": self.button }, "url": self.url },
+                ] },
+            ],
+        })
+    }
+
+    /// Mentions are turned off, so a buyer or external id like `@everyone` pings nobody.
+    fn discord(&self) -> Value {
+        let fields = self.fields.iter()
+            .map(|(name, value)| json!({ "name": name, "value": value, "inline": true }))
+            .collect::<Vec<_>>();
+
+        json!({
+            "embeds": [{
+                "title": truncate(&self.title, MAX_DISCORD_TITLE),
+                "url": self.url,
+                "color": DISCORD_COLOR,
+                "fields": fields,
+            }],
+            "allowed_mentions": { "parse": [] },
+        })
+    }
+
+    fn plain_text(&self) -> String {
+        let mut text = self.title.clone();
+        for (name, value) in &self.fields {
+            text.push_str(&format!("\n{name}: {value}"));
+        }
+        text.push_str(&format!("\n{}", self.url));
+        text
+    }
+}
+
+/// Slack's mrkdwn treats these as markup for links and mentions.
+fn escape_slack(value: &str) -> String {
+    value.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;")
+}
+
+/// At most `max_chars` characters, the last one an ellipsis when cut.
+fn truncate(value: &str, max_chars: usize) -> String {
+    match value.chars().count() > max_chars {
+        true => value.chars().take(max_chars - 1).chain(['…']).collect(),
+        false => value.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{Arc, Mutex};
+    use axum::http::StatusCode;
+    use axum::routing::post;
+    use axum::Router;
+    use rstest::rstest;
+    use uuid::Uuid;
+    use crate::api::state::DB;
+    use crate::events::notifications::templates::{self, TemplateKind};
+    use crate::utils::ssrf::tests::stub_guard;
+
+    fn message() -> ChatMessage {
+        let rendered = Rendered {
+            subject: "Your invoice has been paid".to_string(),
+            text: String::new(),
+            html: String::new(),
+            telegram: String::new(),
+            telegram_button: "View invoice".to_string(),
+        };
+        ChatMessage::new(&rendered, &TemplateVars::sample("https://pay.example.com"), "en")
+    }
+
+    #[rstest]
+    #[case(ChatKind::Slack, "https://hooks.slack.com/services/T0/B0/x", true)]
+    #[case(ChatKind::Slack, "http://hooks.slack.com/services/T0/B0/x", false)]
+    #[case(ChatKind::Slack, "https://discord.com/api/webhooks/1/x", false)]
+    #[case(ChatKind::Discord, "https://discord.com/api/webhooks/1/x", true)]
+    #[case(ChatKind::Discord, "https://discordapp.com/api/webhooks/1/x", true)]
+    #[case(ChatKind::Discord, "https://discord.com/channels/1", false)]
+    #[case(ChatKind::Generic, "https://chat.example.com/hooks/abc", true)]
+    #[case(ChatKind::Generic, "not a url", false)]
+    fn test_check_url(#[case] kind: ChatKind, #[case] url: &str, #[case] valid: bool) {
+        assert_eq!(kind.check_url(url).is_ok(), valid);
+    }
+
+    #[test]
+    fn test_message_fields_skip_empty_values() {
+        let message = message();
+        assert_eq!(message.fields, vec![
+            ("Amount", "25 USDT".to_string()),
+            ("Network", "Ethereum".to_string()),
+            ("External ID", "order-42".to_string()),
+            ("Paid by", "0x0000000000000000000000000000000000000b0b".to_string()),
+        ]);
+        assert!(message.url.starts_with("https://pay.example.com/"));
+    }
+
+    #[test]
+    fn test_payloads() {
+        let mut message = message();
+        message.fields[2].1 = "<!channel> & @everyone".to_string();
+
+        let slack = ChatKind::Slack.payload(&message);
+        assert_eq!(slack["text"], "Your invoice has been paid");
+        assert_eq!(slack["blocks"][0]["text"]["text"], "Your invoice has been paid");
+        assert_eq!(slack["blocks"][1]["fields"][2]["text"], "*External ID*\n&lt;!channel&gt; &amp; @everyone");
+        assert_eq!(slack["blocks"][2]["elements"][0]["url"], message.url.as_str());
+
+        let discord = ChatKind::Discord.payload(&message);
+        assert_eq!(discord["embeds"][0]["fields"][0], json!({ "name": "Amount", "value": "25 USDT", "inline": true }));
+        assert_eq!(discord["allowed_mentions"]["parse"], json!([]));
+
+        let generic = ChatKind::Generic.payload(&message);
+        assert!(generic["text"].as_str().unwrap().starts_with("Your invoice has been paid\nAmount: 25 USDT\n"));
+    }
+
+    #[rstest]
+    #[case("short", 10, "short")]
+    #[case("abcdef", 4, "abc…")]
+    #[case("сумма", 3, "су…")]
+    fn test_truncate(#[case] value: &str, #[case] max_chars: usize, #[case] expected: &str) {
+        assert_eq!(truncate(value, max_chars), expected);
+    }
+
+    /// Local stand-in for a chat app's incoming webhook that answers `status` and keeps the bodies.
+    async fn chat_endpoint(status: StatusCode) -> (String, Arc<Mutex<Vec<Value>>>) {
+        let bodies = Arc::new(Mutex::new(Vec::new()));
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/hook", listener.local_addr().unwrap());
+        let recorded = bodies.clone();
+        let app = Router::new().route("/hook", post(move |body: String| async move {
+            recorded.lock().unwrap().push(serde_json::from_str(&body).unwrap());
+            (status, "invalid_payload")
+        }));
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+        (url, bodies)
+    }
+
+    #[tokio::test]
+    async fn test_send_posts_the_rendered_payload() {
+        let vars = TemplateVars::sample("https://pay.example.com");
+        let rendered = templates::render(&DB::in_memory(), &Uuid::nil(), TemplateKind::InvoicePaid, "es", &vars).await.unwrap();
+        let message = ChatMessage::new(&rendered, &vars, "es");
+        let webhooker = Webhooker::new(stub_guard(&[], true)).unwrap();
+
+        let (url, bodies) = chat_endpoint(StatusCode::OK).await;
+        ChatKind::Discord.send(&webhooker, &url, &message).await.unwrap();
+        let body = bodies.lock().unwrap().pop().unwrap();
+        assert_eq!(body["embeds"][0]["title"], rendered.subject.as_str());
+        assert_eq!(body["embeds"][0]["fields"][0]["name"], "Importe");
+        assert_eq!(body["embeds"][0]["url"], vars.get("invoice_url"));
+
+        let (url, bodies) = chat_endpoint(StatusCode::BAD_REQUEST).await;
+        let err = ChatKind::Slack.send(&webhooker, &url, &message).await.unwrap_err();
+        assert_eq!(err, "slack webhook answered 400: invalid_payload");
+        assert_eq!(bodies.lock().unwrap()[0]["blocks"][2]["elements"][0]["text"]["text"], rendered.telegram_button.as_str());
+    }
+}
@@ -0,0 +1,343 @@
+//! Digest mode: instead of one message per event, a channel can get a summary of everything
+//! that happened in the last hour, day or week. Events are queued by [`super::Notifier::Digest`]
+//! and `monitoring::digest` sends the summaries once their period is over.
+
+#[cfg(any(feature = "mailer", feature = "telegram"))]
+use std::collections::BTreeMap;
+#[cfg(any(feature = "mailer", feature = "telegram"))]
+use bigdecimal::BigDecimal;
+use chrono::{Datelike, Duration, NaiveDateTime, Timelike};
+use serde::{Deserialize, Serialize};
+#[cfg(any(feature = "mailer", feature = "telegram"))]
+use crate::db::notification_digest::NotificationDigestItem;
+#[cfg(any(feature = "mailer", feature = "telegram"))]
+use crate::network::Network;
+#[cfg(any(feature = "mailer", feature = "telegram"))]
+use crate::utils;
+use super::preferences::NotificationChannel;
+#[cfg(any(feature = "mailer", feature = "telegram"))]
+use super::preferences::NotificationEvent;
+#[cfg(any(feature = "mailer", feature = "telegram"))]
+use super::templates::{escape, format_amount, Format, Rendered};
+
+/// How many invoices a digest lists by name.
+#[cfg(any(feature = "mailer", feature = "telegram"))]
+const TOP_INVOICES: usize = 5;
+
+/// Chat webhooks feed channels people already skim, so they stay immediate.
+pub const DIGEST_CHANNELS: [NotificationChannel; 2] = [NotificationChannel::Email, NotificationChannel::Telegram];
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize, Serialize, utoipa::ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum DigestFrequency {
+    /// One message per event, no digest.
+    #[default]
+    Immediate,
+    Hourly,
+    Daily,
+    /// Weeks start on Monday.
+    Weekly,
+}
+
+impl DigestFrequency {
+    pub const ALL: [Self; 4] = [Self::Immediate, Self::Hourly, Self::Daily, Self::Weekly];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Immediate => "immediate",
+            Self::Hourly => "hourly",
+            Self::Daily => "daily",
+            Self::Weekly => "weekly",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|frequency| frequency.as_str() == value)
+    }
+
+    /// Start of the UTC period `now` falls into, which is where the last complete one ends.
+    /// Items left over after switching back to immediate go out right away.
+    pub fn period_end(&self, now: NaiveDateTime) -> NaiveDateTime {
+        let hour = now.date().and_hms_opt(now.hour(), 0, 0).unwrap_or(now);
+        let day = now.date().and_hms_opt(0, 0, 0).unwrap_or(now);
+        match self {
+            Self::Immediate => now,
+            Self::Hourly => hour,
+            Self::Daily => day,
+            Self::Weekly => day - Duration::days(now.weekday().num_days_from_monday() as i64),
+        }
+    }
+}
+
+/// A paid invoice named in the digest.
+#[cfg(any(feature = "mailer", feature = "telegram"))]
+#[derive(Debug, PartialEq)]
+pub struct TopInvoice {
+    pub external_id: String,
+    pub amount: BigDecimal,
+    pub url: String,
+}
+
+/// What a digest says: how often each event happened, what was paid on each network and the
+/// largest paid invoices with an `external_id`.
+#[cfg(any(feature = "mailer", feature = "telegram"))]
+#[derive(Debug, PartialEq)]
+pub struct Digest {
+    pub frequency: DigestFrequency,
+    pub counts: Vec<(NotificationEvent, usize)>,
+    /// Paid amounts per network name.
+    pub totals: BTreeMap<String, BigDecimal>,
+    pub top: Vec<TopInvoice>,
+    pub dashboard_url: String,
+}
+
+#[cfg(any(feature = "mailer", feature = "telegram"))]
+impl Digest {
+    pub fn new(frequency: DigestFrequency, items: &[NotificationDigestItem], networks: &[Network], web_base_url: &str) -> Self {
+        let counts = NotificationEvent::ALL
+            .into_iter()
+            .map(|event| (event, items.iter().filter(|item| item.event_type == event.as_str()).count()))
+            .filter(|(_, count)| *count > 0)
+            .collect();
+
+        let paid = items.iter()
+            .filter(|item| item.event_type == NotificationEvent::InvoicePaid.as_str())
+            .collect::<Vec<_>>();
+
+        let mut totals = BTreeMap::<String, BigDecimal>::new();
+        for item in &paid {
+            let network = item.network
+                .and_then(|id| networks.iter().find(|network| network.id == id as i64))
+                .map(|network| network.name.clone())
+                .unwrap_or_else(|| "Unknown".to_string());
+            *totals.entry(network).or_default() += &item.amount;
+        }
+
+        let mut top = paid.iter()
+            .filter_map(|item| item.external_id.as_ref().map(|external_id| TopInvoice {
+                external_id: external_id.clone(),
+                amount: item.amount.clone(),
+                url: utils::get_invoice_url(web_base_url, &item.invoice_id),
+            }))
+            .collect::<Vec<_>>();
+        top.sort_by(|a, b| b.amount.cmp(&a.amount));
+        top.truncate(TOP_INVOICES);
+
+        Self {
+            frequency,
+            counts,
+            totals,
+            top,
+            dashboard_url: format!("{}/dashboard", web_base_url),
+        }
+    }
+
+    pub fn render(&self, locale: &str) -> Rendered {
+        let labels = Labels::new(locale);
+        let title = labels.title(self.frequency);
+        let paid = self.counts.iter()
+            .find(|(event, _)| *event == NotificationEvent::InvoicePaid)
+            .map(|(_, count)| *count)
+            .unwrap_or_default();
+
+        Rendered {
+            subject: format!("{title}: {paid} {}", labels.paid_invoices),
+            text: self.render_format(&labels, Format::Text),
+            html: self.render_format(&labels, Format::Html),
+            telegram: self.render_format(&labels, Format::TelegramMarkdown),
+            telegram_button: labels.dashboard.to_string(),
+        }
+    }
+
+    fn render_format(&self, labels: &Labels, format: Format) -> String {
+        let e = |value: &str| escape(format, value);
+        let heading = |value: &str| match format {
+            Format::Text => format!("{value}\n"),
+            Format::Html => format!("<h3>{}</h3>\n", e(value)),
+            Format::TelegramMarkdown => format!("*{}*\n", e(value)),
+        };
+        let line = |value: String| match format {
+            Format::Html => format!("<li>{value}</li>\n"),
+            _ => format!("{value}\n"),
+        };
+        let list = |lines: Vec<String>| match format {
+            Format::Html => format!("<ul>\n{}</ul>\n", lines.concat()),
+            _ => lines.concat(),
+        };
+
+        let mut out = heading(labels.title(self.frequency));
+        out += &list(self.counts.iter()
+            .map(|(event, count)| line(format!("{}: {count}", e(labels.event(*event)))))
+            .collect());
+
+        if !self.totals.is_empty() {
+            out += "\n";
+            out += &heading(labels.totals);
+            out += &list(self.totals.iter()
+                .map(|(network, total)| line(format!("{}: {} USDT", e(network), e(&format_amount(total)))))
+                .collect());
+        }
+
+        if !self.top.is_empty() {
+            out += "\n";
+            out += &heading(labels.top);
+            out += &list(self.top.iter()
+                .map(|invoice| {
+                    let amount = e(&format_amount(&invoice.amount));
+                    line(match format {
+                        Format::Text => format!("{}: {amount} USDT, {}", invoice.external_id, invoice.url),
+                        Format::Html => format!("<a href=\"{}\">{}</a>: {amount} USDT", e(&invoice.url), e(&invoice.external_id)),
+                        Format::TelegramMarkdown => format!("[{}]({}): {amount} USDT", e(&invoice.external_id), invoice.url),
+                    })
+                })
+                .collect());
+        }
+
+        match format {
+            Format::Text => out + &format!("\n{}: {}\n", labels.dashboard, self.dashboard_url),
+            Format::Html => out + &format!("<p><a href=\"{}\">{}</a></p>\n", e(&self.dashboard_url), e(labels.dashboard)),
+            // The dashboard link is the message's button.
+            Format::TelegramMarkdown => out,
+        }
+    }
+}
+
+/// Digest texts are generated rather than seller templates, so they are translated here.
+#[cfg(any(feature = "mailer", feature = "telegram"))]
+struct Labels {
+    hourly: &'static str,
+    daily: &'static str,
+    weekly: &'static str,
+    paid_invoices: &'static str,
+    events: [&'static str; 5],
+    totals: &'static str,
+    top: &'static str,
+    dashboard: &'static str,
+}
+
+#[cfg(any(feature = "mailer", feature = "telegram"))]
+impl Labels {
+    fn new(locale: &str) -> Self {
+        match locale {
+            "es" => Self {
+                hourly: "Resumen de la última hora",
+                daily: "Resumen diario",
+                weekly: "Resumen semanal",
+                paid_invoices: "facturas pagadas",
+                events: ["Facturas pagadas", "Pagos parciales", "Facturas vencidas", "Pagos que requieren atención", "Suscripciones por vencer"],
+                totals: "Pagado por red",
+                top: "Mayores facturas",
+                dashboard: "Abrir el panel",
+            },
+            "ru" => Self {
+                hourly: "Сводка за час",
+                daily: "Сводка за день",
+                weekly: "Сводка за неделю",
+                paid_invoices: "оплаченных счетов",
+                events: ["Оплаченные счета", "Частичные оплаты", "Просроченные счета", "Платежи, требующие внимания", "Истекающие подписки"],
+                totals: "Оплачено по сетям",
+                top: "Крупнейшие счета",
+                dashboard: "Открыть панель",
+            },
+            _ => Self {
+                hourly: "Hourly summary",
+                daily: "Daily summary",
+                weekly: "Weekly summary",
+                paid_invoices: "invoices paid",
+                events: ["Invoices paid", "Partial payments", "Invoices expired", "Payments needing attention", "Subscriptions expiring"],
+                totals: "Paid per network",
+                top: "Largest invoices",
+                dashboard: "Open the dashboard",
+            },
+        }
+    }
+
+    fn title(&self, frequency: DigestFrequency) -> &'static str {
+        match frequency {
+            DigestFrequency::Immediate | DigestFrequency::Hourly => self.hourly,
+            DigestFrequency::Daily => self.daily,
+            DigestFrequency::Weekly => self.weekly,
+        }
+    }
+
+    fn event(&self, event: NotificationEvent) -> &'static str {
+        let index = NotificationEvent::ALL.iter().position(|e| *e == event).unwrap_or_default();
+        self.events[index]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[cfg(any(feature = "mailer", feature = "telegram"))]
+    use std::str::FromStr;
+    use rstest::rstest;
+    #[cfg(any(feature = "mailer", feature = "telegram"))]
+    use uuid::Uuid;
+
+    fn at(value: &str) -> NaiveDateTime {
+        NaiveDateTime::parse_from_str(value, "%Y-%m-%d %H:%M:%S").unwrap()
+    }
+
+    // 2026-10-21 is a Wednesday.
+    #[rstest]
+    #[case(DigestFrequency::Immediate, "2026-10-21 15:42:10")]
+    #[case(DigestFrequency::Hourly, "2026-10-21 15:00:00")]
+    #[case(DigestFrequency::Daily, "2026-10-21 00:00:00")]
+    #[case(DigestFrequency::Weekly, "2026-10-19 00:00:00")]
+    fn test_period_end(#[case] frequency: DigestFrequency, #[case] expected: &str) {
+        assert_eq!(frequency.period_end(at("2026-10-21 15:42:10")), at(expected));
+    }
+
+    #[cfg(any(feature = "mailer", feature = "telegram"))]
+    fn item(event: NotificationEvent, amount: &str, network: Option<i32>, external_id: Option<&str>) -> NotificationDigestItem {
+        NotificationDigestItem {
+            id: Uuid::new_v4(),
+            user_id: Uuid::nil(),
+            channel: "email".to_string(),
+            event_type: event.as_str().to_string(),
+            invoice_id: Uuid::nil(),
+            amount: BigDecimal::from_str(amount).unwrap(),
+            network,
+            external_id: external_id.map(str::to_string),
+            created_at: at("2026-10-21 15:42:10"),
+        }
+    }
+
+    #[cfg(any(feature = "mailer", feature = "telegram"))]
+    fn networks() -> Vec<Network> {
+        Network::parse_networks(r#"[
+            {"name":"Ethereum","id":1,"link":"https://rpc.example.com","addresses":{"erc20":"0xabc","contract":"0xdef"}},
+            {"name":"Polygon","id":137,"link":"https://rpc.example.com","addresses":{"erc20":"0xabc","contract":"0xdef"}}
+        ]"#).unwrap()
+    }
+
+    #[cfg(any(feature = "mailer", feature = "telegram"))]
+    #[test]
+    fn test_digest_summarizes_items() {
+        let items = vec![
+            item(NotificationEvent::InvoicePaid, "10.5", Some(1), Some("order-1")),
+            item(NotificationEvent::InvoicePaid, "30", Some(137), Some("order-2")),
+            item(NotificationEvent::InvoicePaid, "4.5", Some(1), None),
+            item(NotificationEvent::PaymentAnomaly, "100", Some(1), Some("order-3")),
+        ];
+        let digest = Digest::new(DigestFrequency::Daily, &items, &networks(), "https://cryo.test");
+
+        assert_eq!(digest.counts, vec![(NotificationEvent::InvoicePaid, 3), (NotificationEvent::PaymentAnomaly, 1)]);
+        assert_eq!(digest.totals.get("Ethereum"), Some(&BigDecimal::from(15)));
+        assert_eq!(digest.totals.get("Polygon"), Some(&BigDecimal::from(30)));
+        assert_eq!(
+            digest.top.iter().map(|invoice| invoice.external_id.as_str()).collect::<Vec<_>>(),
+            vec!["order-2", "order-1"],
+        );
+        assert_eq!(digest.dashboard_url, "https://cryo.test/dashboard");
+
+        let rendered = digest.render("en");
+        assert_eq!(rendered.subject, "Daily summary: 3 invoices paid");
+        assert!(rendered.text.contains("Ethereum: 15 USDT"));
+        assert!(rendered.text.contains("order-2: 30 USDT, https://cryo.test/invoices/"));
+        assert!(rendered.html.contains("<li>Payments needing attention: 1</li>"));
+        assert!(rendered.telegram.contains("[order\\-1](https://cryo.test/invoices/"));
+        assert!(rendered.telegram.contains("10\\.5 USDT"));
+    }
+}
@@ -1,137 +1,537 @@
 use std::sync::Arc;
+use bigdecimal::BigDecimal;
 use chrono::NaiveDateTime;
 use serde::{Deserialize, Serialize};
-use tracing::error;
 use uuid::Uuid;
-use crate::api::state::DB;
-use crate::db::Invoice;
+use crate::db::chat_webhook::ChatWebhook;
+#[cfg(any(feature = "mailer", feature = "telegram"))]
+use crate::db::notification_digest::NewNotificationDigestItem;
+use crate::db::notification_log::{NewNotificationLog, NotificationLog, RECEIPT_EVENT};
+#[cfg(feature = "telegram")]
+use crate::db::telegram_destination::TelegramDestination;
+use crate::db::{Invoice, User};
 use crate::error::AppError;
+use crate::events::webhook_event::{self, WebhookEvent, WebhookEventType};
+#[cfg(feature = "mailer")]
+use crate::mailer::Email;
 use crate::monitoring::app_state::MonitorAppState;
 use crate::utils;
+use chat::{ChatKind, ChatMessage};
+use preferences::{NotificationChannel, NotificationEvent, Preferences};
+use templates::{Rendered, TemplateKind, TemplateVars};
+
+pub mod chat;
+pub mod digest;
+pub mod preferences;
+pub mod templates;
+
+/// An on-chain transfer that reached an invoice without settling it.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct Transfer {
+    pub amount: BigDecimal,
+    pub payer: String,
+    /// Chain id the transfer was read from.
+    pub network: i32,
+    pub tx_hash: String,
+    /// Why it could not be applied, for anomalies.
+    pub reason: Option<String>,
+}
+
+/// Something that happened to a seller's invoice, as sent on every channel.
+#[derive(Clone)]
+pub struct InvoiceNotification {
+    pub event: NotificationEvent,
+    pub invoice: Invoice,
+    pub transfer: Option<Transfer>,
+}
+
+impl InvoiceNotification {
+    pub fn paid(invoice: Invoice) -> Self {
+        Self { event: NotificationEvent::InvoicePaid, invoice, transfer: None }
+    }
+
+    pub fn partially_paid(invoice: Invoice, transfer: Transfer) -> Self {
+        Self { event: NotificationEvent::InvoicePartiallyPaid, invoice, transfer: Some(transfer) }
+    }
+
+    pub fn payment_anomaly(invoice: Invoice, transfer: Transfer) -> Self {
+        Self { event: NotificationEvent::PaymentAnomaly, invoice, transfer: Some(transfer) }
+    }
+
+    pub fn expired(invoice: Invoice) -> Self {
+        Self { event: NotificationEvent::InvoiceExpired, invoice, transfer: None }
+    }
+
+    /// The notification a log entry was about, to send it again.
+    pub async fn from_log(db: &crate::api::state::DB, log: &NotificationLog) -> Result<Self, String> {
+        let event = match log.event_type.as_str() {
+            RECEIPT_EVENT => Some(NotificationEvent::InvoicePaid),
+            event_type => NotificationEvent::parse(event_type),
+        }
+            .ok_or_else(|| format!("{} notifications cannot be resent", log.event_type))?;
+        let invoice_id = log.invoice_id.ok_or("The notification is not about an invoice")?;
+        let invoice = db.get_invoice(&invoice_id)
+            .await
+            .map_err(|err| err.to_string())?
+            .ok_or("The invoice was deleted")?;
+        let transfer = log.transfer.clone()
+            .map(serde_json::from_value)
+            .transpose()
+            .map_err(|err| format!("Invalid transfer: {err}"))?;
+        Ok(Self { event, invoice, transfer })
+    }
+
+    fn webhook_event(&self) -> Result<WebhookEvent, String> {
+        let transfer = || self.transfer.clone().ok_or_else(|| format!("{} without a transfer", self.event.as_str()));
+        Ok(match self.event {
+            NotificationEvent::InvoicePaid => WebhookEvent::invoice(WebhookEventType::InvoicePaid, &self.invoice),
+            NotificationEvent::InvoiceExpired => WebhookEvent::invoice(WebhookEventType::InvoiceExpired, &self.invoice),
+            NotificationEvent::InvoicePartiallyPaid => {
+                let transfer = transfer()?;
+                WebhookEvent::partially_paid(&self.invoice, transfer.amount, transfer.payer)
+            }
+            NotificationEvent::PaymentAnomaly => {
+                let transfer = transfer()?;
+                WebhookEvent::payment_anomaly(&self.invoice, transfer.amount, transfer.payer, transfer.reason.unwrap_or_default())
+            }
+            NotificationEvent::SubscriptionExpiring => return Err("subscription.expiring is not an invoice event".to_string()),
+        })
+    }
+}
 
 #[derive(Debug)]
 pub enum Notifier {
+    #[cfg(feature = "mailer")]
     Email(EmailNotifier),
+    #[cfg(feature = "telegram")]
     Telegram(TelegramNotifier),
+    Chat(ChatNotifier),
     Webhooks(WebhooksNotifier),
+    #[cfg(any(feature = "mailer", feature = "telegram"))]
+    Digest(DigestNotifier),
+    #[cfg(feature = "mailer")]
+    Receipt(ReceiptNotifier),
 }
 
+/// Where a notification goes and in which language; texts come from the seller's templates.
+#[derive(Debug)]
+struct Recipient {
+    user_id: Uuid,
+    locale: String,
+}
+
+#[cfg(feature = "mailer")]
 #[derive(Debug)]
 pub struct EmailNotifier {
     email: String,
+    recipient: Recipient,
 }
 
+#[cfg(feature = "telegram")]
 #[derive(Debug)]
 pub struct TelegramNotifier {
+    destination_id: Uuid,
+    label: String,
     chat_id: String,
+    recipient: Recipient,
+}
+
+/// A Slack, Discord or generic chat webhook.
+pub struct ChatNotifier {
+    id: Uuid,
+    label: String,
+    kind: ChatKind,
+    url: String,
+    recipient: Recipient,
+}
+
+/// The URL holds the channel's credentials, so it stays out of logs.
+impl std::fmt::Debug for ChatNotifier {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ChatNotifier")
+            .field("id", &self.id)
+            .field("kind", &self.kind)
+            .field("recipient", &self.recipient)
+            .finish_non_exhaustive()
+    }
 }
 
 #[derive(Debug)]
 pub struct WebhooksNotifier {
-    endpoints: Vec<(String, String)>,  // (url, secret)
+    user_id: Uuid,
+}
+
+/// Mails the buyer a receipt for a paid invoice, in the seller's language and templates.
+#[cfg(feature = "mailer")]
+#[derive(Debug)]
+pub struct ReceiptNotifier {
+    email: String,
+    recipient: Recipient,
+}
+
+/// Queues the notification for the channel's next digest, see [`digest`].
+#[cfg(any(feature = "mailer", feature = "telegram"))]
+#[derive(Debug)]
+pub struct DigestNotifier {
+    channel: NotificationChannel,
+    user_id: Uuid,
 }
 
 impl Notifier {
-    pub fn from_email(email: String) -> Self {
-        Self::Email(EmailNotifier::new(email))
+    #[cfg(feature = "mailer")]
+    pub fn from_email(email: String, user: &User) -> Self {
+        Self::Email(EmailNotifier::new(email, Recipient::new(user)))
+    }
+
+    #[cfg(feature = "telegram")]
+    pub fn from_telegram_destination(destination: TelegramDestination, user: &User) -> Self {
+        Self::Telegram(TelegramNotifier::new(destination, Recipient::new(user)))
+    }
+
+    pub fn from_chat_webhook(kind: ChatKind, chat_webhook: ChatWebhook, user: &User) -> Self {
+        Self::Chat(ChatNotifier::new(kind, chat_webhook, Recipient::new(user)))
     }
 
-    pub fn from_telegram_data(chat_id: String) -> Self {
-        Self::Telegram(TelegramNotifier::new(chat_id))
+    pub fn from_webhooks(user_id: Uuid) -> Self {
+        Self::Webhooks(WebhooksNotifier::new(user_id))
     }
 
-    pub fn from_webhook_endpoints(endpoints: Vec<(String, String)>) -> Self {
-        Self::Webhooks(WebhooksNotifier::new(endpoints))
+    #[cfg(any(feature = "mailer", feature = "telegram"))]
+    pub fn for_digest(channel: NotificationChannel, user_id: Uuid) -> Self {
+        Self::Digest(DigestNotifier::new(channel, user_id))
     }
 
-    pub async fn notify(&self, app_state: Arc<MonitorAppState>, invoice: Invoice) -> Result<(), String> {
+    /// `seller` is the invoice's owner, whose language and templates the receipt uses.
+    #[cfg(feature = "mailer")]
+    pub fn for_buyer_receipt(buyer_email: String, seller: &User) -> Self {
+        Self::Receipt(ReceiptNotifier::new(buyer_email, Recipient::new(seller)))
+    }
+
+    /// Sends the notification and logs the attempt for email, Telegram and chat webhooks;
+    /// webhook deliveries have their own log.
+    pub async fn notify(&self, app_state: Arc<MonitorAppState>, notification: InvoiceNotification) -> Result<(), String> {
+        let log = self.log_entry(&notification);
+        let result = self.deliver(app_state.clone(), notification).await;
+        if let Some(log) = log {
+            let log = NewNotificationLog { error: result.as_ref().err().cloned(), ..log };
+            if let Err(err) = app_state.db.create_notification_log(&log).await {
+                tracing::error!("Failed to log the {} notification of user {}: {err}", log.channel, log.user_id);
+            }
+        }
+        result
+    }
+
+    /// Sends the notification without logging it, e.g. to resend a logged one.
+    pub async fn deliver(&self, app_state: Arc<MonitorAppState>, notification: InvoiceNotification) -> Result<(), String> {
         match self {
-            Notifier::Email(email) => email.notify(app_state, invoice).await,
-            Notifier::Telegram(telegram) => telegram.notify(app_state, invoice).await,
-            Notifier::Webhooks(webhooks) => webhooks.notify(app_state, invoice).await,
+            #[cfg(feature = "mailer")]
+            Notifier::Email(email) => email.notify(app_state, notification).await,
+            #[cfg(feature = "telegram")]
+            Notifier::Telegram(telegram) => telegram.notify(app_state, notification).await,
+            Notifier::Chat(chat) => chat.notify(app_state, notification).await,
+            Notifier::Webhooks(webhooks) => webhooks.notify(app_state, notification).await,
+            #[cfg(any(feature = "mailer", feature = "telegram"))]
+            Notifier::Digest(digest) => digest.notify(app_state, notification).await,
+            #[cfg(feature = "mailer")]
+            Notifier::Receipt(receipt) => receipt.notify(app_state, notification).await,
         }
     }
 
-    pub async fn get_notifiers(db: &DB, user_id: &Uuid) -> Result<Vec<Notifier>, AppError> {
+    /// How the message shows up in `GET /user/notifications`, without the outcome.
+    fn log_entry(&self, notification: &InvoiceNotification) -> Option<NewNotificationLog> {
+        let (recipient, channel, destination_id, destination) = match self {
+            #[cfg(feature = "mailer")]
+            Notifier::Email(email) => (&email.recipient, NotificationChannel::Email, None, mask_email(&email.email)),
+            #[cfg(feature = "mailer")]
+            Notifier::Receipt(receipt) => (&receipt.recipient, NotificationChannel::Email, None, mask_email(&receipt.email)),
+            #[cfg(feature = "telegram")]
+            Notifier::Telegram(telegram) => (&telegram.recipient, NotificationChannel::Telegram, Some(telegram.destination_id), telegram.label.clone()),
+            Notifier::Chat(chat) => (&chat.recipient, NotificationChannel::Chat, Some(chat.id), chat.label.clone()),
+            Notifier::Webhooks(_) => return None,
+            #[cfg(any(feature = "mailer", feature = "telegram"))]
+            Notifier::Digest(_) => return None,
+        };
+        Some(NewNotificationLog {
+            user_id: recipient.user_id,
+            channel: channel.as_str().to_string(),
+            event_type: match self {
+                #[cfg(feature = "mailer")]
+                Notifier::Receipt(_) => RECEIPT_EVENT.to_string(),
+                _ => notification.event.as_str().to_string(),
+            },
+            invoice_id: Some(notification.invoice.id),
+            destination_id,
+            destination,
+            transfer: notification.transfer.as_ref().and_then(|transfer| serde_json::to_value(transfer).ok()),
+            error: None,
+        })
+    }
+
+    /// The notifier behind a log entry, to send it again to the same address or chat.
+    pub async fn from_log(app_state: &MonitorAppState, log: &NotificationLog) -> Result<Self, String> {
+        let db = &app_state.db;
+        let user = db.get_user_by_id(&log.user_id).await.map_err(|err| err.to_string())?;
+
+        match NotificationChannel::parse(&log.channel) {
+            #[cfg(feature = "mailer")]
+            Some(NotificationChannel::Email) if log.event_type == RECEIPT_EVENT => {
+                app_state.mailer.as_ref().ok_or("Mailer is disabled")?;
+                let invoice_id = log.invoice_id.ok_or("The receipt is not about an invoice")?;
+                let invoice = db.get_invoice(&invoice_id)
+                    .await
+                    .map_err(|err| err.to_string())?
+                    .ok_or("The invoice was deleted")?;
+                let email = db.get_buyer_email(&invoice)
+                    .map_err(|err| err.to_string())?
+                    .ok_or("The invoice has no buyer email")?;
+                Ok(Self::for_buyer_receipt(email, &user))
+            }
+            #[cfg(feature = "mailer")]
+            Some(NotificationChannel::Email) => {
+                app_state.mailer.as_ref().ok_or("Mailer is disabled")?;
+                let email = user.email.clone().ok_or("The account has no email address")?;
+                Ok(Self::from_email(email, &user))
+            }
+            #[cfg(feature = "telegram")]
+            Some(NotificationChannel::Telegram) => {
+                app_state.telegram_client.as_ref().ok_or("Telegram is disabled")?;
+                let destination = db.list_telegram_destinations(&user.id)
+                    .await
+                    .map_err(|err| err.to_string())?
+                    .into_iter()
+                    .find(|destination| Some(destination.id) == log.destination_id)
+                    .ok_or("The Telegram chat was unlinked")?;
+                Ok(Self::from_telegram_destination(destination, &user))
+            }
+            Some(NotificationChannel::Chat) => {
+                let destination_id = log.destination_id.ok_or("The chat webhook is unknown")?;
+                let chat_webhook = db.get_chat_webhook(&destination_id, &user.id)
+                    .await
+                    .map_err(|err| err.to_string())?
+                    .ok_or("The chat webhook was deleted")?;
+                let kind = ChatKind::parse(&chat_webhook.kind).ok_or_else(|| format!("Unknown chat webhook kind {}", chat_webhook.kind))?;
+                Ok(Self::from_chat_webhook(kind, chat_webhook, &user))
+            }
+            _ => Err(format!("{} notifications cannot be resent", log.channel)),
+        }
+    }
+
+    /// Channels the user wants `notification` on, skipping integrations that are disabled on
+    /// this instance. `PATCH /user` switches email and Telegram off as a whole; otherwise the
+    /// channel's preference for the event decides, see [`preferences`]. Every Telegram
+    /// destination and enabled chat webhook whose own filter matches gets one. Email and
+    /// Telegram in digest mode get a single queued entry instead.
+    pub async fn get_notifiers(
+        app_state: &MonitorAppState,
+        user_id: &Uuid,
+        notification: &InvoiceNotification,
+    ) -> Result<Vec<Notifier>, AppError> {
         let mut notifiers = vec![];
+        let db = &app_state.db;
+        let event = notification.event;
+        let invoice = &notification.invoice;
 
         let user = db.get_user_by_id(user_id).await?;
+        let preferences = Preferences::new(db.list_notification_preferences(user_id).await?);
+        let wants = |channel| preferences.allows(channel, event, &invoice.amount, invoice.external_id.as_deref());
+        #[cfg(any(feature = "mailer", feature = "telegram"))]
+        let digests = db.list_notification_digests(user_id).await?;
+        #[cfg(any(feature = "mailer", feature = "telegram"))]
+        let in_digest = |channel: NotificationChannel| digests.iter()
+            .any(|stored| stored.channel == channel.as_str()
+                && digest::DigestFrequency::parse(&stored.frequency).is_some_and(|frequency| frequency != digest::DigestFrequency::Immediate));
 
-        if user.email_notification {
-            if let Some(email) = user.email {
-                notifiers.push(Notifier::from_email(email))
+        #[cfg(feature = "mailer")]
+        if user.email_notification && app_state.mailer.is_some() && wants(NotificationChannel::Email) {
+            match (user.email.clone(), in_digest(NotificationChannel::Email)) {
+                (Some(_), true) => notifiers.push(Notifier::for_digest(NotificationChannel::Email, *user_id)),
+                (Some(email), false) => notifiers.push(Notifier::from_email(email, &user)),
+                (None, _) => {}
             }
         }
 
-        if user.telegram_notification {
-            if let Some(chat_id) = user.telegram_chat_id {
-                notifiers.push(Notifier::from_telegram_data(chat_id))
+        // Receipts go to the buyer, whatever the seller's own channels are.
+        #[cfg(feature = "mailer")]
+        if event == NotificationEvent::InvoicePaid && invoice.send_receipt && app_state.mailer.is_some() {
+            if let Some(email) = db.get_buyer_email(invoice)? {
+                notifiers.push(Notifier::for_buyer_receipt(email, &user));
             }
         }
 
-        let webhooks = db.list_webhooks(user_id).await?;
-        if !webhooks.is_empty() {
-            notifiers.push(
-                Notifier::from_webhook_endpoints(
-                    webhooks.into_iter().map(|wh| (wh.url, wh.secret)).collect()
-                )
-            )
+        #[cfg(feature = "telegram")]
+        if user.telegram_notification && app_state.telegram_client.is_some() && wants(NotificationChannel::Telegram) {
+            let destinations = db.list_telegram_destinations(user_id)
+                .await?
+                .into_iter()
+                .filter(|destination| destination.accepts(event.as_str(), &invoice.amount))
+                .collect::<Vec<_>>();
+            // Each chat's filter is applied again when the digest is sent.
+            if in_digest(NotificationChannel::Telegram) && !destinations.is_empty() {
+                notifiers.push(Notifier::for_digest(NotificationChannel::Telegram, *user_id));
+            } else {
+                notifiers.extend(destinations
+                    .into_iter()
+                    .map(|destination| Notifier::from_telegram_destination(destination, &user)));
+            }
         }
 
+        let chat_webhooks = match wants(NotificationChannel::Chat) {
+            true => db.list_chat_webhooks(user_id).await?,
+            false => vec![],
+        };
+        for chat_webhook in chat_webhooks {
+            if !chat_webhook.accepts(event.as_str()) {
+                continue;
+            }
+            match ChatKind::parse(&chat_webhook.kind) {
+                Some(kind) => notifiers.push(Notifier::from_chat_webhook(kind, chat_webhook, &user)),
+                None => tracing::warn!("Skipped chat webhook {} of user {} with unknown kind {}", chat_webhook.id, chat_webhook.user_id, chat_webhook.kind),
+            }
+        }
+
+        // Which endpoints want the event is decided per webhook when publishing.
+        notifiers.push(Notifier::from_webhooks(*user_id));
+
         Ok(notifiers)
     }
 }
 
+/// `j***@example.com`: enough for support to recognise the address.
+#[cfg(feature = "mailer")]
+pub fn mask_email(email: &str) -> String {
+    match email.split_once('@') {
+        Some((local, domain)) => format!("{}***@{domain}", local.chars().take(1).collect::<String>()),
+        None => "***".to_string(),
+    }
+}
+
 trait Notify {
-    async fn notify(&self, app_state: Arc<MonitorAppState>, invoice: Invoice) -> Result<(), String>;
+    async fn notify(&self, app_state: Arc<MonitorAppState>, notification: InvoiceNotification) -> Result<(), String>;
+}
+
+impl Recipient {
+    fn new(user: &User) -> Self {
+        Self { user_id: user.id, locale: user.locale.clone() }
+    }
+
+    async fn render(&self, app_state: &MonitorAppState, notification: &InvoiceNotification) -> Result<(Rendered, TemplateVars), String> {
+        self.render_kind(app_state, notification, notification.event.template_kind()).await
+    }
+
+    async fn render_kind(&self, app_state: &MonitorAppState, notification: &InvoiceNotification, kind: TemplateKind) -> Result<(Rendered, TemplateVars), String> {
+        let vars = TemplateVars::for_notification(notification, &app_state.config.networks, &app_state.config.web_base_url);
+        let rendered = templates::render(&app_state.db, &self.user_id, kind, &self.locale, &vars).await?;
+        Ok((rendered, vars))
+    }
 }
 
+#[cfg(feature = "mailer")]
 impl EmailNotifier {
-    fn new(email: String) -> Self {
-        Self { email }
+    fn new(email: String, recipient: Recipient) -> Self {
+        Self { email, recipient }
     }
 }
 
+#[cfg(feature = "telegram")]
 impl TelegramNotifier {
-    fn new(chat_id: String) -> Self {
-        Self { chat_id }
+    fn new(destination: TelegramDestination, recipient: Recipient) -> Self {
+        Self { destination_id: destination.id, label: destination.label, chat_id: destination.chat_id, recipient }
+    }
+}
+
+impl ChatNotifier {
+    fn new(kind: ChatKind, chat_webhook: ChatWebhook, recipient: Recipient) -> Self {
+        Self { id: chat_webhook.id, label: chat_webhook.label, kind, url: chat_webhook.url, recipient }
     }
 }
 
 impl WebhooksNotifier {
-    fn new(endpoints: Vec<(String, String)>) -> Self {
-        Self { endpoints }
+    fn new(user_id: Uuid) -> Self {
+        Self { user_id }
+    }
+}
+
+#[cfg(feature = "mailer")]
+impl ReceiptNotifier {
+    fn new(email: String, recipient: Recipient) -> Self {
+        Self { email, recipient }
+    }
+}
+
+#[cfg(any(feature = "mailer", feature = "telegram"))]
+impl DigestNotifier {
+    fn new(channel: NotificationChannel, user_id: Uuid) -> Self {
+        Self { channel, user_id }
     }
 }
 
+#[cfg(feature = "mailer")]
 impl Notify for EmailNotifier {
-    async fn notify(&self, app_state: Arc<MonitorAppState>, invoice: Invoice) -> Result<(), String> {
-        let email = self.email.clone();
-        let url = invoice.web_url()?;
-        let mailer = app_state.mailer.clone();
+    async fn notify(&self, app_state: Arc<MonitorAppState>, notification: InvoiceNotification) -> Result<(), String> {
+        let mailer = app_state.mailer.clone().ok_or("Mailer is disabled")?;
+        let (message, _) = self.recipient.render(&app_state, &notification).await?;
+        let email = Email {
+            to: self.email.clone(),
+            subject: message.subject,
+            text: message.text,
+            html: Some(message.html),
+            tags: vec!["InvoiceNotification".to_string()],
+        };
         utils::retry(2, || {
             let email = email.clone();
-            let url = url.clone();
             let mailer = mailer.clone();
-            async move { mailer.send_invoice_paid(&email, &url).await }
+            async move { mailer.send(&email).await }
         }).await
     }
 }
 
+#[cfg(feature = "mailer")]
+impl Notify for ReceiptNotifier {
+    async fn notify(&self, app_state: Arc<MonitorAppState>, notification: InvoiceNotification) -> Result<(), String> {
+        let mailer = app_state.mailer.clone().ok_or("Mailer is disabled")?;
+        let (message, _) = self.recipient.render_kind(&app_state, &notification, TemplateKind::BuyerReceipt).await?;
+        let email = Email {
+            to: self.email.clone(),
+            subject: message.subject,
+            text: message.text,
+            html: Some(message.html),
+            tags: vec!["BuyerReceipt".to_string()],
+        };
+        utils::retry(2, || {
+            let email = email.clone();
+            let mailer = mailer.clone();
+            async move { mailer.send(&email).await }
+        }).await
+    }
+}
+
+#[cfg(feature = "telegram")]
 impl Notify for TelegramNotifier {
-    async fn notify(&self, app_state: Arc<MonitorAppState>, invoice: Invoice) -> Result<(), String> {
+    async fn notify(&self, app_state: Arc<MonitorAppState>, notification: InvoiceNotification) -> Result<(), String> {
         let chat_id = self.chat_id.clone();
-        let client = app_state.telegram_client.clone();
+        let client = app_state.telegram_client.clone().ok_or("Telegram is disabled")?;
+        let url = notification.invoice.web_url(&app_state.config.web_base_url);
+        let (message, _) = self.recipient.render(&app_state, &notification).await?;
         utils::retry(1, || {
             let chat_id = chat_id.clone();
             let client = client.clone();
-            let invoice = invoice.clone();
-            async move { client.send_invoice_paid(&chat_id, &invoice).await }
+            let text = message.telegram.clone();
+            let button = (message.telegram_button.clone(), url.clone());
+            async move { client.send_markdown(&chat_id, &text, Some(button)).await }
         }).await
     }
 }
 
-#[derive(Deserialize, Serialize)]
+impl Notify for ChatNotifier {
+    async fn notify(&self, app_state: Arc<MonitorAppState>, notification: InvoiceNotification) -> Result<(), String> {
+        let (rendered, vars) = self.recipient.render(&app_state, &notification).await?;
+        let message = ChatMessage::new(&rendered, &vars, &self.recipient.locale);
+        utils::retry(1, || self.kind.send(&app_state.webhooker, &self.url, &message)).await
+    }
+}
+
+/// Body sent to `legacy` webhooks for `invoice.paid`.
+#[derive(Deserialize, Serialize, utoipa::ToSchema)]
 pub struct InvoicePaidNotification {
     pub id: Uuid,
     paid_at: Option<NaiveDateTime>,
@@ -139,36 +539,111 @@ pub struct InvoicePaidNotification {
 }
 
 impl InvoicePaidNotification {
-    fn new(id: Uuid, paid_at: Option<NaiveDateTime>, status: String) -> Self {
+    pub(crate) fn new(id: Uuid, paid_at: Option<NaiveDateTime>, status: String) -> Self {
         Self { id, paid_at, status }
     }
+}
+
+/// Queues the event for the subscribed endpoints; `monitoring::webhook_outbox` sends them and
+/// retries with backoff.
+impl Notify for WebhooksNotifier {
+    async fn notify(&self, app_state: Arc<MonitorAppState>, notification: InvoiceNotification) -> Result<(), String> {
+        let event = notification.webhook_event()?;
+        webhook_event::publish(&app_state.db, &self.user_id, &event)
+            .await
+            .map(|_| ())
+            .map_err(|err| err.to_string())
+    }
+}
 
-    fn from_invoice(invoice: &Invoice) -> Self {
-        Self::new(invoice.id, invoice.paid_at, "SUCCESS".to_string())
+#[cfg(any(feature = "mailer", feature = "telegram"))]
+impl Notify for DigestNotifier {
+    async fn notify(&self, app_state: Arc<MonitorAppState>, notification: InvoiceNotification) -> Result<(), String> {
+        let invoice = &notification.invoice;
+        let item = NewNotificationDigestItem {
+            user_id: self.user_id,
+            channel: self.channel.as_str().to_string(),
+            event_type: notification.event.as_str().to_string(),
+            invoice_id: invoice.id,
+            amount: invoice.amount.clone(),
+            network: notification.transfer.as_ref().map(|transfer| transfer.network).or(invoice.paid_network),
+            external_id: invoice.external_id.clone(),
+        };
+        app_state.db.create_notification_digest_item(&item)
+            .await
+            .map(|_| ())
+            .map_err(|err| err.to_string())
     }
 }
 
-impl Notify for WebhooksNotifier {
-    async fn notify(&self, app_state: Arc<MonitorAppState>, invoice: Invoice) -> Result<(), String> {
-        let payload = serde_json::to_value(InvoicePaidNotification::from_invoice(&invoice))
-            .map_err(|err| utils::make_err(Box::new(err), "notification into json"))?;
-
-        for (url, secret) in &self.endpoints {
-            let webhooker = app_state.webhooker.clone();
-            let url = url.clone();
-            let secret = secret.clone();
-            let payload = payload.clone();
-            if let Err(err) = utils::retry(2, || {
-                let webhooker = webhooker.clone();
-                let url = url.clone();
-                let secret = secret.clone();
-                let payload = payload.clone();
-                async move { webhooker.send(&url, &secret, &payload).await }
-            }).await {
-                error!(err)
+#[cfg(all(test, feature = "mailer"))]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    #[rstest]
+    #[case("seller@example.com", "s***@example.com")]
+    #[case("@example.com", "***@example.com")]
+    #[case("not-an-address", "***")]
+    fn test_mask_email(#[case] email: &str, #[case] masked: &str) {
+        assert_eq!(mask_email(email), masked);
+    }
+
+    #[tokio::test]
+    async fn test_receipt_is_mailed_to_the_buyer() {
+        use crate::api::state::DB;
+        use crate::db::invoice::{InvoiceOptions, InvoicePayment};
+        use crate::db::notification_log::NotificationLogFilter;
+        use crate::mailer::{Mailer, MemorySink};
+
+        let db = DB::in_memory();
+        let sink = MemorySink::default();
+        let app_state = MonitorAppState {
+            mailer: Some(Mailer::with_transport(Arc::new(sink.clone()))),
+            ..MonitorAppState::for_tests(db.clone())
+        };
+        let seller = db.get_or_create_user("seller", None).await.unwrap();
+        let pay = |invoice: Invoice| {
+            let db = db.clone();
+            async move {
+                let payment = InvoicePayment {
+                    seller: invoice.seller.clone(),
+                    amount: invoice.amount.clone(),
+                    buyer: "0xbuyer".to_string(),
+                    paid_at: chrono::Utc::now().naive_utc(),
+                    network: 1,
+                    tx_hash: "0xtx".to_string(),
+                };
+                db.set_invoice_paid(invoice.id, &payment).await.unwrap()
             }
-        }
+        };
+        let notify = |invoice: Invoice| {
+            let app_state = app_state.clone();
+            async move {
+                let notification = InvoiceNotification::paid(invoice);
+                for notifier in Notifier::get_notifiers(&app_state, &seller.id, &notification).await.unwrap() {
+                    notifier.notify(Arc::new(app_state.clone()), notification.clone()).await.unwrap();
+                }
+            }
+        };
+
+        let options = InvoiceOptions { send_receipt: true, buyer_email: Some("buyer@example.com".to_string()), ..Default::default() };
+        let invoice = db.create_invoice_with_options(BigDecimal::from(10), "0xseller", &[1], Some(seller.id), Some("order-7".to_string()), options)
+            .await
+            .unwrap();
+        notify(pay(invoice).await).await;
+
+        let sent = sink.sent();
+        assert_eq!(sent.len(), 1);
+        assert_eq!((sent[0].to.as_str(), sent[0].subject.as_str()), ("buyer@example.com", "Payment receipt for 10 USDT"));
+        assert!(sent[0].text.contains("Seller: 0xseller") && sent[0].text.contains("Transaction: 0xtx"), "{}", sent[0].text);
+        let logs = db.list_notification_logs(&seller.id, &NotificationLogFilter::default(), 10, 0).await.unwrap();
+        assert_eq!((logs[0].event_type.as_str(), logs[0].destination.as_str()), (RECEIPT_EVENT, "b***@example.com"));
 
-        Ok(())
+        // Receipts are off unless asked for, even with an address.
+        let invoice = db.create_invoice(BigDecimal::from(10), "0xseller", &[1], Some(seller.id), None, false).await.unwrap();
+        let invoice = db.set_invoice_buyer_email(&invoice.id, "buyer@example.com", false).await.unwrap().unwrap();
+        notify(pay(invoice).await).await;
+        assert_eq!(sink.sent().len(), 1);
     }
 }
@@ -0,0 +1,180 @@
+//! Which of a seller's channels get which notifications. Every channel and event has a
+//! default; a stored [`NotificationPreference`] replaces it and may add rules on the invoice.
+//! Telegram chats and chat webhooks also filter by their own event types.
+
+use bigdecimal::BigDecimal;
+use serde::{Deserialize, Serialize};
+use crate::db::notification_preference::NotificationPreference;
+use super::templates::TemplateKind;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize, Serialize, utoipa::ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum NotificationChannel {
+    Email,
+    Telegram,
+    /// Slack, Discord and generic chat webhooks.
+    Chat,
+}
+
+impl NotificationChannel {
+    pub const ALL: [Self; 3] = [Self::Email, Self::Telegram, Self::Chat];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Email => "email",
+            Self::Telegram => "telegram",
+            Self::Chat => "chat",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|channel| channel.as_str() == value)
+    }
+}
+
+/// What a seller can be notified about. Names match the webhook event catalog where the
+/// event is also a webhook event.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize, Serialize, utoipa::ToSchema)]
+pub enum NotificationEvent {
+    #[serde(rename = "invoice.paid")]
+    InvoicePaid,
+    #[serde(rename = "invoice.partially_paid")]
+    InvoicePartiallyPaid,
+    #[serde(rename = "invoice.expired")]
+    InvoiceExpired,
+    #[serde(rename = "payment.anomaly")]
+    PaymentAnomaly,
+    /// A subscription of the seller ends soon.
+    #[serde(rename = "subscription.expiring")]
+    SubscriptionExpiring,
+}
+
+impl NotificationEvent {
+    pub const ALL: [Self; 5] = [
+        Self::InvoicePaid,
+        Self::InvoicePartiallyPaid,
+        Self::InvoiceExpired,
+        Self::PaymentAnomaly,
+        Self::SubscriptionExpiring,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::InvoicePaid => "invoice.paid",
+            Self::InvoicePartiallyPaid => "invoice.partially_paid",
+            Self::InvoiceExpired => "invoice.expired",
+            Self::PaymentAnomaly => "payment.anomaly",
+            Self::SubscriptionExpiring => "subscription.expiring",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|event| event.as_str() == value)
+    }
+
+    /// Expired invoices are routine for most shops, so they are opt-in.
+    pub fn enabled_by_default(&self) -> bool {
+        !matches!(self, Self::InvoiceExpired)
+    }
+
+    /// Whether the event is about an invoice, so amount and `external_id` rules apply.
+    pub fn is_about_invoice(&self) -> bool {
+        !matches!(self, Self::SubscriptionExpiring)
+    }
+
+    /// The texts sent for the event on email, Telegram and chat.
+    pub fn template_kind(&self) -> TemplateKind {
+        match self {
+            Self::InvoicePaid => TemplateKind::InvoicePaid,
+            Self::InvoicePartiallyPaid => TemplateKind::InvoicePartiallyPaid,
+            Self::PaymentAnomaly => TemplateKind::PaymentAnomaly,
+            Self::InvoiceExpired => TemplateKind::InvoiceExpired,
+            Self::SubscriptionExpiring => TemplateKind::SubscriptionExpiring,
+        }
+    }
+}
+
+/// A seller's stored preferences, answering for every channel and event.
+pub struct Preferences(Vec<NotificationPreference>);
+
+impl Preferences {
+    pub fn new(preferences: Vec<NotificationPreference>) -> Self {
+        Self(preferences)
+    }
+
+    pub fn get(&self, channel: NotificationChannel, event: NotificationEvent) -> Option<&NotificationPreference> {
+        self.0.iter().find(|preference| preference.channel == channel.as_str() && preference.event_type == event.as_str())
+    }
+
+    /// Whether `channel` gets `event` about an invoice of `amount`: the stored preference and
+    /// its rules, or the event's default.
+    pub fn allows(&self, channel: NotificationChannel, event: NotificationEvent, amount: &BigDecimal, external_id: Option<&str>) -> bool {
+        match self.get(channel, event) {
+            Some(preference) => preference.accepts(amount, external_id),
+            None => event.enabled_by_default(),
+        }
+    }
+
+    /// Whether `channel` gets an event that is not about an invoice, which has no rules.
+    pub fn enables(&self, channel: NotificationChannel, event: NotificationEvent) -> bool {
+        self.get(channel, event).map_or(event.enabled_by_default(), |preference| preference.enabled)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::NaiveDateTime;
+    use rstest::rstest;
+    use uuid::Uuid;
+
+    fn preference(event: NotificationEvent, enabled: bool, min_amount: Option<&str>, prefix: Option<&str>) -> NotificationPreference {
+        NotificationPreference {
+            user_id: Uuid::nil(),
+            channel: "email".to_string(),
+            event_type: event.as_str().to_string(),
+            enabled,
+            min_amount: min_amount.map(|amount| amount.parse().unwrap()),
+            external_id_prefix: prefix.map(str::to_string),
+            updated_at: NaiveDateTime::default(),
+        }
+    }
+
+    #[rstest]
+    #[case(NotificationEvent::InvoicePaid, true)]
+    #[case(NotificationEvent::PaymentAnomaly, true)]
+    #[case(NotificationEvent::InvoiceExpired, false)]
+    fn test_defaults(#[case] event: NotificationEvent, #[case] expected: bool) {
+        let preferences = Preferences::new(vec![]);
+        assert_eq!(preferences.allows(NotificationChannel::Email, event, &BigDecimal::from(10), None), expected);
+    }
+
+    #[rstest]
+    #[case(preference(NotificationEvent::InvoicePaid, false, None, None), "500", Some("shop-1"), false)]
+    #[case(preference(NotificationEvent::InvoicePaid, true, Some("100"), None), "100", None, true)]
+    #[case(preference(NotificationEvent::InvoicePaid, true, Some("100"), None), "99.5", None, false)]
+    #[case(preference(NotificationEvent::InvoicePaid, true, None, Some("shop-")), "1", Some("shop-42"), true)]
+    #[case(preference(NotificationEvent::InvoicePaid, true, None, Some("shop-")), "1", Some("pos-42"), false)]
+    #[case(preference(NotificationEvent::InvoicePaid, true, None, Some("shop-")), "1", None, false)]
+    #[case(preference(NotificationEvent::InvoiceExpired, true, None, None), "1", None, true)]
+    fn test_rules(
+        #[case] stored: NotificationPreference,
+        #[case] amount: &str,
+        #[case] external_id: Option<&str>,
+        #[case] expected: bool,
+    ) {
+        let event = NotificationEvent::parse(&stored.event_type).unwrap();
+        let preferences = Preferences::new(vec![stored]);
+        let amount = amount.parse().unwrap();
+
+        assert_eq!(preferences.allows(NotificationChannel::Email, event, &amount, external_id), expected);
+        assert!(preferences.allows(NotificationChannel::Telegram, NotificationEvent::InvoicePaid, &amount, external_id));
+    }
+
+    #[test]
+    fn test_enables() {
+        let preferences = Preferences::new(vec![preference(NotificationEvent::SubscriptionExpiring, false, None, None)]);
+        assert!(!preferences.enables(NotificationChannel::Email, NotificationEvent::SubscriptionExpiring));
+        assert!(preferences.enables(NotificationChannel::Telegram, NotificationEvent::SubscriptionExpiring));
+    }
+}
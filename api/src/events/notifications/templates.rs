@@ -0,0 +1,484 @@
+//! Notification texts: built-in templates per locale, which sellers can override per locale
+//! for notifications about their invoices and subscriptions.
+//!
+//! `{{name}}` inserts a variable, escaped for where it ends up (HTML in the email body,
+//! MarkdownV2 in Telegram); `{{#name}}...{{/name}}` keeps its content only when the variable
+//! is not empty.
+
+use std::collections::BTreeMap;
+use bigdecimal::BigDecimal;
+use uuid::Uuid;
+use crate::api::state::DB;
+use crate::db::billing::Subscription;
+use crate::db::notification_template::NotificationTemplate;
+use crate::db::Invoice;
+use crate::network::Network;
+use super::InvoiceNotification;
+
+pub const LOCALES: &[&str] = &["en", "es", "ru"];
+pub const DEFAULT_LOCALE: &str = "en";
+
+/// Names usable in templates, see [`TemplateVars::for_notification`].
+pub const VARIABLES: &[&str] = &[
+    "invoice_id", "invoice_url", "amount", "network", "external_id", "buyer", "tx_hash", "explorer_link", "paid_at",
+    "paid_amount", "reason", "seller", "expires_at", "plan", "until", "renew_url",
+];
+
+/// Telegram refuses longer messages.
+const MAX_TELEGRAM_LENGTH: usize = 4096;
+const MAX_TEMPLATE_LENGTH: usize = 20_000;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TemplateKind {
+    InvoicePaid,
+    InvoicePartiallyPaid,
+    PaymentAnomaly,
+    InvoiceExpired,
+    /// Mailed to the buyer of a paid invoice, see [`super::ReceiptNotifier`].
+    BuyerReceipt,
+    /// Sent by `monitoring::subscription_expiry` before a subscription ends.
+    SubscriptionExpiring,
+}
+
+impl TemplateKind {
+    pub const ALL: [Self; 6] = [
+        Self::InvoicePaid, Self::InvoicePartiallyPaid, Self::PaymentAnomaly, Self::InvoiceExpired, Self::BuyerReceipt,
+        Self::SubscriptionExpiring,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::InvoicePaid => "invoice_paid",
+            Self::InvoicePartiallyPaid => "invoice_partially_paid",
+            Self::PaymentAnomaly => "payment_anomaly",
+            Self::InvoiceExpired => "invoice_expired",
+            Self::BuyerReceipt => "buyer_receipt",
+            Self::SubscriptionExpiring => "subscription_expiring",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|kind| kind.as_str() == value)
+    }
+
+    fn builtin(&self, locale: &str) -> Builtin {
+        macro_rules! builtin {
+            ($locale:literal, $kind:literal, $button:literal) => {
+                Builtin {
+                    subject: include_str!(concat!("templates/", $locale, "/", $kind, ".subject.txt")),
+                    text: include_str!(concat!("templates/", $locale, "/", $kind, ".txt")),
+                    html: include_str!(concat!("templates/", $locale, "/", $kind, ".html")),
+                    telegram: include_str!(concat!("templates/", $locale, "/", $kind, ".md")),
+                    button: $button,
+                }
+            };
+        }
+
+        macro_rules! localized {
+            ($kind:literal) => {
+                localized!($kind, "View invoice", "Ver factura", "Открыть счёт")
+            };
+            ($kind:literal, $en:literal, $es:literal, $ru:literal) => {
+                match locale {
+                    "es" => builtin!("es", $kind, $es),
+                    "ru" => builtin!("ru", $kind, $ru),
+                    _ => builtin!("en", $kind, $en),
+                }
+            };
+        }
+
+        match self {
+            Self::InvoicePaid => localized!("invoice_paid"),
+            Self::InvoicePartiallyPaid => localized!("invoice_partially_paid"),
+            Self::PaymentAnomaly => localized!("payment_anomaly"),
+            Self::InvoiceExpired => localized!("invoice_expired"),
+            Self::BuyerReceipt => localized!("buyer_receipt"),
+            Self::SubscriptionExpiring => localized!("subscription_expiring", "Renew", "Renovar", "Продлить"),
+        }
+    }
+}
+
+struct Builtin {
+    subject: &'static str,
+    text: &'static str,
+    html: &'static str,
+    telegram: &'static str,
+    /// Label of the Telegram button that opens the invoice, or the settings to renew.
+    button: &'static str,
+}
+
+/// A notification ready to send on every channel.
+#[derive(Debug, PartialEq)]
+pub struct Rendered {
+    pub subject: String,
+    pub text: String,
+    pub html: String,
+    /// MarkdownV2.
+    pub telegram: String,
+    pub telegram_button: String,
+}
+
+/// Values for the template variables; absent ones are empty.
+#[derive(Clone, Debug, Default)]
+pub struct TemplateVars(BTreeMap<&'static str, String>);
+
+impl TemplateVars {
+    pub fn for_invoice(invoice: &Invoice, networks: &[Network], web_base_url: &str) -> Self {
+        let mut vars = BTreeMap::new();
+        vars.insert("invoice_id", invoice.id.to_string());
+        vars.insert("invoice_url", invoice.web_url(web_base_url));
+        vars.insert("amount", format_amount(&invoice.amount));
+        vars.insert("external_id", invoice.external_id.clone().unwrap_or_default());
+        vars.insert("buyer", invoice.buyer.clone().unwrap_or_default());
+        vars.insert("seller", invoice.seller.clone());
+        vars.insert(
+            "paid_at",
+            invoice.paid_at.map(|paid_at| paid_at.format("%Y-%m-%d %H:%M UTC").to_string()).unwrap_or_default(),
+        );
+        vars.insert(
+            "expires_at",
+            invoice.expires_at.map(|expires_at| expires_at.format("%Y-%m-%d %H:%M UTC").to_string()).unwrap_or_default(),
+        );
+        let mut vars = Self(vars);
+        vars.set_transaction(networks, invoice.paid_network, invoice.tx_hash.as_deref());
+        vars
+    }
+
+    /// The invoice's variables, with the transfer that did not settle it for partial payments
+    /// and anomalies.
+    pub fn for_notification(notification: &InvoiceNotification, networks: &[Network], web_base_url: &str) -> Self {
+        let mut vars = Self::for_invoice(&notification.invoice, networks, web_base_url);
+        if let Some(transfer) = &notification.transfer {
+            vars.0.insert("paid_amount", format_amount(&transfer.amount));
+            vars.0.insert("buyer", transfer.payer.clone());
+            vars.0.insert("reason", transfer.reason.clone().unwrap_or_default());
+            vars.set_transaction(networks, Some(transfer.network), Some(&transfer.tx_hash));
+        }
+        vars
+    }
+
+    fn set_transaction(&mut self, networks: &[Network], network_id: Option<i32>, tx_hash: Option<&str>) {
+        let network = network_id.and_then(|id| networks.iter().find(|network| network.id == id as i64));
+        let explorer_link = network
+            .zip(tx_hash)
+            .and_then(|(network, tx_hash)| network.tx_url(tx_hash));
+        self.0.insert("network", network.map(|network| network.name.clone()).unwrap_or_default());
+        self.0.insert("tx_hash", tx_hash.unwrap_or_default().to_string());
+        self.0.insert("explorer_link", explorer_link.unwrap_or_default());
+    }
+
+    /// The plan, named in `locale`, when it ends and where to renew it.
+    pub fn for_subscription(subscription: &Subscription, locale: &str, web_base_url: &str) -> Self {
+        let mut vars = BTreeMap::new();
+        vars.insert("plan", plan_name(&subscription.target, locale).to_string());
+        vars.insert("until", subscription.until.format("%Y-%m-%d %H:%M UTC").to_string());
+        vars.insert("renew_url", format!("{web_base_url}/settings"));
+        Self(vars)
+    }
+
+    /// Made-up values for test messages, e.g. `POST /user/chat_webhook/{id}/test`.
+    pub fn sample(web_base_url: &str) -> Self {
+        let invoice_id = Uuid::nil();
+        let mut vars = BTreeMap::new();
+        vars.insert("invoice_id", invoice_id.to_string());
+        vars.insert("invoice_url", crate::utils::get_invoice_url(web_base_url, &invoice_id));
+        vars.insert("amount", "25".to_string());
+        vars.insert("network", "Ethereum".to_string());
+        vars.insert("external_id", "order-42".to_string());
+        vars.insert("buyer", "0x0000000000000000000000000000000000000b0b".to_string());
+        vars.insert("seller", "0x00000000000000000000000000000000000005e1".to_string());
+        Self(vars)
+    }
+
+    pub fn get(&self, name: &str) -> &str {
+        self.0.get(name).map(String::as_str).unwrap_or_default()
+    }
+}
+
+/// Falls back to the stored target for plans without a name.
+fn plan_name<'a>(target: &'a str, locale: &str) -> &'a str {
+    match (target, locale) {
+        ("unlimited_invoices", "es") => "Facturas ilimitadas",
+        ("unlimited_invoices", "ru") => "Безлимитные счета",
+        ("unlimited_invoices", _) => "Unlimited invoices",
+        ("private_invoices", "es") => "Facturas privadas",
+        ("private_invoices", "ru") => "Приватные счета",
+        ("private_invoices", _) => "Private invoices",
+        ("high_priority_blockchain_checking", "es") => "Comprobación prioritaria",
+        ("high_priority_blockchain_checking", "ru") => "Приоритетная проверка",
+        ("high_priority_blockchain_checking", _) => "High-priority checking",
+        (target, _) => target,
+    }
+}
+
+/// `10.500000` as `10.5`.
+pub fn format_amount(amount: &BigDecimal) -> String {
+    let plain = amount.to_plain_string();
+    match plain.contains('.') {
+        true => plain.trim_end_matches('0').trim_end_matches('.').to_string(),
+        false => plain,
+    }
+}
+
+#[derive(Clone, Copy)]
+pub(super) enum Format {
+    Text,
+    Html,
+    TelegramMarkdown,
+}
+
+pub(super) fn escape(format: Format, value: &str) -> String {
+    match format {
+        Format::Text => value.to_string(),
+        Format::Html => value
+            .replace('&', "&amp;")
+            .replace('<', "&lt;")
+            .replace('>', "&gt;")
+            .replace('"', "&quot;")
+            .replace('\'', "&#39;"),
+        Format::TelegramMarkdown => value.chars().fold(String::new(), |mut escaped, c| {
+            if "_*[]()~`>#+-=|{}.!\\".contains(c) {
+                escaped.push('\\');
+            }
+            escaped.push(c);
+            escaped
+        }),
+    }
+}
+
+#[derive(Debug)]
+enum Node<'a> {
+    Text(&'a str),
+    Var(&'a str),
+    Section(&'a str, Vec<Node<'a>>),
+}
+
+fn parse(template: &str) -> Result<Vec<Node<'_>>, String> {
+    // Open sections with the nodes collected so far at each level.
+    let mut stack: Vec<(&str, Vec<Node>)> = vec![("", vec![])];
+    let mut rest = template;
+
+    while let Some(start) = rest.find("{{") {
+        let nodes = &mut stack.last_mut().expect("root level is never popped").1;
+        if start > 0 {
+            nodes.push(Node::Text(&rest[..start]));
+        }
+        let end = rest[start..].find("}}").ok_or("unclosed {{")? + start;
+        let tag = rest[start + 2..end].trim();
+        rest = &rest[end + 2..];
+
+        if let Some(name) = tag.strip_prefix('#') {
+            stack.push((known(name.trim())?, vec![]));
+        } else if let Some(name) = tag.strip_prefix('/') {
+            let name = name.trim();
+            if stack.len() == 1 {
+                return Err(format!("{{{{/{name}}}}} without {{{{#{name}}}}}"));
+            }
+            let (open, section) = stack.pop().expect("checked above");
+            if open != name {
+                return Err(format!("{{{{#{open}}}}} closed by {{{{/{name}}}}}"));
+            }
+            stack.last_mut().expect("root level is never popped").1.push(Node::Section(open, section));
+        } else {
+            nodes.push(Node::Var(known(tag)?));
+        }
+    }
+
+    if stack.len() > 1 {
+        return Err(format!("{{{{#{}}}}} is not closed", stack.last().expect("checked above").0));
+    }
+    let (_, mut nodes) = stack.pop().expect("root level is never popped");
+    if !rest.is_empty() {
+        nodes.push(Node::Text(rest));
+    }
+    Ok(nodes)
+}
+
+fn known(name: &str) -> Result<&str, String> {
+    match VARIABLES.contains(&name) {
+        true => Ok(name),
+        false => Err(format!("unknown variable {name}, expected one of {}", VARIABLES.join(", "))),
+    }
+}
+
+fn render_nodes(nodes: &[Node], vars: &TemplateVars, format: Format, out: &mut String) {
+    for node in nodes {
+        match node {
+            Node::Text(text) => out.push_str(text),
+            Node::Var(name) => out.push_str(&escape(format, vars.get(name))),
+            Node::Section(name, nodes) if !vars.get(name).is_empty() => render_nodes(nodes, vars, format, out),
+            Node::Section(..) => {}
+        }
+    }
+}
+
+fn render_template(template: &str, vars: &TemplateVars, format: Format) -> Result<String, String> {
+    let mut out = String::new();
+    render_nodes(&parse(template)?, vars, format, &mut out);
+    Ok(out)
+}
+
+/// Checks a seller's override before it is saved.
+pub fn validate(template: &NotificationTemplate) -> Result<(), String> {
+    let fields = [
+        ("subject", &template.subject),
+        ("text_body", &template.text_body),
+        ("html_body", &template.html_body),
+        ("telegram_body", &template.telegram_body),
+    ];
+
+    for (field, value) in fields {
+        let Some(value) = value else { continue };
+        if value.len() > MAX_TEMPLATE_LENGTH {
+            return Err(format!("{field}: longer than {MAX_TEMPLATE_LENGTH} characters"));
+        }
+        parse(value).map_err(|err| format!("{field}: {err}"))?;
+    }
+
+    match &template.telegram_body {
+        Some(body) if body.chars().count() > MAX_TELEGRAM_LENGTH => {
+            Err(format!("telegram_body: longer than {MAX_TELEGRAM_LENGTH} characters"))
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Renders `kind` in `locale` (the default locale when unsupported), preferring the override
+/// of the seller `user_id` field by field.
+pub async fn render(db: &DB, user_id: &Uuid, kind: TemplateKind, locale: &str, vars: &TemplateVars) -> Result<Rendered, String> {
+    let locale = match LOCALES.contains(&locale) {
+        true => locale,
+        false => DEFAULT_LOCALE,
+    };
+    let builtin = kind.builtin(locale);
+    let custom = db.get_notification_template(user_id, kind.as_str(), locale)
+        .await
+        .map_err(|err| err.to_string())?;
+    let custom = |field: fn(&NotificationTemplate) -> &Option<String>| {
+        custom.as_ref().and_then(|template| field(template).as_deref())
+    };
+
+    let subject = render_template(custom(|t| &t.subject).unwrap_or(builtin.subject), vars, Format::Text)?;
+    Ok(Rendered {
+        subject: subject.lines().map(str::trim).collect::<Vec<_>>().join(" ").trim().to_string(),
+        text: render_template(custom(|t| &t.text_body).unwrap_or(builtin.text), vars, Format::Text)?,
+        html: render_template(custom(|t| &t.html_body).unwrap_or(builtin.html), vars, Format::Html)?,
+        telegram: render_template(custom(|t| &t.telegram_body).unwrap_or(builtin.telegram), vars, Format::TelegramMarkdown)?,
+        telegram_button: builtin.button.to_string(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    fn vars(entries: &[(&'static str, &str)]) -> TemplateVars {
+        TemplateVars(entries.iter().map(|(name, value)| (*name, value.to_string())).collect())
+    }
+
+    #[rstest]
+    #[case("Paid {{amount}}", "Paid 10.5")]
+    #[case("{{#external_id}}Order {{ external_id }}. {{/external_id}}Done", "Order A-1. Done")]
+    #[case("{{#buyer}}Buyer: {{buyer}}{{/buyer}}Done", "Done")]
+    #[case("{{#external_id}}{{#buyer}}never{{/buyer}}{{external_id}}{{/external_id}}", "A-1")]
+    fn test_render(#[case] template: &str, #[case] expected: &str) {
+        let vars = vars(&[("amount", "10.5"), ("external_id", "A-1")]);
+        assert_eq!(render_template(template, &vars, Format::Text).unwrap(), expected);
+    }
+
+    #[test]
+    fn test_values_are_escaped_per_format() {
+        let vars = vars(&[("external_id", "<b>1_000.5</b>")]);
+
+        assert_eq!(render_template("{{external_id}}", &vars, Format::Text).unwrap(), "<b>1_000.5</b>");
+        assert_eq!(render_template("<p>{{external_id}}</p>", &vars, Format::Html).unwrap(), "<p>&lt;b&gt;1_000.5&lt;/b&gt;</p>");
+        assert_eq!(render_template("*{{external_id}}*", &vars, Format::TelegramMarkdown).unwrap(), r"*<b\>1\_000\.5</b\>*");
+    }
+
+    #[rstest]
+    #[case("{{amount", "unclosed {{")]
+    #[case("{{secret}}", "unknown variable secret")]
+    #[case("{{#buyer}}x", "{{#buyer}} is not closed")]
+    #[case("x{{/buyer}}", "{{/buyer}} without {{#buyer}}")]
+    #[case("{{#buyer}}x{{/amount}}", "{{#buyer}} closed by {{/amount}}")]
+    fn test_invalid_templates(#[case] template: &str, #[case] message: &str) {
+        let err = parse(template).unwrap_err();
+        assert!(err.contains(message), "{err}");
+    }
+
+    #[rstest]
+    #[case("10.500000", "10.5")]
+    #[case("10.000000", "10")]
+    #[case("10", "10")]
+    #[case("0.000001", "0.000001")]
+    fn test_format_amount(#[case] amount: &str, #[case] expected: &str) {
+        assert_eq!(format_amount(&amount.parse().unwrap()), expected);
+    }
+
+    #[test]
+    fn test_builtin_templates_are_valid() {
+        for (kind, locale) in TemplateKind::ALL.iter().flat_map(|kind| LOCALES.iter().map(move |locale| (kind, locale))) {
+            let builtin = kind.builtin(locale);
+            for template in [builtin.subject, builtin.text, builtin.html, builtin.telegram] {
+                parse(template).unwrap_or_else(|err| panic!("{} {locale}: {err}", kind.as_str()));
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_render_prefers_seller_overrides_per_field() {
+        let db = DB::in_memory();
+        let seller = db.get_or_create_user("seller", None).await.unwrap();
+        let vars = vars(&[("invoice_id", "42"), ("amount", "10"), ("external_id", "order-7")]);
+
+        let builtin = render(&db, &seller.id, TemplateKind::InvoicePaid, "es", &vars).await.unwrap();
+        assert_eq!(builtin.subject, "Tu factura ha sido pagada");
+        assert!(builtin.text.contains("order-7"));
+
+        db.upsert_notification_template(&NotificationTemplate {
+            user_id: seller.id,
+            kind: "invoice_paid".to_string(),
+            locale: "es".to_string(),
+            subject: Some("Pedido {{external_id}} pagado".to_string()),
+            text_body: None,
+            html_body: None,
+            telegram_body: Some("*{{amount}}* pagados".to_string()),
+            updated_at: Default::default(),
+        }).await.unwrap();
+
+        let custom = render(&db, &seller.id, TemplateKind::InvoicePaid, "es", &vars).await.unwrap();
+        assert_eq!(custom.subject, "Pedido order-7 pagado");
+        assert_eq!(custom.text, builtin.text);
+        assert_eq!(custom.telegram, "*10* pagados");
+
+        let fallback = render(&db, &seller.id, TemplateKind::InvoicePaid, "xx", &vars).await.unwrap();
+        assert_eq!(fallback.subject, "Your invoice has been paid");
+    }
+
+    #[tokio::test]
+    async fn test_transfer_details_are_rendered() {
+        use crate::events::notifications::Transfer;
+
+        let db = DB::in_memory();
+        let seller = db.get_or_create_user("seller", None).await.unwrap();
+        let invoice = db.create_invoice(BigDecimal::from(10), "0xseller", &[1], Some(seller.id), Some("shop-7".to_string()), false)
+            .await
+            .unwrap();
+        let transfer = Transfer {
+            amount: "12.5".parse().unwrap(),
+            payer: "0xpayer".to_string(),
+            network: 1,
+            tx_hash: "0xtx".to_string(),
+            reason: Some("overpaid: required 10, got 12.5".to_string()),
+        };
+
+        let vars = TemplateVars::for_notification(&InvoiceNotification::payment_anomaly(invoice, transfer), &[], "https://pay.example.com");
+        assert_eq!((vars.get("paid_amount"), vars.get("buyer"), vars.get("tx_hash")), ("12.5", "0xpayer", "0xtx"));
+
+        let rendered = render(&db, &seller.id, TemplateKind::PaymentAnomaly, "en", &vars).await.unwrap();
+        assert_eq!(rendered.subject, "A payment to your invoice needs attention");
+        assert!(rendered.text.contains("A transfer of 12.5 USDT to your invoice of 10 USDT could not be applied: overpaid: required 10, got 12.5."));
+        assert!(rendered.telegram.contains("External ID: `shop\\-7`"), "{}", rendered.telegram);
+    }
+}
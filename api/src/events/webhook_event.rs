@@ -0,0 +1,289 @@
+//! Catalog of events sent to merchant webhooks and the envelope they travel in.
+
+use std::str::FromStr;
+use bigdecimal::BigDecimal;
+use chrono::{NaiveDateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tracing::error;
+use uuid::Uuid;
+use crate::api::payments::InvoiceResponse;
+use crate::api::state::DB;
+use crate::db::Invoice;
+use crate::error::AppError;
+use crate::events::notifications::InvoicePaidNotification;
+use crate::utils;
+
+/// Envelope version given to new webhooks. Bump it when a payload changes incompatibly.
+pub const API_VERSION: &str = "2026-10-18";
+/// Webhooks created before the catalog existed; they only get `invoice.paid` in the old flat shape.
+pub const LEGACY_API_VERSION: &str = "legacy";
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize, utoipa::ToSchema)]
+pub enum WebhookEventType {
+    #[serde(rename = "invoice.created")]
+    InvoiceCreated,
+    #[serde(rename = "invoice.paid")]
+    InvoicePaid,
+    /// Edited with `PATCH /payment/invoice/{id}`; the changed fields are in its timeline.
+    #[serde(rename = "invoice.updated")]
+    InvoiceUpdated,
+    /// Less than the invoice amount arrived; the invoice stays unpaid.
+    #[serde(rename = "invoice.partially_paid")]
+    InvoicePartiallyPaid,
+    #[serde(rename = "invoice.expired")]
+    InvoiceExpired,
+    #[serde(rename = "invoice.deleted")]
+    InvoiceDeleted,
+    #[serde(rename = "subscription.activated")]
+    SubscriptionActivated,
+    /// A transfer that could not be applied: the invoice was already paid, expired or void,
+    /// the seller does not match or more than the invoice amount arrived.
+    #[serde(rename = "payment.anomaly")]
+    PaymentAnomaly,
+    /// Sent on demand from `POST /user/webhook/{id}/test`; cannot be subscribed to.
+    #[serde(rename = "webhook.test", skip_deserializing)]
+    WebhookTest,
+}
+
+impl WebhookEventType {
+    /// Everything a webhook can subscribe to.
+    pub const ALL: [Self; 8] = [
+        Self::InvoiceCreated,
+        Self::InvoicePaid,
+        Self::InvoiceUpdated,
+        Self::InvoicePartiallyPaid,
+        Self::InvoiceExpired,
+        Self::InvoiceDeleted,
+        Self::SubscriptionActivated,
+        Self::PaymentAnomaly,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::InvoiceCreated => "invoice.created",
+            Self::InvoicePaid => "invoice.paid",
+            Self::InvoiceUpdated => "invoice.updated",
+            Self::InvoicePartiallyPaid => "invoice.partially_paid",
+            Self::InvoiceExpired => "invoice.expired",
+            Self::InvoiceDeleted => "invoice.deleted",
+            Self::SubscriptionActivated => "subscription.activated",
+            Self::PaymentAnomaly => "payment.anomaly",
+            Self::WebhookTest => "webhook.test",
+        }
+    }
+}
+
+impl FromStr for WebhookEventType {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::ALL.into_iter()
+            .find(|event_type| event_type.as_str() == s)
+            .ok_or_else(|| format!("Unknown event type: {s}"))
+    }
+}
+
+/// An on-chain transfer to an invoice that did not mark it paid.
+#[derive(Clone, Debug, Serialize, utoipa::ToSchema)]
+pub struct PaymentEventData {
+    pub invoice: InvoiceResponse,
+    #[schema(value_type = String, example = "9.50")]
+    pub paid_amount: BigDecimal,
+    pub payer: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reason: Option<String>,
+}
+
+#[derive(Clone, Debug, Serialize, utoipa::ToSchema)]
+pub struct SubscriptionEventData {
+    pub target: String,
+    pub until: NaiveDateTime,
+}
+
+#[derive(Clone, Debug, Serialize, utoipa::ToSchema)]
+pub struct TestEventData {
+    pub webhook_id: Uuid,
+}
+
+/// `invoice.*` events carry the same invoice object as `GET /payment/invoice/{id}`.
+#[derive(Clone, Debug, Serialize, utoipa::ToSchema)]
+#[serde(untagged)]
+pub enum WebhookEventData {
+    Invoice(InvoiceResponse),
+    Payment(PaymentEventData),
+    Subscription(SubscriptionEventData),
+    Test(TestEventData),
+}
+
+/// What a webhook receives: `{ "id", "type", "created", "api_version", "data" }`.
+/// `id` is unique per event and stays the same across redeliveries.
+#[derive(Clone, Debug, Serialize, utoipa::ToSchema)]
+pub struct WebhookEvent {
+    pub id: Uuid,
+    #[serde(rename = "type")]
+    pub event_type: WebhookEventType,
+    /// Unix timestamp, seconds.
+    pub created: i64,
+    #[schema(value_type = String, example = "2026-10-18")]
+    pub api_version: &'static str,
+    pub data: WebhookEventData,
+}
+
+impl WebhookEvent {
+    fn new(event_type: WebhookEventType, data: WebhookEventData) -> Self {
+        Self {
+            id: Uuid::new_v4(),
+            event_type,
+            created: Utc::now().timestamp(),
+            api_version: API_VERSION,
+            data,
+        }
+    }
+
+    /// `invoice.created`, `invoice.paid`, `invoice.updated`, `invoice.expired` or `invoice.deleted`.
+    pub fn invoice(event_type: WebhookEventType, invoice: &Invoice) -> Self {
+        Self::new(event_type, WebhookEventData::Invoice(invoice.clone().into()))
+    }
+
+    pub fn partially_paid(invoice: &Invoice, paid_amount: BigDecimal, payer: String) -> Self {
+        Self::new(
+            WebhookEventType::InvoicePartiallyPaid,
+            WebhookEventData::Payment(PaymentEventData { invoice: invoice.clone().into(), paid_amount, payer, reason: None }),
+        )
+    }
+
+    pub fn payment_anomaly(invoice: &Invoice, paid_amount: BigDecimal, payer: String, reason: String) -> Self {
+        Self::new(
+            WebhookEventType::PaymentAnomaly,
+            WebhookEventData::Payment(PaymentEventData { invoice: invoice.clone().into(), paid_amount, payer, reason: Some(reason) }),
+        )
+    }
+
+    pub fn subscription_activated(target: String, until: NaiveDateTime) -> Self {
+        Self::new(
+            WebhookEventType::SubscriptionActivated,
+            WebhookEventData::Subscription(SubscriptionEventData { target, until }),
+        )
+    }
+
+    pub fn test(webhook_id: Uuid) -> Self {
+        Self::new(WebhookEventType::WebhookTest, WebhookEventData::Test(TestEventData { webhook_id }))
+    }
+
+    /// Body for a webhook on `api_version`; `None` when that version cannot carry this event.
+    pub fn payload_for(&self, api_version: &str) -> Result<Option<Value>, String> {
+        let payload = match (api_version, &self.data) {
+            (LEGACY_API_VERSION, WebhookEventData::Invoice(invoice)) if self.event_type == WebhookEventType::InvoicePaid => {
+                serde_json::to_value(InvoicePaidNotification::new(invoice.id, invoice.paid_at, "SUCCESS".to_string()))
+            }
+            (LEGACY_API_VERSION, _) if self.event_type != WebhookEventType::WebhookTest => return Ok(None),
+            _ => serde_json::to_value(self),
+        };
+
+        payload
+            .map(Some)
+            .map_err(|err| utils::make_err(Box::new(err), "webhook event into json"))
+    }
+}
+
+/// Queues `event` for every webhook of `user_id` subscribed to its type;
+/// `monitoring::webhook_outbox` sends them. Returns how many deliveries were queued.
+pub async fn publish(db: &DB, user_id: &Uuid, event: &WebhookEvent) -> Result<usize, AppError> {
+    let webhooks = db.list_webhooks_for_event(user_id, event.event_type.as_str()).await?;
+    let mut queued = 0;
+
+    for webhook in webhooks {
+        let Some(payload) = event.payload_for(&webhook.api_version).map_err(AppError::Internal)? else {
+            continue;
+        };
+
+        match db.enqueue_webhook_delivery(&webhook.id, &payload).await {
+            Ok(_) => queued += 1,
+            Err(err) => error!("Failed to queue {} for webhook {}: {err}", event.event_type.as_str(), webhook.id),
+        }
+    }
+
+    Ok(queued)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+    use serde_json::json;
+
+    async fn invoice(db: &DB, user_id: Uuid) -> Invoice {
+        db.create_invoice(BigDecimal::from(10), "0x01", &[1], Some(user_id), Some("order-1".to_string()), false)
+            .await
+            .unwrap()
+    }
+
+    #[rstest]
+    #[case("invoice.created", WebhookEventType::InvoiceCreated)]
+    #[case("invoice.partially_paid", WebhookEventType::InvoicePartiallyPaid)]
+    #[case("payment.anomaly", WebhookEventType::PaymentAnomaly)]
+    fn test_event_type_names(#[case] name: &str, #[case] event_type: WebhookEventType) {
+        assert_eq!(event_type.as_str(), name);
+        assert_eq!(name.parse::<WebhookEventType>().unwrap(), event_type);
+        assert_eq!(serde_json::to_value(event_type).unwrap(), json!(name));
+    }
+
+    #[rstest]
+    #[case("invoice.refunded")]
+    #[case("webhook.test")]
+    fn test_unsubscribable_event_type(#[case] name: &str) {
+        assert!(name.parse::<WebhookEventType>().is_err());
+        assert!(serde_json::from_value::<WebhookEventType>(json!(name)).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_envelope() {
+        let db = DB::in_memory();
+        let invoice = invoice(&db, Uuid::new_v4()).await;
+        let event = WebhookEvent::invoice(WebhookEventType::InvoiceCreated, &invoice);
+
+        let payload = event.payload_for(API_VERSION).unwrap().unwrap();
+        assert_eq!(payload["id"], json!(event.id));
+        assert_eq!(payload["type"], json!("invoice.created"));
+        assert_eq!(payload["api_version"], json!(API_VERSION));
+        assert_eq!(payload["data"]["id"], json!(invoice.id));
+        assert_eq!(payload["data"]["external_id"], json!("order-1"));
+        assert_eq!(payload["data"]["amount"], json!("10"));
+        assert!(payload["data"].get("tx_hash").is_some());
+    }
+
+    #[tokio::test]
+    async fn test_legacy_payload() {
+        let db = DB::in_memory();
+        let invoice = invoice(&db, Uuid::new_v4()).await;
+
+        let paid = WebhookEvent::invoice(WebhookEventType::InvoicePaid, &invoice);
+        let payload = paid.payload_for(LEGACY_API_VERSION).unwrap().unwrap();
+        assert_eq!(payload, json!({"id": invoice.id, "paid_at": null, "status": "SUCCESS"}));
+
+        let created = WebhookEvent::invoice(WebhookEventType::InvoiceCreated, &invoice);
+        assert!(created.payload_for(LEGACY_API_VERSION).unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_publish_only_to_subscribed_webhooks() {
+        let db = DB::in_memory();
+        let user_id = Uuid::new_v4();
+        let invoice = invoice(&db, user_id).await;
+        let all = WebhookEventType::ALL.map(|event_type| event_type.as_str().to_string());
+        let everything = db.create_webhook("https://a.example", "", &user_id, &all, API_VERSION).await.unwrap();
+        let paid_only = db.create_webhook("https://b.example", "", &user_id, &["invoice.paid".to_string()], API_VERSION)
+            .await
+            .unwrap();
+
+        let event = WebhookEvent::invoice(WebhookEventType::InvoiceCreated, &invoice);
+        assert_eq!(publish(&db, &user_id, &event).await.unwrap(), 1);
+        assert_eq!(db.list_webhook_deliveries(&everything.id, 10, 0).await.unwrap().len(), 1);
+        assert!(db.list_webhook_deliveries(&paid_only.id, 10, 0).await.unwrap().is_empty());
+
+        let event = WebhookEvent::invoice(WebhookEventType::InvoicePaid, &invoice);
+        assert_eq!(publish(&db, &user_id, &event).await.unwrap(), 2);
+        assert_eq!(publish(&db, &Uuid::new_v4(), &event).await.unwrap(), 0);
+    }
+}
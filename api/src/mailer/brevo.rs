@@ -0,0 +1,122 @@
+use async_trait::async_trait;
+use reqwest::Client;
+use serde_json::json;
+use crate::config::SenderConfig;
+use crate::utils;
+use super::{Email, MailTransport};
+
+const BREVO_URL: &str = "https://api.brevo.com/v3/smtp/email";
+
+/// Sends through Brevo's transactional email API.
+pub struct BrevoTransport {
+    client: Client,
+    api_key: String,
+    sender: SenderConfig,
+    url: String,
+}
+
+impl BrevoTransport {
+    pub fn new(api_key: String, sender: SenderConfig) -> Self {
+        Self { client: Client::new(), api_key, sender, url: BREVO_URL.to_string() }
+    }
+
+    #[cfg(test)]
+    fn with_url(mut self, url: String) -> Self {
+        self.url = url;
+        self
+    }
+
+    fn body(&self, email: &Email) -> serde_json::Value {
+        let mut body = json!({
+            "sender": { "email": self.sender.email },
+            "to": [{ "email": email.to }],
+            "subject": email.subject,
+            "textContent": email.text,
+            "tags": email.tags,
+        });
+
+        if let Some(name) = &self.sender.name {
+            body["sender"]["name"] = json!(name);
+        }
+        if let Some(reply_to) = &self.sender.reply_to {
+            body["replyTo"] = json!({ "email": reply_to });
+        }
+        if let Some(html) = &email.html {
+            body["htmlContent"] = json!(html);
+        }
+
+        body
+    }
+}
+
+#[async_trait]
+impl MailTransport for BrevoTransport {
+    async fn send(&self, email: &Email) -> Result<(), String> {
+        let response = self.client
+            .post(&self.url)
+            .header("accept", "application/json")
+            .header("api-key", &self.api_key)
+            .json(&self.body(email))
+            .send()
+            .await
+            .map_err(|err| utils::make_err(Box::new(err), "send email"))?;
+
+        let status = response.status();
+        if !status.is_success() {
+            let text = response.text().await;
+            return Err(format!("Failed to send notification email: status={:?}, text={:?}", status, text));
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+    use axum::http::{HeaderMap, StatusCode};
+    use axum::routing::post;
+    use axum::{Json, Router};
+    use serde_json::Value;
+    use crate::mailer::tests::sender;
+    use super::*;
+
+    /// The `api-key` header and JSON body of each request.
+    type Received = Arc<Mutex<Vec<(Option<String>, Value)>>>;
+
+    #[tokio::test]
+    async fn test_send() {
+        let received = Received::default();
+        let app = Router::new().route("/v3/smtp/email", post({
+            let received = received.clone();
+            move |headers: HeaderMap, Json(body): Json<Value>| async move {
+                let api_key = headers.get("api-key").and_then(|value| value.to_str().ok()).map(str::to_string);
+                received.lock().unwrap().push((api_key, body));
+                StatusCode::CREATED
+            }
+        }));
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/v3/smtp/email", listener.local_addr().unwrap());
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+
+        let transport = BrevoTransport::new("brevo-key".to_string(), sender()).with_url(url.clone());
+        let email = Email {
+            to: "merchant@example.com".to_string(),
+            subject: "Paid".to_string(),
+            text: "text".to_string(),
+            html: Some("<p>html</p>".to_string()),
+            tags: vec!["InvoiceNotification".to_string()],
+        };
+        transport.send(&email).await.unwrap();
+
+        let (api_key, body) = received.lock().unwrap().pop().unwrap();
+        assert_eq!(api_key.as_deref(), Some("brevo-key"));
+        assert_eq!(body["sender"], json!({ "email": "noreply@example.com", "name": "CryoPay" }));
+        assert_eq!(body["replyTo"], json!({ "email": "support@example.com" }));
+        assert_eq!(body["to"], json!([{ "email": "merchant@example.com" }]));
+        assert_eq!(body["htmlContent"], "<p>html</p>");
+
+        let failing = BrevoTransport::new("k".to_string(), sender()).with_url(format!("{url}/missing"));
+        assert!(failing.send(&email).await.unwrap_err().contains("404"));
+    }
+}
@@ -1,65 +1,90 @@
-use reqwest::{self, Client};
-use serde_json::json;
-use crate::utils;
+//! Outgoing email. [`Mailer`] writes the messages; a [`MailTransport`] delivers them
+//! (Brevo's HTTP API, SMTP, or a file/in-memory sink for development and tests).
+
+use std::sync::Arc;
+use async_trait::async_trait;
+use crate::config::{MailTransportConfig, MailerConfig};
+
+mod brevo;
+mod sink;
+mod smtp;
+
+pub use brevo::BrevoTransport;
+pub use sink::FileSink;
+#[cfg(test)]
+pub use sink::MemorySink;
+pub use smtp::SmtpTransport;
+
+/// A message to a single recipient. The sender comes from the transport.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Email {
+    pub to: String,
+    pub subject: String,
+    pub text: String,
+    pub html: Option<String>,
+    /// Provider-side tags for filtering statistics; ignored by transports without them.
+    pub tags: Vec<String>,
+}
+
+/// Delivers emails as the sender (address, name, reply-to) it was created with.
+#[async_trait]
+pub trait MailTransport: Send + Sync {
+    async fn send(&self, email: &Email) -> Result<(), String>;
+}
 
 #[derive(Clone)]
 pub struct Mailer {
-    api_key: String,
+    transport: Arc<dyn MailTransport>,
 }
 
 impl Mailer {
-    pub fn new() -> Result<Self, String> {
-        let api_key = utils::get_env_var("BREVO_API_KEY")?;
-        Ok(Self { api_key })
+    pub fn new(config: &MailerConfig) -> Result<Self, String> {
+        let sender = config.sender.clone();
+        let transport: Arc<dyn MailTransport> = match &config.transport {
+            MailTransportConfig::Brevo { api_key } => Arc::new(BrevoTransport::new(api_key.clone(), sender)),
+            MailTransportConfig::Smtp(smtp) => Arc::new(SmtpTransport::new(smtp, sender)?),
+            MailTransportConfig::File { dir } => Arc::new(FileSink::new(dir.into(), sender)),
+        };
+
+        Ok(Self::with_transport(transport))
     }
 
-    pub async fn send_invoice_paid(&self, recipient_email: &str, invoice_url: &str) -> Result<(), String> {
-        send_invoice_paid(&self.api_key, recipient_email, invoice_url).await
+    pub fn with_transport(transport: Arc<dyn MailTransport>) -> Self {
+        Self { transport }
     }
-}
 
-async fn send_invoice_paid(api_key: &str, recipient_email: &str, invoice_link: &str) -> Result<(), String> {
-    let sender_email = utils::get_env_var("EMAIL_SENDER")?;
-    let email_data = json!({
-        "sender": {
-            "email": sender_email
-        },
-        "to": [
-            {
-                "email": recipient_email
-            }
-        ],
-        "textContent": format!(
-            "Hello, \n\nYour invoice has been successfully paid. \
-            You can view the invoice at the following link: {}\n\nBest regards, \nCryoPay",
-            invoice_link
-        ),
-        "subject": "Your Invoice Has Been Paid",
-        "tags": ["InvoiceNotification"]
-    });
+    pub async fn send(&self, email: &Email) -> Result<(), String> {
+        self.transport.send(email).await
+    }
+}
 
-    let client = Client::new();
-    let response = client
-        .post("https://api.brevo.com/v3/smtp/email")
-        .header("accept", "application/json")
-        .header("api-key", api_key)
-        .header("content-type", "application/json")
-        .json(&email_data)
-        .send()
-        .await
-        .map_err(|err| utils::make_err(Box::new(err), "send email"))?;
+#[cfg(test)]
+pub mod tests {
+    use crate::config::SenderConfig;
+    use super::*;
 
-    let status = response.status();
-    if !status.is_success() {
-        let text = response.text().await;
-        return Err(
-            format!(
-                "Failed to send notification email: status={:?}, text={:?}",
-                status,
-                text,
-            )
-        )
+    pub fn sender() -> SenderConfig {
+        SenderConfig {
+            email: "noreply@example.com".to_string(),
+            name: Some("CryoPay".to_string()),
+            reply_to: Some("support@example.com".to_string()),
+        }
     }
 
-    Ok(())
+    #[tokio::test]
+    async fn test_send_goes_through_the_transport() {
+        let sink = MemorySink::default();
+        let mailer = Mailer::with_transport(Arc::new(sink.clone()));
+        let email = Email {
+            to: "merchant@example.com".to_string(),
+            subject: "Your invoice has been paid".to_string(),
+            text: "https://example.com/invoice/1".to_string(),
+            html: None,
+            tags: vec![],
+        };
+
+        mailer.send(&email).await.unwrap();
+
+        assert_eq!(sink.sent(), vec![email]);
+    }
 }
@@ -0,0 +1,98 @@
+use std::path::PathBuf;
+#[cfg(test)]
+use std::sync::{Arc, Mutex};
+use async_trait::async_trait;
+use chrono::Utc;
+use uuid::Uuid;
+use crate::config::SenderConfig;
+use crate::utils;
+use super::{smtp, Email, MailTransport};
+
+/// Writes each email as `<timestamp>-<uuid>.eml` into a directory instead of sending it;
+/// the files open in any mail client.
+pub struct FileSink {
+    dir: PathBuf,
+    sender: SenderConfig,
+}
+
+impl FileSink {
+    pub fn new(dir: PathBuf, sender: SenderConfig) -> Self {
+        Self { dir, sender }
+    }
+}
+
+#[async_trait]
+impl MailTransport for FileSink {
+    async fn send(&self, email: &Email) -> Result<(), String> {
+        let message = smtp::message(&self.sender, email)?;
+        let path = self.dir.join(format!("{}-{}.eml", Utc::now().format("%Y%m%dT%H%M%S%.3f"), Uuid::new_v4()));
+
+        tokio::fs::create_dir_all(&self.dir)
+            .await
+            .map_err(|err| utils::make_err(Box::new(err), &format!("create {}", self.dir.display())))?;
+        tokio::fs::write(&path, message.formatted())
+            .await
+            .map_err(|err| utils::make_err(Box::new(err), &format!("write {}", path.display())))
+    }
+}
+
+/// Keeps sent emails in memory so tests can assert on them. Clones share the list.
+#[cfg(test)]
+#[derive(Clone, Default)]
+pub struct MemorySink {
+    sent: Arc<Mutex<Vec<Email>>>,
+    /// Refuses every email with it instead, like a transport that is down.
+    error: Option<String>,
+}
+
+#[cfg(test)]
+impl MemorySink {
+    pub fn failing(error: &str) -> Self {
+        Self { error: Some(error.to_string()), ..Self::default() }
+    }
+
+    pub fn sent(&self) -> Vec<Email> {
+        self.sent.lock().unwrap_or_else(|poisoned| poisoned.into_inner()).clone()
+    }
+}
+
+#[cfg(test)]
+#[async_trait]
+impl MailTransport for MemorySink {
+    async fn send(&self, email: &Email) -> Result<(), String> {
+        if let Some(error) = &self.error {
+            return Err(error.clone());
+        }
+        self.sent.lock().unwrap_or_else(|poisoned| poisoned.into_inner()).push(email.clone());
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::mailer::tests::sender;
+    use super::*;
+
+    #[tokio::test]
+    async fn test_file_sink_writes_eml() {
+        let dir = std::env::temp_dir().join(format!("cryo-mail-{}", Uuid::new_v4()));
+        let sink = FileSink::new(dir.clone(), sender());
+
+        sink.send(&Email {
+            to: "merchant@example.com".to_string(),
+            subject: "Your Invoice Has Been Paid".to_string(),
+            text: "Paid".to_string(),
+            html: None,
+            tags: vec![],
+        }).await.unwrap();
+
+        let files = std::fs::read_dir(&dir).unwrap().map(|entry| entry.unwrap().path()).collect::<Vec<_>>();
+        assert_eq!(files.len(), 1);
+        assert_eq!(files[0].extension().unwrap(), "eml");
+        let contents = std::fs::read_to_string(&files[0]).unwrap();
+        assert!(contents.contains("To: merchant@example.com"));
+        assert!(contents.contains("Reply-To: support@example.com"));
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
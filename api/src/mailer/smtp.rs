@@ -0,0 +1,152 @@
+use async_trait::async_trait;
+use lettre::message::header::ContentType;
+use lettre::message::{Mailbox, MultiPart};
+use lettre::transport::smtp::authentication::Credentials;
+use lettre::{AsyncSmtpTransport, AsyncTransport, Message, Tokio1Executor};
+use crate::config::{SenderConfig, SmtpConfig, SmtpSecurity};
+use crate::utils;
+use super::{Email, MailTransport};
+
+/// Sends through an SMTP relay with STARTTLS, implicit TLS or, for local relays, no encryption.
+pub struct SmtpTransport {
+    transport: AsyncSmtpTransport<Tokio1Executor>,
+    sender: SenderConfig,
+}
+
+impl SmtpTransport {
+    pub fn new(config: &SmtpConfig, sender: SenderConfig) -> Result<Self, String> {
+        let builder = match config.security {
+            SmtpSecurity::StartTls => AsyncSmtpTransport::<Tokio1Executor>::starttls_relay(&config.host)
+                .map_err(|err| utils::make_err(Box::new(err), "configure SMTP STARTTLS"))?,
+            SmtpSecurity::Tls => AsyncSmtpTransport::<Tokio1Executor>::relay(&config.host)
+                .map_err(|err| utils::make_err(Box::new(err), "configure SMTP TLS"))?,
+            SmtpSecurity::None => AsyncSmtpTransport::<Tokio1Executor>::builder_dangerous(&config.host),
+        };
+
+        let builder = match (&config.username, &config.password) {
+            (Some(username), Some(password)) => builder.credentials(Credentials::new(username.clone(), password.clone())),
+            _ => builder,
+        };
+
+        Ok(Self { transport: builder.port(config.port).build(), sender })
+    }
+}
+
+/// The email as a MIME message: plain text, or text and HTML alternatives.
+pub(super) fn message(sender: &SenderConfig, email: &Email) -> Result<Message, String> {
+    let mailbox = |address: &str| address.parse::<Mailbox>()
+        .map_err(|err| utils::make_err(Box::new(err), &format!("parse email address {address}")));
+
+    let from = Mailbox::new(sender.name.clone(), mailbox(&sender.email)?.email);
+    let mut builder = Message::builder()
+        .from(from)
+        .to(mailbox(&email.to)?)
+        .subject(&email.subject);
+    if let Some(reply_to) = &sender.reply_to {
+        builder = builder.reply_to(mailbox(reply_to)?);
+    }
+
+    match &email.html {
+        None => builder.header(ContentType::TEXT_PLAIN).body(email.text.clone()),
+        Some(html) => builder.multipart(MultiPart::alternative_plain_html(email.text.clone(), html.clone())),
+    }.map_err(|err| utils::make_err(Box::new(err), "build email"))
+}
+
+#[async_trait]
+impl MailTransport for SmtpTransport {
+    async fn send(&self, email: &Email) -> Result<(), String> {
+        self.transport
+            .send(message(&self.sender, email)?)
+            .await
+            .map(|_| ())
+            .map_err(|err| utils::make_err(Box::new(err), "send email via SMTP"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+    use tokio::net::TcpListener;
+    use crate::mailer::tests::sender;
+    use super::*;
+
+    /// Minimal SMTP server that accepts one message and returns the DATA section.
+    async fn smtp_stand_in(listener: TcpListener) -> String {
+        let (stream, _) = listener.accept().await.unwrap();
+        let (read, mut write) = stream.into_split();
+        let mut lines = BufReader::new(read).lines();
+        let mut data = String::new();
+        let mut in_data = false;
+
+        write.write_all(b"220 localhost ESMTP\r\n").await.unwrap();
+        while let Some(line) = lines.next_line().await.unwrap() {
+            let reply: &[u8] = if in_data {
+                if line != "." {
+                    data.push_str(&line);
+                    data.push('\n');
+                    continue;
+                }
+                in_data = false;
+                b"250 queued\r\n"
+            } else if line.starts_with("EHLO") {
+                b"250 localhost\r\n"
+            } else if line == "DATA" {
+                in_data = true;
+                b"354 go ahead\r\n"
+            } else if line == "QUIT" {
+                write.write_all(b"221 bye\r\n").await.unwrap();
+                break;
+            } else {
+                b"250 ok\r\n"
+            };
+            write.write_all(reply).await.unwrap();
+        }
+
+        data
+    }
+
+    #[tokio::test]
+    async fn test_send() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let config = SmtpConfig {
+            host: "127.0.0.1".to_string(),
+            port: listener.local_addr().unwrap().port(),
+            security: SmtpSecurity::None,
+            username: None,
+            password: None,
+        };
+        let server = tokio::spawn(smtp_stand_in(listener));
+
+        let transport = SmtpTransport::new(&config, sender()).unwrap();
+        transport.send(&Email {
+            to: "merchant@example.com".to_string(),
+            subject: "Your Invoice Has Been Paid".to_string(),
+            text: "Paid".to_string(),
+            html: None,
+            tags: vec![],
+        }).await.unwrap();
+        drop(transport);
+
+        let data = server.await.unwrap();
+        assert!(data.contains("From: CryoPay <noreply@example.com>"), "{data}");
+        assert!(data.contains("Reply-To: support@example.com"), "{data}");
+        assert!(data.contains("To: merchant@example.com"), "{data}");
+        assert!(data.contains("Subject: Your Invoice Has Been Paid"), "{data}");
+    }
+
+    #[test]
+    fn test_html_is_an_alternative() {
+        let email = Email {
+            to: "merchant@example.com".to_string(),
+            subject: "s".to_string(),
+            text: "plain".to_string(),
+            html: Some("<p>rich</p>".to_string()),
+            tags: vec![],
+        };
+        let formatted = String::from_utf8(message(&sender(), &email).unwrap().formatted()).unwrap();
+
+        assert!(formatted.contains("multipart/alternative"));
+        assert!(formatted.contains("plain") && formatted.contains("<p>rich</p>"));
+        assert!(message(&sender(), &Email { to: "not an address".to_string(), ..email }).is_err());
+    }
+}
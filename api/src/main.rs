@@ -1,15 +1,20 @@
-use std::sync::Arc;
+use clap::Parser;
 use tracing::Level;
 
 mod utils;
 pub mod error;
 mod api;
+mod cli;
 mod db;
 mod events;
 mod network;
+#[cfg(feature = "telegram")]
 mod telegram;
+#[cfg(feature = "mailer")]
 mod mailer;
 mod payments;
+mod config;
+mod shutdown;
 mod monitoring;
 
 #[tokio::main]
@@ -19,34 +24,5 @@ async fn main() -> Result<(), String> {
         .with_env_filter(tracing_subscriber::EnvFilter::from_default_env())
         .init();
 
-    let test = false;
-
-    let networks = network::Network::default_vec()?;
-    let db = api::state::DB::new().await?;
-    let telegram_client = telegram::TelegramClient::new().await?;
-    let daemon_health = Arc::new(monitoring::health::DaemonHealth::new());
-
-    let monitor_networks = networks.clone();
-    let (monitor_db, monitor_telegram_client) = (db.clone(), telegram_client.clone());
-    let monitor_health = daemon_health.clone();
-    let monitor_handle = tokio::spawn(async move {
-        monitoring::daemon::process_networks(
-            test, monitor_networks, &monitor_db, &monitor_telegram_client, monitor_health,
-        ).await
-    });
-
-    let (api_db, api_telegram_client) = (db.clone(), telegram_client.clone());
-    let api_health = daemon_health.clone();
-    let api_handle = tokio::spawn(async move {
-        api::run_api(networks, api_db, api_telegram_client, api_health).await
-    });
-
-    let (bot_db, bot_telegram_client) = (db.clone(), telegram_client.clone());
-    let bot_handle = tokio::spawn(async move {
-        bot_telegram_client.run_as_bot(bot_db).await
-    });
-
-    let _ = tokio::join!(api_handle, monitor_handle, bot_handle);
-
-    Ok(())
+    cli::Cli::parse().run().await
 }
@@ -1,34 +1,117 @@
-use std::time::{Duration, SystemTime, UNIX_EPOCH};
-use hmac::{Hmac, Mac};
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
 use reqwest::Client;
 use serde_json::Value;
-use sha2::Sha256;
+use url::Url;
+use cryo_webhook::{signature_header, SIGNATURE_HEADER, TIMESTAMP_HEADER};
 use crate::api::state::DB;
+use crate::config::MonitorConfig;
+#[cfg(feature = "mailer")]
 use crate::mailer::Mailer;
+#[cfg(feature = "telegram")]
 use crate::telegram::TelegramClient;
 use crate::utils;
+use crate::utils::ssrf::OutboundGuard;
 
 #[derive(Clone)]
 pub struct MonitorAppState {
+    pub config: Arc<MonitorConfig>,
     pub db: DB,
-    pub telegram_client: TelegramClient,
-    pub mailer: Mailer,
+    /// `None` when the Telegram integration is disabled.
+    #[cfg(feature = "telegram")]
+    pub telegram_client: Option<TelegramClient>,
+    /// `None` when email notifications are disabled.
+    #[cfg(feature = "mailer")]
+    pub mailer: Option<Mailer>,
     pub webhooker: Webhooker,
 }
 
 impl MonitorAppState {
-    pub fn new(db: DB, telegram_client: TelegramClient) -> Result<Self, String> {
-        let mailer = Mailer::new()?;
-        let webhooker = Webhooker;
-        Ok(Self { db, telegram_client, mailer, webhooker })
+    pub fn new(
+        config: Arc<MonitorConfig>, db: DB,
+        #[cfg(feature = "telegram")] telegram_client: Option<TelegramClient>,
+    ) -> Result<Self, String> {
+        Ok(Self {
+            #[cfg(feature = "mailer")]
+            mailer: config.mailer.as_ref().map(Mailer::new).transpose()?,
+            config,
+            db,
+            #[cfg(feature = "telegram")]
+            telegram_client,
+            webhooker: Webhooker::new(OutboundGuard::system())?,
+        })
+    }
+
+    /// Over `db`, without networks or integrations; tests add the ones they need.
+    #[cfg(all(test, feature = "mailer"))]
+    pub fn for_tests(db: DB) -> Self {
+        let config = MonitorConfig {
+            database: crate::config::DatabaseConfig { postgres_url: String::new(), encryption_keys: vec![] },
+            networks: vec![],
+            event_signature: String::new(),
+            infura_rpm: 1,
+            web_base_url: "https://pay.example.com".to_string(),
+            telegram: None,
+            mailer: None,
+        };
+        Self {
+            config: Arc::new(config),
+            db,
+            #[cfg(feature = "telegram")]
+            telegram_client: None,
+            #[cfg(feature = "mailer")]
+            mailer: None,
+            webhooker: Webhooker::new(OutboundGuard::system()).unwrap(),
+        }
     }
 }
 
+/// Only this much of a webhook response body is read and kept in the delivery log.
+const RESPONSE_EXCERPT_BYTES: usize = 1024;
+
+/// Posts webhooks through an [`OutboundGuard`] client, so neither the endpoint nor its
+/// redirects can reach internal addresses.
 #[derive(Clone)]
-pub struct Webhooker;
+pub struct Webhooker {
+    guard: OutboundGuard,
+    client: Client,
+}
+
+/// What the endpoint answered. Non-2xx statuses are returned as responses, not errors.
+#[derive(Debug)]
+pub struct WebhookResponse {
+    pub status: u16,
+    pub body_excerpt: String,
+}
+
+impl WebhookResponse {
+    pub fn is_success(&self) -> bool {
+        (200..300).contains(&self.status)
+    }
+}
 
 impl Webhooker {
-    pub async fn send(&self, url: &str, secret: &str, payload: &Value) -> Result<(), String> {
+    pub fn new(guard: OutboundGuard) -> Result<Self, String> {
+        Ok(Self { client: guard.client()?, guard })
+    }
+
+    pub fn guard(&self) -> &OutboundGuard {
+        &self.guard
+    }
+
+    pub fn client(&self) -> &Client {
+        &self.client
+    }
+
+    /// Signs and posts `payload`. `Err` means no response was received (connect, TLS, timeout).
+    ///
+    /// Every non-empty secret adds a signature to `X-Signature-256`, comma-separated, so a
+    /// rotated-out secret keeps verifying during its grace period.
+    pub async fn send(&self, url: &str, secrets: &[&str], payload: &Value) -> Result<WebhookResponse, String> {
+        let parsed = Url::parse(url)
+            .map_err(|err| utils::make_err(Box::new(err), "parse webhook url"))?;
+        self.guard.check(&parsed).await?;
+
         let body = serde_json::to_string(payload)
             .map_err(|err| utils::make_err(Box::new(err), "serialize webhook payload"))?;
 
@@ -37,33 +120,35 @@ impl Webhooker {
             .unwrap_or_default()
             .as_secs();
 
-        let mut request = Client::new()
-            .post(url)
-            .timeout(Duration::from_secs(30))
+        let mut request = self.client
+            .post(parsed)
             .header("content-type", "application/json")
-            .header("X-Webhook-Timestamp", timestamp.to_string());
+            .header(TIMESTAMP_HEADER, timestamp.to_string());
 
         // Empty secret = legacy webhook, no signature (backwards compatible)
-        if !secret.is_empty() {
-            let signed_payload = format!("{timestamp}.{body}");
-            let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes())
-                .map_err(|err| utils::make_err(Box::new(err), "create HMAC"))?;
-            mac.update(signed_payload.as_bytes());
-            let signature = hex::encode(mac.finalize().into_bytes());
-            request = request.header("X-Signature-256", signature);
+        if let Some(signatures) = signature_header(secrets, timestamp, body.as_bytes()) {
+            request = request.header(SIGNATURE_HEADER, signatures);
         }
 
-        let status = request
+        let mut response = request
             .body(body)
             .send()
             .await
-            .map_err(|err| utils::make_err(Box::new(err), "send to webhook"))?
-            .status();
+            .map_err(|err| utils::make_err(Box::new(err), "send to webhook"))?;
 
-        if !status.is_success() {
-            return Err(format!("Webhook to {} failed with status: {}", url, status));
+        let status = response.status().as_u16();
+        let mut excerpt = Vec::new();
+        while excerpt.len() < RESPONSE_EXCERPT_BYTES {
+            match response.chunk().await {
+                Ok(Some(chunk)) => excerpt.extend_from_slice(&chunk),
+                _ => break,
+            }
         }
+        excerpt.truncate(RESPONSE_EXCERPT_BYTES);
 
-        Ok(())
+        Ok(WebhookResponse {
+            status,
+            body_excerpt: String::from_utf8_lossy(&excerpt).into_owned(),
+        })
     }
 }
@@ -8,9 +8,13 @@ use ethers::types::{Filter, Log};
 use tracing::{error, info, warn};
 use crate::{events, utils};
 use crate::api::state::DB;
+use crate::config::MonitorConfig;
 use crate::monitoring::app_state::MonitorAppState;
 use crate::monitoring::health::{DaemonHealth, UNHEALTHY_ERROR_THRESHOLD};
+use crate::monitoring::{digest, invoice_expiry, notification_resend, subscription_expiry, webhook_outbox};
 use crate::network::Network;
+use crate::shutdown::Shutdown;
+#[cfg(feature = "telegram")]
 use crate::telegram::TelegramClient;
 
 const CREDITS_PER_SECOND: usize = 500;
@@ -74,10 +78,10 @@ impl Mode {
         if test { Mode::Test } else { Mode::Real }
     }
 
-    async fn dispatch(&self, app_state: Arc<MonitorAppState>, log: &Log) -> Result<(), String> {
+    async fn dispatch(&self, app_state: Arc<MonitorAppState>, network: &Network, log: &Log) -> Result<(), String> {
         match self {
             Self::Test => events::just_print_log(log).await,
-            Self::Real => events::process_log(&app_state, log).await,
+            Self::Real => events::process_log(&app_state, network, log).await,
         }
     }
 }
@@ -94,7 +98,7 @@ impl LogsRange {
 }
 
 enum InfuraRequest {
-    Logs(LogsRange, Filter),
+    Logs(LogsRange, Box<Filter>),
     LastBlockNumber,
 }
 
@@ -138,7 +142,7 @@ impl InfuraRequest {
                 .map(InfuraResponse::BlockNumber)
                 .map_err(|err| utils::make_err(Box::new(err), "get block number")),
             Self::Logs(range, base_filter) => provider
-                .get_logs(&base_filter.from_block(range.from).to_block(range.to))
+                .get_logs(&(*base_filter).from_block(range.from).to_block(range.to))
                 .await
                 .map(InfuraResponse::Logs)
                 .map_err(|err| utils::make_err(Box::new(err), "get logs"))
@@ -172,7 +176,7 @@ async fn get_logs_result(
     }
 
     InfuraRequest::Logs(
-        LogsRange::new(last_block_number + 1u64, new_block_number), base_filter.clone(),
+        LogsRange::new(last_block_number + 1u64, new_block_number), Box::new(base_filter.clone()),
     )
         .into_response(provider, limiter)
         .await?
@@ -186,12 +190,12 @@ async fn get_network_logs(
     let last_block_number = db.get_block_number(&network.name)
         .await
         .map_err(|e| e.to_string())?
-        .map(|bn| U64::from(bn));
+        .map(U64::from);
 
     let provider = Provider::<Http>::try_from(&network.link)
         .map_err(|err| utils::make_err(Box::new(err), "create provider"))?;
 
-    Ok(match get_logs_result(&provider, base_filter, last_block_number, &limiter)
+    Ok(match get_logs_result(&provider, base_filter, last_block_number, limiter)
         .await? {
         LogsResult::Skip => vec![],
         LogsResult::Result(logs, new_block_number) => {
@@ -203,25 +207,37 @@ async fn get_network_logs(
 }
 
 pub async fn process_networks(
-    test: bool, networks: Vec<Network>, db: &DB, telegram_client: &TelegramClient,
-    daemon_health: Arc<DaemonHealth>,
+    dry_run: bool, config: MonitorConfig, db: &DB,
+    #[cfg(feature = "telegram")] telegram_client: Option<TelegramClient>,
+    daemon_health: Arc<DaemonHealth>, shutdown: Shutdown,
 ) -> Result<(), String> {
-    let app_state = Arc::new(MonitorAppState::new(db.clone(), telegram_client.clone())?);
-
-    let limiter = utils::get_env_or("INFRA_RPM", "1".to_string())?
-        .parse::<u64>()
-        .map(|rpm| Limiter::infura_limiter(rpm as f64))
-        .map_err(|err| utils::make_err(Box::new(err), "parse infra rpm"))?;
-
-    let base_filter = utils::get_env_var("EVENT_SIGNATURE")
-        .map(|event_signature| Filter::new().event(&event_signature))?;
+    let limiter = Limiter::infura_limiter(config.infura_rpm as f64);
+    let base_filter = Filter::new().event(&config.event_signature);
+    let networks = config.networks.clone();
+    let app_state = Arc::new(MonitorAppState::new(
+        Arc::new(config),
+        db.clone(),
+        #[cfg(feature = "telegram")]
+        telegram_client,
+    )?);
+
+    if !dry_run {
+        shutdown.spawn(webhook_outbox::run(app_state.clone(), shutdown.clone()));
+        shutdown.spawn(digest::run(app_state.clone(), shutdown.clone()));
+        shutdown.spawn(notification_resend::run(app_state.clone(), shutdown.clone()));
+        shutdown.spawn(invoice_expiry::run(app_state.clone(), shutdown.clone()));
+        shutdown.spawn(subscription_expiry::run(app_state.clone(), shutdown.clone()));
+    }
 
     let mut consecutive_errors: u32 = 0;
 
-    loop {
+    while !shutdown.is_triggered() {
         for network in &networks {
+            if shutdown.is_triggered() {
+                break;
+            }
             info!("Will be monitored {}", network.name);
-            let logs = match get_network_logs(&network, &app_state.db, &base_filter, &limiter).await {
+            let logs = match get_network_logs(network, &app_state.db, &base_filter, &limiter).await {
                 Ok(logs) => {
                     consecutive_errors = 0;
                     daemon_health.record_success();
@@ -237,7 +253,7 @@ pub async fn process_networks(
                     }
 
                     let backoff = std::cmp::min(5u64.saturating_mul(1 << consecutive_errors.min(4)), 60);
-                    tokio::time::sleep(Duration::from_secs(backoff)).await;
+                    shutdown.sleep(Duration::from_secs(backoff)).await;
                     continue;
                 }
             };
@@ -245,8 +261,9 @@ pub async fn process_networks(
 
             for log in logs {
                 let app_state_clone = app_state.clone();
-                tokio::spawn(async move {
-                    if let Err(err) = Mode::from_bool(test).dispatch(app_state_clone, &log)
+                let network = network.clone();
+                shutdown.spawn(async move {
+                    if let Err(err) = Mode::from_bool(dry_run).dispatch(app_state_clone, &network, &log)
                         .await {
                         error!("Failed dispatch log: {err}")
                     }
@@ -254,6 +271,12 @@ pub async fn process_networks(
             }
         }
 
-        tokio::time::sleep(Duration::from_secs(5)).await;
+        shutdown.sleep(Duration::from_secs(5)).await;
     }
+
+    info!("Monitor stopped after finishing its cycle");
+    shutdown.drain().await;
+    app_state.db.close().await;
+
+    Ok(())
 }
@@ -0,0 +1,265 @@
+use std::sync::Arc;
+use std::time::Duration;
+use chrono::{NaiveDateTime, Utc};
+use tracing::{error, info};
+#[cfg(any(feature = "mailer", feature = "telegram"))]
+use uuid::Uuid;
+use crate::api::state::DB;
+use crate::db::notification_digest::{NotificationDigest, NotificationDigestItem};
+#[cfg(any(feature = "mailer", feature = "telegram"))]
+use crate::db::notification_log::{NewNotificationLog, DIGEST_EVENT};
+use crate::error::AppError;
+#[cfg(any(feature = "mailer", feature = "telegram"))]
+use crate::events::notifications::digest::Digest;
+use crate::events::notifications::digest::DigestFrequency;
+use crate::events::notifications::preferences::NotificationChannel;
+#[cfg(feature = "mailer")]
+use crate::events::notifications::mask_email;
+#[cfg(feature = "mailer")]
+use crate::mailer::Email;
+use crate::monitoring::app_state::MonitorAppState;
+use crate::shutdown::Shutdown;
+#[cfg(any(feature = "mailer", feature = "telegram"))]
+use crate::utils;
+
+/// Digests go out within a minute of their period ending.
+const POLL_INTERVAL: Duration = Duration::from_secs(60);
+
+/// A digest whose period is over, with the items it covers.
+pub struct DueDigest {
+    pub digest: NotificationDigest,
+    #[cfg_attr(not(any(feature = "mailer", feature = "telegram")), allow(dead_code))]
+    pub frequency: DigestFrequency,
+    pub items: Vec<NotificationDigestItem>,
+}
+
+/// Sends digests until shutdown.
+pub async fn run(app_state: Arc<MonitorAppState>, shutdown: Shutdown) {
+    info!("Notification digest worker started");
+
+    while !shutdown.is_triggered() {
+        match take_due(&app_state.db, Utc::now().naive_utc()).await {
+            Ok(due) => {
+                for due in due {
+                    if let Err(err) = deliver(&app_state, &due).await {
+                        error!("Failed to send the {} digest of user {}: {err}", due.digest.channel, due.digest.user_id);
+                    }
+                }
+            }
+            Err(err) => error!("Failed to collect notification digests: {err}"),
+        }
+
+        shutdown.sleep(POLL_INTERVAL).await;
+    }
+
+    info!("Notification digest worker stopped");
+}
+
+/// Claims every digest whose period ended before `now` and lists its items. A digest is
+/// claimed once per period; items stay queued until [`deliver`] sends them.
+pub async fn take_due(db: &DB, now: NaiveDateTime) -> Result<Vec<DueDigest>, AppError> {
+    let mut due = vec![];
+    for digest in db.list_pending_notification_digests().await? {
+        let frequency = DigestFrequency::parse(&digest.frequency).unwrap_or_default();
+        let period_end = frequency.period_end(now);
+        if !db.claim_notification_digest(&digest.user_id, &digest.channel, &period_end).await? {
+            continue;
+        }
+
+        let items = db.list_notification_digest_items(&digest.user_id, &digest.channel, &period_end).await?;
+        if !items.is_empty() {
+            due.push(DueDigest { digest, frequency, items });
+        }
+    }
+
+    Ok(due)
+}
+
+/// Sends the digest and only then drops its items. A failed digest keeps them for the next
+/// period's, so nothing is lost, though a chat that did get this one sees them again.
+pub async fn deliver(app_state: &MonitorAppState, due: &DueDigest) -> Result<(), String> {
+    send(app_state, due).await?;
+    let ids = due.items.iter().map(|item| item.id).collect::<Vec<_>>();
+    app_state.db.delete_notification_digest_items(&ids).await.map_err(|err| err.to_string())
+}
+
+#[cfg_attr(not(any(feature = "mailer", feature = "telegram")), allow(unused_variables))]
+async fn send(app_state: &MonitorAppState, due: &DueDigest) -> Result<(), String> {
+    match NotificationChannel::parse(&due.digest.channel) {
+        #[cfg(feature = "mailer")]
+        Some(NotificationChannel::Email) => send_email(app_state, due).await,
+        #[cfg(feature = "telegram")]
+        Some(NotificationChannel::Telegram) => send_telegram(app_state, due).await,
+        _ => Err(format!("{} digests are not supported", due.digest.channel)),
+    }
+}
+
+/// Digests show up in `GET /user/notifications` like other messages, but cannot be resent.
+#[cfg(any(feature = "mailer", feature = "telegram"))]
+async fn log_sent(db: &DB, due: &DueDigest, destination_id: Option<Uuid>, destination: String, result: &Result<(), String>) {
+    let log = NewNotificationLog {
+        user_id: due.digest.user_id,
+        channel: due.digest.channel.clone(),
+        event_type: DIGEST_EVENT.to_string(),
+        invoice_id: None,
+        destination_id,
+        destination,
+        transfer: None,
+        error: result.as_ref().err().cloned(),
+    };
+    if let Err(err) = db.create_notification_log(&log).await {
+        error!("Failed to log the {} digest of user {}: {err}", log.channel, log.user_id);
+    }
+}
+
+#[cfg(any(feature = "mailer", feature = "telegram"))]
+fn summarize(app_state: &MonitorAppState, due: &DueDigest, items: &[NotificationDigestItem]) -> Digest {
+    Digest::new(due.frequency, items, &app_state.config.networks, &app_state.config.web_base_url)
+}
+
+#[cfg(feature = "mailer")]
+async fn send_email(app_state: &MonitorAppState, due: &DueDigest) -> Result<(), String> {
+    let mailer = app_state.mailer.clone().ok_or("Mailer is disabled")?;
+    let user = app_state.db.get_user_by_id(&due.digest.user_id).await.map_err(|err| err.to_string())?;
+    let Some(to) = user.email.clone().filter(|_| user.email_notification) else {
+        return Ok(());
+    };
+
+    let destination = mask_email(&to);
+    let message = summarize(app_state, due, &due.items).render(&user.locale);
+    let email = Email {
+        to,
+        subject: message.subject,
+        text: message.text,
+        html: Some(message.html),
+        tags: vec!["NotificationDigest".to_string()],
+    };
+    let result = utils::retry(2, || {
+        let email = email.clone();
+        let mailer = mailer.clone();
+        async move { mailer.send(&email).await }
+    }).await;
+    log_sent(&app_state.db, due, None, destination, &result).await;
+    result
+}
+
+/// Every chat gets the items its own event types and minimum amount let through. A failing chat
+/// does not keep the digest from the others; the errors come back together.
+#[cfg(feature = "telegram")]
+async fn send_telegram(app_state: &MonitorAppState, due: &DueDigest) -> Result<(), String> {
+    let client = app_state.telegram_client.clone().ok_or("Telegram is disabled")?;
+    let user = app_state.db.get_user_by_id(&due.digest.user_id).await.map_err(|err| err.to_string())?;
+    if !user.telegram_notification {
+        return Ok(());
+    }
+
+    let destinations = app_state.db.list_telegram_destinations(&user.id).await.map_err(|err| err.to_string())?;
+    let mut errors = Vec::new();
+    for destination in destinations {
+        let items = due.items.iter()
+            .filter(|item| destination.accepts(&item.event_type, &item.amount))
+            .cloned()
+            .collect::<Vec<_>>();
+        if items.is_empty() {
+            continue;
+        }
+
+        let digest = summarize(app_state, due, &items);
+        let message = digest.render(&user.locale);
+        let button = (message.telegram_button.clone(), digest.dashboard_url.clone());
+        let result = utils::retry(1, || {
+            let client = client.clone();
+            let button = button.clone();
+            let chat_id = destination.chat_id.clone();
+            let text = message.telegram.clone();
+            async move { client.send_markdown(&chat_id, &text, Some(button)).await }
+        }).await;
+        log_sent(&app_state.db, due, Some(destination.id), destination.label.clone(), &result).await;
+        if let Err(err) = result {
+            errors.push(format!("{}: {err}", destination.label));
+        }
+    }
+
+    match errors.is_empty() {
+        true => Ok(()),
+        false => Err(errors.join("; ")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bigdecimal::BigDecimal;
+    use uuid::Uuid;
+    use crate::db::notification_digest::NewNotificationDigestItem;
+
+    fn item(user_id: Uuid, channel: &str) -> NewNotificationDigestItem {
+        NewNotificationDigestItem {
+            user_id,
+            channel: channel.to_string(),
+            event_type: "invoice.paid".to_string(),
+            invoice_id: Uuid::new_v4(),
+            amount: BigDecimal::from(10),
+            network: Some(1),
+            external_id: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_take_due_sends_each_period_once() {
+        let db = DB::in_memory();
+        let user_id = Uuid::new_v4();
+        db.upsert_notification_digest(&user_id, "email", "hourly").await.unwrap();
+        db.create_notification_digest_item(&item(user_id, "email")).await.unwrap();
+        db.create_notification_digest_item(&item(user_id, "email")).await.unwrap();
+        // Channels without a digest keep nothing queued, but stray items must stay put.
+        db.create_notification_digest_item(&item(user_id, "telegram")).await.unwrap();
+
+        let now = Utc::now().naive_utc();
+        // The current hour is not over yet.
+        assert!(take_due(&db, now).await.unwrap().is_empty());
+
+        let next_hour = now + chrono::Duration::hours(1);
+        let due = take_due(&db, next_hour).await.unwrap();
+        assert_eq!(due.len(), 1);
+        assert_eq!(due[0].frequency, DigestFrequency::Hourly);
+        assert_eq!(due[0].items.len(), 2);
+
+        db.delete_notification_digest_items(&due[0].items.iter().map(|item| item.id).collect::<Vec<_>>()).await.unwrap();
+        db.create_notification_digest_item(&item(user_id, "email")).await.unwrap();
+        assert!(take_due(&db, next_hour).await.unwrap().is_empty());
+        assert_eq!(take_due(&db, next_hour + chrono::Duration::hours(1)).await.unwrap()[0].items.len(), 1);
+    }
+
+    #[cfg(feature = "mailer")]
+    #[tokio::test]
+    async fn test_deliver_keeps_items_when_sending_fails() {
+        use crate::mailer::{Mailer, MemorySink};
+
+        let db = DB::in_memory();
+        let user = db.get_or_create_user("seller", Some("seller@example.com".to_string())).await.unwrap();
+        db.update_user(&user.id, Some(true), None).await.unwrap();
+        db.upsert_notification_digest(&user.id, "email", "hourly").await.unwrap();
+        db.create_notification_digest_item(&item(user.id, "email")).await.unwrap();
+        let next_hour = Utc::now().naive_utc() + chrono::Duration::hours(1);
+
+        let failing = MonitorAppState {
+            mailer: Some(Mailer::with_transport(Arc::new(MemorySink::failing("mailbox full")))),
+            ..MonitorAppState::for_tests(db.clone())
+        };
+        let due = take_due(&db, next_hour).await.unwrap();
+        assert_eq!(deliver(&failing, &due[0]).await, Err("mailbox full".to_string()));
+
+        // The next period's digest picks the items up again.
+        let sink = MemorySink::default();
+        let app_state = MonitorAppState {
+            mailer: Some(Mailer::with_transport(Arc::new(sink.clone()))),
+            ..MonitorAppState::for_tests(db.clone())
+        };
+        let due = take_due(&db, next_hour + chrono::Duration::hours(1)).await.unwrap();
+        assert_eq!(due[0].items.len(), 1);
+        deliver(&app_state, &due[0]).await.unwrap();
+        assert_eq!(sink.sent().len(), 1);
+        assert!(take_due(&db, next_hour + chrono::Duration::hours(2)).await.unwrap().is_empty());
+    }
+}
@@ -0,0 +1,81 @@
+use std::sync::Arc;
+use std::time::Duration;
+use chrono::{NaiveDateTime, Utc};
+use tracing::{error, info, warn};
+use crate::api::state::DB;
+use crate::db::Invoice;
+use crate::error::AppError;
+use crate::events::notify_owner;
+use crate::events::notifications::InvoiceNotification;
+use crate::monitoring::app_state::MonitorAppState;
+use crate::shutdown::Shutdown;
+
+const POLL_INTERVAL: Duration = Duration::from_secs(60);
+
+/// How long a confirming invoice waits for its transaction before it can expire or reopen.
+pub const CONFIRMING_GRACE: chrono::Duration = chrono::Duration::minutes(30);
+
+/// Expires stale invoices until shutdown.
+pub async fn run(app_state: Arc<MonitorAppState>, shutdown: Shutdown) {
+    info!("Invoice expiry worker started");
+
+    while !shutdown.is_triggered() {
+        match expire_due(&app_state.db, Utc::now().naive_utc()).await {
+            Ok(expired) => {
+                for invoice in expired {
+                    info!("Invoice {} expired", invoice.id);
+                    if let Err(err) = notify_owner(&app_state, InvoiceNotification::expired(invoice)).await {
+                        error!("Failed to notify about an expired invoice: {err}");
+                    }
+                }
+            }
+            Err(err) => error!("Failed to expire invoices: {err}"),
+        }
+
+        shutdown.sleep(POLL_INTERVAL).await;
+    }
+
+    info!("Invoice expiry worker stopped");
+}
+
+/// Expires open invoices past `expires_at` and confirming ones whose transaction has not
+/// been mined within [`CONFIRMING_GRACE`], then reopens the other stale confirming ones.
+/// Returns the expired invoices.
+pub async fn expire_due(db: &DB, now: NaiveDateTime) -> Result<Vec<Invoice>, AppError> {
+    let confirming_before = now - CONFIRMING_GRACE;
+    let expired = db.expire_invoices(now, confirming_before).await?;
+    for invoice in db.reopen_stale_invoices(now, confirming_before).await? {
+        warn!("No payment for confirming invoice {}, reopened it", invoice.id);
+    }
+    Ok(expired)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bigdecimal::BigDecimal;
+    use crate::db::invoice::{InvoiceOptions, InvoiceStatus};
+
+    #[tokio::test]
+    async fn test_expire_due() {
+        let db = DB::in_memory();
+        let now = Utc::now().naive_utc();
+        let create = |expires_at| {
+            let options = InvoiceOptions { expires_at, ..Default::default() };
+            db.create_invoice_with_options(BigDecimal::from(1), "0xa", &[1], None, None, options)
+        };
+        let stale = create(Some(now - chrono::Duration::seconds(1))).await.unwrap();
+        let fresh = create(Some(now + chrono::Duration::hours(1))).await.unwrap();
+        let confirming = create(None).await.unwrap();
+        db.transition_invoice(&confirming.id, InvoiceStatus::Confirming).await.unwrap();
+
+        let expired = expire_due(&db, now).await.unwrap();
+        assert_eq!(expired.iter().map(|invoice| invoice.id).collect::<Vec<_>>(), vec![stale.id]);
+        // Confirming invoices without an expiry wait out the grace period, then reopen.
+        assert!(expire_due(&db, now).await.unwrap().is_empty());
+        assert_eq!(db.get_invoice(&confirming.id).await.unwrap().unwrap().status, InvoiceStatus::Confirming);
+        expire_due(&db, now + CONFIRMING_GRACE + chrono::Duration::seconds(1)).await.unwrap();
+        assert_eq!(db.get_invoice(&confirming.id).await.unwrap().unwrap().status, InvoiceStatus::Open);
+        assert_eq!(db.get_invoice(&fresh.id).await.unwrap().unwrap().status, InvoiceStatus::Open);
+    }
+}
@@ -1,3 +1,8 @@
 pub mod daemon;
 pub mod app_state;
 pub mod health;
+pub mod webhook_outbox;
+pub mod digest;
+pub mod notification_resend;
+pub mod invoice_expiry;
+pub mod subscription_expiry;
@@ -0,0 +1,51 @@
+use std::sync::Arc;
+use std::time::Duration;
+use futures::future::join_all;
+use tracing::{error, info};
+use crate::db::notification_log::NotificationLog;
+use crate::error::AppError;
+use crate::events::notifications::{InvoiceNotification, Notifier};
+use crate::monitoring::app_state::MonitorAppState;
+use crate::shutdown::Shutdown;
+
+const POLL_INTERVAL: Duration = Duration::from_secs(5);
+const BATCH_SIZE: i64 = 20;
+/// Longer than a send with its retries, so a claimed resend is not picked up twice.
+const CLAIM_LEASE: Duration = Duration::from_secs(120);
+
+/// Sends the notifications sellers asked to resend until shutdown.
+pub async fn run(app_state: Arc<MonitorAppState>, shutdown: Shutdown) {
+    info!("Notification resend worker started");
+
+    while !shutdown.is_triggered() {
+        match process_pending(&app_state).await {
+            Ok(processed) if processed as i64 == BATCH_SIZE => continue,
+            Ok(_) => {}
+            Err(err) => error!("Failed to resend notifications: {err}"),
+        }
+
+        shutdown.sleep(POLL_INTERVAL).await;
+    }
+
+    info!("Notification resend worker stopped");
+}
+
+/// Resends every pending notification once; returns how many were claimed.
+pub async fn process_pending(app_state: &Arc<MonitorAppState>) -> Result<usize, AppError> {
+    let logs = app_state.db.claim_pending_notifications(BATCH_SIZE, CLAIM_LEASE).await?;
+    let count = logs.len();
+
+    let results = join_all(logs.iter().map(|log| resend(app_state.clone(), log))).await;
+    for (log, result) in logs.iter().zip(results) {
+        app_state.db.record_notification_attempt(&log.id, result.err().as_deref()).await?;
+    }
+
+    Ok(count)
+}
+
+/// Sends the same message to the same address or chat, with the invoice as it is now.
+async fn resend(app_state: Arc<MonitorAppState>, log: &NotificationLog) -> Result<(), String> {
+    let notification = InvoiceNotification::from_log(&app_state.db, log).await?;
+    let notifier = Notifier::from_log(&app_state, log).await?;
+    notifier.deliver(app_state, notification).await
+}
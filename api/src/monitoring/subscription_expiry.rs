@@ -0,0 +1,209 @@
+use std::sync::Arc;
+use std::time::Duration;
+use chrono::{NaiveDateTime, Utc};
+use tracing::{error, info};
+use uuid::Uuid;
+use crate::api::state::DB;
+use crate::db::billing::Subscription;
+use crate::db::notification_log::NewNotificationLog;
+use crate::error::AppError;
+use crate::events::notifications::chat::{ChatKind, ChatMessage};
+#[cfg(feature = "mailer")]
+use crate::events::notifications::mask_email;
+use crate::events::notifications::preferences::{NotificationChannel, NotificationEvent, Preferences};
+use crate::events::notifications::templates::{self, TemplateVars};
+#[cfg(feature = "mailer")]
+use crate::mailer::Email;
+use crate::monitoring::app_state::MonitorAppState;
+use crate::shutdown::Shutdown;
+use crate::utils;
+
+const POLL_INTERVAL: Duration = Duration::from_secs(600);
+
+/// How long before a subscription ends its seller hears about it.
+pub const NOTICE: chrono::Duration = chrono::Duration::days(3);
+
+const EVENT: NotificationEvent = NotificationEvent::SubscriptionExpiring;
+
+/// Sends `subscription.expiring` notices until shutdown.
+pub async fn run(app_state: Arc<MonitorAppState>, shutdown: Shutdown) {
+    info!("Subscription expiry worker started");
+
+    while !shutdown.is_triggered() {
+        match take_due(&app_state.db, Utc::now().naive_utc()).await {
+            Ok(due) => {
+                for subscription in due {
+                    if let Err(err) = notify(&app_state, &subscription).await {
+                        error!("Failed to notify user {} that their {} subscription ends: {err}", subscription.user_id, subscription.target);
+                        if let Err(err) = app_state.db.release_subscription_expiry(&subscription).await {
+                            error!("Failed to release the expiry notice of user {}: {err}", subscription.user_id);
+                        }
+                    }
+                }
+            }
+            Err(err) => error!("Failed to collect expiring subscriptions: {err}"),
+        }
+
+        shutdown.sleep(POLL_INTERVAL).await;
+    }
+
+    info!("Subscription expiry worker stopped");
+}
+
+/// Claims the subscriptions ending within [`NOTICE`] of `now`, once per `until`; renewing
+/// moves `until`, so the next period gets its own notice.
+pub async fn take_due(db: &DB, now: NaiveDateTime) -> Result<Vec<Subscription>, AppError> {
+    db.claim_expiring_subscriptions(&now, &(now + NOTICE)).await
+}
+
+/// Tells the seller on every channel their preferences enable for `subscription.expiring`,
+/// logging each message. Digests do not hold it back: it is about the account, not an
+/// invoice. Fails only when no message went out, so the notice can be claimed again.
+pub async fn notify(app_state: &MonitorAppState, subscription: &Subscription) -> Result<(), String> {
+    let db = &app_state.db;
+    let user = db.get_user_by_id(&subscription.user_id).await.map_err(|err| err.to_string())?;
+    let preferences = Preferences::new(db.list_notification_preferences(&user.id).await.map_err(|err| err.to_string())?);
+    let vars = TemplateVars::for_subscription(subscription, &user.locale, &app_state.config.web_base_url);
+    let message = templates::render(db, &user.id, EVENT.template_kind(), &user.locale, &vars).await?;
+    let mut results = vec![];
+
+    #[cfg(feature = "mailer")]
+    if let Some(mailer) = app_state.mailer.clone().filter(|_| user.email_notification && preferences.enables(NotificationChannel::Email, EVENT)) {
+        if let Some(to) = user.email.clone() {
+            let email = Email {
+                to,
+                subject: message.subject.clone(),
+                text: message.text.clone(),
+                html: Some(message.html.clone()),
+                tags: vec!["SubscriptionExpiring".to_string()],
+            };
+            let result = utils::retry(2, || {
+                let email = email.clone();
+                let mailer = mailer.clone();
+                async move { mailer.send(&email).await }
+            }).await;
+            results.push(log_sent(db, &user.id, NotificationChannel::Email, None, mask_email(&email.to), result).await);
+        }
+    }
+
+    #[cfg(feature = "telegram")]
+    if let Some(client) = app_state.telegram_client.clone().filter(|_| user.telegram_notification && preferences.enables(NotificationChannel::Telegram, EVENT)) {
+        let button = (message.telegram_button.clone(), vars.get("renew_url").to_string());
+        for destination in db.list_telegram_destinations(&user.id).await.map_err(|err| err.to_string())? {
+            let result = utils::retry(1, || {
+                let client = client.clone();
+                let button = button.clone();
+                let chat_id = destination.chat_id.clone();
+                let text = message.telegram.clone();
+                async move { client.send_markdown(&chat_id, &text, Some(button)).await }
+            }).await;
+            results.push(log_sent(db, &user.id, NotificationChannel::Telegram, Some(destination.id), destination.label.clone(), result).await);
+        }
+    }
+
+    if preferences.enables(NotificationChannel::Chat, EVENT) {
+        let chat_message = ChatMessage::new(&message, &vars, &user.locale);
+        for chat_webhook in db.list_chat_webhooks(&user.id).await.map_err(|err| err.to_string())? {
+            let Some(kind) = ChatKind::parse(&chat_webhook.kind).filter(|_| chat_webhook.enabled) else {
+                continue;
+            };
+            let result = utils::retry(1, || kind.send(&app_state.webhooker, &chat_webhook.url, &chat_message)).await;
+            results.push(log_sent(db, &user.id, NotificationChannel::Chat, Some(chat_webhook.id), chat_webhook.label.clone(), result).await);
+        }
+    }
+
+    match results.is_empty() || results.iter().any(Result::is_ok) {
+        true => Ok(()),
+        false => Err(results.into_iter().filter_map(Result::err).collect::<Vec<_>>().join("; ")),
+    }
+}
+
+/// Notices show up in `GET /user/notifications`, but cannot be resent.
+async fn log_sent(
+    db: &DB,
+    user_id: &Uuid,
+    channel: NotificationChannel,
+    destination_id: Option<Uuid>,
+    destination: String,
+    result: Result<(), String>,
+) -> Result<(), String> {
+    let log = NewNotificationLog {
+        user_id: *user_id,
+        channel: channel.as_str().to_string(),
+        event_type: EVENT.as_str().to_string(),
+        invoice_id: None,
+        destination_id,
+        destination,
+        transfer: None,
+        error: result.as_ref().err().cloned(),
+    };
+    if let Err(err) = db.create_notification_log(&log).await {
+        error!("Failed to log the expiry notice of user {}: {err}", log.user_id);
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_take_due_claims_each_period_once() {
+        let db = DB::in_memory();
+        let now = Utc::now().naive_utc();
+        let user = db.get_or_create_user("seller", None).await.unwrap();
+        let other = db.get_or_create_user("other", None).await.unwrap();
+        db.create_or_update_subscription(&user.id, "unlimited_invoices", None, now + chrono::Duration::days(2)).await.unwrap();
+        db.create_or_update_subscription(&other.id, "unlimited_invoices", None, now + chrono::Duration::days(20)).await.unwrap();
+        db.create_or_update_subscription(&other.id, "private_invoices", None, now - chrono::Duration::days(1)).await.unwrap();
+
+        let due = take_due(&db, now).await.unwrap();
+        assert_eq!(due.iter().map(|subscription| subscription.user_id).collect::<Vec<_>>(), vec![user.id]);
+        assert!(take_due(&db, now).await.unwrap().is_empty());
+
+        // A notice that could not be sent is claimed again.
+        db.release_subscription_expiry(&due[0]).await.unwrap();
+        assert_eq!(take_due(&db, now).await.unwrap().len(), 1);
+
+        // Renewing starts a new period, which gets its own notice.
+        let renewed = now + chrono::Duration::days(2) + chrono::Duration::hours(1);
+        db.create_or_update_subscription(&user.id, "unlimited_invoices", None, renewed).await.unwrap();
+        assert_eq!(take_due(&db, now).await.unwrap()[0].until, renewed);
+    }
+
+    #[cfg(feature = "mailer")]
+    #[tokio::test]
+    async fn test_notify_mails_the_seller() {
+        use crate::db::notification_log::NotificationLogFilter;
+        use crate::mailer::{Mailer, MemorySink};
+
+        let db = DB::in_memory();
+        let now = Utc::now().naive_utc();
+        let user = db.get_or_create_user("seller", Some("seller@example.com".to_string())).await.unwrap();
+        db.update_user(&user.id, Some(true), None).await.unwrap();
+        db.create_or_update_subscription(&user.id, "private_invoices", None, now + chrono::Duration::days(1)).await.unwrap();
+        let subscription = take_due(&db, now).await.unwrap().remove(0);
+
+        let sink = MemorySink::default();
+        let app_state = MonitorAppState {
+            mailer: Some(Mailer::with_transport(Arc::new(sink.clone()))),
+            ..MonitorAppState::for_tests(db.clone())
+        };
+        notify(&app_state, &subscription).await.unwrap();
+        let sent = sink.sent();
+        assert_eq!((sent[0].to.as_str(), sent[0].subject.as_str()), ("seller@example.com", "Your Private invoices subscription ends soon"));
+        assert!(sent[0].text.contains("https://pay.example.com/settings"), "{}", sent[0].text);
+
+        let failing = MonitorAppState {
+            mailer: Some(Mailer::with_transport(Arc::new(MemorySink::failing("mailbox full")))),
+            ..MonitorAppState::for_tests(db.clone())
+        };
+        assert_eq!(notify(&failing, &subscription).await, Err("mailbox full".to_string()));
+
+        let logs = db.list_notification_logs(&user.id, &NotificationLogFilter::default(), 10, 0).await.unwrap();
+        assert_eq!(logs.iter().map(|log| (log.event_type.as_str(), log.error.is_some())).collect::<Vec<_>>(), vec![
+            ("subscription.expiring", true),
+            ("subscription.expiring", false),
+        ]);
+    }
+}
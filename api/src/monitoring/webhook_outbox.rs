@@ -0,0 +1,267 @@
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use futures::future::join_all;
+use tracing::{error, info, warn};
+use crate::api::state::DB;
+use crate::db::webhook_delivery::{DueWebhookDelivery, NewWebhookDeliveryAttempt, WebhookDeliveryStatus};
+use crate::error::AppError;
+use crate::monitoring::app_state::{MonitorAppState, Webhooker};
+use crate::shutdown::Shutdown;
+
+const POLL_INTERVAL: Duration = Duration::from_secs(5);
+const BATCH_SIZE: i64 = 20;
+/// Longer than the 30s send timeout, so a claimed delivery is not picked up twice.
+const CLAIM_LEASE: Duration = Duration::from_secs(120);
+/// With the delays below this keeps retrying for roughly 12 hours.
+pub const MAX_ATTEMPTS: i32 = 12;
+const BASE_RETRY_DELAY_SECS: u64 = 30;
+const MAX_RETRY_DELAY_SECS: u64 = 4 * 3600;
+
+/// Delay before the next try after `attempts` failed ones: 30s, 1m, 2m, ... capped at 4h.
+pub fn retry_delay(attempts: i32) -> Duration {
+    let exponent = attempts.saturating_sub(1).clamp(0, 20) as u32;
+    Duration::from_secs(BASE_RETRY_DELAY_SECS.saturating_mul(1 << exponent).min(MAX_RETRY_DELAY_SECS))
+}
+
+/// Polls the outbox until shutdown. Deliveries survive restarts: anything claimed but not
+/// recorded is retried once its lease runs out.
+pub async fn run(app_state: Arc<MonitorAppState>, shutdown: Shutdown) {
+    info!("Webhook outbox worker started");
+
+    while !shutdown.is_triggered() {
+        match process_due(&app_state.db, &app_state.webhooker).await {
+            // A full batch means more may be waiting; go again without sleeping.
+            Ok(processed) if processed as i64 == BATCH_SIZE => continue,
+            Ok(_) => {}
+            Err(err) => error!("Failed to process webhook outbox: {err}"),
+        }
+
+        shutdown.sleep(POLL_INTERVAL).await;
+    }
+
+    info!("Webhook outbox worker stopped");
+}
+
+/// Attempts every due delivery once; returns how many were claimed.
+pub async fn process_due(db: &DB, webhooker: &Webhooker) -> Result<usize, AppError> {
+    let deliveries = db.claim_due_webhook_deliveries(BATCH_SIZE, CLAIM_LEASE).await?;
+    let count = deliveries.len();
+
+    for result in join_all(deliveries.into_iter().map(|delivery| deliver(db, webhooker, delivery))).await {
+        if let Err(err) = result {
+            error!("Failed to record webhook delivery attempt: {err}");
+        }
+    }
+
+    Ok(count)
+}
+
+async fn deliver(db: &DB, webhooker: &Webhooker, delivery: DueWebhookDelivery) -> Result<(), AppError> {
+    let started = Instant::now();
+    let secrets = [Some(delivery.secret.as_str()), delivery.previous_secret.as_deref()];
+    let secrets = secrets.into_iter().flatten().collect::<Vec<_>>();
+    let result = webhooker.send(&delivery.url, &secrets, &delivery.payload).await;
+    let latency_ms = started.elapsed().as_millis() as i64;
+
+    let (attempt, succeeded) = match result {
+        Ok(response) => (
+            NewWebhookDeliveryAttempt {
+                status_code: Some(response.status as i32),
+                latency_ms,
+                response_excerpt: Some(response.body_excerpt.clone()),
+                error: None,
+            },
+            response.is_success(),
+        ),
+        Err(err) => (
+            NewWebhookDeliveryAttempt { latency_ms, error: Some(err), ..Default::default() },
+            false,
+        ),
+    };
+
+    let attempts = delivery.attempts + 1;
+    let (status, retry_in) = match succeeded {
+        true => (WebhookDeliveryStatus::Delivered, Duration::ZERO),
+        false if attempts >= MAX_ATTEMPTS => {
+            warn!("Delivery {} for webhook {} failed {attempts} times, giving up", delivery.id, delivery.webhook_id);
+            (WebhookDeliveryStatus::Failed, Duration::ZERO)
+        }
+        false => (WebhookDeliveryStatus::Pending, retry_delay(attempts)),
+    };
+
+    db.record_webhook_delivery_attempt(&delivery.id, &attempt, status, retry_in).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+    use axum::http::{HeaderMap, StatusCode};
+    use axum::routing::post;
+    use axum::Router;
+    use crate::events::webhook_event::API_VERSION;
+    use crate::utils::ssrf::tests::stub_guard;
+    use cryo_webhook::{Verifier, SIGNATURE_HEADER, TIMESTAMP_HEADER};
+    use rstest::rstest;
+    use serde_json::json;
+    use uuid::Uuid;
+
+    #[rstest]
+    #[case(1, 30)]
+    #[case(2, 60)]
+    #[case(5, 480)]
+    #[case(10, 4 * 3600)]
+    #[case(MAX_ATTEMPTS, 4 * 3600)]
+    fn test_retry_delay(#[case] attempts: i32, #[case] expected_secs: u64) {
+        assert_eq!(retry_delay(attempts), Duration::from_secs(expected_secs));
+    }
+
+    /// Resolves nothing and lets the loopback stand-ins below through.
+    fn webhooker() -> Webhooker {
+        Webhooker::new(stub_guard(&[], true)).unwrap()
+    }
+
+    /// Local stand-in for a merchant endpoint that always answers with `status`.
+    async fn endpoint(status: StatusCode) -> String {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/hook", listener.local_addr().unwrap());
+        let app = Router::new().route("/hook", post(move || async move { (status, "endpoint says hi") }));
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+        url
+    }
+
+    async fn enqueue(db: &DB, url: &str) -> (Uuid, Uuid) {
+        let user_id = Uuid::new_v4();
+        let webhook = db.create_webhook(url, "secret", &user_id, &[], API_VERSION).await.unwrap();
+        let delivery = db.enqueue_webhook_delivery(&webhook.id, &json!({"id": 1})).await.unwrap();
+        (webhook.id, delivery.id)
+    }
+
+    #[tokio::test]
+    async fn test_successful_delivery_is_logged() {
+        let db = DB::in_memory();
+        let (webhook_id, delivery_id) = enqueue(&db, &endpoint(StatusCode::OK).await).await;
+
+        assert_eq!(process_due(&db, &webhooker()).await.unwrap(), 1);
+
+        let delivery = &db.list_webhook_deliveries(&webhook_id, 10, 0).await.unwrap()[0];
+        assert_eq!(delivery.status, "delivered");
+        assert!(delivery.delivered_at.is_some());
+        let attempts = db.list_webhook_delivery_attempts(&[delivery_id]).await.unwrap();
+        assert_eq!(attempts[0].status_code, Some(200));
+        assert_eq!(attempts[0].response_excerpt.as_deref(), Some("endpoint says hi"));
+        assert_eq!(process_due(&db, &webhooker()).await.unwrap(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_failed_delivery_is_rescheduled_and_can_be_redelivered() {
+        let db = DB::in_memory();
+        let (webhook_id, delivery_id) = enqueue(&db, &endpoint(StatusCode::SERVICE_UNAVAILABLE).await).await;
+
+        process_due(&db, &webhooker()).await.unwrap();
+        let delivery = &db.list_webhook_deliveries(&webhook_id, 10, 0).await.unwrap()[0];
+        assert_eq!((delivery.status.as_str(), delivery.attempts), ("pending", 1));
+        assert!(delivery.next_attempt_at > chrono::Utc::now().naive_utc());
+        // Not due yet.
+        assert_eq!(process_due(&db, &webhooker()).await.unwrap(), 0);
+
+        assert!(db.redeliver_webhook_delivery(&delivery_id, &webhook_id).await.unwrap());
+        process_due(&db, &webhooker()).await.unwrap();
+        let attempts = db.list_webhook_delivery_attempts(&[delivery_id]).await.unwrap();
+        assert_eq!(attempts.len(), 2);
+        assert!(attempts.iter().all(|attempt| attempt.status_code == Some(503)));
+    }
+
+    /// Endpoint stand-in that answers 200 and keeps every request's timestamp, signatures and body.
+    async fn recording_endpoint() -> (String, Arc<Mutex<Vec<(String, String, String)>>>) {
+        let requests = Arc::new(Mutex::new(Vec::new()));
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/hook", listener.local_addr().unwrap());
+        let recorded = requests.clone();
+        let app = Router::new().route("/hook", post(move |headers: HeaderMap, body: String| async move {
+            let header = |name| headers.get(name).and_then(|value| value.to_str().ok()).unwrap_or_default().to_string();
+            recorded.lock().unwrap().push((header(TIMESTAMP_HEADER), header(SIGNATURE_HEADER), body));
+            StatusCode::OK
+        }));
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+        (url, requests)
+    }
+
+    #[tokio::test]
+    async fn test_rotated_secret_signs_with_both_during_grace() {
+        let db = DB::in_memory();
+        let (url, requests) = recording_endpoint().await;
+        let user_id = Uuid::new_v4();
+        let webhook = db.create_webhook(&url, "old", &user_id, &[], API_VERSION).await.unwrap();
+        db.rotate_webhook_secret(&webhook.id, &user_id, "new", Duration::from_secs(60)).await.unwrap();
+        db.enqueue_webhook_delivery(&webhook.id, &json!({"id": 1})).await.unwrap();
+
+        process_due(&db, &webhooker()).await.unwrap();
+
+        let (timestamp, signatures, body) = requests.lock().unwrap()[0].clone();
+        assert_eq!(signatures.split(',').count(), 2);
+        for secret in ["old", "new"] {
+            Verifier::new(secret).verify(&timestamp, &signatures, body.as_bytes()).unwrap();
+        }
+
+        db.rotate_webhook_secret(&webhook.id, &user_id, "newer", Duration::ZERO).await.unwrap();
+        db.enqueue_webhook_delivery(&webhook.id, &json!({"id": 2})).await.unwrap();
+        process_due(&db, &webhooker()).await.unwrap();
+        let (timestamp, signatures, body) = requests.lock().unwrap()[1].clone();
+        assert!(!signatures.contains(','));
+        assert!(Verifier::new("new").verify(&timestamp, &signatures, body.as_bytes()).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_disabled_webhook_deliveries_wait() {
+        let db = DB::in_memory();
+        let user_id = Uuid::new_v4();
+        let webhook = db.create_webhook(&endpoint(StatusCode::OK).await, "secret", &user_id, &[], API_VERSION).await.unwrap();
+        db.enqueue_webhook_delivery(&webhook.id, &json!({"id": 1})).await.unwrap();
+
+        db.update_webhook(&webhook.id, &user_id, None, Some(false), None).await.unwrap();
+        assert_eq!(process_due(&db, &webhooker()).await.unwrap(), 0);
+
+        db.update_webhook(&webhook.id, &user_id, None, Some(true), None).await.unwrap();
+        assert_eq!(process_due(&db, &webhooker()).await.unwrap(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_unreachable_endpoint_records_error() {
+        let db = DB::in_memory();
+        let (_, delivery_id) = enqueue(&db, "http://127.0.0.1:1/hook").await;
+
+        process_due(&db, &webhooker()).await.unwrap();
+        let attempts = db.list_webhook_delivery_attempts(&[delivery_id]).await.unwrap();
+        assert!(attempts[0].status_code.is_none());
+        assert!(attempts[0].error.is_some());
+    }
+
+    #[tokio::test]
+    async fn test_redirect_to_internal_address_is_refused() {
+        let db = DB::in_memory();
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/hook", listener.local_addr().unwrap());
+        let app = Router::new().route("/hook", post(|| async {
+            axum::response::Redirect::temporary("http://169.254.169.254/latest/meta-data/")
+        }));
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+        let (_, delivery_id) = enqueue(&db, &url).await;
+
+        process_due(&db, &webhooker()).await.unwrap();
+        let attempts = db.list_webhook_delivery_attempts(&[delivery_id]).await.unwrap();
+        assert!(attempts[0].status_code.is_none());
+        assert!(attempts[0].error.as_deref().unwrap().contains("redirect refused"));
+    }
+
+    #[tokio::test]
+    async fn test_host_resolving_to_internal_address_is_not_contacted() {
+        let db = DB::in_memory();
+        let webhooker = Webhooker::new(stub_guard(&[("hooks.example.com", "10.0.0.5")], true)).unwrap();
+        let (_, delivery_id) = enqueue(&db, "http://hooks.example.com/hook").await;
+
+        process_due(&db, &webhooker).await.unwrap();
+        let attempts = db.list_webhook_delivery_attempts(&[delivery_id]).await.unwrap();
+        assert!(attempts[0].error.as_deref().unwrap().contains("resolves to an internal address"));
+    }
+}
@@ -6,7 +6,10 @@ pub struct Network {
     pub name: String,
     pub id: i64,
     pub link: String,
-    pub addresses: Addresses
+    pub addresses: Addresses,
+    /// Block explorer base URL, e.g. `https://optimistic.etherscan.io`; transactions link to `<explorer>/tx/<hash>`.
+    #[serde(default)]
+    pub explorer: Option<String>,
 }
 
 #[derive(Clone, Deserialize, Serialize)]
@@ -16,16 +19,13 @@ pub struct Addresses {
 }
 
 impl Network {
-    pub fn default_vec() -> Result<Vec<Self>, String> {
-        Self::vec_from_env("NETWORKS")
-    }
-    pub fn vec_from_env(env_name: &str) -> Result<Vec<Self>, String> {
-        Self::parse_networks(&utils::get_env_var(env_name)?)
+    pub(crate) fn parse_networks(json: &str) -> Result<Vec<Self>, String> {
+        serde_json::from_str(json)
+            .map_err(|err| utils::make_err(Box::new(err), "parse networks"))
     }
 
-    fn parse_networks(json: &str) -> Result<Vec<Self>, String> {
-        serde_json::from_str(json)
-            .map_err(|err| utils::make_err(Box::new(err), "parse env networks"))
+    pub fn tx_url(&self, tx_hash: &str) -> Option<String> {
+        self.explorer.as_ref().map(|explorer| format!("{}/tx/{}", explorer.trim_end_matches('/'), tx_hash))
     }
 }
 
@@ -44,12 +44,15 @@ mod tests {
     }
 
     #[test]
-    fn test_parse_invalid_json() {
-        assert!(Network::parse_networks("not json").is_err());
+    fn test_tx_url() {
+        let networks = Network::parse_networks(
+            r#"[{"name":"Optimism","id":10,"link":"https://rpc.example.com","addresses":{"erc20":"0xabc","contract":"0xdef"},"explorer":"https://optimistic.etherscan.io/"}]"#
+        ).unwrap();
+        assert_eq!(networks[0].tx_url("0x1").as_deref(), Some("https://optimistic.etherscan.io/tx/0x1"));
     }
 
     #[test]
-    fn test_missing_env_var() {
-        assert!(Network::vec_from_env("TEST_NETWORKS_MISSING_12345").is_err());
+    fn test_parse_invalid_json() {
+        assert!(Network::parse_networks("not json").is_err());
     }
 }
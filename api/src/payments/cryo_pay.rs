@@ -5,15 +5,15 @@ use url::Url;
 use uuid::Uuid;
 use crate::api::{get_invoice_full_path, get_target_invoice_path};
 use crate::api::state::DB;
+use crate::config::{ApiConfig, CryoPayConfig};
 use crate::db::billing::Payment;
 use crate::error::AppError;
-use crate::network::Network;
+use crate::payments::cryo_pay_config;
 use crate::utils;
 
-fn get_url(path: &str) -> Result<String, String> {
-    utils::get_self_url()
-        .and_then(|base_url| Url::parse(&base_url)
-            .map_err(|err| utils::make_err(Box::new(err), "parse base url")))
+fn get_url(self_url: &str, path: &str) -> Result<String, String> {
+    Url::parse(self_url)
+        .map_err(|err| utils::make_err(Box::new(err), "parse base url"))
         .and_then(|base| base.join(path)
             .map_err(|err| utils::make_err(Box::new(err), "join path")))
         .map(|url| url.to_string())
@@ -25,38 +25,19 @@ pub struct CryoPayRecipient {
 }
 
 impl CryoPayRecipient {
-    pub fn default(available_networks: &Vec<Network>) -> Result<Self, String> {
-        let seller = utils::get_env_var("CRYO_PAY_SELF_ADDRESS")?;
-        let networks_names: Vec<String> = serde_json::from_str(
-            &utils::get_env_var("CRYO_PAY_RECIEVE_FROM_NETWORKS")?)
-            .map_err(|err| utils::make_err(
-                Box::new(err), "parse CRYO_PAY_RECIEVE_FROM_NETWORKS env"))?;
-        let networks = available_networks
-            .into_iter()
-            .filter(|n| networks_names.contains(&n.name))
-            .map(|n| n.id)
-            .collect::<Vec<i64>>();
-
-        Ok(Self::new(seller, networks))
-    }
-
-    fn new(seller: String, networks: Vec<i64>) -> Self {
-        Self { seller, networks }
+    pub fn new(config: &CryoPayConfig) -> Self {
+        Self { seller: config.seller.clone(), networks: config.networks.clone() }
     }
 }
 
 pub struct CryoPayApi {
+    self_url: String,
     api_key: Option<String>,
 }
 
 impl CryoPayApi {
-    pub fn default() -> Self {
-        let api_key = utils::get_env_var("CRYO_PAY_API_KEY").ok();
-        CryoPayApi::new(api_key)
-    }
-
-    fn new(api_key: Option<String>) -> Self {
-        CryoPayApi{ api_key }
+    pub fn new(config: &ApiConfig, cryo_pay: &CryoPayConfig) -> Self {
+        CryoPayApi { self_url: config.self_url(), api_key: cryo_pay.api_key.clone() }
     }
 
     pub async fn create_invoice(
@@ -73,7 +54,7 @@ impl CryoPayApi {
             "external_id": custom_id,
         });
 
-        let url = get_url(&get_invoice_full_path())?;
+        let url = get_url(&self.self_url, &get_invoice_full_path())?;
         let mut request = Client::new().post(url);
         if let Some(api_key) = &self.api_key {
             request = request.header("Authorization", format!("Bearer {api_key}"))
@@ -110,7 +91,7 @@ impl CryoPayApi {
     }
 
     pub async fn is_invoice_paid(&self, id: &Uuid) -> Result<bool, String> {
-        let mut request = Client::new().get(get_url(&get_target_invoice_path(id))?);
+        let mut request = Client::new().get(get_url(&self.self_url, &get_target_invoice_path(id))?);
         if let Some(api_key) = &self.api_key {
             request = request.header("Authorization", format!("Bearer {api_key}"))
         }
@@ -129,19 +110,16 @@ impl CryoPayApi {
                     .map_err(|err| utils::make_err(Box::new(err), "parse response"))?;
                 match json_response.get("paid_at") {
                         None => Err("Failed to parse paid_at".to_string()),
-                        Some(paid_at) => Ok(match paid_at {
-                            Value::Null => false,
-                            _ => true,
-                        })
+                        Some(paid_at) => Ok(!matches!(paid_at, Value::Null))
                     }
             }
         }
     }
 }
 
-pub fn get_payment_path(invoice_id: &Uuid, callback_url: Option<String>) -> Result<String, String> {
-    let web_base_url_len = utils::web_base_url()?.len();
-    let mut url = Url::parse(&utils::get_invoice_url(invoice_id)?)
+pub fn get_payment_path(web_base_url: &str, invoice_id: &Uuid, callback_url: Option<String>) -> Result<String, String> {
+    let web_base_url_len = web_base_url.len();
+    let mut url = Url::parse(&utils::get_invoice_url(web_base_url, invoice_id))
         .map_err(|err| utils::make_err(Box::new(err), "parse invoice url"))?;
 
     if let Some(callback) = callback_url {
@@ -160,10 +138,11 @@ pub enum PaidPayableResult {
     Payment(Payment),
 }
 
-pub async fn get_paid_payable(db: &DB, invoice_id: &Uuid) -> Result<PaidPayableResult, AppError> {
+pub async fn get_paid_payable(config: &ApiConfig, db: &DB, invoice_id: &Uuid) -> Result<PaidPayableResult, AppError> {
     Ok(match db.get_payment(invoice_id).await? {
         None => PaidPayableResult::NotFound,
-        Some(payment) => match CryoPayApi::default().is_invoice_paid(invoice_id).await
+        Some(payment) => match CryoPayApi::new(config, cryo_pay_config(config).map_err(AppError::Internal)?)
+            .is_invoice_paid(invoice_id).await
             .map_err(AppError::Network)? {
             false => PaidPayableResult::NotPaid,
             true => PaidPayableResult::Payment(payment)
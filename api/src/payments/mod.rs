@@ -3,7 +3,7 @@ use sha2::{Sha256, Digest};
 use url::Url;
 use uuid::Uuid;
 use crate::api::get_cryo_pay_callback_full_path;
-use crate::network::Network;
+use crate::config::{ApiConfig, CryoPayConfig};
 use crate::payments::cryo_pay::{CryoPayApi, CryoPayRecipient, get_payment_path};
 use crate::payments::payable::Payable;
 use crate::utils;
@@ -23,11 +23,10 @@ pub enum ToPayId {
 }
 
 impl ToPay {
-    pub async fn create(amount: BigDecimal, custom_id: Option<String>, payable: Payable) -> Result<Self, String> {
-        let cryo_pay_api = CryoPayApi::default();
-        let cryo_pay_recipient = CryoPayRecipient::default(
-            &Network::default_vec()?
-        )?;
+    pub async fn create(config: &ApiConfig, amount: BigDecimal, custom_id: Option<String>, payable: Payable) -> Result<Self, String> {
+        let cryo_pay = cryo_pay_config(config)?;
+        let cryo_pay_api = CryoPayApi::new(config, cryo_pay);
+        let cryo_pay_recipient = CryoPayRecipient::new(cryo_pay);
 
         let invoice_id = cryo_pay_api
             .create_invoice(&cryo_pay_recipient.seller, &cryo_pay_recipient.networks, custom_id, &amount)
@@ -40,21 +39,26 @@ impl ToPay {
         Self { id, payable }
     }
 
-    pub fn payment_url(&self) -> Result<String, String> {
-        let global_api_url = utils::ApiGlobalUrl::get()?.url()?;
+    pub fn payment_url(&self, config: &ApiConfig) -> Result<String, String> {
+        let global_api_url = cryo_pay_config(config)?.api_global_url.url(&config.web_base_url);
         let callback_path = get_cryo_pay_callback_full_path();
 
-        let secret = utils::get_env_var("APP_SECRET")?;
-        let token = hex::encode(Sha256::digest(format!("internal:{}", secret)));
+        let token = hex::encode(Sha256::digest(format!("internal:{}", config.app_secret)));
 
         let mut callback_url = Url::parse(&utils::combine_paths(&[&global_api_url, &callback_path]))
             .map_err(|err| utils::make_err(Box::new(err), "parse callback url"))?;
         callback_url.query_pairs_mut().append_pair("token", &token);
 
         let payment_path = match self.id {
-            ToPayId::CryoPay(id) => get_payment_path(&id, Some(callback_url.to_string()))?
+            ToPayId::CryoPay(id) => get_payment_path(&config.web_base_url, &id, Some(callback_url.to_string()))?
         };
 
-        Ok(utils::combine_paths(&[&utils::web_base_url()?, &payment_path]))
+        Ok(utils::combine_paths(&[&config.web_base_url, &payment_path]))
     }
 }
+
+pub fn cryo_pay_config(config: &ApiConfig) -> Result<&CryoPayConfig, String> {
+    config.cryo_pay
+        .as_ref()
+        .ok_or_else(|| "Cryo Pay self-payments are not configured (CRYO_PAY_SELF_ADDRESS)".to_string())
+}
@@ -1,8 +1,10 @@
 use std::sync::Arc;
 use serde::{Deserialize, Serialize};
+use tracing::error;
 use crate::api::state::AppState;
 use crate::db::billing::Payment;
 use crate::error::AppError;
+use crate::events::webhook_event::{self, WebhookEvent};
 use crate::payments::donation::Donation;
 use crate::payments::subscription::Subscription;
 use crate::utils;
@@ -26,6 +28,11 @@ pub async fn apply(state: &Arc<AppState>, payment: &Payment) -> Result<(), AppEr
             let user_id = payment.user_id.ok_or_else(|| AppError::Internal("subscription user not detected".to_string()))?;
             let target: String = subscription.target.into();
             state.db.create_or_update_subscription(&user_id, &target, None, subscription.until).await?;
+
+            let event = WebhookEvent::subscription_activated(target, subscription.until);
+            if let Err(err) = webhook_event::publish(&state.db, &user_id, &event).await {
+                error!("Failed to publish subscription.activated for payment {}: {err}", payment.id);
+            }
         }
     }
 
@@ -0,0 +1,156 @@
+use std::future::Future;
+use std::time::Duration;
+use tokio::task::JoinHandle;
+use tokio_util::sync::CancellationToken;
+use tokio_util::task::TaskTracker;
+use tracing::{info, warn};
+
+/// Shared between the API, the monitor and the bot so a single SIGTERM stops all of them.
+/// Work that must not be cut off halfway (event processing, notifications) is spawned through
+/// [`Shutdown::spawn`] and awaited by [`Shutdown::drain`].
+#[derive(Clone)]
+pub struct Shutdown {
+    token: CancellationToken,
+    tasks: TaskTracker,
+    timeout: Duration,
+}
+
+impl Shutdown {
+    pub fn new(timeout: Duration) -> Self {
+        Self { token: CancellationToken::new(), tasks: TaskTracker::new(), timeout }
+    }
+
+    /// Triggers shutdown on SIGTERM or Ctrl+C.
+    pub fn listen_for_signals(&self) {
+        let shutdown = self.clone();
+        tokio::spawn(async move {
+            wait_for_signal().await;
+            info!("Shutdown signal received");
+            shutdown.trigger();
+        });
+    }
+
+    pub fn trigger(&self) {
+        self.token.cancel();
+    }
+
+    pub fn is_triggered(&self) -> bool {
+        self.token.is_cancelled()
+    }
+
+    /// Resolves once shutdown has been requested.
+    pub fn signal(&self) -> impl Future<Output = ()> + Send + 'static {
+        self.token.clone().cancelled_owned()
+    }
+
+    /// Sleeps for `duration`, returning early with `false` if shutdown is requested meanwhile.
+    pub async fn sleep(&self, duration: Duration) -> bool {
+        tokio::select! {
+            _ = tokio::time::sleep(duration) => true,
+            _ = self.token.cancelled() => false,
+        }
+    }
+
+    pub fn spawn<F>(&self, task: F) -> JoinHandle<F::Output>
+    where
+        F: Future + Send + 'static,
+        F::Output: Send + 'static,
+    {
+        self.tasks.spawn(task)
+    }
+
+    /// Runs `component` until it returns by itself, giving it at most the shutdown timeout
+    /// to wind down once shutdown has been requested.
+    pub async fn with_deadline<F>(&self, name: &str, component: F) -> Result<(), String>
+    where
+        F: Future<Output = Result<(), String>>,
+    {
+        tokio::pin!(component);
+
+        tokio::select! {
+            result = &mut component => result,
+            _ = self.token.cancelled() => match tokio::time::timeout(self.timeout, component).await {
+                Ok(result) => result,
+                Err(_) => Err(format!("{name} did not stop within {:?}", self.timeout)),
+            },
+        }
+    }
+
+    /// Waits for tasks started with [`Shutdown::spawn`], up to the shutdown timeout.
+    pub async fn drain(&self) {
+        self.tasks.close();
+        if self.tasks.is_empty() {
+            return;
+        }
+
+        info!("Waiting for {} in-flight tasks", self.tasks.len());
+        if tokio::time::timeout(self.timeout, self.tasks.wait()).await.is_err() {
+            warn!("{} tasks still running after {:?}, exiting anyway", self.tasks.len(), self.timeout);
+        }
+    }
+}
+
+#[cfg(unix)]
+async fn wait_for_signal() {
+    use tokio::signal::unix::{signal, SignalKind};
+
+    match signal(SignalKind::terminate()) {
+        Ok(mut sigterm) => tokio::select! {
+            _ = sigterm.recv() => {},
+            _ = tokio::signal::ctrl_c() => {},
+        },
+        Err(err) => {
+            warn!("Failed to install SIGTERM handler: {err}");
+            let _ = tokio::signal::ctrl_c().await;
+        }
+    }
+}
+
+#[cfg(not(unix))]
+async fn wait_for_signal() {
+    let _ = tokio::signal::ctrl_c().await;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_sleep_returns_early_on_trigger() {
+        let shutdown = Shutdown::new(Duration::from_secs(1));
+        shutdown.trigger();
+        assert!(!shutdown.sleep(Duration::from_secs(60)).await);
+    }
+
+    #[tokio::test]
+    async fn test_with_deadline_lets_component_finish() {
+        let shutdown = Shutdown::new(Duration::from_secs(1));
+        shutdown.trigger();
+        let result = shutdown.with_deadline("test", async {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+            Ok(())
+        }).await;
+        assert!(result.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_with_deadline_gives_up_after_timeout() {
+        let shutdown = Shutdown::new(Duration::from_millis(10));
+        shutdown.trigger();
+        let result = shutdown.with_deadline("test", std::future::pending()).await;
+        assert!(result.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_drain_waits_for_spawned_tasks() {
+        let shutdown = Shutdown::new(Duration::from_secs(1));
+        let done = std::sync::Arc::new(std::sync::atomic::AtomicBool::new(false));
+        let flag = done.clone();
+        shutdown.spawn(async move {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+            flag.store(true, std::sync::atomic::Ordering::SeqCst);
+        });
+        shutdown.drain().await;
+        assert!(done.load(std::sync::atomic::Ordering::SeqCst));
+    }
+}
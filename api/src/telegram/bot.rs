@@ -1,115 +1,357 @@
 use tgbot::api::Client;
-use tgbot::types::{SetWebhook, Update};
-use tgbot::handler::{LongPoll, UpdateHandler, WebhookServer};
-use std::net::SocketAddr;
+use tgbot::types::{
+    CallbackQuery, Chat, ChatMember, ChatMemberUpdated, DeleteWebhook, MaybeInaccessibleMessage, Message, MessageData,
+    MessageSender, Update, UpdateType,
+};
+use tgbot::handler::{LongPoll, UpdateHandler};
 use tracing::{error, info, warn};
-use std::str::FromStr;
-use uuid::Uuid;
-use crate::api::state::DB;
-use crate::telegram::client::send_message;
+use crate::api::middleware::auth::{AppUser, Auth, AuthType};
+use crate::api::middleware::rate_limiting::RateLimit;
+use crate::api::state::{Redis, DB};
+use crate::config::Limits;
+use crate::db::User;
+use crate::shutdown::Shutdown;
+use crate::telegram::client::{answer_callback, edit_reply, is_chat_admin, send_reply, set_commands};
+use crate::telegram::commands::{self, Command, Reply, Seller};
 use crate::utils;
+use uuid::Uuid;
+
+/// Long polling, for when no public URL is configured. Any webhook left over from a previous
+/// deployment is removed first, since Telegram refuses `getUpdates` while one is set.
+pub async fn run_long_poll(handler: Handler, shutdown: Shutdown) -> Result<(), String> {
+    handler.client.execute(DeleteWebhook::default())
+        .await
+        .map_err(|err| utils::make_err(Box::new(err), "delete webhook"))?;
+    if let Err(err) = set_commands(&handler.client, MENU).await {
+        warn!("{err}");
+    }
+
+    info!("running telegram bot as LongPoll");
+    let long_poll = LongPoll::new(handler.client.clone(), handler);
+    let handle = long_poll.get_handle();
+
+    tokio::spawn(async move {
+        shutdown.signal().await;
+        handle.shutdown().await;
+    });
+    long_poll.run().await;
 
-pub enum TelegramBot {
-    LongPool,
-    Webhook(String)
+    Ok(())
 }
 
-impl TelegramBot {
-    pub fn new() -> Result<Self, String> {
-        let env_var = utils::get_env_or("TELEGRAM_WEBHOOK_URL", "0".to_string())?;
+const LINKED: &str = "This chat is now linked to your Cryo Pay account and will receive payment notifications. Send /help to see what else the bot can do.";
+const LINK_EXPIRED: &str = "This link has expired or was already used. Open Settings in Cryo Pay and press Activate Bot again.";
+const NOT_LINKED: &str = "This chat is not linked to a Cryo Pay account. Open Settings in Cryo Pay and press Activate Bot to link it.";
+const UNLINKED: &str = "This chat is unlinked and will no longer receive payment notifications.";
+const TOO_MANY_CHATS: &str = "Your Cryo Pay account already has as many Telegram chats as it can. Unlink one in Settings first.";
+const ADMINS_ONLY: &str = "Only administrators of this chat can link or unlink it.";
+const PRIVATE_ONLY: &str = "This command works only in the private chat with the bot.";
+const GROUP_HELP: &str = "This chat receives payment notifications of a Cryo Pay account.
+/unlink - stop notifying this chat";
+/// Longest label taken from a chat title; sellers can rename it in Settings.
+const MAX_LABEL_LENGTH: usize = 64;
+const TOO_MANY_INVOICES: &str = "You have reached today's invoice limit.";
+const FAILED: &str = "Something went wrong, please try again later.";
 
-        Ok(match utils::is_false(&env_var) {
-            true => Self::LongPool,
-            false => Self::Webhook(env_var),
-        })
+pub(super) const MENU: &[(&str, &str)] = &[
+    ("invoice", "Create an invoice: /invoice 25 USDT order-42"),
+    ("invoices", "Recent invoices"),
+    ("stats", "Today's totals"),
+    ("mute", "Pause payment notifications"),
+    ("unmute", "Resume payment notifications"),
+    ("unlink", "Stop using this chat"),
+];
+
+pub struct Handler {
+    pub(super) client: Client,
+    db: DB,
+    redis: Redis,
+    seller: Seller,
+    limits: Limits,
+}
+
+/// `private`, `group`, `supergroup` or `channel`, as stored with the destination.
+fn chat_type(chat: &Chat) -> &'static str {
+    match chat {
+        Chat::Private(_) => "private",
+        Chat::Group(_) => "group",
+        Chat::Supergroup(_) => "supergroup",
+        Chat::Channel(_) => "channel",
     }
+}
 
-    pub async fn run(&self, client: &Client, db: DB) -> Result<(), String> {
-        let handler = Handler::new(client.clone(), db);
+/// Groups and channels go by their title, private chats by the username.
+fn chat_label(chat: &Chat) -> String {
+    let label = match chat {
+        Chat::Private(chat) => match &chat.username {
+            Some(username) => format!("@{username}"),
+            None => chat.first_name.clone(),
+        },
+        Chat::Group(chat) => chat.title.clone(),
+        Chat::Supergroup(chat) => chat.title.clone(),
+        Chat::Channel(chat) => chat.title.clone(),
+    };
+    match label.trim() {
+        "" => "Private chat".to_string(),
+        label => label.chars().take(MAX_LABEL_LENGTH).collect(),
+    }
+}
 
-        match self {
-            TelegramBot::LongPool => {
-                info!("running telegram bot as LongPool");
-                TelegramBot::run_long_pool(handler).await
-            },
-            TelegramBot::Webhook(url) => {
-                client.execute(SetWebhook::new(url))
-                    .await
-                    .map_err(|err| utils::make_err(Box::new(err), "set webhook"))?;
+/// Whether the sender may link or unlink the chat, when the message alone tells: anyone in a
+/// private chat or a channel (only administrators post there), and anonymous administrators
+/// posting as the group. `None` when a group member's role has to be asked from Telegram.
+fn may_manage(chat: &Chat, sender: &MessageSender) -> Option<bool> {
+    match (chat, sender) {
+        (Chat::Private(_) | Chat::Channel(_), _) => Some(true),
+        (_, MessageSender::Chat(sender)) => Some(sender.get_id() == chat.get_id()),
+        (_, MessageSender::User(_)) => None,
+        (_, MessageSender::Unknown) => Some(false),
+    }
+}
+
+impl Handler {
+    pub(super) fn new(client: Client, db: DB, redis: Redis, seller: Seller, limits: Limits) -> Self {
+        Self { client, db, redis, seller, limits }
+    }
+
+    async fn linked_user(&self, chat_id: &str) -> Result<Option<User>, String> {
+        self.db.get_user_by_telegram_chat_id(chat_id).await.map_err(|err| err.to_string())
+    }
 
-                info!("running telegram bot as Webhook on {}", url);
-                TelegramBot::run_webhook(handler, url).await
-            },
+    async fn start(&self, chat: &Chat, token: Option<&str>) -> Result<Reply, String> {
+        let Some(token) = token else {
+            return Ok(Reply::text(match self.linked_user(&chat.get_id().to_string()).await? {
+                Some(_) if matches!(chat, Chat::Private(_)) => commands::HELP,
+                Some(_) => GROUP_HELP,
+                None => NOT_LINKED,
+            }));
+        };
+
+        let Some(user_id) = self.redis.take_telegram_link_token(token).await.map_err(|err| err.to_string())? else {
+            return Ok(Reply::text(LINK_EXPIRED));
+        };
+
+        self.link(&user_id, chat).await
+    }
+
+    /// Relinking a chat the user already has keeps its settings; new chats count against
+    /// `TELEGRAM_DESTINATIONS_PER_USER`.
+    async fn link(&self, user_id: &Uuid, chat: &Chat) -> Result<Reply, String> {
+        let chat_id = chat.get_id().to_string();
+        let destinations = self.db.list_telegram_destinations(user_id).await.map_err(|err| err.to_string())?;
+        let known = destinations.iter().any(|destination| destination.chat_id == chat_id);
+        if !known && destinations.len() >= self.limits.telegram_destinations_per_user {
+            return Ok(Reply::text(TOO_MANY_CHATS));
+        }
+
+        self.db.link_telegram_chat(user_id, &chat_id, chat_type(chat), &chat_label(chat))
+            .await
+            .map_err(|err| err.to_string())?;
+        info!("Linked a telegram {} to user_id={:?}", chat_type(chat), user_id);
+
+        Ok(Reply::text(LINKED))
+    }
+
+    /// `/start` and `/unlink` in a group would otherwise let any member silence the seller or
+    /// take the chat over for their own account.
+    async fn can_manage(&self, message: &Message) -> Result<bool, String> {
+        match (may_manage(&message.chat, &message.sender), message.sender.get_user()) {
+            (Some(allowed), _) => Ok(allowed),
+            (None, Some(user)) => is_chat_admin(&self.client, message.chat.get_id().into(), user.id.into()).await,
+            (None, None) => Ok(false),
         }
     }
 
-    async fn run_long_pool(handler: Handler) -> Result<(), String> {
-        LongPoll::new(handler.client.clone(), handler)
-            .run()
-            .await;
+    async fn unlink(&self, chat_id: &str) -> Result<Reply, String> {
+        let unlinked = self.db.unlink_telegram_chat(chat_id).await.map_err(|err| err.to_string())?;
 
+        Ok(Reply::text(match unlinked {
+            true => UNLINKED,
+            false => NOT_LINKED,
+        }))
+    }
+
+    /// The bot joining a group or channel links it to the account of whoever added it, if
+    /// their private chat is linked; channels can be linked no other way, since Telegram
+    /// passes no start token there. The bot leaving or being blocked unlinks the chat.
+    async fn handle_membership(&self, update: &ChatMemberUpdated) -> Result<Option<Reply>, String> {
+        let chat_id = update.chat.get_id().to_string();
+        match update.new_chat_member {
+            ChatMember::Left(_) | ChatMember::Kicked(_) => {
+                if self.db.unlink_telegram_chat(&chat_id).await.map_err(|err| err.to_string())? {
+                    info!("Unlinked a telegram {} the bot was removed from", chat_type(&update.chat));
+                }
+                Ok(None)
+            }
+            ChatMember::Administrator(_) | ChatMember::Member { .. } if !matches!(update.chat, Chat::Private(_)) => {
+                if self.linked_user(&chat_id).await?.is_some() {
+                    return Ok(None);
+                }
+                let adder = self.db.get_telegram_destination(&update.from.id.to_string())
+                    .await
+                    .map_err(|err| err.to_string())?
+                    .filter(|destination| destination.is_private());
+                match adder {
+                    Some(adder) => self.link(&adder.user_id, &update.chat).await.map(Some),
+                    None => Ok(None),
+                }
+            }
+            _ => Ok(None),
+        }
+    }
+
+    /// Telegram gives a group a new id when it becomes a supergroup.
+    async fn migrate(&self, message: &Message, new_chat_id: i64) -> Result<(), String> {
+        let chat_id = message.chat.get_id().to_string();
+        if self.db.move_telegram_chat(&chat_id, &new_chat_id.to_string(), "supergroup").await.map_err(|err| err.to_string())? {
+            info!("Moved a telegram destination to its new supergroup id");
+        }
         Ok(())
     }
 
-    async fn run_webhook(handler: Handler, url: &str) -> Result<(), String> {
-        WebhookServer::new("/", handler)
-            .run(
-                SocketAddr::from_str(&url)
-                    .map_err(|err| utils::make_err(Box::new(err), "parse socket addr"))?)
+    /// Invoices from chat count against the same daily limit as the web UI.
+    async fn create_invoice(&self, user: User, args: &[&str]) -> Result<Reply, String> {
+        let app_user = AppUser::new("telegram".to_string(), Some(Auth::new(AuthType::WEB, user.clone())));
+        let allowed = RateLimit::product_invoice(&self.db, &self.limits.rate, &app_user)
+            .await
+            .is_ok(&self.redis, &app_user)
             .await
-            .map_err(|err| utils::make_err(Box::new(err), "run telegram bot as webhook"))?;
+            .map_err(|err| err.to_string())?;
 
-        Ok(())
+        match allowed {
+            true => self.seller.create_invoice(&user, args).await,
+            false => Ok(Reply::text(TOO_MANY_INVOICES)),
+        }
     }
-}
 
-struct Handler {
-    client: Client,
-    db: DB,
-}
+    /// Groups and channels only take `/start`, `/unlink` and `/help`; the seller commands would
+    /// show the account to everyone in the chat. Linking and unlinking a group is up to its
+    /// administrators.
+    async fn reply(&self, message: &Message, command: Command<'_>) -> Result<Reply, String> {
+        let chat = &message.chat;
+        let chat_id = chat.get_id().to_string();
+        let manages = matches!(command, Command::Start(Some(_)) | Command::Unlink);
+        if manages && !self.can_manage(message).await? {
+            return Ok(Reply::text(ADMINS_ONLY));
+        }
+        match command {
+            Command::Start(token) => return self.start(chat, token).await,
+            Command::Unlink => return self.unlink(&chat_id).await,
+            _ => {}
+        }
+        let Some(user) = self.linked_user(&chat_id).await? else {
+            return Ok(Reply::text(NOT_LINKED));
+        };
+        if !matches!(chat, Chat::Private(_)) {
+            return Ok(Reply::text(match command {
+                Command::Help => GROUP_HELP,
+                _ => PRIVATE_ONLY,
+            }));
+        }
 
-impl Handler {
-    fn new(client: Client, db: DB) -> Self {
-        Self { client, db }
+        match command {
+            Command::Start(_) | Command::Unlink => unreachable!("handled above"),
+            Command::Invoice(args) => self.create_invoice(user, &args).await,
+            Command::Invoices => self.seller.invoices(&user, 0).await,
+            Command::Stats => self.seller.stats(&user).await,
+            Command::Mute => self.seller.mute(&user, true).await,
+            Command::Unmute => self.seller.mute(&user, false).await,
+            Command::Help => Ok(Reply::text(commands::HELP)),
+        }
+    }
+
+    /// Pagination buttons of `/invoices` edit the message they belong to.
+    async fn handle_callback(&self, query: &CallbackQuery) {
+        if let Err(err) = answer_callback(&self.client, &query.id).await {
+            error!(err);
+        }
+
+        let (Some(MaybeInaccessibleMessage::Message(message)), Some(page)) = (
+            &query.message,
+            query.data.as_deref().and_then(commands::parse_callback),
+        ) else {
+            return;
+        };
+        let chat_id = message.chat.get_id();
+
+        let reply = match self.linked_user(&chat_id.to_string()).await {
+            Ok(Some(user)) => self.seller.invoices(&user, page).await,
+            Ok(None) => Ok(Reply::text(NOT_LINKED)),
+            Err(err) => Err(err),
+        };
+        match reply {
+            Ok(reply) => if let Err(err) = edit_reply(&self.client, chat_id.into(), message.id, &reply).await { error!(err) },
+            Err(err) => error!("{err}"),
+        }
     }
 }
 
 impl UpdateHandler for Handler {
     async fn handle(&self, update: Update) {
-        let chat_id = match update.get_chat_id() {
-            None => {
-                warn!("chat id not extractable: {:?}", update);
+        match &update.update_type {
+            UpdateType::CallbackQuery(query) => return self.handle_callback(query).await,
+            UpdateType::BotStatus(member) => {
+                let reply = match self.handle_membership(member).await {
+                    Ok(reply) => reply,
+                    Err(err) => return error!("{err}"),
+                };
+                if let Some(reply) = reply {
+                    if let Err(err) = send_reply(&self.client, member.chat.get_id().into(), &reply).await { error!(err) }
+                }
                 return;
             }
-            Some(chat_id) => chat_id
+            _ => {}
+        }
+
+        let Some(message) = update.get_message() else {
+            return;
+        };
+        if let MessageData::MigrateToChatId(new_chat_id) = message.data {
+            if let Err(err) = self.migrate(message, new_chat_id).await { error!("{err}") }
+            return;
+        }
+        let Some(command) = message.get_text().and_then(|text| Command::parse(&text.data)) else {
+            return;
         };
+        let chat_id = message.chat.get_id();
 
-        let user_id = match update.get_message()
-            .and_then(|msg| msg.get_text())
-            .map(|text| &text.data) {
-            None => return,
-            Some(text) => {
-                let texts = text.split(" ").collect::<Vec<&str>>();
-                let user_id_str = match texts.last() {
-                    None => return,
-                    Some(text) => text.to_owned()
-                };
-                match Uuid::parse_str(user_id_str) {
-                    Ok(user_id) => user_id,
-                    Err(_) => return,
-                }
+        let reply = match self.reply(message, command).await {
+            Ok(reply) => reply,
+            Err(err) => {
+                error!("{err}");
+                Reply::text(FAILED)
             }
         };
 
-        match self.db.set_user_telegram_chat_id(&user_id, Some(chat_id.to_string())).await {
-            Ok(_) => info!("Set telegram_chat_id for user_id={:?}", user_id),
-            Err(err) => error!("{err}"),
-        };
+        if let Err(err) = send_reply(&self.client, chat_id.into(), &reply).await { error!(err) }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+    use tgbot::types::{ChannelChat, GroupChat, PrivateChat, SupergroupChat, User as TelegramUser};
+
+    #[rstest]
+    #[case(Chat::Private(PrivateChat::new(1, "Alice").with_username("alice")), "@alice", "private")]
+    #[case(Chat::Private(PrivateChat::new(1, "Alice")), "Alice", "private")]
+    #[case(Chat::Private(PrivateChat::new(1, " ")), "Private chat", "private")]
+    #[case(Chat::Group(GroupChat::new(-1, "Team")), "Team", "group")]
+    #[case(Chat::Channel(ChannelChat::new(-100, "Payments of the northern warehouse team, including weekend night shifts")), "Payments of the northern warehouse team, including weekend night", "channel")]
+    fn test_chat_label(#[case] chat: Chat, #[case] label: &str, #[case] kind: &str) {
+        assert_eq!(chat_label(&chat), label);
+        assert_eq!(chat_type(&chat), kind);
+    }
 
-        if let Err(err) = send_message(
-            &self.client,
-            chat_id.into(),
-            "We have saved your contact and will be able to send you payment notifications here."
-        ).await { error!(err) }
+    #[rstest]
+    #[case(Chat::Private(PrivateChat::new(1, "Alice")), MessageSender::User(TelegramUser::new(1, "Alice", false)), Some(true))]
+    #[case(Chat::Channel(ChannelChat::new(-100, "Shop")), MessageSender::Chat(Chat::Channel(ChannelChat::new(-100, "Shop"))), Some(true))]
+    #[case(Chat::Group(GroupChat::new(-1, "Team")), MessageSender::User(TelegramUser::new(2, "Bob", false)), None)]
+    #[case(Chat::Supergroup(SupergroupChat::new(-100, "Team")), MessageSender::Chat(Chat::Supergroup(SupergroupChat::new(-100, "Team"))), Some(true))]
+    #[case(Chat::Supergroup(SupergroupChat::new(-100, "Team")), MessageSender::Chat(Chat::Channel(ChannelChat::new(-200, "Other"))), Some(false))]
+    #[case(Chat::Group(GroupChat::new(-1, "Team")), MessageSender::Unknown, Some(false))]
+    fn test_may_manage(#[case] chat: Chat, #[case] sender: MessageSender, #[case] expected: Option<bool>) {
+        assert_eq!(may_manage(&chat, &sender), expected);
     }
 }
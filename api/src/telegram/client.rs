@@ -1,49 +1,100 @@
 use tgbot::api::Client;
-use tgbot::types::{ChatId, GetBot, InlineKeyboardButton, InlineKeyboardMarkup, SendMessage};
-use crate::db::Invoice;
+use tgbot::types::{
+    AnswerCallbackQuery, BotCommand, ChatId, ChatMember, EditMessageText, GetBot, GetChatMember, InlineKeyboardButton,
+    InlineKeyboardMarkup, Integer, ParseMode, SendMessage, SetBotCommands,
+};
+use super::commands::{Button, Reply};
 use crate::utils;
 
-pub async fn get_client() -> Result<Client, String> {
-    let token = utils::get_env_var("TGBOT_TOKEN")?;
+pub async fn get_client(token: &str) -> Result<Client, String> {
     Client::new(token)
         .map_err(|err| utils::make_err(Box::new(err), "create telegram client"))
 }
 
-pub async fn send_invoice_paid(client: &Client, chat_id: ChatId, invoice: &Invoice) -> Result<(), String> {
-    let mut reply_markup = InlineKeyboardMarkup::default();
-    reply_markup = reply_markup.add_row(
-        vec![InlineKeyboardButton::for_url("Check", &invoice.web_url()?)]
-    );
-
-    let mut lines = vec![
-        "Invoice".to_string(),
-        format!("ID: {}", &invoice.id.to_string()),
-        "Paid".to_string(),
-    ];
-    if let Some(external_id) = &invoice.external_id {
-        lines.insert(2, format!("External ID: {}", external_id))
+pub async fn send_markdown(client: &Client, chat_id: ChatId, text: &str, button: Option<(String, String)>) -> Result<(), String> {
+    let mut message = SendMessage::new(chat_id, text).with_parse_mode(ParseMode::MarkdownV2);
+    if let Some((label, url)) = button {
+        let reply_markup = InlineKeyboardMarkup::default()
+            .add_row(vec![InlineKeyboardButton::for_url(label, url)]);
+        message = message.with_reply_markup(reply_markup);
     }
-    let message = lines.join("\n");
 
     client
-        .execute(SendMessage::new(
-            chat_id, &message)
-                .with_reply_markup(reply_markup))
+        .execute(message)
         .await
         .map_err(|err| utils::make_err(Box::new(err), "send telegram message"))?;
 
     Ok(())
 }
 
-pub async fn send_message(client: &Client, chat_id: ChatId, text: &str) -> Result<(), String> {
+fn keyboard(reply: &Reply) -> InlineKeyboardMarkup {
+    reply.buttons.iter().fold(InlineKeyboardMarkup::default(), |markup, row| markup.add_row(
+        row.iter().map(|button| match button {
+            Button::Url(label, url) => InlineKeyboardButton::for_url(label, url),
+            Button::Callback(label, data) => InlineKeyboardButton::for_callback_data(label, data),
+        }).collect::<Vec<_>>()
+    ))
+}
+
+pub async fn send_reply(client: &Client, chat_id: ChatId, reply: &Reply) -> Result<(), String> {
+    let mut message = SendMessage::new(chat_id, &reply.text);
+    if !reply.buttons.is_empty() {
+        message = message.with_reply_markup(keyboard(reply));
+    }
+
     client
-        .execute(SendMessage::new(chat_id, text))
+        .execute(message)
         .await
         .map_err(|err| utils::make_err(Box::new(err), "send telegram message"))?;
 
     Ok(())
 }
 
+/// Replaces the text and buttons of a message the bot sent, e.g. to turn a page.
+pub async fn edit_reply(client: &Client, chat_id: ChatId, message_id: Integer, reply: &Reply) -> Result<(), String> {
+    client
+        .execute(EditMessageText::for_chat_message(chat_id, message_id, &reply.text).with_reply_markup(keyboard(reply)))
+        .await
+        .map_err(|err| utils::make_err(Box::new(err), "edit telegram message"))?;
+
+    Ok(())
+}
+
+/// Stops the loading indicator on the pressed button.
+pub async fn answer_callback(client: &Client, callback_query_id: &str) -> Result<(), String> {
+    client
+        .execute(AnswerCallbackQuery::new(callback_query_id))
+        .await
+        .map_err(|err| utils::make_err(Box::new(err), "answer telegram callback query"))?;
+
+    Ok(())
+}
+
+/// Whether the user created or administers the chat.
+pub async fn is_chat_admin(client: &Client, chat_id: ChatId, user_id: Integer) -> Result<bool, String> {
+    client
+        .execute(GetChatMember::new(chat_id, user_id))
+        .await
+        .map_err(|err| utils::make_err(Box::new(err), "get telegram chat member"))
+        .map(|member| matches!(member, ChatMember::Administrator(_) | ChatMember::Creator(_)))
+}
+
+/// The command menu Telegram shows next to the input field.
+pub async fn set_commands(client: &Client, commands: &[(&str, &str)]) -> Result<(), String> {
+    let commands = commands
+        .iter()
+        .map(|(name, description)| BotCommand::new(*name, *description))
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|err| utils::make_err(Box::new(err), "build telegram bot commands"))?;
+
+    client
+        .execute(SetBotCommands::new(commands))
+        .await
+        .map_err(|err| utils::make_err(Box::new(err), "set telegram bot commands"))?;
+
+    Ok(())
+}
+
 pub async fn get_bot_name(client: &Client) -> Result<String, String> {
     client
         .execute(GetBot)
@@ -0,0 +1,362 @@
+//! Bot commands and their replies. Everything except `/start` and `/unlink` works on the
+//! account linked to the chat.
+
+use bigdecimal::BigDecimal;
+use chrono::Utc;
+use std::str::FromStr;
+use crate::api::payments::{self, CreateInvoiceRequest};
+use crate::api::response_error::ResponseError;
+use crate::api::state::DB;
+use crate::db::{Invoice, User};
+use crate::events::notifications::templates::format_amount;
+use crate::network::Network;
+
+pub const INVOICES_PAGE_SIZE: i64 = 5;
+const NO_MORE_INVOICES: &str = "No more invoices.";
+const INVOICES_CALLBACK: &str = "invoices";
+/// The only asset invoices are paid in.
+const CURRENCY: &str = "USDT";
+
+pub const HELP: &str = "Commands:
+/invoice <amount> [USDT] [external id] - create an invoice and get its pay link
+/invoices - your recent invoices
+/stats - today's totals
+/mute, /unmute - pause or resume payment notifications
+/unlink - stop using this chat";
+const INVOICE_USAGE: &str = "Usage: /invoice <amount> [USDT] [external id], e.g. /invoice 25 USDT order-42";
+
+#[derive(Debug, PartialEq)]
+pub enum Command<'a> {
+    /// `/start <token>` from the link issued by `/user/attach_telegram`.
+    Start(Option<&'a str>),
+    Unlink,
+    /// `/invoice` with its arguments.
+    Invoice(Vec<&'a str>),
+    Invoices,
+    Stats,
+    Mute,
+    Unmute,
+    Help,
+}
+
+impl<'a> Command<'a> {
+    /// `None` for messages that are not commands.
+    pub fn parse(text: &'a str) -> Option<Self> {
+        let mut words = text.split_whitespace();
+        // In groups commands come as `/start@bot_name`.
+        let command = words.next()?.strip_prefix('/')?.split('@').next()?;
+
+        Some(match command {
+            "start" => Command::Start(words.next()),
+            "unlink" | "stop" => Command::Unlink,
+            "invoice" => Command::Invoice(words.collect()),
+            "invoices" => Command::Invoices,
+            "stats" => Command::Stats,
+            "mute" => Command::Mute,
+            "unmute" => Command::Unmute,
+            _ => Command::Help,
+        })
+    }
+}
+
+/// Page of `/invoices` requested by an inline keyboard button.
+pub fn parse_callback(data: &str) -> Option<i64> {
+    data.strip_prefix(INVOICES_CALLBACK)?
+        .strip_prefix(':')?
+        .parse()
+        .ok()
+        .filter(|page| *page >= 0)
+}
+
+#[derive(Debug, PartialEq)]
+pub enum Button {
+    Url(String, String),
+    Callback(String, String),
+}
+
+/// Plain text with rows of inline keyboard buttons below it.
+#[derive(Debug, PartialEq)]
+pub struct Reply {
+    pub text: String,
+    pub buttons: Vec<Vec<Button>>,
+}
+
+impl Reply {
+    pub fn text(text: impl Into<String>) -> Self {
+        Self { text: text.into(), buttons: vec![] }
+    }
+
+    fn with_row(mut self, row: Vec<Button>) -> Self {
+        if !row.is_empty() {
+            self.buttons.push(row);
+        }
+        self
+    }
+}
+
+#[derive(Debug, PartialEq)]
+struct InvoiceArgs {
+    amount: BigDecimal,
+    external_id: Option<String>,
+}
+
+impl InvoiceArgs {
+    fn parse(args: &[&str]) -> Result<Self, String> {
+        let mut args = args.iter().copied().peekable();
+        let amount = args.next()
+            .and_then(|amount| BigDecimal::from_str(&amount.replace(',', ".")).ok())
+            .ok_or(INVOICE_USAGE)?;
+
+        // Tickers are upper case, so `order` is an external id and `USDC` a currency.
+        if let Some(currency) = args.next_if(|arg| (3..=5).contains(&arg.len()) && arg.chars().all(|c| c.is_ascii_uppercase())) {
+            if currency != CURRENCY {
+                return Err(format!("Only {CURRENCY} invoices are supported."));
+            }
+        }
+
+        let external_id = args.next().map(str::to_string);
+        if args.next().is_some() {
+            return Err(INVOICE_USAGE.to_string());
+        }
+
+        Ok(Self { amount, external_id })
+    }
+}
+
+/// What a linked seller can do from chat.
+pub struct Seller {
+    db: DB,
+    web_base_url: String,
+    networks: Vec<Network>,
+}
+
+impl Seller {
+    pub fn new(db: DB, web_base_url: String, networks: Vec<Network>) -> Self {
+        Self { db, web_base_url, networks }
+    }
+
+    /// Reuses the wallet and networks of the seller's latest invoice, so the first one has to be
+    /// created on the web.
+    pub async fn create_invoice(&self, user: &User, args: &[&str]) -> Result<Reply, String> {
+        let args = match InvoiceArgs::parse(args) {
+            Ok(args) => args,
+            Err(usage) => return Ok(Reply::text(usage)),
+        };
+
+        let Some(latest) = self.db.user_own_invoices(1, 0, &user.id).await.map_err(|err| err.to_string())?.pop() else {
+            return Ok(Reply::text(format!(
+                "Create your first invoice on {} to choose the wallet and networks; the bot reuses them.",
+                self.web_base_url,
+            )));
+        };
+
+        let request = CreateInvoiceRequest {
+            amount: args.amount,
+            seller: latest.seller,
+            networks: latest.networks,
+            external_id: args.external_id,
+            send_receipt: None,
+            buyer_email: None,
+            expires_at: None,
+            metadata: None,
+        };
+        let invoice = match payments::create_invoice(&self.db, &self.networks, Some(user.id), request).await {
+            Ok(invoice) => invoice,
+            Err(ResponseError::Bad(message)) => return Ok(Reply::text(message)),
+            Err(err) => return Err(format!("create invoice from telegram: {err:?}")),
+        };
+
+        let url = invoice.web_url(&self.web_base_url);
+        Ok(Reply::text(format!("Invoice for {} is ready:\n{url}", describe(&invoice)))
+            .with_row(vec![Button::Url("Open invoice".to_string(), url)]))
+    }
+
+    /// `page` starts at 0; buttons move between pages. It comes from callback data any client
+    /// can forge, so a page too far to have an offset is just past the last one.
+    pub async fn invoices(&self, user: &User, page: i64) -> Result<Reply, String> {
+        let Some(offset) = page.checked_mul(INVOICES_PAGE_SIZE) else {
+            return Ok(Reply::text(NO_MORE_INVOICES));
+        };
+        let mut invoices = self.db
+            .user_own_invoices(INVOICES_PAGE_SIZE + 1, offset, &user.id)
+            .await
+            .map_err(|err| err.to_string())?;
+        let has_next = invoices.len() as i64 > INVOICES_PAGE_SIZE;
+        invoices.truncate(INVOICES_PAGE_SIZE as usize);
+
+        if invoices.is_empty() {
+            return Ok(Reply::text(match page {
+                0 => "You have no invoices yet. Create one with /invoice.",
+                _ => NO_MORE_INVOICES,
+            }));
+        }
+
+        let first = offset + 1;
+        let mut text = format!("Invoices {first}-{}:", first + invoices.len() as i64 - 1);
+        for invoice in &invoices {
+            let status = match invoice.paid_at {
+                Some(paid_at) => format!("paid {}", paid_at.format("%Y-%m-%d %H:%M")),
+                None => invoice.status.as_str().to_string(),
+            };
+            text.push_str(&format!(
+                "\n\n{} - {status}\ncreated {}\n{}",
+                describe(invoice),
+                invoice.created_at.format("%Y-%m-%d %H:%M"),
+                invoice.web_url(&self.web_base_url),
+            ));
+        }
+
+        let mut row = vec![];
+        if page > 0 {
+            row.push(Button::Callback("< Newer".to_string(), format!("{INVOICES_CALLBACK}:{}", page - 1)));
+        }
+        if has_next {
+            row.push(Button::Callback("Older >".to_string(), format!("{INVOICES_CALLBACK}:{}", page + 1)));
+        }
+
+        Ok(Reply::text(text).with_row(row))
+    }
+
+    /// Totals of the invoices created since midnight UTC.
+    pub async fn stats(&self, user: &User) -> Result<Reply, String> {
+        let since = Utc::now().date_naive().and_hms_opt(0, 0, 0).expect("midnight exists");
+        let summary = self.db.invoice_summary(&user.id, since).await.map_err(|err| err.to_string())?;
+
+        Ok(Reply::text(format!(
+            "Today (UTC): {} invoices, {} paid\nInvoiced: {} {CURRENCY}\nReceived: {} {CURRENCY}",
+            summary.total_invoices,
+            summary.paid_invoices,
+            format_amount(&summary.total_amount),
+            format_amount(&summary.paid_amount),
+        )))
+    }
+
+    pub async fn mute(&self, user: &User, muted: bool) -> Result<Reply, String> {
+        self.db.update_user(&user.id, None, Some(!muted)).await.map_err(|err| err.to_string())?;
+
+        Ok(Reply::text(match muted {
+            true => "Payment notifications are muted. Send /unmute to turn them back on.",
+            false => "Payment notifications are on.",
+        }))
+    }
+}
+
+/// `25 USDT (order-42)`.
+fn describe(invoice: &Invoice) -> String {
+    match &invoice.external_id {
+        Some(external_id) => format!("{} {CURRENCY} ({external_id})", format_amount(&invoice.amount)),
+        None => format!("{} {CURRENCY}", format_amount(&invoice.amount)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    #[rstest]
+    #[case("/start 0f1e2d3c", Some(Command::Start(Some("0f1e2d3c"))))]
+    #[case("/start", Some(Command::Start(None)))]
+    #[case("/start@cryo_pay_bot  abc", Some(Command::Start(Some("abc"))))]
+    #[case("/unlink", Some(Command::Unlink))]
+    #[case("/stop", Some(Command::Unlink))]
+    #[case("/invoice 25 USDT order-42", Some(Command::Invoice(vec!["25", "USDT", "order-42"])))]
+    #[case("/invoices@cryo_pay_bot", Some(Command::Invoices))]
+    #[case("/help", Some(Command::Help))]
+    #[case("hello /start abc", None)]
+    #[case("", None)]
+    fn test_parse_command(#[case] text: &str, #[case] expected: Option<Command>) {
+        assert_eq!(Command::parse(text), expected);
+    }
+
+    #[rstest]
+    #[case(&["25"], Ok(("25", None)))]
+    #[case(&["25.50", "USDT", "order-42"], Ok(("25.50", Some("order-42"))))]
+    #[case(&["2,5", "order"], Ok(("2.5", Some("order"))))]
+    #[case(&["25", "USDC", "order-42"], Err("Only USDT invoices are supported."))]
+    #[case(&[], Err(INVOICE_USAGE))]
+    #[case(&["twenty"], Err(INVOICE_USAGE))]
+    #[case(&["25", "USDT", "order", "42"], Err(INVOICE_USAGE))]
+    fn test_parse_invoice_args(#[case] args: &[&str], #[case] expected: Result<(&str, Option<&str>), &str>) {
+        let expected = expected
+            .map(|(amount, external_id)| InvoiceArgs {
+                amount: BigDecimal::from_str(amount).unwrap(),
+                external_id: external_id.map(str::to_string),
+            })
+            .map_err(str::to_string);
+        assert_eq!(InvoiceArgs::parse(args), expected);
+    }
+
+    #[rstest]
+    #[case("invoices:2", Some(2))]
+    #[case("invoices:-1", None)]
+    #[case("other:1", None)]
+    fn test_parse_callback(#[case] data: &str, #[case] expected: Option<i64>) {
+        assert_eq!(parse_callback(data), expected);
+    }
+
+    async fn seller() -> (Seller, User) {
+        let db = DB::in_memory();
+        let user = db.get_or_create_user("firebase-1", None).await.unwrap();
+        let networks = Network::parse_networks(
+            r#"[{"name":"Optimism","id":10,"link":"https://rpc.example.com","addresses":{"erc20":"0xabc","contract":"0xdef"}}]"#
+        ).unwrap();
+        (Seller::new(db, "https://cryo.example.com".to_string(), networks), user)
+    }
+
+    #[tokio::test]
+    async fn test_invoice_reuses_latest_wallet_and_networks() {
+        let (seller, user) = seller().await;
+        let reply = seller.create_invoice(&user, &["25"]).await.unwrap();
+        assert!(reply.text.starts_with("Create your first invoice on https://cryo.example.com"));
+
+        seller.db.create_invoice(BigDecimal::from(10), "0xSeller", &[10], Some(user.id), None, false).await.unwrap();
+        let reply = seller.create_invoice(&user, &["25.50", "USDT", "order-42"]).await.unwrap();
+
+        let invoice = seller.db.user_own_invoices(1, 0, &user.id).await.unwrap().pop().unwrap();
+        assert_eq!((invoice.seller.as_str(), invoice.networks.as_slice()), ("0xseller", &[10][..]));
+        assert_eq!(invoice.external_id.as_deref(), Some("order-42"));
+        let url = format!("https://cryo.example.com/invoices/{}", invoice.id);
+        assert_eq!(reply, Reply::text(format!("Invoice for 25.5 USDT (order-42) is ready:\n{url}"))
+            .with_row(vec![Button::Url("Open invoice".to_string(), url)]));
+
+        let reply = seller.create_invoice(&user, &["0"]).await.unwrap();
+        assert_eq!(reply.text, "Amount must be positive");
+    }
+
+    #[tokio::test]
+    async fn test_invoices_are_paginated() {
+        let (seller, user) = seller().await;
+        assert!(seller.invoices(&user, 0).await.unwrap().text.starts_with("You have no invoices yet"));
+
+        for _ in 0..INVOICES_PAGE_SIZE + 2 {
+            seller.db.create_invoice(BigDecimal::from(10), "0x01", &[10], Some(user.id), None, false).await.unwrap();
+        }
+
+        let first = seller.invoices(&user, 0).await.unwrap();
+        assert!(first.text.starts_with("Invoices 1-5:"));
+        assert_eq!(first.text.matches(" - open").count(), 5);
+        assert_eq!(first.buttons, vec![vec![Button::Callback("Older >".to_string(), "invoices:1".to_string())]]);
+
+        let second = seller.invoices(&user, 1).await.unwrap();
+        assert!(second.text.starts_with("Invoices 6-7:"));
+        assert_eq!(second.buttons, vec![vec![Button::Callback("< Newer".to_string(), "invoices:0".to_string())]]);
+
+        assert_eq!(seller.invoices(&user, 2).await.unwrap().text, "No more invoices.");
+        assert_eq!(seller.invoices(&user, i64::MAX).await.unwrap().text, "No more invoices.");
+    }
+
+    #[tokio::test]
+    async fn test_stats_and_mute() {
+        let (seller, user) = seller().await;
+        seller.db.create_invoice(BigDecimal::from_str("12.50").unwrap(), "0x01", &[10], Some(user.id), None, false).await.unwrap();
+
+        let stats = seller.stats(&user).await.unwrap();
+        assert_eq!(stats.text, "Today (UTC): 1 invoices, 0 paid\nInvoiced: 12.5 USDT\nReceived: 0 USDT");
+
+        seller.mute(&user, true).await.unwrap();
+        assert!(!seller.db.get_user_by_id(&user.id).await.unwrap().telegram_notification);
+        seller.mute(&user, false).await.unwrap();
+        assert!(seller.db.get_user_by_id(&user.id).await.unwrap().telegram_notification);
+    }
+}
@@ -1,41 +1,64 @@
+use std::sync::Arc;
 use tgbot::api::Client;
 use tgbot::types::{ChatId, Integer};
-use bot::TelegramBot;
-use crate::api::state::DB;
-use crate::db::Invoice;
+use bot::Handler;
+use commands::Seller;
+use crate::api::state::{Redis, DB};
+use crate::config::{ApiConfig, BotConfig, TelegramConfig, TelegramWebhookConfig};
+use crate::shutdown::Shutdown;
 use crate::utils;
 
 mod client;
 mod bot;
+mod commands;
+mod webhook;
+
+pub use webhook::TelegramWebhook;
 
 #[derive(Clone)]
 pub struct TelegramClient {
     client: Client,
+    webhook: Option<TelegramWebhookConfig>,
 }
 
 impl TelegramClient {
-    pub async fn new() -> Result<Self, String> {
-        let client = client::get_client().await?;
+    pub async fn new(config: &TelegramConfig) -> Result<Self, String> {
+        let client = client::get_client(&config.token).await?;
 
-        Ok(Self { client })
+        Ok(Self { client, webhook: config.webhook.clone() })
     }
 
-    pub async fn send_invoice_paid(&self, chat_id: &str, invoice: &Invoice) -> Result<(), String> {
+    /// Sends MarkdownV2 `text`, optionally with a `(label, url)` button below it.
+    pub async fn send_markdown(&self, chat_id: &str, text: &str, button: Option<(String, String)>) -> Result<(), String> {
         let chat_id: ChatId = chat_id
             .parse::<Integer>()
             .map_err(|err| utils::make_err(Box::new(err), "parse chat id"))?
             .into();
 
-        client::send_invoice_paid(&self.client, chat_id, invoice).await
+        client::send_markdown(&self.client, chat_id, text, button).await
     }
 
     pub async fn get_bot_name(&self) -> Result<String, String> {
         client::get_bot_name(&self.client).await
     }
 
-    pub async fn run_as_bot(&self, db: DB) -> Result<(), String> {
-        let telegram_bot = TelegramBot::new()?;
+    /// Long polling; with a webhook configured the API receives the updates instead.
+    pub async fn run_as_bot(&self, config: &BotConfig, db: DB, redis: Redis, shutdown: Shutdown) -> Result<(), String> {
+        if self.webhook.is_some() {
+            return Err("TELEGRAM_WEBHOOK_URL is set, so bot updates are served by the API".to_string());
+        }
+        let seller = Seller::new(db.clone(), config.web_base_url.clone(), config.networks.clone());
+        let handler = Handler::new(self.client.clone(), db, redis, seller, config.limits.clone());
+
+        bot::run_long_poll(handler, shutdown).await
+    }
+
+    /// The update endpoint for the API's router, when a webhook is configured.
+    pub fn webhook(&self, config: &ApiConfig, db: DB, redis: Redis) -> Option<Arc<TelegramWebhook>> {
+        let webhook = self.webhook.clone()?;
+        let seller = Seller::new(db.clone(), config.web_base_url.clone(), config.networks.clone());
+        let handler = Handler::new(self.client.clone(), db, redis, seller, config.limits.clone());
 
-        telegram_bot.run(&self.client, db).await
+        Some(Arc::new(TelegramWebhook::new(handler, webhook.url, webhook.secret)))
     }
 }
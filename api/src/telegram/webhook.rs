@@ -0,0 +1,144 @@
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+use axum::Router;
+use axum::body::Bytes;
+use axum::extract::State;
+use axum::http::{HeaderMap, StatusCode};
+use axum::routing::post;
+use sha2::{Digest, Sha256};
+use tgbot::handler::UpdateHandler;
+use tgbot::types::{SetWebhook, Update};
+use tracing::{info, warn};
+use crate::shutdown::Shutdown;
+use crate::telegram::bot::{Handler, MENU};
+use crate::telegram::client::set_commands;
+use crate::utils;
+
+/// Where the API receives updates; `TELEGRAM_WEBHOOK_URL` must point here.
+pub const TELEGRAM_WEBHOOK_PATH: &str = "/telegram/webhook";
+const SECRET_HEADER: &str = "x-telegram-bot-api-secret-token";
+const REGISTER_RETRY: Duration = Duration::from_secs(30);
+
+/// Bot updates pushed by Telegram to the API. Requests without the secret given to
+/// `setWebhook` are rejected, so the endpoint can live on the public router.
+pub struct TelegramWebhook<H = Handler> {
+    handler: H,
+    url: String,
+    secret: String,
+    registered: AtomicBool,
+}
+
+impl<H: UpdateHandler + Send + Sync + 'static> TelegramWebhook<H> {
+    pub(super) fn new(handler: H, url: String, secret: String) -> Self {
+        Self { handler, url, secret, registered: AtomicBool::new(false) }
+    }
+
+    /// Whether Telegram has accepted the webhook since startup; reported by `/health`.
+    pub fn is_registered(&self) -> bool {
+        self.registered.load(Ordering::Relaxed)
+    }
+
+    pub fn router(self: Arc<Self>) -> Router {
+        Router::new()
+            .route(TELEGRAM_WEBHOOK_PATH, post(receive::<H>))
+            .with_state(self)
+    }
+
+    /// Digests are compared instead of the raw values so the comparison time says nothing
+    /// about how much of a guessed secret was right.
+    fn is_authorized(&self, headers: &HeaderMap) -> bool {
+        headers.get(SECRET_HEADER)
+            .is_some_and(|token| Sha256::digest(token.as_bytes()) == Sha256::digest(self.secret.as_bytes()))
+    }
+}
+
+impl TelegramWebhook<Handler> {
+    /// Registers the URL and secret with Telegram, retrying until it succeeds or shutdown.
+    pub async fn register(self: Arc<Self>, shutdown: Shutdown) {
+        let client = &self.handler.client;
+        if let Err(err) = set_commands(client, MENU).await {
+            warn!("{err}");
+        }
+
+        loop {
+            let request = SetWebhook::new(&self.url).with_secret_token(&self.secret);
+            match client.execute(request).await {
+                Ok(_) => {
+                    self.registered.store(true, Ordering::Relaxed);
+                    info!("Telegram webhook registered at {}", self.url);
+                    return;
+                }
+                Err(err) => warn!("{}, retrying in {REGISTER_RETRY:?}", utils::make_err(Box::new(err), "set telegram webhook")),
+            }
+
+            if !shutdown.sleep(REGISTER_RETRY).await {
+                return;
+            }
+        }
+    }
+}
+
+/// Updates are handled before responding, so Telegram sends the next one for a chat only
+/// after the previous one is done. Malformed updates are acknowledged, as Telegram would
+/// otherwise redeliver them forever.
+async fn receive<H: UpdateHandler + Send + Sync + 'static>(
+    State(webhook): State<Arc<TelegramWebhook<H>>>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> StatusCode {
+    if !webhook.is_authorized(&headers) {
+        warn!("Rejected a telegram update without a valid secret token");
+        return StatusCode::UNAUTHORIZED;
+    }
+
+    match serde_json::from_slice::<Update>(&body) {
+        Ok(update) => webhook.handler.handle(update).await,
+        Err(err) => warn!("Ignored a malformed telegram update: {err}"),
+    }
+
+    StatusCode::OK
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+
+    #[derive(Default)]
+    struct Recorder(Mutex<Vec<i64>>);
+
+    impl UpdateHandler for Recorder {
+        async fn handle(&self, update: Update) {
+            self.0.lock().unwrap().push(update.id);
+        }
+    }
+
+    const UPDATE: &str = r#"{"update_id": 42, "message": {
+        "message_id": 1, "date": 0, "text": "/help",
+        "chat": {"id": 7, "type": "private", "first_name": "A"},
+        "from": {"id": 7, "is_bot": false, "first_name": "A"}
+    }}"#;
+
+    async fn post(webhook: &Arc<TelegramWebhook<Recorder>>, secret: Option<&str>, body: &str) -> StatusCode {
+        let mut headers = HeaderMap::new();
+        if let Some(secret) = secret {
+            headers.insert(SECRET_HEADER, secret.parse().unwrap());
+        }
+        receive(State(webhook.clone()), headers, Bytes::from(body.to_string())).await
+    }
+
+    #[tokio::test]
+    async fn test_updates_need_the_secret_token() {
+        let webhook = Arc::new(TelegramWebhook::new(Recorder::default(), "https://example.com".to_string(), "s3cret".to_string()));
+
+        assert_eq!(post(&webhook, None, UPDATE).await, StatusCode::UNAUTHORIZED);
+        assert_eq!(post(&webhook, Some("s3cre"), UPDATE).await, StatusCode::UNAUTHORIZED);
+        assert!(webhook.handler.0.lock().unwrap().is_empty());
+
+        assert_eq!(post(&webhook, Some("s3cret"), UPDATE).await, StatusCode::OK);
+        assert_eq!(post(&webhook, Some("s3cret"), "{\"not\": \"an update\"}").await, StatusCode::OK);
+        assert_eq!(*webhook.handler.0.lock().unwrap(), vec![42]);
+        assert!(!webhook.is_registered());
+    }
+}
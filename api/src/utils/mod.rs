@@ -1,8 +1,8 @@
-use std::env;
+pub mod ssrf;
+
 use hex::encode;
 use rand::{thread_rng, Rng};
 use rand::distributions::Alphanumeric;
-use serde_json::Value;
 use sha2::{Digest, Sha256};
 use url::Url;
 use uuid::Uuid;
@@ -11,14 +11,6 @@ pub fn make_err(err: Box<dyn std::error::Error>, process: &str) -> String {
     format!("Failed {}: {:?}", process, err)
 }
 
-pub fn get_env_var(key: &str) -> Result<String, String> {
-    env::var(key).map_err(|_| format!("{} must be set", key))
-}
-
-pub fn get_env_or(key: &str, default: String) -> Result<String, String> {
-    get_env_var(key).or(Ok(default))
-}
-
 pub fn is_false(val: &str) -> bool {
     let true_values = vec!["", "0", "false", "n", "no", "not"];
 
@@ -29,15 +21,12 @@ fn contains_value(list: &Vec<&str>, value: &str) -> bool {
     list.contains(&value)
 }
 
-pub fn web_base_url() -> Result<String, String> {
-    get_env_var("WEB_BASE_URL")
-}
-
-pub fn get_invoice_url(invoice_id: &Uuid) -> Result<String, String> {
-    Ok(format!("{}/invoices/{}", web_base_url()?, invoice_id.to_string()))
+pub fn get_invoice_url(web_base_url: &str, invoice_id: &Uuid) -> String {
+    format!("{}/invoices/{}", web_base_url, invoice_id)
 }
 
-pub async fn get_suggested_gas_fees(infura_token: &str, network_id: i64) -> Result<Value, String> {
+#[cfg(feature = "infura")]
+pub async fn get_suggested_gas_fees(infura_token: &str, network_id: i64) -> Result<serde_json::Value, String> {
     let api_url = format!(
         "https://gas.api.infura.io/v3/{}/networks/{}/suggestedGasFees",
         infura_token, network_id
@@ -70,8 +59,7 @@ impl ApiKey {
         Self { value }
     }
 
-    pub fn hash_value(api_key: &str) -> String {
-        let app_secret = env::var("APP_SECRET").expect("APP_SECRET must be set");
+    pub fn hash_value(api_key: &str, app_secret: &str) -> String {
         let mut hasher = Sha256::new();
         hasher.update(app_secret);
         hasher.update(api_key);
@@ -80,8 +68,8 @@ impl ApiKey {
         encode(result)
     }
 
-    pub fn hashed_value(&self) -> String {
-        ApiKey::hash_value(&self.value)
+    pub fn hashed_value(&self, app_secret: &str) -> String {
+        ApiKey::hash_value(&self.value, app_secret)
     }
 }
 
@@ -115,37 +103,24 @@ where
     Err(last_err)
 }
 
-pub fn get_bind_address() -> Result<String, String> {
-    let host = get_env_var("HOST")?;
-    let port = get_env_var("PORT")?;
-    Ok(format!("{}:{}", host, port))
-}
-
 pub fn combine_paths(paths: &[&str]) -> String {
     paths.concat()
 }
 
-pub fn get_self_url() -> Result<String, String> {
-    get_bind_address().map(|addr| format!("http://{}", addr))
-}
-
-#[derive(Debug, PartialEq)]
+#[derive(Clone, Debug, PartialEq)]
 enum ApiGlobalUrlType {
     Full,
     Path
 }
 
+#[derive(Clone)]
 pub struct ApiGlobalUrl {
     url_type: ApiGlobalUrlType,
     value: String,
 }
 
 impl ApiGlobalUrl {
-    pub fn get() -> Result<Self, String> {
-        Self::from_str(get_env_var("API_GLOBAL_URL")?)
-    }
-
-    fn from_str(env_value: String) -> Result<Self, String> {
+    pub fn from_str(env_value: String) -> Result<Self, String> {
         Ok(Self {
             url_type: match Url::parse(&env_value) {
                 Err(_) => match env_value.starts_with("/") {
@@ -158,11 +133,11 @@ impl ApiGlobalUrl {
         })
     }
 
-    pub fn url(&self) -> Result<String, String> {
-        Ok(match self.url_type {
+    pub fn url(&self, web_base_url: &str) -> String {
+        match self.url_type {
             ApiGlobalUrlType::Full => self.value.clone(),
-            ApiGlobalUrlType::Path => combine_paths(&[&web_base_url()?, &self.value.clone()])
-        })
+            ApiGlobalUrlType::Path => combine_paths(&[web_base_url, &self.value.clone()])
+        }
     }
 }
 
@@ -0,0 +1,301 @@
+//! Keeps outbound webhook requests away from internal networks.
+//!
+//! Checking the URL once is not enough: a public host name can later resolve to a private
+//! address (DNS rebinding) and a public endpoint can redirect to one. So the client built here
+//! resolves every host through [`GuardedResolver`], which drops internal addresses right before
+//! connecting, and re-checks every redirect target.
+
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::sync::Arc;
+use std::time::Duration;
+use reqwest::dns::{Addrs, Name, Resolve, Resolving};
+use reqwest::redirect::{Attempt, Policy};
+use reqwest::Client;
+use url::{Host, Url};
+use crate::utils;
+
+const MAX_REDIRECTS: usize = 3;
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Docker/compose service names of this deployment.
+const BLOCKED_HOSTS: &[&str] = &[
+    "localhost", "postgres", "redis", "api", "web", "nginx", "traefik",
+];
+
+pub fn is_internal_ip(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(v4) => is_internal_ipv4(v4),
+        IpAddr::V6(v6) => is_internal_ipv6(v6),
+    }
+}
+
+fn is_internal_ipv4(ip: Ipv4Addr) -> bool {
+    let [a, b, ..] = ip.octets();
+    ip.is_loopback()
+        || ip.is_private()
+        || ip.is_link_local()
+        || ip.is_unspecified()
+        || ip.is_broadcast()
+        || ip.is_multicast()
+        || ip.is_documentation()
+        || a == 0
+        // CGNAT, 100.64.0.0/10
+        || (a == 100 && (b & 0xc0) == 64)
+        // IETF protocol assignments, 192.0.0.0/24
+        || (a == 192 && b == 0 && ip.octets()[2] == 0)
+        // Benchmarking, 198.18.0.0/15
+        || (a == 198 && (b & 0xfe) == 18)
+        // Reserved, 240.0.0.0/4
+        || a >= 240
+}
+
+fn is_internal_ipv6(ip: Ipv6Addr) -> bool {
+    let segments = ip.segments();
+    // IPv4-mapped (::ffff:a.b.c.d), IPv4-compatible (::a.b.c.d) and NAT64 (64:ff9b::a.b.c.d)
+    // reach the embedded IPv4 address.
+    let embedded = match segments {
+        [0, 0, 0, 0, 0, 0xffff, _, _] | [0, 0, 0, 0, 0, 0, _, _] | [0x64, 0xff9b, 0, 0, 0, 0, _, _] => {
+            Some(Ipv4Addr::new((segments[6] >> 8) as u8, segments[6] as u8, (segments[7] >> 8) as u8, segments[7] as u8))
+        }
+        // 6to4, 2002:a.b.c.d::/48
+        [0x2002, high, low, ..] => Some(Ipv4Addr::new((high >> 8) as u8, high as u8, (low >> 8) as u8, low as u8)),
+        _ => None,
+    };
+
+    ip.is_loopback()
+        || ip.is_unspecified()
+        || ip.is_multicast()
+        || embedded.is_some_and(is_internal_ipv4)
+        // Unique local, fc00::/7
+        || (segments[0] & 0xfe00) == 0xfc00
+        // Link-local fe80::/10 and the deprecated site-local fec0::/10
+        || (segments[0] & 0xffc0) == 0xfe80
+        || (segments[0] & 0xffc0) == 0xfec0
+        // Documentation, 2001:db8::/32
+        || (segments[0] == 0x2001 && segments[1] == 0x0db8)
+}
+
+/// Resolves through the system resolver.
+struct SystemResolver;
+
+impl Resolve for SystemResolver {
+    fn resolve(&self, name: Name) -> Resolving {
+        let host = name.as_str().to_string();
+        Box::pin(async move {
+            let addrs = tokio::net::lookup_host((host.as_str(), 0)).await?.collect::<Vec<_>>();
+            Ok(Box::new(addrs.into_iter()) as Addrs)
+        })
+    }
+}
+
+/// Wraps a resolver and keeps only public addresses; fails when none are left.
+struct GuardedResolver {
+    inner: Arc<dyn Resolve>,
+    allow_loopback: bool,
+}
+
+impl Resolve for GuardedResolver {
+    fn resolve(&self, name: Name) -> Resolving {
+        let host = name.as_str().to_string();
+        let resolving = self.inner.resolve(name);
+        let allow_loopback = self.allow_loopback;
+
+        Box::pin(async move {
+            let addrs = resolving.await?
+                .filter(|addr| is_allowed(addr.ip(), allow_loopback))
+                .collect::<Vec<SocketAddr>>();
+
+            if addrs.is_empty() {
+                return Err(format!("{host} resolves only to internal addresses").into());
+            }
+
+            Ok(Box::new(addrs.into_iter()) as Addrs)
+        })
+    }
+}
+
+fn is_allowed(ip: IpAddr, allow_loopback: bool) -> bool {
+    !is_internal_ip(ip) || (allow_loopback && ip.is_loopback())
+}
+
+/// Validates webhook URLs and builds HTTP clients that stay on public addresses.
+#[derive(Clone)]
+pub struct OutboundGuard {
+    resolver: Arc<dyn Resolve>,
+    /// Lets tests talk to local endpoint stand-ins; everything else internal stays blocked.
+    allow_loopback: bool,
+}
+
+impl OutboundGuard {
+    pub fn system() -> Self {
+        Self { resolver: Arc::new(SystemResolver), allow_loopback: false }
+    }
+
+    #[cfg(test)]
+    pub fn with_resolver(resolver: Arc<dyn Resolve>, allow_loopback: bool) -> Self {
+        Self { resolver, allow_loopback }
+    }
+
+    /// Scheme, host and literal IP checks that need no DNS.
+    fn check_target(&self, url: &Url) -> Result<(), String> {
+        match url.scheme() {
+            "http" | "https" => {}
+            s => return Err(format!("Unsupported scheme: {s}")),
+        }
+
+        match url.host().ok_or("URL must have a host")? {
+            Host::Ipv4(ip) if !is_allowed(ip.into(), self.allow_loopback) => Err(format!("Internal IP not allowed: {ip}")),
+            Host::Ipv6(ip) if !is_allowed(ip.into(), self.allow_loopback) => Err(format!("Internal IP not allowed: {ip}")),
+            Host::Domain(domain) => {
+                let domain = domain.trim_end_matches('.').to_lowercase();
+                match BLOCKED_HOSTS.contains(&domain.as_str()) || domain.ends_with(".localhost") {
+                    true => Err(format!("Blocked host: {domain}")),
+                    false => Ok(()),
+                }
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Rejects the URL when its host is blocked or resolves to any internal address.
+    pub async fn check(&self, url: &Url) -> Result<(), String> {
+        self.check_target(url)?;
+
+        let Some(Host::Domain(domain)) = url.host() else {
+            return Ok(());
+        };
+        let name: Name = domain.parse()
+            .map_err(|err| utils::make_err(Box::new(err), "parse host name"))?;
+        let addrs = self.resolver.resolve(name)
+            .await
+            .map_err(|err| format!("Failed to resolve {domain}: {err}"))?
+            .collect::<Vec<_>>();
+
+        if addrs.is_empty() {
+            return Err(format!("Failed to resolve {domain}"));
+        }
+        match addrs.iter().find(|addr| !is_allowed(addr.ip(), self.allow_loopback)) {
+            Some(addr) => Err(format!("{domain} resolves to an internal address: {}", addr.ip())),
+            None => Ok(()),
+        }
+    }
+
+    /// Client for webhook requests: resolves through the guard and follows at most
+    /// [`MAX_REDIRECTS`] redirects, none of them to an internal target.
+    pub fn client(&self) -> Result<Client, String> {
+        let guard = self.clone();
+        let redirect = Policy::custom(move |attempt: Attempt| {
+            if attempt.previous().len() >= MAX_REDIRECTS {
+                return attempt.error(format!("more than {MAX_REDIRECTS} redirects"));
+            }
+            match guard.check_target(attempt.url()) {
+                Ok(()) => attempt.follow(),
+                Err(err) => attempt.error(format!("redirect refused: {err}")),
+            }
+        });
+
+        Client::builder()
+            .dns_resolver(Arc::new(GuardedResolver { inner: self.resolver.clone(), allow_loopback: self.allow_loopback }))
+            .redirect(redirect)
+            .timeout(REQUEST_TIMEOUT)
+            .build()
+            .map_err(|err| utils::make_err(Box::new(err), "build webhook client"))
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use std::collections::HashMap;
+    use rstest::rstest;
+
+    /// Answers from a fixed table instead of DNS.
+    pub struct StubResolver(pub HashMap<&'static str, Vec<IpAddr>>);
+
+    impl Resolve for StubResolver {
+        fn resolve(&self, name: Name) -> Resolving {
+            let addrs = self.0.get(name.as_str()).cloned().unwrap_or_default();
+            Box::pin(async move {
+                Ok(Box::new(addrs.into_iter().map(|ip| SocketAddr::new(ip, 0))) as Addrs)
+            })
+        }
+    }
+
+    pub fn stub_guard(entries: &[(&'static str, &str)], allow_loopback: bool) -> OutboundGuard {
+        let mut table: HashMap<&'static str, Vec<IpAddr>> = HashMap::new();
+        for (host, ip) in entries {
+            table.entry(*host).or_default().push(ip.parse().unwrap());
+        }
+        OutboundGuard::with_resolver(Arc::new(StubResolver(table)), allow_loopback)
+    }
+
+    #[rstest]
+    #[case("127.0.0.1", true)]
+    #[case("10.1.2.3", true)]
+    #[case("172.16.0.1", true)]
+    #[case("192.168.1.1", true)]
+    #[case("169.254.169.254", true)]
+    #[case("100.64.0.1", true)]
+    #[case("100.127.255.255", true)]
+    #[case("0.0.0.0", true)]
+    #[case("198.18.0.1", true)]
+    #[case("100.128.0.1", false)]
+    #[case("93.184.216.34", false)]
+    #[case("::1", true)]
+    #[case("fd00::1", true)]
+    #[case("fe80::1", true)]
+    #[case("::ffff:127.0.0.1", true)]
+    #[case("::ffff:10.0.0.1", true)]
+    #[case("64:ff9b::a9fe:a9fe", true)]
+    #[case("2002:0a00:0001::", true)]
+    #[case("::ffff:93.184.216.34", false)]
+    #[case("2606:4700:4700::1111", false)]
+    fn test_is_internal_ip(#[case] ip: &str, #[case] internal: bool) {
+        assert_eq!(is_internal_ip(ip.parse().unwrap()), internal);
+    }
+
+    #[tokio::test]
+    async fn test_check_allows_public() {
+        let guard = stub_guard(&[("hooks.example.com", "93.184.216.34")], false);
+
+        for url in ["https://hooks.example.com/cryo", "http://93.184.216.34/hook"] {
+            guard.check(&Url::parse(url).unwrap()).await.unwrap();
+        }
+    }
+
+    #[tokio::test]
+    async fn test_check_rejects() {
+        let guard = stub_guard(
+            &[
+                ("rebind.example.com", "10.0.0.7"),
+                ("mixed.example.com", "93.184.216.34"),
+                ("mixed.example.com", "100.64.1.1"),
+            ],
+            false,
+        );
+        let cases = [
+            ("ftp://hooks.example.com/", "Unsupported scheme"),
+            ("http://localhost:8080/", "Blocked host"),
+            ("http://app.localhost/", "Blocked host"),
+            ("http://redis/", "Blocked host"),
+            ("http://10.0.0.1/", "Internal IP"),
+            ("http://[::ffff:169.254.169.254]/", "Internal IP"),
+            ("http://[fd12::1]/", "Internal IP"),
+            ("http://rebind.example.com/", "internal address: 10.0.0.7"),
+            ("http://mixed.example.com/", "internal address: 100.64.1.1"),
+            ("http://unknown.example.com/", "Failed to resolve"),
+        ];
+
+        for (url, message) in cases {
+            let err = guard.check(&Url::parse(url).unwrap()).await.unwrap_err();
+            assert!(err.contains(message), "{url}: {err}");
+        }
+    }
+
+    #[tokio::test]
+    async fn test_client_refuses_hosts_resolving_internally() {
+        let guard = stub_guard(&[("rebind.example.com", "127.0.0.1")], false);
+        let err = guard.client().unwrap().get("http://rebind.example.com:1/").send().await.unwrap_err();
+        assert!(format!("{err:?}").contains("resolves only to internal addresses"), "{err:?}");
+    }
+}
@@ -107,6 +107,7 @@ function Analytics() {
                                     <th className="text-end">Paid</th>
                                     <th className="text-end">Amount (USDT)</th>
                                     <th className="text-end">Paid (USDT)</th>
+                                    <th className="text-end">Expired / void</th>
                                 </tr>
                             </thead>
                             <tbody>
@@ -117,6 +118,7 @@ function Analytics() {
                                         <td className="text-end">{row.paid_invoices}</td>
                                         <td className="text-end">{Number(row.total_amount).toFixed(2)}</td>
                                         <td className="text-end">{Number(row.paid_amount).toFixed(2)}</td>
+                                        <td className="text-end">{row.expired_invoices} / {row.void_invoices}</td>
                                     </tr>
                                 ))}
                             </tbody>
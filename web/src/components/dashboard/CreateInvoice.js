@@ -14,6 +14,14 @@ const ExternalIdInfo = ({id, children, title}) => (
 
 const externalIdInfoText = "This ID will be visible to the payer. You can use it as a name or for any other purpose.";
 
+// Hours until an unpaid invoice expires; 0 never expires.
+const EXPIRY_OPTIONS = [
+    {hours: 0, label: 'Never'},
+    {hours: 1, label: 'In 1 hour'},
+    {hours: 24, label: 'In 24 hours'},
+    {hours: 24 * 7, label: 'In 7 days'},
+];
+
 const CreateInvoice = (
     {
         createBtn,
@@ -29,7 +37,7 @@ const CreateInvoice = (
 ) => {
     const navigate = useNavigate();
     const [showModal, setShowModal] = useState(false);
-    const [newInvoice, setNewInvoice] = useState({amount: '', seller: '', networks: [], external_id: ''});
+    const [newInvoice, setNewInvoice] = useState({amount: '', seller: '', networks: [], external_id: '', expires_in: 0});
     const [creating, setCreating] = useState(false);
     const [networks, setNetworks] = useState([]);
     const [error, setError] = useState(null);
@@ -88,6 +96,10 @@ const CreateInvoice = (
         if (external_id) {
             data.external_id = newInvoice.external_id;
         }
+        if (newInvoice.expires_in > 0) {
+            // The API takes UTC without an offset.
+            data.expires_at = new Date(Date.now() + newInvoice.expires_in * 3600 * 1000).toISOString().slice(0, 19);
+        }
 
         setCreating(true);
         axios
@@ -95,7 +107,7 @@ const CreateInvoice = (
             .then((response) => {
                 setCreating(false);
                 setShowModal(false);
-                setNewInvoice({amount: '', seller: '', networks: [], external_id: ''});
+                setNewInvoice({amount: '', seller: '', networks: [], external_id: '', expires_in: 0});
                 setShowExternalId(false);
                 const newInvoiceId = response.data.id;
                 navigate(`/invoices/${newInvoiceId}`);
@@ -226,6 +238,17 @@ const CreateInvoice = (
                                     ))}
                             </div>
                         </Form.Group>
+                        <Form.Group controlId="formExpiresIn" className="mt-3">
+                            <Form.Label>Expires</Form.Label>
+                            <Form.Select
+                                value={newInvoice.expires_in}
+                                onChange={(e) => setNewInvoice({...newInvoice, expires_in: parseInt(e.target.value)})}
+                            >
+                                {EXPIRY_OPTIONS.map(({hours, label}) => (
+                                    <option key={hours} value={hours}>{label}</option>
+                                ))}
+                            </Form.Select>
+                        </Form.Group>
                         {showExternalBlock && (
                             <Form.Group controlId="formExternalId" className="mt-3">
                                 <Button
@@ -1,5 +1,5 @@
 import React, {useEffect, useState} from 'react';
-import {Alert, Button, Spinner, Table, Form} from 'react-bootstrap';
+import {Alert, Badge, Button, Spinner, Table, Form} from 'react-bootstrap';
 import axios from 'axios';
 import {useSearchParams} from 'react-router-dom';
 import {apiUrl, getNetwork} from "../../utils";
@@ -9,6 +9,9 @@ import NetworkIcon from "../NetworkIcon";
 
 const PAGE_SIZE = 10;
 
+// Unpaid invoices show their status instead of a payment date.
+const STATUS_VARIANTS = {open: 'primary', confirming: 'info', expired: 'secondary', void: 'dark'};
+
 const InvoiceList = ({isLoggedIn}) => {
     const [invoices, setInvoices] = useState([]);
     const [loading, setLoading] = useState(true);
@@ -119,7 +122,7 @@ const InvoiceList = ({isLoggedIn}) => {
                         <th>Networks</th>
                         <th>Amount</th>
                         <th>Created At</th>
-                        <th>Paid At</th>
+                        <th>Paid At / Status</th>
                     </tr>
                     </thead>
                     <tbody>
@@ -160,7 +163,7 @@ const InvoiceList = ({isLoggedIn}) => {
                                 {invoice.paid_at ? (
                                     <LocalDate date={invoice.paid_at}/>
                                 ) : (
-                                    'Unpaid'
+                                    <Badge bg={STATUS_VARIANTS[invoice.status] || 'secondary'}>{invoice.status}</Badge>
                                 )}
                             </td>
                         </tr>
@@ -69,6 +69,9 @@ const CreateInvoiceContent = () => (
                         <strong>Recipient Details:</strong> Provide the necessary
                         information.
                     </li>
+                    <li>
+                        <strong>Expires:</strong> Optionally, when the invoice stops accepting payments.
+                    </li>
                 </ul>
             </li>
             <li>Click "Submit" to generate the invoice.</li>
@@ -77,6 +80,27 @@ const CreateInvoiceContent = () => (
             After creating an invoice, you can share it with the recipient. The
             invoice will be visible on your dashboard for easy tracking.
         </p>
+        <p>
+            Every invoice has a <code>status</code>: <code>open</code> until paid, <code>confirming</code> while
+            the buyer's transaction is being mined, then <code>paid</code>. With <code>expires_at</code> (UTC) on{" "}
+            <code>POST /payment/invoice</code>, an unpaid invoice becomes <code>expired</code> at that time and you
+            get <code>invoice.expired</code>; a transaction already sent from the payment page gets 30 more minutes.
+            Voiding an unpaid invoice (<code>DELETE /payment/invoice/&#123;id&#125;</code>) makes it{" "}
+            <code>void</code>. Expired and void invoices can no longer be paid: a transfer that still arrives is
+            reported as <code>payment.anomaly</code> and the invoice stays as it is.
+        </p>
+        <p>
+            While an invoice is <code>open</code>, <code>PATCH /payment/invoice/&#123;id&#125;</code> changes its{" "}
+            <code>amount</code>, <code>networks</code>, <code>external_id</code>, <code>expires_at</code> or{" "}
+            <code>metadata</code> (a JSON object of your own, up to 4 KB); send only the fields to change,{" "}
+            <code>null</code> clears the optional ones, and <code>invoice.updated</code> is sent. Open invoices
+            you own show an <em>Edit Invoice</em> button for the amount and external ID.
+        </p>
+        <p>
+            For the invoice's owner, <code>GET /payment/invoice/&#123;id&#125;?with_timeline=true</code> adds
+            its <code>timeline</code> (omitted for anyone else): its creation, every edit with the changed fields' <code>from</code> and{" "}
+            <code>to</code> values, and every status change, oldest first.
+        </p>
     </>
 )
 
@@ -100,7 +124,76 @@ const NotificationsContent = () => (
         </ul>
         <p>
             To enable notifications, link your Telegram account in the
-            "Settings" section.
+            "Settings" section: "Activate Bot" opens the bot with a one-time link that is valid for
+            10 minutes. Send <code>/unlink</code> to the bot, or press "Unlink all" in Settings, to stop them.
+        </p>
+        <p>
+            Notifications can also go to your team: "Add group" links a group the bot is added to, and
+            "Add channel" adds the bot to a channel as an admin. Groups and channels are also linked when
+            you add the bot to them yourself while your private chat is linked. Each chat can be limited to
+            some event types or to invoices above a minimum amount through{" "}
+            <code>PATCH /user/telegram/{"{id}"}</code>. In groups the bot only answers{" "}
+            <code>/start</code>, <code>/unlink</code> and <code>/help</code>.
+        </p>
+        <p>
+            Slack and Discord channels get notifications through their incoming webhook URLs: add one under
+            "Slack &amp; Discord" in Settings or with <code>POST /user/chat_webhook</code>{" "}
+            (<code>kind</code> <code>slack</code>, <code>discord</code> or <code>generic</code> for other
+            apps that accept <code>{"{\"text\": ...}"}</code>). "Test" posts a sample message to the channel.
+        </p>
+        <p>
+            The linked chat also works as a small dashboard: <code>/invoice 25 USDT order-42</code> creates an
+            invoice with the wallet and networks of your latest one and replies with its pay link,{" "}
+            <code>/invoices</code> lists recent invoices with their status, <code>/stats</code> shows today's
+            totals and <code>/mute</code> / <code>/unmute</code> pause notifications.
+        </p>
+        <p>
+            Notifications are sent in your language (<code>locale</code> on <code>PATCH /user</code>:
+            English, Spanish or Russian). You can replace the texts with your own per language via{" "}
+            <code>PUT /user/template/invoice_paid/&#123;locale&#125;</code>: an email subject, a plain-text and an
+            HTML body, and a Telegram MarkdownV2 message. Templates use{" "}
+            <code>{"{{amount}}"}</code>, <code>{"{{network}}"}</code>, <code>{"{{external_id}}"}</code>,{" "}
+            <code>{"{{buyer}}"}</code>, <code>{"{{explorer_link}}"}</code>, <code>{"{{invoice_url}}"}</code> and more
+            (see <code>GET /user/template</code>); <code>{"{{#external_id}}...{{/external_id}}"}</code> is kept only
+            when the value is set. Fields you leave out keep the built-in text. Partial payments
+            (<code>invoice_partially_paid</code>) and payment anomalies (<code>payment_anomaly</code>) have
+            their own templates, which also offer <code>{"{{paid_amount}}"}</code> and <code>{"{{reason}}"}</code>.
+            Three days before a subscription ends you get a <code>subscription_expiring</code> notice with{" "}
+            <code>{"{{plan}}"}</code>, <code>{"{{until}}"}</code> and a <code>{"{{renew_url}}"}</code> link.
+        </p>
+        <p>
+            Each channel (<code>email</code>, <code>telegram</code>, <code>chat</code>) can be set per event:{" "}
+            <code>invoice.paid</code>, <code>invoice.partially_paid</code>, <code>invoice.expired</code>,{" "}
+            <code>payment.anomaly</code> and <code>subscription.expiring</code>. Expired invoices are off by
+            default, everything else is on. <code>PUT /user/notification_preference/email/invoice.paid</code> with{" "}
+            <code>{'{"enabled": true, "min_amount": "100", "external_id_prefix": "shop-"}'}</code> emails only
+            paid invoices of at least 100 USDT whose <code>external_id</code> starts with <code>shop-</code>;{" "}
+            <code>DELETE</code> on the same path restores the default. The email and Telegram switches above
+            still turn a channel off for every event.
+        </p>
+        <p>
+            Busy shops can get a summary instead of a message per event. Set email or Telegram to{" "}
+            <code>hourly</code>, <code>daily</code> or <code>weekly</code> with{" "}
+            <code>PUT /user/notification_digest/telegram</code> and <code>{'{"frequency": "daily"}'}</code>: shortly
+            after midnight UTC you get the number of paid invoices, partial payments and anomalies, the total
+            paid on each network and links to the largest invoices by <code>external_id</code>. Weeks start on
+            Monday. <code>immediate</code> switches back; chat webhooks always get every event.
+        </p>
+        <p>
+            <code>GET /user/notifications</code> lists every email, Telegram and chat message sent to you, newest
+            first, with where it went and whether it arrived; filter with <code>?status=failed</code>,{" "}
+            <code>channel</code>, <code>event</code> or <code>invoice_id</code>. A failed message can be sent
+            again with <code>POST /user/notifications/&#123;id&#125;/resend</code>; it goes to the same address or
+            chat and shows the invoice as it is now. Digests cannot be resent.
+        </p>
+        <p>
+            Buyers can get a receipt too. Turn on <em>Buyer receipts</em> in the settings (<code>buyer_receipts</code>{" "}
+            on <code>PATCH /user</code>) for every new invoice, or pass <code>{'"send_receipt": true'}</code> when
+            creating one. The payment page then asks for the buyer's email, which you can also set with{" "}
+            <code>buyer_email</code> at creation. A buyer can set the address once; only you can change it
+            afterwards. Once paid, the buyer is emailed the amount, network, transaction
+            hash and your wallet address in your language; override the <code>buyer_receipt</code> template to add
+            your shop's details. Receipts show up in the notification log as <code>receipt</code>.
         </p>
     </>
 )
@@ -124,6 +217,10 @@ const MakingPaymentsContent = () => (
                 sent to seller upon completion.
             </li>
         </ol>
+        <p>
+            If the seller offers receipts, the invoice page has an optional email field; fill it in before paying
+            to get a receipt with the transaction details.
+        </p>
         <p>
             <strong>Note:</strong> Avoid paying the same invoice twice, as the
             system processes payments reliably. Double-check before initiating a
@@ -136,40 +233,83 @@ const WebhooksContent = () => (
     <>
         <h5>Webhook Notifications</h5>
         <p>
-            When an invoice is paid, Cryo Pay sends an HTTP <code>POST</code> to each
-            configured webhook URL. Webhooks are managed in the <em>Settings</em> section
+            Cryo Pay sends an HTTP <code>POST</code> to each configured webhook URL when one of the
+            events it subscribes to happens. Webhooks are managed in the <em>Settings</em> section
             (requires account login).
         </p>
 
+        <h6>Events</h6>
+        <ul>
+            <li><code>invoice.created</code>, <code>invoice.updated</code>, <code>invoice.paid</code>,{" "}
+                <code>invoice.expired</code>,{" "}
+                <code>invoice.deleted</code> — <code>data</code> is the invoice, as returned by{" "}
+                <code>GET /payment/invoice/&#123;id&#125;</code>; deleted invoices are kept with status{" "}
+                <code>void</code></li>
+            <li><code>invoice.partially_paid</code> — less than the invoice amount arrived; the invoice stays unpaid.{" "}
+                <code>data</code> has the <code>invoice</code>, <code>paid_amount</code> and <code>payer</code></li>
+            <li><code>payment.anomaly</code> — a transfer that could not be applied (already paid, expired, void,
+                wrong seller, overpaid); same <code>data</code> plus the <code>reason</code></li>
+            <li><code>subscription.activated</code> — <code>data</code> has the <code>target</code> and{" "}
+                <code>until</code></li>
+        </ul>
+        <p>
+            Pass <code>events</code> when creating a webhook to pick the types it receives; all types are sent
+            when omitted. Payload schemas (<code>WebhookEvent</code> and friends) are in the OpenAPI document.
+        </p>
+
         <h6>Payload</h6>
         <pre>{`{
-  "id": "b92d6367-6bf1-49b8-8180-d7fb79d7c75b",
-  "paid_at": "2025-02-14T23:05:38",
-  "status": "SUCCESS"
+  "id": "0d7c5a52-2f5e-4b0e-a0b4-8f1e9e5d6a31",
+  "type": "invoice.paid",
+  "created": 1739574338,
+  "api_version": "2026-10-18",
+  "data": {
+    "id": "b92d6367-6bf1-49b8-8180-d7fb79d7c75b",
+    "created_at": "2025-02-14T23:01:12",
+    "amount": "10.00",
+    "seller": "0x...",
+    "buyer": "0x...",
+    "paid_at": "2025-02-14T23:05:38",
+    "networks": [10, 42161],
+    "external_id": "order-42",
+    "paid_network": 10,
+    "tx_hash": "0x...",
+    "send_receipt": false,
+    "status": "paid",
+    "expires_at": null,
+    "metadata": {"sku": "A1"}
+  }
 }`}</pre>
-        <p><code>paid_at</code> is an ISO 8601 datetime string (may be <code>null</code> in edge cases).</p>
+        <p>
+            <code>id</code> identifies the event and stays the same when a delivery is retried, so it can be
+            used to drop duplicates. Datetimes in <code>data</code> are ISO 8601 strings; <code>created</code> is
+            unix seconds. Webhooks created before the event catalog have <code>api_version: "legacy"</code> and
+            keep receiving only paid invoices as <code>{`{"id", "paid_at", "status": "SUCCESS"}`}</code>.
+        </p>
 
         <h6>Request Headers</h6>
         <ul>
             <li><code>Content-Type: application/json</code></li>
             <li><code>X-Webhook-Timestamp: &lt;unix seconds&gt;</code> — always present</li>
-            <li><code>X-Signature-256: &lt;hex&gt;</code> — present when a secret is set</li>
+            <li><code>X-Signature-256: &lt;hex&gt;[,&lt;hex&gt;]</code> — present when a secret is set; two
+                comma-separated signatures (new secret first) while a rotated-out secret is in its grace period</li>
         </ul>
 
         <h6>Signature Verification</h6>
         <p>
             The signature is <code>{"HMAC-SHA256(secret, \"{timestamp}.{raw_json_body}\")"}</code>.
             The signed string is the timestamp, a literal dot, then the exact JSON body bytes.
-            Always use a constant-time comparison to prevent timing attacks.
+            Accept the request if any of the comma-separated signatures matches, and always use a
+            constant-time comparison to prevent timing attacks.
         </p>
 
         <strong>Python</strong>
         <pre>{`import hmac, hashlib
 
-def verify(secret: str, timestamp: str, body: bytes, signature: str) -> bool:
+def verify(secret: str, timestamp: str, body: bytes, signatures: str) -> bool:
     msg = f"{timestamp}.".encode() + body
     expected = hmac.new(secret.encode(), msg, hashlib.sha256).hexdigest()
-    return hmac.compare_digest(expected, signature)
+    return any(hmac.compare_digest(expected, s) for s in signatures.split(","))
 
 # In your request handler:
 timestamp = request.headers["X-Webhook-Timestamp"]
@@ -179,10 +319,11 @@ verify(WEBHOOK_SECRET, timestamp, request.body, signature)`}</pre>
         <strong>Node.js</strong>
         <pre>{`const crypto = require('crypto');
 
-function verify(secret, timestamp, body, signature) {
+function verify(secret, timestamp, body, signatures) {
   const msg = \`\${timestamp}.\${body}\`;
-  const expected = crypto.createHmac('sha256', secret).update(msg).digest('hex');
-  return crypto.timingSafeEqual(Buffer.from(expected), Buffer.from(signature));
+  const expected = Buffer.from(crypto.createHmac('sha256', secret).update(msg).digest('hex'));
+  return signatures.split(',').some((signature) =>
+    signature.length === expected.length && crypto.timingSafeEqual(expected, Buffer.from(signature)));
 }
 
 // body must be the raw request body string, not parsed JSON
@@ -190,12 +331,47 @@ const timestamp = req.headers['x-webhook-timestamp'];
 const signature = req.headers['x-signature-256'];
 verify(WEBHOOK_SECRET, timestamp, rawBody, signature);`}</pre>
 
+        <strong>Rust</strong>
+        <p>
+            The <code>cryo-webhook</code> crate (<code>api/cryo-webhook</code> in the repository) signs and verifies
+            deliveries, enforces a timestamp tolerance (5 minutes by default) and rejects replays. With the{" "}
+            <code>axum</code> or <code>actix</code> feature, the <code>VerifiedWebhook</code> extractor verifies
+            the request and parses its body; it answers 401 when the signature does not check out.
+        </p>
+        <pre>{`// axum: the Verifier lives in the router state
+let app = Router::new()
+    .route("/cryo", post(|VerifiedWebhook(event): VerifiedWebhook<serde_json::Value>| async move {
+        // event["type"], event["data"]
+    }))
+    .with_state(Verifier::new(&webhook_secret));
+
+// actix-web
+App::new()
+    .app_data(web::Data::new(Verifier::new(&webhook_secret)))
+    .route("/cryo", web::post().to(|VerifiedWebhook(event): VerifiedWebhook| async move { "ok" }));`}</pre>
+
         <p>
             <strong>Notes:</strong>{" "}
-            Max 2 webhooks per account. The URL must be publicly reachable (localhost and private IPs are blocked)
-            and return 2xx on a test <code>POST</code> sent at creation time.
-            Delivery is retried up to 2 times on failure.
+            Max 2 webhooks per account. The URL must be publicly reachable and return 2xx on a test <code>POST</code> sent at creation time.
+            Any non-2xx response or timeout is retried with exponential backoff (30s, 1m, 2m, … up to 4h between
+            attempts) for about 12 hours. Every attempt is logged with its status code, latency and the start of
+            the response body; see <code>GET /user/webhook/&#123;id&#125;/deliveries</code> and redeliver with{" "}
+            <code>POST /user/webhook/&#123;id&#125;/deliveries/&#123;delivery_id&#125;/redeliver</code>.
+            Host names are resolved before every delivery; hosts resolving to localhost, private, link-local,
+            CGNAT or IPv6 unique-local addresses are refused, and at most 3 redirects to public hosts are followed.
             Webhooks created without a secret skip signature generation (legacy mode).
+            The secret is shown in full only when the webhook is created and when it is rotated; store it then,
+            later responses show only its last 4 characters.
+        </p>
+        <p>
+            <strong>Managing webhooks:</strong>{" "}
+            <code>PATCH /user/webhook/&#123;id&#125;</code> changes <code>url</code>, <code>enabled</code> or{" "}
+            <code>events</code>; a disabled webhook receives nothing and its queued deliveries resume when it is
+            enabled again. <code>POST /user/webhook/&#123;id&#125;/rotate-secret</code> with{" "}
+            <code>{`{"grace_period_secs": 86400}`}</code> returns the new secret; until the grace period ends
+            every delivery carries signatures for both secrets, so you can switch without dropping events.{" "}
+            <code>POST /user/webhook/&#123;id&#125;/test</code> sends a signed <code>webhook.test</code> event
+            right away and returns what your endpoint answered.
         </p>
     </>
 )
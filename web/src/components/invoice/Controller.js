@@ -11,7 +11,7 @@ function Controller({ invoice, own, erc20Abi, contractAbi, networks, waitPayment
     const navigate = useNavigate();
     const [processingPayment, setPaymentProcessing] = useState(false);
     const [paymentSuccessful, setPaymentSuccessful] = useState(false);
-    const [processingDelete, setDeleteProcessing] = useState(false);
+    const [processingVoid, setVoidProcessing] = useState(false);
     const [error, setError] = useState(null);
     const isMetaMask = !!(window.ethereum || {}).isMetaMask;
 
@@ -71,6 +71,11 @@ function Controller({ invoice, own, erc20Abi, contractAbi, networks, waitPayment
                         maxFeePerGas: gasFees.maxFeePerGas,
                         maxPriorityFeePerGas: gasFees.maxPriorityFeePerGas,
                     }),
+                })
+                // Keeps the invoice from expiring while the transaction is mined.
+                .on('transactionHash', (txHash) => {
+                    axios.post(apiUrl(`/payment/invoice/${invoice.id}/confirming`), {tx_hash: txHash, network: networkId})
+                        .catch((err) => console.error('Failed to mark the invoice as confirming', err));
                 });
         };
 
@@ -108,18 +113,18 @@ function Controller({ invoice, own, erc20Abi, contractAbi, networks, waitPayment
         }
     };
 
-    const handleDelete = async () => {
+    const handleVoid = async () => {
         try {
-            setDeleteProcessing(true);
+            setVoidProcessing(true);
             await axios.delete(
                 apiUrl(`/payment/invoice/${invoice.id}`),
                 {withCredentials: true}
             );
             navigate('/dashboard');
         } catch (err) {
-            setError('Failed to delete the invoice, please try again.');
+            setError('Failed to void the invoice, please try again.');
         } finally {
-            setDeleteProcessing(false);
+            setVoidProcessing(false);
         }
     };
 
@@ -150,10 +155,10 @@ function Controller({ invoice, own, erc20Abi, contractAbi, networks, waitPayment
                     <Col className="text-end">
                         <Button
                             variant="danger"
-                            onClick={handleDelete}
-                            disabled={processingDelete}
+                            onClick={handleVoid}
+                            disabled={processingVoid}
                         >
-                            {processingDelete ? 'Deleting...' : 'Delete Invoice'}
+                            {processingVoid ? 'Voiding...' : 'Void Invoice'}
                         </Button>
                     </Col>
                 )}
@@ -0,0 +1,83 @@
+import {Alert, Button, Form, Modal} from "react-bootstrap";
+import React, {useState} from "react";
+import axios from "axios";
+import {apiUrl} from "../../utils";
+
+function EditInvoice({invoice, onSaved}) {
+    const [show, setShow] = useState(false);
+    const [amount, setAmount] = useState(invoice.amount);
+    const [externalId, setExternalId] = useState(invoice.external_id || '');
+    const [saving, setSaving] = useState(false);
+    const [error, setError] = useState(null);
+
+    const handleShow = () => {
+        setAmount(invoice.amount);
+        setExternalId(invoice.external_id || '');
+        setError(null);
+        setShow(true);
+    };
+
+    const handleSave = async (e) => {
+        e.preventDefault();
+        setError(null);
+        setSaving(true);
+        try {
+            await axios.patch(
+                apiUrl(`/payment/invoice/${invoice.id}`),
+                {amount: String(amount), external_id: externalId.trim() || null},
+                {withCredentials: true}
+            );
+            setShow(false);
+            onSaved();
+        } catch (err) {
+            setError(err.response?.data?.message || 'Failed to save the invoice, please try again.');
+        } finally {
+            setSaving(false);
+        }
+    };
+
+    return (
+        <>
+            <Button variant="outline-secondary" className="mb-4" onClick={handleShow}>
+                Edit Invoice
+            </Button>
+            <Modal show={show} onHide={() => setShow(false)}>
+                <Form onSubmit={handleSave}>
+                    <Modal.Header closeButton>
+                        <Modal.Title>Edit Invoice</Modal.Title>
+                    </Modal.Header>
+                    <Modal.Body>
+                        <Form.Group className="mb-3" controlId="edit-amount">
+                            <Form.Label>Amount</Form.Label>
+                            <Form.Control
+                                type="number"
+                                step="0.01"
+                                min="0.01"
+                                value={amount}
+                                onChange={(e) => setAmount(e.target.value)}
+                                required
+                            />
+                        </Form.Group>
+                        <Form.Group controlId="edit-external-id">
+                            <Form.Label>External ID</Form.Label>
+                            <Form.Control
+                                type="text"
+                                value={externalId}
+                                onChange={(e) => setExternalId(e.target.value)}
+                            />
+                        </Form.Group>
+                        {error && <Alert variant="danger" className="mt-3">{error}</Alert>}
+                    </Modal.Body>
+                    <Modal.Footer>
+                        <Button variant="secondary" onClick={() => setShow(false)}>Cancel</Button>
+                        <Button type="submit" variant="primary" disabled={saving}>
+                            {saving ? 'Saving...' : 'Save'}
+                        </Button>
+                    </Modal.Footer>
+                </Form>
+            </Modal>
+        </>
+    );
+}
+
+export default EditInvoice;
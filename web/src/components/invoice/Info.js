@@ -56,6 +56,12 @@ function Info({invoice}) {
                     <LocalDate date={invoice.paid_at}/>
                 </ListGroup.Item>
             )}
+            {!invoice.paid_at && invoice.expires_at && (
+                <ListGroup.Item>
+                    <strong>{invoice.status === 'expired' ? 'Expired At:' : 'Expires At:'}</strong>{' '}
+                    <LocalDate date={invoice.expires_at}/>
+                </ListGroup.Item>
+            )}
             <ListGroup.Item>
                 <Button variant="outline-secondary" size="sm" onClick={handleCopy}>
                     {copied ? 'Link copied!' : 'Copy payment link'}
@@ -4,6 +4,9 @@ import {Alert, Container, Spinner, Modal, Button} from 'react-bootstrap';
 import {apiUrl, getBlockchainInfo, getInvoice} from "../../utils";
 import Info from "./Info";
 import Controller from "./Controller";
+import ReceiptEmail from "./ReceiptEmail";
+import EditInvoice from "./EditInvoice";
+import Timeline from "./Timeline";
 
 const updateIfNotPaidAfterSeconds = 10;
 
@@ -13,6 +16,8 @@ function Invoice() {
     const {invoice_id} = useParams();
     const [invoice, setInvoice] = useState(null);
     const [own, setOwn] = useState(false);
+    const [timeline, setTimeline] = useState([]);
+    const [reloads, setReloads] = useState(0);
     const [loading, setLoading] = useState(true);
     const [error, setError] = useState(null);
     const [erc20Abi, setErc20Abi] = useState(null);
@@ -45,6 +50,7 @@ function Invoice() {
             .then((response) => {
                 setInvoice(response.data.invoice);
                 setOwn(response.data.own);
+                setTimeline(response.data.timeline);
             })
             .catch((err) => {
                 if (err.response && err.response.status === 404) {
@@ -55,10 +61,10 @@ function Invoice() {
                 }
             })
             .finally(() => setLoading(false));
-    }, [invoice_id, navigate]);
+    }, [invoice_id, navigate, reloads]);
 
     useEffect(() => {
-        if (!!invoice && !invoice.paid_at) {
+        if (!!invoice && (invoice.status === 'open' || invoice.status === 'confirming')) {
             const interval = setInterval(() => {
                 getInvoice(invoice_id)
                     .then((response) => {
@@ -99,18 +105,31 @@ function Invoice() {
 
             <Info invoice={invoice}/>
 
+            {own && invoice.status === 'open' && (
+                <EditInvoice invoice={invoice} onSaved={() => setReloads(reloads + 1)}/>
+            )}
+
             {invoice.paid_at ? (
                 <Alert variant="success" className="text-center">
                     This invoice has already been paid.
                 </Alert>
-            ) : <Controller
-                invoice={invoice}
-                own={own}
-                erc20Abi={erc20Abi}
-                contractAbi={contractAbi}
-                networks={networks}
-                waitPaymentSuccessful={waitPaymentSuccessful}
-            />}
+            ) : invoice.status === 'expired' || invoice.status === 'void' ? (
+                <Alert variant="secondary" className="text-center">
+                    {invoice.status === 'expired'
+                        ? 'This invoice has expired and can no longer be paid.'
+                        : 'This invoice was cancelled by the seller and can no longer be paid.'}
+                </Alert>
+            ) : <>
+                {invoice.send_receipt && <ReceiptEmail invoice={invoice}/>}
+                <Controller
+                    invoice={invoice}
+                    own={own}
+                    erc20Abi={erc20Abi}
+                    contractAbi={contractAbi}
+                    networks={networks}
+                    waitPaymentSuccessful={waitPaymentSuccessful}
+                />
+            </>}
 
             {error && (
                 <Alert variant="danger" className="text-center mt-4">
@@ -118,6 +137,8 @@ function Invoice() {
                 </Alert>
             )}
 
+            {own && <Timeline timeline={timeline}/>}
+
             <PaymentInProcess
                 showModal={showModal}
                 setShowModal={setShowModal}
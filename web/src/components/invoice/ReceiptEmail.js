@@ -0,0 +1,49 @@
+import {Alert, Button, Form, InputGroup} from "react-bootstrap";
+import React, {useState} from "react";
+import axios from "axios";
+import {apiUrl} from "../../utils";
+
+function ReceiptEmail({invoice}) {
+    const [email, setEmail] = useState('');
+    const [saving, setSaving] = useState(false);
+    const [savedEmail, setSavedEmail] = useState(null);
+    const [error, setError] = useState(null);
+
+    const handleSave = async (e) => {
+        e.preventDefault();
+        setError(null);
+        setSaving(true);
+        try {
+            await axios.put(apiUrl(`/payment/invoice/${invoice.id}/buyer_email`), {email: email.trim()});
+            setSavedEmail(email.trim());
+        } catch (err) {
+            setError(err.response?.data?.message || 'Failed to save the email, please check it and try again.');
+        } finally {
+            setSaving(false);
+        }
+    };
+
+    return (
+        <Form className="mb-4" onSubmit={handleSave}>
+            <Form.Label htmlFor="receipt-email">Email for the receipt (optional)</Form.Label>
+            <InputGroup>
+                <Form.Control
+                    id="receipt-email"
+                    type="email"
+                    placeholder="you@example.com"
+                    value={email}
+                    onChange={(e) => setEmail(e.target.value)}
+                />
+                <Button type="submit" variant="outline-primary" disabled={saving || !email.trim()}>
+                    {saving ? 'Saving...' : 'Save'}
+                </Button>
+            </InputGroup>
+            {savedEmail && !error && (
+                <Form.Text className="text-success">The receipt will be sent to {savedEmail} once paid.</Form.Text>
+            )}
+            {error && <Alert variant="danger" className="mt-2">{error}</Alert>}
+        </Form>
+    );
+}
+
+export default ReceiptEmail;
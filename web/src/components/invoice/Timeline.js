@@ -0,0 +1,49 @@
+import {ListGroup} from "react-bootstrap";
+import React from "react";
+import LocalDate from "../common/LocalDate";
+
+const EVENT_LABELS = {
+    created: 'Created',
+    updated: 'Edited',
+    confirming: 'Payment submitted',
+    open: 'Reopened',
+    paid: 'Paid',
+    expired: 'Expired',
+    void: 'Voided',
+};
+
+const formatValue = (value) => {
+    if (value === null || value === undefined) return '—';
+    return typeof value === 'object' ? JSON.stringify(value) : String(value);
+};
+
+function Timeline({timeline}) {
+    if (!timeline || timeline.length === 0) {
+        return null;
+    }
+
+    return (
+        <>
+            <h5 className="mt-4">Timeline</h5>
+            <ListGroup variant="flush" className="mb-4">
+                {timeline.map((event) => (
+                    <ListGroup.Item key={event.id}>
+                        <strong>{EVENT_LABELS[event.kind] || event.kind}</strong>{' '}
+                        <small className="text-muted"><LocalDate date={event.created_at}/></small>
+                        {event.changes && (
+                            <ul className="mb-0 small">
+                                {Object.entries(event.changes).map(([field, {from, to}]) => (
+                                    <li key={field}>
+                                        {field}: {formatValue(from)} → {formatValue(to)}
+                                    </li>
+                                ))}
+                            </ul>
+                        )}
+                    </ListGroup.Item>
+                ))}
+            </ListGroup>
+        </>
+    );
+}
+
+export default Timeline;
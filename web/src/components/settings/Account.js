@@ -3,7 +3,12 @@ import { Container, Form, Button, Alert, Spinner } from 'react-bootstrap';
 import axios from 'axios';
 import ApiKeys from "./ApiKeys";
 import CallbackUrls from "./CallbackUrls";
+import ChatWebhooks from "./ChatWebhooks";
+import NotificationDigests from "./NotificationDigests";
+import NotificationLog from "./NotificationLog";
+import NotificationPreferences from "./NotificationPreferences";
 import Subscriptions from "./Subscriptions";
+import TelegramChats from "./TelegramChats";
 import Webhooks from "./Webhooks";
 import { apiUrl } from '../../utils';
 
@@ -56,6 +61,19 @@ function Account() {
         window.open(apiUrl(attachTelegramPath), '_blank');
     };
 
+    const handleUnlinkBot = async () => {
+        setError(null);
+
+        try {
+            await axios.delete(apiUrl('/user/telegram'), { withCredentials: true });
+            const response = await axios.get(apiUrl('/user'), { withCredentials: true });
+            setSettings(response.data);
+            setAttachTelegramPath(response.data.attach_telegram_path);
+        } catch (err) {
+            setError("Failed to unlink Telegram.");
+        }
+    };
+
     if (loading) return <div className="text-center"><Spinner animation="border" /></div>;
 
     return (
@@ -87,6 +105,17 @@ function Account() {
                         checked={settings.telegram_notification}
                         onChange={handleChange}
                     />
+                    <div className="d-flex mt-2">
+                        <Form.Check
+                            type="switch"
+                            id="buyer-receipts"
+                            label="Buyer receipts"
+                            name="buyer_receipts"
+                            checked={!!settings.buyer_receipts}
+                            onChange={handleChange}
+                        />
+                        <div className="ms-5 text-muted">Email buyers a receipt when they pay, unless an invoice says otherwise</div>
+                    </div>
                 </Form>
 
                 {error && <Alert variant="danger">{error}</Alert>}
@@ -98,14 +127,23 @@ function Account() {
                         <Button onClick={handleActivateBot} variant="outline-primary" size="sm">Activate Bot</Button>
                     </Alert>
                 )}
+                {settings.telegram_linked && <TelegramChats onUnlinkAll={handleUnlinkBot} />}
             </Container>
             <hr/>
+            {/* Notification Events Section */}
+            <NotificationPreferences/>
+            <NotificationDigests/>
+            <NotificationLog/>
+            <hr/>
             {/* Callback Urls Section */}
             <CallbackUrls/>
             <hr/>
             {/* Webhook Urls Section */}
             <Webhooks/>
             <hr/>
+            {/* Chat Webhooks Section */}
+            <ChatWebhooks/>
+            <hr/>
             {/* API Keys Section */}
             <ApiKeys/>
         </Container>
@@ -0,0 +1,168 @@
+import React, { useState, useEffect } from 'react';
+import { Container, Button, Alert, Spinner, Form, Row, Col } from 'react-bootstrap';
+import axios from 'axios';
+import { apiUrl } from '../../utils';
+
+const KINDS = [
+    { value: 'slack', label: 'Slack' },
+    { value: 'discord', label: 'Discord' },
+    { value: 'generic', label: 'Other' },
+];
+
+function ChatWebhooks() {
+    const [chatWebhooks, setChatWebhooks] = useState([]);
+    const [loading, setLoading] = useState(true);
+    const [error, setError] = useState(null);
+    const [notice, setNotice] = useState(null);
+    const [creating, setCreating] = useState(false);
+    const [kind, setKind] = useState('slack');
+    const [label, setLabel] = useState('');
+    const [url, setUrl] = useState('');
+
+    useEffect(() => {
+        const fetchChatWebhooks = async () => {
+            try {
+                const response = await axios.get(apiUrl('/user/chat_webhook'), { withCredentials: true });
+                setChatWebhooks(response.data);
+            } catch (err) {
+                setError('Failed to load chat webhooks.');
+            } finally {
+                setLoading(false);
+            }
+        };
+        fetchChatWebhooks();
+    }, []);
+
+    const handleCreate = async (event) => {
+        event.preventDefault();
+        setCreating(true);
+        setError(null);
+        setNotice(null);
+        try {
+            const response = await axios.post(
+                apiUrl('/user/chat_webhook'),
+                { kind, label, url },
+                { withCredentials: true }
+            );
+            setChatWebhooks([...chatWebhooks, response.data]);
+            setLabel('');
+            setUrl('');
+        } catch (err) {
+            setError(err.response?.data?.message || 'Failed to add chat webhook.');
+        } finally {
+            setCreating(false);
+        }
+    };
+
+    const handleTest = async (id) => {
+        setError(null);
+        setNotice(null);
+        try {
+            const response = await axios.post(apiUrl(`/user/chat_webhook/${id}/test`), {}, { withCredentials: true });
+            if (response.data.delivered) {
+                setNotice('Sample message posted.');
+            } else {
+                setError(response.data.error);
+            }
+        } catch (err) {
+            setError('Failed to post a sample message.');
+        }
+    };
+
+    const handleDelete = async (id) => {
+        setError(null);
+        setNotice(null);
+        try {
+            await axios.delete(apiUrl(`/user/chat_webhook/${id}`), { withCredentials: true });
+            setChatWebhooks(chatWebhooks.filter((chatWebhook) => chatWebhook.id !== id));
+        } catch (err) {
+            setError('Failed to delete chat webhook.');
+        }
+    };
+
+    if (loading) return <div><Spinner animation="border" /></div>;
+
+    return (
+        <Container>
+            <h3 className="text-dark">Slack & Discord</h3>
+            <p className="text-dark">
+                Post notifications to channels through their incoming webhook URLs.
+            </p>
+
+            {error && <Alert variant="danger">{error}</Alert>}
+            {notice && <Alert variant="success">{notice}</Alert>}
+
+            <Form onSubmit={handleCreate} className="mb-3">
+                <Row className="g-2">
+                    <Col xs={3}>
+                        <Form.Select value={kind} onChange={(e) => setKind(e.target.value)} disabled={creating}>
+                            {KINDS.map((option) => (
+                                <option key={option.value} value={option.value}>{option.label}</option>
+                            ))}
+                        </Form.Select>
+                    </Col>
+                    <Col xs={3}>
+                        <Form.Control
+                            placeholder="Label"
+                            value={label}
+                            onChange={(e) => setLabel(e.target.value)}
+                            required
+                            disabled={creating}
+                        />
+                    </Col>
+                    <Col xs={4}>
+                        <Form.Control
+                            type="url"
+                            placeholder="Incoming webhook URL"
+                            value={url}
+                            onChange={(e) => setUrl(e.target.value)}
+                            required
+                            disabled={creating}
+                        />
+                    </Col>
+                    <Col xs={2}>
+                        <Button type="submit" variant="outline-dark" disabled={creating}>
+                            {creating ? 'Adding...' : 'Add'}
+                        </Button>
+                    </Col>
+                </Row>
+            </Form>
+
+            {chatWebhooks.length === 0 ? (
+                <div>No chat webhooks yet.</div>
+            ) : (
+                <ul className="list-unstyled">
+                    {chatWebhooks.map((chatWebhook) => (
+                        <li
+                            key={chatWebhook.id}
+                            className="d-flex align-items-center mb-1"
+                            style={{ maxWidth: '600px' }}
+                        >
+                            <Button
+                                variant="danger"
+                                size="sm"
+                                className="me-2"
+                                onClick={() => handleDelete(chatWebhook.id)}
+                            >
+                                X
+                            </Button>
+                            <Button
+                                variant="outline-secondary"
+                                size="sm"
+                                className="me-2"
+                                onClick={() => handleTest(chatWebhook.id)}
+                            >
+                                Test
+                            </Button>
+                            <span className="text-truncate">
+                                {chatWebhook.label} <span className="text-muted">({chatWebhook.kind}, {chatWebhook.url})</span>
+                            </span>
+                        </li>
+                    ))}
+                </ul>
+            )}
+        </Container>
+    );
+}
+
+export default ChatWebhooks;
@@ -0,0 +1,80 @@
+import React, { useState, useEffect } from 'react';
+import { Container, Alert, Spinner, Form } from 'react-bootstrap';
+import axios from 'axios';
+import { apiUrl } from '../../utils';
+
+const CHANNEL_LABELS = {
+    email: 'Email',
+    telegram: 'Telegram',
+};
+
+const FREQUENCIES = [
+    { value: 'immediate', label: 'Every event' },
+    { value: 'hourly', label: 'Hourly summary' },
+    { value: 'daily', label: 'Daily summary' },
+    { value: 'weekly', label: 'Weekly summary' },
+];
+
+function NotificationDigests() {
+    const [digests, setDigests] = useState([]);
+    const [loading, setLoading] = useState(true);
+    const [error, setError] = useState(null);
+
+    useEffect(() => {
+        const fetchDigests = async () => {
+            try {
+                const response = await axios.get(apiUrl('/user/notification_digest'), { withCredentials: true });
+                setDigests(response.data);
+            } catch (err) {
+                setError('Failed to load notification digests.');
+            } finally {
+                setLoading(false);
+            }
+        };
+        fetchDigests();
+    }, []);
+
+    const handleChange = async (digest, frequency) => {
+        setError(null);
+        try {
+            const response = await axios.put(
+                apiUrl(`/user/notification_digest/${digest.channel}`),
+                { frequency },
+                { withCredentials: true }
+            );
+            setDigests(digests.map((d) => (d === digest ? response.data : d)));
+        } catch (err) {
+            setError('Failed to save the digest frequency.');
+        }
+    };
+
+    if (loading) return <div><Spinner animation="border" /></div>;
+
+    return (
+        <Container>
+            <h3 className="text-dark">Digests</h3>
+            <p className="text-dark">
+                Get one summary per hour, day or week instead of a message per event.
+                Slack and Discord always get every event.
+            </p>
+
+            {error && <Alert variant="danger">{error}</Alert>}
+
+            {digests.map((digest) => (
+                <Form.Group key={digest.channel} className="mb-2" style={{ maxWidth: '300px' }}>
+                    <Form.Label className="text-dark">{CHANNEL_LABELS[digest.channel] || digest.channel}</Form.Label>
+                    <Form.Select
+                        value={digest.frequency}
+                        onChange={(e) => handleChange(digest, e.target.value)}
+                    >
+                        {FREQUENCIES.map((frequency) => (
+                            <option key={frequency.value} value={frequency.value}>{frequency.label}</option>
+                        ))}
+                    </Form.Select>
+                </Form.Group>
+            ))}
+        </Container>
+    );
+}
+
+export default NotificationDigests;
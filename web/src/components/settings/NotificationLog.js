@@ -0,0 +1,100 @@
+import React, { useState, useEffect } from 'react';
+import { Container, Button, Alert, Spinner, Form, Table } from 'react-bootstrap';
+import axios from 'axios';
+import { apiUrl } from '../../utils';
+
+const STATUSES = [
+    { value: '', label: 'All' },
+    { value: 'failed', label: 'Failed' },
+    { value: 'sent', label: 'Sent' },
+    { value: 'pending', label: 'Resending' },
+];
+
+function NotificationLog() {
+    const [notifications, setNotifications] = useState([]);
+    const [status, setStatus] = useState('');
+    const [loading, setLoading] = useState(true);
+    const [error, setError] = useState(null);
+
+    useEffect(() => {
+        const fetchNotifications = async () => {
+            setLoading(true);
+            try {
+                const params = { limit: 20 };
+                if (status) params.status = status;
+                const response = await axios.get(apiUrl('/user/notifications'), { params, withCredentials: true });
+                setNotifications(response.data);
+            } catch (err) {
+                setError('Failed to load notifications.');
+            } finally {
+                setLoading(false);
+            }
+        };
+        fetchNotifications();
+    }, [status]);
+
+    const handleResend = async (notification) => {
+        setError(null);
+        try {
+            await axios.post(apiUrl(`/user/notifications/${notification.id}/resend`), {}, { withCredentials: true });
+            setNotifications(notifications.map((n) => (
+                n === notification ? { ...n, status: 'pending', resendable: false } : n
+            )));
+        } catch (err) {
+            setError(err.response?.data?.message || 'Failed to resend the notification.');
+        }
+    };
+
+    return (
+        <Container>
+            <h3 className="text-dark">Sent notifications</h3>
+            <p className="text-dark">
+                Emails, Telegram and chat messages sent to you, and why any failed.
+            </p>
+
+            {error && <Alert variant="danger">{error}</Alert>}
+
+            <Form.Select
+                className="mb-2"
+                style={{ maxWidth: '200px' }}
+                value={status}
+                onChange={(e) => setStatus(e.target.value)}
+            >
+                {STATUSES.map((s) => <option key={s.value} value={s.value}>{s.label}</option>)}
+            </Form.Select>
+
+            {loading ? <Spinner animation="border" /> : (
+                <Table size="sm">
+                    <thead>
+                        <tr>
+                            <th>Time</th>
+                            <th>Event</th>
+                            <th>Sent to</th>
+                            <th>Status</th>
+                            <th />
+                        </tr>
+                    </thead>
+                    <tbody>
+                        {notifications.map((notification) => (
+                            <tr key={notification.id}>
+                                <td>{new Date(notification.created_at + 'Z').toLocaleString()}</td>
+                                <td>{notification.event}</td>
+                                <td>{notification.channel}: {notification.destination}</td>
+                                <td title={notification.error || ''}>{notification.status}</td>
+                                <td>
+                                    {notification.resendable && (
+                                        <Button size="sm" variant="outline-dark" onClick={() => handleResend(notification)}>
+                                            Resend
+                                        </Button>
+                                    )}
+                                </td>
+                            </tr>
+                        ))}
+                    </tbody>
+                </Table>
+            )}
+        </Container>
+    );
+}
+
+export default NotificationLog;
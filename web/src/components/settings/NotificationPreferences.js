@@ -0,0 +1,111 @@
+import React, { useState, useEffect } from 'react';
+import { Container, Alert, Spinner, Form, Table } from 'react-bootstrap';
+import axios from 'axios';
+import { apiUrl } from '../../utils';
+
+const CHANNELS = [
+    { value: 'email', label: 'Email' },
+    { value: 'telegram', label: 'Telegram' },
+    { value: 'chat', label: 'Slack & Discord' },
+];
+
+const EVENTS = [
+    { value: 'invoice.paid', label: 'Invoice paid' },
+    { value: 'invoice.partially_paid', label: 'Partially paid' },
+    { value: 'invoice.expired', label: 'Invoice expired' },
+    { value: 'payment.anomaly', label: 'Payment anomaly' },
+    { value: 'subscription.expiring', label: 'Subscription expiring' },
+];
+
+function rules(preference) {
+    const parts = [];
+    if (preference.min_amount) parts.push(`≥ ${preference.min_amount}`);
+    if (preference.external_id_prefix) parts.push(`${preference.external_id_prefix}*`);
+    return parts.join(', ');
+}
+
+function NotificationPreferences() {
+    const [preferences, setPreferences] = useState([]);
+    const [loading, setLoading] = useState(true);
+    const [error, setError] = useState(null);
+
+    useEffect(() => {
+        const fetchPreferences = async () => {
+            try {
+                const response = await axios.get(apiUrl('/user/notification_preference'), { withCredentials: true });
+                setPreferences(response.data);
+            } catch (err) {
+                setError('Failed to load notification preferences.');
+            } finally {
+                setLoading(false);
+            }
+        };
+        fetchPreferences();
+    }, []);
+
+    const find = (channel, event) => preferences.find((p) => p.channel === channel && p.event === event);
+
+    const handleToggle = async (preference) => {
+        setError(null);
+        try {
+            const response = await axios.put(
+                apiUrl(`/user/notification_preference/${preference.channel}/${preference.event}`),
+                {
+                    enabled: !preference.enabled,
+                    min_amount: preference.min_amount,
+                    external_id_prefix: preference.external_id_prefix,
+                },
+                { withCredentials: true }
+            );
+            setPreferences(preferences.map((p) => (p === preference ? response.data : p)));
+        } catch (err) {
+            setError('Failed to save the notification preference.');
+        }
+    };
+
+    if (loading) return <div><Spinner animation="border" /></div>;
+
+    return (
+        <Container>
+            <h3 className="text-dark">Notification events</h3>
+            <p className="text-dark">
+                Choose which events each channel receives.
+            </p>
+
+            {error && <Alert variant="danger">{error}</Alert>}
+
+            <Table size="sm" style={{ maxWidth: '600px' }}>
+                <thead>
+                    <tr>
+                        <th>Event</th>
+                        {CHANNELS.map((channel) => <th key={channel.value}>{channel.label}</th>)}
+                    </tr>
+                </thead>
+                <tbody>
+                    {EVENTS.map((event) => (
+                        <tr key={event.value}>
+                            <td>{event.label}</td>
+                            {CHANNELS.map((channel) => {
+                                const preference = find(channel.value, event.value);
+                                if (!preference) return <td key={channel.value} />;
+                                return (
+                                    <td key={channel.value}>
+                                        <Form.Check
+                                            type="switch"
+                                            id={`${channel.value}-${event.value}`}
+                                            checked={preference.enabled}
+                                            onChange={() => handleToggle(preference)}
+                                            label={<span className="text-muted small">{rules(preference)}</span>}
+                                        />
+                                    </td>
+                                );
+                            })}
+                        </tr>
+                    ))}
+                </tbody>
+            </Table>
+        </Container>
+    );
+}
+
+export default NotificationPreferences;
@@ -0,0 +1,78 @@
+import React, { useState, useEffect } from 'react';
+import { Button, Alert, Spinner } from 'react-bootstrap';
+import axios from 'axios';
+import { apiUrl } from '../../utils';
+
+function TelegramChats({ onUnlinkAll }) {
+    const [chats, setChats] = useState([]);
+    const [loading, setLoading] = useState(true);
+    const [error, setError] = useState(null);
+
+    useEffect(() => {
+        const fetchChats = async () => {
+            try {
+                const response = await axios.get(apiUrl('/user/telegram'), { withCredentials: true });
+                setChats(response.data);
+            } catch (err) {
+                setError('Failed to load Telegram chats.');
+            } finally {
+                setLoading(false);
+            }
+        };
+        fetchChats();
+    }, []);
+
+    const handleAdd = (chat) => {
+        window.open(apiUrl(`/user/attach_telegram?chat=${chat}`), '_blank');
+    };
+
+    const handleDelete = async (id) => {
+        setError(null);
+        try {
+            await axios.delete(apiUrl(`/user/telegram/${id}`), { withCredentials: true });
+            const remaining = chats.filter((chat) => chat.id !== id);
+            setChats(remaining);
+            if (remaining.length === 0) onUnlinkAll();
+        } catch (err) {
+            setError('Failed to unlink the chat.');
+        }
+    };
+
+    if (loading) return <div><Spinner animation="border" /></div>;
+
+    return (
+        <div className="mx-3">
+            <span className="text-muted">Telegram notifications go to these chats.</span>
+
+            {error && <Alert variant="danger" className="mt-2">{error}</Alert>}
+
+            <ul className="list-unstyled my-2">
+                {chats.map((chat) => (
+                    <li
+                        key={chat.id}
+                        className="d-flex align-items-center mb-1"
+                        style={{ maxWidth: '500px' }}
+                    >
+                        <Button
+                            variant="danger"
+                            size="sm"
+                            className="me-2"
+                            onClick={() => handleDelete(chat.id)}
+                        >
+                            X
+                        </Button>
+                        <span className="text-truncate">
+                            {chat.label} <span className="text-muted">({chat.chat_type})</span>
+                        </span>
+                    </li>
+                ))}
+            </ul>
+
+            <Button onClick={() => handleAdd('group')} variant="outline-primary" size="sm">Add group</Button>
+            <Button onClick={() => handleAdd('channel')} variant="outline-primary" size="sm" className="ms-2">Add channel</Button>
+            <Button onClick={onUnlinkAll} variant="outline-danger" size="sm" className="ms-2">Unlink all</Button>
+        </div>
+    );
+}
+
+export default TelegramChats;